@@ -1,4 +1,7 @@
+#include <stdbool.h>
 #include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
 
 void print_int(int n)
 {
@@ -14,3 +17,53 @@ void print_str(char *s)
 {
   printf("%s", s);
 }
+
+void print_char(int c)
+{
+  putchar(c);
+}
+
+char *string_concat(char *a, char *b)
+{
+  size_t len_a = strlen(a);
+  char *result = malloc(len_a + strlen(b) + 1);
+  strcpy(result, a);
+  strcpy(result + len_a, b);
+  return result;
+}
+
+char *int_to_string(int n)
+{
+  char *result = malloc(12);
+  snprintf(result, 12, "%d", n);
+  return result;
+}
+
+char *char_to_string(int c)
+{
+  char *result = malloc(2);
+  result[0] = c;
+  result[1] = '\0';
+  return result;
+}
+
+char *bool_to_string(bool b)
+{
+  return b ? "true" : "false";
+}
+
+void assert(bool condition)
+{
+  if (!condition) {
+    fputs("assertion failed\n", stderr);
+    exit(1);
+  }
+}
+
+void str_slice_check(int start, int end, int len)
+{
+  if (!(0 <= start && start <= end && end <= len)) {
+    fprintf(stderr, "str_slice out of bounds: start %d, end %d, length %d\n", start, end, len);
+    exit(1);
+  }
+}
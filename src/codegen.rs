@@ -1,9 +1,18 @@
-use std::{collections::HashMap, ffi::CString, mem::MaybeUninit};
+pub mod c;
+pub mod wasm;
+
+use std::{
+    collections::HashMap,
+    ffi::{CStr, CString},
+    mem::MaybeUninit,
+};
 
 use llvm::{
     prelude::{LLVMBasicBlockRef, LLVMBuilderRef, LLVMValueRef},
+    target::LLVMTargetDataRef,
     target_machine::{
-        LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMRelocMode, LLVMTargetRef,
+        LLVMCodeGenFileType, LLVMCodeGenOptLevel, LLVMCodeModel, LLVMRelocMode,
+        LLVMTargetMachineRef, LLVMTargetRef,
     },
     LLVMCallConv, LLVMContext, LLVMIntPredicate, LLVMLinkage, LLVMModule, LLVMType, LLVMValue,
 };
@@ -12,8 +21,8 @@ use llvm_sys as llvm;
 use crate::{
     parser::{CompareOperation, MathOperation},
     typechecker::{
-        CheckedBlock, CheckedExpression, CheckedFieldAccess, CheckedLiteral, CheckedProgram,
-        CheckedStatement, Type,
+        CheckedBlock, CheckedExpression, CheckedLiteral, CheckedPattern, CheckedProgram,
+        CheckedStatement, Enum, StrBuiltin, Struct, Type,
     },
 };
 
@@ -63,11 +72,16 @@ impl ScopeStack {
 struct EmitContext {
     context: *mut LLVMContext,
     module: *mut LLVMModule,
+    /// Layout of the target, which unions are sized by
+    target_data: LLVMTargetDataRef,
     builder: LLVMBuilderRef,
     current_function: Option<*mut LLVMValue>,
     known_functions: HashMap<String, (*mut LLVMValue, *mut LLVMType)>,
+    /// Named LLVM types of both structs and enums
     known_structs: HashMap<String, *mut LLVMType>,
     scope_stack: ScopeStack,
+    /// Targets of `continue` and `break` for each loop being emitted
+    loop_stack: Vec<(LLVMBasicBlockRef, LLVMBasicBlockRef)>,
 }
 
 #[derive(Debug)]
@@ -81,6 +95,41 @@ pub fn generate_executable<P>(
     program: &CheckedProgram,
     print_llir: bool,
 ) -> eyre::Result<()> {
+    with_module(program, |target_machine, module| unsafe {
+        let mut error_message: MaybeUninit<*mut i8> = MaybeUninit::uninit();
+        if llvm::target_machine::LLVMTargetMachineEmitToFile(
+            target_machine,
+            module,
+            c_str!(mut b"./build/out.o"),
+            LLVMCodeGenFileType::LLVMObjectFile,
+            error_message.as_mut_ptr(),
+        ) != 0
+        {
+            llvm::core::LLVMDisposeMessage(error_message.assume_init());
+        }
+
+        if print_llir {
+            llvm::core::LLVMDumpModule(module);
+        }
+    })
+}
+
+/// Returns the textual LLVM IR of the program.
+pub fn generate_llvm_ir(program: &CheckedProgram) -> eyre::Result<String> {
+    with_module(program, |_target_machine, module| unsafe {
+        let ir = llvm::core::LLVMPrintModuleToString(module);
+        let text = CStr::from_ptr(ir).to_string_lossy().into_owned();
+        llvm::core::LLVMDisposeMessage(ir);
+        text
+    })
+}
+
+/// Emits the program into a module for the host target and passes it to `f`, disposing of it
+/// afterwards.
+fn with_module<T>(
+    program: &CheckedProgram,
+    f: impl FnOnce(LLVMTargetMachineRef, *mut LLVMModule) -> T,
+) -> eyre::Result<T> {
     unsafe {
         llvm::target::LLVM_InitializeAllTargetInfos();
         llvm::target::LLVM_InitializeAllTargets();
@@ -121,66 +170,159 @@ pub fn generate_executable<P>(
             &mut EmitContext {
                 context,
                 module,
+                target_data: target_data_layout,
                 builder,
                 current_function: None,
                 known_functions: HashMap::new(),
                 known_structs: HashMap::new(),
                 scope_stack: ScopeStack { scopes: vec![] },
+                loop_stack: vec![],
             },
             program,
         )?;
 
-        {
-            let mut error_message: MaybeUninit<*mut i8> = MaybeUninit::uninit();
-            if llvm::target_machine::LLVMTargetMachineEmitToFile(
-                target_machine,
-                module,
-                c_str!(mut b"./build/out.o"),
-                LLVMCodeGenFileType::LLVMObjectFile,
-                error_message.as_mut_ptr(),
-            ) != 0
-            {
-                llvm::core::LLVMDisposeMessage(error_message.assume_init());
-            }
-        }
+        let result = f(target_machine, module);
 
-        if print_llir {
-            llvm::core::LLVMDumpModule(module);
-        }
         llvm::core::LLVMDisposeBuilder(builder);
         llvm::core::LLVMDisposeModule(module);
         llvm::core::LLVMContextDispose(context);
+        Ok(result)
     }
+}
 
+/// A union is laid out as its most aligned field followed by enough bytes to fit its largest
+/// field, so that it has the size and alignment a C compiler would give it.
+unsafe fn set_union_body(
+    ctx: &mut EmitContext,
+    union: &Struct,
+    fields: &[*mut LLVMType],
+) -> eyre::Result<()> {
+    let mut body = vec![];
+    if let Some(&most_aligned) = fields
+        .iter()
+        .max_by_key(|&&field| llvm::target::LLVMABIAlignmentOfType(ctx.target_data, field))
+    {
+        let align = llvm::target::LLVMABIAlignmentOfType(ctx.target_data, most_aligned) as u64;
+        let size = fields
+            .iter()
+            .map(|&field| llvm::target::LLVMABISizeOfType(ctx.target_data, field))
+            .max()
+            .unwrap_or(0)
+            .next_multiple_of(align);
+        body.push(most_aligned);
+
+        let padding = size - llvm::target::LLVMABISizeOfType(ctx.target_data, most_aligned);
+        if padding > 0 {
+            body.push(llvm::core::LLVMArrayType(
+                llvm::core::LLVMInt8TypeInContext(ctx.context),
+                padding.try_into()?,
+            ));
+        }
+    }
+
+    llvm::core::LLVMStructSetBody(
+        ctx.known_structs[&union.name],
+        body.as_mut_ptr(),
+        body.len().try_into()?,
+        0,
+    );
     Ok(())
 }
 
 unsafe fn emit_program(ctx: &mut EmitContext, program: &CheckedProgram) -> eyre::Result<()> {
+    // Enum and struct types are all declared before any of them receives its body, so that
+    // structs and enums can contain each other regardless of their order
+    let type_names = program
+        .enums
+        .iter()
+        .map(|r#enum| &r#enum.name)
+        .chain(program.structs.iter().map(|struc| &struc.name));
+    for name in type_names {
+        let named_type =
+            llvm::core::LLVMStructCreateNamed(ctx.context, CString::new(name.as_str())?.as_ptr());
+        assert!(ctx.known_structs.insert(name.clone(), named_type).is_none());
+    }
+
     for struc in &program.structs {
+        // Unions are sized by their fields, which requires all of their types to have a body
+        if struc.is_union {
+            continue;
+        }
+        if struc.is_opaque {
+            // Opaque structs with a known size are a blob of bytes, so they can be allocated
+            if let Some(size) = struc.opaque_size {
+                let mut bytes = [llvm::core::LLVMArrayType(
+                    llvm::core::LLVMInt8TypeInContext(ctx.context),
+                    size.try_into()?,
+                )];
+                llvm::core::LLVMStructSetBody(
+                    ctx.known_structs[&struc.name],
+                    bytes.as_mut_ptr(),
+                    1,
+                    0,
+                );
+            }
+            continue;
+        }
+
         let mut fields: Vec<_> = struc
             .fields
             .iter()
             .map(|field| type_to_llvm(ctx, &field.1))
             .collect::<eyre::Result<_>>()?;
 
-        let struct_type = llvm::core::LLVMStructCreateNamed(
-            ctx.context,
-            CString::new(struc.name.as_str())?.as_ptr(),
+        llvm::core::LLVMStructSetBody(
+            ctx.known_structs[&struc.name],
+            fields.as_mut_ptr() as *mut _,
+            fields.len().try_into()?,
+            0,
         );
+    }
 
-        if !struc.is_opaque {
-            llvm::core::LLVMStructSetBody(
-                struct_type,
-                fields.as_mut_ptr() as *mut _,
-                fields.len().try_into()?,
-                0,
-            );
+    for r#enum in &program.enums {
+        let mut fields = vec![llvm::core::LLVMInt32TypeInContext(ctx.context)];
+        for (_, value_type) in &r#enum.variants {
+            if let Some(value_type) = value_type {
+                fields.push(type_to_llvm(ctx, value_type)?);
+            }
         }
 
-        assert!(ctx
-            .known_structs
-            .insert(struc.name.clone(), struct_type)
-            .is_none());
+        llvm::core::LLVMStructSetBody(
+            ctx.known_structs[&r#enum.name],
+            fields.as_mut_ptr(),
+            fields.len().try_into()?,
+            0,
+        );
+    }
+
+    // Unions that contain other unions can only receive their body after those did, so they're
+    // given their body once the types of all of their fields are sized
+    let mut unions: Vec<_> = program
+        .structs
+        .iter()
+        .filter(|struc| struc.is_union)
+        .collect();
+    while !unions.is_empty() {
+        let mut pending = vec![];
+        for union in unions.iter().copied() {
+            let fields: Vec<_> = union
+                .fields
+                .iter()
+                .map(|field| type_to_llvm(ctx, &field.1))
+                .collect::<eyre::Result<_>>()?;
+            if fields
+                .iter()
+                .all(|&field| llvm::core::LLVMTypeIsSized(field) != 0)
+            {
+                set_union_body(ctx, union, &fields)?;
+            } else {
+                pending.push(union);
+            }
+        }
+        if pending.len() == unions.len() {
+            break;
+        }
+        unions = pending;
     }
 
     for func in &program.extern_functions {
@@ -194,19 +336,30 @@ unsafe fn emit_program(ctx: &mut EmitContext, program: &CheckedProgram) -> eyre:
             return_type,
             params.as_mut_ptr(),
             params.len().try_into()?,
-            0,
+            func.is_variadic as i32,
         );
         let function = llvm::core::LLVMAddFunction(
             ctx.module,
             CString::new(func.name.as_str())?.as_ptr(),
             function_type,
         );
-        llvm::core::LLVMSetFunctionCallConv(function, LLVMCallConv::LLVMCCallConv as u32);
+        let calling_convention = match func.calling_convention.as_deref() {
+            Some("stdcall") => LLVMCallConv::LLVMX86StdcallCallConv,
+            Some("fastcall") => LLVMCallConv::LLVMX86FastcallCallConv,
+            Some("thiscall") => LLVMCallConv::LLVMX86ThisCallCallConv,
+            Some("vectorcall") => LLVMCallConv::LLVMX86VectorCallCallConv,
+            Some("win64") => LLVMCallConv::LLVMWin64CallConv,
+            Some("sysv64") => LLVMCallConv::LLVMX8664SysVCallConv,
+            _ => LLVMCallConv::LLVMCCallConv,
+        };
+        llvm::core::LLVMSetFunctionCallConv(function, calling_convention as u32);
 
         ctx.known_functions
             .insert(func.name.clone(), (function, function_type));
     }
 
+    // Declare every function before emitting any bodies, so calls can refer to
+    // functions and methods defined further down
     for func in &program.functions {
         let mut params: Vec<_> = func
             .parameters
@@ -229,6 +382,10 @@ unsafe fn emit_program(ctx: &mut EmitContext, program: &CheckedProgram) -> eyre:
 
         ctx.known_functions
             .insert(func.name.clone(), (function, function_type));
+    }
+
+    for func in &program.functions {
+        let (function, _) = ctx.known_functions[&func.name];
 
         ctx.scope_stack.push_scope();
         ctx.current_function = Some(function);
@@ -285,6 +442,7 @@ unsafe fn emit_statement(ctx: &mut EmitContext, statement: &CheckedStatement) ->
         CheckedStatement::Expression(expr) => {
             emit_expression(ctx, expr, ExprEmitAs::RValue)?;
         }
+        CheckedStatement::Const => {}
         CheckedStatement::LetAssign(variable_name, value_expr) => {
             let var_type = type_to_llvm(ctx, &value_expr.ttype())?;
             let var = llvm::core::LLVMBuildAlloca(ctx.builder, var_type, c_str!(b""));
@@ -344,11 +502,191 @@ unsafe fn emit_statement(ctx: &mut EmitContext, statement: &CheckedStatement) ->
             llvm::core::LLVMBuildCondBr(ctx.builder, condition, loop_block, after_loop_block);
 
             llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, loop_block);
+            ctx.loop_stack.push((condition_block, after_loop_block));
             emit_block(ctx, &while_loop.body, loop_block)?;
+            ctx.loop_stack.pop();
             llvm::core::LLVMBuildBr(ctx.builder, condition_block);
 
             llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, after_loop_block);
         }
+        CheckedStatement::Match(r#match) => {
+            let scrutinee_type = type_to_llvm(ctx, &r#match.scrutinee.ttype())?;
+            let scrutinee = emit_expression(ctx, &r#match.scrutinee, ExprEmitAs::RValue)?;
+            let scrutinee_storage =
+                llvm::core::LLVMBuildAlloca(ctx.builder, scrutinee_type, c_str!(b""));
+            llvm::core::LLVMBuildStore(ctx.builder, scrutinee, scrutinee_storage);
+
+            let after_match_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"after_match_block"),
+            );
+
+            // Arms are tested one after another, falling through to the next arm on mismatch
+            for arm in &r#match.arms {
+                let arm_block = llvm::core::LLVMAppendBasicBlockInContext(
+                    ctx.context,
+                    ctx.current_function.unwrap(),
+                    c_str!(b"match_arm_block"),
+                );
+                let next_arm_block = llvm::core::LLVMAppendBasicBlockInContext(
+                    ctx.context,
+                    ctx.current_function.unwrap(),
+                    c_str!(b"next_arm_block"),
+                );
+
+                let condition = match &arm.pattern {
+                    CheckedPattern::Wildcard => None,
+                    CheckedPattern::Literal(literal) => {
+                        let literal = emit_expression(ctx, literal, ExprEmitAs::RValue)?;
+                        Some(llvm::core::LLVMBuildICmp(
+                            ctx.builder,
+                            LLVMIntPredicate::LLVMIntEQ,
+                            scrutinee,
+                            literal,
+                            c_str!(b""),
+                        ))
+                    }
+                    CheckedPattern::EnumVariant(pattern) => {
+                        let tag_ptr = llvm::core::LLVMBuildStructGEP(
+                            ctx.builder,
+                            scrutinee_storage,
+                            0,
+                            c_str!(b""),
+                        );
+                        let tag = llvm::core::LLVMBuildLoad(ctx.builder, tag_ptr, c_str!(b""));
+                        let variant_tag = llvm::core::LLVMConstInt(
+                            llvm::core::LLVMInt32TypeInContext(ctx.context),
+                            pattern.variant_index as u64,
+                            0,
+                        );
+                        Some(llvm::core::LLVMBuildICmp(
+                            ctx.builder,
+                            LLVMIntPredicate::LLVMIntEQ,
+                            tag,
+                            variant_tag,
+                            c_str!(b""),
+                        ))
+                    }
+                };
+
+                match condition {
+                    Some(condition) => {
+                        llvm::core::LLVMBuildCondBr(
+                            ctx.builder,
+                            condition,
+                            arm_block,
+                            next_arm_block,
+                        );
+                    }
+                    None => {
+                        llvm::core::LLVMBuildBr(ctx.builder, arm_block);
+                    }
+                }
+
+                llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, arm_block);
+                ctx.scope_stack.push_scope();
+                if let CheckedPattern::EnumVariant(pattern) = &arm.pattern {
+                    if let Some(binding) = &pattern.binding {
+                        let field_index =
+                            enum_value_field_index(&pattern.r#enum, pattern.variant_index)
+                                .expect("only variants holding a value have a binding");
+                        let value_ptr = llvm::core::LLVMBuildStructGEP(
+                            ctx.builder,
+                            scrutinee_storage,
+                            field_index,
+                            c_str!(b""),
+                        );
+                        ctx.scope_stack.add_variable(binding.clone(), value_ptr);
+                    }
+                }
+                emit_expression(ctx, &arm.body, ExprEmitAs::RValue)?;
+                ctx.scope_stack.pop_scope();
+                llvm::core::LLVMBuildBr(ctx.builder, after_match_block);
+
+                llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, next_arm_block);
+            }
+
+            // The typechecker ensures that one of the arms always matches
+            llvm::core::LLVMBuildUnreachable(ctx.builder);
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, after_match_block);
+        }
+        CheckedStatement::Switch(switch) => {
+            let scrutinee_type = switch.scrutinee.ttype();
+            let scrutinee = emit_expression(ctx, &switch.scrutinee, ExprEmitAs::RValue)?;
+            let (value, value_type) = if let Type::UserDefined(_) = scrutinee_type {
+                // Enum values are switched on by their tag
+                let scrutinee_storage = llvm::core::LLVMBuildAlloca(
+                    ctx.builder,
+                    type_to_llvm(ctx, &scrutinee_type)?,
+                    c_str!(b""),
+                );
+                llvm::core::LLVMBuildStore(ctx.builder, scrutinee, scrutinee_storage);
+                let tag_ptr =
+                    llvm::core::LLVMBuildStructGEP(ctx.builder, scrutinee_storage, 0, c_str!(b""));
+                (
+                    llvm::core::LLVMBuildLoad(ctx.builder, tag_ptr, c_str!(b"")),
+                    llvm::core::LLVMInt32TypeInContext(ctx.context),
+                )
+            } else {
+                (scrutinee, type_to_llvm(ctx, &scrutinee_type)?)
+            };
+
+            let default_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"switch_default_block"),
+            );
+            let after_switch_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"after_switch_block"),
+            );
+
+            // A single `switch` instruction, which LLVM can turn into a jump table
+            let case_count: usize = switch.cases.iter().map(|(values, _)| values.len()).sum();
+            let switch_instruction =
+                llvm::core::LLVMBuildSwitch(ctx.builder, value, default_block, case_count as u32);
+            for (values, body) in &switch.cases {
+                let case_block = llvm::core::LLVMAppendBasicBlockInContext(
+                    ctx.context,
+                    ctx.current_function.unwrap(),
+                    c_str!(b"switch_case_block"),
+                );
+                for &case_value in values {
+                    let case_value = llvm::core::LLVMConstInt(value_type, case_value as u64, 1);
+                    llvm::core::LLVMAddCase(switch_instruction, case_value, case_block);
+                }
+                emit_block(ctx, body, case_block)?;
+                llvm::core::LLVMBuildBr(ctx.builder, after_switch_block);
+            }
+            emit_block(ctx, &switch.default, default_block)?;
+            llvm::core::LLVMBuildBr(ctx.builder, after_switch_block);
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, after_switch_block);
+        }
+        CheckedStatement::Loop(body) => {
+            let loop_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"loop_block"),
+            );
+            let after_loop_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"after_loop_block"),
+            );
+
+            llvm::core::LLVMBuildBr(ctx.builder, loop_block);
+
+            ctx.loop_stack.push((loop_block, after_loop_block));
+            emit_block(ctx, body, loop_block)?;
+            ctx.loop_stack.pop();
+            llvm::core::LLVMBuildBr(ctx.builder, loop_block);
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, after_loop_block);
+        }
         CheckedStatement::ForInLoop(for_in) => {
             ctx.scope_stack.push_scope();
 
@@ -375,16 +713,49 @@ unsafe fn emit_statement(ctx: &mut EmitContext, statement: &CheckedStatement) ->
                 index_var_storage,
             );
 
-            let iterable = emit_expression(ctx, &for_in.iterable, ExprEmitAs::LValue)?;
-            let iterable_elem_ptr = llvm::core::LLVMBuildBitCast(
-                ctx.builder,
-                iterable,
-                type_to_llvm(
-                    ctx,
-                    &Type::Pointer(Box::new(for_in.elem_var_type.clone()), false),
-                )?,
-                c_str!(b"iterable_ptr"),
-            );
+            // Ranges start at their first value, arrays and slices at a pointer to their first
+            // element
+            let (iterable_start, iterable_len) = match for_in.iterable.ttype() {
+                Type::Array(_, array_len) => {
+                    let iterable = emit_expression(ctx, &for_in.iterable, ExprEmitAs::LValue)?;
+                    let iterable_elem_ptr = llvm::core::LLVMBuildBitCast(
+                        ctx.builder,
+                        iterable,
+                        type_to_llvm(
+                            ctx,
+                            &Type::Pointer(Box::new(for_in.elem_var_type.clone()), false),
+                        )?,
+                        c_str!(b"iterable_ptr"),
+                    );
+                    let array_len = llvm::core::LLVMConstInt(
+                        type_to_llvm(ctx, &Type::Int)?,
+                        array_len.try_into()?,
+                        0,
+                    );
+                    (iterable_elem_ptr, array_len)
+                }
+                Type::Slice(_) => {
+                    let slice = emit_expression(ctx, &for_in.iterable, ExprEmitAs::RValue)?;
+                    (
+                        llvm::core::LLVMBuildExtractValue(
+                            ctx.builder,
+                            slice,
+                            0,
+                            c_str!(b"iterable_ptr"),
+                        ),
+                        llvm::core::LLVMBuildExtractValue(ctx.builder, slice, 1, c_str!(b"")),
+                    )
+                }
+                Type::Range(_) => {
+                    let range = emit_expression(ctx, &for_in.iterable, ExprEmitAs::RValue)?;
+                    let start =
+                        llvm::core::LLVMBuildExtractValue(ctx.builder, range, 0, c_str!(b"start"));
+                    let end = llvm::core::LLVMBuildExtractValue(ctx.builder, range, 1, c_str!(b""));
+                    let range_len = llvm::core::LLVMBuildSub(ctx.builder, end, start, c_str!(b""));
+                    (start, range_len)
+                }
+                _ => panic!("non-iterable in for-in loop iterable"),
+            };
 
             let condition_block = llvm::core::LLVMAppendBasicBlockInContext(
                 ctx.context,
@@ -396,6 +767,11 @@ unsafe fn emit_statement(ctx: &mut EmitContext, statement: &CheckedStatement) ->
                 ctx.current_function.unwrap(),
                 c_str!(b"loop_block"),
             );
+            let increment_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"increment_block"),
+            );
             let after_loop_block = llvm::core::LLVMAppendBasicBlockInContext(
                 ctx.context,
                 ctx.current_function.unwrap(),
@@ -408,34 +784,37 @@ unsafe fn emit_statement(ctx: &mut EmitContext, statement: &CheckedStatement) ->
             let current_idx =
                 llvm::core::LLVMBuildLoad(ctx.builder, index_var_storage, c_str!(b"current_idx"));
 
-            let array_len = if let Type::Array(_, array_len) = for_in.iterable.ttype() {
-                llvm::core::LLVMConstInt(type_to_llvm(ctx, &Type::Int)?, array_len.try_into()?, 0)
-            } else {
-                panic!("non-array in for-in loop iterable");
-            };
-
             let condition = llvm::core::LLVMBuildICmp(
                 ctx.builder,
                 LLVMIntPredicate::LLVMIntSLT,
                 current_idx,
-                array_len,
+                iterable_len,
                 c_str!(b""),
             );
             llvm::core::LLVMBuildCondBr(ctx.builder, condition, loop_block, after_loop_block);
 
             llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, loop_block);
             {
-                let elem_ptr = llvm::core::LLVMBuildGEP(
-                    ctx.builder,
-                    iterable_elem_ptr,
-                    [current_idx].as_mut_ptr(),
-                    1,
-                    c_str!(b""),
-                );
-                let elem = llvm::core::LLVMBuildLoad(ctx.builder, elem_ptr, c_str!(b""));
+                let elem = if let Type::Range(_) = for_in.iterable.ttype() {
+                    llvm::core::LLVMBuildAdd(ctx.builder, iterable_start, current_idx, c_str!(b""))
+                } else {
+                    let elem_ptr = llvm::core::LLVMBuildGEP(
+                        ctx.builder,
+                        iterable_start,
+                        [current_idx].as_mut_ptr(),
+                        1,
+                        c_str!(b""),
+                    );
+                    llvm::core::LLVMBuildLoad(ctx.builder, elem_ptr, c_str!(b""))
+                };
                 llvm::core::LLVMBuildStore(ctx.builder, elem, elem_var_storage);
             }
+            ctx.loop_stack.push((increment_block, after_loop_block));
             emit_block(ctx, &for_in.body, loop_block)?;
+            ctx.loop_stack.pop();
+            llvm::core::LLVMBuildBr(ctx.builder, increment_block);
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, increment_block);
             {
                 let const_1 = llvm::core::LLVMConstInt(type_to_llvm(ctx, &Type::Int)?, 1, 0);
                 let new_idx =
@@ -452,6 +831,25 @@ unsafe fn emit_statement(ctx: &mut EmitContext, statement: &CheckedStatement) ->
             let return_value = emit_expression(ctx, return_value, ExprEmitAs::RValue)?;
             llvm::core::LLVMBuildRet(ctx.builder, return_value);
 
+            let unreachable_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"unreachable_block"),
+            );
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, unreachable_block);
+        }
+        CheckedStatement::Break(loop_depth) | CheckedStatement::Continue(loop_depth) => {
+            let &(continue_block, break_block) =
+                ctx.loop_stack.iter().rev().nth(*loop_depth).expect(
+                    "break and continue only appear in loops as established by typechecker",
+                );
+            let target_block = if let CheckedStatement::Break(_) = statement {
+                break_block
+            } else {
+                continue_block
+            };
+            llvm::core::LLVMBuildBr(ctx.builder, target_block);
+
             let unreachable_block = llvm::core::LLVMAppendBasicBlockInContext(
                 ctx.context,
                 ctx.current_function.unwrap(),
@@ -475,11 +873,15 @@ unsafe fn emit_expression(
                 let int_type = type_to_llvm(ctx, ttype)?;
                 llvm::core::LLVMConstInt(int_type, *value as u64, 1)
             }
+            CheckedLiteral::Char(value, ttype) => {
+                llvm::core::LLVMConstInt(type_to_llvm(ctx, ttype)?, *value as u64, 0)
+            }
             CheckedLiteral::Bool(value, _type) => {
                 let value = if *value { 1 } else { 0 };
                 llvm::core::LLVMConstInt(llvm::core::LLVMInt1TypeInContext(ctx.context), value, 0)
             }
-            CheckedLiteral::String(value, _type) => {
+            CheckedLiteral::Null(ttype) => llvm::core::LLVMConstNull(type_to_llvm(ctx, ttype)?),
+            CheckedLiteral::String(value, ttype) => {
                 let bytes = value.as_bytes();
                 let i8 = llvm::core::LLVMInt8TypeInContext(ctx.context);
                 let str_type = llvm::core::LLVMArrayType(i8, (bytes.len() + 1).try_into()?);
@@ -503,7 +905,53 @@ unsafe fn emit_expression(
                     ),
                 );
 
-                str
+                match ttype {
+                    Type::Str => {
+                        let data =
+                            llvm::core::LLVMConstBitCast(str, llvm::core::LLVMPointerType(i8, 0));
+                        let len = llvm::core::LLVMConstInt(
+                            type_to_llvm(ctx, &Type::Int)?,
+                            bytes.len().try_into()?,
+                            0,
+                        );
+                        let mut fields = [data, len];
+                        llvm::core::LLVMConstStructInContext(
+                            ctx.context,
+                            fields.as_mut_ptr(),
+                            fields.len().try_into()?,
+                            0,
+                        )
+                    }
+                    _ => str,
+                }
+            }
+            CheckedLiteral::Struct(struct_literal, r#struct, struct_type) if r#struct.is_union => {
+                // The field is stored through a pointer to the union's memory
+                let (field_name, field_value) = struct_literal
+                    .fields
+                    .iter()
+                    .next()
+                    .expect("union literals initialize one field");
+                let (_, field_type) = r#struct
+                    .fields
+                    .iter()
+                    .find(|(declared_field_name, _)| declared_field_name == field_name)
+                    .expect("existence of field was established by typechecker");
+
+                let value = emit_expression(ctx, field_value, ExprEmitAs::RValue)?;
+                let storage = llvm::core::LLVMBuildAlloca(
+                    ctx.builder,
+                    type_to_llvm(ctx, struct_type)?,
+                    c_str!(b""),
+                );
+                let field_ptr = llvm::core::LLVMBuildBitCast(
+                    ctx.builder,
+                    storage,
+                    llvm::core::LLVMPointerType(type_to_llvm(ctx, field_type)?, 0),
+                    c_str!(b""),
+                );
+                llvm::core::LLVMBuildStore(ctx.builder, value, field_ptr);
+                llvm::core::LLVMBuildLoad(ctx.builder, storage, c_str!(b""))
             }
             CheckedLiteral::Struct(struct_literal, r#struct, struct_type) => {
                 let struct_type_ref = type_to_llvm(ctx, struct_type)?;
@@ -520,13 +968,45 @@ unsafe fn emit_expression(
                     })
                     .collect::<eyre::Result<_>>()?;
 
-                llvm::core::LLVMConstNamedStruct(
-                    struct_type_ref,
-                    field_values.as_mut_ptr(),
-                    field_values.len().try_into()?,
-                )
+                if field_values
+                    .iter()
+                    .all(|&field_value| llvm::core::LLVMIsConstant(field_value) != 0)
+                {
+                    llvm::core::LLVMConstNamedStruct(
+                        struct_type_ref,
+                        field_values.as_mut_ptr(),
+                        field_values.len().try_into()?,
+                    )
+                } else {
+                    // Fields only known at runtime are inserted one by one
+                    let mut struct_value = llvm::core::LLVMGetUndef(struct_type_ref);
+                    for (field_index, field_value) in field_values.into_iter().enumerate() {
+                        struct_value = llvm::core::LLVMBuildInsertValue(
+                            ctx.builder,
+                            struct_value,
+                            field_value,
+                            field_index.try_into()?,
+                            c_str!(b""),
+                        );
+                    }
+                    struct_value
+                }
+            }
+            CheckedLiteral::Tuple(elements, tuple_type) => {
+                let mut tuple_value = llvm::core::LLVMGetUndef(type_to_llvm(ctx, tuple_type)?);
+                for (element_index, element) in elements.iter().enumerate() {
+                    let element = emit_expression(ctx, element, ExprEmitAs::RValue)?;
+                    tuple_value = llvm::core::LLVMBuildInsertValue(
+                        ctx.builder,
+                        tuple_value,
+                        element,
+                        element_index.try_into()?,
+                        c_str!(b""),
+                    );
+                }
+                tuple_value
             }
-            CheckedLiteral::Array(array_literal, _array_type) => {
+            CheckedLiteral::Array(array_literal, array_type) => {
                 let element_type = array_literal
                     .element_type
                     .as_ref()
@@ -538,15 +1018,62 @@ unsafe fn emit_expression(
                     .map(|elem| emit_expression(ctx, elem, ExprEmitAs::RValue))
                     .collect::<eyre::Result<_>>()?;
 
-                llvm::core::LLVMConstArray(
-                    type_to_llvm(ctx, element_type)?,
-                    elements.as_mut_ptr(),
-                    elements.len().try_into()?,
-                )
+                if elements
+                    .iter()
+                    .all(|&element| llvm::core::LLVMIsConstant(element) != 0)
+                {
+                    llvm::core::LLVMConstArray(
+                        type_to_llvm(ctx, element_type)?,
+                        elements.as_mut_ptr(),
+                        elements.len().try_into()?,
+                    )
+                } else {
+                    // Elements only known at runtime are inserted one by one
+                    let mut array_value = llvm::core::LLVMGetUndef(type_to_llvm(ctx, array_type)?);
+                    for (element_index, element) in elements.into_iter().enumerate() {
+                        array_value = llvm::core::LLVMBuildInsertValue(
+                            ctx.builder,
+                            array_value,
+                            element,
+                            element_index.try_into()?,
+                            c_str!(b""),
+                        );
+                    }
+                    array_value
+                }
             }
         },
         CheckedExpression::FunctionCall(func_call) => {
             let &(callee, callee_type) = ctx.known_functions.get(&func_call.name).unwrap();
+            let mut args: Vec<LLVMValueRef> = func_call
+                .args
+                .iter()
+                .map(|expr| emit_expression(ctx, expr, ExprEmitAs::RValue))
+                .collect::<eyre::Result<_>>()?;
+            let call = llvm::core::LLVMBuildCall2(
+                ctx.builder,
+                callee_type,
+                callee,
+                args.as_mut_ptr(),
+                args.len().try_into()?,
+                c_str!(b"function_call"),
+            );
+            // Calls have to use the calling convention the callee was declared with
+            llvm::core::LLVMSetInstructionCallConv(
+                call,
+                llvm::core::LLVMGetFunctionCallConv(callee),
+            );
+            call
+        }
+        CheckedExpression::FunctionPointerCall(func_call) => {
+            let callee_type = match func_call.callee.ttype() {
+                Type::FnPtr {
+                    params,
+                    return_type,
+                } => function_type_to_llvm(ctx, &params, &return_type)?,
+                _ => panic!("call through non-function pointer"),
+            };
+            let callee = emit_expression(ctx, &func_call.callee, ExprEmitAs::RValue)?;
             let mut args: Vec<LLVMValueRef> = func_call
                 .args
                 .iter()
@@ -561,12 +1088,20 @@ unsafe fn emit_expression(
                 c_str!(b"function_call"),
             )
         }
+        CheckedExpression::Function(function_name, _type) => {
+            let &(function, _) = ctx
+                .known_functions
+                .get(function_name)
+                .expect("function should exist as determined by typechecker");
+            function
+        }
         CheckedExpression::CompareOp(lhs, rhs, op, _type) => {
             let lhs = emit_expression(ctx, lhs, ExprEmitAs::RValue)?;
             let rhs = emit_expression(ctx, rhs, ExprEmitAs::RValue)?;
             let predicate = match op {
                 CompareOperation::GreaterThan => LLVMIntPredicate::LLVMIntSGT,
                 CompareOperation::Equality => LLVMIntPredicate::LLVMIntEQ,
+                CompareOperation::NotEqual => LLVMIntPredicate::LLVMIntNE,
                 CompareOperation::GreaterThanEqual => LLVMIntPredicate::LLVMIntSGE,
                 CompareOperation::LessThan => LLVMIntPredicate::LLVMIntSLT,
                 CompareOperation::LessThanEqual => LLVMIntPredicate::LLVMIntSLE,
@@ -594,8 +1129,68 @@ unsafe fn emit_expression(
                 MathOperation::Division => {
                     llvm::core::LLVMBuildSDiv(ctx.builder, lhs, rhs, c_str!(b""))
                 }
+                MathOperation::Modulo => {
+                    llvm::core::LLVMBuildSRem(ctx.builder, lhs, rhs, c_str!(b""))
+                }
+                MathOperation::BitAnd => {
+                    llvm::core::LLVMBuildAnd(ctx.builder, lhs, rhs, c_str!(b""))
+                }
+                MathOperation::BitOr => llvm::core::LLVMBuildOr(ctx.builder, lhs, rhs, c_str!(b"")),
+                MathOperation::BitXor => {
+                    llvm::core::LLVMBuildXor(ctx.builder, lhs, rhs, c_str!(b""))
+                }
+                MathOperation::Shl => llvm::core::LLVMBuildShl(ctx.builder, lhs, rhs, c_str!(b"")),
+                MathOperation::Shr => llvm::core::LLVMBuildAShr(ctx.builder, lhs, rhs, c_str!(b"")),
             }
         }
+        CheckedExpression::LogicalAnd(lhs, rhs) | CheckedExpression::LogicalOr(lhs, rhs) => {
+            let is_and = matches!(expression, CheckedExpression::LogicalAnd(_, _));
+
+            let rhs_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"logical_rhs_block"),
+            );
+            let after_logical_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"after_logical_block"),
+            );
+
+            // The rhs is only evaluated if the lhs does not already determine the result
+            let lhs = emit_expression(ctx, lhs, ExprEmitAs::RValue)?;
+            let lhs_end_block = llvm::core::LLVMGetInsertBlock(ctx.builder);
+            if is_and {
+                llvm::core::LLVMBuildCondBr(ctx.builder, lhs, rhs_block, after_logical_block);
+            } else {
+                llvm::core::LLVMBuildCondBr(ctx.builder, lhs, after_logical_block, rhs_block);
+            }
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, rhs_block);
+            let rhs = emit_expression(ctx, rhs, ExprEmitAs::RValue)?;
+            let rhs_end_block = llvm::core::LLVMGetInsertBlock(ctx.builder);
+            llvm::core::LLVMBuildBr(ctx.builder, after_logical_block);
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, after_logical_block);
+            let i1 = llvm::core::LLVMInt1TypeInContext(ctx.context);
+            let phi = llvm::core::LLVMBuildPhi(ctx.builder, i1, c_str!(b"logical_op"));
+            let short_circuit_value = llvm::core::LLVMConstInt(i1, if is_and { 0 } else { 1 }, 0);
+            llvm::core::LLVMAddIncoming(
+                phi,
+                [short_circuit_value, rhs].as_mut_ptr(),
+                [lhs_end_block, rhs_end_block].as_mut_ptr(),
+                2,
+            );
+            phi
+        }
+        CheckedExpression::UnaryMinus(inner, _type) => {
+            let inner = emit_expression(ctx, inner, ExprEmitAs::RValue)?;
+            llvm::core::LLVMBuildNeg(ctx.builder, inner, c_str!(b""))
+        }
+        CheckedExpression::Not(inner) | CheckedExpression::BitNot(inner, _) => {
+            let inner = emit_expression(ctx, inner, ExprEmitAs::RValue)?;
+            llvm::core::LLVMBuildNot(ctx.builder, inner, c_str!(b""))
+        }
         CheckedExpression::Variable(variable_name, _type, _is_mut) => {
             let var_ref = ctx.scope_stack.get_variable(variable_name);
             match emit_as {
@@ -603,6 +1198,27 @@ unsafe fn emit_expression(
                 ExprEmitAs::LValue => return Ok(var_ref),
             }
         }
+        CheckedExpression::FieldAccess(field_access, r#struct, field_type) if r#struct.is_union => {
+            // Every field of a union starts at the beginning of its memory
+            let mut object = emit_expression(ctx, &field_access.object, ExprEmitAs::LValue)?;
+            if field_access.object_is_ptr {
+                object = llvm::core::LLVMBuildLoad(ctx.builder, object, c_str!(b""));
+            }
+
+            let field_ptr = llvm::core::LLVMBuildBitCast(
+                ctx.builder,
+                object,
+                llvm::core::LLVMPointerType(type_to_llvm(ctx, field_type)?, 0),
+                c_str!(b""),
+            );
+
+            match emit_as {
+                ExprEmitAs::RValue => {
+                    llvm::core::LLVMBuildLoad(ctx.builder, field_ptr, c_str!(b""))
+                }
+                ExprEmitAs::LValue => return Ok(field_ptr),
+            }
+        }
         CheckedExpression::FieldAccess(field_access, r#struct, _struct_type) => {
             // let struct_type_ref = type_to_llvm(ctx, struct_type);
             let field_index = r#struct
@@ -636,18 +1252,44 @@ unsafe fn emit_expression(
                 ExprEmitAs::LValue => return Ok(field_ptr),
             }
         }
+        CheckedExpression::TupleIndex(tuple_index) => match emit_as {
+            // Extracting from the value allows indexing into temporaries like `f().0`
+            ExprEmitAs::RValue => {
+                let object = emit_expression(ctx, &tuple_index.object, ExprEmitAs::RValue)?;
+                llvm::core::LLVMBuildExtractValue(
+                    ctx.builder,
+                    object,
+                    tuple_index.index.try_into()?,
+                    c_str!(b""),
+                )
+            }
+            ExprEmitAs::LValue => {
+                let object = emit_expression(ctx, &tuple_index.object, ExprEmitAs::LValue)?;
+                llvm::core::LLVMBuildStructGEP(
+                    ctx.builder,
+                    object,
+                    tuple_index.index.try_into()?,
+                    c_str!(b""),
+                )
+            }
+        },
         CheckedExpression::ArrayIndex(array_index) => {
             let index = emit_expression(ctx, &array_index.index, ExprEmitAs::RValue)?;
-            let array = emit_expression(ctx, &array_index.array, ExprEmitAs::LValue)?;
-            let array_ptr = llvm::core::LLVMBuildBitCast(
-                ctx.builder,
-                array,
-                type_to_llvm(
-                    ctx,
-                    &Type::Pointer(Box::new(array_index.ttype.clone()), false),
-                )?,
-                c_str!(b""),
-            );
+            let array_ptr = if let Type::Slice(_) = array_index.array.ttype() {
+                let slice = emit_expression(ctx, &array_index.array, ExprEmitAs::RValue)?;
+                llvm::core::LLVMBuildExtractValue(ctx.builder, slice, 0, c_str!(b""))
+            } else {
+                let array = emit_expression(ctx, &array_index.array, ExprEmitAs::LValue)?;
+                llvm::core::LLVMBuildBitCast(
+                    ctx.builder,
+                    array,
+                    type_to_llvm(
+                        ctx,
+                        &Type::Pointer(Box::new(array_index.ttype.clone()), false),
+                    )?,
+                    c_str!(b""),
+                )
+            };
             let element_ptr = llvm::core::LLVMBuildGEP(
                 ctx.builder,
                 array_ptr,
@@ -662,12 +1304,69 @@ unsafe fn emit_expression(
                 ExprEmitAs::LValue => return Ok(element_ptr),
             }
         }
+        // Nullable pointers are represented the same as pointers
+        CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+            return emit_expression(ctx, &pointer_to_nullable.pointer, emit_as);
+        }
+        CheckedExpression::ArrayToSlice(array_to_slice) => {
+            let array_len = if let Type::Array(_, array_len) = array_to_slice.array.ttype() {
+                array_len
+            } else {
+                panic!("non-array converted to slice");
+            };
+
+            // Slices of temporaries point into a copy of the array on the stack
+            let array = match *array_to_slice.array {
+                CheckedExpression::Variable(..)
+                | CheckedExpression::FieldAccess(..)
+                | CheckedExpression::ArrayIndex(_)
+                | CheckedExpression::Deref(_) => {
+                    emit_expression(ctx, &array_to_slice.array, ExprEmitAs::LValue)?
+                }
+                _ => {
+                    let value = emit_expression(ctx, &array_to_slice.array, ExprEmitAs::RValue)?;
+                    let storage = llvm::core::LLVMBuildAlloca(
+                        ctx.builder,
+                        type_to_llvm(ctx, &array_to_slice.array.ttype())?,
+                        c_str!(b""),
+                    );
+                    llvm::core::LLVMBuildStore(ctx.builder, value, storage);
+                    storage
+                }
+            };
+
+            let slice_type = type_to_llvm(ctx, &array_to_slice.ttype)?;
+            let element_ptr_type = llvm::core::LLVMStructGetTypeAtIndex(slice_type, 0);
+            let elements =
+                llvm::core::LLVMBuildBitCast(ctx.builder, array, element_ptr_type, c_str!(b""));
+            let len =
+                llvm::core::LLVMConstInt(type_to_llvm(ctx, &Type::Int)?, array_len.try_into()?, 0);
+
+            let slice = llvm::core::LLVMGetUndef(slice_type);
+            let slice =
+                llvm::core::LLVMBuildInsertValue(ctx.builder, slice, elements, 0, c_str!(b""));
+            llvm::core::LLVMBuildInsertValue(ctx.builder, slice, len, 1, c_str!(b""))
+        }
         CheckedExpression::Assignment(lhs, rhs) => {
             let destination = emit_expression(ctx, lhs, ExprEmitAs::LValue)?;
             let value = emit_expression(ctx, rhs, ExprEmitAs::RValue)?;
             llvm::core::LLVMBuildStore(ctx.builder, value, destination);
             value
         }
+        CheckedExpression::Range(range) => {
+            let start = emit_expression(ctx, &range.start, ExprEmitAs::RValue)?;
+            let mut end = emit_expression(ctx, &range.end, ExprEmitAs::RValue)?;
+            if range.inclusive {
+                let const_1 = llvm::core::LLVMConstInt(llvm::core::LLVMTypeOf(end), 1, 0);
+                end = llvm::core::LLVMBuildAdd(ctx.builder, end, const_1, c_str!(b""));
+            }
+
+            let range_type = type_to_llvm(ctx, &range.ttype)?;
+            let range_value = llvm::core::LLVMGetUndef(range_type);
+            let range_value =
+                llvm::core::LLVMBuildInsertValue(ctx.builder, range_value, start, 0, c_str!(b""));
+            llvm::core::LLVMBuildInsertValue(ctx.builder, range_value, end, 1, c_str!(b""))
+        }
         CheckedExpression::PointerTo(pointer_to) => {
             emit_expression(ctx, &pointer_to.inner, ExprEmitAs::LValue)?
         }
@@ -678,22 +1377,282 @@ unsafe fn emit_expression(
                 ExprEmitAs::RValue => llvm::core::LLVMBuildLoad(ctx.builder, pointer, c_str!(b"")),
             }
         }
+        CheckedExpression::Block(block) => {
+            ctx.scope_stack.push_scope();
+            for stmt in &block.body.statements {
+                emit_statement(ctx, stmt)?;
+            }
+            let value = match block.value {
+                Some(ref value) => emit_expression(ctx, value, emit_as)?,
+                None => llvm::core::LLVMGetUndef(type_to_llvm(ctx, &block.ttype)?),
+            };
+            ctx.scope_stack.pop_scope();
+            value
+        }
+        CheckedExpression::IfExpr(if_expr) => {
+            let if_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"if_block"),
+            );
+            let else_block = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"else_block"),
+            );
+            let after_if_else = llvm::core::LLVMAppendBasicBlockInContext(
+                ctx.context,
+                ctx.current_function.unwrap(),
+                c_str!(b"after_if_else"),
+            );
+
+            let condition = emit_expression(ctx, &if_expr.condition, ExprEmitAs::RValue)?;
+            llvm::core::LLVMBuildCondBr(ctx.builder, condition, if_block, else_block);
+
+            // Every branch provides a value to the phi node, which is undefined for branches that
+            // never reach their end
+            let if_type = type_to_llvm(ctx, &if_expr.ttype)?;
+            let mut incoming = vec![];
+            for (branch, bb) in [
+                (&if_expr.if_body, if_block),
+                (&if_expr.else_body, else_block),
+            ] {
+                llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, bb);
+                ctx.scope_stack.push_scope();
+                for stmt in &branch.body.statements {
+                    emit_statement(ctx, stmt)?;
+                }
+                let value = match branch.value {
+                    Some(ref value) if branch.ttype != Type::Never => {
+                        emit_expression(ctx, value, ExprEmitAs::RValue)?
+                    }
+                    Some(ref value) => {
+                        emit_expression(ctx, value, ExprEmitAs::RValue)?;
+                        llvm::core::LLVMGetUndef(if_type)
+                    }
+                    None => llvm::core::LLVMGetUndef(if_type),
+                };
+                incoming.push((value, llvm::core::LLVMGetInsertBlock(ctx.builder)));
+                ctx.scope_stack.pop_scope();
+                llvm::core::LLVMBuildBr(ctx.builder, after_if_else);
+            }
+
+            llvm::core::LLVMPositionBuilderAtEnd(ctx.builder, after_if_else);
+            if matches!(if_expr.ttype, Type::Unit | Type::Never) {
+                llvm::core::LLVMGetUndef(if_type)
+            } else {
+                let phi = llvm::core::LLVMBuildPhi(ctx.builder, if_type, c_str!(b"if_value"));
+                for (mut value, mut bb) in incoming {
+                    llvm::core::LLVMAddIncoming(phi, &mut value, &mut bb, 1);
+                }
+                phi
+            }
+        }
+        CheckedExpression::Cast(cast) => {
+            let inner_type = cast.inner.ttype();
+            let inner = emit_expression(ctx, &cast.inner, ExprEmitAs::RValue)?;
+            let target_type = type_to_llvm(ctx, &cast.ttype)?;
+            match (&inner_type, &cast.ttype) {
+                (inner_type, ttype) if inner_type.matches(ttype) => inner,
+                (Type::Pointer(..) | Type::NullablePointer(..), _) => {
+                    llvm::core::LLVMBuildPointerCast(ctx.builder, inner, target_type, c_str!(b""))
+                }
+                (_, Type::Pointer(..) | Type::NullablePointer(..)) => {
+                    llvm::core::LLVMBuildIntToPtr(ctx.builder, inner, target_type, c_str!(b""))
+                }
+                // Bools are zero extended, the integer types are all signed
+                (inner_type, _) => llvm::core::LLVMBuildIntCast2(
+                    ctx.builder,
+                    inner,
+                    target_type,
+                    (*inner_type != Type::Bool).into(),
+                    c_str!(b""),
+                ),
+            }
+        }
+        CheckedExpression::StrBuiltin(str_builtin) => {
+            let string = emit_expression(ctx, &str_builtin.args[0], ExprEmitAs::RValue)?;
+            match str_builtin.builtin {
+                StrBuiltin::Len => {
+                    llvm::core::LLVMBuildExtractValue(ctx.builder, string, 1, c_str!(b""))
+                }
+                StrBuiltin::Data => {
+                    llvm::core::LLVMBuildExtractValue(ctx.builder, string, 0, c_str!(b""))
+                }
+                StrBuiltin::Slice => {
+                    let data =
+                        llvm::core::LLVMBuildExtractValue(ctx.builder, string, 0, c_str!(b""));
+                    let start = emit_expression(ctx, &str_builtin.args[1], ExprEmitAs::RValue)?;
+                    let end = emit_expression(ctx, &str_builtin.args[2], ExprEmitAs::RValue)?;
+                    let data = llvm::core::LLVMBuildGEP(
+                        ctx.builder,
+                        data,
+                        [start].as_mut_ptr(),
+                        1,
+                        c_str!(b""),
+                    );
+                    let len = llvm::core::LLVMBuildSub(ctx.builder, end, start, c_str!(b""));
+
+                    let slice = llvm::core::LLVMGetUndef(type_to_llvm(ctx, &Type::Str)?);
+                    let slice =
+                        llvm::core::LLVMBuildInsertValue(ctx.builder, slice, data, 0, c_str!(b""));
+                    llvm::core::LLVMBuildInsertValue(ctx.builder, slice, len, 1, c_str!(b""))
+                }
+                StrBuiltin::FromCString => {
+                    let (strlen, strlen_type) = declare_strlen(ctx);
+                    let len = llvm::core::LLVMBuildCall2(
+                        ctx.builder,
+                        strlen_type,
+                        strlen,
+                        [string].as_mut_ptr(),
+                        1,
+                        c_str!(b""),
+                    );
+                    let len = llvm::core::LLVMBuildIntCast2(
+                        ctx.builder,
+                        len,
+                        type_to_llvm(ctx, &Type::Int)?,
+                        0,
+                        c_str!(b""),
+                    );
+
+                    let str_value = llvm::core::LLVMGetUndef(type_to_llvm(ctx, &Type::Str)?);
+                    let str_value = llvm::core::LLVMBuildInsertValue(
+                        ctx.builder,
+                        str_value,
+                        string,
+                        0,
+                        c_str!(b""),
+                    );
+                    llvm::core::LLVMBuildInsertValue(ctx.builder, str_value, len, 1, c_str!(b""))
+                }
+            }
+        }
+        // Folded to a constant using the target's data layout
+        CheckedExpression::SizeOf(ttype) => llvm::core::LLVMConstTruncOrBitCast(
+            llvm::core::LLVMSizeOf(type_to_llvm(ctx, ttype)?),
+            type_to_llvm(ctx, &Type::Int)?,
+        ),
+        CheckedExpression::OffsetOf(r#struct, field_name) => {
+            // All fields of a union start at its beginning
+            let offset = if r#struct.is_union {
+                0
+            } else {
+                let field_index = r#struct
+                    .fields
+                    .iter()
+                    .position(|(declared_field_name, _)| declared_field_name == field_name)
+                    .expect("existence of field in offsetof was established by typechecker");
+                llvm::target::LLVMOffsetOfElement(
+                    ctx.target_data,
+                    ctx.known_structs[&r#struct.name],
+                    field_index.try_into()?,
+                )
+            };
+            llvm::core::LLVMConstInt(type_to_llvm(ctx, &Type::Int)?, offset, 0)
+        }
+        CheckedExpression::EnumVariant(enum_variant) => {
+            let enum_type = type_to_llvm(ctx, &enum_variant.ttype)?;
+            let tag = llvm::core::LLVMConstInt(
+                llvm::core::LLVMInt32TypeInContext(ctx.context),
+                enum_variant.variant_index as u64,
+                0,
+            );
+
+            let mut enum_value = llvm::core::LLVMBuildInsertValue(
+                ctx.builder,
+                llvm::core::LLVMGetUndef(enum_type),
+                tag,
+                0,
+                c_str!(b""),
+            );
+            if let Some(value) = &enum_variant.value {
+                let value = emit_expression(ctx, value, ExprEmitAs::RValue)?;
+                let field_index =
+                    enum_value_field_index(&enum_variant.r#enum, enum_variant.variant_index)
+                        .expect("variant holds a value as established by typechecker");
+                enum_value = llvm::core::LLVMBuildInsertValue(
+                    ctx.builder,
+                    enum_value,
+                    value,
+                    field_index,
+                    c_str!(b""),
+                );
+            }
+
+            enum_value
+        }
     };
 
     Ok(value_ref)
 }
 
+/// Enums are represented as a struct of an `i32` tag, holding the index of the variant, followed
+/// by a field for every variant that holds a value. Returns the index of the field holding the
+/// value of the given variant.
+fn enum_value_field_index(r#enum: &Enum, variant_index: usize) -> Option<u32> {
+    r#enum.variants[variant_index].1.as_ref()?;
+
+    let preceding_values = r#enum.variants[..variant_index]
+        .iter()
+        .filter(|(_, value_type)| value_type.is_some())
+        .count();
+    Some(preceding_values as u32 + 1)
+}
+
+/// The C standard library's `strlen`, declared on first use unless the program declares it itself.
+unsafe fn declare_strlen(ctx: &mut EmitContext) -> (*mut LLVMValue, *mut LLVMType) {
+    if let Some(&strlen) = ctx.known_functions.get("strlen") {
+        return strlen;
+    }
+    let mut params = [llvm::core::LLVMPointerType(
+        llvm::core::LLVMInt8TypeInContext(ctx.context),
+        0,
+    )];
+    let function_type = llvm::core::LLVMFunctionType(
+        llvm::core::LLVMInt64TypeInContext(ctx.context),
+        params.as_mut_ptr(),
+        1,
+        0,
+    );
+    let function = llvm::core::LLVMAddFunction(ctx.module, c_str!(b"strlen"), function_type);
+    ctx.known_functions
+        .insert("strlen".to_string(), (function, function_type));
+    (function, function_type)
+}
+
+unsafe fn function_type_to_llvm(
+    ctx: &mut EmitContext,
+    params: &[Type],
+    return_type: &Type,
+) -> eyre::Result<*mut LLVMType> {
+    let mut params: Vec<_> = params
+        .iter()
+        .map(|param| type_to_llvm(ctx, param))
+        .collect::<eyre::Result<_>>()?;
+    let return_type = type_to_llvm(ctx, return_type)?;
+    Ok(llvm::core::LLVMFunctionType(
+        return_type,
+        params.as_mut_ptr(),
+        params.len().try_into()?,
+        0,
+    ))
+}
+
 unsafe fn type_to_llvm(ctx: &mut EmitContext, ttype: &Type) -> eyre::Result<*mut LLVMType> {
     Ok(match ttype {
-        Type::Pointer(subtype, _is_mut) => {
+        Type::Pointer(subtype, _is_mut) | Type::NullablePointer(subtype, _is_mut) => {
             let subtype = type_to_llvm(ctx, subtype)?;
             llvm::core::LLVMPointerType(subtype, 0)
         }
+        Type::Null => {
+            llvm::core::LLVMPointerType(llvm::core::LLVMInt8TypeInContext(ctx.context), 0)
+        }
         Type::CChar => llvm::core::LLVMInt8TypeInContext(ctx.context),
-        Type::GenericInt | Type::Int | Type::CInt => {
+        Type::GenericInt | Type::Int | Type::CInt | Type::Char => {
             llvm::core::LLVMInt32TypeInContext(ctx.context)
         }
-        Type::Unit => llvm::core::LLVMVoidTypeInContext(ctx.context),
+        Type::Unit | Type::Never => llvm::core::LLVMVoidTypeInContext(ctx.context),
         Type::UserDefined(name) => *ctx
             .known_structs
             .get(name)
@@ -704,6 +1663,62 @@ unsafe fn type_to_llvm(ctx: &mut EmitContext, ttype: &Type) -> eyre::Result<*mut
         Type::Array(element_type, size) => {
             llvm::core::LLVMArrayType(type_to_llvm(ctx, element_type)?, (*size).try_into()?)
         }
+        // Strings are a pointer to their first byte along with their length, like slices
+        Type::Str => {
+            let mut field_types = [
+                llvm::core::LLVMPointerType(llvm::core::LLVMInt8TypeInContext(ctx.context), 0),
+                type_to_llvm(ctx, &Type::Int)?,
+            ];
+            llvm::core::LLVMStructTypeInContext(
+                ctx.context,
+                field_types.as_mut_ptr(),
+                field_types.len().try_into()?,
+                0,
+            )
+        }
+        // Slices are a pointer to their first element along with their length
+        Type::Slice(element_type) => {
+            let mut field_types = [
+                llvm::core::LLVMPointerType(type_to_llvm(ctx, element_type)?, 0),
+                type_to_llvm(ctx, &Type::Int)?,
+            ];
+            llvm::core::LLVMStructTypeInContext(
+                ctx.context,
+                field_types.as_mut_ptr(),
+                field_types.len().try_into()?,
+                0,
+            )
+        }
+        // Ranges are their start along with their exclusive end
+        Type::Range(element_type) => {
+            let element_type = type_to_llvm(ctx, element_type)?;
+            let mut field_types = [element_type, element_type];
+            llvm::core::LLVMStructTypeInContext(
+                ctx.context,
+                field_types.as_mut_ptr(),
+                field_types.len().try_into()?,
+                0,
+            )
+        }
+        Type::FnPtr {
+            params,
+            return_type,
+        } => llvm::core::LLVMPointerType(function_type_to_llvm(ctx, params, return_type)?, 0),
+        Type::Tuple(element_types) => {
+            let mut element_types: Vec<_> = element_types
+                .iter()
+                .map(|element_type| type_to_llvm(ctx, element_type))
+                .collect::<eyre::Result<_>>()?;
+            llvm::core::LLVMStructTypeInContext(
+                ctx.context,
+                element_types.as_mut_ptr(),
+                element_types.len().try_into()?,
+                0,
+            )
+        }
         Type::Incomplete => panic!("attempted to use incomplete type in llvm codegen"),
+        Type::TypeParam(_) => {
+            unreachable!("only the instances of generic functions are compiled")
+        }
     })
 }
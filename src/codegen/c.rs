@@ -0,0 +1,1194 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Write},
+    path::Path,
+};
+
+use eyre::bail;
+
+use crate::{
+    parser::CompareOperation,
+    typechecker::{
+        CheckedBlock, CheckedExpression, CheckedFunction, CheckedIfExpr, CheckedLiteral,
+        CheckedPattern, CheckedProgram, CheckedStatement, Enum, StrBuiltin, Struct, Type,
+    },
+};
+
+/// Names that can't be used as identifiers in C, Clara names that collide with them get a trailing
+/// underscore.
+const C_RESERVED: &[&str] = &[
+    "auto", "bool", "break", "case", "char", "const", "continue", "default", "do", "double",
+    "else", "enum", "extern", "false", "float", "for", "goto", "if", "inline", "int", "long",
+    "register", "restrict", "return", "short", "signed", "sizeof", "static", "struct", "switch",
+    "true", "typedef", "union", "unsigned", "void", "volatile", "while", "NULL",
+];
+
+pub fn generate_c_file<P: AsRef<Path>>(
+    c_filepath: P,
+    program: &CheckedProgram,
+) -> eyre::Result<()> {
+    let mut source = String::new();
+    let mut codegen = CCodegen::default();
+    codegen.emit_program(&mut source, program)?;
+    if let Some(what) = codegen.unsupported {
+        bail!("{what} are not supported by the C backend");
+    }
+    std::fs::write(c_filepath, source)?;
+    Ok(())
+}
+
+/// Translates a typechecked program to a single C source file. Structs and enums become C structs,
+/// tuples, arrays, strings, slices and ranges become generated structs named `clara_*`, so that all of them
+/// can be passed around by value like in the LLVM backend. Every function is prototyped before any
+/// body, and Clara's `main` is called from a C `main` returning 0.
+#[derive(Debug, Default)]
+pub struct CCodegen {
+    /// Forward declarations of every struct and typedefs of function pointers
+    declarations: String,
+    /// Struct bodies, each following the bodies of the types it holds by value
+    definitions: String,
+    prototypes: String,
+    functions: String,
+    structs: HashMap<String, Struct>,
+    enums: HashMap<String, Enum>,
+    /// C names of the generated types by a description of their layout, types that are laid out
+    /// the same, like `(int,)` and `(c_int,)`, share a name
+    generated_types: HashMap<String, String>,
+    /// Generated struct types in the order they were named, to define each of them eventually
+    generated_structs: Vec<(String, Type)>,
+    /// Struct types whose body has been written or is being written
+    defined: HashSet<String>,
+    indent: usize,
+    /// The loops around the statement being written, with the innermost loop last
+    loops: Vec<LoopLabels>,
+    loop_count: usize,
+    /// The number of temporaries operands were stored in, to name each of them uniquely
+    temporary_count: usize,
+    /// The first of the values that can't be translated, no file is written if there is one
+    unsupported: Option<&'static str>,
+}
+
+/// C's `break` and `continue` only leave the innermost loop, those of outer loops jump to labels
+/// at the end of the loop's body and after the loop, which are only written when they're used.
+#[derive(Debug)]
+struct LoopLabels {
+    id: usize,
+    break_used: bool,
+    continue_used: bool,
+    /// Whether the statement being written is inside a `switch` in the loop's body, where C's
+    /// `break` would leave the `switch` instead of the loop
+    in_switch: bool,
+}
+
+impl CCodegen {
+    pub fn emit_program(&mut self, out: &mut impl Write, program: &CheckedProgram) -> fmt::Result {
+        for r#struct in &program.structs {
+            self.structs.insert(r#struct.name.clone(), r#struct.clone());
+            let name = c_ident(&r#struct.name);
+            let keyword = if r#struct.is_union { "union" } else { "struct" };
+            writeln!(self.declarations, "typedef {keyword} {name} {name};")?;
+        }
+        for r#enum in &program.enums {
+            self.enums.insert(r#enum.name.clone(), r#enum.clone());
+            let name = c_ident(&r#enum.name);
+            writeln!(self.declarations, "typedef struct {name} {name};")?;
+        }
+
+        for func in &program.extern_functions {
+            let mut params: Vec<_> = func
+                .parameters
+                .iter()
+                .map(|param| self.declaration(&param.ttype, &c_ident(&param.name)))
+                .collect();
+            if func.is_variadic {
+                params.push("...".to_string());
+            }
+            let signature = self.signature(&func_name(&func.name), &func.return_type, params);
+            let attribute = match func.calling_convention.as_deref() {
+                Some("stdcall") => " __attribute__((stdcall))",
+                Some("fastcall") => " __attribute__((fastcall))",
+                Some("thiscall") => " __attribute__((thiscall))",
+                Some("vectorcall") => " __attribute__((vectorcall))",
+                Some("win64") => " __attribute__((ms_abi))",
+                Some("sysv64") => " __attribute__((sysv_abi))",
+                _ => "",
+            };
+            writeln!(self.prototypes, "extern {signature}{attribute};")?;
+        }
+
+        for func in &program.functions {
+            self.emit_function(func)?;
+        }
+
+        if program.functions.iter().any(|func| func.name == "main") {
+            writeln!(
+                self.functions,
+                "int main(void) {{\n    {}();\n    return 0;\n}}",
+                func_name("main")
+            )?;
+        }
+
+        for r#struct in &program.structs {
+            self.define(&Type::UserDefined(r#struct.name.clone()))?;
+        }
+        for r#enum in &program.enums {
+            self.define(&Type::UserDefined(r#enum.name.clone()))?;
+        }
+        // Defining a type can name further generated types, which are defined in turn
+        let mut generated_idx = 0;
+        while let Some((_, ttype)) = self.generated_structs.get(generated_idx).cloned() {
+            self.define(&ttype)?;
+            generated_idx += 1;
+        }
+
+        write!(out, "#include <stdbool.h>\n#include <stddef.h>\n")?;
+        for section in [
+            &self.declarations,
+            &self.definitions,
+            &self.prototypes,
+            &self.functions,
+        ] {
+            if !section.is_empty() {
+                write!(out, "\n{}\n", section.trim_end())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_function(&mut self, func: &CheckedFunction) -> fmt::Result {
+        let params = func
+            .parameters
+            .iter()
+            .map(|param| self.declaration(&param.ttype, &c_ident(&param.name)))
+            .collect();
+        let signature = self.signature(&func_name(&func.name), &func.return_type, params);
+        writeln!(self.prototypes, "{signature};")?;
+
+        write!(self.functions, "{signature} ")?;
+        self.emit_block(&func.body)?;
+        writeln!(self.functions, "\n")
+    }
+
+    fn signature(&mut self, name: &str, return_type: &Type, params: Vec<String>) -> String {
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params.join(", ")
+        };
+        format!("{} {name}({params})", self.type_name(return_type))
+    }
+
+    fn emit_indent(&mut self) -> fmt::Result {
+        write!(self.functions, "{:width$}", "", width = self.indent * 4)
+    }
+
+    /// Writes the block without a trailing newline, starting at the current position.
+    fn emit_block(&mut self, block: &CheckedBlock) -> fmt::Result {
+        writeln!(self.functions, "{{")?;
+        self.indent += 1;
+        for statement in &block.statements {
+            self.emit_statement(statement)?;
+        }
+        self.indent -= 1;
+        self.emit_indent()?;
+        write!(self.functions, "}}")
+    }
+
+    /// Writes the statements of a case in a block of their own, leaving the `switch` at its end.
+    fn emit_switch_case_body(&mut self, body: &CheckedBlock) -> fmt::Result {
+        writeln!(self.functions, "{{")?;
+        self.indent += 1;
+        for statement in &body.statements {
+            self.emit_statement(statement)?;
+        }
+        if !body.diverges() {
+            self.emit_line("break;")?;
+        }
+        self.indent -= 1;
+        self.emit_line("}")
+    }
+
+    /// Writes the body of a loop, starting with the given line, followed by the labels the loop is
+    /// left through by nested loops.
+    fn emit_loop_body(&mut self, first_line: Option<&str>, body: &CheckedBlock) -> fmt::Result {
+        self.loops.push(LoopLabels {
+            id: self.loop_count,
+            break_used: false,
+            continue_used: false,
+            in_switch: false,
+        });
+        self.loop_count += 1;
+
+        writeln!(self.functions, "{{")?;
+        self.indent += 1;
+        if let Some(first_line) = first_line {
+            self.emit_line(first_line)?;
+        }
+        for statement in &body.statements {
+            self.emit_statement(statement)?;
+        }
+        let labels = self.loops.pop().unwrap();
+        if labels.continue_used {
+            self.emit_line(&format!("clara_continue_{}:;", labels.id))?;
+        }
+        self.indent -= 1;
+        self.emit_line("}")?;
+        if labels.break_used {
+            self.emit_line(&format!("clara_break_{}:;", labels.id))?;
+        }
+        Ok(())
+    }
+
+    /// Writes the statements of an if expression, which end with the value of the expression.
+    fn emit_if_expression(&mut self, if_expr: &CheckedIfExpr) -> fmt::Result {
+        let has_value = !matches!(if_expr.ttype, Type::Unit | Type::Never);
+        if has_value {
+            let if_type = self.type_name(&if_expr.ttype);
+            self.emit_line(&format!("{if_type} clara_if_value;"))?;
+        }
+
+        let condition = self.expression(&if_expr.condition);
+        self.emit_line(&format!("if ({condition}) {{"))?;
+        for (branch, end) in [(&if_expr.if_body, "} else {"), (&if_expr.else_body, "}")] {
+            self.indent += 1;
+            for statement in &branch.body.statements {
+                self.emit_statement(statement)?;
+            }
+            if let Some(ref value) = branch.value {
+                let value = self.expression(value);
+                if has_value && branch.ttype != Type::Never {
+                    self.emit_line(&format!("clara_if_value = {value};"))?;
+                } else {
+                    self.emit_line(&format!("{value};"))?;
+                }
+            }
+            self.indent -= 1;
+            self.emit_line(end)?;
+        }
+
+        if has_value {
+            self.emit_line("clara_if_value;")?;
+        }
+        Ok(())
+    }
+
+    fn emit_line(&mut self, line: &str) -> fmt::Result {
+        self.emit_indent()?;
+        writeln!(self.functions, "{line}")
+    }
+
+    fn emit_statement(&mut self, statement: &CheckedStatement) -> fmt::Result {
+        match statement {
+            CheckedStatement::Expression(expression) => {
+                let expression = self.expression(expression);
+                self.emit_line(&format!("{expression};"))?;
+            }
+            CheckedStatement::Const => {}
+            CheckedStatement::LetAssign(variable_name, value) => {
+                let value_type = value.ttype();
+                let value = self.expression(value);
+                if let Type::Unit | Type::Never = value_type {
+                    self.emit_line(&format!("{value};"))?;
+                } else {
+                    let declaration = self.declaration(&value_type, &c_ident(variable_name));
+                    self.emit_line(&format!("{declaration} = {value};"))?;
+                }
+            }
+            CheckedStatement::IfElse(if_else) => {
+                self.emit_indent()?;
+                let mut if_else = if_else;
+                loop {
+                    let condition = self.expression(&if_else.condition);
+                    write!(self.functions, "if ({condition}) ")?;
+                    self.emit_block(&if_else.if_body)?;
+
+                    match if_else.else_body.statements.as_slice() {
+                        [] => break,
+                        // Write `else if` instead of nesting the `if` in the else block
+                        [CheckedStatement::IfElse(else_if)] => {
+                            write!(self.functions, " else ")?;
+                            if_else = else_if;
+                        }
+                        _ => {
+                            write!(self.functions, " else ")?;
+                            self.emit_block(&if_else.else_body)?;
+                            break;
+                        }
+                    }
+                }
+                writeln!(self.functions)?;
+            }
+            CheckedStatement::WhileLoop(while_loop) => {
+                let condition = self.expression(&while_loop.condition);
+                self.emit_indent()?;
+                write!(self.functions, "while ({condition}) ")?;
+                self.emit_loop_body(None, &while_loop.body)?;
+            }
+            CheckedStatement::Loop(body) => {
+                self.emit_indent()?;
+                write!(self.functions, "for (;;) ")?;
+                self.emit_loop_body(None, body)?;
+            }
+            CheckedStatement::Match(r#match) => {
+                // The scrutinee is evaluated once, the arms are tested one after another
+                self.emit_line("{")?;
+                self.indent += 1;
+                let scrutinee_type = r#match.scrutinee.ttype();
+                let declaration = self.declaration(&scrutinee_type, "clara_scrutinee");
+                let scrutinee = self.expression(&r#match.scrutinee);
+                self.emit_line(&format!("{declaration} = {scrutinee};"))?;
+
+                self.emit_indent()?;
+                for (arm_idx, arm) in r#match.arms.iter().enumerate() {
+                    if arm_idx > 0 {
+                        write!(self.functions, " else ")?;
+                    }
+                    let condition = match &arm.pattern {
+                        CheckedPattern::Wildcard => None,
+                        CheckedPattern::Literal(literal) => {
+                            Some(format!("clara_scrutinee == {}", self.operand(literal)))
+                        }
+                        CheckedPattern::EnumVariant(pattern) => {
+                            Some(format!("clara_scrutinee.tag == {}", pattern.variant_index))
+                        }
+                    };
+                    if let Some(ref condition) = condition {
+                        write!(self.functions, "if ({condition}) ")?;
+                    }
+
+                    writeln!(self.functions, "{{")?;
+                    self.indent += 1;
+                    if let CheckedPattern::EnumVariant(pattern) = &arm.pattern {
+                        if let Some(binding) = &pattern.binding {
+                            let (variant_name, value_type) =
+                                &pattern.r#enum.variants[pattern.variant_index];
+                            let value_type = value_type
+                                .as_ref()
+                                .expect("only variants holding a value have a binding");
+                            let declaration = self.declaration(value_type, &c_ident(binding));
+                            self.emit_line(&format!(
+                                "{declaration} = clara_scrutinee.value_{variant_name};"
+                            ))?;
+                        }
+                    }
+                    let body = self.expression(&arm.body);
+                    self.emit_line(&format!("{body};"))?;
+                    self.indent -= 1;
+                    self.emit_indent()?;
+                    write!(self.functions, "}}")?;
+
+                    // Arms after a wildcard are never reached
+                    if condition.is_none() {
+                        break;
+                    }
+                }
+                writeln!(self.functions)?;
+                self.indent -= 1;
+                self.emit_line("}")?;
+            }
+            CheckedStatement::Switch(switch) => {
+                let scrutinee = if let Type::UserDefined(_) = switch.scrutinee.ttype() {
+                    format!("{}.tag", self.operand(&switch.scrutinee))
+                } else {
+                    self.expression(&switch.scrutinee)
+                };
+                self.emit_line(&format!("switch ({scrutinee}) {{"))?;
+
+                let outer_in_switch = self
+                    .loops
+                    .last_mut()
+                    .map(|labels| std::mem::replace(&mut labels.in_switch, true));
+                for (values, body) in &switch.cases {
+                    self.emit_indent()?;
+                    for value in values {
+                        write!(self.functions, "case {value}: ")?;
+                    }
+                    self.emit_switch_case_body(body)?;
+                }
+                self.emit_indent()?;
+                write!(self.functions, "default: ")?;
+                self.emit_switch_case_body(&switch.default)?;
+                if let (Some(labels), Some(in_switch)) = (self.loops.last_mut(), outer_in_switch) {
+                    labels.in_switch = in_switch;
+                }
+
+                self.emit_line("}")?;
+            }
+            CheckedStatement::ForInLoop(for_in) => {
+                self.emit_line("{")?;
+                self.indent += 1;
+
+                // Arrays are iterated as slices, ranges count up from their start
+                let iterable_type = for_in.iterable.ttype();
+                let (iterable, iterable_type) = match iterable_type {
+                    Type::Array(element_type, array_len) => {
+                        let slice_type = Type::Slice(element_type);
+                        let slice = format!(
+                            "({}){{ {}.elements, {array_len} }}",
+                            self.type_name(&slice_type),
+                            self.array_storage(&for_in.iterable)
+                        );
+                        (slice, slice_type)
+                    }
+                    Type::Slice(_) | Type::Range(_) => {
+                        (self.expression(&for_in.iterable), iterable_type)
+                    }
+                    _ => panic!("non-iterable in for-in loop iterable"),
+                };
+                let declaration = self.declaration(&iterable_type, "clara_iterable");
+                self.emit_line(&format!("{declaration} = {iterable};"))?;
+
+                let index = for_in
+                    .index_var
+                    .as_deref()
+                    .map_or_else(|| "clara_index".to_string(), c_ident);
+                let (iterable_len, element) = if let Type::Range(_) = iterable_type {
+                    (
+                        "clara_iterable.end - clara_iterable.start",
+                        format!("clara_iterable.start + {index}"),
+                    )
+                } else {
+                    (
+                        "clara_iterable.len",
+                        format!("clara_iterable.elements[{index}]"),
+                    )
+                };
+                self.emit_indent()?;
+                writeln!(
+                    self.functions,
+                    "for (int {index} = 0; {index} < {iterable_len}; {index}++) "
+                )?;
+                let declaration =
+                    self.declaration(&for_in.elem_var_type, &c_ident(&for_in.elem_var_name));
+                self.emit_loop_body(Some(&format!("{declaration} = {element};")), &for_in.body)?;
+
+                self.indent -= 1;
+                self.emit_line("}")?;
+            }
+            CheckedStatement::Return(return_value) => {
+                if let Type::Unit | Type::Never = return_value.ttype() {
+                    let return_value = self.expression(return_value);
+                    self.emit_line(&format!("{return_value};"))?;
+                    self.emit_line("return;")?;
+                } else {
+                    let return_value = self.expression(return_value);
+                    self.emit_line(&format!("return {return_value};"))?;
+                }
+            }
+            CheckedStatement::Break(0)
+                if !self.loops.last().is_some_and(|labels| labels.in_switch) =>
+            {
+                self.emit_line("break;")?
+            }
+            CheckedStatement::Continue(0) => self.emit_line("continue;")?,
+            CheckedStatement::Break(loop_depth) => {
+                let labels = self.loops.iter_mut().rev().nth(*loop_depth).unwrap();
+                labels.break_used = true;
+                let id = labels.id;
+                self.emit_line(&format!("goto clara_break_{id};"))?;
+            }
+            CheckedStatement::Continue(loop_depth) => {
+                let labels = self.loops.iter_mut().rev().nth(*loop_depth).unwrap();
+                labels.continue_used = true;
+                let id = labels.id;
+                self.emit_line(&format!("goto clara_continue_{id};"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The expression as an operand of another expression, in parentheses unless it binds as
+    /// tightly as a C postfix expression.
+    fn operand(&mut self, expression: &CheckedExpression) -> String {
+        let needs_parens = match expression {
+            CheckedExpression::Literal(CheckedLiteral::Int(value, _)) => *value < 0,
+            CheckedExpression::CompareOp(..)
+            | CheckedExpression::MathOp(..)
+            | CheckedExpression::LogicalAnd(..)
+            | CheckedExpression::LogicalOr(..)
+            | CheckedExpression::UnaryMinus(..)
+            | CheckedExpression::Not(_)
+            | CheckedExpression::BitNot(..)
+            | CheckedExpression::Assignment(..)
+            | CheckedExpression::PointerTo(_)
+            | CheckedExpression::Deref(_)
+            | CheckedExpression::SizeOf(_)
+            | CheckedExpression::OffsetOf(..)
+            | CheckedExpression::Cast(_) => true,
+            CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+                return self.operand(&pointer_to_nullable.pointer);
+            }
+            _ => false,
+        };
+
+        let expression = self.expression(expression);
+        if needs_parens {
+            format!("({expression})")
+        } else {
+            expression
+        }
+    }
+
+    fn expression(&mut self, expression: &CheckedExpression) -> String {
+        match expression {
+            CheckedExpression::Literal(literal) => match literal {
+                CheckedLiteral::Int(value, _) => match *value {
+                    // `2147483648` doesn't fit an `int`, so the negation of it can't be written
+                    i32::MIN => "-2147483647 - 1".to_string(),
+                    value => value.to_string(),
+                },
+                CheckedLiteral::Char(value, _) => c_char_literal(*value),
+                CheckedLiteral::Bool(value, _) => value.to_string(),
+                CheckedLiteral::Null(_) => "NULL".to_string(),
+                CheckedLiteral::String(value, Type::Str) => format!(
+                    "({}){{ {}, {} }}",
+                    self.type_name(&Type::Str),
+                    c_string_literal(value),
+                    value.len()
+                ),
+                CheckedLiteral::String(value, _) => c_string_literal(value),
+                CheckedLiteral::Struct(struct_literal, r#struct, struct_type)
+                    if r#struct.is_union =>
+                {
+                    let field_values: Vec<_> = struct_literal
+                        .fields
+                        .iter()
+                        .map(|(field_name, field_value)| {
+                            format!(
+                                ".{} = {}",
+                                c_ident(field_name),
+                                self.expression(field_value)
+                            )
+                        })
+                        .collect();
+                    format!(
+                        "({}){{ {} }}",
+                        self.type_name(struct_type),
+                        field_values.join(", ")
+                    )
+                }
+                CheckedLiteral::Struct(struct_literal, r#struct, struct_type) => {
+                    let field_values: Vec<_> = r#struct
+                        .fields
+                        .iter()
+                        .map(|(declared_field_name, _)| {
+                            let field_value = struct_literal
+                                .fields
+                                .get(declared_field_name.as_str())
+                                .expect("existence of field value was established by typechecker");
+                            (field_value, false)
+                        })
+                        .collect();
+                    let struct_type = self.type_name(struct_type);
+                    self.sequenced(&field_values, |field_values| {
+                        format!("({struct_type}){{ {} }}", field_values.join(", "))
+                    })
+                }
+                CheckedLiteral::Tuple(elements, tuple_type) => {
+                    let tuple_type = self.type_name(tuple_type);
+                    let elements: Vec<_> =
+                        elements.iter().map(|element| (element, false)).collect();
+                    self.sequenced(&elements, |elements| {
+                        let elements = if elements.is_empty() {
+                            "0".to_string()
+                        } else {
+                            elements.join(", ")
+                        };
+                        format!("({tuple_type}){{ {elements} }}")
+                    })
+                }
+                CheckedLiteral::Array(array_literal, array_type) => {
+                    if array_literal.element_type.is_none() {
+                        return self.unsupported("empty arrays without an element type");
+                    }
+                    let array_type = self.type_name(array_type);
+                    let elements: Vec<_> = array_literal
+                        .elements
+                        .iter()
+                        .map(|element| (element, false))
+                        .collect();
+                    self.sequenced(&elements, |elements| {
+                        format!("({array_type}){{ {{ {} }} }}", elements.join(", "))
+                    })
+                }
+            },
+            CheckedExpression::FunctionCall(func_call) => {
+                let args: Vec<_> = func_call.args.iter().map(|arg| (arg, false)).collect();
+                let name = func_name(&func_call.name);
+                self.sequenced(&args, |args| format!("{name}({})", args.join(", ")))
+            }
+            CheckedExpression::FunctionPointerCall(func_call) => {
+                let mut operands = vec![(func_call.callee.as_ref(), true)];
+                operands.extend(func_call.args.iter().map(|arg| (arg, false)));
+                self.sequenced(&operands, |operands| {
+                    format!("{}({})", operands[0], operands[1..].join(", "))
+                })
+            }
+            CheckedExpression::Function(function_name, _type) => func_name(function_name),
+            CheckedExpression::Variable(variable_name, _type, _is_mut) => c_ident(variable_name),
+            CheckedExpression::CompareOp(lhs, rhs, op, _type) => {
+                let op = match op {
+                    CompareOperation::Equality => "==",
+                    CompareOperation::NotEqual => "!=",
+                    CompareOperation::GreaterThan => ">",
+                    CompareOperation::GreaterThanEqual => ">=",
+                    CompareOperation::LessThan => "<",
+                    CompareOperation::LessThanEqual => "<=",
+                };
+                self.sequenced(&[(lhs, true), (rhs, true)], |operands| {
+                    format!("{} {op} {}", operands[0], operands[1])
+                })
+            }
+            CheckedExpression::MathOp(lhs, rhs, op, _type) => self
+                .sequenced(&[(lhs, true), (rhs, true)], |operands| {
+                    format!("{} {} {}", operands[0], op.symbol(), operands[1])
+                }),
+            CheckedExpression::LogicalAnd(lhs, rhs) => {
+                format!("{} && {}", self.operand(lhs), self.operand(rhs))
+            }
+            CheckedExpression::LogicalOr(lhs, rhs) => {
+                format!("{} || {}", self.operand(lhs), self.operand(rhs))
+            }
+            CheckedExpression::UnaryMinus(inner, _type) => format!("-{}", self.operand(inner)),
+            CheckedExpression::Not(inner) => format!("!{}", self.operand(inner)),
+            CheckedExpression::BitNot(inner, _type) => format!("~{}", self.operand(inner)),
+            CheckedExpression::FieldAccess(field_access, _struct, _type) => {
+                let object = self.operand(&field_access.object);
+                let access = if field_access.object_is_ptr {
+                    "->"
+                } else {
+                    "."
+                };
+                format!("{object}{access}{}", c_ident(&field_access.field_name))
+            }
+            CheckedExpression::TupleIndex(tuple_index) => {
+                format!(
+                    "{}._{}",
+                    self.operand(&tuple_index.object),
+                    tuple_index.index
+                )
+            }
+            // Arrays and slices both keep their elements in an `elements` field
+            CheckedExpression::ArrayIndex(array_index) => {
+                let array = self.operand(&array_index.array);
+                let index = self.expression(&array_index.index);
+                format!("{array}.elements[{index}]")
+            }
+            // Nullable pointers are represented the same as pointers
+            CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+                self.expression(&pointer_to_nullable.pointer)
+            }
+            CheckedExpression::ArrayToSlice(array_to_slice) => {
+                let array_len = if let Type::Array(_, array_len) = array_to_slice.array.ttype() {
+                    array_len
+                } else {
+                    panic!("non-array converted to slice");
+                };
+                let array = self.array_storage(&array_to_slice.array);
+                format!(
+                    "({}){{ {array}.elements, {array_len} }}",
+                    self.type_name(&array_to_slice.ttype)
+                )
+            }
+            CheckedExpression::Assignment(lhs, rhs) => {
+                format!("{} = {}", self.expression(lhs), self.expression(rhs))
+            }
+            CheckedExpression::Range(range) => {
+                let start = self.expression(&range.start);
+                let end = if range.inclusive {
+                    format!("{} + 1", self.operand(&range.end))
+                } else {
+                    self.expression(&range.end)
+                };
+                format!("({}){{ {start}, {end} }}", self.type_name(&range.ttype))
+            }
+            CheckedExpression::PointerTo(pointer_to) => {
+                format!("&{}", self.operand(&pointer_to.inner))
+            }
+            CheckedExpression::Deref(deref) => format!("*{}", self.operand(&deref.inner)),
+            CheckedExpression::SizeOf(ttype) => format!("(int)sizeof({})", self.type_name(ttype)),
+            CheckedExpression::OffsetOf(r#struct, field_name) => format!(
+                "(int)offsetof({}, {})",
+                c_ident(&r#struct.name),
+                c_ident(field_name)
+            ),
+            // C can't cast structs, not even to their own type
+            CheckedExpression::Cast(cast) if cast.inner.ttype().matches(&cast.ttype) => {
+                self.expression(&cast.inner)
+            }
+            CheckedExpression::Cast(cast) => {
+                format!(
+                    "({}){}",
+                    self.type_name(&cast.ttype),
+                    self.operand(&cast.inner)
+                )
+            }
+            CheckedExpression::StrBuiltin(str_builtin) => {
+                let string = self.operand(&str_builtin.args[0]);
+                match str_builtin.builtin {
+                    StrBuiltin::Len => format!("{string}.len"),
+                    StrBuiltin::Data => format!("{string}.data"),
+                    StrBuiltin::Slice => {
+                        let start = self.operand(&str_builtin.args[1]);
+                        let end = self.operand(&str_builtin.args[2]);
+                        format!(
+                            "({}){{ {string}.data + {start}, {end} - {start} }}",
+                            self.type_name(&Type::Str)
+                        )
+                    }
+                    // Evaluated into a temporary first, as the string is used twice
+                    StrBuiltin::FromCString => format!(
+                        "({{ char *clara_string = {string}; ({}){{ clara_string, __builtin_strlen(clara_string) }}; }})",
+                        self.type_name(&Type::Str)
+                    ),
+                }
+            }
+            // A GNU statement expression, which takes the value of its last expression statement
+            CheckedExpression::Block(block) => {
+                let outer_functions = std::mem::take(&mut self.functions);
+                self.indent += 1;
+                for statement in &block.body.statements {
+                    self.emit_statement(statement)
+                        .expect("writing to a string can't fail");
+                }
+                if let Some(ref value) = block.value {
+                    let value = self.expression(value);
+                    self.emit_line(&format!("{value};"))
+                        .expect("writing to a string can't fail");
+                }
+                self.indent -= 1;
+
+                let statements = std::mem::replace(&mut self.functions, outer_functions);
+                format!("({{\n{statements}{:width$}}})", "", width = self.indent * 4)
+            }
+            // A statement expression assigning the value of the taken branch to a temporary
+            CheckedExpression::IfExpr(if_expr) => {
+                let outer_functions = std::mem::take(&mut self.functions);
+                self.indent += 1;
+                self.emit_if_expression(if_expr)
+                    .expect("writing to a string can't fail");
+                self.indent -= 1;
+
+                let statements = std::mem::replace(&mut self.functions, outer_functions);
+                format!("({{\n{statements}{:width$}}})", "", width = self.indent * 4)
+            }
+            CheckedExpression::EnumVariant(enum_variant) => {
+                let enum_type = self.type_name(&enum_variant.ttype);
+                let tag = enum_variant.variant_index;
+                match &enum_variant.value {
+                    Some(value) => {
+                        let variant_name = &enum_variant.r#enum.variants[tag].0;
+                        let value = self.expression(value);
+                        format!("({enum_type}){{ .tag = {tag}, .value_{variant_name} = {value} }}")
+                    }
+                    None => format!("({enum_type}){{ .tag = {tag} }}"),
+                }
+            }
+        }
+    }
+
+    /// C leaves the order in which function arguments, the initializers of a compound literal and
+    /// the operands of most operators are evaluated unspecified, the other backends evaluate them
+    /// first to last. When an operand has side effects that could make a difference, the operands
+    /// up to it are stored in temporaries first, in a statement expression ending with the value
+    /// `combine` writes from the operands. Each operand is paired with whether it's written as
+    /// the operand of an operator.
+    fn sequenced(
+        &mut self,
+        operands: &[(&CheckedExpression, bool)],
+        combine: impl FnOnce(Vec<String>) -> String,
+    ) -> String {
+        let mut hoisted = vec![false; operands.len()];
+        if let Some(last_effect) = operands
+            .iter()
+            .rposition(|(operand, _)| has_side_effects(operand))
+        {
+            // Operands after it only need to be evaluated after it when it can change them
+            let read_after = operands[last_effect + 1..]
+                .iter()
+                .any(|(operand, _)| !is_constant(operand));
+            let end = if read_after {
+                last_effect + 1
+            } else {
+                last_effect
+            };
+            for (idx, (operand, _)) in operands[..end].iter().enumerate() {
+                hoisted[idx] =
+                    !is_constant(operand) && !matches!(operand.ttype(), Type::Unit | Type::Never);
+            }
+        }
+
+        let write = |this: &mut Self, operand, is_operand| {
+            if is_operand {
+                this.operand(operand)
+            } else {
+                this.expression(operand)
+            }
+        };
+        if !hoisted.contains(&true) {
+            let operands = operands
+                .iter()
+                .map(|&(operand, is_operand)| write(self, operand, is_operand))
+                .collect();
+            return combine(operands);
+        }
+
+        let outer_functions = std::mem::take(&mut self.functions);
+        self.indent += 1;
+        let mut written = vec![];
+        for (&(operand, is_operand), hoisted) in operands.iter().zip(hoisted) {
+            if hoisted {
+                let name = format!("clara_temporary_{}", self.temporary_count);
+                self.temporary_count += 1;
+                let declaration = self.declaration(&operand.ttype(), &name);
+                let value = self.expression(operand);
+                self.emit_line(&format!("{declaration} = {value};"))
+                    .expect("writing to a string can't fail");
+                written.push(name);
+            } else {
+                written.push(write(self, operand, is_operand));
+            }
+        }
+        let value = combine(written);
+        self.emit_line(&format!("{value};"))
+            .expect("writing to a string can't fail");
+        self.indent -= 1;
+
+        let statements = std::mem::replace(&mut self.functions, outer_functions);
+        format!("({{\n{statements}{:width$}}})", "", width = self.indent * 4)
+    }
+
+    /// An lvalue holding the array, slices of temporaries point into a copy of the array that
+    /// lives until the end of the enclosing block.
+    fn array_storage(&mut self, array: &CheckedExpression) -> String {
+        match array {
+            CheckedExpression::Variable(..)
+            | CheckedExpression::FieldAccess(..)
+            | CheckedExpression::ArrayIndex(_)
+            | CheckedExpression::Deref(_) => self.operand(array),
+            _ => {
+                let array_type = self.type_name(&array.ttype());
+                format!("(({array_type}[]){{ {} }})[0]", self.expression(array))
+            }
+        }
+    }
+
+    /// A declaration of `name` with the given type, all types are written as a single name
+    /// optionally followed by `*`s so the name always comes last.
+    fn declaration(&mut self, ttype: &Type, name: &str) -> String {
+        format!("{} {name}", self.type_name(ttype))
+    }
+
+    fn type_name(&mut self, ttype: &Type) -> String {
+        match ttype {
+            Type::Pointer(subtype, _is_mut) | Type::NullablePointer(subtype, _is_mut) => {
+                format!("{}*", self.type_name(subtype))
+            }
+            Type::Null => "void*".to_string(),
+            Type::CChar => "char".to_string(),
+            Type::GenericInt | Type::Int | Type::CInt | Type::Char => "int".to_string(),
+            Type::Bool => "bool".to_string(),
+            Type::Unit | Type::Never => "void".to_string(),
+            Type::UserDefined(name) => c_ident(name),
+            Type::String => self.unsupported("`string` values"),
+            Type::GenericEmptyArray => self.unsupported("empty arrays without an element type"),
+            Type::FnPtr {
+                params,
+                return_type,
+            } => {
+                let params: Vec<_> = params.iter().map(|param| self.type_name(param)).collect();
+                let params = if params.is_empty() {
+                    "void".to_string()
+                } else {
+                    params.join(", ")
+                };
+                let return_type = self.type_name(return_type);
+                self.generated_type(format!("fn({params}) {return_type}"), "fn", ttype, |name| {
+                    format!("typedef {return_type} (*{name})({params});")
+                })
+            }
+            Type::Array(element_type, size) => {
+                let layout = format!("[{}; {size}]", self.type_name(element_type));
+                self.generated_struct(layout, "array", ttype)
+            }
+            Type::Str => self.generated_struct("str".to_string(), "str", ttype),
+            Type::Slice(element_type) => {
+                let layout = format!("[{}]", self.type_name(element_type));
+                self.generated_struct(layout, "slice", ttype)
+            }
+            Type::Range(element_type) => {
+                let layout = format!("range<{}>", self.type_name(element_type));
+                self.generated_struct(layout, "range", ttype)
+            }
+            Type::Tuple(element_types) => {
+                let element_types: Vec<_> = element_types
+                    .iter()
+                    .map(|element_type| self.type_name(element_type))
+                    .collect();
+                let layout = format!("({})", element_types.join(", "));
+                self.generated_struct(layout, "tuple", ttype)
+            }
+            Type::Incomplete => panic!("attempted to use incomplete type in c codegen"),
+            Type::TypeParam(_) => {
+                unreachable!("only the instances of generic functions are compiled")
+            }
+        }
+    }
+
+    /// Records that the program can't be translated, returning a placeholder for the value or type.
+    fn unsupported(&mut self, what: &'static str) -> String {
+        self.unsupported.get_or_insert(what);
+        "void".to_string()
+    }
+
+    fn generated_struct(&mut self, layout: String, kind: &str, ttype: &Type) -> String {
+        self.generated_type(layout, kind, ttype, |name| {
+            format!("typedef struct {name} {name};")
+        })
+    }
+
+    /// The name of the generated type laid out as `layout`, writing its declaration the first
+    /// time it is named.
+    fn generated_type(
+        &mut self,
+        layout: String,
+        kind: &str,
+        ttype: &Type,
+        declaration: impl FnOnce(&str) -> String,
+    ) -> String {
+        if let Some(name) = self.generated_types.get(&layout) {
+            return name.clone();
+        }
+
+        let name = format!("clara_{kind}_{}", self.generated_types.len());
+        self.declarations.push_str(&declaration(&name));
+        self.declarations.push('\n');
+        self.generated_types.insert(layout, name.clone());
+        if kind != "fn" {
+            self.generated_structs.push((name.clone(), ttype.clone()));
+        }
+        name
+    }
+
+    /// Writes the body of a struct type after the bodies of the types it holds by value.
+    fn define(&mut self, ttype: &Type) -> fmt::Result {
+        let name = self.type_name(ttype);
+        let is_struct = matches!(
+            ttype,
+            Type::UserDefined(_)
+                | Type::Array(..)
+                | Type::Str
+                | Type::Slice(_)
+                | Type::Range(_)
+                | Type::Tuple(_)
+        );
+        if !is_struct || !self.defined.insert(name.clone()) {
+            return Ok(());
+        }
+
+        let fields: Vec<(Type, String)> =
+            match ttype {
+                Type::UserDefined(type_name) => {
+                    if let Some(r#struct) = self.structs.get(type_name) {
+                        if r#struct.is_opaque {
+                            // Opaque structs with a known size are a blob of bytes
+                            let Some(size) = r#struct.opaque_size else {
+                                return Ok(());
+                            };
+                            vec![(Type::CChar, format!("bytes[{size}]"))]
+                        } else {
+                            r#struct
+                                .fields
+                                .iter()
+                                .map(|(field_name, field_type)| {
+                                    (field_type.clone(), c_ident(field_name))
+                                })
+                                .collect()
+                        }
+                    } else {
+                        // Enums are a tag holding the index of the variant, followed by a field for
+                        // every variant that holds a value
+                        let r#enum = &self.enums[type_name];
+                        std::iter::once((Type::Int, "tag".to_string()))
+                            .chain(r#enum.variants.iter().filter_map(
+                                |(variant_name, value_type)| {
+                                    Some((value_type.clone()?, format!("value_{variant_name}")))
+                                },
+                            ))
+                            .collect()
+                    }
+                }
+                Type::Tuple(element_types) => element_types
+                    .iter()
+                    .enumerate()
+                    .map(|(element_idx, element_type)| {
+                        (element_type.clone(), format!("_{element_idx}"))
+                    })
+                    .collect(),
+                Type::Range(element_type) => vec![
+                    (*element_type.clone(), "start".to_string()),
+                    (*element_type.clone(), "end".to_string()),
+                ],
+                Type::Str => vec![
+                    (
+                        Type::Pointer(Box::new(Type::CChar), false),
+                        "data".to_string(),
+                    ),
+                    (Type::Int, "len".to_string()),
+                ],
+                Type::Slice(element_type) => vec![
+                    (
+                        Type::Pointer(element_type.clone(), false),
+                        "elements".to_string(),
+                    ),
+                    (Type::Int, "len".to_string()),
+                ],
+                Type::Array(element_type, size) => {
+                    vec![(*element_type.clone(), format!("elements[{size}]"))]
+                }
+                _ => unreachable!(),
+            };
+
+        for (field_type, _) in &fields {
+            self.define(field_type)?;
+        }
+
+        let is_union = matches!(
+            ttype,
+            Type::UserDefined(type_name)
+                if self.structs.get(type_name).is_some_and(|r#struct| r#struct.is_union)
+        );
+        let keyword = if is_union { "union" } else { "struct" };
+        let mut definition = format!("{keyword} {name} {{\n");
+        for (field_type, field_name) in &fields {
+            writeln!(
+                definition,
+                "    {};",
+                self.declaration(field_type, field_name)
+            )?;
+        }
+        // C doesn't allow structs without fields
+        if fields.is_empty() {
+            writeln!(definition, "    char empty;")?;
+        }
+        writeln!(definition, "}};\n")?;
+        self.definitions.push_str(&definition);
+        Ok(())
+    }
+}
+
+/// Whether evaluating the expression could do more than compute its value, blocks are assumed to.
+fn has_side_effects(expression: &CheckedExpression) -> bool {
+    match expression {
+        CheckedExpression::FunctionCall(_)
+        | CheckedExpression::FunctionPointerCall(_)
+        | CheckedExpression::Assignment(..)
+        | CheckedExpression::Block(_)
+        | CheckedExpression::IfExpr(_) => true,
+        CheckedExpression::Literal(CheckedLiteral::Struct(struct_literal, ..)) => {
+            struct_literal.fields.values().any(has_side_effects)
+        }
+        CheckedExpression::Literal(CheckedLiteral::Tuple(elements, _)) => {
+            elements.iter().any(has_side_effects)
+        }
+        CheckedExpression::Literal(CheckedLiteral::Array(array_literal, _)) => {
+            array_literal.elements.iter().any(has_side_effects)
+        }
+        CheckedExpression::StrBuiltin(str_builtin) => str_builtin.args.iter().any(has_side_effects),
+        CheckedExpression::CompareOp(lhs, rhs, ..)
+        | CheckedExpression::MathOp(lhs, rhs, ..)
+        | CheckedExpression::LogicalAnd(lhs, rhs)
+        | CheckedExpression::LogicalOr(lhs, rhs) => has_side_effects(lhs) || has_side_effects(rhs),
+        CheckedExpression::UnaryMinus(inner, _)
+        | CheckedExpression::Not(inner)
+        | CheckedExpression::BitNot(inner, _) => has_side_effects(inner),
+        CheckedExpression::FieldAccess(field_access, ..) => has_side_effects(&field_access.object),
+        CheckedExpression::TupleIndex(tuple_index) => has_side_effects(&tuple_index.object),
+        CheckedExpression::ArrayIndex(array_index) => {
+            has_side_effects(&array_index.array) || has_side_effects(&array_index.index)
+        }
+        CheckedExpression::ArrayToSlice(array_to_slice) => has_side_effects(&array_to_slice.array),
+        CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+            has_side_effects(&pointer_to_nullable.pointer)
+        }
+        CheckedExpression::Range(range) => {
+            has_side_effects(&range.start) || has_side_effects(&range.end)
+        }
+        CheckedExpression::PointerTo(pointer_to) => has_side_effects(&pointer_to.inner),
+        CheckedExpression::Deref(deref) => has_side_effects(&deref.inner),
+        CheckedExpression::Cast(cast) => has_side_effects(&cast.inner),
+        CheckedExpression::EnumVariant(enum_variant) => {
+            enum_variant.value.as_deref().is_some_and(has_side_effects)
+        }
+        CheckedExpression::Literal(_)
+        | CheckedExpression::Variable(..)
+        | CheckedExpression::Function(..)
+        | CheckedExpression::SizeOf(_)
+        | CheckedExpression::OffsetOf(..) => false,
+    }
+}
+
+/// Whether the expression has the same value wherever it's evaluated.
+fn is_constant(expression: &CheckedExpression) -> bool {
+    match expression {
+        CheckedExpression::Literal(literal) => !matches!(
+            literal,
+            CheckedLiteral::Struct(..) | CheckedLiteral::Tuple(..) | CheckedLiteral::Array(..)
+        ),
+        CheckedExpression::Function(..)
+        | CheckedExpression::SizeOf(_)
+        | CheckedExpression::OffsetOf(..) => true,
+        _ => false,
+    }
+}
+
+/// Instances of generic structs are named `Struct<type, ...>`.
+fn c_ident(name: &str) -> String {
+    if C_RESERVED.contains(&name) {
+        format!("{name}_")
+    } else {
+        name.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect()
+    }
+}
+
+/// Methods are named `Type::method` and instances of generic functions `function<type, ...>`,
+/// Clara's `main` is called by the `main` of the C program.
+fn func_name(name: &str) -> String {
+    if name == "main" {
+        "clara_main".to_string()
+    } else {
+        c_ident(&name.replace("::", "__"))
+    }
+}
+
+fn c_char_literal(value: char) -> String {
+    match value {
+        '\'' => r"'\''".to_string(),
+        '\\' => r"'\\'".to_string(),
+        '\n' => r"'\n'".to_string(),
+        '\t' => r"'\t'".to_string(),
+        ' '..='~' => format!("'{value}'"),
+        _ => (value as u32).to_string(),
+    }
+}
+
+/// The bytes of the string as a C string literal. Other bytes than printable ASCII are written as
+/// octal escapes, which unlike hexadecimal escapes end after three digits.
+fn c_string_literal(value: &str) -> String {
+    let mut literal = String::from('"');
+    for &byte in value.as_bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b'\n' => literal.push_str("\\n"),
+            b'\t' => literal.push_str("\\t"),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => literal.push_str(&format!("\\{byte:03o}")),
+        }
+    }
+    literal.push('"');
+    literal
+}
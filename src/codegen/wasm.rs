@@ -0,0 +1,1126 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::Write,
+    path::Path,
+};
+
+use eyre::bail;
+
+use crate::{
+    parser::{CompareOperation, MathOperation},
+    typechecker::{
+        CheckedBlock, CheckedExpression, CheckedFunction, CheckedLiteral, CheckedPattern,
+        CheckedProgram, CheckedStatement, Struct, Type,
+    },
+};
+
+/// String literals are placed in memory from here on, so that address 0 stays free for `null`.
+const DATA_START: usize = 8;
+const PAGE_SIZE: usize = 65536;
+/// Pages of memory after the string literals for the stack frames of functions, which grow down
+/// from the end of memory.
+const STACK_PAGES: usize = 1;
+
+pub fn generate_wat_file<P: AsRef<Path>>(
+    wat_filepath: P,
+    program: &CheckedProgram,
+) -> eyre::Result<()> {
+    let mut source = String::new();
+    WasmCodegen::default().emit_program(&mut source, program)?;
+    std::fs::write(wat_filepath, source)?;
+    Ok(())
+}
+
+/// Translates a typechecked program to a WebAssembly module in the text format. Integers,
+/// characters, booleans and pointers are all `i32`s, pointers being addresses into the exported
+/// linear memory that holds the string literals. Extern functions are imported from the `env`
+/// module and every function is exported under its own name.
+///
+/// Variables whose address is taken and variables holding structs live in the function's stack
+/// frame in memory, which `$stack_pointer` points to the end of. Structs can be created, copied
+/// and accessed through fields and pointers, but not passed to or returned from functions.
+/// Programs with values of other types, like tuples, arrays and `str`s, can't be translated, and
+/// neither can iterating over anything but a range.
+#[derive(Debug, Default)]
+pub struct WasmCodegen {
+    imports: String,
+    data: String,
+    functions: String,
+    /// Bytes of memory taken up by string literals, after `DATA_START`
+    data_len: usize,
+    structs: HashMap<String, Struct>,
+    /// Whether any function has a stack frame
+    uses_stack: bool,
+    /// Function body being emitted
+    body: String,
+    /// Locals of the function being emitted, besides its parameters
+    locals: Vec<String>,
+    /// Names of the locals and parameters of the function being emitted
+    local_names: HashSet<String>,
+    /// Where the variables in scope are kept by their Clara names
+    scopes: Vec<HashMap<String, Variable>>,
+    /// Names of the variables of the function being emitted whose address is taken
+    addressed: HashSet<String>,
+    /// Bytes taken up by the variables in the stack frame of the function being emitted, `None`
+    /// when the function has no stack frame
+    frame_size: Option<usize>,
+    /// Label numbers of the loops being emitted
+    loops: Vec<usize>,
+    label_count: usize,
+    indent: usize,
+}
+
+/// Where the value of a variable is kept.
+#[derive(Debug, Clone)]
+enum Variable {
+    /// In a local of the function, an empty name for variables of types without values
+    Local(String),
+    /// In the stack frame of the function, at the offset in bytes from its start
+    Stack(usize),
+}
+
+impl WasmCodegen {
+    pub fn emit_program(
+        &mut self,
+        out: &mut impl std::fmt::Write,
+        program: &CheckedProgram,
+    ) -> eyre::Result<()> {
+        for func in &program.extern_functions {
+            if func.is_variadic {
+                bail!(
+                    "variadic extern function `{}` is not supported by the wasm backend",
+                    func.name
+                );
+            }
+            let param_types = func
+                .parameters
+                .iter()
+                .map(|param| value_type(&param.ttype))
+                .collect::<eyre::Result<Vec<_>>>()?;
+            let mut signature = String::new();
+            for param_type in param_types.into_iter().flatten() {
+                write!(signature, " (param {param_type})")?;
+            }
+            if let Some(return_type) = value_type(&func.return_type)? {
+                write!(signature, " (result {return_type})")?;
+            }
+            writeln!(
+                self.imports,
+                "  (import \"env\" \"{}\" (func ${}{signature}))",
+                func.name, func.name
+            )?;
+        }
+
+        for r#struct in &program.structs {
+            self.structs.insert(r#struct.name.clone(), r#struct.clone());
+        }
+
+        for func in &program.functions {
+            self.emit_function(func)?;
+        }
+
+        let mut pages = (DATA_START + self.data_len).div_ceil(PAGE_SIZE);
+        if self.uses_stack {
+            pages += STACK_PAGES;
+        }
+        writeln!(out, "(module")?;
+        write!(out, "{}", self.imports)?;
+        writeln!(out, "  (memory (export \"memory\") {pages})")?;
+        if self.uses_stack {
+            let stack_end = pages * PAGE_SIZE;
+            writeln!(
+                out,
+                "  (global $stack_pointer (mut i32) (i32.const {stack_end}))"
+            )?;
+        }
+        write!(out, "{}", self.data)?;
+        write!(out, "{}", self.functions)?;
+        writeln!(out, ")")?;
+        Ok(())
+    }
+
+    fn emit_function(&mut self, func: &CheckedFunction) -> eyre::Result<()> {
+        self.locals.clear();
+        self.local_names.clear();
+        self.label_count = 0;
+        self.indent = 2;
+        self.scopes.push(HashMap::new());
+
+        let mut frame_scan = FrameScan::default();
+        frame_scan.block(&func.body);
+        self.addressed = frame_scan.addressed;
+        let has_frame = frame_scan.has_struct_values || !self.addressed.is_empty();
+        self.frame_size = has_frame.then_some(0);
+        if has_frame {
+            self.uses_stack = true;
+            self.local_names.insert("$clara_frame".to_string());
+            self.local_names.insert("$clara_stack_top".to_string());
+        }
+
+        let mut signature = String::new();
+        for param in &func.parameters {
+            let param_name = self.local_name(&param.name);
+            if let Some(param_type) = value_type(&param.ttype)? {
+                write!(signature, " (param {param_name} {param_type})")?;
+            }
+            if self.addressed.contains(&param.name) {
+                let offset = self.allocate(&param.ttype)?;
+                let store = store_instruction(&param.ttype);
+                let address = frame_address(offset);
+                self.emit_line(&format!("({store} {address} (local.get {param_name}))"))?;
+                self.declare(&param.name, Variable::Stack(offset));
+            } else {
+                self.declare(&param.name, Variable::Local(param_name));
+            }
+        }
+        let return_type = value_type(&func.return_type)?;
+        if let Some(return_type) = return_type {
+            write!(signature, " (result {return_type})")?;
+        }
+
+        self.emit_block(&func.body)?;
+        // Every path through a function with a result returns, but validation doesn't know that
+        if return_type.is_some() {
+            self.emit_line("(unreachable)")?;
+        } else if has_frame && !func.body.diverges() {
+            self.emit_line(FRAME_EPILOGUE)?;
+        }
+        self.scopes.pop();
+
+        writeln!(
+            self.functions,
+            "  (func {} (export \"{}\"){signature}",
+            func_id(&func.name),
+            func.name
+        )?;
+        if has_frame {
+            self.locals.push("$clara_frame".to_string());
+            self.locals.push("$clara_stack_top".to_string());
+        }
+        for local in &self.locals {
+            writeln!(self.functions, "    (local {local} i32)")?;
+        }
+        if let Some(frame_size) = self.frame_size.take() {
+            // Frames are kept aligned for the values in them
+            let frame_size = frame_size.next_multiple_of(4);
+            writeln!(
+                self.functions,
+                "    (local.set $clara_stack_top (global.get $stack_pointer))"
+            )?;
+            writeln!(
+                self.functions,
+                "    (local.set $clara_frame (i32.sub (local.get $clara_stack_top) (i32.const {frame_size})))"
+            )?;
+            writeln!(
+                self.functions,
+                "    (global.set $stack_pointer (local.get $clara_frame))"
+            )?;
+        }
+        write!(self.functions, "{}", std::mem::take(&mut self.body))?;
+        writeln!(self.functions, "  )")?;
+        Ok(())
+    }
+
+    /// A slot for a value of the type in the stack frame of the function, at the returned offset.
+    fn allocate(&mut self, ttype: &Type) -> eyre::Result<usize> {
+        let (size, align) = self.layout(ttype)?;
+        let frame_size = self
+            .frame_size
+            .as_mut()
+            .expect("functions with variables in memory have a stack frame");
+        let offset = frame_size.next_multiple_of(align);
+        *frame_size = offset + size;
+        Ok(offset)
+    }
+
+    /// The size and alignment in bytes of values of the type in memory.
+    fn layout(&self, ttype: &Type) -> eyre::Result<(usize, usize)> {
+        let Type::UserDefined(name) = ttype else {
+            let size = match ttype {
+                Type::CChar | Type::Bool => 1,
+                _ => match value_type(ttype)? {
+                    Some(_) => 4,
+                    None => 0,
+                },
+            };
+            return Ok((size, size.max(1)));
+        };
+        let r#struct = self.struct_of(name)?;
+        let mut size: usize = 0;
+        let mut align = 1;
+        for (_, field_type) in &r#struct.fields {
+            let (field_size, field_align) = self.layout(field_type)?;
+            align = align.max(field_align);
+            if r#struct.is_union {
+                size = size.max(field_size);
+            } else {
+                size = size.next_multiple_of(field_align) + field_size;
+            }
+        }
+        Ok((size.next_multiple_of(align), align))
+    }
+
+    /// The offset in bytes of the field from the start of the struct.
+    fn field_offset(&self, r#struct: &Struct, field_name: &str) -> eyre::Result<usize> {
+        let mut offset: usize = 0;
+        for (name, field_type) in &r#struct.fields {
+            let (field_size, field_align) = self.layout(field_type)?;
+            // All fields of a union start at its beginning
+            if r#struct.is_union {
+                if name == field_name {
+                    return Ok(0);
+                }
+                continue;
+            }
+            offset = offset.next_multiple_of(field_align);
+            if name == field_name {
+                return Ok(offset);
+            }
+            offset += field_size;
+        }
+        panic!("existence of field was established by typechecker");
+    }
+
+    fn struct_of(&self, name: &str) -> eyre::Result<&Struct> {
+        match self.structs.get(name) {
+            Some(r#struct) if !r#struct.is_opaque => Ok(r#struct),
+            _ => bail!("values of type `{name}` are not supported by the wasm backend"),
+        }
+    }
+
+    /// Instructions storing the value at the address, structs are stored field by field.
+    fn store(&mut self, address: String, value: &CheckedExpression) -> eyre::Result<String> {
+        let ttype = value.ttype();
+        let Type::UserDefined(ref name) = ttype else {
+            let store = store_instruction(&ttype);
+            return Ok(format!("({store} {address} {})", self.expression(value)?));
+        };
+
+        let r#struct = self.struct_of(name)?.clone();
+        let target = self.new_local("clara_target");
+        let mut instructions = vec![format!("(local.set {target} {address})")];
+        if let CheckedExpression::Literal(CheckedLiteral::Struct(struct_literal, ..)) = value {
+            for (field_name, _) in &r#struct.fields {
+                let Some(field_value) = struct_literal.fields.get(field_name) else {
+                    continue;
+                };
+                let offset = self.field_offset(&r#struct, field_name)?;
+                let field_address = format!("(i32.add (local.get {target}) (i32.const {offset}))");
+                instructions.push(self.store(field_address, field_value)?);
+            }
+        } else {
+            let source = self.address_of(value)?;
+            instructions.push(self.copy(&target, source, &ttype)?);
+        }
+        Ok(format!("(block {})", instructions.join(" ")))
+    }
+
+    /// Instructions copying the struct at the source address to the address in the target local,
+    /// a word at a time unless the struct only holds bytes.
+    fn copy(&mut self, target: &str, source: String, ttype: &Type) -> eyre::Result<String> {
+        let source_local = self.new_local("clara_source");
+        let mut instructions = vec![format!("(local.set {source_local} {source})")];
+        let (size, align) = self.layout(ttype)?;
+        let (load, store) = if align == 4 {
+            ("i32.load", "i32.store")
+        } else {
+            ("i32.load8_u", "i32.store8")
+        };
+        for offset in (0..size).step_by(align) {
+            instructions.push(format!(
+                "({store} (i32.add (local.get {target}) (i32.const {offset})) ({load} (i32.add (local.get {source_local}) (i32.const {offset}))))"
+            ));
+        }
+        Ok(instructions.join(" "))
+    }
+
+    /// The address of the place in memory the expression refers to.
+    fn address_of(&mut self, expression: &CheckedExpression) -> eyre::Result<String> {
+        Ok(match expression {
+            CheckedExpression::Variable(variable_name, ..) => match self.lookup(variable_name) {
+                Variable::Stack(offset) => frame_address(*offset),
+                Variable::Local(_) => {
+                    unreachable!("variables whose address is taken are in memory")
+                }
+            },
+            CheckedExpression::Deref(deref) => self.expression(&deref.inner)?,
+            CheckedExpression::FieldAccess(field_access, r#struct, _type) => {
+                let object = if field_access.object_is_ptr {
+                    self.expression(&field_access.object)?
+                } else {
+                    self.address_of(&field_access.object)?
+                };
+                let offset = self.field_offset(r#struct, &field_access.field_name)?;
+                format!("(i32.add {object} (i32.const {offset}))")
+            }
+            _ => unsupported("struct values that aren't stored in a variable")?,
+        })
+    }
+
+    /// A name for a new local that no other local of the function has.
+    fn local_name(&mut self, name: &str) -> String {
+        let mut local_name = format!("${name}");
+        let mut suffix = 1;
+        while self.local_names.contains(&local_name) {
+            local_name = format!("${name}.{suffix}");
+            suffix += 1;
+        }
+        self.local_names.insert(local_name.clone());
+        local_name
+    }
+
+    fn new_local(&mut self, name: &str) -> String {
+        let local_name = self.local_name(name);
+        self.locals.push(local_name.clone());
+        local_name
+    }
+
+    fn declare(&mut self, name: &str, variable: Variable) {
+        self.scopes
+            .last_mut()
+            .unwrap()
+            .insert(name.to_string(), variable);
+    }
+
+    fn lookup(&self, name: &str) -> &Variable {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .expect("existence of variable was previously established in typechecker")
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.label_count += 1;
+        self.label_count
+    }
+
+    fn emit_line(&mut self, line: &str) -> std::fmt::Result {
+        writeln!(self.body, "{:indent$}{line}", "", indent = self.indent * 2)
+    }
+
+    fn emit_block(&mut self, block: &CheckedBlock) -> eyre::Result<()> {
+        self.scopes.push(HashMap::new());
+        for statement in &block.statements {
+            self.emit_statement(statement)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Emits the expression for its side effects, dropping its value.
+    fn emit_expression_statement(&mut self, expression: &CheckedExpression) -> eyre::Result<()> {
+        let value = self.expression(expression)?;
+        if value_type(&expression.ttype())?.is_some() {
+            self.emit_line(&format!("(drop {value})"))?;
+        } else {
+            self.emit_line(&value)?;
+        }
+        Ok(())
+    }
+
+    /// Emits the statements of a loop body, which `continue` jumps to the end of.
+    fn emit_loop_body(&mut self, label: usize, body: &CheckedBlock) -> eyre::Result<()> {
+        self.emit_line(&format!("(block $continue_{label}"))?;
+        self.indent += 1;
+        self.loops.push(label);
+        self.emit_block(body)?;
+        self.loops.pop();
+        self.indent -= 1;
+        self.emit_line(")")?;
+        Ok(())
+    }
+
+    fn emit_statement(&mut self, statement: &CheckedStatement) -> eyre::Result<()> {
+        match statement {
+            CheckedStatement::Expression(expression) => {
+                self.emit_expression_statement(expression)?;
+            }
+            CheckedStatement::Const => {}
+            CheckedStatement::LetAssign(variable_name, value) => {
+                let ttype = value.ttype();
+                if self.addressed.contains(variable_name) || matches!(ttype, Type::UserDefined(_)) {
+                    let offset = self.allocate(&ttype)?;
+                    let store = self.store(frame_address(offset), value)?;
+                    self.emit_line(&store)?;
+                    self.declare(variable_name, Variable::Stack(offset));
+                } else if value_type(&ttype)?.is_some() {
+                    let value = self.expression(value)?;
+                    let local_name = self.new_local(variable_name);
+                    self.emit_line(&format!("(local.set {local_name} {value})"))?;
+                    self.declare(variable_name, Variable::Local(local_name));
+                } else {
+                    self.emit_expression_statement(value)?;
+                    self.declare(variable_name, Variable::Local(String::new()));
+                }
+            }
+            CheckedStatement::IfElse(if_else) => {
+                let condition = self.expression(&if_else.condition)?;
+                self.emit_line(&format!("(if {condition}"))?;
+                self.indent += 1;
+                self.emit_line("(then")?;
+                self.indent += 1;
+                self.emit_block(&if_else.if_body)?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+                if !if_else.else_body.statements.is_empty() {
+                    self.emit_line("(else")?;
+                    self.indent += 1;
+                    self.emit_block(&if_else.else_body)?;
+                    self.indent -= 1;
+                    self.emit_line(")")?;
+                }
+                self.indent -= 1;
+                self.emit_line(")")?;
+            }
+            CheckedStatement::WhileLoop(while_loop) => {
+                let label = self.new_label();
+                self.emit_line(&format!("(block $break_{label}"))?;
+                self.indent += 1;
+                self.emit_line(&format!("(loop $loop_{label}"))?;
+                self.indent += 1;
+                let condition = self.expression(&while_loop.condition)?;
+                self.emit_line(&format!("(br_if $break_{label} (i32.eqz {condition}))"))?;
+                self.emit_loop_body(label, &while_loop.body)?;
+                self.emit_line(&format!("(br $loop_{label})"))?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+            }
+            CheckedStatement::Loop(body) => {
+                let label = self.new_label();
+                self.emit_line(&format!("(block $break_{label}"))?;
+                self.indent += 1;
+                self.emit_line(&format!("(loop $loop_{label}"))?;
+                self.indent += 1;
+                self.emit_loop_body(label, body)?;
+                self.emit_line(&format!("(br $loop_{label})"))?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+            }
+            CheckedStatement::ForInLoop(for_in) => {
+                let range = match &for_in.iterable {
+                    CheckedExpression::Range(range) => range,
+                    _ => bail!("the wasm backend only supports iterating over range expressions"),
+                };
+                value_type(&for_in.elem_var_type)?;
+                let start = self.expression(&range.start)?;
+                let mut end = self.expression(&range.end)?;
+                if range.inclusive {
+                    end = format!("(i32.add {end} (i32.const 1))");
+                }
+
+                self.scopes.push(HashMap::new());
+                let elem = self.new_local(&for_in.elem_var_name);
+                let end_local = self.new_local("clara_end");
+                self.emit_line(&format!("(local.set {elem} {start})"))?;
+                self.emit_line(&format!("(local.set {end_local} {end})"))?;
+                let index = match &for_in.index_var {
+                    Some(index_var_name) => {
+                        let index = self.new_local(index_var_name);
+                        self.emit_line(&format!("(local.set {index} (i32.const 0))"))?;
+                        self.declare(index_var_name, Variable::Local(index.clone()));
+                        Some(index)
+                    }
+                    None => None,
+                };
+                self.declare(&for_in.elem_var_name, Variable::Local(elem.clone()));
+
+                let label = self.new_label();
+                self.emit_line(&format!("(block $break_{label}"))?;
+                self.indent += 1;
+                self.emit_line(&format!("(loop $loop_{label}"))?;
+                self.indent += 1;
+                self.emit_line(&format!(
+                    "(br_if $break_{label} (i32.ge_s (local.get {elem}) (local.get {end_local})))"
+                ))?;
+                self.emit_loop_body(label, &for_in.body)?;
+                for counter in std::iter::once(&elem).chain(index.as_ref()) {
+                    self.emit_line(&format!(
+                        "(local.set {counter} (i32.add (local.get {counter}) (i32.const 1)))"
+                    ))?;
+                }
+                self.emit_line(&format!("(br $loop_{label})"))?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+                self.scopes.pop();
+            }
+            CheckedStatement::Match(r#match) => {
+                value_type(&r#match.scrutinee.ttype())?;
+                let scrutinee = self.expression(&r#match.scrutinee)?;
+                let scrutinee_local = self.new_local("clara_scrutinee");
+                self.emit_line(&format!("(local.set {scrutinee_local} {scrutinee})"))?;
+
+                // Every arm leaves the block once its body ran, otherwise the next one is tried
+                let label = self.new_label();
+                self.emit_line(&format!("(block $match_{label}"))?;
+                self.indent += 1;
+                for arm in &r#match.arms {
+                    let is_wildcard = match &arm.pattern {
+                        CheckedPattern::Wildcard => true,
+                        CheckedPattern::Literal(literal) => {
+                            let literal = self.expression(literal)?;
+                            self.emit_line(&format!(
+                                "(if (i32.eq (local.get {scrutinee_local}) {literal})"
+                            ))?;
+                            self.indent += 1;
+                            self.emit_line("(then")?;
+                            self.indent += 1;
+                            false
+                        }
+                        CheckedPattern::EnumVariant(_) => {
+                            unreachable!("enum values were rejected by `value_type`")
+                        }
+                    };
+
+                    self.emit_expression_statement(&arm.body)?;
+                    self.emit_line(&format!("(br $match_{label})"))?;
+
+                    if !is_wildcard {
+                        self.indent -= 1;
+                        self.emit_line(")")?;
+                        self.indent -= 1;
+                        self.emit_line(")")?;
+                    }
+                }
+                self.indent -= 1;
+                self.emit_line(")")?;
+            }
+            CheckedStatement::Switch(switch) => {
+                value_type(&switch.scrutinee.ttype())?;
+                let scrutinee = self.expression(&switch.scrutinee)?;
+                let scrutinee_local = self.new_local("clara_scrutinee");
+                self.emit_line(&format!("(local.set {scrutinee_local} {scrutinee})"))?;
+
+                // Each case leaves the block once its body ran, the default body comes last
+                let label = self.new_label();
+                self.emit_line(&format!("(block $switch_{label}"))?;
+                self.indent += 1;
+                for (values, body) in &switch.cases {
+                    let condition = values
+                        .iter()
+                        .map(|value| {
+                            format!("(i32.eq (local.get {scrutinee_local}) (i32.const {value}))")
+                        })
+                        .reduce(|lhs, rhs| format!("(i32.or {lhs} {rhs})"))
+                        .unwrap_or_else(|| "(i32.const 0)".to_string());
+                    self.emit_line(&format!("(if {condition}"))?;
+                    self.indent += 1;
+                    self.emit_line("(then")?;
+                    self.indent += 1;
+                    self.emit_block(body)?;
+                    self.emit_line(&format!("(br $switch_{label})"))?;
+                    self.indent -= 1;
+                    self.emit_line(")")?;
+                    self.indent -= 1;
+                    self.emit_line(")")?;
+                }
+                self.emit_block(&switch.default)?;
+                self.indent -= 1;
+                self.emit_line(")")?;
+            }
+            CheckedStatement::Return(return_value) => {
+                let value = self.expression(return_value)?;
+                let has_value = value_type(&return_value.ttype())?.is_some();
+                if self.frame_size.is_none() {
+                    if has_value {
+                        self.emit_line(&format!("(return {value})"))?;
+                    } else {
+                        self.emit_line(&value)?;
+                        self.emit_line("(return)")?;
+                    }
+                } else if has_value {
+                    // The value may be read from the frame, which is only freed afterwards
+                    let result = self.new_local("clara_result");
+                    self.emit_line(&format!("(local.set {result} {value})"))?;
+                    self.emit_line(FRAME_EPILOGUE)?;
+                    self.emit_line(&format!("(return (local.get {result}))"))?;
+                } else {
+                    self.emit_line(&value)?;
+                    self.emit_line(FRAME_EPILOGUE)?;
+                    self.emit_line("(return)")?;
+                }
+            }
+            CheckedStatement::Break(loop_depth) => {
+                let label = self.loops[self.loops.len() - 1 - loop_depth];
+                self.emit_line(&format!("(br $break_{label})"))?;
+            }
+            CheckedStatement::Continue(loop_depth) => {
+                let label = self.loops[self.loops.len() - 1 - loop_depth];
+                self.emit_line(&format!("(br $continue_{label})"))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The address of the string in memory, terminated by a 0 like C strings.
+    fn string_literal(&mut self, value: &str) -> eyre::Result<usize> {
+        let address = DATA_START + self.data_len;
+        let mut escaped = String::new();
+        for byte in value.bytes() {
+            match byte {
+                b'"' | b'\\' => write!(escaped, "\\{}", byte as char)?,
+                b' '..=b'~' => escaped.push(byte as char),
+                _ => write!(escaped, "\\{byte:02x}")?,
+            }
+        }
+        writeln!(
+            self.data,
+            "  (data (i32.const {address}) \"{escaped}\\00\")"
+        )?;
+        self.data_len += value.len() + 1;
+        Ok(address)
+    }
+
+    /// The expression in the folded form, an instruction with its operands in parentheses.
+    fn expression(&mut self, expression: &CheckedExpression) -> eyre::Result<String> {
+        Ok(match expression {
+            CheckedExpression::Literal(literal) => match literal {
+                CheckedLiteral::Int(value, _) => format!("(i32.const {value})"),
+                CheckedLiteral::Char(value, _) => format!("(i32.const {})", *value as u32),
+                CheckedLiteral::Bool(value, _) => format!("(i32.const {})", *value as i32),
+                CheckedLiteral::Null(_) => "(i32.const 0)".to_string(),
+                CheckedLiteral::String(_, Type::Str) => unsupported("`str` values")?,
+                CheckedLiteral::String(value, _) => {
+                    format!("(i32.const {})", self.string_literal(value)?)
+                }
+                CheckedLiteral::Struct(..) => unsupported("struct literals")?,
+                CheckedLiteral::Tuple(..) => unsupported("tuple literals")?,
+                CheckedLiteral::Array(..) => unsupported("array literals")?,
+            },
+            CheckedExpression::FunctionCall(func_call) => {
+                let mut call = format!("(call {}", func_id(&func_call.name));
+                for arg in &func_call.args {
+                    write!(call, " {}", self.expression(arg)?)?;
+                }
+                call.push(')');
+                call
+            }
+            CheckedExpression::Variable(variable_name, ttype, _is_mut) => {
+                if value_type(ttype)?.is_none() {
+                    "(nop)".to_string()
+                } else {
+                    match self.lookup(variable_name) {
+                        Variable::Local(local_name) => format!("(local.get {local_name})"),
+                        Variable::Stack(offset) => {
+                            format!("({} {})", load_instruction(ttype), frame_address(*offset))
+                        }
+                    }
+                }
+            }
+            CheckedExpression::CompareOp(lhs, rhs, op, _type) => {
+                let instruction = match op {
+                    CompareOperation::Equality => "i32.eq",
+                    CompareOperation::NotEqual => "i32.ne",
+                    CompareOperation::GreaterThan => "i32.gt_s",
+                    CompareOperation::GreaterThanEqual => "i32.ge_s",
+                    CompareOperation::LessThan => "i32.lt_s",
+                    CompareOperation::LessThanEqual => "i32.le_s",
+                };
+                format!(
+                    "({instruction} {} {})",
+                    self.expression(lhs)?,
+                    self.expression(rhs)?
+                )
+            }
+            CheckedExpression::MathOp(lhs, rhs, op, _type) => {
+                let instruction = match op {
+                    MathOperation::Addition => "i32.add",
+                    MathOperation::Subtraction => "i32.sub",
+                    MathOperation::Multiplication => "i32.mul",
+                    MathOperation::Division => "i32.div_s",
+                    MathOperation::Modulo => "i32.rem_s",
+                    MathOperation::BitAnd => "i32.and",
+                    MathOperation::BitOr => "i32.or",
+                    MathOperation::BitXor => "i32.xor",
+                    MathOperation::Shl => "i32.shl",
+                    MathOperation::Shr => "i32.shr_s",
+                };
+                format!(
+                    "({instruction} {} {})",
+                    self.expression(lhs)?,
+                    self.expression(rhs)?
+                )
+            }
+            CheckedExpression::LogicalAnd(lhs, rhs) => format!(
+                "(if (result i32) {} (then {}) (else (i32.const 0)))",
+                self.expression(lhs)?,
+                self.expression(rhs)?
+            ),
+            CheckedExpression::LogicalOr(lhs, rhs) => format!(
+                "(if (result i32) {} (then (i32.const 1)) (else {}))",
+                self.expression(lhs)?,
+                self.expression(rhs)?
+            ),
+            CheckedExpression::UnaryMinus(inner, _type) => {
+                format!("(i32.sub (i32.const 0) {})", self.expression(inner)?)
+            }
+            CheckedExpression::Not(inner) => format!("(i32.eqz {})", self.expression(inner)?),
+            CheckedExpression::BitNot(inner, _type) => {
+                format!("(i32.xor {} (i32.const -1))", self.expression(inner)?)
+            }
+            CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+                self.expression(&pointer_to_nullable.pointer)?
+            }
+            CheckedExpression::Deref(deref) => {
+                value_type(&deref.ttype)?;
+                let load = load_instruction(&deref.ttype);
+                format!("({load} {})", self.expression(&deref.inner)?)
+            }
+            CheckedExpression::FieldAccess(_, _, ttype) => {
+                value_type(ttype)?;
+                let load = load_instruction(ttype);
+                format!("({load} {})", self.address_of(expression)?)
+            }
+            CheckedExpression::PointerTo(pointer_to) => self.address_of(&pointer_to.inner)?,
+            CheckedExpression::Assignment(lhs, rhs) => match &**lhs {
+                CheckedExpression::Variable(variable_name, _type, _is_mut)
+                    if matches!(self.lookup(variable_name), Variable::Local(_)) =>
+                {
+                    let value = self.expression(rhs)?;
+                    let Variable::Local(local_name) = self.lookup(variable_name) else {
+                        unreachable!();
+                    };
+                    format!("(local.set {local_name} {value})")
+                }
+                CheckedExpression::Variable(..) | CheckedExpression::Deref(_) => {
+                    let address = self.address_of(lhs)?;
+                    if let CheckedExpression::Literal(CheckedLiteral::Struct(..)) = &**rhs {
+                        // The fields of the literal may read the struct being assigned to, so
+                        // it's built in a slot of its own first
+                        let ttype = rhs.ttype();
+                        let offset = self.allocate(&ttype)?;
+                        let build = self.store(frame_address(offset), rhs)?;
+                        let target = self.new_local("clara_target");
+                        let copy = self.copy(&target, frame_address(offset), &ttype)?;
+                        format!("(block {build} (local.set {target} {address}) {copy})")
+                    } else {
+                        self.store(address, rhs)?
+                    }
+                }
+                _ => unreachable!("lhs of assignment is a variable or dereference"),
+            },
+            CheckedExpression::Block(block) => {
+                let outer_body = std::mem::take(&mut self.body);
+                self.indent += 1;
+                self.scopes.push(HashMap::new());
+                for statement in &block.body.statements {
+                    self.emit_statement(statement)?;
+                }
+                if let Some(ref value) = block.value {
+                    let value = self.expression(value)?;
+                    self.emit_line(&value)?;
+                }
+                self.scopes.pop();
+                self.indent -= 1;
+
+                let statements = std::mem::replace(&mut self.body, outer_body);
+                let result = match value_type(&block.ttype)? {
+                    Some(value_type) => format!(" (result {value_type})"),
+                    None => String::new(),
+                };
+                format!(
+                    "(block{result}\n{statements}{:indent$})",
+                    "",
+                    indent = self.indent * 2
+                )
+            }
+            CheckedExpression::IfExpr(if_expr) => {
+                let condition = self.expression(&if_expr.condition)?;
+                let outer_body = std::mem::take(&mut self.body);
+                self.indent += 1;
+                for (keyword, branch) in [("then", &if_expr.if_body), ("else", &if_expr.else_body)]
+                {
+                    self.emit_line(&format!("({keyword}"))?;
+                    self.indent += 1;
+                    self.scopes.push(HashMap::new());
+                    for statement in &branch.body.statements {
+                        self.emit_statement(statement)?;
+                    }
+                    if let Some(ref value) = branch.value {
+                        let value = self.expression(value)?;
+                        self.emit_line(&value)?;
+                    }
+                    self.scopes.pop();
+                    self.indent -= 1;
+                    self.emit_line(")")?;
+                }
+                self.indent -= 1;
+
+                let branches = std::mem::replace(&mut self.body, outer_body);
+                let result = match value_type(&if_expr.ttype)? {
+                    Some(value_type) => format!(" (result {value_type})"),
+                    None => String::new(),
+                };
+                format!(
+                    "(if{result} {condition}\n{branches}{:indent$})",
+                    "",
+                    indent = self.indent * 2
+                )
+            }
+            // All other values the wasm backend supports are already 32 bit integers
+            CheckedExpression::Cast(cast) => {
+                let inner = self.expression(&cast.inner)?;
+                if cast.ttype == Type::CChar && cast.inner.ttype() != Type::CChar {
+                    format!("(i32.extend8_s {inner})")
+                } else {
+                    inner
+                }
+            }
+            CheckedExpression::SizeOf(ttype) => format!("(i32.const {})", self.layout(ttype)?.0),
+            CheckedExpression::OffsetOf(r#struct, field_name) => {
+                format!("(i32.const {})", self.field_offset(r#struct, field_name)?)
+            }
+            CheckedExpression::Function(..) | CheckedExpression::FunctionPointerCall(_) => {
+                unsupported("function pointers")?
+            }
+            CheckedExpression::TupleIndex(_) => unsupported("tuple indexing")?,
+            CheckedExpression::ArrayIndex(_) | CheckedExpression::ArrayToSlice(_) => {
+                unsupported("arrays")?
+            }
+            CheckedExpression::Range(_) => unsupported("ranges outside of `for` loops")?,
+            CheckedExpression::EnumVariant(_) => unsupported("enums")?,
+            CheckedExpression::StrBuiltin(_) => unsupported("`str` values")?,
+        })
+    }
+}
+
+/// Frees the stack frame of the function before it returns.
+const FRAME_EPILOGUE: &str = "(global.set $stack_pointer (local.get $clara_stack_top))";
+
+/// The address of the slot at the offset in the stack frame of the function.
+fn frame_address(offset: usize) -> String {
+    format!("(i32.add (local.get $clara_frame) (i32.const {offset}))")
+}
+
+/// Bytes and booleans take up a single byte in memory, `c_char`s are signed.
+fn load_instruction(ttype: &Type) -> &'static str {
+    match ttype {
+        Type::CChar => "i32.load8_s",
+        Type::Bool => "i32.load8_u",
+        _ => "i32.load",
+    }
+}
+
+fn store_instruction(ttype: &Type) -> &'static str {
+    match ttype {
+        Type::CChar | Type::Bool => "i32.store8",
+        _ => "i32.store",
+    }
+}
+
+/// Finds out which variables of a function have to be kept in its stack frame before it's
+/// emitted, so that its returns know whether to free the frame.
+#[derive(Debug, Default)]
+struct FrameScan {
+    /// Names of the variables whose address is taken
+    addressed: HashSet<String>,
+    /// Whether the function declares variables holding structs or assigns struct literals
+    has_struct_values: bool,
+}
+
+impl FrameScan {
+    fn block(&mut self, block: &CheckedBlock) {
+        for statement in &block.statements {
+            self.statement(statement);
+        }
+    }
+
+    fn statement(&mut self, statement: &CheckedStatement) {
+        match statement {
+            CheckedStatement::Expression(expression) | CheckedStatement::Return(expression) => {
+                self.expression(expression)
+            }
+            CheckedStatement::LetAssign(_, value) => {
+                if let Type::UserDefined(_) = value.ttype() {
+                    self.has_struct_values = true;
+                }
+                self.expression(value);
+            }
+            CheckedStatement::WhileLoop(while_loop) => {
+                self.expression(&while_loop.condition);
+                self.block(&while_loop.body);
+            }
+            CheckedStatement::Loop(body) => self.block(body),
+            CheckedStatement::IfElse(if_else) => {
+                self.expression(&if_else.condition);
+                self.block(&if_else.if_body);
+                self.block(&if_else.else_body);
+            }
+            CheckedStatement::Match(r#match) => {
+                self.expression(&r#match.scrutinee);
+                for arm in &r#match.arms {
+                    self.expression(&arm.body);
+                }
+            }
+            CheckedStatement::Switch(switch) => {
+                self.expression(&switch.scrutinee);
+                for (_, body) in &switch.cases {
+                    self.block(body);
+                }
+                self.block(&switch.default);
+            }
+            CheckedStatement::ForInLoop(for_in) => {
+                self.expression(&for_in.iterable);
+                self.block(&for_in.body);
+            }
+            CheckedStatement::Break(_)
+            | CheckedStatement::Continue(_)
+            | CheckedStatement::Const => {}
+        }
+    }
+
+    fn expression(&mut self, expression: &CheckedExpression) {
+        match expression {
+            CheckedExpression::Assignment(lhs, rhs) => {
+                if let CheckedExpression::Literal(CheckedLiteral::Struct(..)) = rhs.as_ref() {
+                    self.has_struct_values = true;
+                }
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            CheckedExpression::PointerTo(pointer_to) => {
+                // The address of a field is inside of the variable holding the struct
+                let mut inner = pointer_to.inner.as_ref();
+                while let CheckedExpression::FieldAccess(field_access, ..) = inner {
+                    if field_access.object_is_ptr {
+                        break;
+                    }
+                    inner = &field_access.object;
+                }
+                if let CheckedExpression::Variable(name, ..) = inner {
+                    self.addressed.insert(name.clone());
+                }
+                self.expression(&pointer_to.inner);
+            }
+            CheckedExpression::Literal(literal) => match literal {
+                CheckedLiteral::Struct(struct_literal, ..) => {
+                    for field_value in struct_literal.fields.values() {
+                        self.expression(field_value);
+                    }
+                }
+                CheckedLiteral::Array(array_literal, _) => {
+                    for element in &array_literal.elements {
+                        self.expression(element);
+                    }
+                }
+                CheckedLiteral::Tuple(elements, _) => {
+                    for element in elements {
+                        self.expression(element);
+                    }
+                }
+                _ => {}
+            },
+            CheckedExpression::FunctionCall(func_call) => {
+                for arg in &func_call.args {
+                    self.expression(arg);
+                }
+            }
+            CheckedExpression::FunctionPointerCall(func_call) => {
+                self.expression(&func_call.callee);
+                for arg in &func_call.args {
+                    self.expression(arg);
+                }
+            }
+            CheckedExpression::StrBuiltin(str_builtin) => {
+                for arg in &str_builtin.args {
+                    self.expression(arg);
+                }
+            }
+            CheckedExpression::CompareOp(lhs, rhs, ..)
+            | CheckedExpression::MathOp(lhs, rhs, ..)
+            | CheckedExpression::LogicalAnd(lhs, rhs)
+            | CheckedExpression::LogicalOr(lhs, rhs) => {
+                self.expression(lhs);
+                self.expression(rhs);
+            }
+            CheckedExpression::UnaryMinus(inner, _)
+            | CheckedExpression::Not(inner)
+            | CheckedExpression::BitNot(inner, _) => self.expression(inner),
+            CheckedExpression::FieldAccess(field_access, ..) => {
+                self.expression(&field_access.object)
+            }
+            CheckedExpression::TupleIndex(tuple_index) => self.expression(&tuple_index.object),
+            CheckedExpression::ArrayIndex(array_index) => {
+                self.expression(&array_index.array);
+                self.expression(&array_index.index);
+            }
+            CheckedExpression::ArrayToSlice(array_to_slice) => {
+                self.expression(&array_to_slice.array)
+            }
+            CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+                self.expression(&pointer_to_nullable.pointer)
+            }
+            CheckedExpression::Range(range) => {
+                self.expression(&range.start);
+                self.expression(&range.end);
+            }
+            CheckedExpression::Deref(deref) => self.expression(&deref.inner),
+            CheckedExpression::Cast(cast) => self.expression(&cast.inner),
+            CheckedExpression::EnumVariant(enum_variant) => {
+                if let Some(value) = &enum_variant.value {
+                    self.expression(value);
+                }
+            }
+            CheckedExpression::Block(block) => {
+                self.block(&block.body);
+                if let Some(value) = &block.value {
+                    self.expression(value);
+                }
+            }
+            CheckedExpression::IfExpr(if_expr) => {
+                self.expression(&if_expr.condition);
+                for body in [&if_expr.if_body, &if_expr.else_body] {
+                    self.block(&body.body);
+                    if let Some(value) = &body.value {
+                        self.expression(value);
+                    }
+                }
+            }
+            CheckedExpression::Variable(..)
+            | CheckedExpression::Function(..)
+            | CheckedExpression::SizeOf(_)
+            | CheckedExpression::OffsetOf(..) => {}
+        }
+    }
+}
+
+/// The WebAssembly type of values of the type, `None` for types without values.
+fn value_type(ttype: &Type) -> eyre::Result<Option<&'static str>> {
+    Ok(match ttype {
+        Type::Unit | Type::Never => None,
+        Type::GenericInt
+        | Type::Int
+        | Type::CInt
+        | Type::Char
+        | Type::CChar
+        | Type::Bool
+        | Type::Null
+        | Type::Pointer(..)
+        | Type::NullablePointer(..) => Some("i32"),
+        _ => bail!("values of type `{ttype}` are not supported by the wasm backend"),
+    })
+}
+
+/// Identifiers can't contain the spaces, commas and brackets in the names of instances of generic
+/// functions.
+fn func_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|c| match c {
+            ' ' | ',' | ';' | '"' | '(' | ')' | '[' | ']' | '{' | '}' => '_',
+            c => c,
+        })
+        .collect();
+    format!("${id}")
+}
+
+fn unsupported(what: &str) -> eyre::Result<String> {
+    bail!("{what} are not supported by the wasm backend")
+}
@@ -0,0 +1,26 @@
+use codespan_reporting::diagnostic::Diagnostic;
+
+use crate::{source_map::SourceMap, span::FileId};
+
+/// Errors from any stage of the compiler, which can be rendered for the terminal or as JSON.
+pub trait IntoDiagnostic {
+    fn report(&self) -> Diagnostic<usize>;
+
+    /// `source_map` holds the files the spans of the error point into, to locate them by line and
+    /// column.
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value;
+
+    /// Warnings are reported like errors but don't stop the compilation.
+    fn is_warning(&self) -> bool {
+        false
+    }
+
+    /// The report without labels at synthetic spans, which have no source code to show.
+    fn report_located(&self) -> Diagnostic<usize> {
+        let mut diagnostic = self.report();
+        diagnostic
+            .labels
+            .retain(|label| label.file_id != FileId::SYNTHETIC.0);
+        diagnostic
+    }
+}
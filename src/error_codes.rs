@@ -0,0 +1,56 @@
+//! Stable codes identifying every kind of error reported by the lexer, parser and while loading
+//! imported files. Codes are never reused or renumbered, so tools can rely on them across
+//! releases.
+
+// Lexer errors
+
+/// A character that does not start any token.
+pub const UNKNOWN_TOKEN: &str = "E0001";
+/// A string literal missing its closing `"`.
+pub const UNTERMINATED_STRING: &str = "E0002";
+/// An integer literal that does not fit into a signed 32-bit integer.
+pub const INVALID_INT: &str = "E0003";
+/// A `\` followed by a character that is not a valid escape sequence.
+pub const INVALID_ESCAPE_SEQUENCE: &str = "E0004";
+/// A character literal missing its closing `'`.
+pub const UNTERMINATED_CHAR_LITERAL: &str = "E0005";
+/// A character literal containing more than one character.
+pub const MULTI_CHAR_LITERAL: &str = "E0006";
+/// A character literal containing no character.
+pub const EMPTY_CHAR_LITERAL: &str = "E0007";
+/// A block comment missing its closing `*/`.
+pub const UNTERMINATED_BLOCK_COMMENT: &str = "E0008";
+/// A `${}` placeholder in a string literal containing no expression.
+pub const EMPTY_INTERPOLATION: &str = "E0009";
+/// A multi-line string literal missing its closing `"""`.
+pub const UNTERMINATED_MULTILINE_STRING: &str = "E0010";
+
+// Parser errors
+
+/// A token that cannot appear at this position.
+pub const UNEXPECTED_TOKEN: &str = "E0101";
+/// A token other than the identifier required at this position.
+pub const EXPECTED_IDENTIFIER: &str = "E0102";
+/// A token other than the specific token required at this position.
+pub const EXPECTED_TOKEN: &str = "E0103";
+/// The input ended in the middle of a declaration, statement or expression.
+pub const UNEXPECTED_END_OF_INPUT: &str = "E0104";
+/// A struct literal in a condition, iterable or match scrutinee without parentheses.
+pub const STRUCT_LITERAL_IN_RESTRICTED_CONTEXT: &str = "E0105";
+/// A token at the top level of a program that does not start a declaration.
+pub const UNEXPECTED_TOP_LEVEL_TOKEN: &str = "E0106";
+/// An attribute that is not followed by a function or struct declaration.
+pub const MISPLACED_ATTRIBUTE: &str = "E0107";
+/// A field appearing more than once in a struct declaration or struct literal.
+pub const DUPLICATE_FIELD: &str = "E0108";
+/// A positional argument following a named argument in a call.
+pub const POSITIONAL_ARG_AFTER_NAMED_ARG: &str = "E0109";
+/// A loop label that is not followed by a `while`, `for` or `loop`.
+pub const LABEL_WITHOUT_LOOP: &str = "E0110";
+
+// Import errors
+
+/// An import of a file that does not exist or can't be read.
+pub const IMPORT_NOT_FOUND: &str = "E0201";
+/// A file importing itself, directly or through other imports.
+pub const CIRCULAR_IMPORT: &str = "E0202";
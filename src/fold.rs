@@ -0,0 +1,821 @@
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use serde_json::json;
+
+use crate::{
+    diagnostic::IntoDiagnostic,
+    parser::{
+        CallArg, CompareOperation, FunctionParameter, Literal, MathOperation, ParsedArrayIndex,
+        ParsedArrayLiteral, ParsedBlock, ParsedCast, ParsedConstDecl, ParsedDeref, ParsedEnum,
+        ParsedEnumVariant, ParsedExpression, ParsedExternFunction, ParsedFieldAccess,
+        ParsedForInLoop, ParsedFunction, ParsedFunctionCall, ParsedIfElse, ParsedImpl,
+        ParsedLetAssign, ParsedMatch, ParsedMatchArm, ParsedMethodCall, ParsedNamedArg,
+        ParsedPattern, ParsedPointerTo, ParsedProgram, ParsedRange, ParsedStatement, ParsedStruct,
+        ParsedStructLiteral, ParsedSwitch, ParsedTrait, ParsedTupleIndex, ParsedType,
+        ParsedTypeAlias, ParsedUnion, ParsedWhileLoop,
+    },
+    source_map::SourceMap,
+    span::{Span, Spanned},
+};
+
+/// Rewriting traversal of the parsed AST, taking every node by value and returning its
+/// replacement. Every method defaults to the identity transform, rebuilding its node from its
+/// folded children through the free function of the same name.
+pub trait Fold {
+    fn fold_program(&mut self, program: ParsedProgram) -> ParsedProgram {
+        fold_program(self, program)
+    }
+
+    fn fold_const_decl(&mut self, const_decl: ParsedConstDecl) -> ParsedConstDecl {
+        fold_const_decl(self, const_decl)
+    }
+
+    fn fold_type_alias(&mut self, type_alias: ParsedTypeAlias) -> ParsedTypeAlias {
+        fold_type_alias(self, type_alias)
+    }
+
+    fn fold_struct(&mut self, r#struct: ParsedStruct) -> ParsedStruct {
+        fold_struct(self, r#struct)
+    }
+
+    fn fold_enum(&mut self, r#enum: ParsedEnum) -> ParsedEnum {
+        fold_enum(self, r#enum)
+    }
+
+    fn fold_union(&mut self, union: ParsedUnion) -> ParsedUnion {
+        fold_union(self, union)
+    }
+
+    fn fold_impl(&mut self, r#impl: ParsedImpl) -> ParsedImpl {
+        fold_impl(self, r#impl)
+    }
+
+    fn fold_trait(&mut self, r#trait: ParsedTrait) -> ParsedTrait {
+        fold_trait(self, r#trait)
+    }
+
+    fn fold_extern_function(&mut self, func: ParsedExternFunction) -> ParsedExternFunction {
+        fold_extern_function(self, func)
+    }
+
+    fn fold_function(&mut self, func: ParsedFunction) -> ParsedFunction {
+        fold_function(self, func)
+    }
+
+    fn fold_parameter(&mut self, param: FunctionParameter) -> FunctionParameter {
+        fold_parameter(self, param)
+    }
+
+    fn fold_block(&mut self, block: ParsedBlock) -> ParsedBlock {
+        fold_block(self, block)
+    }
+
+    fn fold_statement(&mut self, statement: ParsedStatement) -> ParsedStatement {
+        fold_statement(self, statement)
+    }
+
+    fn fold_match_arm(&mut self, arm: ParsedMatchArm) -> ParsedMatchArm {
+        fold_match_arm(self, arm)
+    }
+
+    fn fold_pattern(&mut self, pattern: ParsedPattern) -> ParsedPattern {
+        fold_pattern(self, pattern)
+    }
+
+    fn fold_expression(&mut self, expression: ParsedExpression) -> ParsedExpression {
+        fold_expression(self, expression)
+    }
+
+    fn fold_literal(&mut self, literal: Literal) -> Literal {
+        fold_literal(self, literal)
+    }
+
+    fn fold_type(&mut self, ttype: ParsedType) -> ParsedType {
+        fold_type(self, ttype)
+    }
+}
+
+pub fn fold_program<F: Fold + ?Sized>(folder: &mut F, program: ParsedProgram) -> ParsedProgram {
+    ParsedProgram {
+        imports: program.imports,
+        consts: program
+            .consts
+            .into_iter()
+            .map(|const_decl| folder.fold_const_decl(const_decl))
+            .collect(),
+        type_aliases: program
+            .type_aliases
+            .into_iter()
+            .map(|type_alias| folder.fold_type_alias(type_alias))
+            .collect(),
+        structs: program
+            .structs
+            .into_iter()
+            .map(|r#struct| folder.fold_struct(r#struct))
+            .collect(),
+        enums: program
+            .enums
+            .into_iter()
+            .map(|r#enum| folder.fold_enum(r#enum))
+            .collect(),
+        unions: program
+            .unions
+            .into_iter()
+            .map(|union| folder.fold_union(union))
+            .collect(),
+        impls: program
+            .impls
+            .into_iter()
+            .map(|r#impl| folder.fold_impl(r#impl))
+            .collect(),
+        traits: program
+            .traits
+            .into_iter()
+            .map(|r#trait| folder.fold_trait(r#trait))
+            .collect(),
+        extern_functions: program
+            .extern_functions
+            .into_iter()
+            .map(|func| folder.fold_extern_function(func))
+            .collect(),
+        functions: program
+            .functions
+            .into_iter()
+            .map(|func| folder.fold_function(func))
+            .collect(),
+    }
+}
+
+pub fn fold_const_decl<F: Fold + ?Sized>(
+    folder: &mut F,
+    const_decl: ParsedConstDecl,
+) -> ParsedConstDecl {
+    ParsedConstDecl {
+        ttype: folder.fold_type(const_decl.ttype),
+        value: folder.fold_expression(const_decl.value),
+        ..const_decl
+    }
+}
+
+pub fn fold_type_alias<F: Fold + ?Sized>(
+    folder: &mut F,
+    type_alias: ParsedTypeAlias,
+) -> ParsedTypeAlias {
+    ParsedTypeAlias {
+        aliased: folder.fold_type(type_alias.aliased),
+        ..type_alias
+    }
+}
+
+pub fn fold_struct<F: Fold + ?Sized>(folder: &mut F, r#struct: ParsedStruct) -> ParsedStruct {
+    match r#struct {
+        ParsedStruct::Opaque(name, name_span, size, attributes, is_public) => {
+            ParsedStruct::Opaque(name, name_span, size, attributes, is_public)
+        }
+        ParsedStruct::Transparent(
+            name,
+            name_span,
+            generic_params,
+            fields,
+            attributes,
+            is_public,
+        ) => ParsedStruct::Transparent(
+            name,
+            name_span,
+            generic_params,
+            fields
+                .into_iter()
+                .map(|(field_name, field_type)| (field_name, folder.fold_type(field_type)))
+                .collect(),
+            attributes,
+            is_public,
+        ),
+    }
+}
+
+pub fn fold_enum<F: Fold + ?Sized>(folder: &mut F, r#enum: ParsedEnum) -> ParsedEnum {
+    ParsedEnum {
+        variants: r#enum
+            .variants
+            .into_iter()
+            .map(|(variant_name, variant_span, value_type)| {
+                (
+                    variant_name,
+                    variant_span,
+                    value_type.map(|value_type| folder.fold_type(value_type)),
+                )
+            })
+            .collect(),
+        ..r#enum
+    }
+}
+
+pub fn fold_union<F: Fold + ?Sized>(folder: &mut F, union: ParsedUnion) -> ParsedUnion {
+    ParsedUnion {
+        fields: union
+            .fields
+            .into_iter()
+            .map(|(field_name, field_type)| (field_name, folder.fold_type(field_type)))
+            .collect(),
+        ..union
+    }
+}
+
+pub fn fold_impl<F: Fold + ?Sized>(folder: &mut F, r#impl: ParsedImpl) -> ParsedImpl {
+    ParsedImpl {
+        methods: r#impl
+            .methods
+            .into_iter()
+            .map(|method| folder.fold_function(method))
+            .collect(),
+        ..r#impl
+    }
+}
+
+pub fn fold_trait<F: Fold + ?Sized>(folder: &mut F, r#trait: ParsedTrait) -> ParsedTrait {
+    ParsedTrait {
+        methods: r#trait
+            .methods
+            .into_iter()
+            .map(|method| folder.fold_extern_function(method))
+            .collect(),
+        ..r#trait
+    }
+}
+
+pub fn fold_extern_function<F: Fold + ?Sized>(
+    folder: &mut F,
+    func: ParsedExternFunction,
+) -> ParsedExternFunction {
+    ParsedExternFunction {
+        parameters: func
+            .parameters
+            .into_iter()
+            .map(|param| folder.fold_parameter(param))
+            .collect(),
+        return_type: folder.fold_type(func.return_type),
+        ..func
+    }
+}
+
+pub fn fold_function<F: Fold + ?Sized>(folder: &mut F, func: ParsedFunction) -> ParsedFunction {
+    ParsedFunction {
+        parameters: func
+            .parameters
+            .into_iter()
+            .map(|param| folder.fold_parameter(param))
+            .collect(),
+        return_type: folder.fold_type(func.return_type),
+        body: folder.fold_block(func.body),
+        ..func
+    }
+}
+
+pub fn fold_parameter<F: Fold + ?Sized>(
+    folder: &mut F,
+    param: FunctionParameter,
+) -> FunctionParameter {
+    FunctionParameter {
+        ttype: folder.fold_type(param.ttype),
+        ..param
+    }
+}
+
+pub fn fold_block<F: Fold + ?Sized>(folder: &mut F, block: ParsedBlock) -> ParsedBlock {
+    ParsedBlock {
+        statements: block
+            .statements
+            .into_iter()
+            .map(|statement| folder.fold_statement(statement))
+            .collect(),
+        tail_expr: block
+            .tail_expr
+            .map(|tail_expr| folder.fold_expression(tail_expr)),
+    }
+}
+
+fn fold_if_else<F: Fold + ?Sized>(folder: &mut F, if_else: ParsedIfElse) -> ParsedIfElse {
+    ParsedIfElse {
+        condition: folder.fold_expression(if_else.condition),
+        if_body: folder.fold_block(if_else.if_body),
+        else_body: if_else
+            .else_body
+            .map(|else_body| folder.fold_block(else_body)),
+        ..if_else
+    }
+}
+
+pub fn fold_statement<F: Fold + ?Sized>(
+    folder: &mut F,
+    statement: ParsedStatement,
+) -> ParsedStatement {
+    match statement {
+        ParsedStatement::Expression(expr) => {
+            ParsedStatement::Expression(folder.fold_expression(expr))
+        }
+        ParsedStatement::LetAssign(let_assign) => ParsedStatement::LetAssign(ParsedLetAssign {
+            type_annotation: let_assign
+                .type_annotation
+                .map(|(annotation, span)| (folder.fold_type(annotation), span)),
+            value: folder.fold_expression(let_assign.value),
+            ..let_assign
+        }),
+        ParsedStatement::Const(const_decl) => {
+            ParsedStatement::Const(folder.fold_const_decl(const_decl))
+        }
+        ParsedStatement::WhileLoop(while_loop) => ParsedStatement::WhileLoop(ParsedWhileLoop {
+            condition: folder.fold_expression(while_loop.condition),
+            body: folder.fold_block(while_loop.body),
+            else_body: while_loop
+                .else_body
+                .map(|else_body| folder.fold_block(else_body)),
+            label: while_loop.label,
+        }),
+        ParsedStatement::WhileLet {
+            pattern,
+            value,
+            body,
+            label,
+            span,
+        } => ParsedStatement::WhileLet {
+            pattern: folder.fold_pattern(pattern),
+            value: folder.fold_expression(value),
+            body: folder.fold_block(body),
+            label,
+            span,
+        },
+        ParsedStatement::Loop(body, label, span) => {
+            ParsedStatement::Loop(folder.fold_block(body), label, span)
+        }
+        ParsedStatement::IfElse(if_else) => ParsedStatement::IfElse(fold_if_else(folder, if_else)),
+        ParsedStatement::Match(r#match) => ParsedStatement::Match(ParsedMatch {
+            scrutinee: Box::new(folder.fold_expression(*r#match.scrutinee)),
+            arms: r#match
+                .arms
+                .into_iter()
+                .map(|arm| folder.fold_match_arm(arm))
+                .collect(),
+            span: r#match.span,
+        }),
+        ParsedStatement::Switch(switch) => ParsedStatement::Switch(ParsedSwitch {
+            scrutinee: Box::new(folder.fold_expression(*switch.scrutinee)),
+            cases: switch
+                .cases
+                .into_iter()
+                .map(|(values, body)| {
+                    let values = values
+                        .into_iter()
+                        .map(|value| folder.fold_expression(value))
+                        .collect();
+                    (values, folder.fold_block(body))
+                })
+                .collect(),
+            default: switch.default.map(|default| folder.fold_block(default)),
+            span: switch.span,
+        }),
+        ParsedStatement::ForInLoop(for_in) => ParsedStatement::ForInLoop(ParsedForInLoop {
+            iterable_value: folder.fold_expression(for_in.iterable_value),
+            body: folder.fold_block(for_in.body),
+            ..for_in
+        }),
+        ParsedStatement::Return(expr) => ParsedStatement::Return(folder.fold_expression(expr)),
+        ParsedStatement::Break(label, span) => ParsedStatement::Break(label, span),
+        ParsedStatement::Defer(call, span) => {
+            ParsedStatement::Defer(folder.fold_expression(call), span)
+        }
+        ParsedStatement::Continue(label, span) => ParsedStatement::Continue(label, span),
+        ParsedStatement::Unsafe(body, span) => {
+            ParsedStatement::Unsafe(folder.fold_block(body), span)
+        }
+    }
+}
+
+pub fn fold_match_arm<F: Fold + ?Sized>(folder: &mut F, arm: ParsedMatchArm) -> ParsedMatchArm {
+    ParsedMatchArm {
+        pattern: folder.fold_pattern(arm.pattern),
+        body: folder.fold_expression(arm.body),
+        span: arm.span,
+    }
+}
+
+pub fn fold_pattern<F: Fold + ?Sized>(folder: &mut F, pattern: ParsedPattern) -> ParsedPattern {
+    match pattern {
+        ParsedPattern::Literal(literal) => ParsedPattern::Literal(folder.fold_literal(literal)),
+        pattern => pattern,
+    }
+}
+
+pub fn fold_expression<F: Fold + ?Sized>(
+    folder: &mut F,
+    expression: ParsedExpression,
+) -> ParsedExpression {
+    match expression {
+        ParsedExpression::Literal(literal) => {
+            ParsedExpression::Literal(folder.fold_literal(literal))
+        }
+        ParsedExpression::FunctionCall(func_call) => {
+            ParsedExpression::FunctionCall(fold_function_call(folder, func_call))
+        }
+        ParsedExpression::Variable(name, span) => ParsedExpression::Variable(name, span),
+        ParsedExpression::CompareOp(lhs, rhs, op) => {
+            ParsedExpression::CompareOp(fold_boxed(folder, lhs), fold_boxed(folder, rhs), op)
+        }
+        ParsedExpression::MathOp(lhs, rhs, op) => {
+            ParsedExpression::MathOp(fold_boxed(folder, lhs), fold_boxed(folder, rhs), op)
+        }
+        ParsedExpression::LogicalAnd(lhs, rhs) => {
+            ParsedExpression::LogicalAnd(fold_boxed(folder, lhs), fold_boxed(folder, rhs))
+        }
+        ParsedExpression::LogicalOr(lhs, rhs) => {
+            ParsedExpression::LogicalOr(fold_boxed(folder, lhs), fold_boxed(folder, rhs))
+        }
+        ParsedExpression::UnaryMinus(inner, span) => {
+            ParsedExpression::UnaryMinus(fold_boxed(folder, inner), span)
+        }
+        ParsedExpression::Not(inner, span) => {
+            ParsedExpression::Not(fold_boxed(folder, inner), span)
+        }
+        ParsedExpression::BitNot(inner, span) => {
+            ParsedExpression::BitNot(fold_boxed(folder, inner), span)
+        }
+        ParsedExpression::Parenthesized(inner, span) => {
+            ParsedExpression::Parenthesized(fold_boxed(folder, inner), span)
+        }
+        ParsedExpression::FieldAccess(field_access) => {
+            ParsedExpression::FieldAccess(ParsedFieldAccess {
+                object: fold_boxed(folder, field_access.object),
+                ..field_access
+            })
+        }
+        ParsedExpression::MethodCall(method_call) => {
+            ParsedExpression::MethodCall(ParsedMethodCall {
+                object: fold_boxed(folder, method_call.object),
+                args: fold_call_args(folder, method_call.args),
+                ..method_call
+            })
+        }
+        ParsedExpression::TupleIndex(tuple_index) => {
+            ParsedExpression::TupleIndex(ParsedTupleIndex {
+                object: fold_boxed(folder, tuple_index.object),
+                ..tuple_index
+            })
+        }
+        ParsedExpression::ArrayIndex(array_index) => {
+            ParsedExpression::ArrayIndex(ParsedArrayIndex {
+                array: fold_boxed(folder, array_index.array),
+                index: fold_boxed(folder, array_index.index),
+            })
+        }
+        ParsedExpression::Assignment(lhs, rhs) => {
+            ParsedExpression::Assignment(fold_boxed(folder, lhs), fold_boxed(folder, rhs))
+        }
+        ParsedExpression::Range(range) => ParsedExpression::Range(ParsedRange {
+            start: fold_boxed(folder, range.start),
+            end: fold_boxed(folder, range.end),
+            ..range
+        }),
+        ParsedExpression::PointerTo(pointer_to) => ParsedExpression::PointerTo(ParsedPointerTo {
+            inner: fold_boxed(folder, pointer_to.inner),
+            ..pointer_to
+        }),
+        ParsedExpression::Deref(deref) => ParsedExpression::Deref(ParsedDeref {
+            inner: fold_boxed(folder, deref.inner),
+            ..deref
+        }),
+        ParsedExpression::EnumVariant(enum_variant) => {
+            ParsedExpression::EnumVariant(ParsedEnumVariant {
+                value: enum_variant.value.map(|value| fold_boxed(folder, value)),
+                ..enum_variant
+            })
+        }
+        ParsedExpression::SizeOf(ttype, span) => {
+            ParsedExpression::SizeOf(folder.fold_type(ttype), span)
+        }
+        ParsedExpression::OffsetOf {
+            ttype,
+            field,
+            field_span,
+            span,
+        } => ParsedExpression::OffsetOf {
+            ttype: folder.fold_type(ttype),
+            field,
+            field_span,
+            span,
+        },
+        ParsedExpression::Cast(cast) => ParsedExpression::Cast(ParsedCast {
+            target_type: folder.fold_type(cast.target_type),
+            inner: fold_boxed(folder, cast.inner),
+            ..cast
+        }),
+        ParsedExpression::ArrayRepeat { value, count, span } => ParsedExpression::ArrayRepeat {
+            value: fold_boxed(folder, value),
+            count,
+            span,
+        },
+        ParsedExpression::Block(block, span) => {
+            ParsedExpression::Block(Box::new(folder.fold_block(*block)), span)
+        }
+        ParsedExpression::IfExpr(if_else) => {
+            ParsedExpression::IfExpr(Box::new(fold_if_else(folder, *if_else)))
+        }
+    }
+}
+
+fn fold_boxed<F: Fold + ?Sized>(
+    folder: &mut F,
+    mut expr: Box<ParsedExpression>,
+) -> Box<ParsedExpression> {
+    *expr = folder.fold_expression(*expr);
+    expr
+}
+
+fn fold_function_call<F: Fold + ?Sized>(
+    folder: &mut F,
+    func_call: ParsedFunctionCall,
+) -> ParsedFunctionCall {
+    ParsedFunctionCall {
+        type_args: func_call
+            .type_args
+            .into_iter()
+            .map(|type_arg| folder.fold_type(type_arg))
+            .collect(),
+        args: fold_call_args(folder, func_call.args),
+        ..func_call
+    }
+}
+
+fn fold_call_args<F: Fold + ?Sized>(
+    folder: &mut F,
+    args: Vec<(CallArg, Span)>,
+) -> Vec<(CallArg, Span)> {
+    args.into_iter()
+        .map(|(arg, arg_span)| {
+            let arg = match arg {
+                CallArg::Positional(value) => CallArg::Positional(folder.fold_expression(value)),
+                CallArg::Named(named_arg) => CallArg::Named(ParsedNamedArg {
+                    value: folder.fold_expression(named_arg.value),
+                    ..named_arg
+                }),
+            };
+            (arg, arg_span)
+        })
+        .collect()
+}
+
+pub fn fold_literal<F: Fold + ?Sized>(folder: &mut F, literal: Literal) -> Literal {
+    match literal {
+        Literal::Struct(struct_literal, span) => Literal::Struct(
+            ParsedStructLiteral {
+                type_args: struct_literal
+                    .type_args
+                    .into_iter()
+                    .map(|type_arg| folder.fold_type(type_arg))
+                    .collect(),
+                fields: struct_literal
+                    .fields
+                    .into_iter()
+                    .map(|(field_name, field_span, field_value)| {
+                        (field_name, field_span, folder.fold_expression(field_value))
+                    })
+                    .collect(),
+                ..struct_literal
+            },
+            span,
+        ),
+        Literal::Array(array_literal, span) => Literal::Array(
+            ParsedArrayLiteral {
+                elements: array_literal
+                    .elements
+                    .into_iter()
+                    .map(|element| folder.fold_expression(element))
+                    .collect(),
+            },
+            span,
+        ),
+        Literal::Tuple(elements, span) => Literal::Tuple(
+            elements
+                .into_iter()
+                .map(|element| folder.fold_expression(element))
+                .collect(),
+            span,
+        ),
+        literal => literal,
+    }
+}
+
+pub fn fold_type<F: Fold + ?Sized>(folder: &mut F, ttype: ParsedType) -> ParsedType {
+    match ttype {
+        ParsedType::Pointer(subtype, is_mut) => {
+            ParsedType::Pointer(Box::new(folder.fold_type(*subtype)), is_mut)
+        }
+        ParsedType::NullablePointer(subtype, is_mut) => {
+            ParsedType::NullablePointer(Box::new(folder.fold_type(*subtype)), is_mut)
+        }
+        ParsedType::Array(subtype, size) => {
+            ParsedType::Array(Box::new(folder.fold_type(*subtype)), size)
+        }
+        ParsedType::Slice(subtype) => ParsedType::Slice(Box::new(folder.fold_type(*subtype))),
+        ParsedType::Tuple(element_types) => ParsedType::Tuple(
+            element_types
+                .into_iter()
+                .map(|element_type| folder.fold_type(element_type))
+                .collect(),
+        ),
+        ParsedType::Generic(name, type_args) => ParsedType::Generic(
+            name,
+            type_args
+                .into_iter()
+                .map(|type_arg| folder.fold_type(type_arg))
+                .collect(),
+        ),
+        ParsedType::FnPtr {
+            params,
+            return_type,
+        } => ParsedType::FnPtr {
+            params: params
+                .into_iter()
+                .map(|param| folder.fold_type(param))
+                .collect(),
+            return_type: Box::new(folder.fold_type(*return_type)),
+        },
+        ttype => ttype,
+    }
+}
+
+/// Evaluates arithmetic and comparisons on integer literals and logic on bool literals at compile
+/// time, folding `(1 + 2) * 3` into `9` and `1 < 2 && true` into `true`. Operations that would
+/// overflow or divide by zero are left for the program to evaluate and warned about, except in the
+/// initializers of constants, where the typechecker reports them as errors.
+#[derive(Debug, Default)]
+pub struct ConstantFoldPass {
+    pub warnings: Vec<ConstantFoldWarning>,
+}
+
+impl Fold for ConstantFoldPass {
+    fn fold_const_decl(&mut self, const_decl: ParsedConstDecl) -> ParsedConstDecl {
+        let warning_count = self.warnings.len();
+        let const_decl = fold_const_decl(self, const_decl);
+        self.warnings.truncate(warning_count);
+        const_decl
+    }
+
+    fn fold_expression(&mut self, expression: ParsedExpression) -> ParsedExpression {
+        // Fold the operands first so nested arithmetic collapses from the inside out
+        match fold_expression(self, expression) {
+            ParsedExpression::MathOp(lhs, rhs, op) => {
+                let span = lhs.span().to(rhs.span());
+                let (lhs_value, rhs_value) = match (&*lhs, &*rhs) {
+                    (
+                        ParsedExpression::Literal(Literal::Int(lhs, _)),
+                        ParsedExpression::Literal(Literal::Int(rhs, _)),
+                    ) => (*lhs, *rhs),
+                    _ => return ParsedExpression::MathOp(lhs, rhs, op),
+                };
+
+                match eval_math(lhs_value, rhs_value, op) {
+                    Some(value) => ParsedExpression::Literal(Literal::Int(value, span)),
+                    None => {
+                        self.warnings.push(match op {
+                            MathOperation::Division | MathOperation::Modulo if rhs_value == 0 => {
+                                ConstantFoldWarning::DivisionByZero(rhs.span())
+                            }
+                            _ => ConstantFoldWarning::Overflow(op, span),
+                        });
+                        ParsedExpression::MathOp(lhs, rhs, op)
+                    }
+                }
+            }
+            ParsedExpression::CompareOp(lhs, rhs, op) => match (&*lhs, &*rhs) {
+                (
+                    ParsedExpression::Literal(Literal::Int(lhs_value, _)),
+                    ParsedExpression::Literal(Literal::Int(rhs_value, _)),
+                ) => {
+                    let ordering = lhs_value.cmp(rhs_value);
+                    let value = match op {
+                        CompareOperation::Equality => ordering.is_eq(),
+                        CompareOperation::NotEqual => ordering.is_ne(),
+                        CompareOperation::GreaterThan => ordering.is_gt(),
+                        CompareOperation::GreaterThanEqual => ordering.is_ge(),
+                        CompareOperation::LessThan => ordering.is_lt(),
+                        CompareOperation::LessThanEqual => ordering.is_le(),
+                    };
+                    ParsedExpression::Literal(Literal::Bool(value, lhs.span().to(rhs.span())))
+                }
+                _ => ParsedExpression::CompareOp(lhs, rhs, op),
+            },
+            ParsedExpression::LogicalAnd(lhs, rhs) => match (&*lhs, &*rhs) {
+                (
+                    ParsedExpression::Literal(Literal::Bool(lhs_value, _)),
+                    ParsedExpression::Literal(Literal::Bool(rhs_value, _)),
+                ) => ParsedExpression::Literal(Literal::Bool(
+                    *lhs_value && *rhs_value,
+                    lhs.span().to(rhs.span()),
+                )),
+                _ => ParsedExpression::LogicalAnd(lhs, rhs),
+            },
+            ParsedExpression::LogicalOr(lhs, rhs) => match (&*lhs, &*rhs) {
+                (
+                    ParsedExpression::Literal(Literal::Bool(lhs_value, _)),
+                    ParsedExpression::Literal(Literal::Bool(rhs_value, _)),
+                ) => ParsedExpression::Literal(Literal::Bool(
+                    *lhs_value || *rhs_value,
+                    lhs.span().to(rhs.span()),
+                )),
+                _ => ParsedExpression::LogicalOr(lhs, rhs),
+            },
+            ParsedExpression::UnaryMinus(inner, op_span) => match *inner {
+                ParsedExpression::Literal(Literal::Int(value, span)) => match value.checked_neg() {
+                    Some(value) => ParsedExpression::Literal(Literal::Int(value, op_span.to(span))),
+                    None => {
+                        let span = op_span.to(span);
+                        self.warnings.push(ConstantFoldWarning::Overflow(
+                            MathOperation::Subtraction,
+                            span,
+                        ));
+                        ParsedExpression::UnaryMinus(
+                            Box::new(ParsedExpression::Literal(Literal::Int(value, span))),
+                            op_span,
+                        )
+                    }
+                },
+                inner => ParsedExpression::UnaryMinus(Box::new(inner), op_span),
+            },
+            ParsedExpression::Not(inner, op_span) => match *inner {
+                ParsedExpression::Literal(Literal::Bool(value, span)) => {
+                    ParsedExpression::Literal(Literal::Bool(!value, op_span.to(span)))
+                }
+                inner => ParsedExpression::Not(Box::new(inner), op_span),
+            },
+            ParsedExpression::Parenthesized(inner, span) => match *inner {
+                ParsedExpression::Literal(Literal::Int(value, _)) => {
+                    ParsedExpression::Literal(Literal::Int(value, span))
+                }
+                ParsedExpression::Literal(Literal::Bool(value, _)) => {
+                    ParsedExpression::Literal(Literal::Bool(value, span))
+                }
+                inner => ParsedExpression::Parenthesized(Box::new(inner), span),
+            },
+            expression => expression,
+        }
+    }
+}
+
+/// `None` if the operation overflows or divides by zero.
+fn eval_math(lhs: i32, rhs: i32, op: MathOperation) -> Option<i32> {
+    match op {
+        MathOperation::Addition => lhs.checked_add(rhs),
+        MathOperation::Subtraction => lhs.checked_sub(rhs),
+        MathOperation::Multiplication => lhs.checked_mul(rhs),
+        MathOperation::Division => lhs.checked_div(rhs),
+        MathOperation::Modulo => lhs.checked_rem(rhs),
+        MathOperation::BitAnd => Some(lhs & rhs),
+        MathOperation::BitOr => Some(lhs | rhs),
+        MathOperation::BitXor => Some(lhs ^ rhs),
+        MathOperation::Shl => lhs.checked_shl(u32::try_from(rhs).ok()?),
+        MathOperation::Shr => lhs.checked_shr(u32::try_from(rhs).ok()?),
+    }
+}
+
+#[derive(Debug)]
+pub enum ConstantFoldWarning {
+    Overflow(MathOperation, Span),
+    DivisionByZero(Span),
+}
+
+impl IntoDiagnostic for ConstantFoldWarning {
+    fn report(&self) -> Diagnostic<usize> {
+        match *self {
+            Self::Overflow(op, span) => Diagnostic::warning()
+                .with_message("constant expression overflows")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!(
+                        "`{}` overflows an `int`, it is left to be evaluated at runtime",
+                        op.symbol()
+                    ),
+                )]),
+            Self::DivisionByZero(span) => Diagnostic::warning()
+                .with_message("division by zero")
+                .with_labels(vec![
+                    Label::primary(span.source.0, span).with_message("this divisor is zero")
+                ]),
+        }
+    }
+
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value {
+        match *self {
+            Self::Overflow(op, span) => json!({
+                "message": format!("`{}` in constant expression overflows", op.symbol()),
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DivisionByZero(span) => json!({
+                "message": "division by zero",
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+        }
+    }
+
+    fn is_warning(&self) -> bool {
+        true
+    }
+}
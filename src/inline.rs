@@ -0,0 +1,278 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::typechecker::{
+    CheckedBlock, CheckedExpression, CheckedLiteral, CheckedProgram, CheckedStatement,
+};
+
+/// The largest number of expressions the body of an inlined function can have.
+const INLINE_SIZE_LIMIT: usize = 32;
+
+/// Replaces calls to functions marked `#[inline]` with their bodies, runs on the checked program
+/// before it's handed to a backend.
+///
+/// Only functions whose body is a single expression, without blocks binding variables of their
+/// own, are inlined, and only at calls whose arguments are simple enough to be evaluated in place
+/// of every use of their parameter. Recursive functions and functions larger than
+/// [`INLINE_SIZE_LIMIT`] are always called.
+pub fn inline_functions(program: &mut CheckedProgram) {
+    let mut candidates: HashMap<String, Candidate> = program
+        .functions
+        .iter()
+        .filter(|func| func.is_inline)
+        .filter_map(|func| {
+            let body = match func.body.statements.as_slice() {
+                [CheckedStatement::Return(value)] | [CheckedStatement::Expression(value)] => value,
+                _ => return None,
+            };
+            let mut body = body.clone();
+            if !is_inlinable(&mut body) || size(&mut body) > INLINE_SIZE_LIMIT {
+                return None;
+            }
+            let parameters = func
+                .parameters
+                .iter()
+                .map(|param| param.name.clone())
+                .collect();
+            Some((func.name.clone(), Candidate { parameters, body }))
+        })
+        .collect();
+
+    let callees: HashMap<String, HashSet<String>> = candidates
+        .iter_mut()
+        .map(|(name, candidate)| {
+            let mut callees = HashSet::new();
+            calls(&mut candidate.body, &mut callees);
+            (name.clone(), callees)
+        })
+        .collect();
+    candidates.retain(|name, _| !calls_itself(&callees, name));
+
+    let inliner = Inliner { candidates };
+    for func in &mut program.functions {
+        inliner.inline_block(&mut func.body);
+    }
+}
+
+/// The body of an inlinable function, along with the names of the parameters it uses.
+struct Candidate {
+    parameters: Vec<String>,
+    body: CheckedExpression,
+}
+
+struct Inliner {
+    candidates: HashMap<String, Candidate>,
+}
+
+impl Inliner {
+    fn inline_block(&self, block: &mut CheckedBlock) {
+        for statement in &mut block.statements {
+            self.inline_statement(statement);
+        }
+    }
+
+    fn inline_statement(&self, statement: &mut CheckedStatement) {
+        match statement {
+            CheckedStatement::Expression(expression)
+            | CheckedStatement::LetAssign(_, expression)
+            | CheckedStatement::Return(expression) => self.inline_expression(expression),
+            CheckedStatement::WhileLoop(while_loop) => {
+                self.inline_expression(&mut while_loop.condition);
+                self.inline_block(&mut while_loop.body);
+            }
+            CheckedStatement::Loop(body) => self.inline_block(body),
+            CheckedStatement::IfElse(if_else) => {
+                self.inline_expression(&mut if_else.condition);
+                self.inline_block(&mut if_else.if_body);
+                self.inline_block(&mut if_else.else_body);
+            }
+            CheckedStatement::Match(r#match) => {
+                self.inline_expression(&mut r#match.scrutinee);
+                for arm in &mut r#match.arms {
+                    self.inline_expression(&mut arm.body);
+                }
+            }
+            CheckedStatement::Switch(switch) => {
+                self.inline_expression(&mut switch.scrutinee);
+                for (_, body) in &mut switch.cases {
+                    self.inline_block(body);
+                }
+                self.inline_block(&mut switch.default);
+            }
+            CheckedStatement::ForInLoop(for_in) => {
+                self.inline_expression(&mut for_in.iterable);
+                self.inline_block(&mut for_in.body);
+            }
+            CheckedStatement::Break(_)
+            | CheckedStatement::Continue(_)
+            | CheckedStatement::Const => {}
+        }
+    }
+
+    fn inline_expression(&self, expression: &mut CheckedExpression) {
+        match expression {
+            CheckedExpression::Block(block) => {
+                self.inline_block(&mut block.body);
+                if let Some(value) = &mut block.value {
+                    self.inline_expression(value);
+                }
+            }
+            CheckedExpression::IfExpr(if_expr) => {
+                self.inline_expression(&mut if_expr.condition);
+                for body in [&mut if_expr.if_body, &mut if_expr.else_body] {
+                    self.inline_block(&mut body.body);
+                    if let Some(value) = &mut body.value {
+                        self.inline_expression(value);
+                    }
+                }
+            }
+            _ => {
+                for subexpression in subexpressions(expression) {
+                    self.inline_expression(subexpression);
+                }
+            }
+        }
+
+        let CheckedExpression::FunctionCall(func_call) = expression else {
+            return;
+        };
+        let Some(candidate) = self.candidates.get(&func_call.name) else {
+            return;
+        };
+        if !func_call.args.iter().all(is_simple_argument) {
+            return;
+        }
+
+        let arguments: HashMap<&str, &CheckedExpression> = candidate
+            .parameters
+            .iter()
+            .map(String::as_str)
+            .zip(&func_call.args)
+            .collect();
+        let mut body = candidate.body.clone();
+        substitute(&mut body, &arguments);
+        // The body may call other inlinable functions, it can't lead back to this one
+        self.inline_expression(&mut body);
+        *expression = body;
+    }
+}
+
+/// Arguments that can be evaluated any number of times, or not at all, with the same result:
+/// literals and immutable variables.
+fn is_simple_argument(argument: &CheckedExpression) -> bool {
+    match argument {
+        CheckedExpression::Literal(literal) => !matches!(
+            literal,
+            CheckedLiteral::Struct(..) | CheckedLiteral::Array(..) | CheckedLiteral::Tuple(..)
+        ),
+        CheckedExpression::Variable(_, _, is_mut) => !is_mut,
+        _ => false,
+    }
+}
+
+/// Blocks could bind variables clashing with the names of arguments, so bodies containing them
+/// aren't inlined.
+fn is_inlinable(expression: &mut CheckedExpression) -> bool {
+    match expression {
+        CheckedExpression::Block(_) | CheckedExpression::IfExpr(_) => false,
+        _ => subexpressions(expression).into_iter().all(is_inlinable),
+    }
+}
+
+/// The number of expressions making up the expression, an estimate of the code it generates.
+fn size(expression: &mut CheckedExpression) -> usize {
+    1 + subexpressions(expression)
+        .into_iter()
+        .map(size)
+        .sum::<usize>()
+}
+
+/// Collects the names of the functions called in the expression.
+fn calls(expression: &mut CheckedExpression, names: &mut HashSet<String>) {
+    if let CheckedExpression::FunctionCall(func_call) = expression {
+        names.insert(func_call.name.clone());
+    }
+    for subexpression in subexpressions(expression) {
+        calls(subexpression, names);
+    }
+}
+
+/// Whether the function can call itself, directly or through the bodies of other candidates.
+fn calls_itself(callees: &HashMap<String, HashSet<String>>, name: &str) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![name];
+    while let Some(caller) = pending.pop() {
+        for callee in callees.get(caller).into_iter().flatten() {
+            if callee == name {
+                return true;
+            }
+            if seen.insert(callee) {
+                pending.push(callee);
+            }
+        }
+    }
+    false
+}
+
+/// Replaces the uses of parameters with the arguments passed for them.
+fn substitute(expression: &mut CheckedExpression, arguments: &HashMap<&str, &CheckedExpression>) {
+    if let CheckedExpression::Variable(name, _, _) = expression {
+        if let Some(&argument) = arguments.get(name.as_str()) {
+            *expression = argument.clone();
+        }
+        return;
+    }
+    for subexpression in subexpressions(expression) {
+        substitute(subexpression, arguments);
+    }
+}
+
+/// The expressions directly contained in the expression, except for those inside of blocks.
+fn subexpressions(expression: &mut CheckedExpression) -> Vec<&mut CheckedExpression> {
+    match expression {
+        CheckedExpression::Literal(literal) => match literal {
+            CheckedLiteral::Struct(struct_literal, _, _) => {
+                struct_literal.fields.values_mut().collect()
+            }
+            CheckedLiteral::Array(array_literal, _) => array_literal.elements.iter_mut().collect(),
+            CheckedLiteral::Tuple(elements, _) => elements.iter_mut().collect(),
+            _ => vec![],
+        },
+        CheckedExpression::FunctionCall(func_call) => func_call.args.iter_mut().collect(),
+        CheckedExpression::FunctionPointerCall(func_call) => {
+            let mut subexpressions = vec![func_call.callee.as_mut()];
+            subexpressions.extend(func_call.args.iter_mut());
+            subexpressions
+        }
+        CheckedExpression::StrBuiltin(str_builtin) => str_builtin.args.iter_mut().collect(),
+        CheckedExpression::CompareOp(lhs, rhs, ..)
+        | CheckedExpression::MathOp(lhs, rhs, ..)
+        | CheckedExpression::LogicalAnd(lhs, rhs)
+        | CheckedExpression::LogicalOr(lhs, rhs)
+        | CheckedExpression::Assignment(lhs, rhs) => vec![lhs.as_mut(), rhs.as_mut()],
+        CheckedExpression::UnaryMinus(inner, _)
+        | CheckedExpression::Not(inner)
+        | CheckedExpression::BitNot(inner, _) => vec![inner.as_mut()],
+        CheckedExpression::FieldAccess(field_access, ..) => vec![field_access.object.as_mut()],
+        CheckedExpression::TupleIndex(tuple_index) => vec![tuple_index.object.as_mut()],
+        CheckedExpression::ArrayIndex(array_index) => {
+            vec![array_index.array.as_mut(), array_index.index.as_mut()]
+        }
+        CheckedExpression::ArrayToSlice(array_to_slice) => vec![array_to_slice.array.as_mut()],
+        CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+            vec![pointer_to_nullable.pointer.as_mut()]
+        }
+        CheckedExpression::Range(range) => vec![range.start.as_mut(), range.end.as_mut()],
+        CheckedExpression::PointerTo(pointer_to) => vec![pointer_to.inner.as_mut()],
+        CheckedExpression::Deref(deref) => vec![deref.inner.as_mut()],
+        CheckedExpression::Cast(cast) => vec![cast.inner.as_mut()],
+        CheckedExpression::EnumVariant(enum_variant) => {
+            enum_variant.value.iter_mut().map(Box::as_mut).collect()
+        }
+        CheckedExpression::Variable(..)
+        | CheckedExpression::Function(..)
+        | CheckedExpression::SizeOf(_)
+        | CheckedExpression::OffsetOf(..)
+        | CheckedExpression::Block(_)
+        | CheckedExpression::IfExpr(_) => vec![],
+    }
+}
@@ -1,27 +1,63 @@
+use std::fmt;
+
 use codespan_reporting::diagnostic::{Diagnostic, Label};
 use serde_json::json;
 
-use crate::span::{FileId, Span};
+use crate::{
+    diagnostic::IntoDiagnostic,
+    error_codes,
+    source_map::SourceMap,
+    span::{FileId, Span},
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum TokenKind {
     StringLiteral(String),
+    /// A string literal containing at least one `${expr}` placeholder
+    InterpolatedString(Vec<InterpolatedPart>),
+    CharLiteral(char),
     IntLiteral(i32),
     Ident(String),
+    /// The label of a loop, as in `'outer: while ...` and `break 'outer;`, without the quote
+    Lifetime(String),
     True,
     False,
+    Null,
     Opaque,
     Struct,
+    Enum,
+    Union,
+    Impl,
+    Trait,
+    Type,
     Extern,
     Fn,
+    Pub,
+    Import,
+    As,
+    Sizeof,
+    Offsetof,
+    Cast,
     Let,
+    Const,
     Mut,
+    Ref,
     While,
+    Loop,
     If,
     Else,
+    Match,
     For,
     In,
     Return,
+    Break,
+    Continue,
+    Defer,
+    Unsafe,
+    Switch,
+    Case,
+    Default,
+    Underscore,
     OParen,
     CParen,
     OBrace,
@@ -31,41 +67,96 @@ pub enum TokenKind {
     SemiColon,
     Comma,
     Colon,
+    ColonColon,
     RightArrow,
     Equal,
     EqualEqual,
+    FatArrow,
+    BangEqual,
+    Bang,
+    AmpAmp,
+    PipePipe,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
     GreaterThan,
     LessThan,
+    GreaterGreater,
+    LessLess,
     GreaterThanEqual,
     LessThanEqual,
     Plus,
     Minus,
     Star,
     Slash,
+    Percent,
+    PlusEqual,
+    MinusEqual,
+    StarEqual,
+    SlashEqual,
     Dot,
+    DotDot,
+    DotDotEqual,
+    DotDotDot,
+    Hash,
+    Question,
     Unknown,
 }
 
+/// A piece of an interpolated string, either literal text or the tokens of a placeholder.
+#[derive(Debug, Clone)]
+pub enum InterpolatedPart {
+    Literal(String),
+    Expr(Vec<Token>),
+}
+
 impl TokenKind {
     pub fn human_name(&self) -> &'static str {
         use TokenKind::*;
         match *self {
-            StringLiteral(_) => "string literal",
+            StringLiteral(_) | InterpolatedString(_) => "string literal",
+            CharLiteral(_) => "character literal",
             IntLiteral(_) => "integer literal",
             True | False => "boolean literal",
+            Null => "`null` keyword",
             Ident(_) => "identifier",
+            Lifetime(_) => "loop label",
             Fn => "`fn` keyword",
+            Pub => "`pub` keyword",
             Extern => "`extern` keyword",
+            Import => "`import` keyword",
+            As => "`as` keyword",
+            Sizeof => "`sizeof` keyword",
+            Offsetof => "`offsetof` keyword",
+            Cast => "`cast` keyword",
             Opaque => "`opaque` keyword",
             Struct => "`struct` keyword",
+            Enum => "`enum` keyword",
+            Union => "`union` keyword",
+            Impl => "`impl` keyword",
+            Trait => "`trait` keyword",
+            Type => "`type` keyword",
             Let => "`let` keyword",
+            Const => "`const` keyword",
             Mut => "`mut` keyword",
+            Ref => "`ref` keyword",
             While => "`while` keyword",
+            Loop => "`loop` keyword",
             If => "`if` keyword",
             Else => "`else` keyword",
+            Match => "`match` keyword",
             For => "`for` keyword",
             In => "`in` keyword",
             Return => "`return` keyword",
+            Break => "`break` keyword",
+            Continue => "`continue` keyword",
+            Defer => "`defer` keyword",
+            Unsafe => "`unsafe` keyword",
+            Switch => "`switch` keyword",
+            Case => "`case` keyword",
+            Default => "`default` keyword",
+            Underscore => "`_`",
             OParen => "`(`",
             CParen => "`)`",
             OBrace => "`{`",
@@ -75,9 +166,21 @@ impl TokenKind {
             SemiColon => "`;`",
             Comma => "`,`",
             Colon => "`:`",
+            ColonColon => "`::`",
             RightArrow => "`->`",
             Equal => "`=`",
             EqualEqual => "`==`",
+            FatArrow => "`=>`",
+            BangEqual => "`!=`",
+            Bang => "`!`",
+            AmpAmp => "`&&`",
+            PipePipe => "`||`",
+            Ampersand => "`&`",
+            Pipe => "`|`",
+            Caret => "`^`",
+            Tilde => "`~`",
+            GreaterGreater => "`>>`",
+            LessLess => "`<<`",
             GreaterThan => "`>`",
             GreaterThanEqual => "`>=`",
             LessThan => "`<`",
@@ -86,7 +189,17 @@ impl TokenKind {
             Minus => "`-`",
             Star => "`*`",
             Slash => "`/`",
+            Percent => "`%`",
+            PlusEqual => "`+=`",
+            MinusEqual => "`-=`",
+            StarEqual => "`*=`",
+            SlashEqual => "`/=`",
             Dot => "`.`",
+            DotDot => "`..`",
+            DotDotEqual => "`..=`",
+            DotDotDot => "`...`",
+            Hash => "`#`",
+            Question => "`?`",
             Unknown => "unknown token",
         }
     }
@@ -97,12 +210,19 @@ pub enum LexError {
     UnknownToken(char, Span),
     UnterminatedString(Span),
     InvalidInt(Span),
+    InvalidEscapeSequence { escape_char: char, span: Span },
+    UnterminatedCharLiteral(Span),
+    MultiCharLiteral(Span),
+    EmptyCharLiteral(Span),
+    UnterminatedBlockComment(Span),
+    EmptyInterpolation(Span),
+    UnterminatedMultilineString(Span),
 }
 
-impl LexError {
-    pub fn report(&self) -> Diagnostic<usize> {
+impl IntoDiagnostic for LexError {
+    fn report(&self) -> Diagnostic<usize> {
         use LexError::*;
-        match *self {
+        let diagnostic = match *self {
             UnknownToken(c, span) => Diagnostic::error()
                 .with_message(format!("unknown token `{}`", c))
                 .with_labels(vec![Label::primary(span.source.0, span)]),
@@ -115,31 +235,138 @@ impl LexError {
                 .with_message("invalid integer")
                 .with_labels(vec![Label::primary(span.source.0, span)
                     .with_message("value does not fit into signed 32-bit integer")]),
-        }
+            InvalidEscapeSequence { escape_char, span } => Diagnostic::error()
+                .with_message(format!("invalid escape sequence `\\{}`", escape_char))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Valid escape sequences are `\\\\`, `\\\"`, `\\'`, `\\n`, `\\r`, `\\t`, `\\0` and `\\$`."
+                        .to_string(),
+                ]),
+            UnterminatedCharLiteral(span) => Diagnostic::error()
+                .with_message("unterminated character literal")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    "Each character literal needs to be terminated with a matching `'`.",
+                )]),
+            MultiCharLiteral(span) => Diagnostic::error()
+                .with_message("character literal may only contain one character")
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Use a string literal (`\"...\"`) for more than one character.".to_string(),
+                ]),
+            EmptyCharLiteral(span) => Diagnostic::error()
+                .with_message("empty character literal")
+                .with_labels(vec![Label::primary(span.source.0, span)]),
+            UnterminatedBlockComment(span) => Diagnostic::error()
+                .with_message("unterminated block comment")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    "Each block comment needs to be terminated with a matching `*/`.",
+                )]),
+            EmptyInterpolation(span) => Diagnostic::error()
+                .with_message("empty string interpolation")
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Use `\\$` to include a literal `$` in a string.".to_string()
+                ]),
+            UnterminatedMultilineString(span) => Diagnostic::error()
+                .with_message("unterminated multi-line string")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    "Each multi-line string needs to be terminated with a matching `\"\"\"`.",
+                )]),
+        };
+        diagnostic.with_code(self.error_code())
     }
-}
 
-impl LexError {
-    pub fn json(&self) -> serde_json::Value {
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value {
         use LexError::*;
-        match *self {
+        let mut json = match *self {
             UnknownToken(c, span) => json!({
                 "message": format!("unknown character `{c}` encountered"),
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             UnterminatedString(span) => json!({
                 "message": "unterminated string",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             InvalidInt(span) => json!({
                 "message": "invalid integer literal",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
+            }),
+            InvalidEscapeSequence { escape_char, span } => json!({
+                "message": format!("invalid escape sequence `\\{escape_char}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            UnterminatedCharLiteral(span) => json!({
+                "message": "unterminated character literal",
+                "span": span.json_with_source(source_map),
             }),
+            MultiCharLiteral(span) => json!({
+                "message": "character literal may only contain one character",
+                "span": span.json_with_source(source_map),
+            }),
+            EmptyCharLiteral(span) => json!({
+                "message": "empty character literal",
+                "span": span.json_with_source(source_map),
+            }),
+            UnterminatedBlockComment(span) => json!({
+                "message": "unterminated block comment",
+                "span": span.json_with_source(source_map),
+            }),
+            EmptyInterpolation(span) => json!({
+                "message": "empty string interpolation",
+                "span": span.json_with_source(source_map),
+            }),
+            UnterminatedMultilineString(span) => json!({
+                "message": "unterminated multi-line string",
+                "span": span.json_with_source(source_map),
+            }),
+        };
+        json["code"] = self.error_code().into();
+        json
+    }
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use LexError::*;
+        match self {
+            UnknownToken(c, _) => write!(f, "unknown token `{}`", c),
+            UnterminatedString(_) => write!(f, "unterminated string"),
+            InvalidInt(_) => write!(f, "invalid integer"),
+            InvalidEscapeSequence { escape_char, .. } => {
+                write!(f, "invalid escape sequence `\\{}`", escape_char)
+            }
+            UnterminatedCharLiteral(_) => write!(f, "unterminated character literal"),
+            MultiCharLiteral(_) => write!(f, "character literal may only contain one character"),
+            EmptyCharLiteral(_) => write!(f, "empty character literal"),
+            UnterminatedBlockComment(_) => write!(f, "unterminated block comment"),
+            EmptyInterpolation(_) => write!(f, "empty string interpolation"),
+            UnterminatedMultilineString(_) => write!(f, "unterminated multi-line string"),
         }
     }
 }
 
-#[derive(Debug)]
+impl std::error::Error for LexError {}
+
+impl LexError {
+    /// The stable code identifying this kind of error, see [`error_codes`].
+    pub fn error_code(&self) -> &'static str {
+        use LexError::*;
+        match self {
+            UnknownToken(..) => error_codes::UNKNOWN_TOKEN,
+            UnterminatedString(_) => error_codes::UNTERMINATED_STRING,
+            InvalidInt(_) => error_codes::INVALID_INT,
+            InvalidEscapeSequence { .. } => error_codes::INVALID_ESCAPE_SEQUENCE,
+            UnterminatedCharLiteral(_) => error_codes::UNTERMINATED_CHAR_LITERAL,
+            MultiCharLiteral(_) => error_codes::MULTI_CHAR_LITERAL,
+            EmptyCharLiteral(_) => error_codes::EMPTY_CHAR_LITERAL,
+            UnterminatedBlockComment(_) => error_codes::UNTERMINATED_BLOCK_COMMENT,
+            EmptyInterpolation(_) => error_codes::EMPTY_INTERPOLATION,
+            UnterminatedMultilineString(_) => error_codes::UNTERMINATED_MULTILINE_STRING,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
@@ -151,9 +378,62 @@ impl Token {
     }
 }
 
-pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
-    let source = source.as_bytes();
-    let mut idx = 0;
+/// Comments between two tokens, along with the whitespace separating them.
+#[derive(Debug, Clone)]
+pub struct Comments {
+    /// From the start of the first comment to the end of the last one
+    pub span: Span,
+    pub text: String,
+    /// Whether the comments stand on lines of their own between top-level declarations, rather
+    /// than inside of a declaration or after one on the same line
+    pub is_between_declarations: bool,
+}
+
+/// The comments of the source, which are skipped like whitespace when lexing.
+pub fn comments(source_text: &str) -> Vec<Comments> {
+    let (tokens, _) = lex(FileId(0), source_text);
+    let mut comments = vec![];
+    let mut gap_start = 0;
+    let mut depth = 0;
+    // Declarations end in a semicolon or their closing brace
+    let mut after_declaration = true;
+    for token in tokens.iter().map(Some).chain([None]) {
+        let gap_end = token.map_or(source_text.len(), |token| token.span.start);
+        let gap = &source_text[gap_start..gap_end];
+        if let Some(first) = gap.find(|c: char| !c.is_ascii_whitespace()) {
+            let text = gap[first..].trim_end();
+            comments.push(Comments {
+                span: Span::new(FileId(0), gap_start + first, text.len()),
+                text: text.to_string(),
+                is_between_declarations: after_declaration
+                    && (gap_start == 0 || gap[..first].contains('\n')),
+            });
+        }
+
+        let Some(token) = token else {
+            break;
+        };
+        match token.kind {
+            TokenKind::OBrace => depth += 1,
+            TokenKind::CBrace => depth -= 1,
+            _ => {}
+        }
+        after_declaration =
+            depth == 0 && matches!(token.kind, TokenKind::CBrace | TokenKind::SemiColon);
+        gap_start = token.span.end();
+    }
+    comments
+}
+
+pub fn lex(file_id: FileId, source_text: &str) -> (Vec<Token>, Vec<LexError>) {
+    lex_from(file_id, source_text, 0)
+}
+
+/// Lex `source_text` starting at byte `start`, so the placeholders of interpolated strings get
+/// spans into the surrounding source.
+fn lex_from(file_id: FileId, source_text: &str, start: usize) -> (Vec<Token>, Vec<LexError>) {
+    let source = source_text.as_bytes();
+    let mut idx = start;
 
     let mut tokens = vec![];
     let mut errors = vec![];
@@ -174,8 +454,73 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
             continue;
         }
 
+        // Block comments, which may be nested
+        if source[idx] == b'/' && source.get(idx + 1) == Some(&b'*') {
+            let start = idx;
+            let mut depth = 0;
+
+            while idx < source.len() {
+                if source[idx..].starts_with(b"/*") {
+                    depth += 1;
+                    idx += 2;
+                } else if source[idx..].starts_with(b"*/") {
+                    depth -= 1;
+                    idx += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else {
+                    idx += 1;
+                }
+            }
+
+            if depth > 0 {
+                errors.push(LexError::UnterminatedBlockComment(Span::new(
+                    file_id, start, 2,
+                )));
+            }
+            continue;
+        }
+
+        // Raw string literals, which end at the first `"` followed by as many `#`s as they started
+        // with and don't process escape sequences
+        if source[idx] == b'r' {
+            let hashes = source[idx + 1..]
+                .iter()
+                .take_while(|&&byte| byte == b'#')
+                .count();
+            if source.get(idx + 1 + hashes) == Some(&b'"') {
+                let start = idx;
+                let content_start = idx + hashes + 2;
+                let terminator = format!("\"{}", "#".repeat(hashes));
+
+                let content = match source_text[content_start..].find(&terminator) {
+                    Some(len) => {
+                        idx = content_start + len + terminator.len();
+                        &source_text[content_start..content_start + len]
+                    }
+                    None => {
+                        errors.push(LexError::UnterminatedString(Span::new(
+                            file_id,
+                            start,
+                            source.len() - start,
+                        )));
+                        idx = source.len();
+                        &source_text[content_start..]
+                    }
+                };
+
+                tokens.push(Token::new(
+                    TokenKind::StringLiteral(content.to_string()),
+                    Span::new(file_id, start, idx - start),
+                ));
+
+                continue;
+            }
+        }
+
         // Identifiers & keywords
-        if source[idx].is_ascii_alphabetic() {
+        if source[idx].is_ascii_alphabetic() || source[idx] == b'_' {
             let start = idx;
 
             while idx < source.len() && (source[idx].is_ascii_alphanumeric() || source[idx] == b'_')
@@ -188,19 +533,43 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
             let len = idx - start;
             let kind = match name {
                 "fn" => TokenKind::Fn,
+                "pub" => TokenKind::Pub,
                 "extern" => TokenKind::Extern,
+                "import" => TokenKind::Import,
+                "as" => TokenKind::As,
+                "sizeof" => TokenKind::Sizeof,
+                "offsetof" => TokenKind::Offsetof,
+                "cast" => TokenKind::Cast,
                 "opaque" => TokenKind::Opaque,
                 "struct" => TokenKind::Struct,
+                "enum" => TokenKind::Enum,
+                "union" => TokenKind::Union,
+                "impl" => TokenKind::Impl,
+                "trait" => TokenKind::Trait,
+                "type" => TokenKind::Type,
                 "let" => TokenKind::Let,
+                "const" => TokenKind::Const,
                 "mut" => TokenKind::Mut,
+                "ref" => TokenKind::Ref,
                 "while" => TokenKind::While,
+                "loop" => TokenKind::Loop,
                 "if" => TokenKind::If,
                 "else" => TokenKind::Else,
+                "match" => TokenKind::Match,
                 "for" => TokenKind::For,
                 "in" => TokenKind::In,
                 "true" => TokenKind::True,
                 "false" => TokenKind::False,
+                "null" => TokenKind::Null,
                 "return" => TokenKind::Return,
+                "break" => TokenKind::Break,
+                "continue" => TokenKind::Continue,
+                "defer" => TokenKind::Defer,
+                "unsafe" => TokenKind::Unsafe,
+                "switch" => TokenKind::Switch,
+                "case" => TokenKind::Case,
+                "default" => TokenKind::Default,
+                "_" => TokenKind::Underscore,
                 _ => TokenKind::Ident(name.to_owned()),
             };
             tokens.push(Token::new(kind, Span::new(file_id, start, len)));
@@ -208,13 +577,72 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
             continue;
         }
 
+        // Multi-line string literals, taken verbatim apart from their common indentation
+        if source[idx..].starts_with(b"\"\"\"") {
+            let start = idx;
+            let content_start = idx + 3;
+
+            let content = match source_text[content_start..].find("\"\"\"") {
+                Some(len) => {
+                    idx = content_start + len + 3;
+                    &source_text[content_start..content_start + len]
+                }
+                None => {
+                    errors.push(LexError::UnterminatedMultilineString(Span::new(
+                        file_id, start, 3,
+                    )));
+                    idx = source.len();
+                    &source_text[content_start..]
+                }
+            };
+
+            tokens.push(Token::new(
+                TokenKind::StringLiteral(trim_indent(content)),
+                Span::new(file_id, start, idx - start),
+            ));
+
+            continue;
+        }
+
         // String literals
         if source[idx] == b'"' {
             let start = idx;
             idx += 1; // Consume opening quote
 
+            let mut string = String::new();
+            let mut parts = vec![];
             while idx < source.len() && source[idx] != b'"' {
-                idx += 1;
+                if source[idx..].starts_with(b"${") {
+                    let placeholder_start = idx;
+                    let expr_start = idx + 2;
+                    idx = find_placeholder_end(source, expr_start);
+                    if idx == source.len() {
+                        break;
+                    }
+
+                    let (expr_tokens, mut errs) =
+                        lex_from(file_id, &source_text[..idx], expr_start);
+                    errors.append(&mut errs);
+                    idx += 1; // Consume closing brace
+
+                    if expr_tokens.is_empty() {
+                        errors.push(LexError::EmptyInterpolation(Span::new(
+                            file_id,
+                            placeholder_start,
+                            idx - placeholder_start,
+                        )));
+                        continue;
+                    }
+
+                    if !string.is_empty() {
+                        parts.push(InterpolatedPart::Literal(std::mem::take(&mut string)));
+                    }
+                    parts.push(InterpolatedPart::Expr(expr_tokens));
+                } else if let Some(c) =
+                    lex_literal_char(file_id, source_text, &mut idx, &mut errors)
+                {
+                    string.push(c);
+                }
             }
 
             if idx == source.len() {
@@ -227,24 +655,86 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
                 idx += 1; // Consume closing quote
             }
 
-            if idx == start + 1 {
-                // The string had no content and no closing quote
-                tokens.push(Token::new(
-                    TokenKind::StringLiteral(String::new()),
-                    Span::new(file_id, start, 1),
-                ));
+            let kind = if parts.is_empty() {
+                TokenKind::StringLiteral(string)
             } else {
-                // +1 and -1 on the bounds to exclude quotation marks
-                let string = std::str::from_utf8(&source[(start + 1)..(idx - 1)])
-                    .unwrap()
-                    .to_owned();
+                if !string.is_empty() {
+                    parts.push(InterpolatedPart::Literal(string));
+                }
+                TokenKind::InterpolatedString(parts)
+            };
+            tokens.push(Token::new(kind, Span::new(file_id, start, idx - start)));
 
-                tokens.push(Token::new(
-                    TokenKind::StringLiteral(string),
-                    Span::new(file_id, start, idx - start),
-                ));
+            continue;
+        }
+
+        // Loop labels, a quote followed by an identifier where a label is declared before a `:` or
+        // used after `break` or `continue`. Anywhere else it's an unclosed character literal, which
+        // ends with the identifier so the rest of the line is still lexed.
+        if source[idx] == b'\''
+            && source
+                .get(idx + 1)
+                .is_some_and(|&c| c.is_ascii_alphabetic() || c == b'_')
+        {
+            let name_len = source[idx + 1..]
+                .iter()
+                .take_while(|&&c| c.is_ascii_alphanumeric() || c == b'_')
+                .count();
+            let end = idx + 1 + name_len;
+            let after_jump = tokens
+                .last()
+                .is_some_and(|token| matches!(token.kind, TokenKind::Break | TokenKind::Continue));
+            let name = std::str::from_utf8(&source[idx + 1..end]).unwrap();
+            let span = Span::new(file_id, idx, end - idx);
+            if source.get(end) == Some(&b':') || after_jump {
+                tokens.push(Token::new(TokenKind::Lifetime(name.to_owned()), span));
+                idx = end;
+
+                continue;
+            } else if source.get(end) != Some(&b'\'') {
+                errors.push(LexError::UnterminatedCharLiteral(span));
+                let c = name.chars().next().unwrap();
+                tokens.push(Token::new(TokenKind::CharLiteral(c), span));
+                idx = end;
+
+                continue;
+            }
+        }
+
+        // Character literals
+        if source[idx] == b'\'' {
+            let start = idx;
+            idx += 1; // Consume opening quote
+
+            let mut chars = vec![];
+            while idx < source.len() && source[idx] != b'\'' && source[idx] != b'\n' {
+                if let Some(c) = lex_literal_char(file_id, source_text, &mut idx, &mut errors) {
+                    chars.push(c);
+                }
+            }
+
+            if idx == source.len() || source[idx] == b'\n' {
+                errors.push(LexError::UnterminatedCharLiteral(Span::new(
+                    file_id,
+                    start,
+                    idx - start,
+                )));
+            } else {
+                idx += 1; // Consume closing quote
+
+                let span = Span::new(file_id, start, idx - start);
+                match chars.len() {
+                    0 => errors.push(LexError::EmptyCharLiteral(span)),
+                    1 => {}
+                    _ => errors.push(LexError::MultiCharLiteral(span)),
+                }
             }
 
+            tokens.push(Token::new(
+                TokenKind::CharLiteral(chars.first().copied().unwrap_or('\0')),
+                Span::new(file_id, start, idx - start),
+            ));
+
             continue;
         }
 
@@ -271,9 +761,12 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
             continue;
         }
 
+        // Characters outside of ASCII span several bytes, which are skipped together
         let mut unknown_char = |c: char| {
-            tokens.push(Token::new(TokenKind::Unknown, Span::new(file_id, idx, 1)));
-            errors.push(LexError::UnknownToken(c, Span::new(file_id, idx, 1)));
+            let span = Span::new(file_id, idx, c.len_utf8());
+            tokens.push(Token::new(TokenKind::Unknown, span));
+            errors.push(LexError::UnknownToken(c, span));
+            c.len_utf8() - 1
         };
 
         match source[idx] {
@@ -282,55 +775,141 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
             b'{' => tokens.push(Token::new(TokenKind::OBrace, Span::new(file_id, idx, 1))),
             b'}' => tokens.push(Token::new(TokenKind::CBrace, Span::new(file_id, idx, 1))),
             b'[' => tokens.push(Token::new(TokenKind::OBracket, Span::new(file_id, idx, 1))),
+            b'#' => tokens.push(Token::new(TokenKind::Hash, Span::new(file_id, idx, 1))),
+            b'?' => tokens.push(Token::new(TokenKind::Question, Span::new(file_id, idx, 1))),
+            b'^' => tokens.push(Token::new(TokenKind::Caret, Span::new(file_id, idx, 1))),
+            b'~' => tokens.push(Token::new(TokenKind::Tilde, Span::new(file_id, idx, 1))),
             b']' => tokens.push(Token::new(TokenKind::CBracket, Span::new(file_id, idx, 1))),
             b';' => tokens.push(Token::new(TokenKind::SemiColon, Span::new(file_id, idx, 1))),
             b',' => tokens.push(Token::new(TokenKind::Comma, Span::new(file_id, idx, 1))),
-            b':' => tokens.push(Token::new(TokenKind::Colon, Span::new(file_id, idx, 1))),
+            b':' => match source.get(idx + 1) {
+                Some(b':') => {
+                    idx += 1;
+                    tokens.push(Token::new(
+                        TokenKind::ColonColon,
+                        Span::new(file_id, idx - 1, 2),
+                    ))
+                }
+                _ => tokens.push(Token::new(TokenKind::Colon, Span::new(file_id, idx, 1))),
+            },
+            b'+' | b'*' | b'/' if source.get(idx + 1) == Some(&b'=') => {
+                let kind = match source[idx] {
+                    b'+' => TokenKind::PlusEqual,
+                    b'*' => TokenKind::StarEqual,
+                    _ => TokenKind::SlashEqual,
+                };
+                idx += 1;
+                tokens.push(Token::new(kind, Span::new(file_id, idx - 1, 2)))
+            }
             b'+' => tokens.push(Token::new(TokenKind::Plus, Span::new(file_id, idx, 1))),
             b'*' => tokens.push(Token::new(TokenKind::Star, Span::new(file_id, idx, 1))),
             b'/' => tokens.push(Token::new(TokenKind::Slash, Span::new(file_id, idx, 1))),
+            b'%' => tokens.push(Token::new(TokenKind::Percent, Span::new(file_id, idx, 1))),
             b'=' => {
                 let token = match source.get(idx + 1) {
                     Some(b'=') => {
                         idx += 1;
                         Token::new(TokenKind::EqualEqual, Span::new(file_id, idx - 1, 2))
                     }
-                    _ => Token::new(TokenKind::Equal, Span::new(file_id, idx, 2)),
+                    Some(b'>') => {
+                        idx += 1;
+                        Token::new(TokenKind::FatArrow, Span::new(file_id, idx - 1, 2))
+                    }
+                    _ => Token::new(TokenKind::Equal, Span::new(file_id, idx, 1)),
                 };
                 tokens.push(token);
             }
+            b'!' => match source.get(idx + 1) {
+                Some(b'=') => {
+                    idx += 1;
+                    tokens.push(Token::new(
+                        TokenKind::BangEqual,
+                        Span::new(file_id, idx - 1, 2),
+                    ))
+                }
+                _ => tokens.push(Token::new(TokenKind::Bang, Span::new(file_id, idx, 1))),
+            },
+            b'&' => match source.get(idx + 1) {
+                Some(b'&') => {
+                    idx += 1;
+                    tokens.push(Token::new(
+                        TokenKind::AmpAmp,
+                        Span::new(file_id, idx - 1, 2),
+                    ))
+                }
+                _ => tokens.push(Token::new(TokenKind::Ampersand, Span::new(file_id, idx, 1))),
+            },
+            b'|' => match source.get(idx + 1) {
+                Some(b'|') => {
+                    idx += 1;
+                    tokens.push(Token::new(
+                        TokenKind::PipePipe,
+                        Span::new(file_id, idx - 1, 2),
+                    ))
+                }
+                _ => tokens.push(Token::new(TokenKind::Pipe, Span::new(file_id, idx, 1))),
+            },
             b'<' => {
                 let token = match source.get(idx + 1) {
+                    Some(b'<') => {
+                        idx += 1;
+                        Token::new(TokenKind::LessLess, Span::new(file_id, idx - 1, 2))
+                    }
                     Some(b'=') => {
                         idx += 1;
                         Token::new(TokenKind::LessThanEqual, Span::new(file_id, idx - 1, 2))
                     }
-                    _ => Token::new(TokenKind::LessThan, Span::new(file_id, idx, 2)),
+                    _ => Token::new(TokenKind::LessThan, Span::new(file_id, idx, 1)),
                 };
                 tokens.push(token);
             }
             b'>' => {
                 let token = match source.get(idx + 1) {
+                    Some(b'>') => {
+                        idx += 1;
+                        Token::new(TokenKind::GreaterGreater, Span::new(file_id, idx - 1, 2))
+                    }
                     Some(b'=') => {
                         idx += 1;
                         Token::new(TokenKind::GreaterThanEqual, Span::new(file_id, idx - 1, 2))
                     }
-                    _ => Token::new(TokenKind::GreaterThan, Span::new(file_id, idx, 2)),
+                    _ => Token::new(TokenKind::GreaterThan, Span::new(file_id, idx, 1)),
+                };
+                tokens.push(token);
+            }
+            b'.' => {
+                let token = match (source.get(idx + 1), source.get(idx + 2)) {
+                    (Some(b'.'), Some(b'.')) => {
+                        idx += 2;
+                        Token::new(TokenKind::DotDotDot, Span::new(file_id, idx - 2, 3))
+                    }
+                    (Some(b'.'), Some(b'=')) => {
+                        idx += 2;
+                        Token::new(TokenKind::DotDotEqual, Span::new(file_id, idx - 2, 3))
+                    }
+                    (Some(b'.'), _) => {
+                        idx += 1;
+                        Token::new(TokenKind::DotDot, Span::new(file_id, idx - 1, 2))
+                    }
+                    _ => Token::new(TokenKind::Dot, Span::new(file_id, idx, 1)),
                 };
                 tokens.push(token);
             }
-            b'.' => tokens.push(Token::new(TokenKind::Dot, Span::new(file_id, idx, 1))),
             b'-' => {
                 let token = match source.get(idx + 1) {
                     Some(b'>') => {
                         idx += 1;
                         Token::new(TokenKind::RightArrow, Span::new(file_id, idx - 1, 2))
                     }
+                    Some(b'=') => {
+                        idx += 1;
+                        Token::new(TokenKind::MinusEqual, Span::new(file_id, idx - 1, 2))
+                    }
                     _ => Token::new(TokenKind::Minus, Span::new(file_id, idx, 1)),
                 };
                 tokens.push(token);
             }
-            e => unknown_char(e as char),
+            _ => idx += unknown_char(source_text[idx..].chars().next().unwrap()),
         }
 
         idx += 1;
@@ -338,3 +917,97 @@ pub fn lex(file_id: FileId, source: &str) -> (Vec<Token>, Vec<LexError>) {
 
     (tokens, errors)
 }
+
+/// Remove the indentation common to all non-blank lines of a multi-line string, along with a
+/// blank first and last line, so the string can be indented along with the surrounding code.
+fn trim_indent(content: &str) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    if content.ends_with('\n') {
+        lines.push("");
+    }
+    if lines.first().is_some_and(|line| line.trim().is_empty()) {
+        lines.remove(0);
+    }
+    if lines.last().is_some_and(|line| line.trim().is_empty()) {
+        lines.pop();
+    }
+
+    let indent = lines
+        .iter()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start().len())
+        .min()
+        .unwrap_or(0);
+
+    lines
+        .iter()
+        .map(|line| {
+            let line_indent = line.len() - line.trim_start().len();
+            &line[indent.min(line_indent)..]
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Find the `}` closing the placeholder whose expression starts at `idx`, skipping over nested
+/// braces and the contents of string and character literals. Returns the length of the source
+/// if the placeholder is never closed.
+fn find_placeholder_end(source: &[u8], mut idx: usize) -> usize {
+    let mut depth = 0;
+    while idx < source.len() {
+        match source[idx] {
+            b'{' => depth += 1,
+            b'}' if depth == 0 => return idx,
+            b'}' => depth -= 1,
+            quote @ (b'"' | b'\'') => {
+                idx += 1;
+                while idx < source.len() && source[idx] != quote {
+                    if source[idx] == b'\\' {
+                        idx += 1;
+                    }
+                    idx += 1;
+                }
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    source.len()
+}
+
+/// Lex a single character inside of a string or character literal, decoding
+/// escape sequences. Returns `None` for invalid escape sequences.
+fn lex_literal_char(
+    file_id: FileId,
+    source_text: &str,
+    idx: &mut usize,
+    errors: &mut Vec<LexError>,
+) -> Option<char> {
+    let c = source_text[*idx..].chars().next().unwrap();
+    if c != '\\' || *idx + 1 == source_text.len() {
+        *idx += c.len_utf8();
+        return Some(c);
+    }
+
+    let escape_char = source_text[(*idx + 1)..].chars().next().unwrap();
+    let unescaped = match escape_char {
+        '\\' => Some('\\'),
+        '"' => Some('"'),
+        '\'' => Some('\''),
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        '0' => Some('\0'),
+        '$' => Some('$'),
+        _ => {
+            errors.push(LexError::InvalidEscapeSequence {
+                escape_char,
+                span: Span::new(file_id, *idx, 1 + escape_char.len_utf8()),
+            });
+            None
+        }
+    };
+    *idx += 1 + escape_char.len_utf8();
+
+    unescaped
+}
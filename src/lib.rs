@@ -2,7 +2,17 @@
 #![feature(concat_bytes)]
 
 pub mod codegen;
+pub mod diagnostic;
+pub mod error_codes;
+pub mod fold;
+pub mod inline;
 pub mod lexer;
+pub mod lints;
+pub mod loader;
 pub mod parser;
+pub mod pretty_print;
+pub mod source_map;
 pub mod span;
 pub mod typechecker;
+pub mod visitor;
+pub mod vm;
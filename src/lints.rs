@@ -0,0 +1,268 @@
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use serde_json::json;
+
+use crate::{
+    diagnostic::IntoDiagnostic,
+    parser::{
+        FunctionParameter, ParsedBlock, ParsedExpression, ParsedExternFunction, ParsedFunction,
+        ParsedMatchArm, ParsedPattern, ParsedProgram, ParsedStatement,
+    },
+    source_map::SourceMap,
+    span::Span,
+    typechecker::KNOWN_CALLING_CONVENTIONS,
+    visitor::{self, Visitor},
+};
+
+#[derive(Debug)]
+pub enum Warning {
+    UnusedVariable { name: String, span: Span },
+    UnnecessaryMut { name: String, span: Span },
+    UnknownCallingConvention { name: String, span: Span },
+}
+
+impl IntoDiagnostic for Warning {
+    fn report(&self) -> Diagnostic<usize> {
+        match self {
+            Self::UnusedVariable { name, span } => Diagnostic::warning()
+                .with_message(format!("unused variable `{name}`"))
+                .with_labels(vec![Label::primary(span.source.0, *span)
+                    .with_message("this variable is never read")])
+                .with_notes(vec![format!(
+                    "if this is intentional, prefix it with an underscore: `_{name}`"
+                )]),
+            Self::UnnecessaryMut { name, span } => Diagnostic::warning()
+                .with_message(format!("variable `{name}` does not need to be mutable"))
+                .with_labels(vec![Label::primary(span.source.0, *span)
+                    .with_message("this variable is never mutated, remove the `mut`")]),
+            Self::UnknownCallingConvention { name, span } => Diagnostic::warning()
+                .with_message(format!("unknown calling convention `{name}`"))
+                .with_labels(vec![Label::primary(span.source.0, *span)
+                    .with_message("the C calling convention is used instead")])
+                .with_notes(vec![format!(
+                    "known calling conventions are {}",
+                    KNOWN_CALLING_CONVENTIONS
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )]),
+        }
+    }
+
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value {
+        match self {
+            Self::UnusedVariable { name, span } => json!({
+                "message": format!("unused variable `{name}`"),
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnnecessaryMut { name, span } => json!({
+                "message": format!("variable `{name}` does not need to be mutable"),
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownCallingConvention { name, span } => json!({
+                "message": format!("unknown calling convention `{name}`"),
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+        }
+    }
+
+    fn is_warning(&self) -> bool {
+        true
+    }
+}
+
+#[derive(Debug)]
+struct Binding {
+    name: String,
+    span: Span,
+    /// Only `let` bindings are reported, parameters and loop variables are tracked for shadowing.
+    is_let: bool,
+    is_mut: bool,
+    is_read: bool,
+    is_mutated: bool,
+}
+
+/// Finds `let` bindings that are never read and `let mut` bindings that are never assigned to or
+/// borrowed mutably. Variables whose name starts with an underscore are not reported.
+#[derive(Debug, Default)]
+pub struct UnusedVariables {
+    scopes: Vec<Vec<Binding>>,
+    pub warnings: Vec<Warning>,
+}
+
+impl UnusedVariables {
+    fn declare(&mut self, name: &str, span: Span, is_let: bool, is_mut: bool) {
+        self.scopes.last_mut().unwrap().push(Binding {
+            name: name.to_string(),
+            span,
+            is_let,
+            is_mut,
+            is_read: false,
+            is_mutated: false,
+        });
+    }
+
+    /// The binding `name` refers to at this point, the latest one in the innermost scope.
+    fn lookup(&mut self, name: &str) -> Option<&mut Binding> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .flat_map(|scope| scope.iter_mut().rev())
+            .find(|binding| binding.name == name)
+    }
+
+    fn push_scope(&mut self) {
+        self.scopes.push(vec![]);
+    }
+
+    fn pop_scope(&mut self) {
+        for binding in self.scopes.pop().unwrap() {
+            if !binding.is_let || binding.name.starts_with('_') {
+                continue;
+            }
+
+            if !binding.is_read {
+                self.warnings.push(Warning::UnusedVariable {
+                    name: binding.name,
+                    span: binding.span,
+                });
+            } else if binding.is_mut && !binding.is_mutated {
+                self.warnings.push(Warning::UnnecessaryMut {
+                    name: binding.name,
+                    span: binding.span,
+                });
+            }
+        }
+    }
+
+    /// Marks the variable at the root of a place expression like `a.b[0].1` as mutated.
+    fn mark_mutated(&mut self, place: &ParsedExpression) {
+        match place {
+            ParsedExpression::Variable(name, _) => {
+                if let Some(binding) = self.lookup(name) {
+                    binding.is_mutated = true;
+                }
+            }
+            ParsedExpression::FieldAccess(field_access) => self.mark_mutated(&field_access.object),
+            ParsedExpression::TupleIndex(tuple_index) => self.mark_mutated(&tuple_index.object),
+            ParsedExpression::ArrayIndex(array_index) => self.mark_mutated(&array_index.array),
+            ParsedExpression::Parenthesized(inner, _) => self.mark_mutated(inner),
+            _ => {}
+        }
+    }
+}
+
+impl Visitor for UnusedVariables {
+    // Extern functions have no body for their parameters to be used in
+    fn visit_extern_function(&mut self, _func: &ParsedExternFunction) {}
+
+    fn visit_function(&mut self, func: &ParsedFunction) {
+        self.push_scope();
+        visitor::walk_function(self, func);
+        self.pop_scope();
+    }
+
+    fn visit_parameter(&mut self, param: &FunctionParameter) {
+        self.declare(&param.name, param.name_span, false, false);
+        visitor::walk_parameter(self, param);
+    }
+
+    fn visit_block(&mut self, block: &ParsedBlock) {
+        self.push_scope();
+        visitor::walk_block(self, block);
+        self.pop_scope();
+    }
+
+    fn visit_statement(&mut self, statement: &ParsedStatement) {
+        match statement {
+            ParsedStatement::LetAssign(let_assign) => {
+                // The value is visited first, it still sees the bindings `let` may shadow
+                visitor::walk_statement(self, statement);
+                for (name, span) in let_assign.pattern.bindings() {
+                    self.declare(name, span, true, let_assign.is_mut);
+                }
+            }
+            ParsedStatement::ForInLoop(for_in) => {
+                self.visit_expression(&for_in.iterable_value);
+                self.push_scope();
+                self.declare(
+                    &for_in.elem_var_name,
+                    for_in.elem_var_name_span,
+                    false,
+                    false,
+                );
+                if let Some((ref index_var_name, index_var_span)) = for_in.index_var {
+                    self.declare(index_var_name, index_var_span, false, false);
+                }
+                self.visit_block(&for_in.body);
+                self.pop_scope();
+            }
+            _ => visitor::walk_statement(self, statement),
+        }
+    }
+
+    fn visit_match_arm(&mut self, arm: &ParsedMatchArm) {
+        self.push_scope();
+        if let ParsedPattern::EnumVariant(ref pattern) = arm.pattern {
+            if let Some((ref name, span)) = pattern.binding {
+                self.declare(name, span, false, false);
+            }
+        }
+        visitor::walk_match_arm(self, arm);
+        self.pop_scope();
+    }
+
+    fn visit_expression(&mut self, expression: &ParsedExpression) {
+        match expression {
+            ParsedExpression::Variable(name, _) => {
+                if let Some(binding) = self.lookup(name) {
+                    binding.is_read = true;
+                }
+            }
+            ParsedExpression::FunctionCall(func_call) => {
+                // A call through a function pointer variable reads it
+                if let Some(binding) = self.lookup(&func_call.name) {
+                    binding.is_read = true;
+                }
+                visitor::walk_expression(self, expression);
+            }
+            ParsedExpression::Assignment(lhs, rhs) => {
+                self.mark_mutated(lhs);
+                // Assigning to a variable is not a read of it, assigning through it is
+                if !matches!(**lhs, ParsedExpression::Variable(..)) {
+                    self.visit_expression(lhs);
+                }
+                self.visit_expression(rhs);
+            }
+            ParsedExpression::PointerTo(pointer_to) => {
+                if pointer_to.is_mut {
+                    self.mark_mutated(&pointer_to.inner);
+                }
+                visitor::walk_expression(self, expression);
+            }
+            ParsedExpression::MethodCall(method_call) => {
+                // The receiver might be taken as `->mut self`, which isn't known before typechecking
+                self.mark_mutated(&method_call.object);
+                visitor::walk_expression(self, expression);
+            }
+            _ => visitor::walk_expression(self, expression),
+        }
+    }
+}
+
+/// Extern functions declared with a calling convention the compiler doesn't know.
+pub fn unknown_calling_conventions(program: &ParsedProgram) -> Vec<Warning> {
+    program
+        .extern_functions
+        .iter()
+        .filter_map(|func| func.calling_convention.as_ref())
+        .filter(|(name, _)| !KNOWN_CALLING_CONVENTIONS.contains(&name.as_str()))
+        .map(|(name, span)| Warning::UnknownCallingConvention {
+            name: name.clone(),
+            span: *span,
+        })
+        .collect()
+}
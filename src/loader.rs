@@ -0,0 +1,180 @@
+//! Loading a program from its root file along with every file it imports.
+
+use std::path::{Path, PathBuf};
+
+use codespan_reporting::diagnostic::{Diagnostic, Label};
+use serde_json::json;
+
+use crate::{
+    diagnostic::IntoDiagnostic,
+    error_codes,
+    lexer::{self, LexError},
+    parser::{self, ParseError, ParsedImport, ParsedProgram},
+    source_map::SourceMap,
+    span::{FileId, Span},
+};
+
+pub enum CompileError {
+    Lex(LexError),
+    Parse(ParseError),
+    ImportNotFound(String, Span),
+    CircularImport(String, Span),
+}
+
+impl IntoDiagnostic for CompileError {
+    fn report(&self) -> Diagnostic<usize> {
+        match *self {
+            Self::Lex(ref e) => e.report(),
+            Self::Parse(ref e) => e.report(),
+            Self::ImportNotFound(ref path, span) => Diagnostic::error()
+                .with_message(format!("cannot find imported file `{path}`"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Imports are relative to the directory of the importing file.".to_string(),
+                ])
+                .with_code(error_codes::IMPORT_NOT_FOUND),
+            Self::CircularImport(ref path, span) => Diagnostic::error()
+                .with_message(format!("circular import of `{path}`"))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("file is already being imported")])
+                .with_notes(vec![
+                    "A file can't import itself, neither directly nor through other imports."
+                        .to_string(),
+                ])
+                .with_code(error_codes::CIRCULAR_IMPORT),
+        }
+    }
+
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value {
+        match *self {
+            Self::Lex(ref e) => e.json(source_map),
+            Self::Parse(ref e) => e.json(source_map),
+            Self::ImportNotFound(ref path, span) => json!({
+                "message": format!("cannot find imported file `{path}`"),
+                "span": span.json_with_source(source_map),
+                "code": error_codes::IMPORT_NOT_FOUND,
+            }),
+            Self::CircularImport(ref path, span) => json!({
+                "message": format!("circular import of `{path}`"),
+                "span": span.json_with_source(source_map),
+                "code": error_codes::CIRCULAR_IMPORT,
+            }),
+        }
+    }
+}
+
+/// Lexes and parses the file and everything it imports, merging all declarations into a single
+/// program. Every file is only loaded once, no matter how often it is imported.
+pub fn load_program(
+    source_map: &mut SourceMap,
+    file_id: FileId,
+) -> (ParsedProgram, Vec<CompileError>) {
+    let mut loader = Loader {
+        source_map,
+        import_stack: vec![],
+        loaded: vec![],
+        errors: vec![],
+    };
+    let program = loader.load_file(file_id);
+    (program, loader.errors)
+}
+
+/// Lexes and parses only the file itself, leaving its imports unresolved.
+pub fn parse_file(source_map: &SourceMap, file_id: FileId) -> (ParsedProgram, Vec<CompileError>) {
+    let (tokens, lex_errors) = lexer::lex(file_id, source_map.source(file_id));
+    let (program, parse_errors) = parser::parse_program(&tokens, &mut 0);
+
+    let errors = lex_errors
+        .into_iter()
+        .map(CompileError::Lex)
+        .chain(parse_errors.into_iter().map(CompileError::Parse))
+        .collect();
+    (program, errors)
+}
+
+struct Loader<'a> {
+    source_map: &'a mut SourceMap,
+    /// The files currently being loaded, each one imported by the one before it.
+    import_stack: Vec<PathBuf>,
+    loaded: Vec<PathBuf>,
+    errors: Vec<CompileError>,
+}
+
+impl Loader<'_> {
+    fn load_file(&mut self, file_id: FileId) -> ParsedProgram {
+        let path = canonicalize(self.source_map.path(file_id));
+        self.import_stack.push(path.clone());
+        self.loaded.push(path);
+
+        let (mut program, mut errors) = parse_file(self.source_map, file_id);
+        self.errors.append(&mut errors);
+
+        let imported: Vec<_> = program
+            .imports
+            .iter()
+            .filter_map(|import| self.load_import(file_id, import))
+            .collect();
+        for imported in imported {
+            merge(&mut program, imported);
+        }
+
+        self.import_stack.pop();
+        program
+    }
+
+    fn load_import(&mut self, importer: FileId, import: &ParsedImport) -> Option<ParsedProgram> {
+        let importer_dir = self
+            .source_map
+            .path(importer)
+            .parent()
+            .unwrap_or_else(|| Path::new(""));
+        let mut path = importer_dir.join(&import.path);
+        if path.extension().is_none() {
+            path.set_extension("clara");
+        }
+
+        let canonical_path = canonicalize(&path);
+        if self.import_stack.contains(&canonical_path) {
+            self.errors.push(CompileError::CircularImport(
+                import.path.clone(),
+                import.path_span,
+            ));
+            return None;
+        }
+        if self.loaded.contains(&canonical_path) {
+            return None;
+        }
+
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(_) => {
+                self.errors.push(CompileError::ImportNotFound(
+                    import.path.clone(),
+                    import.path_span,
+                ));
+                return None;
+            }
+        };
+
+        let file_id = self.source_map.add_file(path, source);
+        Some(self.load_file(file_id))
+    }
+}
+
+/// Files are told apart by their canonical path, paths that can't be canonicalized (like `-` for
+/// stdin) are used as is.
+fn canonicalize(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn merge(program: &mut ParsedProgram, imported: ParsedProgram) {
+    program.consts.extend(imported.consts);
+    program.type_aliases.extend(imported.type_aliases);
+    program.structs.extend(imported.structs);
+    program.enums.extend(imported.enums);
+    program.unions.extend(imported.unions);
+    program.impls.extend(imported.impls);
+    program.traits.extend(imported.traits);
+    program.extern_functions.extend(imported.extern_functions);
+    program.functions.extend(imported.functions);
+}
@@ -0,0 +1,528 @@
+use std::{
+    collections::HashMap,
+    io::{self, BufRead, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+use clara::{
+    diagnostic::IntoDiagnostic,
+    lexer::{self, Token, TokenKind},
+    lints, loader,
+    parser::{FunctionParameter, ParsedExternFunction, ParsedFunction, ParsedStatement},
+    source_map::SourceMap,
+    span::{FileId, Span, Spanned},
+    typechecker::{self, Type},
+    visitor::{self, Visitor},
+};
+use codespan_reporting::diagnostic::{LabelStyle, Severity};
+use serde_json::{json, Value};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// Serves the Language Server Protocol over `input` and `output`. Documents are synced in full,
+/// on every change they are compiled up to typechecking to publish their diagnostics. Hovering an
+/// expression shows its type and functions and variables can be jumped to from their uses.
+///
+/// The JSON-RPC messages are read and written here rather than through `tower-lsp`, which isn't a
+/// dependency of the compiler and would bring an async runtime along for these few requests.
+pub fn run(mut input: impl BufRead, output: &mut impl Write) -> io::Result<()> {
+    let mut documents: HashMap<String, String> = HashMap::new();
+
+    while let Some(message) = read_message(&mut input)? {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "clara" },
+            }),
+            "shutdown" => Value::Null,
+            "exit" => return Ok(()),
+            "textDocument/didOpen" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                documents.insert(uri.to_string(), text.to_string());
+                publish_diagnostics(output, uri, &Analysis::new(uri, text))?;
+                continue;
+            }
+            "textDocument/didChange" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                // Documents are synced in full, so the last change holds the whole text
+                let changes = params["contentChanges"].as_array();
+                if let Some(text) = changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    documents.insert(uri.to_string(), text.to_string());
+                    publish_diagnostics(output, uri, &Analysis::new(uri, text))?;
+                }
+                continue;
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                documents.remove(uri);
+                send(
+                    output,
+                    &json!({
+                        "jsonrpc": "2.0",
+                        "method": "textDocument/publishDiagnostics",
+                        "params": { "uri": uri, "diagnostics": [] },
+                    }),
+                )?;
+                continue;
+            }
+            "textDocument/hover" | "textDocument/definition" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                match documents.get(uri) {
+                    Some(text) => {
+                        let analysis = Analysis::new(uri, text);
+                        let offset = position_to_offset(text, &params["position"]);
+                        if method == "textDocument/hover" {
+                            analysis.hover(offset)
+                        } else {
+                            analysis.definition(offset)
+                        }
+                    }
+                    None => Value::Null,
+                }
+            }
+            _ => {
+                // Unknown notifications are ignored, unknown requests have to be answered
+                if let Some(id) = message.get("id") {
+                    send(
+                        output,
+                        &json!({
+                            "jsonrpc": "2.0",
+                            "id": id,
+                            "error": {
+                                "code": METHOD_NOT_FOUND,
+                                "message": format!("unsupported method `{method}`"),
+                            },
+                        }),
+                    )?;
+                }
+                continue;
+            }
+        };
+
+        send(
+            output,
+            &json!({ "jsonrpc": "2.0", "id": message["id"], "result": result }),
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Reads the next message, returning `None` once the input ends.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut content_length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(length) = header.strip_prefix("Content-Length:") {
+            content_length = length.trim().parse().ok();
+        }
+    }
+
+    let content_length = content_length.ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length")
+    })?;
+    let mut content = vec![0; content_length];
+    input.read_exact(&mut content)?;
+    serde_json::from_slice(&content)
+        .map(Some)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn send(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let content = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{content}", content.len())?;
+    output.flush()
+}
+
+fn publish_diagnostics(output: &mut impl Write, uri: &str, analysis: &Analysis) -> io::Result<()> {
+    send(
+        output,
+        &json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": analysis.diagnostics },
+        }),
+    )
+}
+
+/// A document compiled together with the files it imports, which are read from disk.
+struct Analysis {
+    uri: String,
+    source_map: SourceMap,
+    file_id: FileId,
+    diagnostics: Vec<Value>,
+    definitions: Vec<Definition>,
+    /// Empty if the program didn't parse, it isn't typechecked then
+    expression_types: Vec<(Span, Type)>,
+}
+
+impl Analysis {
+    fn new(uri: &str, text: &str) -> Self {
+        let mut source_map = SourceMap::default();
+        let file_id = source_map.add_file(uri_to_path(uri), text.to_string());
+        let (program, load_errors) = loader::load_program(&mut source_map, file_id);
+
+        let mut analysis = Self {
+            uri: uri.to_string(),
+            source_map,
+            file_id,
+            diagnostics: vec![],
+            definitions: vec![],
+            expression_types: vec![],
+        };
+        analysis.add_diagnostics(&load_errors);
+        analysis.add_diagnostics(&lints::unknown_calling_conventions(&program));
+
+        let mut definitions = Definitions::new(file_id, text);
+        definitions.visit_program(&program);
+        analysis.definitions = definitions.definitions;
+
+        if load_errors.is_empty() {
+            let (checked_program, typecheck_errors) = typechecker::typecheck_program(&program);
+            analysis.add_diagnostics(&typecheck_errors);
+            analysis.expression_types = checked_program.expression_types;
+        }
+
+        analysis
+    }
+
+    /// Adds the errors located in the document, errors in imported files are left out.
+    fn add_diagnostics(&mut self, errors: &[impl IntoDiagnostic]) {
+        let source = self.source_map.source(self.file_id);
+        for error in errors {
+            let diagnostic = error.report();
+            let primary_label = diagnostic
+                .labels
+                .iter()
+                .find(|label| label.style == LabelStyle::Primary);
+            let label = match primary_label {
+                Some(label) if label.file_id == self.file_id.0 => label,
+                _ => continue,
+            };
+
+            let mut message = diagnostic.message.clone();
+            if !label.message.is_empty() {
+                message = format!("{message}\n{}", label.message);
+            }
+            self.diagnostics.push(json!({
+                "range": range(source, label.range.start, label.range.end),
+                "severity": if diagnostic.severity == Severity::Warning { 2 } else { 1 },
+                "source": "clara",
+                "message": message,
+            }));
+        }
+    }
+
+    /// The type of the innermost expression at `offset`.
+    fn hover(&self, offset: usize) -> Value {
+        let expression_type = self
+            .expression_types
+            .iter()
+            .filter(|(span, _)| span.source == self.file_id && contains(*span, offset))
+            .min_by_key(|(span, _)| span.len);
+        match expression_type {
+            Some((span, ttype)) => json!({
+                "contents": { "kind": "plaintext", "value": ttype.to_string() },
+                "range": range(self.source_map.source(self.file_id), span.start, span.end()),
+            }),
+            None => Value::Null,
+        }
+    }
+
+    /// Where the function or variable named by the identifier at `offset` is declared. Variables
+    /// are looked up among the declarations whose scope contains `offset`, the innermost one
+    /// shadowing the others, functions in all loaded files.
+    fn definition(&self, offset: usize) -> Value {
+        let source = self.source_map.source(self.file_id);
+        let (tokens, _) = lexer::lex(self.file_id, source);
+        let name = tokens.iter().find_map(|token| match &token.kind {
+            TokenKind::Ident(name) if contains(token.span, offset) => Some(name),
+            _ => None,
+        });
+        let name = match name {
+            Some(name) => name,
+            None => return Value::Null,
+        };
+
+        // Scopes nest, so the innermost one containing `offset` starts last
+        let local = self
+            .definitions
+            .iter()
+            .filter(|definition| {
+                &definition.name == name
+                    && definition.span.source == self.file_id
+                    && (contains(definition.span, offset)
+                        || definition
+                            .scope
+                            .as_ref()
+                            .is_some_and(|scope| scope.contains(&offset)))
+            })
+            .max_by_key(|definition| definition.span.start);
+        let definition = local.or_else(|| {
+            self.definitions.iter().find(|definition| {
+                definition.kind != DefinitionKind::Local && &definition.name == name
+            })
+        });
+
+        match definition {
+            Some(definition) => {
+                let uri = if definition.span.source == self.file_id {
+                    self.uri.clone()
+                } else {
+                    path_to_uri(self.source_map.path(definition.span.source))
+                };
+                json!({
+                    "uri": uri,
+                    "range": range(
+                        self.source_map.source(definition.span.source),
+                        definition.span.start,
+                        definition.span.end(),
+                    ),
+                })
+            }
+            None => Value::Null,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum DefinitionKind {
+    Function,
+    ExternFunction,
+    /// Parameters, `let` bindings and the variables of `for` loops
+    Local,
+}
+
+#[derive(Debug)]
+struct Definition {
+    name: String,
+    /// The span of the declared name
+    span: Span,
+    kind: DefinitionKind,
+    /// The bytes of the document the variable can be referred to in, `None` for functions and
+    /// variables of imported files
+    scope: Option<Range<usize>>,
+}
+
+/// Collects the declarations of functions and variables.
+#[derive(Debug)]
+struct Definitions {
+    file_id: FileId,
+    /// Tokens of the document, the braces among them delimit the scopes of its variables
+    tokens: Vec<Token>,
+    definitions: Vec<Definition>,
+}
+
+impl Definitions {
+    fn new(file_id: FileId, text: &str) -> Self {
+        Self {
+            file_id,
+            tokens: lexer::lex(file_id, text).0,
+            definitions: vec![],
+        }
+    }
+
+    fn add(&mut self, name: &str, span: Span, kind: DefinitionKind) {
+        self.definitions.push(Definition {
+            name: name.to_string(),
+            span,
+            kind,
+            scope: None,
+        });
+    }
+
+    fn add_local(&mut self, name: &str, span: Span, scope: Option<Range<usize>>) {
+        self.definitions.push(Definition {
+            name: name.to_string(),
+            span,
+            kind: DefinitionKind::Local,
+            scope,
+        });
+    }
+
+    /// Adds a variable visible in the block following `from`, like a parameter in the body of its
+    /// function.
+    fn add_block_local(&mut self, name: &str, span: Span, from: usize) {
+        let scope = (span.source == self.file_id).then(|| {
+            let mut depth = 0;
+            let end = self.tokens_from(from).find_map(|token| match token.kind {
+                TokenKind::OBrace => {
+                    depth += 1;
+                    None
+                }
+                TokenKind::CBrace => {
+                    depth -= 1;
+                    (depth == 0).then_some(token.span.start)
+                }
+                _ => None,
+            });
+            from..end.unwrap_or(usize::MAX)
+        });
+        self.add_local(name, span, scope);
+    }
+
+    /// Adds a variable bound by a `let`, visible after the statement up to the end of the block
+    /// containing it. Its own value still refers to the variables it shadows.
+    fn add_let(&mut self, name: &str, span: Span) {
+        let scope = (span.source == self.file_id).then(|| {
+            let mut depth = 0;
+            let mut start = None;
+            let end = self
+                .tokens_from(span.end())
+                .find_map(|token| match token.kind {
+                    TokenKind::SemiColon if depth == 0 && start.is_none() => {
+                        start = Some(token.span.end());
+                        None
+                    }
+                    TokenKind::OBrace => {
+                        depth += 1;
+                        None
+                    }
+                    TokenKind::CBrace if depth == 0 => Some(token.span.start),
+                    TokenKind::CBrace => {
+                        depth -= 1;
+                        None
+                    }
+                    _ => None,
+                });
+            let end = end.unwrap_or(usize::MAX);
+            start.unwrap_or(end)..end
+        });
+        self.add_local(name, span, scope);
+    }
+
+    fn tokens_from(&self, offset: usize) -> impl Iterator<Item = &Token> {
+        self.tokens
+            .iter()
+            .skip_while(move |token| token.span.start < offset)
+    }
+}
+
+impl Visitor for Definitions {
+    fn visit_function(&mut self, func: &ParsedFunction) {
+        self.add(&func.name, func.name_span, DefinitionKind::Function);
+        visitor::walk_function(self, func);
+    }
+
+    fn visit_extern_function(&mut self, func: &ParsedExternFunction) {
+        self.add(&func.name, func.name_span, DefinitionKind::ExternFunction);
+    }
+
+    fn visit_parameter(&mut self, param: &FunctionParameter) {
+        self.add_block_local(&param.name, param.name_span, param.name_span.end());
+    }
+
+    fn visit_statement(&mut self, statement: &ParsedStatement) {
+        match statement {
+            ParsedStatement::LetAssign(let_assign) => {
+                for (name, span) in let_assign.pattern.bindings() {
+                    self.add_let(name, span);
+                }
+            }
+            ParsedStatement::ForInLoop(for_in) => {
+                // The iterable can't refer to the variables, the body comes right after it
+                let body_start = for_in.iterable_value.span().end();
+                if let Some((ref index_var_name, index_var_span)) = for_in.index_var {
+                    self.add_block_local(index_var_name, index_var_span, body_start);
+                }
+                self.add_block_local(&for_in.elem_var_name, for_in.elem_var_name_span, body_start);
+            }
+            _ => {}
+        }
+        visitor::walk_statement(self, statement);
+    }
+}
+
+fn contains(span: Span, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end()
+}
+
+/// The path of a `file://` URI, which imports of the document are resolved relative to.
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+    let mut bytes = vec![];
+    let mut idx = 0;
+    while idx < path.len() {
+        let escaped = path
+            .get(idx + 1..idx + 3)
+            .filter(|_| path.as_bytes()[idx] == b'%')
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                idx += 3;
+            }
+            None => {
+                bytes.push(path.as_bytes()[idx]);
+                idx += 1;
+            }
+        }
+    }
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    uri
+}
+
+/// Converts an LSP position, a line and a character offset in UTF-16 code units, to a byte offset.
+fn position_to_offset(source: &str, position: &Value) -> usize {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as usize;
+
+    let line_start = match line {
+        0 => 0,
+        _ => match source.match_indices('\n').nth(line - 1) {
+            Some((newline, _)) => newline + 1,
+            None => return source.len(),
+        },
+    };
+    let mut units = 0;
+    for (offset, c) in source[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + offset;
+        }
+        units += c.len_utf16();
+    }
+    source.len()
+}
+
+fn offset_to_position(source: &str, offset: usize) -> Value {
+    let preceding = &source[..offset];
+    let line = preceding.matches('\n').count();
+    let line_start = preceding.rfind('\n').map_or(0, |newline| newline + 1);
+    let character: usize = preceding[line_start..].chars().map(char::len_utf16).sum();
+    json!({ "line": line, "character": character })
+}
+
+fn range(source: &str, start: usize, end: usize) -> Value {
+    json!({
+        "start": offset_to_position(source, start),
+        "end": offset_to_position(source, end),
+    })
+}
@@ -1,7 +1,27 @@
 use clap::Parser;
-use clara::{codegen, lexer, parser, span::FileId, typechecker};
+use clara::{
+    codegen,
+    diagnostic::IntoDiagnostic,
+    fold::{ConstantFoldPass, Fold},
+    inline, lexer,
+    lints::{self, UnusedVariables},
+    loader,
+    pretty_print::{self, PrettyPrinter},
+    source_map::SourceMap,
+    typechecker,
+    visitor::Visitor,
+    vm,
+};
+use codespan_reporting::{
+    files::Files,
+    term::termcolor::{ColorChoice, StandardStream},
+};
+use serde_json::json;
 use std::{io::Read, path::PathBuf};
 
+mod lsp;
+mod repl;
+
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Args {
@@ -14,13 +34,98 @@ struct Args {
     #[clap(long)]
     print_llir: bool,
 
-    input: String,
+    /// What to compile the program to, `object` writes `./build/out.o`, `c` writes the program
+    /// translated to C next to it and `llvm-ir` prints the LLVM IR of the program
+    #[clap(long, arg_enum, default_value = "object")]
+    emit: Emit,
+
+    /// What platform to compile the program for, `wasm32` writes the program as WebAssembly text
+    /// next to `./build/out.o`, regardless of `--emit`
+    #[clap(long, arg_enum, default_value = "native")]
+    target: Target,
+
+    /// Print the parsed program as formatted source and exit
+    #[clap(long)]
+    pretty_print: bool,
+
+    /// Format the input file in place, indenting by four spaces. Comments are kept if they stand on
+    /// lines of their own or at the end of a line, files with comments anywhere else are left
+    /// alone
+    #[clap(long)]
+    fmt: bool,
+
+    /// With `--fmt`, only check whether the file is formatted, exiting with 1 if it isn't
+    #[clap(long, requires = "fmt")]
+    check: bool,
+
+    /// Print the tokens of the input as a JSON array and exit
+    #[clap(long)]
+    dump_tokens: bool,
+
+    /// Typecheck input a line at a time, printing the type of every entered expression
+    #[clap(long)]
+    repl: bool,
+
+    /// Run a language server speaking the Language Server Protocol over stdin and stdout
+    #[clap(long)]
+    lsp: bool,
+
+    /// Warn about variables that are never read or needlessly declared `mut`
+    #[clap(long)]
+    warn_unused: bool,
+
+    /// Run the program in the bytecode VM instead of compiling it
+    #[clap(long)]
+    interpret: bool,
+
+    /// Run the functions marked `#[test]` in the bytecode VM and report which of them failed
+    #[clap(long)]
+    test: bool,
+
+    /// Print the parsed program as JSON and exit, see `dump_ast` for the format
+    #[cfg(feature = "serde")]
+    #[clap(long)]
+    dump_ast: bool,
+
+    #[clap(required_unless_present_any = ["repl", "lsp"])]
+    input: Option<String>,
+}
+
+#[derive(clap::ArgEnum, Clone, Debug)]
+enum Emit {
+    Object,
+    C,
+    LlvmIr,
+}
+
+#[derive(clap::ArgEnum, Clone, Debug)]
+enum Target {
+    Native,
+    Wasm32,
 }
 
 fn main() {
     let args = Args::parse();
 
-    let source_filepath = args.input;
+    if args.lsp {
+        lsp::run(std::io::stdin().lock(), &mut std::io::stdout())
+            .expect("expected to be able to read from stdin and write to stdout");
+        return;
+    }
+
+    if args.repl {
+        let diagnostics_writer = StandardStream::stderr(ColorChoice::Always);
+        repl::run(
+            std::io::stdin().lock(),
+            &mut std::io::stdout(),
+            args.json_diagnostics,
+            &diagnostics_writer,
+        )
+        .expect("expected to be able to read from stdin and write to stdout");
+        return;
+    }
+
+    let source_filepath = args.input.unwrap();
     let (source, source_file) = if source_filepath == "-" {
         let mut source = String::new();
         std::io::stdin()
@@ -35,83 +140,271 @@ fn main() {
         )
     };
 
-    let diagnostics_writer = codespan_reporting::term::termcolor::StandardStream::stderr(
-        codespan_reporting::term::termcolor::ColorChoice::Always,
-    );
-    let mut files = codespan_reporting::files::SimpleFiles::new();
+    let diagnostics_writer = StandardStream::stderr(ColorChoice::Always);
+    let mut source_map = SourceMap::default();
+    let file_id = source_map.add_file(PathBuf::from(&source_filepath), source);
 
-    let (tokens, lex_errors) = lexer::lex(FileId(0), &source);
+    if args.dump_tokens {
+        let (tokens, lex_errors) = lexer::lex(file_id, source_map.source(file_id));
+        emit_diagnostics(
+            &lex_errors,
+            args.json_diagnostics,
+            &diagnostics_writer,
+            &source_map,
+        );
 
-    if args.json_diagnostics {
-        for e in &lex_errors {
-            println!("{}", e.json());
-        }
-    } else {
-        files.add(source_filepath, &source);
-        for e in &lex_errors {
-            codespan_reporting::term::emit(
-                &mut diagnostics_writer.lock(),
-                &codespan_reporting::term::Config::default(),
-                &files,
-                &e.report(),
-            )
-            .unwrap();
-        }
+        let tokens: Vec<_> = tokens
+            .iter()
+            .map(|token| {
+                json!({
+                    "kind": token.kind.human_name(),
+                    "start": token.span.start,
+                    "len": token.span.len,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::Array(tokens));
+        std::process::exit(if lex_errors.is_empty() { 0 } else { 1 });
     }
 
-    let (program, parse_errors) = parser::parse_program(&tokens, &mut 0);
-
-    if args.json_diagnostics {
-        for e in &parse_errors {
-            println!("{}", e.json());
-        }
+    // Formatting and dumping the AST only concern the file itself, not the files it imports
+    #[cfg(feature = "serde")]
+    let single_file = args.pretty_print || args.fmt || args.dump_ast;
+    #[cfg(not(feature = "serde"))]
+    let single_file = args.pretty_print || args.fmt;
+    let (program, load_errors) = if single_file {
+        loader::parse_file(&source_map, file_id)
     } else {
-        for e in &parse_errors {
-            codespan_reporting::term::emit(
-                &mut diagnostics_writer.lock(),
-                &codespan_reporting::term::Config::default(),
-                &files,
-                &e.report(),
-            )
-            .unwrap();
-        }
+        loader::load_program(&mut source_map, file_id)
+    };
+    emit_diagnostics(
+        &load_errors,
+        args.json_diagnostics,
+        &diagnostics_writer,
+        &source_map,
+    );
+
+    // The AST is dumped even if there were errors, as far as it could be parsed
+    #[cfg(feature = "serde")]
+    if args.dump_ast {
+        dump_ast(&program);
+        std::process::exit(if load_errors.is_empty() { 0 } else { 1 });
     }
 
-    if !lex_errors.is_empty() || !parse_errors.is_empty() {
+    if !load_errors.is_empty() {
         std::process::exit(1);
     }
 
-    let (checked_program, typecheck_errors) = typechecker::typecheck_program(&program);
+    if args.pretty_print {
+        let mut formatted = String::new();
+        PrettyPrinter::new(&mut formatted)
+            .fmt_program(&program)
+            .expect("writing to a string should not fail");
+        print!("{formatted}");
+        std::process::exit(0);
+    }
 
-    if args.json_diagnostics {
-        for e in &typecheck_errors {
-            println!("{}", e.json());
-        }
-    } else {
-        for e in &typecheck_errors {
-            codespan_reporting::term::emit(
-                &mut diagnostics_writer.lock(),
-                &codespan_reporting::term::Config::default(),
-                &files,
-                &e.report(),
-            )
-            .unwrap();
+    if args.fmt {
+        let source = source_map.source(file_id);
+        let (comments, inner_comments): (Vec<_>, Vec<_>) = lexer::comments(source)
+            .into_iter()
+            .partition(|comments| comments.is_between_declarations);
+
+        let mut formatted = String::new();
+        PrettyPrinter::new(&mut formatted)
+            .with_indent_width(4)
+            .with_comments(comments)
+            .fmt_program(&program)
+            .expect("writing to a string should not fail");
+        let formatted = pretty_print::insert_inner_comments(
+            &program,
+            source,
+            &formatted,
+            &inner_comments,
+            4,
+        )
+        .unwrap_or_else(|span| {
+            let location = source_map
+                .location(file_id.0, span.start)
+                .expect("comments should lie inside of the source");
+            eprintln!(
+                "error: {source_filepath}:{}:{}: formatting would remove this comment, only comments on lines of their own or at the end of a line can be kept",
+                location.line_number, location.column_number
+            );
+            std::process::exit(1);
+        });
+        if args.check {
+            if formatted != source {
+                eprintln!("{source_filepath} is not formatted");
+                std::process::exit(1);
+            }
+        } else if source_filepath == "-" {
+            print!("{formatted}");
+        } else if formatted != source {
+            std::fs::write(&source_filepath, formatted)
+                .expect("expected to be able to write the formatted source back");
         }
+        std::process::exit(0);
     }
 
+    let mut constant_fold = ConstantFoldPass::default();
+    let program = constant_fold.fold_program(program);
+    emit_diagnostics(
+        &constant_fold.warnings,
+        args.json_diagnostics,
+        &diagnostics_writer,
+        &source_map,
+    );
+
+    emit_diagnostics(
+        &lints::unknown_calling_conventions(&program),
+        args.json_diagnostics,
+        &diagnostics_writer,
+        &source_map,
+    );
+
+    if args.warn_unused {
+        let mut unused_variables = UnusedVariables::default();
+        unused_variables.visit_program(&program);
+        emit_diagnostics(
+            &unused_variables.warnings,
+            args.json_diagnostics,
+            &diagnostics_writer,
+            &source_map,
+        );
+    }
+
+    let (mut checked_program, typecheck_errors) = typechecker::typecheck_program(&program);
+    emit_diagnostics(
+        &typecheck_errors,
+        args.json_diagnostics,
+        &diagnostics_writer,
+        &source_map,
+    );
+
     if args.no_emit {
         std::process::exit(0);
     }
 
-    if !typecheck_errors.is_empty() {
+    if !typecheck_errors.iter().all(IntoDiagnostic::is_warning) {
         std::process::exit(1);
     }
 
+    inline::inline_functions(&mut checked_program);
+
+    if args.test {
+        let all_passed = run_tests(&checked_program);
+        std::process::exit(if all_passed { 0 } else { 1 });
+    }
+
+    if args.interpret {
+        let vm_program = vm::compiler::compile(&checked_program).unwrap_or_else(|err| {
+            eprintln!("error: {err}");
+            std::process::exit(1);
+        });
+        let stdout = std::io::stdout();
+        if let Err(err) = vm::interpreter::Interpreter::new(&vm_program, stdout.lock()).run() {
+            eprintln!("runtime error: {err}");
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let mut o_filepath = PathBuf::from("./build");
     o_filepath.push(
         source_file
             .file_stem()
             .unwrap_or_else(|| source_file.file_name().unwrap()),
     );
-    codegen::generate_executable(&o_filepath, &checked_program, args.print_llir).unwrap();
+    if let Target::Wasm32 = args.target {
+        codegen::wasm::generate_wat_file(o_filepath.with_extension("wat"), &checked_program)
+            .unwrap_or_else(|err| {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            });
+        return;
+    }
+
+    match args.emit {
+        Emit::Object => {
+            codegen::generate_executable(&o_filepath, &checked_program, args.print_llir).unwrap()
+        }
+        Emit::C => codegen::c::generate_c_file(o_filepath.with_extension("c"), &checked_program)
+            .unwrap_or_else(|err| {
+                eprintln!("error: {err}");
+                std::process::exit(1);
+            }),
+        Emit::LlvmIr => print!("{}", codegen::generate_llvm_ir(&checked_program).unwrap()),
+    }
+}
+
+/// Runs every `#[test]` function of the program in the VM, a test fails when it stops with a
+/// runtime error like a failed `assert`. Returns whether all tests passed.
+fn run_tests(program: &typechecker::CheckedProgram) -> bool {
+    let plural = if program.tests.len() == 1 { "" } else { "s" };
+    println!("running {} test{plural}", program.tests.len());
+
+    let mut failed = 0;
+    for test in &program.tests {
+        let result = vm::compiler::compile_with_entry(program, test)
+            .map_err(|err| err.to_string())
+            .and_then(|vm_program| {
+                let stdout = std::io::stdout();
+                vm::interpreter::Interpreter::new(&vm_program, stdout.lock())
+                    .run()
+                    .map_err(|err| err.to_string())
+            });
+        match result {
+            Ok(()) => println!("test {test} ... ok"),
+            Err(err) => {
+                println!("test {test} ... FAILED: {err}");
+                failed += 1;
+            }
+        }
+    }
+
+    let passed = program.tests.len() - failed;
+    let outcome = if failed == 0 { "ok" } else { "FAILED" };
+    println!("\ntest result: {outcome}. {passed} passed; {failed} failed");
+    failed == 0
+}
+
+/// Prints the program as pretty-printed JSON. Every AST struct becomes an object with a key for each
+/// of its fields, enums are externally tagged:
+///
+/// - unit variants are the name of the variant, `"Unit"`
+/// - single value variants are an object with the value under the variant name,
+///   `{ "Variable": ["x", { "source": 0, "start": 4, "len": 1 }] }`
+/// - variants holding multiple values hold them in an array, `{ "MathOp": [lhs, rhs, "Addition"] }`
+/// - struct-like variants hold an object, `{ "FnPtr": { "params": [...], "return_type": ... } }`
+///
+/// Spans are objects of `source`, the index of the file, and the byte offsets `start` and `len`.
+/// `Option`s are `null` or their value, tuples are arrays.
+#[cfg(feature = "serde")]
+fn dump_ast(program: &clara::parser::ParsedProgram) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(program).expect("serializing the AST should not fail")
+    );
+}
+
+fn emit_diagnostics(
+    errors: &[impl IntoDiagnostic],
+    json_diagnostics: bool,
+    diagnostics_writer: &StandardStream,
+    source_map: &SourceMap,
+) {
+    for e in errors {
+        if json_diagnostics {
+            println!("{}", e.json(source_map));
+        } else {
+            codespan_reporting::term::emit(
+                &mut diagnostics_writer.lock(),
+                &codespan_reporting::term::Config::default(),
+                source_map,
+                &e.report_located(),
+            )
+            .unwrap();
+        }
+    }
 }
@@ -1,10 +1,16 @@
+use std::fmt;
+
 use codespan_reporting::diagnostic::{Diagnostic, Label};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
-    lexer::{Token, TokenKind},
-    span::{FileId, Span, Spanned},
-    typechecker::Type,
+    diagnostic::IntoDiagnostic,
+    error_codes,
+    lexer::{InterpolatedPart, Token, TokenKind},
+    source_map::SourceMap,
+    span::{Span, Spanned},
 };
 
 #[derive(Debug)]
@@ -13,12 +19,26 @@ pub enum ParseError {
     ExpectedIdentifier(Span),
     ExpectedToken(TokenKind, Span),
     UnexpectedEndOfInput(Span),
+    StructLiteralInRestrictedContext(Span),
+    UnexpectedTopLevelToken {
+        found: TokenKind,
+        span: Span,
+        recovered_at: Span,
+    },
+    MisplacedAttribute(Span),
+    DuplicateField {
+        field_name: String,
+        first_span: Span,
+        second_span: Span,
+    },
+    PositionalArgAfterNamedArg(Span),
+    LabelWithoutLoop(Span),
 }
 
-impl ParseError {
-    pub fn report(&self) -> Diagnostic<usize> {
+impl IntoDiagnostic for ParseError {
+    fn report(&self) -> Diagnostic<usize> {
         use ParseError::*;
-        match *self {
+        let diagnostic = match *self {
             UnexpectedToken(span) => Diagnostic::error()
                 .with_message("unexpected token encountered")
                 .with_labels(vec![Label::primary(span.source.0, span)]),
@@ -31,30 +51,163 @@ impl ParseError {
             UnexpectedEndOfInput(span) => Diagnostic::error()
                 .with_message("unexpected end of input")
                 .with_labels(vec![Label::primary(span.source.0, span)]),
-        }
+            StructLiteralInRestrictedContext(span) => Diagnostic::error()
+                .with_message("struct literals are not allowed here")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    "surround struct literal with parentheses to use it here",
+                )])
+                .with_notes(vec![
+                    "A `{` after a name in a condition, iterable or match scrutinee starts a block."
+                        .to_string(),
+                ]),
+            UnexpectedTopLevelToken {
+                ref found,
+                span,
+                recovered_at,
+            } => Diagnostic::error()
+                .with_message(format!("unexpected {} at top level", found.human_name()))
+                .with_labels(vec![
+                    Label::primary(span.source.0, span).with_message("expected a declaration"),
+                    Label::secondary(recovered_at.source.0, recovered_at)
+                        .with_message("parsing resumed here"),
+                ])
+                .with_notes(vec![
+                    "Only functions, extern functions, structs, enums, unions, impls, type aliases and constants can be declared at the top level."
+                        .to_string(),
+                ]),
+            MisplacedAttribute(span) => Diagnostic::error()
+                .with_message("attribute is not applied to anything")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("expected a function or struct after this attribute")]),
+            DuplicateField {
+                ref field_name,
+                first_span,
+                second_span,
+            } => Diagnostic::error()
+                .with_message(format!("duplicate field `{field_name}`"))
+                .with_labels(vec![
+                    Label::primary(second_span.source.0, second_span)
+                        .with_message("field appears again here"),
+                    Label::secondary(first_span.source.0, first_span)
+                        .with_message(format!("`{field_name}` first appears here")),
+                ]),
+            PositionalArgAfterNamedArg(span) => Diagnostic::error()
+                .with_message("positional argument after named argument")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("expected a named argument")])
+                .with_notes(vec![
+                    "Positional arguments have to come before any named arguments.".to_string(),
+                ]),
+            LabelWithoutLoop(span) => Diagnostic::error()
+                .with_message("label is not applied to a loop")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("expected `while`, `for` or `loop` after this label")]),
+        };
+        diagnostic.with_code(self.error_code())
     }
-}
 
-impl ParseError {
-    pub fn json(&self) -> serde_json::Value {
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value {
         use ParseError::*;
-        match *self {
+        let mut json = match *self {
             UnexpectedToken(span) => json!({
                 "message": "unexpected token encountered",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             ExpectedIdentifier(span) => json!({
                 "message": "expected identifier",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             ExpectedToken(ref kind, span) => json!({
                 "message": format!("expected token {}", kind.human_name()),
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             UnexpectedEndOfInput(span) => json!({
                 "message": "reached unexpected end of input",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
+            }),
+            StructLiteralInRestrictedContext(span) => json!({
+                "message": "struct literals are not allowed here, surround struct literal with parentheses to use it here",
+                "span": span.json_with_source(source_map),
+            }),
+            UnexpectedTopLevelToken {
+                ref found,
+                span,
+                recovered_at,
+            } => json!({
+                "message": format!("unexpected {} at top level", found.human_name()),
+                "span": span.json_with_source(source_map),
+                "recovered_at": recovered_at.json_with_source(source_map),
             }),
+            MisplacedAttribute(span) => json!({
+                "message": "attribute is not applied to anything, expected a function or struct after it",
+                "span": span.json_with_source(source_map),
+            }),
+            DuplicateField {
+                ref field_name,
+                first_span,
+                second_span,
+            } => json!({
+                "message": format!("duplicate field `{field_name}`"),
+                "span": second_span.json_with_source(source_map),
+                "first_span": first_span.json_with_source(source_map),
+            }),
+            PositionalArgAfterNamedArg(span) => json!({
+                "message": "positional argument after named argument",
+                "span": span.json_with_source(source_map),
+            }),
+            LabelWithoutLoop(span) => json!({
+                "message": "label is not applied to a loop, expected `while`, `for` or `loop` after it",
+                "span": span.json_with_source(source_map),
+            }),
+        };
+        json["code"] = self.error_code().into();
+        json
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            UnexpectedToken(_) => write!(f, "unexpected token encountered"),
+            ExpectedIdentifier(_) => write!(f, "expected identifier"),
+            ExpectedToken(kind, _) => write!(f, "expected token {}", kind.human_name()),
+            UnexpectedEndOfInput(_) => write!(f, "unexpected end of input"),
+            StructLiteralInRestrictedContext(_) => {
+                write!(f, "struct literals are not allowed here")
+            }
+            UnexpectedTopLevelToken { found, .. } => {
+                write!(f, "unexpected {} at top level", found.human_name())
+            }
+            MisplacedAttribute(_) => write!(f, "attribute is not applied to anything"),
+            DuplicateField { field_name, .. } => write!(f, "duplicate field `{field_name}`"),
+            PositionalArgAfterNamedArg(_) => {
+                write!(f, "positional argument after named argument")
+            }
+            LabelWithoutLoop(_) => write!(f, "label is not applied to a loop"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// The stable code identifying this kind of error, see [`error_codes`].
+    pub fn error_code(&self) -> &'static str {
+        use ParseError::*;
+        match self {
+            UnexpectedToken(_) => error_codes::UNEXPECTED_TOKEN,
+            ExpectedIdentifier(_) => error_codes::EXPECTED_IDENTIFIER,
+            ExpectedToken(..) => error_codes::EXPECTED_TOKEN,
+            UnexpectedEndOfInput(_) => error_codes::UNEXPECTED_END_OF_INPUT,
+            StructLiteralInRestrictedContext(_) => {
+                error_codes::STRUCT_LITERAL_IN_RESTRICTED_CONTEXT
+            }
+            UnexpectedTopLevelToken { .. } => error_codes::UNEXPECTED_TOP_LEVEL_TOKEN,
+            MisplacedAttribute(_) => error_codes::MISPLACED_ATTRIBUTE,
+            DuplicateField { .. } => error_codes::DUPLICATE_FIELD,
+            PositionalArgAfterNamedArg(_) => error_codes::POSITIONAL_ARG_AFTER_NAMED_ARG,
+            LabelWithoutLoop(_) => error_codes::LABEL_WITHOUT_LOOP,
         }
     }
 }
@@ -66,38 +219,88 @@ pub enum Restriction {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedFunctionCall {
     pub name: String,
     pub name_span: Span,
-    pub args: Vec<ParsedExpression>,
+    /// Types given explicitly for the type parameters of a generic function, as in
+    /// `identity::<int>(x)`
+    pub type_args: Vec<ParsedType>,
+    /// Every argument along with a span from its start up to and including its trailing comma.
+    pub args: Vec<(CallArg, Span)>,
     pub span: Span,
 }
 
+/// An argument passed to the parameter of the same name, as in `resize(width: 10, height: 20)`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedNamedArg {
+    pub name: String,
+    pub name_span: Span,
+    pub value: ParsedExpression,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CallArg {
+    Positional(ParsedExpression),
+    Named(ParsedNamedArg),
+}
+
+impl CallArg {
+    pub fn value(&self) -> &ParsedExpression {
+        match self {
+            Self::Positional(value) => value,
+            Self::Named(named_arg) => &named_arg.value,
+        }
+    }
+}
+
+impl Spanned for CallArg {
+    fn span(&self) -> Span {
+        match self {
+            Self::Positional(value) => value.span(),
+            Self::Named(named_arg) => named_arg.name_span.to(named_arg.value.span()),
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedStructLiteral {
     pub name: String,
     pub name_span: Span,
+    /// Types given explicitly for the type parameters of a generic struct, as in
+    /// `Pair<int, bool> { ... }`
+    pub type_args: Vec<ParsedType>,
     pub fields: Vec<(String, Span, ParsedExpression)>,
     pub span: Span,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedArrayLiteral {
     pub elements: Vec<ParsedExpression>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Literal {
     String(String, Span),
+    Char(char, Span),
     Int(i32, Span),
     Bool(bool, Span),
+    Null(Span),
     Struct(ParsedStructLiteral, Span),
     Array(ParsedArrayLiteral, Span),
+    Tuple(Vec<ParsedExpression>, Span),
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum CompareOperation {
     Equality,
+    NotEqual,
     GreaterThan,
     GreaterThanEqual,
     LessThan,
@@ -105,14 +308,47 @@ pub enum CompareOperation {
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum MathOperation {
     Addition,
     Subtraction,
     Multiplication,
     Division,
+    Modulo,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl MathOperation {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Addition => "+",
+            Self::Subtraction => "-",
+            Self::Multiplication => "*",
+            Self::Division => "/",
+            Self::Modulo => "%",
+            Self::BitAnd => "&",
+            Self::BitOr => "|",
+            Self::BitXor => "^",
+            Self::Shl => "<<",
+            Self::Shr => ">>",
+        }
+    }
+
+    /// Whether the operation is only defined for integer operands.
+    pub fn is_integer_only(self) -> bool {
+        !matches!(
+            self,
+            Self::Addition | Self::Subtraction | Self::Multiplication | Self::Division
+        )
+    }
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedFieldAccess {
     pub object: Box<ParsedExpression>,
     pub object_span: Span,
@@ -122,12 +358,44 @@ pub struct ParsedFieldAccess {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedMethodCall {
+    pub object: Box<ParsedExpression>,
+    pub object_span: Span,
+    pub method_name: String,
+    pub method_name_span: Span,
+    /// Every argument along with a span from its start up to and including its trailing comma.
+    pub args: Vec<(CallArg, Span)>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedTupleIndex {
+    pub object: Box<ParsedExpression>,
+    pub index: usize,
+    pub index_span: Span,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedArrayIndex {
     pub index: Box<ParsedExpression>,
     pub array: Box<ParsedExpression>,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedRange {
+    pub start: Box<ParsedExpression>,
+    pub end: Box<ParsedExpression>,
+    pub inclusive: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedPointerTo {
     pub pointer_span: Span,
     pub inner: Box<ParsedExpression>,
@@ -135,12 +403,34 @@ pub struct ParsedPointerTo {
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedEnumVariant {
+    pub enum_name: String,
+    pub enum_name_span: Span,
+    pub variant_name: String,
+    pub variant_name_span: Span,
+    pub value: Option<Box<ParsedExpression>>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedDeref {
     pub star_span: Span,
     pub inner: Box<ParsedExpression>,
 }
 
+/// `cast<T>(inner)`, converting the value of `inner` to the type `T`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedCast {
+    pub target_type: ParsedType,
+    pub inner: Box<ParsedExpression>,
+    pub span: Span,
+}
+
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ParsedExpression {
     Literal(Literal),
     FunctionCall(ParsedFunctionCall),
@@ -151,11 +441,41 @@ pub enum ParsedExpression {
         CompareOperation,
     ),
     MathOp(Box<ParsedExpression>, Box<ParsedExpression>, MathOperation),
+    LogicalAnd(Box<ParsedExpression>, Box<ParsedExpression>),
+    LogicalOr(Box<ParsedExpression>, Box<ParsedExpression>),
+    UnaryMinus(Box<ParsedExpression>, Span),
+    Not(Box<ParsedExpression>, Span),
+    BitNot(Box<ParsedExpression>, Span),
+    Parenthesized(Box<ParsedExpression>, Span),
     FieldAccess(ParsedFieldAccess),
+    MethodCall(ParsedMethodCall),
+    TupleIndex(ParsedTupleIndex),
     ArrayIndex(ParsedArrayIndex),
     Assignment(Box<ParsedExpression>, Box<ParsedExpression>),
+    Range(ParsedRange),
     PointerTo(ParsedPointerTo),
     Deref(ParsedDeref),
+    EnumVariant(ParsedEnumVariant),
+    /// `sizeof(T)`, the size of a type in bytes.
+    SizeOf(ParsedType, Span),
+    /// `offsetof(S, "field")`, the offset in bytes of a field from the start of a struct.
+    OffsetOf {
+        ttype: ParsedType,
+        field: String,
+        field_span: Span,
+        span: Span,
+    },
+    Cast(ParsedCast),
+    /// `[value; count]`, an array holding `count` copies of the value.
+    ArrayRepeat {
+        value: Box<ParsedExpression>,
+        count: usize,
+        span: Span,
+    },
+    /// `{ statements; value }`, evaluating to the value of its tail expression.
+    Block(Box<ParsedBlock>, Span),
+    /// `if condition { value } else { value }` in the position of an expression.
+    IfExpr(Box<ParsedIfElse>),
 }
 
 impl Spanned for ParsedExpression {
@@ -163,105 +483,485 @@ impl Spanned for ParsedExpression {
         match self {
             Self::Literal(l) => match l {
                 Literal::String(_, span) => *span,
+                Literal::Char(_, span) => *span,
                 Literal::Int(_, span) => *span,
                 Literal::Bool(_, span) => *span,
+                Literal::Null(span) => *span,
                 Literal::Struct(_, span) => *span,
                 Literal::Array(_, span) => *span,
+                Literal::Tuple(_, span) => *span,
             },
             Self::FunctionCall(f) => f.span,
             Self::Variable(_, span) => *span,
             Self::CompareOp(lhs, rhs, _) => lhs.span().to(rhs.span()),
             Self::MathOp(lhs, rhs, _) => lhs.span().to(rhs.span()),
+            Self::LogicalAnd(lhs, rhs) => lhs.span().to(rhs.span()),
+            Self::LogicalOr(lhs, rhs) => lhs.span().to(rhs.span()),
+            Self::UnaryMinus(inner, op_span) => op_span.to(inner.span()),
+            Self::Not(inner, op_span) => op_span.to(inner.span()),
+            Self::BitNot(inner, op_span) => op_span.to(inner.span()),
+            Self::Parenthesized(_, span) => *span,
             Self::FieldAccess(field_access) => field_access.span,
+            Self::MethodCall(method_call) => method_call.span,
+            Self::TupleIndex(tuple_index) => tuple_index.span,
             Self::ArrayIndex(array_index) => array_index.array.span().to(array_index.index.span()),
             Self::Assignment(lhs, rhs) => lhs.span().to(rhs.span()),
+            Self::Range(range) => range.span,
             Self::PointerTo(pointer_to) => pointer_to.pointer_span.to(pointer_to.inner.span()),
             Self::Deref(deref) => deref.star_span.to(deref.inner.span()),
+            Self::EnumVariant(enum_variant) => enum_variant.span,
+            Self::SizeOf(_, span) => *span,
+            Self::OffsetOf { span, .. } => *span,
+            Self::Cast(cast) => cast.span,
+            Self::ArrayRepeat { span, .. } => *span,
+            Self::Block(_, span) => *span,
+            Self::IfExpr(if_else) => if_else.span,
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedEnumVariantPattern {
+    pub enum_name: String,
+    pub enum_name_span: Span,
+    pub variant_name: String,
+    pub variant_name_span: Span,
+    pub binding: Option<(String, Span)>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParsedPattern {
+    Wildcard(Span),
+    Literal(Literal),
+    EnumVariant(ParsedEnumVariantPattern),
+}
+
+impl Spanned for ParsedPattern {
+    fn span(&self) -> Span {
+        match self {
+            Self::Wildcard(span) => *span,
+            Self::Literal(
+                Literal::String(_, span)
+                | Literal::Char(_, span)
+                | Literal::Int(_, span)
+                | Literal::Bool(_, span)
+                | Literal::Null(span)
+                | Literal::Struct(_, span)
+                | Literal::Array(_, span)
+                | Literal::Tuple(_, span),
+            ) => *span,
+            Self::EnumVariant(enum_variant) => enum_variant.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedMatchArm {
+    pub pattern: ParsedPattern,
+    pub body: ParsedExpression,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedMatch {
+    pub scrutinee: Box<ParsedExpression>,
+    pub arms: Vec<ParsedMatchArm>,
+    pub span: Span,
+}
+
+/// `switch value { case 1, 2: ... default: ... }`, where each case lists the constant values it
+/// handles. Only the statements of the matching case run, there's no fallthrough.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedSwitch {
+    pub scrutinee: Box<ParsedExpression>,
+    pub cases: Vec<(Vec<ParsedExpression>, ParsedBlock)>,
+    pub default: Option<ParsedBlock>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedWhileLoop {
     pub condition: ParsedExpression,
     pub body: ParsedBlock,
+    /// Runs when the condition is false the first time it is checked, so the body never ran
+    pub else_body: Option<ParsedBlock>,
+    /// The name of the loop for `break` and `continue` in nested loops, as in `'outer: while`
+    pub label: Option<(String, Span)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedIfElse {
     pub condition: ParsedExpression,
     pub if_body: ParsedBlock,
+    /// An `else if` is represented as an else block containing only the nested if-else statement.
     pub else_body: Option<ParsedBlock>,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedForInLoop {
     pub elem_var_name: String,
     pub elem_var_name_span: Span,
+    /// The first of two names, as in `for index, elem in xs`, bound to the 0-based `int` index of
+    /// the element
     pub index_var: Option<(String, Span)>,
     pub iterable_value: ParsedExpression,
     pub body: ParsedBlock,
+    pub label: Option<(String, Span)>,
 }
 
-#[derive(Debug)]
+/// A type as written in the source, user defined names and aliases are resolved by the typechecker.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParsedType {
+    Name(String),
+    /// A generic struct with its type arguments, `Pair<int, bool>`.
+    Generic(String, Vec<ParsedType>),
+    Pointer(Box<ParsedType>, bool),
+    /// A pointer that may be `null`, written `?->T`.
+    NullablePointer(Box<ParsedType>, bool),
+    Array(Box<ParsedType>, usize),
+    Slice(Box<ParsedType>),
+    Tuple(Vec<ParsedType>),
+    FnPtr {
+        params: Vec<ParsedType>,
+        return_type: Box<ParsedType>,
+    },
+    Unit,
+}
+
+impl ParsedType {
+    pub fn to_str(&self) -> String {
+        match self {
+            Self::Name(name) => name.clone(),
+            Self::Generic(name, type_args) => format!(
+                "{name}<{}>",
+                type_args
+                    .iter()
+                    .map(ParsedType::to_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::Pointer(ty, true) => format!("->mut {}", ty.to_str()),
+            Self::Pointer(ty, false) => format!("->{}", ty.to_str()),
+            Self::NullablePointer(ty, true) => format!("?->mut {}", ty.to_str()),
+            Self::NullablePointer(ty, false) => format!("?->{}", ty.to_str()),
+            Self::Array(elem_type, size) => format!("[{}; {size}]", elem_type.to_str()),
+            Self::Slice(elem_type) => format!("[{}]", elem_type.to_str()),
+            Self::Tuple(element_types) if element_types.len() == 1 => {
+                format!("({},)", element_types[0].to_str())
+            }
+            Self::Tuple(element_types) => format!(
+                "({})",
+                element_types
+                    .iter()
+                    .map(ParsedType::to_str)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Self::FnPtr {
+                params,
+                return_type,
+            } => {
+                let params = params
+                    .iter()
+                    .map(ParsedType::to_str)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                match **return_type {
+                    Self::Unit => format!("fn({params})"),
+                    ref return_type => format!("fn({params}): {}", return_type.to_str()),
+                }
+            }
+            Self::Unit => "()".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedLetAssign {
+    pub pattern: ParsedLetPattern,
+    pub type_annotation: Option<(ParsedType, Span)>,
+    pub value: ParsedExpression,
+    pub is_mut: bool,
+}
+
+/// What a `let` binds its value to, a single name or the elements of a tuple or fields of a
+/// struct as in `let (a, _) = pair;` and `let Point { x, y: other } = point;`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ParsedLetPattern {
+    Ident(String, Span),
+    /// `_` for elements that aren't bound
+    Tuple(Vec<(String, Span)>, Span),
+    /// The fields are the name of the field, the name it is bound to and the span of the binding
+    Struct {
+        name: String,
+        name_span: Span,
+        fields: Vec<(String, String, Span)>,
+        span: Span,
+    },
+}
+
+impl ParsedLetPattern {
+    /// The names introduced by the pattern, along with where they're declared.
+    pub fn bindings(&self) -> Vec<(&str, Span)> {
+        match self {
+            Self::Ident(name, span) => vec![(name, *span)],
+            Self::Tuple(elements, _) => elements
+                .iter()
+                .filter(|(name, _)| name != "_")
+                .map(|(name, span)| (name.as_str(), *span))
+                .collect(),
+            Self::Struct { fields, .. } => fields
+                .iter()
+                .map(|(_, binding, span)| (binding.as_str(), *span))
+                .collect(),
+        }
+    }
+}
+
+impl Spanned for ParsedLetPattern {
+    fn span(&self) -> Span {
+        match self {
+            Self::Ident(_, span) | Self::Tuple(_, span) | Self::Struct { span, .. } => *span,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedConstDecl {
+    /// Whether the constant is declared `pub`, always false for constants local to a block
+    pub is_public: bool,
     pub name: String,
     pub name_span: Span,
+    pub ttype: ParsedType,
+    pub type_span: Span,
     pub value: ParsedExpression,
-    pub is_mut: bool,
+    pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ParsedStatement {
     Expression(ParsedExpression),
     LetAssign(ParsedLetAssign),
+    Const(ParsedConstDecl),
     WhileLoop(ParsedWhileLoop),
+    /// `while let pattern = value { ... }`, which runs until the value doesn't match the pattern
+    WhileLet {
+        pattern: ParsedPattern,
+        value: ParsedExpression,
+        body: ParsedBlock,
+        label: Option<(String, Span)>,
+        span: Span,
+    },
+    /// `loop { ... }` with its label and span
+    Loop(ParsedBlock, Option<(String, Span)>, Span),
     IfElse(ParsedIfElse),
+    Match(ParsedMatch),
+    Switch(ParsedSwitch),
     ForInLoop(ParsedForInLoop),
     Return(ParsedExpression),
+    /// `break` with the label of the loop it leaves, the innermost loop without one
+    Break(Option<(String, Span)>, Span),
+    Continue(Option<(String, Span)>, Span),
+    /// A call run when the enclosing block is left, along with the span of the `defer` keyword.
+    Defer(ParsedExpression, Span),
+    /// `unsafe { ... }`, the only place where unsafe operations like dereferencing a pointer are
+    /// allowed
+    Unsafe(ParsedBlock, Span),
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedBlock {
     pub statements: Vec<ParsedStatement>,
+    /// The expression ending the block without a semicolon, which is the block's value
+    pub tail_expr: Option<ParsedExpression>,
+}
+
+/// `#[name]` or `#[name(arg, ...)]` in front of a function or struct, arguments are identifiers or
+/// string literals.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedAttribute {
+    pub name: String,
+    pub args: Vec<String>,
+    pub span: Span,
+}
+
+/// A type parameter of a generic function, `T` in `fn identity<T>(x: T): T`, with the traits
+/// the types it stands for have to implement, as in `T: Printable + Eq`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedGenericParam {
+    pub name: String,
+    pub span: Span,
+    pub bounds: Vec<(String, Span)>,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedFunction {
+    pub is_public: bool,
     pub name: String,
     pub name_span: Span,
+    pub generic_params: Vec<ParsedGenericParam>,
     pub parameters: Vec<FunctionParameter>,
     pub body: ParsedBlock,
-    pub return_type: Type,
+    pub return_type: ParsedType,
     pub return_type_span: Span,
+    pub attributes: Vec<ParsedAttribute>,
+    /// Marked `#[inline]`, so calls to it are replaced by its body where possible
+    pub is_inline: bool,
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FunctionParameter {
     pub name: String,
     pub name_span: Span,
-    pub ttype: Type,
+    /// Passed by pointer as in `x: ref Point`, the function sees a `->Point` while callers pass
+    /// a `Point`
+    pub is_by_ref: bool,
+    pub ttype: ParsedType,
     pub type_span: Span,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedExternFunction {
+    pub attributes: Vec<ParsedAttribute>,
+    /// The calling convention named as in `extern "stdcall" fn`, the platform's C convention if
+    /// none is given
+    pub calling_convention: Option<(String, Span)>,
+    pub is_public: bool,
     pub name: String,
     pub name_span: Span,
     pub parameters: Vec<FunctionParameter>,
-    pub return_type: Type,
+    pub is_variadic: bool,
+    pub return_type: ParsedType,
     pub return_type_span: Span,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum ParsedStruct {
-    Opaque(String, Span),
-    Transparent(String, Span, Vec<(String, Type)>),
+    /// An opaque struct with its size in bytes, as in `opaque struct SDL_Window: 128;`. Without a
+    /// size it can only be used behind pointers.
+    Opaque(String, Span, Option<usize>, Vec<ParsedAttribute>, bool),
+    Transparent(
+        String,
+        Span,
+        Vec<ParsedGenericParam>,
+        Vec<(String, ParsedType)>,
+        Vec<ParsedAttribute>,
+        bool,
+    ),
+}
+
+impl ParsedStruct {
+    pub fn attributes(&self) -> &[ParsedAttribute] {
+        match self {
+            Self::Opaque(_, _, _, attributes, _) | Self::Transparent(_, _, _, _, attributes, _) => {
+                attributes
+            }
+        }
+    }
+
+    /// Whether the struct is declared `pub`
+    pub fn is_public(&self) -> bool {
+        match self {
+            Self::Opaque(_, _, _, _, is_public) | Self::Transparent(_, _, _, _, _, is_public) => {
+                *is_public
+            }
+        }
+    }
+}
+
+/// `impl Type { ... }`, or `impl Trait for Type { ... }` when it implements a trait.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedImpl {
+    pub trait_name: Option<(String, Span)>,
+    pub struct_name: String,
+    pub struct_name_span: Span,
+    pub methods: Vec<ParsedFunction>,
+    pub span: Span,
+}
+
+/// `trait Name { fn method(self: ->Self); ... }`, the methods are declared without a body.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedTrait {
+    pub name: String,
+    pub name_span: Span,
+    pub methods: Vec<ParsedExternFunction>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedEnum {
+    pub name: String,
+    pub name_span: Span,
+    pub variants: Vec<(String, Span, Option<ParsedType>)>,
+    pub span: Span,
+}
+
+/// `union Name { field: Type, ... }`, whose fields all share the same memory like in C.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedUnion {
+    pub name: String,
+    pub name_span: Span,
+    pub fields: Vec<(String, ParsedType)>,
+    pub span: Span,
+}
+
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedTypeAlias {
+    pub name: String,
+    pub name_span: Span,
+    pub aliased: ParsedType,
+    pub span: Span,
+}
+
+/// `import "path";` or `import "path" as alias;`, the path is relative to the importing file and
+/// leaves out the `.clara` extension. Imported declarations are merged into the importing program,
+/// the alias does not introduce a namespace for them yet.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ParsedImport {
+    pub path: String,
+    pub path_span: Span,
+    pub alias: Option<(String, Span)>,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ParsedProgram {
+    pub imports: Vec<ParsedImport>,
+    pub consts: Vec<ParsedConstDecl>,
+    pub type_aliases: Vec<ParsedTypeAlias>,
     pub structs: Vec<ParsedStruct>,
+    pub enums: Vec<ParsedEnum>,
+    pub unions: Vec<ParsedUnion>,
+    pub impls: Vec<ParsedImpl>,
+    pub traits: Vec<ParsedTrait>,
     pub extern_functions: Vec<ParsedExternFunction>,
     pub functions: Vec<ParsedFunction>,
 }
@@ -300,20 +1000,88 @@ macro_rules! recover_at_token {
 pub fn parse_program(tokens: &[Token], idx: &mut usize) -> (ParsedProgram, Vec<ParseError>) {
     let mut errors = vec![];
     let mut program = ParsedProgram {
+        imports: vec![],
+        consts: vec![],
+        type_aliases: vec![],
         structs: vec![],
+        enums: vec![],
+        unions: vec![],
+        impls: vec![],
+        traits: vec![],
         extern_functions: vec![],
         functions: vec![],
     };
 
+    // Attributes apply to the declaration following them
+    let mut attributes: Vec<ParsedAttribute> = vec![];
+    // Set by a `pub` modifier, which applies to the declaration following it
+    let mut is_public = false;
+
     while *idx < tokens.len() {
         let reached_unexpected_eoi = (|| {
             let token = &tokens[*idx];
+            if !matches!(
+                token.kind,
+                TokenKind::Hash
+                    | TokenKind::Pub
+                    | TokenKind::Fn
+                    | TokenKind::Extern
+                    | TokenKind::Struct
+                    | TokenKind::Opaque
+            ) {
+                for attribute in attributes.drain(..) {
+                    errors.push(ParseError::MisplacedAttribute(attribute.span));
+                }
+            }
+
             match token {
+                Token {
+                    kind: TokenKind::Pub,
+                    ..
+                } => {
+                    *idx += 1; // Consume `pub` keyword
+
+                    // Only functions, structs and constants can be made public
+                    let next = tokens.get(*idx)?;
+                    if matches!(
+                        next.kind,
+                        TokenKind::Fn
+                            | TokenKind::Extern
+                            | TokenKind::Struct
+                            | TokenKind::Opaque
+                            | TokenKind::Const
+                    ) {
+                        is_public = true;
+                    } else {
+                        errors.push(ParseError::UnexpectedToken(next.span));
+                    }
+                }
+                Token {
+                    kind: TokenKind::Hash,
+                    ..
+                } => {
+                    let (attribute, mut errs) = parse_attribute(tokens, idx)?;
+                    attributes.push(attribute);
+                    errors.append(&mut errs);
+                }
+                Token {
+                    kind: TokenKind::Import,
+                    ..
+                } => {
+                    let (import, mut errs) = parse_import(tokens, idx)?;
+                    program.imports.push(import);
+                    errors.append(&mut errs);
+                }
                 Token {
                     kind: TokenKind::Opaque,
                     ..
                 } => {
-                    let (r#struct, mut errs) = parse_opaque_struct(tokens, idx)?;
+                    let (r#struct, mut errs) = parse_opaque_struct(
+                        tokens,
+                        idx,
+                        std::mem::take(&mut attributes),
+                        std::mem::take(&mut is_public),
+                    )?;
                     program.structs.push(r#struct);
                     errors.append(&mut errs);
                 }
@@ -321,15 +1089,65 @@ pub fn parse_program(tokens: &[Token], idx: &mut usize) -> (ParsedProgram, Vec<P
                     kind: TokenKind::Struct,
                     ..
                 } => {
-                    let (r#struct, mut errs) = parse_struct(tokens, idx)?;
+                    let (r#struct, mut errs) = parse_struct(
+                        tokens,
+                        idx,
+                        std::mem::take(&mut attributes),
+                        std::mem::take(&mut is_public),
+                    )?;
                     program.structs.push(r#struct);
                     errors.append(&mut errs);
                 }
+                Token {
+                    kind: TokenKind::Impl,
+                    ..
+                } => {
+                    let (r#impl, mut errs) = parse_impl(tokens, idx)?;
+                    program.impls.push(r#impl);
+                    errors.append(&mut errs);
+                }
+                Token {
+                    kind: TokenKind::Trait,
+                    ..
+                } => {
+                    let (r#trait, mut errs) = parse_trait(tokens, idx)?;
+                    program.traits.push(r#trait);
+                    errors.append(&mut errs);
+                }
+                Token {
+                    kind: TokenKind::Enum,
+                    ..
+                } => {
+                    let (r#enum, mut errs) = parse_enum(tokens, idx)?;
+                    program.enums.push(r#enum);
+                    errors.append(&mut errs);
+                }
+                Token {
+                    kind: TokenKind::Union,
+                    ..
+                } => {
+                    let (union, mut errs) = parse_union(tokens, idx)?;
+                    program.unions.push(union);
+                    errors.append(&mut errs);
+                }
+                Token {
+                    kind: TokenKind::Type,
+                    ..
+                } => {
+                    let (type_alias, mut errs) = parse_type_alias(tokens, idx)?;
+                    program.type_aliases.push(type_alias);
+                    errors.append(&mut errs);
+                }
                 Token {
                     kind: TokenKind::Fn,
                     ..
                 } => {
-                    let (fun, mut errs) = parse_function(tokens, idx)?;
+                    let (fun, mut errs) = parse_function(
+                        tokens,
+                        idx,
+                        std::mem::take(&mut attributes),
+                        std::mem::take(&mut is_public),
+                    )?;
                     program.functions.push(fun);
                     errors.append(&mut errs);
                 }
@@ -337,13 +1155,49 @@ pub fn parse_program(tokens: &[Token], idx: &mut usize) -> (ParsedProgram, Vec<P
                     kind: TokenKind::Extern,
                     ..
                 } => {
-                    let (fun, mut errs) = parse_extern_function(tokens, idx)?;
+                    let (fun, mut errs) = parse_extern_function(
+                        tokens,
+                        idx,
+                        std::mem::take(&mut attributes),
+                        std::mem::take(&mut is_public),
+                    )?;
                     program.extern_functions.push(fun);
                     errors.append(&mut errs);
                 }
-                _ => {
-                    errors.push(ParseError::UnexpectedToken(token.span));
-                    *idx += 1;
+                Token {
+                    kind: TokenKind::Const,
+                    ..
+                } => {
+                    let (const_decl, mut errs) =
+                        parse_const_decl(tokens, idx, std::mem::take(&mut is_public))?;
+                    program.consts.push(const_decl);
+                    errors.append(&mut errs);
+                    expect!(&mut errors, tokens, idx, TokenKind::SemiColon);
+                }
+                _ => {
+                    // Skip ahead to the next declaration, so a stray statement or expression is
+                    // reported once instead of once for each of its tokens
+                    *idx += 1;
+                    while let Some(token) = tokens.get(*idx) {
+                        if starts_declaration(&token.kind) {
+                            break;
+                        }
+                        *idx += 1;
+                    }
+
+                    // Without another declaration parsing resumes at the end of the input
+                    let recovered_at = match tokens.get(*idx) {
+                        Some(next) => next.span,
+                        None => {
+                            let last_span = tokens[*idx - 1].span;
+                            Span::new(last_span.source, last_span.end(), 0)
+                        }
+                    };
+                    errors.push(ParseError::UnexpectedTopLevelToken {
+                        found: token.kind.clone(),
+                        span: token.span,
+                        recovered_at,
+                    });
                 }
             }
             Some(())
@@ -357,10 +1211,115 @@ pub fn parse_program(tokens: &[Token], idx: &mut usize) -> (ParsedProgram, Vec<P
         };
     }
 
+    for attribute in attributes {
+        errors.push(ParseError::MisplacedAttribute(attribute.span));
+    }
+
     (program, errors)
 }
 
-fn parse_struct(tokens: &[Token], idx: &mut usize) -> Option<(ParsedStruct, Vec<ParseError>)> {
+fn starts_declaration(kind: &TokenKind) -> bool {
+    matches!(
+        kind,
+        TokenKind::Hash
+            | TokenKind::Import
+            | TokenKind::Opaque
+            | TokenKind::Struct
+            | TokenKind::Impl
+            | TokenKind::Trait
+            | TokenKind::Enum
+            | TokenKind::Union
+            | TokenKind::Type
+            | TokenKind::Fn
+            | TokenKind::Pub
+            | TokenKind::Extern
+            | TokenKind::Const
+    )
+}
+
+fn parse_attribute(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedAttribute, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let hash_span = tokens.get(*idx)?.span;
+    *idx += 1; // Consume `#` token
+
+    expect!(&mut errors, tokens, idx, TokenKind::OBracket);
+
+    // `unsafe` is a keyword, but also the name of the attribute marking unsafe extern functions
+    let name = if let Token {
+        kind: TokenKind::Unsafe,
+        ..
+    } = tokens.get(*idx)?
+    {
+        *idx += 1; // Consume `unsafe` keyword
+        "unsafe".to_string()
+    } else {
+        let (name, _name_span, mut errs) = parse_name(tokens, idx)?;
+        errors.append(&mut errs);
+        name
+    };
+
+    let mut args = vec![];
+    if let Token {
+        kind: TokenKind::OParen,
+        ..
+    } = tokens.get(*idx)?
+    {
+        *idx += 1; // Consume `(` token
+
+        while !matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::CParen,
+                ..
+            }
+        ) {
+            match tokens.get(*idx)? {
+                Token {
+                    kind: TokenKind::Ident(arg) | TokenKind::StringLiteral(arg),
+                    ..
+                } => {
+                    *idx += 1; // Consume argument
+                    args.push(arg.clone());
+                }
+                token => {
+                    errors.push(ParseError::ExpectedIdentifier(token.span));
+                    break;
+                }
+            }
+
+            if matches!(
+                tokens.get(*idx)?,
+                &Token {
+                    kind: TokenKind::Comma,
+                    ..
+                }
+            ) {
+                *idx += 1;
+            } else {
+                break;
+            }
+        }
+
+        recover_at_token!(&mut errors, tokens, idx, TokenKind::CParen);
+    }
+
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::CBracket);
+
+    let span = hash_span.to(tokens[*idx - 1].span);
+
+    Some((ParsedAttribute { name, args, span }, errors))
+}
+
+fn parse_struct(
+    tokens: &[Token],
+    idx: &mut usize,
+    attributes: Vec<ParsedAttribute>,
+    is_public: bool,
+) -> Option<(ParsedStruct, Vec<ParseError>)> {
     let mut errors = vec![];
 
     expect!(&mut errors, tokens, idx, TokenKind::Struct);
@@ -368,9 +1327,43 @@ fn parse_struct(tokens: &[Token], idx: &mut usize) -> Option<(ParsedStruct, Vec<
     let (name, name_span, mut errs) = parse_name(tokens, idx)?;
     errors.append(&mut errs);
 
+    let (generic_params, mut errs) = parse_generic_params(tokens, idx)?;
+    errors.append(&mut errs);
+    // Only type parameters of functions can be bounded by traits so far
+    for (_, bound_span) in generic_params
+        .iter()
+        .flat_map(|generic_param| &generic_param.bounds)
+    {
+        errors.push(ParseError::UnexpectedToken(*bound_span));
+    }
+
+    let (fields, mut errs) = parse_fields(tokens, idx)?;
+    errors.append(&mut errs);
+
+    Some((
+        ParsedStruct::Transparent(
+            name,
+            name_span,
+            generic_params,
+            fields,
+            attributes,
+            is_public,
+        ),
+        errors,
+    ))
+}
+
+/// The names and types of the fields of a struct or union, in the order they're declared
+type Fields = Vec<(String, ParsedType)>;
+
+/// The fields of a struct or union between braces, as in `{ x: int, y: int }`.
+fn parse_fields(tokens: &[Token], idx: &mut usize) -> Option<(Fields, Vec<ParseError>)> {
+    let mut errors = vec![];
+
     expect!(&mut errors, tokens, idx, TokenKind::OBrace);
 
     let mut fields = vec![];
+    let mut field_name_spans = vec![];
     while *idx < tokens.len()
         && !matches!(
             tokens.get(*idx)?,
@@ -381,9 +1374,18 @@ fn parse_struct(tokens: &[Token], idx: &mut usize) -> Option<(ParsedStruct, Vec<
         )
     {
         let (field, mut errs) = parse_parameter(tokens, idx)?;
-        fields.push((field.name, field.ttype));
         errors.append(&mut errs);
 
+        if let Some(first) = fields.iter().position(|(name, _)| *name == field.name) {
+            errors.push(ParseError::DuplicateField {
+                field_name: field.name.clone(),
+                first_span: field_name_spans[first],
+                second_span: field.name_span,
+            });
+        }
+        fields.push((field.name, field.ttype));
+        field_name_spans.push(field.name_span);
+
         if matches!(
             tokens.get(*idx)?,
             &Token {
@@ -399,8 +1401,30 @@ fn parse_struct(tokens: &[Token], idx: &mut usize) -> Option<(ParsedStruct, Vec<
 
     recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
 
+    Some((fields, errors))
+}
+
+fn parse_union(tokens: &[Token], idx: &mut usize) -> Option<(ParsedUnion, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let union_span = tokens.get(*idx)?.span;
+    expect!(&mut errors, tokens, idx, TokenKind::Union);
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    let (fields, mut errs) = parse_fields(tokens, idx)?;
+    errors.append(&mut errs);
+
+    let span = union_span.to(tokens[*idx - 1].span);
+
     Some((
-        ParsedStruct::Transparent(name, name_span, fields.into_iter().collect()),
+        ParsedUnion {
+            name,
+            name_span,
+            fields,
+            span,
+        },
         errors,
     ))
 }
@@ -408,6 +1432,8 @@ fn parse_struct(tokens: &[Token], idx: &mut usize) -> Option<(ParsedStruct, Vec<
 fn parse_opaque_struct(
     tokens: &[Token],
     idx: &mut usize,
+    attributes: Vec<ParsedAttribute>,
+    is_public: bool,
 ) -> Option<(ParsedStruct, Vec<ParseError>)> {
     let mut errors = vec![];
 
@@ -417,109 +1443,177 @@ fn parse_opaque_struct(
     let (name, name_span, mut errs) = parse_name(tokens, idx)?;
     errors.append(&mut errs);
 
-    expect!(&mut errors, tokens, idx, TokenKind::SemiColon);
+    let size = if let Some(Token {
+        kind: TokenKind::Colon,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume colon token
+        match tokens.get(*idx)? {
+            Token {
+                kind: TokenKind::IntLiteral(size),
+                ..
+            } => {
+                *idx += 1;
+                Some(*size as usize)
+            }
+            token => {
+                *idx += 1;
+                errors.push(ParseError::UnexpectedToken(token.span));
+                None
+            }
+        }
+    } else {
+        None
+    };
 
-    Some((ParsedStruct::Opaque(name, name_span), errors))
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::SemiColon);
+
+    Some((
+        ParsedStruct::Opaque(name, name_span, size, attributes, is_public),
+        errors,
+    ))
 }
 
-fn parse_extern_function(
-    tokens: &[Token],
-    idx: &mut usize,
-) -> Option<(ParsedExternFunction, Vec<ParseError>)> {
+fn parse_impl(tokens: &[Token], idx: &mut usize) -> Option<(ParsedImpl, Vec<ParseError>)> {
     let mut errors = vec![];
 
-    *idx += 1; // Consume `extern` keyword
-
-    expect!(&mut errors, tokens, idx, TokenKind::Fn);
+    let impl_span = tokens.get(*idx)?.span;
+    expect!(&mut errors, tokens, idx, TokenKind::Impl);
 
-    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    let (mut struct_name, mut struct_name_span, mut errs) = parse_name(tokens, idx)?;
     errors.append(&mut errs);
 
-    expect!(&mut errors, tokens, idx, TokenKind::OParen);
-
-    let mut parameters = vec![];
-    while *idx < tokens.len()
-        && !matches!(
-            tokens.get(*idx)?,
-            &Token {
-                kind: TokenKind::CParen,
-                ..
-            }
-        )
+    // The name before `for` is the trait being implemented
+    let mut trait_name = None;
+    if let Token {
+        kind: TokenKind::For,
+        ..
+    } = tokens.get(*idx)?
     {
-        let (param, mut errs) = parse_parameter(tokens, idx)?;
-        parameters.push(param);
+        *idx += 1; // Consume `for` keyword
+        let (name, name_span, mut errs) = parse_name(tokens, idx)?;
         errors.append(&mut errs);
+        trait_name = Some((
+            std::mem::replace(&mut struct_name, name),
+            std::mem::replace(&mut struct_name_span, name_span),
+        ));
+    }
 
-        if matches!(
-            tokens.get(*idx)?,
-            &Token {
-                kind: TokenKind::Comma,
-                ..
-            }
-        ) {
-            *idx += 1;
-        } else {
-            break;
+    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
+
+    let mut methods = vec![];
+    while let Token {
+        kind: TokenKind::Fn,
+        ..
+    } = tokens.get(*idx)?
+    {
+        let (method, mut errs) = parse_function(tokens, idx, vec![], false)?;
+        errors.append(&mut errs);
+        // Only free functions can be generic so far
+        if let Some(generic_param) = method.generic_params.first() {
+            errors.push(ParseError::UnexpectedToken(generic_param.span));
         }
+        methods.push(method);
     }
 
-    expect!(&mut errors, tokens, idx, TokenKind::CParen);
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
 
-    let (return_type, return_type_span) = if let Some(Token {
-        kind: TokenKind::Colon,
+    let span = impl_span.to(tokens[*idx - 1].span);
+
+    Some((
+        ParsedImpl {
+            trait_name,
+            struct_name,
+            struct_name_span,
+            methods,
+            span,
+        },
+        errors,
+    ))
+}
+
+fn parse_trait(tokens: &[Token], idx: &mut usize) -> Option<(ParsedTrait, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let trait_span = tokens.get(*idx)?.span;
+    expect!(&mut errors, tokens, idx, TokenKind::Trait);
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
+
+    let mut methods = vec![];
+    while let Token {
+        kind: TokenKind::Fn,
         ..
-    }) = tokens.get(*idx)
+    } = tokens.get(*idx)?
     {
-        expect!(&mut errors, tokens, idx, TokenKind::Colon);
-        let (return_type, return_type_span, mut errs) = parse_type(tokens, idx)?;
+        let (method, mut errs) = parse_function_declaration(tokens, idx)?;
         errors.append(&mut errs);
+        // Methods of traits can't be variadic
+        if method.is_variadic {
+            errors.push(ParseError::UnexpectedToken(method.name_span));
+        }
+        methods.push(method);
+    }
 
-        (return_type, return_type_span)
-    } else {
-        (Type::Unit, Span::new(FileId(0), 0, 0))
-    };
-
-    // Semicolon should be the very next token, but if there was a parse error before
-    // that might not be the case.
-    // Looking for the next semicolon allows for recovery from an invalid state
-    recover_at_token!(&mut errors, tokens, idx, TokenKind::SemiColon);
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
 
-    let fun = ParsedExternFunction {
-        name,
-        name_span,
-        parameters,
-        return_type,
-        return_type_span,
-    };
+    let span = trait_span.to(tokens[*idx - 1].span);
 
-    Some((fun, errors))
+    Some((
+        ParsedTrait {
+            name,
+            name_span,
+            methods,
+            span,
+        },
+        errors,
+    ))
 }
 
-fn parse_function(tokens: &[Token], idx: &mut usize) -> Option<(ParsedFunction, Vec<ParseError>)> {
+fn parse_enum(tokens: &[Token], idx: &mut usize) -> Option<(ParsedEnum, Vec<ParseError>)> {
     let mut errors = vec![];
 
-    expect!(&mut errors, tokens, idx, TokenKind::Fn);
+    let enum_span = tokens.get(*idx)?.span;
+    expect!(&mut errors, tokens, idx, TokenKind::Enum);
 
     let (name, name_span, mut errs) = parse_name(tokens, idx)?;
     errors.append(&mut errs);
 
-    expect!(&mut errors, tokens, idx, TokenKind::OParen);
+    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
 
-    let mut parameters = vec![];
+    let mut variants = vec![];
     while *idx < tokens.len()
         && !matches!(
             tokens.get(*idx)?,
             &Token {
-                kind: TokenKind::CParen,
+                kind: TokenKind::CBrace,
                 ..
             }
         )
     {
-        let (param, mut errs) = parse_parameter(tokens, idx)?;
-        parameters.push(param);
+        let (variant_name, variant_span, mut errs) = parse_name(tokens, idx)?;
         errors.append(&mut errs);
 
+        let payload_type = if let Token {
+            kind: TokenKind::OParen,
+            ..
+        } = tokens.get(*idx)?
+        {
+            *idx += 1; // Consume `(` token
+            let (payload_type, _payload_type_span, mut errs) = parse_type(tokens, idx)?;
+            errors.append(&mut errs);
+            expect!(&mut errors, tokens, idx, TokenKind::CParen);
+            Some(payload_type)
+        } else {
+            None
+        };
+
+        variants.push((variant_name, variant_span, payload_type));
+
         if matches!(
             tokens.get(*idx)?,
             &Token {
@@ -533,305 +1627,1602 @@ fn parse_function(tokens: &[Token], idx: &mut usize) -> Option<(ParsedFunction,
         }
     }
 
-    expect!(&mut errors, tokens, idx, TokenKind::CParen);
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
 
-    let (return_type, return_type_span) = if let Some(Token {
-        kind: TokenKind::Colon,
-        ..
-    }) = tokens.get(*idx)
-    {
-        expect!(&mut errors, tokens, idx, TokenKind::Colon);
-        let (return_type, return_type_span, mut errs) = parse_type(tokens, idx)?;
-        errors.append(&mut errs);
+    let span = enum_span.to(tokens[*idx - 1].span);
 
-        (return_type, return_type_span)
-    } else {
-        (Type::Unit, Span::new(FileId(0), 0, 0))
-    };
+    Some((
+        ParsedEnum {
+            name,
+            name_span,
+            variants,
+            span,
+        },
+        errors,
+    ))
+}
 
-    let (body, mut errs) = parse_block(tokens, idx)?;
+fn parse_enum_variant(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedEnumVariant, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let (enum_name, enum_name_span, mut errs) = parse_name(tokens, idx)?;
     errors.append(&mut errs);
 
-    let fun = ParsedFunction {
-        name,
-        name_span,
+    expect!(&mut errors, tokens, idx, TokenKind::ColonColon);
+
+    let (variant_name, variant_name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    let value = if let Token {
+        kind: TokenKind::OParen,
+        ..
+    } = tokens.get(*idx)?
+    {
+        *idx += 1; // Consume `(` token
+        let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+        errors.append(&mut errs);
+        expect!(&mut errors, tokens, idx, TokenKind::CParen);
+        Some(Box::new(value))
+    } else {
+        None
+    };
+
+    let span = enum_name_span.to(tokens[*idx - 1].span);
+
+    Some((
+        ParsedEnumVariant {
+            enum_name,
+            enum_name_span,
+            variant_name,
+            variant_name_span,
+            value,
+            span,
+        },
+        errors,
+    ))
+}
+
+fn parse_import(tokens: &[Token], idx: &mut usize) -> Option<(ParsedImport, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    *idx += 1; // Consume `import` keyword
+
+    let (path, path_span) = match tokens.get(*idx)? {
+        Token {
+            kind: TokenKind::StringLiteral(path),
+            span,
+        } => {
+            *idx += 1; // Consume path
+            (path.clone(), *span)
+        }
+        token => {
+            errors.push(ParseError::ExpectedToken(
+                TokenKind::StringLiteral(String::new()),
+                token.span,
+            ));
+            (String::new(), token.span)
+        }
+    };
+
+    let alias = if let Token {
+        kind: TokenKind::As,
+        ..
+    } = tokens.get(*idx)?
+    {
+        *idx += 1; // Consume `as` keyword
+        let (alias, alias_span, mut errs) = parse_name(tokens, idx)?;
+        errors.append(&mut errs);
+        Some((alias, alias_span))
+    } else {
+        None
+    };
+
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::SemiColon);
+
+    Some((
+        ParsedImport {
+            path,
+            path_span,
+            alias,
+        },
+        errors,
+    ))
+}
+
+fn parse_type_alias(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedTypeAlias, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let type_span = tokens.get(*idx)?.span;
+    *idx += 1; // Consume `type` keyword
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::Equal);
+
+    let (aliased, _aliased_span, mut errs) = parse_type(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::SemiColon);
+
+    let span = type_span.to(tokens[*idx - 1].span);
+
+    Some((
+        ParsedTypeAlias {
+            name,
+            name_span,
+            aliased,
+            span,
+        },
+        errors,
+    ))
+}
+
+fn parse_const_decl(
+    tokens: &[Token],
+    idx: &mut usize,
+    is_public: bool,
+) -> Option<(ParsedConstDecl, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let const_span = tokens.get(*idx)?.span;
+    *idx += 1; // Consume `const` keyword
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::Colon);
+
+    let (ttype, type_span, mut errs) = parse_type(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::Equal);
+
+    let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+    errors.append(&mut errs);
+
+    let span = const_span.to(tokens[*idx - 1].span);
+
+    Some((
+        ParsedConstDecl {
+            is_public,
+            name,
+            name_span,
+            ttype,
+            type_span,
+            value,
+            span,
+        },
+        errors,
+    ))
+}
+
+fn parse_extern_function(
+    tokens: &[Token],
+    idx: &mut usize,
+    attributes: Vec<ParsedAttribute>,
+    is_public: bool,
+) -> Option<(ParsedExternFunction, Vec<ParseError>)> {
+    *idx += 1; // Consume `extern` keyword
+
+    let calling_convention = if let Some(Token {
+        kind: TokenKind::StringLiteral(name),
+        span,
+    }) = tokens.get(*idx)
+    {
+        *idx += 1;
+        Some((name.clone(), *span))
+    } else {
+        None
+    };
+
+    let (func, errors) = parse_function_declaration(tokens, idx)?;
+    Some((
+        ParsedExternFunction {
+            attributes,
+            calling_convention,
+            is_public,
+            ..func
+        },
+        errors,
+    ))
+}
+
+/// A function without a body, `fn name(...): type;`, as declared by `extern` or a trait.
+fn parse_function_declaration(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedExternFunction, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    expect!(&mut errors, tokens, idx, TokenKind::Fn);
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::OParen);
+
+    let mut parameters = vec![];
+    let mut is_variadic = false;
+    while *idx < tokens.len()
+        && !matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::CParen,
+                ..
+            }
+        )
+    {
+        // `...` takes the place of any number of extra arguments after the last parameter
+        if let Token {
+            kind: TokenKind::DotDotDot,
+            ..
+        } = tokens.get(*idx)?
+        {
+            *idx += 1; // Consume `...` token
+            is_variadic = true;
+            break;
+        }
+
+        let (param, mut errs) = parse_parameter(tokens, idx)?;
+        parameters.push(param);
+        errors.append(&mut errs);
+
+        if matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::Comma,
+                ..
+            }
+        ) {
+            *idx += 1;
+        } else {
+            break;
+        }
+    }
+
+    expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+    let (return_type, return_type_span) = if let Some(Token {
+        kind: TokenKind::Colon,
+        ..
+    }) = tokens.get(*idx)
+    {
+        expect!(&mut errors, tokens, idx, TokenKind::Colon);
+        let (return_type, return_type_span, mut errs) = parse_type(tokens, idx)?;
+        errors.append(&mut errs);
+
+        (return_type, return_type_span)
+    } else {
+        (ParsedType::Unit, Span::synthetic())
+    };
+
+    // Semicolon should be the very next token, but if there was a parse error before
+    // that might not be the case.
+    // Looking for the next semicolon allows for recovery from an invalid state
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::SemiColon);
+
+    let fun = ParsedExternFunction {
+        attributes: vec![],
+        calling_convention: None,
+        is_public: false,
+        name,
+        name_span,
+        parameters,
+        is_variadic,
+        return_type,
+        return_type_span,
+    };
+
+    Some((fun, errors))
+}
+
+fn parse_function(
+    tokens: &[Token],
+    idx: &mut usize,
+    attributes: Vec<ParsedAttribute>,
+    is_public: bool,
+) -> Option<(ParsedFunction, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    expect!(&mut errors, tokens, idx, TokenKind::Fn);
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    let (generic_params, mut errs) = parse_generic_params(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::OParen);
+
+    let mut parameters = vec![];
+    while *idx < tokens.len()
+        && !matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::CParen,
+                ..
+            }
+        )
+    {
+        let (param, mut errs) = parse_parameter(tokens, idx)?;
+        parameters.push(param);
+        errors.append(&mut errs);
+
+        if matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::Comma,
+                ..
+            }
+        ) {
+            *idx += 1;
+        } else {
+            break;
+        }
+    }
+
+    expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+    let (return_type, return_type_span) = if let Some(Token {
+        kind: TokenKind::Colon,
+        ..
+    }) = tokens.get(*idx)
+    {
+        expect!(&mut errors, tokens, idx, TokenKind::Colon);
+        let (return_type, return_type_span, mut errs) = parse_type(tokens, idx)?;
+        errors.append(&mut errs);
+
+        (return_type, return_type_span)
+    } else {
+        (ParsedType::Unit, Span::synthetic())
+    };
+
+    let (body, mut errs) = parse_block(tokens, idx)?;
+    errors.append(&mut errs);
+
+    let is_inline = attributes
+        .iter()
+        .any(|attribute| attribute.name == "inline");
+    let fun = ParsedFunction {
+        is_public,
+        name,
+        name_span,
+        generic_params,
         body,
         parameters,
         return_type,
         return_type_span,
+        attributes,
+        is_inline,
     };
 
     Some((fun, errors))
 }
 
-fn parse_type(tokens: &[Token], idx: &mut usize) -> Option<(Type, Span, Vec<ParseError>)> {
+/// The optional type parameters `<T, ...>` following the name of a function or struct.
+fn parse_generic_params(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(Vec<ParsedGenericParam>, Vec<ParseError>)> {
     let mut errors = vec![];
 
-    let is_pointer = matches!(
-        tokens.get(*idx)?,
-        Token {
-            kind: TokenKind::RightArrow,
-            ..
-        }
-    );
-    if is_pointer {
-        *idx += 1;
-    }
-    let is_mut_pointer = is_pointer
-        && matches!(
+    let mut generic_params = vec![];
+    if let Some(Token {
+        kind: TokenKind::LessThan,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1;
+        while !matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::GreaterThan,
+                ..
+            }
+        ) {
+            let (name, span, mut errs) = parse_name(tokens, idx)?;
+            errors.append(&mut errs);
+
+            let mut bounds = vec![];
+            if let Token {
+                kind: TokenKind::Colon,
+                ..
+            } = tokens.get(*idx)?
+            {
+                *idx += 1; // Consume `:` token
+                loop {
+                    let (bound, bound_span, mut errs) = parse_name(tokens, idx)?;
+                    errors.append(&mut errs);
+                    bounds.push((bound, bound_span));
+
+                    if let Token {
+                        kind: TokenKind::Plus,
+                        ..
+                    } = tokens.get(*idx)?
+                    {
+                        *idx += 1;
+                    } else {
+                        break;
+                    }
+                }
+            }
+
+            generic_params.push(ParsedGenericParam { name, span, bounds });
+
+            if matches!(
+                tokens.get(*idx)?,
+                &Token {
+                    kind: TokenKind::Comma,
+                    ..
+                }
+            ) {
+                *idx += 1;
+            } else {
+                break;
+            }
+        }
+        expect!(&mut errors, tokens, idx, TokenKind::GreaterThan);
+    }
+
+    Some((generic_params, errors))
+}
+
+/// The type arguments `<T, ...>` of a generic struct or function. A `>>` closing this list also
+/// closes the enclosing one, which is recorded in `closes_outer`.
+fn parse_type_args(
+    tokens: &[Token],
+    idx: &mut usize,
+    closes_outer: &mut bool,
+) -> Option<(Vec<ParsedType>, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    expect!(&mut errors, tokens, idx, TokenKind::LessThan);
+
+    let mut type_args = vec![];
+    loop {
+        match tokens.get(*idx)? {
+            Token {
+                kind: TokenKind::GreaterThan,
+                ..
+            } => {
+                *idx += 1;
+                break;
+            }
+            Token {
+                kind: TokenKind::GreaterGreater,
+                ..
+            } => {
+                *idx += 1;
+                *closes_outer = true;
+                break;
+            }
+            _ => {}
+        }
+
+        let mut closed = false;
+        let (type_arg, _type_arg_span, mut errs) = parse_type_closing(tokens, idx, &mut closed)?;
+        errors.append(&mut errs);
+        type_args.push(type_arg);
+        if closed {
+            break;
+        }
+
+        match tokens.get(*idx)? {
+            Token {
+                kind: TokenKind::Comma,
+                ..
+            } => *idx += 1,
+            Token {
+                kind: TokenKind::GreaterThan | TokenKind::GreaterGreater,
+                ..
+            } => {}
+            tok => {
+                errors.push(ParseError::ExpectedToken(TokenKind::GreaterThan, tok.span));
+                break;
+            }
+        }
+    }
+
+    Some((type_args, errors))
+}
+
+/// Type arguments given in an expression, which no enclosing type arguments can be closed with.
+fn parse_explicit_type_args(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(Vec<ParsedType>, Vec<ParseError>)> {
+    let mut closes_outer = false;
+    let (type_args, mut errors) = parse_type_args(tokens, idx, &mut closes_outer)?;
+    if closes_outer {
+        errors.push(ParseError::UnexpectedToken(tokens[*idx - 1].span));
+    }
+    Some((type_args, errors))
+}
+
+fn parse_type(tokens: &[Token], idx: &mut usize) -> Option<(ParsedType, Span, Vec<ParseError>)> {
+    let mut closes_outer = false;
+    let (ttype, type_span, mut errors) = parse_type_closing(tokens, idx, &mut closes_outer)?;
+    // A `>>` closing type arguments without any enclosing ones
+    if closes_outer {
+        errors.push(ParseError::UnexpectedToken(tokens[*idx - 1].span));
+    }
+    Some((ttype, type_span, errors))
+}
+
+/// Parses a type, which may end in type arguments closed by a `>>` that also closes the type
+/// arguments the type is part of.
+fn parse_type_closing(
+    tokens: &[Token],
+    idx: &mut usize,
+    closes_outer: &mut bool,
+) -> Option<(ParsedType, Span, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let is_nullable = matches!(
+        tokens.get(*idx)?,
+        Token {
+            kind: TokenKind::Question,
+            ..
+        }
+    );
+    if is_nullable {
+        *idx += 1; // Consume `?` token
+    }
+    let is_pointer = matches!(
+        tokens.get(*idx)?,
+        Token {
+            kind: TokenKind::RightArrow,
+            ..
+        }
+    );
+    if is_pointer {
+        *idx += 1;
+    } else if is_nullable {
+        errors.push(ParseError::ExpectedToken(
+            TokenKind::RightArrow,
+            tokens.get(*idx)?.span,
+        ));
+    }
+    let is_mut_pointer = is_pointer
+        && matches!(
+            tokens.get(*idx)?,
+            Token {
+                kind: TokenKind::Mut,
+                ..
+            }
+        );
+    if is_mut_pointer {
+        *idx += 1;
+    }
+    let wrap_pointer = |ttype| {
+        if is_nullable {
+            ParsedType::NullablePointer(Box::new(ttype), is_mut_pointer)
+        } else if is_pointer {
+            ParsedType::Pointer(Box::new(ttype), is_mut_pointer)
+        } else {
+            ttype
+        }
+    };
+
+    let (ttype, type_span) = match tokens.get(*idx)? {
+        tok @ &Token {
+            kind: TokenKind::Ident(ref name),
+            ..
+        } => {
+            *idx += 1;
+            if let Some(Token {
+                kind: TokenKind::LessThan,
+                ..
+            }) = tokens.get(*idx)
+            {
+                let (type_args, mut errs) = parse_type_args(tokens, idx, closes_outer)?;
+                errors.append(&mut errs);
+                (
+                    ParsedType::Generic(name.clone(), type_args),
+                    tok.span.to(tokens[*idx - 1].span),
+                )
+            } else {
+                (ParsedType::Name(name.clone()), tok.span)
+            }
+        }
+        &Token {
+            kind: TokenKind::OParen,
+            span: oparen_span,
+        } => {
+            *idx += 1; // Consume `(` token
+
+            let mut element_types = vec![];
+            let mut has_trailing_comma = false;
+            while *idx < tokens.len()
+                && !matches!(
+                    tokens.get(*idx)?,
+                    &Token {
+                        kind: TokenKind::CParen,
+                        ..
+                    }
+                )
+            {
+                let (element_type, _element_type_span, mut errs) = parse_type(tokens, idx)?;
+                errors.append(&mut errs);
+                element_types.push(element_type);
+
+                has_trailing_comma = matches!(
+                    tokens.get(*idx)?,
+                    &Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    }
+                );
+                if has_trailing_comma {
+                    *idx += 1;
+                } else {
+                    break;
+                }
+            }
+
+            expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+            // `()` is the unit type and `(T)` is just `T`, one element tuples are written `(T,)`
+            let ttype = match element_types.len() {
+                0 => ParsedType::Unit,
+                1 if !has_trailing_comma => element_types.pop().unwrap(),
+                _ => ParsedType::Tuple(element_types),
+            };
+            (ttype, oparen_span.to(tokens[*idx - 1].span))
+        }
+        &Token {
+            kind: TokenKind::Fn,
+            span: fn_span,
+        } => {
+            *idx += 1; // Consume `fn` keyword
+
+            expect!(&mut errors, tokens, idx, TokenKind::OParen);
+
+            let mut params = vec![];
+            while *idx < tokens.len()
+                && !matches!(
+                    tokens.get(*idx)?,
+                    &Token {
+                        kind: TokenKind::CParen,
+                        ..
+                    }
+                )
+            {
+                let (param, _param_span, mut errs) = parse_type(tokens, idx)?;
+                errors.append(&mut errs);
+                params.push(param);
+
+                if let Some(&Token {
+                    kind: TokenKind::Comma,
+                    ..
+                }) = tokens.get(*idx)
+                {
+                    *idx += 1;
+                } else {
+                    break;
+                }
+            }
+
+            expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+            // Like in function declarations the return type is optional
+            let return_type = if let Some(&Token {
+                kind: TokenKind::Colon,
+                ..
+            }) = tokens.get(*idx)
+            {
+                *idx += 1; // Consume `:` token
+                let (return_type, _return_type_span, mut errs) =
+                    parse_type_closing(tokens, idx, closes_outer)?;
+                errors.append(&mut errs);
+                return_type
+            } else {
+                ParsedType::Unit
+            };
+
+            (
+                ParsedType::FnPtr {
+                    params,
+                    return_type: Box::new(return_type),
+                },
+                fn_span.to(tokens[*idx - 1].span),
+            )
+        }
+        &Token {
+            kind: TokenKind::OBracket,
+            span: obracket_span,
+        } => {
+            *idx += 1; // Consume `[` token
+
+            let (element_type, _element_type_span, mut errs) = parse_type(tokens, idx)?;
+            errors.append(&mut errs);
+
+            // Slices `[T]` leave out the size of arrays `[T; N]`
+            if let Some(&Token {
+                kind: TokenKind::CBracket,
+                span: cbracket_span,
+            }) = tokens.get(*idx)
+            {
+                *idx += 1; // Consume `]` token
+                let ttype = wrap_pointer(ParsedType::Slice(Box::new(element_type)));
+                return Some((ttype, obracket_span.to(cbracket_span), errors));
+            }
+
+            expect!(&mut errors, tokens, idx, TokenKind::SemiColon);
+
+            let size = match tokens.get(*idx)? {
+                &Token {
+                    kind: TokenKind::IntLiteral(size),
+                    ..
+                } => {
+                    *idx += 1;
+                    size as usize
+                }
+                tok => {
+                    errors.push(ParseError::UnexpectedToken(tok.span));
+                    0
+                }
+            };
+
+            expect!(&mut errors, tokens, idx, TokenKind::CBracket);
+
+            (
+                ParsedType::Array(Box::new(element_type), size),
+                obracket_span.to(tokens[*idx - 1].span),
+            )
+        }
+        tok => {
+            errors.push(ParseError::ExpectedIdentifier(tok.span));
+            (ParsedType::Unit, tok.span)
+        }
+    };
+
+    Some((wrap_pointer(ttype), type_span, errors))
+}
+
+fn parse_parameter(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(FunctionParameter, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::Colon);
+
+    let is_by_ref = if let Some(Token {
+        kind: TokenKind::Ref,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume `ref` keyword
+        true
+    } else {
+        false
+    };
+
+    let (ttype, type_span, mut errs) = parse_type(tokens, idx)?;
+    errors.append(&mut errs);
+
+    Some((
+        FunctionParameter {
+            name,
+            name_span,
+            is_by_ref,
+            ttype,
+            type_span,
+        },
+        errors,
+    ))
+}
+
+fn parse_block(tokens: &[Token], idx: &mut usize) -> Option<(ParsedBlock, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
+
+    let mut statements = vec![];
+    let mut tail_expr = None;
+    while *idx < tokens.len()
+        && !matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::CBrace,
+                ..
+            }
+        )
+    {
+        let (stmt, mut errs) = parse_statement(tokens, idx)?;
+        errors.append(&mut errs);
+
+        // An expression directly followed by the closing brace is the value of the block
+        let ends_block = matches!(
+            tokens.get(*idx),
+            Some(Token {
+                kind: TokenKind::CBrace,
+                ..
+            })
+        ) && !matches!(
+            tokens[*idx - 1],
+            Token {
+                kind: TokenKind::SemiColon,
+                ..
+            }
+        );
+        match stmt {
+            ParsedStatement::Expression(expr) if ends_block => tail_expr = Some(expr),
+            stmt => statements.push(stmt),
+        }
+    }
+
+    expect!(&mut errors, tokens, idx, TokenKind::CBrace);
+
+    Some((
+        ParsedBlock {
+            statements,
+            tail_expr,
+        },
+        errors,
+    ))
+}
+
+fn parse_statement(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedStatement, Vec<ParseError>)> {
+    let (statement, mut errors, needs_semi) = match tokens.get(*idx)? {
+        Token {
+            kind: TokenKind::Let,
+            ..
+        } => {
+            let mut errors = vec![];
+
+            *idx += 1; // Consume `let` token
+
+            let is_mut = if let Token {
+                kind: TokenKind::Mut,
+                ..
+            } = tokens.get(*idx)?
+            {
+                *idx += 1; // Consume `mut` token
+                true
+            } else {
+                false
+            };
+
+            let (pattern, mut errs) = parse_let_pattern(tokens, idx)?;
+            errors.append(&mut errs);
+
+            let type_annotation = if let Token {
+                kind: TokenKind::Colon,
+                ..
+            } = tokens.get(*idx)?
+            {
+                *idx += 1; // Consume `:` token
+                let (ttype, type_span, mut errs) = parse_type(tokens, idx)?;
+                errors.append(&mut errs);
+                Some((ttype, type_span))
+            } else {
+                None
+            };
+
+            expect!(&mut errors, tokens, idx, TokenKind::Equal);
+
+            let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+            errors.append(&mut errs);
+
+            (
+                ParsedStatement::LetAssign(ParsedLetAssign {
+                    pattern,
+                    type_annotation,
+                    value,
+                    is_mut,
+                }),
+                errors,
+                true,
+            )
+        }
+        Token {
+            kind: TokenKind::Const,
+            ..
+        } => {
+            let (const_decl, errors) = parse_const_decl(tokens, idx, false)?;
+            (ParsedStatement::Const(const_decl), errors, true)
+        }
+        Token {
+            kind: TokenKind::Match,
+            ..
+        } => {
+            let (r#match, errors) = parse_match(tokens, idx)?;
+            (ParsedStatement::Match(r#match), errors, false)
+        }
+        Token {
+            kind: TokenKind::Switch,
+            ..
+        } => {
+            let (switch, errors) = parse_switch(tokens, idx)?;
+            (ParsedStatement::Switch(switch), errors, false)
+        }
+        Token {
+            kind: TokenKind::While,
+            ..
+        } if matches!(
+            tokens.get(*idx + 1),
+            Some(Token {
+                kind: TokenKind::Let,
+                ..
+            })
+        ) =>
+        {
+            let (stmt, errors) = parse_while_let(tokens, idx)?;
+            (stmt, errors, false)
+        }
+        Token {
+            kind: TokenKind::While,
+            ..
+        } => {
+            let (stmt, errors) = parse_while_loop(tokens, idx)?;
+            (ParsedStatement::WhileLoop(stmt), errors, false)
+        }
+        Token {
+            kind: TokenKind::Loop,
+            span: loop_span,
+        } => {
+            *idx += 1; // Consume `loop` token
+            let (body, errors) = parse_block(tokens, idx)?;
+            let span = loop_span.to(tokens[*idx - 1].span);
+            (ParsedStatement::Loop(body, None, span), errors, false)
+        }
+        Token {
+            kind: TokenKind::Lifetime(name),
+            span,
+        } => {
+            let mut errors = vec![];
+
+            *idx += 1; // Consume label
+            expect!(&mut errors, tokens, idx, TokenKind::Colon);
+
+            let label = Some((name.clone(), *span));
+            let (mut statement, mut errs) = parse_statement(tokens, idx)?;
+            errors.append(&mut errs);
+            match statement {
+                ParsedStatement::WhileLoop(ref mut while_loop) => while_loop.label = label,
+                ParsedStatement::WhileLet {
+                    label: ref mut loop_label,
+                    ..
+                } => *loop_label = label,
+                ParsedStatement::ForInLoop(ref mut for_in) => for_in.label = label,
+                ParsedStatement::Loop(_, ref mut loop_label, _) => *loop_label = label,
+                _ => errors.push(ParseError::LabelWithoutLoop(*span)),
+            }
+            (statement, errors, false)
+        }
+        Token {
+            kind: TokenKind::If,
+            ..
+        } => {
+            let (if_else, errors) = parse_if_else(tokens, idx)?;
+            (ParsedStatement::IfElse(if_else), errors, false)
+        }
+        Token {
+            kind: TokenKind::For,
+            ..
+        } => {
+            let (for_in, errors) = parse_for_in_loop(tokens, idx)?;
+            (ParsedStatement::ForInLoop(for_in), errors, false)
+        }
+        Token {
+            kind: TokenKind::OBrace,
+            span: obrace_span,
+        } => {
+            // A block statement ends at its closing brace, so `{ ... } -x;` isn't a subtraction
+            let (block, errors) = parse_block(tokens, idx)?;
+            let span = obrace_span.to(tokens[*idx - 1].span);
+            if let Some(Token {
+                kind: TokenKind::SemiColon,
+                ..
+            }) = tokens.get(*idx)
+            {
+                *idx += 1; // Consume optional `;` token
+            }
+            (
+                ParsedStatement::Expression(ParsedExpression::Block(Box::new(block), span)),
+                errors,
+                false,
+            )
+        }
+        Token {
+            kind: TokenKind::Return,
+            ..
+        } => {
+            *idx += 1; // Consume `return` token
+            let (return_value, errors) = parse_expression(tokens, idx, Restriction::None)?;
+            (ParsedStatement::Return(return_value), errors, true)
+        }
+        Token {
+            kind: TokenKind::Break,
+            span,
+        } => {
+            *idx += 1; // Consume `break` token
+            let label = parse_loop_label(tokens, idx);
+            (ParsedStatement::Break(label, *span), vec![], true)
+        }
+        Token {
+            kind: TokenKind::Continue,
+            span,
+        } => {
+            *idx += 1; // Consume `continue` token
+            let label = parse_loop_label(tokens, idx);
+            (ParsedStatement::Continue(label, *span), vec![], true)
+        }
+        Token {
+            kind: TokenKind::Defer,
+            span,
+        } => {
+            *idx += 1; // Consume `defer` token
+            let (call, errors) = parse_expression(tokens, idx, Restriction::None)?;
+            (ParsedStatement::Defer(call, *span), errors, true)
+        }
+        Token {
+            kind: TokenKind::Unsafe,
+            span: unsafe_span,
+        } => {
+            *idx += 1; // Consume `unsafe` token
+            let (body, errors) = parse_block(tokens, idx)?;
+            let span = unsafe_span.to(tokens[*idx - 1].span);
+            (ParsedStatement::Unsafe(body, span), errors, false)
+        }
+        _ => {
+            let (expr, errors) = parse_expression(tokens, idx, Restriction::None)?;
+
+            // The expression ending a block needs no semicolon, it is the value of the block
+            let needs_semi = !matches!(
+                tokens.get(*idx),
+                Some(Token {
+                    kind: TokenKind::CBrace,
+                    ..
+                })
+            );
+            (ParsedStatement::Expression(expr), errors, needs_semi)
+        }
+    };
+
+    if needs_semi {
+        // Semicolon should be the very next token, but if there was a parse error before
+        // that might not be the case.
+        // Looking for the next semicolon allows for recovery from an invalid state
+        recover_at_token!(&mut errors, tokens, idx, TokenKind::SemiColon);
+    }
+
+    Some((statement, errors))
+}
+
+/// The label after `break` or `continue`, if there is one.
+fn parse_loop_label(tokens: &[Token], idx: &mut usize) -> Option<(String, Span)> {
+    let Some(Token {
+        kind: TokenKind::Lifetime(name),
+        span,
+    }) = tokens.get(*idx)
+    else {
+        return None;
+    };
+    *idx += 1; // Consume label
+    Some((name.clone(), *span))
+}
+
+fn parse_for_in_loop(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedForInLoop, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    expect!(&mut errors, tokens, idx, TokenKind::For);
+
+    // The index comes first in `for index, elem in xs`
+    let ((elem_var_name, elem_var_name_span), index_var) = if matches!(
+        tokens.get(*idx + 1),
+        Some(Token {
+            kind: TokenKind::Comma,
+            ..
+        })
+    ) {
+        let (index_var_name, index_var_name_span, mut errs) = parse_name(tokens, idx)?;
+        errors.append(&mut errs);
+
+        expect!(&mut errors, tokens, idx, TokenKind::Comma);
+
+        let (elem_var_name, elem_var_name_span, mut errs) = parse_name(tokens, idx)?;
+        errors.append(&mut errs);
+
+        (
+            (elem_var_name, elem_var_name_span),
+            Some((index_var_name, index_var_name_span)),
+        )
+    } else {
+        let (elem_var_name, elem_var_name_span, mut errs) = parse_name(tokens, idx)?;
+        errors.append(&mut errs);
+
+        ((elem_var_name, elem_var_name_span), None)
+    };
+
+    expect!(&mut errors, tokens, idx, TokenKind::In);
+
+    let (iterable_value, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
+    errors.append(&mut errs);
+
+    let (body, mut errs) = parse_block(tokens, idx)?;
+    errors.append(&mut errs);
+
+    Some((
+        ParsedForInLoop {
+            elem_var_name,
+            elem_var_name_span,
+            index_var,
+            iterable_value,
+            body,
+            label: None,
+        },
+        errors,
+    ))
+}
+
+fn parse_match(tokens: &[Token], idx: &mut usize) -> Option<(ParsedMatch, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let match_span = tokens.get(*idx)?.span;
+    *idx += 1; // Consume `match` keyword
+
+    let (scrutinee, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
+
+    let mut arms = vec![];
+    while *idx < tokens.len()
+        && !matches!(
+            tokens.get(*idx)?,
+            &Token {
+                kind: TokenKind::CBrace,
+                ..
+            }
+        )
+    {
+        let (pattern, mut errs) = parse_pattern(tokens, idx)?;
+        errors.append(&mut errs);
+
+        expect!(&mut errors, tokens, idx, TokenKind::FatArrow);
+
+        let (body, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+        errors.append(&mut errs);
+
+        let span = pattern.span().to(body.span());
+        arms.push(ParsedMatchArm {
+            pattern,
+            body,
+            span,
+        });
+
+        if matches!(
             tokens.get(*idx)?,
-            Token {
-                kind: TokenKind::Mut,
+            &Token {
+                kind: TokenKind::Comma,
                 ..
             }
-        );
-    if is_mut_pointer {
-        *idx += 1;
+        ) {
+            *idx += 1;
+        } else {
+            break;
+        }
     }
 
-    let (ttype, type_span) = if let tok @ &Token {
-        kind: TokenKind::Ident(ref name),
-        ..
-    } = tokens.get(*idx)?
-    {
-        *idx += 1;
-        (Type::from_string(name), tok.span)
-    } else {
-        let span = tokens.get(*idx)?.span;
-        errors.push(ParseError::ExpectedIdentifier(span));
-        (Type::Unit, span)
-    };
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
 
-    let ttype = if is_pointer {
-        Type::Pointer(Box::new(ttype), is_mut_pointer)
-    } else {
-        ttype
-    };
+    let span = match_span.to(tokens[*idx - 1].span);
 
-    Some((ttype, type_span, errors))
+    Some((
+        ParsedMatch {
+            scrutinee: Box::new(scrutinee),
+            arms,
+            span,
+        },
+        errors,
+    ))
 }
 
-fn parse_parameter(
-    tokens: &[Token],
-    idx: &mut usize,
-) -> Option<(FunctionParameter, Vec<ParseError>)> {
+fn parse_switch(tokens: &[Token], idx: &mut usize) -> Option<(ParsedSwitch, Vec<ParseError>)> {
     let mut errors = vec![];
 
-    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+    let switch_span = tokens.get(*idx)?.span;
+    *idx += 1; // Consume `switch` keyword
+
+    let (scrutinee, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
     errors.append(&mut errs);
 
-    expect!(&mut errors, tokens, idx, TokenKind::Colon);
+    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
 
-    let (ttype, type_span, mut errs) = parse_type(tokens, idx)?;
-    errors.append(&mut errs);
+    let mut cases = vec![];
+    let mut default = None;
+    while *idx < tokens.len() {
+        match tokens.get(*idx)? {
+            Token {
+                kind: TokenKind::Case,
+                ..
+            } => {
+                *idx += 1; // Consume `case` keyword
+
+                let mut values = vec![];
+                loop {
+                    let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+                    errors.append(&mut errs);
+                    values.push(value);
+
+                    if let Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    } = tokens.get(*idx)?
+                    {
+                        *idx += 1;
+                    } else {
+                        break;
+                    }
+                }
+                expect!(&mut errors, tokens, idx, TokenKind::Colon);
+
+                let (body, mut errs) = parse_switch_case_body(tokens, idx)?;
+                errors.append(&mut errs);
+                cases.push((values, body));
+            }
+            Token {
+                kind: TokenKind::Default,
+                span,
+            } => {
+                *idx += 1; // Consume `default` keyword
+                expect!(&mut errors, tokens, idx, TokenKind::Colon);
+
+                let (body, mut errs) = parse_switch_case_body(tokens, idx)?;
+                errors.append(&mut errs);
+                if default.is_some() {
+                    errors.push(ParseError::UnexpectedToken(*span));
+                }
+                default = Some(body);
+            }
+            _ => break,
+        }
+    }
+
+    recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
+
+    let span = switch_span.to(tokens[*idx - 1].span);
 
     Some((
-        FunctionParameter {
-            name,
-            name_span,
-            ttype,
-            type_span,
+        ParsedSwitch {
+            scrutinee: Box::new(scrutinee),
+            cases,
+            default,
+            span,
         },
         errors,
     ))
 }
 
-fn parse_block(tokens: &[Token], idx: &mut usize) -> Option<(ParsedBlock, Vec<ParseError>)> {
+/// The statements after a `case` or `default` label, which end at the next label or the end of
+/// the `switch`.
+fn parse_switch_case_body(
+    tokens: &[Token],
+    idx: &mut usize,
+) -> Option<(ParsedBlock, Vec<ParseError>)> {
     let mut errors = vec![];
 
-    expect!(&mut errors, tokens, idx, TokenKind::OBrace);
-
     let mut statements = vec![];
     while *idx < tokens.len()
         && !matches!(
-            tokens.get(*idx)?,
-            &Token {
-                kind: TokenKind::CBrace,
-                ..
-            }
+            tokens.get(*idx)?.kind,
+            TokenKind::Case | TokenKind::Default | TokenKind::CBrace
         )
     {
         let (stmt, mut errs) = parse_statement(tokens, idx)?;
-        statements.push(stmt);
         errors.append(&mut errs);
+        statements.push(stmt);
     }
 
-    expect!(&mut errors, tokens, idx, TokenKind::CBrace);
-
-    Some((ParsedBlock { statements }, errors))
+    Some((
+        ParsedBlock {
+            statements,
+            tail_expr: None,
+        },
+        errors,
+    ))
 }
 
-fn parse_statement(
+fn parse_let_pattern(
     tokens: &[Token],
     idx: &mut usize,
-) -> Option<(ParsedStatement, Vec<ParseError>)> {
-    let (statement, mut errors, needs_semi) = match tokens.get(*idx)? {
+) -> Option<(ParsedLetPattern, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let start_span = tokens.get(*idx)?.span;
+    match tokens.get(*idx)? {
         Token {
-            kind: TokenKind::Let,
+            kind: TokenKind::OParen,
             ..
         } => {
-            let mut errors = vec![];
+            *idx += 1; // Consume `(` token
 
-            *idx += 1; // Consume `let` token
+            let mut elements = vec![];
+            while !matches!(
+                tokens.get(*idx)?,
+                &Token {
+                    kind: TokenKind::CParen,
+                    ..
+                }
+            ) {
+                if let Token {
+                    kind: TokenKind::Underscore,
+                    span,
+                } = tokens.get(*idx)?
+                {
+                    *idx += 1; // Consume `_` token
+                    elements.push(("_".to_string(), *span));
+                } else {
+                    let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+                    errors.append(&mut errs);
+                    elements.push((name, name_span));
+                }
 
-            let is_mut = if let Token {
-                kind: TokenKind::Mut,
-                ..
-            } = tokens.get(*idx)?
-            {
-                *idx += 1; // Consume `mut` token
-                true
-            } else {
-                false
-            };
+                if matches!(
+                    tokens.get(*idx)?,
+                    &Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    }
+                ) {
+                    *idx += 1;
+                } else {
+                    break;
+                }
+            }
 
-            let (name, name_span) = if let tok @ &Token {
-                kind: TokenKind::Ident(ref name),
+            recover_at_token!(&mut errors, tokens, idx, TokenKind::CParen);
+
+            let span = start_span.to(tokens[*idx - 1].span);
+            Some((ParsedLetPattern::Tuple(elements, span), errors))
+        }
+        Token {
+            kind: TokenKind::Ident(name),
+            span: name_span,
+        } if matches!(
+            tokens.get(*idx + 1),
+            Some(Token {
+                kind: TokenKind::OBrace,
                 ..
-            } = &tokens.get(*idx)?
-            {
-                *idx += 1;
-                (name.clone(), tok.span)
-            } else {
-                errors.push(ParseError::ExpectedIdentifier(tokens.get(*idx)?.span));
-                (String::new(), tokens.get(*idx)?.span)
-            };
+            })
+        ) =>
+        {
+            *idx += 2; // Consume struct name and `{` token
 
-            expect!(&mut errors, tokens, idx, TokenKind::Equal);
+            let mut fields = vec![];
+            while !matches!(
+                tokens.get(*idx)?,
+                &Token {
+                    kind: TokenKind::CBrace,
+                    ..
+                }
+            ) {
+                let (field_name, field_name_span, mut errs) = parse_name(tokens, idx)?;
+                errors.append(&mut errs);
 
-            let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
-            errors.append(&mut errs);
+                // `field: binding` binds the field to another name than its own
+                if let Token {
+                    kind: TokenKind::Colon,
+                    ..
+                } = tokens.get(*idx)?
+                {
+                    *idx += 1; // Consume `:` token
+                    let (binding, binding_span, mut errs) = parse_name(tokens, idx)?;
+                    errors.append(&mut errs);
+                    fields.push((field_name, binding, binding_span));
+                } else {
+                    fields.push((field_name.clone(), field_name, field_name_span));
+                }
 
-            (
-                ParsedStatement::LetAssign(ParsedLetAssign {
-                    name,
-                    name_span,
-                    value,
-                    is_mut,
-                }),
+                if matches!(
+                    tokens.get(*idx)?,
+                    &Token {
+                        kind: TokenKind::Comma,
+                        ..
+                    }
+                ) {
+                    *idx += 1;
+                } else {
+                    break;
+                }
+            }
+
+            recover_at_token!(&mut errors, tokens, idx, TokenKind::CBrace);
+
+            let span = start_span.to(tokens[*idx - 1].span);
+            Some((
+                ParsedLetPattern::Struct {
+                    name: name.clone(),
+                    name_span: *name_span,
+                    fields,
+                    span,
+                },
                 errors,
-                true,
-            )
+            ))
+        }
+        _ => {
+            let (name, name_span, mut errs) = parse_name(tokens, idx)?;
+            errors.append(&mut errs);
+            Some((ParsedLetPattern::Ident(name, name_span), errors))
         }
+    }
+}
+
+fn parse_pattern(tokens: &[Token], idx: &mut usize) -> Option<(ParsedPattern, Vec<ParseError>)> {
+    let mut errors = vec![];
+
+    let pattern = match tokens.get(*idx)? {
         Token {
-            kind: TokenKind::While,
-            ..
+            kind: TokenKind::Underscore,
+            span,
         } => {
-            let (stmt, errors) = parse_while_loop(tokens, idx)?;
-            (ParsedStatement::WhileLoop(stmt), errors, false)
+            *idx += 1; // Consume `_` token
+            ParsedPattern::Wildcard(*span)
         }
         Token {
-            kind: TokenKind::If,
-            ..
+            kind: TokenKind::IntLiteral(int),
+            span,
         } => {
-            let (if_else, errors) = parse_if_else(tokens, idx)?;
-            (ParsedStatement::IfElse(if_else), errors, false)
+            *idx += 1;
+            ParsedPattern::Literal(Literal::Int(*int, *span))
         }
         Token {
-            kind: TokenKind::For,
-            ..
+            kind: TokenKind::CharLiteral(c),
+            span,
         } => {
-            let (for_in, errors) = parse_for_in_loop(tokens, idx)?;
-            (ParsedStatement::ForInLoop(for_in), errors, false)
+            *idx += 1;
+            ParsedPattern::Literal(Literal::Char(*c, *span))
+        }
+        tok @ Token {
+            kind: TokenKind::True | TokenKind::False,
+            span,
+        } => {
+            *idx += 1;
+            let bool_value = matches!(tok.kind, TokenKind::True);
+            ParsedPattern::Literal(Literal::Bool(bool_value, *span))
         }
         Token {
-            kind: TokenKind::Return,
+            kind: TokenKind::Ident(_),
             ..
         } => {
-            *idx += 1; // Consume `return` token
-            let (return_value, errors) = parse_expression(tokens, idx, Restriction::None)?;
-            (ParsedStatement::Return(return_value), errors, true)
+            let (enum_name, enum_name_span, mut errs) = parse_name(tokens, idx)?;
+            errors.append(&mut errs);
+
+            expect!(&mut errors, tokens, idx, TokenKind::ColonColon);
+
+            let (variant_name, variant_name_span, mut errs) = parse_name(tokens, idx)?;
+            errors.append(&mut errs);
+
+            let binding = if let Token {
+                kind: TokenKind::OParen,
+                ..
+            } = tokens.get(*idx)?
+            {
+                *idx += 1; // Consume `(` token
+                let (binding_name, binding_span, mut errs) = parse_name(tokens, idx)?;
+                errors.append(&mut errs);
+                expect!(&mut errors, tokens, idx, TokenKind::CParen);
+                Some((binding_name, binding_span))
+            } else {
+                None
+            };
+
+            let span = enum_name_span.to(tokens[*idx - 1].span);
+            ParsedPattern::EnumVariant(ParsedEnumVariantPattern {
+                enum_name,
+                enum_name_span,
+                variant_name,
+                variant_name_span,
+                binding,
+                span,
+            })
         }
-        _ => {
-            let (expr, errors) = parse_expression(tokens, idx, Restriction::None)?;
-            (ParsedStatement::Expression(expr), errors, true)
+        tok => {
+            errors.push(ParseError::UnexpectedToken(tok.span));
+            *idx += 1;
+            ParsedPattern::Wildcard(tok.span)
         }
     };
 
-    if needs_semi {
-        // Semicolon should be the very next token, but if there was a parse error before
-        // that might not be the case.
-        // Looking for the next semicolon allows for recovery from an invalid state
-        recover_at_token!(&mut errors, tokens, idx, TokenKind::SemiColon);
-    }
-
-    Some((statement, errors))
+    Some((pattern, errors))
 }
 
-fn parse_for_in_loop(
+fn parse_while_loop(
     tokens: &[Token],
     idx: &mut usize,
-) -> Option<(ParsedForInLoop, Vec<ParseError>)> {
-    let mut errors = vec![];
-
-    expect!(&mut errors, tokens, idx, TokenKind::For);
-
-    let ((elem_var_name, elem_var_name_span), index_var) = if matches!(
-        tokens.get(*idx + 1),
-        Some(Token {
-            kind: TokenKind::Comma,
-            ..
-        })
-    ) {
-        let (index_var_name, index_var_name_span, mut errs) = parse_name(tokens, idx)?;
-        errors.append(&mut errs);
-
-        expect!(&mut errors, tokens, idx, TokenKind::Comma);
-
-        let (elem_var_name, elem_var_name_span, mut errs) = parse_name(tokens, idx)?;
-        errors.append(&mut errs);
-
-        (
-            (elem_var_name, elem_var_name_span),
-            Some((index_var_name, index_var_name_span)),
-        )
-    } else {
-        let (elem_var_name, elem_var_name_span, mut errs) = parse_name(tokens, idx)?;
-        errors.append(&mut errs);
-
-        ((elem_var_name, elem_var_name_span), None)
-    };
+) -> Option<(ParsedWhileLoop, Vec<ParseError>)> {
+    let mut errors = vec![];
 
-    expect!(&mut errors, tokens, idx, TokenKind::In);
+    expect!(&mut errors, tokens, idx, TokenKind::While);
 
-    let (iterable_value, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
+    let (condition, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
     errors.append(&mut errs);
 
     let (body, mut errs) = parse_block(tokens, idx)?;
     errors.append(&mut errs);
 
+    let else_body = if matches!(
+        tokens.get(*idx),
+        Some(Token {
+            kind: TokenKind::Else,
+            ..
+        })
+    ) {
+        *idx += 1; // Consume `else` keyword
+        let (else_body, mut errs) = parse_block(tokens, idx)?;
+        errors.append(&mut errs);
+        Some(else_body)
+    } else {
+        None
+    };
+
     Some((
-        ParsedForInLoop {
-            elem_var_name,
-            elem_var_name_span,
-            index_var,
-            iterable_value,
+        ParsedWhileLoop {
+            condition,
             body,
+            else_body,
+            label: None,
         },
         errors,
     ))
 }
 
-fn parse_while_loop(
+fn parse_while_let(
     tokens: &[Token],
     idx: &mut usize,
-) -> Option<(ParsedWhileLoop, Vec<ParseError>)> {
+) -> Option<(ParsedStatement, Vec<ParseError>)> {
     let mut errors = vec![];
 
-    expect!(&mut errors, tokens, idx, TokenKind::While);
+    let while_span = tokens.get(*idx)?.span;
+    *idx += 2; // Consume `while` and `let` keywords
 
-    let (condition, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
+    let (pattern, mut errs) = parse_pattern(tokens, idx)?;
+    errors.append(&mut errs);
+
+    expect!(&mut errors, tokens, idx, TokenKind::Equal);
+
+    let (value, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
     errors.append(&mut errs);
 
     let (body, mut errs) = parse_block(tokens, idx)?;
     errors.append(&mut errs);
 
-    Some((ParsedWhileLoop { condition, body }, errors))
+    let span = while_span.to(tokens[*idx - 1].span);
+    Some((
+        ParsedStatement::WhileLet {
+            pattern,
+            value,
+            body,
+            label: None,
+            span,
+        },
+        errors,
+    ))
 }
 
 fn parse_if_else(tokens: &[Token], idx: &mut usize) -> Option<(ParsedIfElse, Vec<ParseError>)> {
     let mut errors = vec![];
 
+    let if_span = tokens.get(*idx)?.span;
     expect!(&mut errors, tokens, idx, TokenKind::If);
 
     let (condition, mut errs) = parse_expression(tokens, idx, Restriction::NoStructLiteral)?;
@@ -849,7 +3240,22 @@ fn parse_if_else(tokens: &[Token], idx: &mut usize) -> Option<(ParsedIfElse, Vec
     ) {
         expect!(&mut errors, tokens, idx, TokenKind::Else);
 
-        let (else_body, mut errs) = parse_block(tokens, idx)?;
+        let (else_body, mut errs) = if let Token {
+            kind: TokenKind::If,
+            ..
+        } = tokens.get(*idx)?
+        {
+            let (else_if, errs) = parse_if_else(tokens, idx)?;
+            (
+                ParsedBlock {
+                    statements: vec![ParsedStatement::IfElse(else_if)],
+                    tail_expr: None,
+                },
+                errs,
+            )
+        } else {
+            parse_block(tokens, idx)?
+        };
         errors.append(&mut errs);
 
         Some(else_body)
@@ -857,26 +3263,172 @@ fn parse_if_else(tokens: &[Token], idx: &mut usize) -> Option<(ParsedIfElse, Vec
         None
     };
 
+    let span = if_span.to(tokens[*idx - 1].span);
+
     Some((
         ParsedIfElse {
             condition,
             if_body,
             else_body,
+            span,
         },
         errors,
     ))
 }
 
+/// Expressions are parsed with one function per precedence level, from the loosest to the
+/// tightest binding:
+///
+/// | Level       | Operators                              | Associativity |
+/// |-------------|----------------------------------------|---------------|
+/// | Assignment  | `=` `+=` `-=` `*=` `/=`                | None          |
+/// | Range       | `..` `..=`                             | None          |
+/// | Logical or  | `\|\|`                                 | Left          |
+/// | Logical and | `&&`                                   | Left          |
+/// | Comparison  | `==` `!=` `<` `<=` `>` `>=`            | Left          |
+/// | Sum         | `+` `-`                                | Left          |
+/// | Product     | `*` `/` `%`                            | Left          |
+/// | Unary       | `-` `!` `*` `->` `->mut`               | Prefix        |
+/// | Postfix     | `.field` `.0` `.method()` `[index]`    | Left          |
+///
+/// Every level parses its operands with the function of the next tighter level.
 fn parse_expression(
     tokens: &[Token],
     idx: &mut usize,
     restriction: Restriction,
 ) -> Option<(ParsedExpression, Vec<ParseError>)> {
-    let (expr, mut errors) = parse_assignment(tokens, idx, restriction)?;
-    let expr = if let Some(
+    parse_assignment(tokens, idx, restriction)
+}
+
+fn parse_assignment(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (expr, mut errors) = parse_range(tokens, idx, restriction)?;
+    let expr = match tokens.get(*idx) {
+        Some(Token {
+            kind: TokenKind::Equal,
+            ..
+        }) => {
+            *idx += 1; // Consume operator token
+
+            let (rhs, mut errs) = parse_range(tokens, idx, restriction)?;
+            errors.append(&mut errs);
+
+            ParsedExpression::Assignment(Box::new(expr), Box::new(rhs))
+        }
+        Some(Token {
+            kind:
+                op @ (TokenKind::PlusEqual
+                | TokenKind::MinusEqual
+                | TokenKind::StarEqual
+                | TokenKind::SlashEqual),
+            ..
+        }) => {
+            *idx += 1; // Consume operator token
+
+            let op = match op {
+                TokenKind::PlusEqual => MathOperation::Addition,
+                TokenKind::MinusEqual => MathOperation::Subtraction,
+                TokenKind::StarEqual => MathOperation::Multiplication,
+                _ => MathOperation::Division,
+            };
+
+            let (rhs, mut errs) = parse_range(tokens, idx, restriction)?;
+            errors.append(&mut errs);
+
+            // `a op= b` is sugar for `a = a op b`, so the lhs is evaluated twice
+            let value = ParsedExpression::MathOp(Box::new(expr.clone()), Box::new(rhs), op);
+            ParsedExpression::Assignment(Box::new(expr), Box::new(value))
+        }
+        _ => expr,
+    };
+    Some((expr, errors))
+}
+
+fn parse_range(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (expr, mut errors) = parse_logical_or(tokens, idx, restriction)?;
+    let expr = match tokens.get(*idx) {
+        Some(Token {
+            kind: op @ (TokenKind::DotDot | TokenKind::DotDotEqual),
+            ..
+        }) => {
+            *idx += 1; // Consume operator token
+
+            let (end, mut errs) = parse_logical_or(tokens, idx, restriction)?;
+            errors.append(&mut errs);
+
+            let span = expr.span().to(end.span());
+            ParsedExpression::Range(ParsedRange {
+                start: Box::new(expr),
+                end: Box::new(end),
+                inclusive: matches!(op, TokenKind::DotDotEqual),
+                span,
+            })
+        }
+        _ => expr,
+    };
+    Some((expr, errors))
+}
+
+fn parse_logical_or(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_logical_and(tokens, idx, restriction)?;
+    while let Some(Token {
+        kind: TokenKind::PipePipe,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume operator token
+
+        let (rhs, mut errs) = parse_logical_and(tokens, idx, restriction)?;
+        errors.append(&mut errs);
+
+        expr = ParsedExpression::LogicalOr(Box::new(expr), Box::new(rhs));
+    }
+    Some((expr, errors))
+}
+
+fn parse_logical_and(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_comparison(tokens, idx, restriction)?;
+    while let Some(Token {
+        kind: TokenKind::AmpAmp,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume operator token
+
+        let (rhs, mut errs) = parse_comparison(tokens, idx, restriction)?;
+        errors.append(&mut errs);
+
+        expr = ParsedExpression::LogicalAnd(Box::new(expr), Box::new(rhs));
+    }
+    Some((expr, errors))
+}
+
+fn parse_comparison(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_bit_or(tokens, idx, restriction)?;
+    while let Some(
         tok @ Token {
             kind:
                 TokenKind::EqualEqual
+                | TokenKind::BangEqual
                 | TokenKind::GreaterThan
                 | TokenKind::GreaterThanEqual
                 | TokenKind::LessThan
@@ -889,6 +3441,7 @@ fn parse_expression(
 
         let op = match tok.kind {
             TokenKind::EqualEqual => CompareOperation::Equality,
+            TokenKind::BangEqual => CompareOperation::NotEqual,
             TokenKind::GreaterThan => CompareOperation::GreaterThan,
             TokenKind::GreaterThanEqual => CompareOperation::GreaterThanEqual,
             TokenKind::LessThan => CompareOperation::LessThan,
@@ -896,36 +3449,103 @@ fn parse_expression(
             _ => unreachable!(),
         };
 
-        let (rhs, mut errs) = parse_expression(tokens, idx, restriction)?;
+        let (rhs, mut errs) = parse_bit_or(tokens, idx, restriction)?;
         errors.append(&mut errs);
 
-        ParsedExpression::CompareOp(Box::new(expr), Box::new(rhs), op)
-    } else {
-        expr
-    };
+        expr = ParsedExpression::CompareOp(Box::new(expr), Box::new(rhs), op);
+    }
     Some((expr, errors))
 }
 
-fn parse_assignment(
+fn parse_bit_or(
     tokens: &[Token],
     idx: &mut usize,
     restriction: Restriction,
 ) -> Option<(ParsedExpression, Vec<ParseError>)> {
-    let (expr, mut errors) = parse_math(tokens, idx, restriction)?;
-    let expr = if let Some(Token {
-        kind: TokenKind::Equal,
+    let (mut expr, mut errors) = parse_bit_xor(tokens, idx, restriction)?;
+    while let Some(Token {
+        kind: TokenKind::Pipe,
         ..
     }) = tokens.get(*idx)
     {
         *idx += 1; // Consume operator token
 
+        let (rhs, mut errs) = parse_bit_xor(tokens, idx, restriction)?;
+        errors.append(&mut errs);
+
+        expr = ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), MathOperation::BitOr);
+    }
+    Some((expr, errors))
+}
+
+fn parse_bit_xor(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_bit_and(tokens, idx, restriction)?;
+    while let Some(Token {
+        kind: TokenKind::Caret,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume operator token
+
+        let (rhs, mut errs) = parse_bit_and(tokens, idx, restriction)?;
+        errors.append(&mut errs);
+
+        expr = ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), MathOperation::BitXor);
+    }
+    Some((expr, errors))
+}
+
+fn parse_bit_and(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_shift(tokens, idx, restriction)?;
+    while let Some(Token {
+        kind: TokenKind::Ampersand,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume operator token
+
+        let (rhs, mut errs) = parse_shift(tokens, idx, restriction)?;
+        errors.append(&mut errs);
+
+        expr = ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), MathOperation::BitAnd);
+    }
+    Some((expr, errors))
+}
+
+fn parse_shift(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_math(tokens, idx, restriction)?;
+    while let Some(
+        tok @ Token {
+            kind: TokenKind::LessLess | TokenKind::GreaterGreater,
+            ..
+        },
+    ) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume operator token
+
+        let op = match tok.kind {
+            TokenKind::LessLess => MathOperation::Shl,
+            TokenKind::GreaterGreater => MathOperation::Shr,
+            _ => unreachable!(),
+        };
+
         let (rhs, mut errs) = parse_math(tokens, idx, restriction)?;
         errors.append(&mut errs);
 
-        ParsedExpression::Assignment(Box::new(expr), Box::new(rhs))
-    } else {
-        expr
-    };
+        expr = ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), op);
+    }
     Some((expr, errors))
 }
 
@@ -934,8 +3554,8 @@ fn parse_math(
     idx: &mut usize,
     restriction: Restriction,
 ) -> Option<(ParsedExpression, Vec<ParseError>)> {
-    let (expr, mut errors) = parse_factor(tokens, idx, restriction)?;
-    let expr = if let Some(
+    let (mut expr, mut errors) = parse_factor(tokens, idx, restriction)?;
+    while let Some(
         tok @ Token {
             kind: TokenKind::Plus | TokenKind::Minus,
             ..
@@ -950,100 +3570,279 @@ fn parse_math(
             _ => unreachable!(),
         };
 
-        let (rhs, mut errs) = parse_math(tokens, idx, restriction)?;
+        let (rhs, mut errs) = parse_factor(tokens, idx, restriction)?;
+        errors.append(&mut errs);
+
+        expr = ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), op);
+    }
+    Some((expr, errors))
+}
+
+fn parse_factor(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_unary(tokens, idx, restriction)?;
+    while let Some(
+        tok @ Token {
+            kind: TokenKind::Star | TokenKind::Slash | TokenKind::Percent,
+            ..
+        },
+    ) = tokens.get(*idx)
+    {
+        *idx += 1; // Consume operator token
+
+        let op = match tok.kind {
+            TokenKind::Star => MathOperation::Multiplication,
+            TokenKind::Slash => MathOperation::Division,
+            TokenKind::Percent => MathOperation::Modulo,
+            _ => unreachable!(),
+        };
+
+        let (rhs, mut errs) = parse_unary(tokens, idx, restriction)?;
         errors.append(&mut errs);
 
-        ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), op)
-    } else {
-        expr
-    };
-    Some((expr, errors))
-}
+        expr = ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), op);
+    }
+    Some((expr, errors))
+}
+
+fn parse_unary(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    match tokens.get(*idx)? {
+        pointer_tok @ Token {
+            kind: TokenKind::RightArrow,
+            ..
+        } => {
+            *idx += 1; // Consume `->` token
+            let mut_span = if let Token {
+                kind: TokenKind::Mut,
+                span,
+            } = tokens.get(*idx)?
+            {
+                *idx += 1; // Consume `mut` token
+                Some(*span)
+            } else {
+                None
+            };
+            let (expr, errors) = parse_unary(tokens, idx, restriction)?;
+
+            let pointer_span = if let Some(mut_span) = mut_span {
+                pointer_tok.span.to(mut_span)
+            } else {
+                pointer_tok.span
+            };
+            Some((
+                ParsedExpression::PointerTo(ParsedPointerTo {
+                    pointer_span,
+                    inner: Box::new(expr),
+                    is_mut: mut_span.is_some(),
+                }),
+                errors,
+            ))
+        }
+        Token {
+            kind: TokenKind::Star,
+            span: star_span,
+        } => {
+            *idx += 1; // Consume `*` token
+            let (expr, errors) = parse_unary(tokens, idx, restriction)?;
+            Some((
+                ParsedExpression::Deref(ParsedDeref {
+                    star_span: *star_span,
+                    inner: Box::new(expr),
+                }),
+                errors,
+            ))
+        }
+        tok @ Token {
+            kind: TokenKind::Minus | TokenKind::Bang | TokenKind::Tilde,
+            span: op_span,
+        } => {
+            *idx += 1; // Consume operator token
+            let (expr, errors) = parse_unary(tokens, idx, restriction)?;
+            let expr = match tok.kind {
+                TokenKind::Minus => ParsedExpression::UnaryMinus(Box::new(expr), *op_span),
+                TokenKind::Bang => ParsedExpression::Not(Box::new(expr), *op_span),
+                _ => ParsedExpression::BitNot(Box::new(expr), *op_span),
+            };
+            Some((expr, errors))
+        }
+        _ => parse_postfix(tokens, idx, restriction),
+    }
+}
+
+fn parse_postfix(
+    tokens: &[Token],
+    idx: &mut usize,
+    restriction: Restriction,
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
+    let (mut expr, mut errors) = parse_primary(tokens, idx, restriction)?;
+    loop {
+        expr = match tokens.get(*idx) {
+            Some(Token {
+                kind: TokenKind::Dot,
+                ..
+            }) => {
+                *idx += 1; // Consume dot token.
+
+                if let &Token {
+                    kind: TokenKind::IntLiteral(index),
+                    span: index_span,
+                } = tokens.get(*idx)?
+                {
+                    *idx += 1; // Consume index token
+
+                    let span = expr.span().to(index_span);
+                    ParsedExpression::TupleIndex(ParsedTupleIndex {
+                        object: Box::new(expr),
+                        index: index as usize,
+                        index_span,
+                        span,
+                    })
+                } else if let Some(Token {
+                    kind: TokenKind::OParen,
+                    ..
+                }) = tokens.get(*idx + 1)
+                {
+                    let (call, mut errs) = parse_function_call(tokens, idx)?;
+                    errors.append(&mut errs);
+
+                    let object_span = expr.span();
+                    let span = object_span.to(call.span);
+                    ParsedExpression::MethodCall(ParsedMethodCall {
+                        object: Box::new(expr),
+                        object_span,
+                        method_name: call.name,
+                        method_name_span: call.name_span,
+                        args: call.args,
+                        span,
+                    })
+                } else {
+                    let (field_name, field_name_span, mut errs) = parse_name(tokens, idx)?;
+                    errors.append(&mut errs);
 
-fn parse_factor(
-    tokens: &[Token],
-    idx: &mut usize,
-    restriction: Restriction,
-) -> Option<(ParsedExpression, Vec<ParseError>)> {
-    let (expr, mut errors) = parse_term(tokens, idx, restriction)?;
-    let expr = if let Some(
-        tok @ Token {
-            kind: TokenKind::Star | TokenKind::Slash,
-            ..
-        },
-    ) = tokens.get(*idx)
-    {
-        *idx += 1; // Consume operator token
+                    let object_span = expr.span();
+                    let span = object_span.to(field_name_span);
 
-        let op = match tok.kind {
-            TokenKind::Star => MathOperation::Multiplication,
-            TokenKind::Slash => MathOperation::Division,
-            _ => unreachable!(),
-        };
+                    ParsedExpression::FieldAccess(ParsedFieldAccess {
+                        object: Box::new(expr),
+                        object_span,
+                        field_name,
+                        field_name_span,
+                        span,
+                    })
+                }
+            }
+            Some(Token {
+                kind: TokenKind::OBracket,
+                ..
+            }) => {
+                *idx += 1; // Consume `[` token
 
-        let (rhs, mut errs) = parse_factor(tokens, idx, restriction)?;
-        errors.append(&mut errs);
+                let (index, mut errs) = parse_expression(tokens, idx, restriction)?;
+                errors.append(&mut errs);
 
-        ParsedExpression::MathOp(Box::new(expr), Box::new(rhs), op)
-    } else {
-        expr
-    };
+                expect!(&mut errors, tokens, idx, TokenKind::CBracket);
+
+                ParsedExpression::ArrayIndex(ParsedArrayIndex {
+                    index: Box::new(index),
+                    array: Box::new(expr),
+                })
+            }
+            _ => break,
+        };
+    }
     Some((expr, errors))
 }
 
-fn parse_term(
+fn parse_primary(
     tokens: &[Token],
     idx: &mut usize,
     restriction: Restriction,
 ) -> Option<(ParsedExpression, Vec<ParseError>)> {
     let mut errors = vec![];
-    let (expr, mut errors) = loop {
+    let (expr, errors) = loop {
         break match tokens.get(*idx)? {
-            pointer_tok @ Token {
-                kind: TokenKind::RightArrow,
-                ..
+            Token {
+                kind: TokenKind::OParen,
+                span: oparen_span,
             } => {
-                *idx += 1; // Consume `->` token
-                let mut_span = if let Token {
-                    kind: TokenKind::Mut,
-                    span,
+                *idx += 1; // Consume `(` token
+                           // The parentheses delimit struct literals, so they are allowed inside of them
+                           // even in the conditions of `if` and `while`, as in `if (Foo { x: 1 }).done() {`
+                let (expr, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+                errors.append(&mut errs);
+
+                // A comma after the first expression turns the parentheses into a tuple literal
+                let mut elements = vec![expr];
+                let mut is_tuple = false;
+                while let Token {
+                    kind: TokenKind::Comma,
+                    ..
+                } = tokens.get(*idx)?
+                {
+                    *idx += 1; // Consume `,` token
+                    is_tuple = true;
+
+                    if let Token {
+                        kind: TokenKind::CParen,
+                        ..
+                    } = tokens.get(*idx)?
+                    {
+                        break;
+                    }
+
+                    let (element, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+                    errors.append(&mut errs);
+                    elements.push(element);
+                }
+
+                if let Token {
+                    kind: TokenKind::CParen,
+                    ..
                 } = tokens.get(*idx)?
                 {
-                    *idx += 1; // Consume `mut` token
-                    Some(*span)
+                    *idx += 1; // Consume `)` token
                 } else {
-                    None
-                };
-                let (expr, mut errs) = parse_term(tokens, idx, restriction)?;
-                errors.append(&mut errs);
+                    errors.push(ParseError::ExpectedToken(
+                        TokenKind::CParen,
+                        tokens.get(*idx)?.span,
+                    ));
+                    // Skip to the end of the statement, the `;` itself is consumed by
+                    // the statement parser
+                    while *idx < tokens.len()
+                        && !matches!(
+                            tokens[*idx],
+                            Token {
+                                kind: TokenKind::SemiColon,
+                                ..
+                            }
+                        )
+                    {
+                        *idx += 1;
+                    }
+                }
 
-                let pointer_span = if let Some(mut_span) = mut_span {
-                    pointer_tok.span.to(mut_span)
+                let span = oparen_span.to(tokens[*idx - 1].span);
+                if is_tuple {
+                    (
+                        ParsedExpression::Literal(Literal::Tuple(elements, span)),
+                        errors,
+                    )
                 } else {
-                    pointer_tok.span
-                };
-                (
-                    ParsedExpression::PointerTo(ParsedPointerTo {
-                        pointer_span,
-                        inner: Box::new(expr),
-                        is_mut: mut_span.is_some(),
-                    }),
-                    errors,
-                )
-            }
-            Token {
-                kind: TokenKind::Star,
-                span: star_span,
-            } => {
-                *idx += 1; // Consume `*` token
-                let (expr, errors) = parse_term(tokens, idx, restriction)?;
-                (
-                    ParsedExpression::Deref(ParsedDeref {
-                        star_span: *star_span,
-                        inner: Box::new(expr),
-                    }),
-                    errors,
-                )
+                    (
+                        ParsedExpression::Parenthesized(
+                            Box::new(elements.pop().expect("first expression was parsed")),
+                            span,
+                        ),
+                        errors,
+                    )
+                }
             }
             tok @ Token {
                 kind: TokenKind::Ident(name),
@@ -1058,17 +3857,76 @@ fn parse_term(
                     (ParsedExpression::FunctionCall(func_call), errors)
                 }
                 Some(Token {
-                    kind: TokenKind::OBrace,
+                    kind: TokenKind::ColonColon,
+                    ..
+                }) if matches!(
+                    tokens.get(*idx + 2),
+                    Some(Token {
+                        kind: TokenKind::LessThan,
+                        ..
+                    })
+                ) =>
+                {
+                    let (func_call, mut errs) = parse_function_call(tokens, idx)?;
+                    errors.append(&mut errs);
+                    (ParsedExpression::FunctionCall(func_call), errors)
+                }
+                Some(Token {
+                    kind: TokenKind::ColonColon,
                     ..
                 }) => {
+                    let (enum_variant, mut errs) = parse_enum_variant(tokens, idx)?;
+                    errors.append(&mut errs);
+                    (ParsedExpression::EnumVariant(enum_variant), errors)
+                }
+                Some(Token {
+                    kind: TokenKind::LessThan,
+                    ..
+                }) if is_generic_struct_literal(tokens, *idx + 1, restriction) => {
+                    let (struct_literal, mut errs) = parse_struct_literal(tokens, idx)?;
+                    errors.append(&mut errs);
+                    let span = struct_literal.span;
+
                     if restriction == Restriction::NoStructLiteral {
-                        // TODO: Add help for how to use struct literals in restricted expressions
+                        errors.push(ParseError::StructLiteralInRestrictedContext(span));
+                    }
+                    (
+                        ParsedExpression::Literal(Literal::Struct(struct_literal, span)),
+                        errors,
+                    )
+                }
+                Some(Token {
+                    kind: TokenKind::OBrace,
+                    ..
+                }) => {
+                    // A `{` followed by `field:` can only be a struct literal, outside of those
+                    // the brace starts the block of the surrounding statement
+                    let looks_like_struct_literal = matches!(
+                        (tokens.get(*idx + 2), tokens.get(*idx + 3)),
+                        (
+                            Some(Token {
+                                kind: TokenKind::Ident(_),
+                                ..
+                            }),
+                            Some(Token {
+                                kind: TokenKind::Colon,
+                                ..
+                            })
+                        )
+                    );
+
+                    if restriction == Restriction::NoStructLiteral && !looks_like_struct_literal {
                         *idx += 1; // Consume ident token
                         (ParsedExpression::Variable(name.clone(), tok.span), errors)
                     } else {
                         let (struct_literal, mut errs) = parse_struct_literal(tokens, idx)?;
                         errors.append(&mut errs);
                         let span = struct_literal.span;
+
+                        // Continue as if the literal was parenthesized to recover
+                        if restriction == Restriction::NoStructLiteral {
+                            errors.push(ParseError::StructLiteralInRestrictedContext(span));
+                        }
                         (
                             ParsedExpression::Literal(Literal::Struct(struct_literal, span)),
                             errors,
@@ -1090,6 +3948,25 @@ fn parse_term(
                     errors,
                 )
             }
+            tok @ Token {
+                kind: TokenKind::InterpolatedString(parts),
+                ..
+            } => {
+                *idx += 1;
+                let (expr, mut errs) = parse_interpolated_string(parts, tok.span);
+                errors.append(&mut errs);
+                (expr, errors)
+            }
+            tok @ Token {
+                kind: TokenKind::CharLiteral(c),
+                ..
+            } => {
+                *idx += 1;
+                (
+                    ParsedExpression::Literal(Literal::Char(*c, tok.span)),
+                    errors,
+                )
+            }
             tok @ Token {
                 kind: TokenKind::IntLiteral(int),
                 ..
@@ -1112,15 +3989,113 @@ fn parse_term(
                 )
             }
             Token {
-                kind: TokenKind::OBracket,
+                kind: TokenKind::Null,
+                span,
+            } => {
+                *idx += 1; // Consume `null` token
+                (ParsedExpression::Literal(Literal::Null(*span)), errors)
+            }
+            Token {
+                kind: TokenKind::OBrace,
+                span: obrace_span,
+            } => {
+                let (block, mut errs) = parse_block(tokens, idx)?;
+                errors.append(&mut errs);
+
+                let span = obrace_span.to(tokens[*idx - 1].span);
+                (ParsedExpression::Block(Box::new(block), span), errors)
+            }
+            Token {
+                kind: TokenKind::If,
                 ..
             } => {
-                let (array, span, errors) = parse_array_literal(tokens, idx)?;
+                let (if_else, mut errs) = parse_if_else(tokens, idx)?;
+                errors.append(&mut errs);
+                (ParsedExpression::IfExpr(Box::new(if_else)), errors)
+            }
+            Token {
+                kind: TokenKind::Sizeof,
+                span: sizeof_span,
+            } => {
+                *idx += 1; // Consume `sizeof` token
+                expect!(&mut errors, tokens, idx, TokenKind::OParen);
+                let (ttype, _type_span, mut errs) = parse_type(tokens, idx)?;
+                errors.append(&mut errs);
+                expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+                let span = sizeof_span.to(tokens[*idx - 1].span);
+                (ParsedExpression::SizeOf(ttype, span), errors)
+            }
+            Token {
+                kind: TokenKind::Offsetof,
+                span: offsetof_span,
+            } => {
+                *idx += 1; // Consume `offsetof` token
+                expect!(&mut errors, tokens, idx, TokenKind::OParen);
+                let (ttype, _type_span, mut errs) = parse_type(tokens, idx)?;
+                errors.append(&mut errs);
+                expect!(&mut errors, tokens, idx, TokenKind::Comma);
+                let (field, field_span) = match tokens.get(*idx)? {
+                    Token {
+                        kind: TokenKind::StringLiteral(field),
+                        span,
+                    } => {
+                        *idx += 1; // Consume field name
+                        (field.clone(), *span)
+                    }
+                    token => {
+                        errors.push(ParseError::ExpectedToken(
+                            TokenKind::StringLiteral(String::new()),
+                            token.span,
+                        ));
+                        (String::new(), token.span)
+                    }
+                };
+                expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+                let span = offsetof_span.to(tokens[*idx - 1].span);
+                (
+                    ParsedExpression::OffsetOf {
+                        ttype,
+                        field,
+                        field_span,
+                        span,
+                    },
+                    errors,
+                )
+            }
+            Token {
+                kind: TokenKind::Cast,
+                span: cast_span,
+            } => {
+                *idx += 1; // Consume `cast` token
+                expect!(&mut errors, tokens, idx, TokenKind::LessThan);
+                let mut closed = false;
+                let (target_type, _type_span, mut errs) =
+                    parse_type_closing(tokens, idx, &mut closed)?;
+                errors.append(&mut errs);
+                if !closed {
+                    expect!(&mut errors, tokens, idx, TokenKind::GreaterThan);
+                }
+                expect!(&mut errors, tokens, idx, TokenKind::OParen);
+                let (inner, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+                errors.append(&mut errs);
+                expect!(&mut errors, tokens, idx, TokenKind::CParen);
+
+                let span = cast_span.to(tokens[*idx - 1].span);
                 (
-                    ParsedExpression::Literal(Literal::Array(array, span)),
+                    ParsedExpression::Cast(ParsedCast {
+                        target_type,
+                        inner: Box::new(inner),
+                        span,
+                    }),
                     errors,
                 )
             }
+            Token {
+                kind: TokenKind::OBracket,
+                ..
+            } => parse_array_literal(tokens, idx)?,
             tok => {
                 errors.push(ParseError::UnexpectedToken(tok.span));
                 *idx += 1;
@@ -1129,57 +4104,14 @@ fn parse_term(
         };
     };
 
-    let expr = if let Some(Token {
-        kind: TokenKind::Dot,
-        ..
-    }) = tokens.get(*idx)
-    {
-        *idx += 1; // Consume dot token.
-
-        let (field_name, field_name_span, mut errs) = parse_name(tokens, idx)?;
-        errors.append(&mut errs);
-
-        let object_span = expr.span();
-        let span = object_span.to(field_name_span);
-
-        ParsedExpression::FieldAccess(ParsedFieldAccess {
-            object: Box::new(expr),
-            object_span,
-            field_name,
-            field_name_span,
-            span,
-        })
-    } else {
-        expr
-    };
-
-    let expr = if let Some(Token {
-        kind: TokenKind::OBracket,
-        ..
-    }) = tokens.get(*idx)
-    {
-        *idx += 1; // Consume `[` token
-
-        let (index, mut errs) = parse_expression(tokens, idx, restriction)?;
-        errors.append(&mut errs);
-
-        expect!(&mut errors, tokens, idx, TokenKind::CBracket);
-
-        ParsedExpression::ArrayIndex(ParsedArrayIndex {
-            index: Box::new(index),
-            array: Box::new(expr),
-        })
-    } else {
-        expr
-    };
-
     Some((expr, errors))
 }
 
+/// An array literal listing its elements, or `[value; N]` repeating a single value `N` times.
 fn parse_array_literal(
     tokens: &[Token],
     idx: &mut usize,
-) -> Option<(ParsedArrayLiteral, Span, Vec<ParseError>)> {
+) -> Option<(ParsedExpression, Vec<ParseError>)> {
     let mut errors = vec![];
 
     expect!(&mut errors, tokens, idx, TokenKind::OBracket);
@@ -1199,6 +4131,39 @@ fn parse_array_literal(
         elements.push(arg);
         errors.append(&mut errs);
 
+        if elements.len() == 1
+            && matches!(
+                tokens.get(*idx)?,
+                &Token {
+                    kind: TokenKind::SemiColon,
+                    ..
+                }
+            )
+        {
+            *idx += 1; // Consume `;` token
+
+            let count = match tokens.get(*idx)? {
+                &Token {
+                    kind: TokenKind::IntLiteral(count),
+                    ..
+                } => {
+                    *idx += 1;
+                    count as usize
+                }
+                tok => {
+                    errors.push(ParseError::UnexpectedToken(tok.span));
+                    *idx += 1;
+                    0
+                }
+            };
+
+            expect!(&mut errors, tokens, idx, TokenKind::CBracket);
+            let span = o_brace_span.to(tokens[*idx - 1].span);
+
+            let value = Box::new(elements.remove(0));
+            return Some((ParsedExpression::ArrayRepeat { value, count, span }, errors));
+        }
+
         if matches!(
             &tokens.get(*idx)?,
             &Token {
@@ -1215,9 +4180,9 @@ fn parse_array_literal(
     expect!(&mut errors, tokens, idx, TokenKind::CBracket);
     let c_brace_span = tokens[*idx - 1].span;
 
+    let array = ParsedArrayLiteral { elements };
     Some((
-        ParsedArrayLiteral { elements },
-        o_brace_span.to(c_brace_span),
+        ParsedExpression::Literal(Literal::Array(array, o_brace_span.to(c_brace_span))),
         errors,
     ))
 }
@@ -1228,6 +4193,18 @@ fn parse_struct_literal(
 ) -> Option<(ParsedStructLiteral, Vec<ParseError>)> {
     let (name, name_span, mut errors) = parse_name(tokens, idx)?;
 
+    let type_args = if let Some(Token {
+        kind: TokenKind::LessThan,
+        ..
+    }) = tokens.get(*idx)
+    {
+        let (type_args, mut errs) = parse_explicit_type_args(tokens, idx)?;
+        errors.append(&mut errs);
+        type_args
+    } else {
+        vec![]
+    };
+
     expect!(&mut errors, tokens, idx, TokenKind::OBrace);
 
     let mut fields = vec![];
@@ -1248,6 +4225,13 @@ fn parse_struct_literal(
         let (field_value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
         errors.append(&mut errs);
 
+        if let Some((_, first_span, _)) = fields.iter().find(|(name, _, _)| *name == field_name) {
+            errors.push(ParseError::DuplicateField {
+                field_name: field_name.clone(),
+                first_span: *first_span,
+                second_span: field_name_span,
+            });
+        }
         fields.push((field_name, field_name_span, field_value));
 
         if matches!(
@@ -1270,6 +4254,7 @@ fn parse_struct_literal(
         ParsedStructLiteral {
             name,
             name_span,
+            type_args,
             fields,
             span: name_span.to(c_brace_span),
         },
@@ -1277,15 +4262,138 @@ fn parse_struct_literal(
     ))
 }
 
+/// Whether the `<` at `idx` starts the type arguments of a struct literal rather than a comparison,
+/// which is the case when it parses as type arguments followed by a `{`. Where struct literals are
+/// restricted the `{` also has to be followed by `field:`.
+fn is_generic_struct_literal(tokens: &[Token], idx: usize, restriction: Restriction) -> bool {
+    let mut lookahead = idx;
+    let Some((_, errors)) = parse_explicit_type_args(tokens, &mut lookahead) else {
+        return false;
+    };
+    if !errors.is_empty() {
+        return false;
+    }
+
+    match tokens.get(lookahead..lookahead + 3) {
+        Some(
+            [Token {
+                kind: TokenKind::OBrace,
+                ..
+            }, Token {
+                kind: TokenKind::Ident(_),
+                ..
+            }, Token {
+                kind: TokenKind::Colon,
+                ..
+            }],
+        ) => true,
+        _ => {
+            restriction != Restriction::NoStructLiteral
+                && matches!(
+                    tokens.get(lookahead),
+                    Some(Token {
+                        kind: TokenKind::OBrace,
+                        ..
+                    })
+                )
+        }
+    }
+}
+
+/// Desugar an interpolated string into calls concatenating its parts, so `"a ${x} b"` becomes
+/// `string_concat(string_concat("a ", to_string(x)), " b")`.
+fn parse_interpolated_string(
+    parts: &[InterpolatedPart],
+    span: Span,
+) -> (ParsedExpression, Vec<ParseError>) {
+    let mut errors = vec![];
+    let call = |name: &str, args: Vec<ParsedExpression>, span: Span| {
+        ParsedExpression::FunctionCall(ParsedFunctionCall {
+            name: name.to_string(),
+            name_span: span,
+            type_args: vec![],
+            args: args
+                .into_iter()
+                .map(|arg| {
+                    let arg_span = arg.span();
+                    (CallArg::Positional(arg), arg_span)
+                })
+                .collect(),
+            span,
+        })
+    };
+
+    let mut pieces = vec![];
+    for part in parts {
+        match part {
+            InterpolatedPart::Literal(string) => {
+                pieces.push(ParsedExpression::Literal(Literal::String(
+                    string.clone(),
+                    span,
+                )));
+            }
+            InterpolatedPart::Expr(expr_tokens) => {
+                // The placeholder's tokens end where its `}` was, which stops the expression
+                let end = expr_tokens.last().unwrap().span;
+                let sentinel = Span::new(end.source, end.end(), 0);
+                let mut tokens = expr_tokens.clone();
+                tokens.push(Token {
+                    kind: TokenKind::CBrace,
+                    span: sentinel,
+                });
+
+                let mut idx = 0;
+                let Some((expr, mut errs)) = parse_expression(&tokens, &mut idx, Restriction::None)
+                else {
+                    errors.push(ParseError::UnexpectedEndOfInput(sentinel));
+                    continue;
+                };
+                errors.append(&mut errs);
+                if idx + 1 < tokens.len() {
+                    errors.push(ParseError::UnexpectedToken(tokens[idx].span));
+                }
+
+                let expr_span = expr.span();
+                pieces.push(call("to_string", vec![expr], expr_span));
+            }
+        }
+    }
+
+    let mut pieces = pieces.into_iter();
+    let Some(first) = pieces.next() else {
+        return (
+            ParsedExpression::Literal(Literal::String(String::new(), span)),
+            errors,
+        );
+    };
+    let expr = pieces.fold(first, |acc, piece| {
+        call("string_concat", vec![acc, piece], span)
+    });
+    (expr, errors)
+}
+
 fn parse_function_call(
     tokens: &[Token],
     idx: &mut usize,
 ) -> Option<(ParsedFunctionCall, Vec<ParseError>)> {
     let (name, name_span, mut errors) = parse_name(tokens, idx)?;
 
+    let type_args = if let Some(Token {
+        kind: TokenKind::ColonColon,
+        ..
+    }) = tokens.get(*idx)
+    {
+        *idx += 1;
+        let (type_args, mut errs) = parse_explicit_type_args(tokens, idx)?;
+        errors.append(&mut errs);
+        type_args
+    } else {
+        vec![]
+    };
+
     expect!(&mut errors, tokens, idx, TokenKind::OParen);
 
-    let mut args = vec![];
+    let mut args: Vec<(CallArg, Span)> = vec![];
     while *idx < tokens.len()
         && !matches!(
             tokens.get(*idx)?,
@@ -1295,19 +4403,46 @@ fn parse_function_call(
             }
         )
     {
-        let (arg, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
-        args.push(arg);
-        errors.append(&mut errs);
-
-        if matches!(
-            &tokens.get(*idx)?,
-            &Token {
-                kind: TokenKind::Comma,
+        // A name followed by a single colon names the parameter the argument is passed to
+        let arg = if let (
+            Some(Token {
+                kind: TokenKind::Ident(name),
+                span: name_span,
+            }),
+            Some(Token {
+                kind: TokenKind::Colon,
                 ..
+            }),
+        ) = (tokens.get(*idx), tokens.get(*idx + 1))
+        {
+            *idx += 2;
+            let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+            errors.append(&mut errs);
+            CallArg::Named(ParsedNamedArg {
+                name: name.clone(),
+                name_span: *name_span,
+                value,
+            })
+        } else {
+            let (value, mut errs) = parse_expression(tokens, idx, Restriction::None)?;
+            errors.append(&mut errs);
+            if matches!(args.last(), Some((CallArg::Named(_), _))) {
+                errors.push(ParseError::PositionalArgAfterNamedArg(value.span()));
             }
-        ) {
+            CallArg::Positional(value)
+        };
+
+        if let comma @ Token {
+            kind: TokenKind::Comma,
+            ..
+        } = tokens.get(*idx)?
+        {
             *idx += 1;
+            let arg_span = arg.span().to(comma.span);
+            args.push((arg, arg_span));
         } else {
+            let arg_span = arg.span();
+            args.push((arg, arg_span));
             break;
         }
     }
@@ -1318,6 +4453,7 @@ fn parse_function_call(
     let func_call = ParsedFunctionCall {
         name,
         name_span,
+        type_args,
         args,
         span: name_span.to(cparen_span),
     };
@@ -0,0 +1,1407 @@
+use std::{
+    collections::HashMap,
+    fmt::{self, Write},
+    ops::Range,
+};
+
+use crate::{
+    lexer::{self, Comments, Token, TokenKind},
+    parser::{
+        CallArg, CompareOperation, FunctionParameter, Literal, MathOperation, ParsedAttribute,
+        ParsedBlock, ParsedConstDecl, ParsedEnum, ParsedExpression, ParsedExternFunction,
+        ParsedFunction, ParsedGenericParam, ParsedIfElse, ParsedImpl, ParsedImport,
+        ParsedLetPattern, ParsedMatchArm, ParsedPattern, ParsedProgram, ParsedStatement,
+        ParsedStruct, ParsedSwitch, ParsedTrait, ParsedType, ParsedTypeAlias, ParsedUnion,
+    },
+    span::{FileId, Span},
+};
+
+// Binding strength of every expression level, mirroring the precedence table of the parser from
+// loosest to tightest. Operands binding looser than their position requires are written in
+// parentheses.
+const PRECEDENCE_ASSIGNMENT: u8 = 1;
+const PRECEDENCE_RANGE: u8 = 2;
+const PRECEDENCE_LOGICAL_OR: u8 = 3;
+const PRECEDENCE_LOGICAL_AND: u8 = 4;
+const PRECEDENCE_COMPARISON: u8 = 5;
+const PRECEDENCE_BIT_OR: u8 = 6;
+const PRECEDENCE_BIT_XOR: u8 = 7;
+const PRECEDENCE_BIT_AND: u8 = 8;
+const PRECEDENCE_SHIFT: u8 = 9;
+const PRECEDENCE_SUM: u8 = 10;
+const PRECEDENCE_PRODUCT: u8 = 11;
+const PRECEDENCE_UNARY: u8 = 12;
+const PRECEDENCE_POSTFIX: u8 = 13;
+const PRECEDENCE_PRIMARY: u8 = 14;
+
+/// Writes a parsed program back out as Clara source, indenting blocks by two spaces unless
+/// configured otherwise. The output parses to the same AST as the program it was written from.
+pub struct PrettyPrinter<W: Write> {
+    out: W,
+    indent: usize,
+    indent_width: usize,
+    /// Comments to write before top-level declarations, see [`PrettyPrinter::with_comments`]
+    comments: Vec<Comments>,
+    /// The comments preceding every top-level declaration by the start of a span inside of it
+    leading_comments: HashMap<usize, Vec<Comments>>,
+}
+
+impl<W: Write> PrettyPrinter<W> {
+    pub fn new(out: W) -> Self {
+        Self {
+            out,
+            indent: 0,
+            indent_width: 2,
+            comments: vec![],
+            leading_comments: HashMap::new(),
+        }
+    }
+
+    /// Indent every level of blocks by `indent_width` spaces.
+    pub fn with_indent_width(mut self, indent_width: usize) -> Self {
+        self.indent_width = indent_width;
+        self
+    }
+
+    /// Keep the comments of the source of the program, which have to stand between top-level
+    /// declarations. Every comment is written before the declaration following it, comments after
+    /// the last declaration at the end. Comments inside of declarations are written by
+    /// [`insert_inner_comments`].
+    pub fn with_comments(mut self, comments: Vec<Comments>) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    pub fn fmt_program(&mut self, program: &ParsedProgram) -> fmt::Result {
+        let trailing_comments = self.assign_comments(program);
+        let mut needs_separator = false;
+
+        // Items that fit on a single line are grouped, all others are separated by a blank line
+        if !program.imports.is_empty() {
+            for import in &program.imports {
+                self.fmt_leading_comments(import.path_span)?;
+                self.fmt_import(import)?;
+            }
+            needs_separator = true;
+        }
+        if !program.extern_functions.is_empty() {
+            self.fmt_separator(&mut needs_separator)?;
+            for func in &program.extern_functions {
+                self.fmt_leading_comments(func.name_span)?;
+                self.fmt_extern_function(func)?;
+            }
+        }
+        if !program.consts.is_empty() {
+            self.fmt_separator(&mut needs_separator)?;
+            for const_decl in &program.consts {
+                self.fmt_leading_comments(const_decl.name_span)?;
+                self.fmt_const_decl(const_decl)?;
+                writeln!(self.out)?;
+            }
+        }
+        if !program.type_aliases.is_empty() {
+            self.fmt_separator(&mut needs_separator)?;
+            for type_alias in &program.type_aliases {
+                self.fmt_leading_comments(type_alias.name_span)?;
+                self.fmt_type_alias(type_alias)?;
+            }
+        }
+        for r#struct in &program.structs {
+            self.fmt_separator(&mut needs_separator)?;
+            self.fmt_leading_comments(struct_name_span(r#struct))?;
+            self.fmt_struct(r#struct)?;
+        }
+        for r#enum in &program.enums {
+            self.fmt_separator(&mut needs_separator)?;
+            self.fmt_leading_comments(r#enum.name_span)?;
+            self.fmt_enum(r#enum)?;
+        }
+        for union in &program.unions {
+            self.fmt_separator(&mut needs_separator)?;
+            self.fmt_leading_comments(union.name_span)?;
+            self.fmt_union(union)?;
+        }
+        for r#trait in &program.traits {
+            self.fmt_separator(&mut needs_separator)?;
+            self.fmt_leading_comments(r#trait.name_span)?;
+            self.fmt_trait(r#trait)?;
+        }
+        for r#impl in &program.impls {
+            self.fmt_separator(&mut needs_separator)?;
+            self.fmt_leading_comments(r#impl.struct_name_span)?;
+            self.fmt_impl(r#impl)?;
+        }
+        for func in &program.functions {
+            self.fmt_separator(&mut needs_separator)?;
+            self.fmt_leading_comments(func.name_span)?;
+            self.fmt_function(func)?;
+            writeln!(self.out)?;
+        }
+
+        if !trailing_comments.is_empty() {
+            self.fmt_separator(&mut needs_separator)?;
+            for comments in trailing_comments {
+                writeln!(self.out, "{}", comments.text)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Assigns the comments to the top-level declarations following them, returning the comments
+    /// after the last declaration.
+    fn assign_comments(&mut self, program: &ParsedProgram) -> Vec<Comments> {
+        let mut declarations: Vec<usize> = declaration_spans(program)
+            .iter()
+            .map(|span| span.start)
+            .collect();
+        declarations.sort_unstable();
+
+        let mut trailing_comments = vec![];
+        for comments in std::mem::take(&mut self.comments) {
+            let next = declarations.partition_point(|&start| start < comments.span.start);
+            match declarations.get(next) {
+                Some(&start) => self
+                    .leading_comments
+                    .entry(start)
+                    .or_default()
+                    .push(comments),
+                None => trailing_comments.push(comments),
+            }
+        }
+        trailing_comments
+    }
+
+    fn fmt_leading_comments(&mut self, span: Span) -> fmt::Result {
+        for comments in self
+            .leading_comments
+            .remove(&span.start)
+            .unwrap_or_default()
+        {
+            writeln!(self.out, "{}", comments.text)?;
+        }
+        Ok(())
+    }
+
+    fn fmt_separator(&mut self, needs_separator: &mut bool) -> fmt::Result {
+        if *needs_separator {
+            writeln!(self.out)?;
+        }
+        *needs_separator = true;
+        Ok(())
+    }
+
+    fn fmt_indent(&mut self) -> fmt::Result {
+        write!(
+            self.out,
+            "{:width$}",
+            "",
+            width = self.indent * self.indent_width
+        )
+    }
+
+    /// Writes `const NAME: T = value;` without a trailing newline, the declaration is used both
+    /// as an item and as a statement.
+    pub fn fmt_const_decl(&mut self, const_decl: &ParsedConstDecl) -> fmt::Result {
+        self.fmt_visibility(const_decl.is_public)?;
+        write!(self.out, "const {}: ", const_decl.name)?;
+        self.fmt_type(&const_decl.ttype)?;
+        write!(self.out, " = ")?;
+        self.fmt_expression(&const_decl.value)?;
+        write!(self.out, ";")
+    }
+
+    pub fn fmt_import(&mut self, import: &ParsedImport) -> fmt::Result {
+        write!(self.out, "import \"")?;
+        write_escaped_string(&mut self.out, &import.path)?;
+        write!(self.out, "\"")?;
+        if let Some((alias, _)) = &import.alias {
+            write!(self.out, " as {alias}")?;
+        }
+        writeln!(self.out, ";")
+    }
+
+    pub fn fmt_type_alias(&mut self, type_alias: &ParsedTypeAlias) -> fmt::Result {
+        write!(self.out, "type {} = ", type_alias.name)?;
+        self.fmt_type(&type_alias.aliased)?;
+        writeln!(self.out, ";")
+    }
+
+    pub fn fmt_struct(&mut self, r#struct: &ParsedStruct) -> fmt::Result {
+        self.fmt_attributes(r#struct.attributes())?;
+        self.fmt_visibility(r#struct.is_public())?;
+        match r#struct {
+            ParsedStruct::Opaque(name, _, None, _, _) => {
+                writeln!(self.out, "opaque struct {name};")
+            }
+            ParsedStruct::Opaque(name, _, Some(size), _, _) => {
+                writeln!(self.out, "opaque struct {name}: {size};")
+            }
+            ParsedStruct::Transparent(name, _, generic_params, fields, _, _)
+                if fields.is_empty() =>
+            {
+                write!(self.out, "struct {name}")?;
+                self.fmt_generic_params(generic_params)?;
+                writeln!(self.out, " {{}}")
+            }
+            ParsedStruct::Transparent(name, _, generic_params, fields, _, _) => {
+                write!(self.out, "struct {name}")?;
+                self.fmt_generic_params(generic_params)?;
+                writeln!(self.out, " {{")?;
+                self.indent += 1;
+                for (field_name, field_type) in fields {
+                    self.fmt_indent()?;
+                    write!(self.out, "{field_name}: ")?;
+                    self.fmt_type(field_type)?;
+                    writeln!(self.out, ",")?;
+                }
+                self.indent -= 1;
+                writeln!(self.out, "}}")
+            }
+        }
+    }
+
+    pub fn fmt_enum(&mut self, r#enum: &ParsedEnum) -> fmt::Result {
+        if r#enum.variants.is_empty() {
+            return writeln!(self.out, "enum {} {{}}", r#enum.name);
+        }
+
+        writeln!(self.out, "enum {} {{", r#enum.name)?;
+        self.indent += 1;
+        for (variant_name, _, value_type) in &r#enum.variants {
+            self.fmt_indent()?;
+            write!(self.out, "{variant_name}")?;
+            if let Some(value_type) = value_type {
+                write!(self.out, "(")?;
+                self.fmt_type(value_type)?;
+                write!(self.out, ")")?;
+            }
+            writeln!(self.out, ",")?;
+        }
+        self.indent -= 1;
+        writeln!(self.out, "}}")
+    }
+
+    pub fn fmt_union(&mut self, union: &ParsedUnion) -> fmt::Result {
+        if union.fields.is_empty() {
+            return writeln!(self.out, "union {} {{}}", union.name);
+        }
+
+        writeln!(self.out, "union {} {{", union.name)?;
+        self.indent += 1;
+        for (field_name, field_type) in &union.fields {
+            self.fmt_indent()?;
+            write!(self.out, "{field_name}: ")?;
+            self.fmt_type(field_type)?;
+            writeln!(self.out, ",")?;
+        }
+        self.indent -= 1;
+        writeln!(self.out, "}}")
+    }
+
+    pub fn fmt_impl(&mut self, r#impl: &ParsedImpl) -> fmt::Result {
+        write!(self.out, "impl ")?;
+        if let Some((trait_name, _)) = &r#impl.trait_name {
+            write!(self.out, "{trait_name} for ")?;
+        }
+        if r#impl.methods.is_empty() {
+            return writeln!(self.out, "{} {{}}", r#impl.struct_name);
+        }
+
+        writeln!(self.out, "{} {{", r#impl.struct_name)?;
+        self.indent += 1;
+        for (i, method) in r#impl.methods.iter().enumerate() {
+            if i > 0 {
+                writeln!(self.out)?;
+            }
+            self.fmt_indent()?;
+            self.fmt_function(method)?;
+            writeln!(self.out)?;
+        }
+        self.indent -= 1;
+        writeln!(self.out, "}}")
+    }
+
+    pub fn fmt_trait(&mut self, r#trait: &ParsedTrait) -> fmt::Result {
+        if r#trait.methods.is_empty() {
+            return writeln!(self.out, "trait {} {{}}", r#trait.name);
+        }
+
+        writeln!(self.out, "trait {} {{", r#trait.name)?;
+        self.indent += 1;
+        for method in &r#trait.methods {
+            self.fmt_indent()?;
+            write!(self.out, "fn {}", method.name)?;
+            self.fmt_signature(&method.parameters, false, &method.return_type)?;
+            writeln!(self.out, ";")?;
+        }
+        self.indent -= 1;
+        writeln!(self.out, "}}")
+    }
+
+    pub fn fmt_extern_function(&mut self, func: &ParsedExternFunction) -> fmt::Result {
+        self.fmt_attributes(&func.attributes)?;
+        self.fmt_visibility(func.is_public)?;
+        write!(self.out, "extern ")?;
+        if let Some((ref calling_convention, _)) = func.calling_convention {
+            write!(self.out, "\"")?;
+            write_escaped_string(&mut self.out, calling_convention)?;
+            write!(self.out, "\" ")?;
+        }
+        write!(self.out, "fn {}", func.name)?;
+        self.fmt_signature(&func.parameters, func.is_variadic, &func.return_type)?;
+        writeln!(self.out, ";")
+    }
+
+    /// Writes the function up to and including the closing brace of its body, without a trailing
+    /// newline.
+    pub fn fmt_function(&mut self, func: &ParsedFunction) -> fmt::Result {
+        self.fmt_attributes(&func.attributes)?;
+        self.fmt_visibility(func.is_public)?;
+        write!(self.out, "fn {}", func.name)?;
+        self.fmt_generic_params(&func.generic_params)?;
+        self.fmt_signature(&func.parameters, false, &func.return_type)?;
+        write!(self.out, " ")?;
+        self.fmt_block(&func.body)
+    }
+
+    fn fmt_visibility(&mut self, is_public: bool) -> fmt::Result {
+        if is_public {
+            write!(self.out, "pub ")?;
+        }
+        Ok(())
+    }
+
+    fn fmt_generic_params(&mut self, generic_params: &[ParsedGenericParam]) -> fmt::Result {
+        if generic_params.is_empty() {
+            return Ok(());
+        }
+        let params: Vec<String> = generic_params
+            .iter()
+            .map(|generic_param| {
+                let bounds: Vec<&str> = generic_param
+                    .bounds
+                    .iter()
+                    .map(|(bound, _)| bound.as_str())
+                    .collect();
+                if bounds.is_empty() {
+                    generic_param.name.clone()
+                } else {
+                    format!("{}: {}", generic_param.name, bounds.join(" + "))
+                }
+            })
+            .collect();
+        write!(self.out, "<{}>", params.join(", "))
+    }
+
+    fn fmt_type_args(&mut self, type_args: &[ParsedType]) -> fmt::Result {
+        write!(self.out, "<")?;
+        for (i, type_arg) in type_args.iter().enumerate() {
+            if i > 0 {
+                write!(self.out, ", ")?;
+            }
+            self.fmt_type(type_arg)?;
+        }
+        write!(self.out, ">")
+    }
+
+    /// Writes every attribute on its own line, followed by the indentation of the item they apply
+    /// to.
+    fn fmt_attributes(&mut self, attributes: &[ParsedAttribute]) -> fmt::Result {
+        for attribute in attributes {
+            write!(self.out, "#[{}", attribute.name)?;
+            if !attribute.args.is_empty() {
+                write!(self.out, "(")?;
+                for (i, arg) in attribute.args.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    if is_identifier(arg) {
+                        write!(self.out, "{arg}")?;
+                    } else {
+                        write!(self.out, "\"")?;
+                        write_escaped_string(&mut self.out, arg)?;
+                        write!(self.out, "\"")?;
+                    }
+                }
+                write!(self.out, ")")?;
+            }
+            writeln!(self.out, "]")?;
+            self.fmt_indent()?;
+        }
+        Ok(())
+    }
+
+    fn fmt_signature(
+        &mut self,
+        parameters: &[FunctionParameter],
+        is_variadic: bool,
+        return_type: &ParsedType,
+    ) -> fmt::Result {
+        write!(self.out, "(")?;
+        for (i, param) in parameters.iter().enumerate() {
+            if i > 0 {
+                write!(self.out, ", ")?;
+            }
+            self.fmt_parameter(param)?;
+        }
+        if is_variadic {
+            if !parameters.is_empty() {
+                write!(self.out, ", ")?;
+            }
+            write!(self.out, "...")?;
+        }
+        write!(self.out, ")")?;
+
+        if !matches!(return_type, ParsedType::Unit) {
+            write!(self.out, ": ")?;
+            self.fmt_type(return_type)?;
+        }
+        Ok(())
+    }
+
+    pub fn fmt_parameter(&mut self, param: &FunctionParameter) -> fmt::Result {
+        write!(self.out, "{}: ", param.name)?;
+        if param.is_by_ref {
+            write!(self.out, "ref ")?;
+        }
+        self.fmt_type(&param.ttype)
+    }
+
+    /// Writes the block from its opening to its closing brace, the statements inside are indented
+    /// one level deeper than the current line.
+    pub fn fmt_block(&mut self, block: &ParsedBlock) -> fmt::Result {
+        if block.statements.is_empty() && block.tail_expr.is_none() {
+            return write!(self.out, "{{}}");
+        }
+
+        writeln!(self.out, "{{")?;
+        self.indent += 1;
+        for statement in &block.statements {
+            self.fmt_indent()?;
+            self.fmt_statement(statement)?;
+            writeln!(self.out)?;
+        }
+        if let Some(ref tail_expr) = block.tail_expr {
+            self.fmt_indent()?;
+            self.fmt_expression(tail_expr)?;
+            writeln!(self.out)?;
+        }
+        self.indent -= 1;
+        self.fmt_indent()?;
+        write!(self.out, "}}")
+    }
+
+    fn fmt_loop_label(&mut self, label: &Option<(String, Span)>) -> fmt::Result {
+        match label {
+            Some((label, _)) => write!(self.out, "'{label}: "),
+            None => Ok(()),
+        }
+    }
+
+    fn fmt_if_else(&mut self, if_else: &ParsedIfElse) -> fmt::Result {
+        write!(self.out, "if ")?;
+        self.fmt_expression(&if_else.condition)?;
+        write!(self.out, " ")?;
+        self.fmt_block(&if_else.if_body)?;
+
+        match if_else.else_body {
+            // The parser turns `else if` into an else block holding just the nested if
+            Some(ParsedBlock {
+                ref statements,
+                tail_expr: None,
+            }) if matches!(statements[..], [ParsedStatement::IfElse(_)]) => {
+                write!(self.out, " else ")?;
+                self.fmt_statement(&statements[0])
+            }
+            Some(ref else_body) => {
+                write!(self.out, " else ")?;
+                self.fmt_block(else_body)
+            }
+            None => Ok(()),
+        }
+    }
+
+    pub fn fmt_statement(&mut self, statement: &ParsedStatement) -> fmt::Result {
+        match statement {
+            ParsedStatement::Expression(ParsedExpression::Block(block, _)) => self.fmt_block(block),
+            ParsedStatement::Expression(expr) => {
+                self.fmt_expression(expr)?;
+                write!(self.out, ";")
+            }
+            ParsedStatement::LetAssign(let_assign) => {
+                write!(self.out, "let ")?;
+                if let_assign.is_mut {
+                    write!(self.out, "mut ")?;
+                }
+                self.fmt_let_pattern(&let_assign.pattern)?;
+                if let Some((ref annotation, _)) = let_assign.type_annotation {
+                    write!(self.out, ": ")?;
+                    self.fmt_type(annotation)?;
+                }
+                write!(self.out, " = ")?;
+                self.fmt_expression(&let_assign.value)?;
+                write!(self.out, ";")
+            }
+            ParsedStatement::Const(const_decl) => self.fmt_const_decl(const_decl),
+            ParsedStatement::WhileLoop(while_loop) => {
+                self.fmt_loop_label(&while_loop.label)?;
+                write!(self.out, "while ")?;
+                self.fmt_expression(&while_loop.condition)?;
+                write!(self.out, " ")?;
+                self.fmt_block(&while_loop.body)?;
+                if let Some(ref else_body) = while_loop.else_body {
+                    write!(self.out, " else ")?;
+                    self.fmt_block(else_body)?;
+                }
+                Ok(())
+            }
+            ParsedStatement::WhileLet {
+                pattern,
+                value,
+                body,
+                label,
+                ..
+            } => {
+                self.fmt_loop_label(label)?;
+                write!(self.out, "while let ")?;
+                self.fmt_pattern(pattern)?;
+                write!(self.out, " = ")?;
+                self.fmt_expression(value)?;
+                write!(self.out, " ")?;
+                self.fmt_block(body)
+            }
+            ParsedStatement::Loop(body, label, _) => {
+                self.fmt_loop_label(label)?;
+                write!(self.out, "loop ")?;
+                self.fmt_block(body)
+            }
+            ParsedStatement::IfElse(if_else) => self.fmt_if_else(if_else),
+            ParsedStatement::Match(r#match) => {
+                write!(self.out, "match ")?;
+                self.fmt_expression(&r#match.scrutinee)?;
+                if r#match.arms.is_empty() {
+                    return write!(self.out, " {{}}");
+                }
+
+                writeln!(self.out, " {{")?;
+                self.indent += 1;
+                for arm in &r#match.arms {
+                    self.fmt_indent()?;
+                    self.fmt_match_arm(arm)?;
+                    writeln!(self.out, ",")?;
+                }
+                self.indent -= 1;
+                self.fmt_indent()?;
+                write!(self.out, "}}")
+            }
+            ParsedStatement::Switch(switch) => self.fmt_switch(switch),
+            ParsedStatement::ForInLoop(for_in) => {
+                self.fmt_loop_label(&for_in.label)?;
+                write!(self.out, "for ")?;
+                if let Some((ref index_var_name, _)) = for_in.index_var {
+                    write!(self.out, "{index_var_name}, ")?;
+                }
+                write!(self.out, "{} in ", for_in.elem_var_name)?;
+                self.fmt_expression(&for_in.iterable_value)?;
+                write!(self.out, " ")?;
+                self.fmt_block(&for_in.body)
+            }
+            ParsedStatement::Return(expr) => {
+                write!(self.out, "return ")?;
+                self.fmt_expression(expr)?;
+                write!(self.out, ";")
+            }
+            ParsedStatement::Break(label, _) => {
+                write!(self.out, "break")?;
+                if let Some((ref label, _)) = label {
+                    write!(self.out, " '{label}")?;
+                }
+                write!(self.out, ";")
+            }
+            ParsedStatement::Continue(label, _) => {
+                write!(self.out, "continue")?;
+                if let Some((ref label, _)) = label {
+                    write!(self.out, " '{label}")?;
+                }
+                write!(self.out, ";")
+            }
+            ParsedStatement::Defer(call, _) => {
+                write!(self.out, "defer ")?;
+                self.fmt_expression(call)?;
+                write!(self.out, ";")
+            }
+            ParsedStatement::Unsafe(body, _) => {
+                write!(self.out, "unsafe ")?;
+                self.fmt_block(body)
+            }
+        }
+    }
+
+    fn fmt_switch(&mut self, switch: &ParsedSwitch) -> fmt::Result {
+        write!(self.out, "switch ")?;
+        self.fmt_expression(&switch.scrutinee)?;
+        if switch.cases.is_empty() && switch.default.is_none() {
+            return write!(self.out, " {{}}");
+        }
+
+        writeln!(self.out, " {{")?;
+        self.indent += 1;
+        for (values, body) in &switch.cases {
+            self.fmt_indent()?;
+            write!(self.out, "case ")?;
+            for (value_idx, value) in values.iter().enumerate() {
+                if value_idx > 0 {
+                    write!(self.out, ", ")?;
+                }
+                self.fmt_expression(value)?;
+            }
+            writeln!(self.out, ":")?;
+            self.fmt_switch_case_body(body)?;
+        }
+        if let Some(ref default) = switch.default {
+            self.fmt_indent()?;
+            writeln!(self.out, "default:")?;
+            self.fmt_switch_case_body(default)?;
+        }
+        self.indent -= 1;
+        self.fmt_indent()?;
+        write!(self.out, "}}")
+    }
+
+    /// The statements of a case, indented below its label.
+    fn fmt_switch_case_body(&mut self, body: &ParsedBlock) -> fmt::Result {
+        self.indent += 1;
+        for statement in &body.statements {
+            self.fmt_indent()?;
+            self.fmt_statement(statement)?;
+            writeln!(self.out)?;
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    pub fn fmt_match_arm(&mut self, arm: &ParsedMatchArm) -> fmt::Result {
+        self.fmt_pattern(&arm.pattern)?;
+        write!(self.out, " => ")?;
+        self.fmt_expression(&arm.body)
+    }
+
+    pub fn fmt_let_pattern(&mut self, pattern: &ParsedLetPattern) -> fmt::Result {
+        match pattern {
+            ParsedLetPattern::Ident(name, _) => write!(self.out, "{name}"),
+            ParsedLetPattern::Tuple(elements, _) => {
+                let names: Vec<&str> = elements.iter().map(|(name, _)| name.as_str()).collect();
+                write!(self.out, "({})", names.join(", "))
+            }
+            ParsedLetPattern::Struct { name, fields, .. } => {
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|(field_name, binding, _)| {
+                        if field_name == binding {
+                            field_name.clone()
+                        } else {
+                            format!("{field_name}: {binding}")
+                        }
+                    })
+                    .collect();
+                write!(self.out, "{name} {{ {} }}", fields.join(", "))
+            }
+        }
+    }
+
+    pub fn fmt_pattern(&mut self, pattern: &ParsedPattern) -> fmt::Result {
+        match pattern {
+            ParsedPattern::Wildcard(_) => write!(self.out, "_"),
+            ParsedPattern::Literal(literal) => self.fmt_literal(literal),
+            ParsedPattern::EnumVariant(variant) => {
+                write!(self.out, "{}::{}", variant.enum_name, variant.variant_name)?;
+                if let Some((ref binding, _)) = variant.binding {
+                    write!(self.out, "({binding})")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub fn fmt_expression(&mut self, expression: &ParsedExpression) -> fmt::Result {
+        match expression {
+            ParsedExpression::Literal(literal) => self.fmt_literal(literal),
+            ParsedExpression::FunctionCall(func_call) => {
+                write!(self.out, "{}", func_call.name)?;
+                if !func_call.type_args.is_empty() {
+                    write!(self.out, "::")?;
+                    self.fmt_type_args(&func_call.type_args)?;
+                }
+                self.fmt_args(&func_call.args)
+            }
+            ParsedExpression::Variable(name, _) => write!(self.out, "{name}"),
+            ParsedExpression::CompareOp(lhs, rhs, op) => {
+                let op = match op {
+                    CompareOperation::Equality => "==",
+                    CompareOperation::NotEqual => "!=",
+                    CompareOperation::GreaterThan => ">",
+                    CompareOperation::GreaterThanEqual => ">=",
+                    CompareOperation::LessThan => "<",
+                    CompareOperation::LessThanEqual => "<=",
+                };
+                self.fmt_binary(lhs, rhs, op, PRECEDENCE_COMPARISON)
+            }
+            ParsedExpression::MathOp(lhs, rhs, op) => {
+                self.fmt_binary(lhs, rhs, op.symbol(), precedence(expression))
+            }
+            ParsedExpression::LogicalAnd(lhs, rhs) => {
+                self.fmt_binary(lhs, rhs, "&&", PRECEDENCE_LOGICAL_AND)
+            }
+            ParsedExpression::LogicalOr(lhs, rhs) => {
+                self.fmt_binary(lhs, rhs, "||", PRECEDENCE_LOGICAL_OR)
+            }
+            ParsedExpression::Assignment(lhs, rhs) => {
+                // Both sides of an assignment are parsed one level tighter than the assignment
+                self.fmt_operand(lhs, PRECEDENCE_ASSIGNMENT + 1)?;
+                write!(self.out, " = ")?;
+                self.fmt_operand(rhs, PRECEDENCE_ASSIGNMENT + 1)
+            }
+            ParsedExpression::Range(range) => {
+                // Ranges don't chain, so both bounds are parsed one level tighter as well
+                self.fmt_operand(&range.start, PRECEDENCE_RANGE + 1)?;
+                write!(self.out, "{}", if range.inclusive { "..=" } else { ".." })?;
+                self.fmt_operand(&range.end, PRECEDENCE_RANGE + 1)
+            }
+            ParsedExpression::UnaryMinus(inner, _) => {
+                write!(self.out, "-")?;
+                self.fmt_operand(inner, PRECEDENCE_UNARY)
+            }
+            ParsedExpression::Not(inner, _) => {
+                write!(self.out, "!")?;
+                self.fmt_operand(inner, PRECEDENCE_UNARY)
+            }
+            ParsedExpression::BitNot(inner, _) => {
+                write!(self.out, "~")?;
+                self.fmt_operand(inner, PRECEDENCE_UNARY)
+            }
+            ParsedExpression::PointerTo(pointer_to) => {
+                write!(self.out, "->")?;
+                if pointer_to.is_mut {
+                    write!(self.out, "mut ")?;
+                }
+                self.fmt_operand(&pointer_to.inner, PRECEDENCE_UNARY)
+            }
+            ParsedExpression::Deref(deref) => {
+                write!(self.out, "*")?;
+                self.fmt_operand(&deref.inner, PRECEDENCE_UNARY)
+            }
+            ParsedExpression::SizeOf(ttype, _) => {
+                write!(self.out, "sizeof(")?;
+                self.fmt_type(ttype)?;
+                write!(self.out, ")")
+            }
+            ParsedExpression::OffsetOf { ttype, field, .. } => {
+                write!(self.out, "offsetof(")?;
+                self.fmt_type(ttype)?;
+                write!(self.out, ", \"")?;
+                write_escaped_string(&mut self.out, field)?;
+                write!(self.out, "\")")
+            }
+            ParsedExpression::Cast(cast) => {
+                write!(self.out, "cast<")?;
+                self.fmt_type(&cast.target_type)?;
+                write!(self.out, ">(")?;
+                self.fmt_expression(&cast.inner)?;
+                write!(self.out, ")")
+            }
+            ParsedExpression::ArrayRepeat { value, count, .. } => {
+                write!(self.out, "[")?;
+                self.fmt_expression(value)?;
+                write!(self.out, "; {count}]")
+            }
+            ParsedExpression::Block(block, _) => self.fmt_block(block),
+            ParsedExpression::IfExpr(if_else) => self.fmt_if_else(if_else),
+            ParsedExpression::Parenthesized(inner, _) => {
+                write!(self.out, "(")?;
+                self.fmt_expression(inner)?;
+                write!(self.out, ")")
+            }
+            ParsedExpression::FieldAccess(field_access) => {
+                self.fmt_operand(&field_access.object, PRECEDENCE_POSTFIX)?;
+                write!(self.out, ".{}", field_access.field_name)
+            }
+            ParsedExpression::MethodCall(method_call) => {
+                self.fmt_operand(&method_call.object, PRECEDENCE_POSTFIX)?;
+                write!(self.out, ".{}", method_call.method_name)?;
+                self.fmt_args(&method_call.args)
+            }
+            ParsedExpression::TupleIndex(tuple_index) => {
+                self.fmt_operand(&tuple_index.object, PRECEDENCE_POSTFIX)?;
+                write!(self.out, ".{}", tuple_index.index)
+            }
+            ParsedExpression::ArrayIndex(array_index) => {
+                self.fmt_operand(&array_index.array, PRECEDENCE_POSTFIX)?;
+                write!(self.out, "[")?;
+                self.fmt_expression(&array_index.index)?;
+                write!(self.out, "]")
+            }
+            ParsedExpression::EnumVariant(enum_variant) => {
+                write!(
+                    self.out,
+                    "{}::{}",
+                    enum_variant.enum_name, enum_variant.variant_name
+                )?;
+                if let Some(ref value) = enum_variant.value {
+                    write!(self.out, "(")?;
+                    self.fmt_expression(value)?;
+                    write!(self.out, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Binary operators are left associative in the parser, so only the left operand may be
+    /// another operation of the same level without parentheses.
+    fn fmt_binary(
+        &mut self,
+        lhs: &ParsedExpression,
+        rhs: &ParsedExpression,
+        op: &str,
+        precedence: u8,
+    ) -> fmt::Result {
+        self.fmt_operand(lhs, precedence)?;
+        write!(self.out, " {op} ")?;
+        self.fmt_operand(rhs, precedence + 1)
+    }
+
+    fn fmt_operand(&mut self, operand: &ParsedExpression, min_precedence: u8) -> fmt::Result {
+        if precedence(operand) < min_precedence {
+            write!(self.out, "(")?;
+            self.fmt_expression(operand)?;
+            write!(self.out, ")")
+        } else {
+            self.fmt_expression(operand)
+        }
+    }
+
+    fn fmt_args(&mut self, args: &[(CallArg, Span)]) -> fmt::Result {
+        write!(self.out, "(")?;
+        for (i, (arg, _)) in args.iter().enumerate() {
+            if i > 0 {
+                write!(self.out, ", ")?;
+            }
+            if let CallArg::Named(named_arg) = arg {
+                write!(self.out, "{}: ", named_arg.name)?;
+            }
+            self.fmt_expression(arg.value())?;
+        }
+        write!(self.out, ")")
+    }
+
+    pub fn fmt_literal(&mut self, literal: &Literal) -> fmt::Result {
+        match literal {
+            Literal::String(string, _) => {
+                write!(self.out, "\"")?;
+                write_escaped_string(&mut self.out, string)?;
+                write!(self.out, "\"")
+            }
+            Literal::Char(c, _) => {
+                write!(self.out, "'")?;
+                write_escaped_char(&mut self.out, *c, '\'')?;
+                write!(self.out, "'")
+            }
+            Literal::Int(int, _) => write!(self.out, "{int}"),
+            Literal::Bool(bool_value, _) => write!(self.out, "{bool_value}"),
+            Literal::Null(_) => write!(self.out, "null"),
+            Literal::Struct(struct_literal, _) => {
+                write!(self.out, "{}", struct_literal.name)?;
+                if !struct_literal.type_args.is_empty() {
+                    self.fmt_type_args(&struct_literal.type_args)?;
+                }
+                if struct_literal.fields.is_empty() {
+                    return write!(self.out, " {{}}");
+                }
+
+                writeln!(self.out, " {{")?;
+                self.indent += 1;
+                for (field_name, _, field_value) in &struct_literal.fields {
+                    self.fmt_indent()?;
+                    write!(self.out, "{field_name}: ")?;
+                    self.fmt_expression(field_value)?;
+                    writeln!(self.out, ",")?;
+                }
+                self.indent -= 1;
+                self.fmt_indent()?;
+                write!(self.out, "}}")
+            }
+            Literal::Array(array_literal, _) => {
+                write!(self.out, "[")?;
+                for (i, element) in array_literal.elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    self.fmt_expression(element)?;
+                }
+                write!(self.out, "]")
+            }
+            Literal::Tuple(elements, _) => {
+                write!(self.out, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    self.fmt_expression(element)?;
+                }
+                // One element tuples need the trailing comma to not be read as parentheses
+                if elements.len() == 1 {
+                    write!(self.out, ",")?;
+                }
+                write!(self.out, ")")
+            }
+        }
+    }
+
+    pub fn fmt_type(&mut self, ttype: &ParsedType) -> fmt::Result {
+        match ttype {
+            ParsedType::Name(name) => write!(self.out, "{name}"),
+            ParsedType::Generic(name, type_args) => {
+                write!(self.out, "{name}")?;
+                self.fmt_type_args(type_args)
+            }
+            ParsedType::Pointer(subtype, is_mut) | ParsedType::NullablePointer(subtype, is_mut) => {
+                if let ParsedType::NullablePointer(..) = ttype {
+                    write!(self.out, "?")?;
+                }
+                write!(self.out, "->")?;
+                if *is_mut {
+                    write!(self.out, "mut ")?;
+                }
+                // A pointer type can't directly follow `->`, the inner one has to be parenthesized
+                if let ParsedType::Pointer(..) | ParsedType::NullablePointer(..) = **subtype {
+                    write!(self.out, "(")?;
+                    self.fmt_type(subtype)?;
+                    write!(self.out, ")")
+                } else {
+                    self.fmt_type(subtype)
+                }
+            }
+            ParsedType::Array(subtype, size) => {
+                write!(self.out, "[")?;
+                self.fmt_type(subtype)?;
+                write!(self.out, "; {size}]")
+            }
+            ParsedType::Slice(subtype) => {
+                write!(self.out, "[")?;
+                self.fmt_type(subtype)?;
+                write!(self.out, "]")
+            }
+            ParsedType::Tuple(element_types) => {
+                write!(self.out, "(")?;
+                for (i, element_type) in element_types.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    self.fmt_type(element_type)?;
+                }
+                if element_types.len() == 1 {
+                    write!(self.out, ",")?;
+                }
+                write!(self.out, ")")
+            }
+            ParsedType::FnPtr {
+                params,
+                return_type,
+            } => {
+                write!(self.out, "fn(")?;
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        write!(self.out, ", ")?;
+                    }
+                    self.fmt_type(param)?;
+                }
+                write!(self.out, ")")?;
+                if !matches!(**return_type, ParsedType::Unit) {
+                    write!(self.out, ": ")?;
+                    self.fmt_type(return_type)?;
+                }
+                Ok(())
+            }
+            ParsedType::Unit => write!(self.out, "()"),
+        }
+    }
+}
+
+/// Writes the comments from inside of top-level declarations into `formatted`, the output of
+/// [`PrettyPrinter::fmt_program`] for `program` parsed from `source`. Comments stay next to the
+/// tokens around them: comments on lines of their own go on a line of their own before the token
+/// following them, comments after code go to the end of the line of the token before them.
+///
+/// Returns the span of the first comment which can't be kept that way, because it stands in the
+/// middle of a line of the formatted program or next to tokens formatting added or removed.
+pub fn insert_inner_comments(
+    program: &ParsedProgram,
+    source: &str,
+    formatted: &str,
+    comments: &[Comments],
+    indent_width: usize,
+) -> Result<String, Span> {
+    let (source_tokens, _) = lexer::lex(FileId(0), source);
+    let (formatted_tokens, _) = lexer::lex(FileId(0), formatted);
+    let matches = match_tokens(
+        program,
+        source,
+        &source_tokens,
+        formatted,
+        &formatted_tokens,
+    );
+    let mut is_matched = vec![false; formatted_tokens.len()];
+    for &index in matches.iter().flatten() {
+        is_matched[index] = true;
+    }
+
+    let mut insertions = vec![];
+    for comments in comments {
+        let next = source_tokens.partition_point(|token| token.span.start < comments.span.start);
+        let on_own_line = next == 0
+            || source[source_tokens[next - 1].span.end()..comments.span.start].contains('\n');
+        let insertion = if on_own_line {
+            let line_start = source[..comments.span.start]
+                .rfind('\n')
+                .map_or(0, |index| index + 1);
+            let lines = comment_lines(&comments.text, comments.span.start - line_start);
+            matches.get(next).copied().flatten().and_then(|index| {
+                insert_before(formatted, &formatted_tokens[index], &lines, indent_width)
+            })
+        } else {
+            let lines = comment_lines(
+                &comments.text,
+                comments
+                    .text
+                    .lines()
+                    .skip(1)
+                    .filter(|line| !line.trim().is_empty())
+                    .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+                    .min()
+                    .unwrap_or(0),
+            );
+            matches[next - 1].and_then(|mut index| {
+                // Tokens formatting added after the one before the comment, like a trailing comma,
+                // go before the comment
+                while formatted_tokens.get(index + 1).is_some_and(|following| {
+                    !is_matched[index + 1]
+                        && !formatted[formatted_tokens[index].span.end()..following.span.start]
+                            .contains('\n')
+                }) {
+                    index += 1;
+                }
+                insert_after(formatted, &formatted_tokens[index], &lines)
+            })
+        };
+        insertions.push(insertion.ok_or(comments.span)?);
+    }
+
+    insertions.sort_by_key(|&(offset, _)| offset);
+    let mut with_comments = String::with_capacity(formatted.len());
+    let mut copied = 0;
+    for (offset, text) in insertions {
+        with_comments.push_str(&formatted[copied..offset]);
+        with_comments.push_str(&text);
+        copied = offset;
+    }
+    with_comments.push_str(&formatted[copied..]);
+    Ok(with_comments)
+}
+
+/// The lines of a comment with up to `indent` columns of the indentation they had in the source
+/// removed.
+fn comment_lines(text: &str, indent: usize) -> Vec<&str> {
+    text.lines()
+        .map(|line| {
+            let line_indent = line.len() - line.trim_start_matches([' ', '\t']).len();
+            line[line_indent.min(indent)..].trim_end()
+        })
+        .collect()
+}
+
+/// Where to write the lines of a comment standing on lines of its own before `token`, indented
+/// like the token, or like the contents of the block if `token` closes it.
+fn insert_before(
+    formatted: &str,
+    token: &Token,
+    lines: &[&str],
+    indent_width: usize,
+) -> Option<(usize, String)> {
+    let line_start = formatted[..token.span.start]
+        .rfind('\n')
+        .map_or(0, |index| index + 1);
+    let indent = formatted[line_start..token.span.start].len()
+        - formatted[line_start..token.span.start]
+            .trim_start_matches(' ')
+            .len();
+    let closes_block = matches!(
+        token.kind,
+        TokenKind::CBrace | TokenKind::CParen | TokenKind::CBracket
+    );
+    let comment_indent = if closes_block {
+        indent + indent_width
+    } else {
+        indent
+    };
+
+    let mut text = String::new();
+    if line_start + indent == token.span.start {
+        for line in lines {
+            if !line.is_empty() {
+                text.push_str(&" ".repeat(comment_indent));
+            }
+            text.push_str(line);
+            text.push('\n');
+        }
+        Some((line_start, text))
+    } else if closes_block && formatted[..token.span.start].ends_with(['{', '(', '[']) {
+        // The comment is all there is in the block, which formatting wrote on a single line
+        for line in lines {
+            text.push('\n');
+            if !line.is_empty() {
+                text.push_str(&" ".repeat(comment_indent));
+            }
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&" ".repeat(indent));
+        Some((token.span.start, text))
+    } else {
+        None
+    }
+}
+
+/// Where to write the lines of a comment following `token` on the same line, which has to be the
+/// last token on its line. Lines of the comment after the first are indented like the line of
+/// `token`.
+fn insert_after(formatted: &str, token: &Token, lines: &[&str]) -> Option<(usize, String)> {
+    let end = token.span.end();
+    let rest_of_line = formatted[end..].split('\n').next().unwrap_or_default();
+    if !rest_of_line.trim().is_empty() {
+        return None;
+    }
+
+    let line_start = formatted[..end].rfind('\n').map_or(0, |index| index + 1);
+    let line = &formatted[line_start..end];
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let mut text = String::new();
+    for (index, line) in lines.iter().enumerate() {
+        if index == 0 {
+            text.push(' ');
+        } else {
+            text.push('\n');
+            if !line.is_empty() {
+                text.push_str(&" ".repeat(indent));
+            }
+        }
+        text.push_str(line);
+    }
+    Some((end, text))
+}
+
+/// Pairs every token of the source with the same token of the formatted program, if there is one.
+/// Tokens are paired up declaration by declaration, as formatting reorders declarations, and by
+/// their text within a declaration. Where formatting added or removed tokens, like parentheses or
+/// trailing commas, pairing continues with the nearest tokens a few tokens ahead that are the same
+/// again.
+fn match_tokens(
+    program: &ParsedProgram,
+    source: &str,
+    source_tokens: &[Token],
+    formatted: &str,
+    formatted_tokens: &[Token],
+) -> Vec<Option<usize>> {
+    const LOOKAHEAD: usize = 8;
+
+    let mut matches = vec![None; source_tokens.len()];
+    let spans = declaration_spans(program);
+    let source_declarations = split_declarations(source_tokens);
+    let formatted_declarations = split_declarations(formatted_tokens);
+    if source_declarations.len() != spans.len() || formatted_declarations.len() != spans.len() {
+        return matches;
+    }
+
+    let same = |source_index: usize, formatted_index: usize| {
+        let source_span = source_tokens[source_index].span;
+        let formatted_span = formatted_tokens[formatted_index].span;
+        source[source_span.start..source_span.end()]
+            == formatted[formatted_span.start..formatted_span.end()]
+    };
+    for (span, formatted_range) in spans.iter().zip(formatted_declarations) {
+        let Some(source_range) = source_declarations.iter().find(|range| {
+            source_tokens[range.start].span.start <= span.start
+                && span.start < source_tokens[range.end - 1].span.end()
+        }) else {
+            continue;
+        };
+
+        let (mut i, mut j) = (source_range.start, formatted_range.start);
+        while i < source_range.end && j < formatted_range.end {
+            if same(i, j) {
+                matches[i] = Some(j);
+                i += 1;
+                j += 1;
+                continue;
+            }
+            let resync = (1..=LOOKAHEAD)
+                .flat_map(|skipped| (0..=skipped).map(move |a| (i + a, j + skipped - a)))
+                .find(|&(a, b)| a < source_range.end && b < formatted_range.end && same(a, b));
+            let Some((a, b)) = resync else {
+                break;
+            };
+            (i, j) = (a, b);
+        }
+    }
+    matches
+}
+
+/// Splits tokens into the ranges of the top-level declarations, which end in a semicolon or in a
+/// closing brace followed by the start of the next declaration.
+fn split_declarations(tokens: &[Token]) -> Vec<Range<usize>> {
+    use TokenKind::*;
+
+    let mut declarations = vec![];
+    let mut start = 0;
+    let mut depth = 0usize;
+    for (index, token) in tokens.iter().enumerate() {
+        match token.kind {
+            OParen | OBrace | OBracket => depth += 1,
+            CParen | CBrace | CBracket => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        let ends_declaration = depth == 0
+            && match token.kind {
+                SemiColon => true,
+                CBrace => tokens.get(index + 1).is_none_or(|next| {
+                    matches!(
+                        next.kind,
+                        Hash | Pub
+                            | Extern
+                            | Fn
+                            | Import
+                            | Const
+                            | Type
+                            | Opaque
+                            | Struct
+                            | Enum
+                            | Union
+                            | Trait
+                            | Impl
+                    )
+                }),
+                _ => false,
+            };
+        if ends_declaration {
+            declarations.push(start..index + 1);
+            start = index + 1;
+        }
+    }
+    declarations
+}
+
+fn precedence(expression: &ParsedExpression) -> u8 {
+    match expression {
+        ParsedExpression::LogicalOr(..) => PRECEDENCE_LOGICAL_OR,
+        ParsedExpression::LogicalAnd(..) => PRECEDENCE_LOGICAL_AND,
+        ParsedExpression::CompareOp(..) => PRECEDENCE_COMPARISON,
+        ParsedExpression::Assignment(..) => PRECEDENCE_ASSIGNMENT,
+        ParsedExpression::Range(_) => PRECEDENCE_RANGE,
+        ParsedExpression::MathOp(_, _, op) => match op {
+            MathOperation::BitOr => PRECEDENCE_BIT_OR,
+            MathOperation::BitXor => PRECEDENCE_BIT_XOR,
+            MathOperation::BitAnd => PRECEDENCE_BIT_AND,
+            MathOperation::Shl | MathOperation::Shr => PRECEDENCE_SHIFT,
+            MathOperation::Addition | MathOperation::Subtraction => PRECEDENCE_SUM,
+            MathOperation::Multiplication | MathOperation::Division | MathOperation::Modulo => {
+                PRECEDENCE_PRODUCT
+            }
+        },
+        ParsedExpression::UnaryMinus(..)
+        | ParsedExpression::Not(..)
+        | ParsedExpression::BitNot(..)
+        | ParsedExpression::PointerTo(_)
+        | ParsedExpression::Deref(_) => PRECEDENCE_UNARY,
+        // Negative numbers only come out of passes rewriting the AST, the parser reads them as
+        // a unary minus applied to the literal
+        ParsedExpression::Literal(Literal::Int(int, _)) if *int < 0 => PRECEDENCE_UNARY,
+        ParsedExpression::FieldAccess(_)
+        | ParsedExpression::MethodCall(_)
+        | ParsedExpression::TupleIndex(_)
+        | ParsedExpression::ArrayIndex(_) => PRECEDENCE_POSTFIX,
+        ParsedExpression::Literal(_)
+        | ParsedExpression::FunctionCall(_)
+        | ParsedExpression::Variable(..)
+        | ParsedExpression::Parenthesized(..)
+        | ParsedExpression::EnumVariant(_)
+        | ParsedExpression::SizeOf(..)
+        | ParsedExpression::OffsetOf { .. }
+        | ParsedExpression::Cast(_)
+        | ParsedExpression::ArrayRepeat { .. }
+        | ParsedExpression::Block(..)
+        | ParsedExpression::IfExpr(_) => PRECEDENCE_PRIMARY,
+    }
+}
+
+/// Attribute arguments parse the same from an identifier and a string literal, identifiers read
+/// nicer where the argument can be lexed as one.
+fn is_identifier(arg: &str) -> bool {
+    matches!(
+        lexer::lex(FileId(0), arg).0.as_slice(),
+        [Token { kind: TokenKind::Ident(ident), .. }] if ident == arg
+    )
+}
+
+/// A `$` starting a `${` is escaped, as it would otherwise start a placeholder.
+fn write_escaped_string<W: Write>(out: &mut W, string: &str) -> fmt::Result {
+    let mut chars = string.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '$' && chars.peek() == Some(&'{') {
+            write!(out, "\\$")?;
+        } else {
+            write_escaped_char(out, c, '"')?;
+        }
+    }
+    Ok(())
+}
+
+fn write_escaped_char<W: Write>(out: &mut W, c: char, quote: char) -> fmt::Result {
+    match c {
+        '\\' => write!(out, "\\\\"),
+        '\n' => write!(out, "\\n"),
+        '\r' => write!(out, "\\r"),
+        '\t' => write!(out, "\\t"),
+        '\0' => write!(out, "\\0"),
+        c if c == quote => write!(out, "\\{c}"),
+        c => write!(out, "{c}"),
+    }
+}
+
+/// A span inside of every top-level declaration, in the order [`PrettyPrinter::fmt_program`]
+/// writes the declarations.
+fn declaration_spans(program: &ParsedProgram) -> Vec<Span> {
+    program
+        .imports
+        .iter()
+        .map(|import| import.path_span)
+        .chain(program.extern_functions.iter().map(|func| func.name_span))
+        .chain(program.consts.iter().map(|const_decl| const_decl.name_span))
+        .chain(program.type_aliases.iter().map(|alias| alias.name_span))
+        .chain(program.structs.iter().map(struct_name_span))
+        .chain(program.enums.iter().map(|r#enum| r#enum.name_span))
+        .chain(program.unions.iter().map(|union| union.name_span))
+        .chain(program.traits.iter().map(|r#trait| r#trait.name_span))
+        .chain(program.impls.iter().map(|r#impl| r#impl.struct_name_span))
+        .chain(program.functions.iter().map(|func| func.name_span))
+        .collect()
+}
+
+fn struct_name_span(r#struct: &ParsedStruct) -> Span {
+    match r#struct {
+        ParsedStruct::Opaque(_, span, ..) | ParsedStruct::Transparent(_, span, ..) => *span,
+    }
+}
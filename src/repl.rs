@@ -0,0 +1,170 @@
+use std::{
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use clara::{
+    diagnostic::IntoDiagnostic,
+    lexer::{self, TokenKind},
+    loader,
+    source_map::SourceMap,
+    span::FileId,
+    typechecker::{self, CheckedStatement, Type},
+};
+use codespan_reporting::term::termcolor::StandardStream;
+
+use crate::emit_diagnostics;
+
+/// Everything entered so far that typechecked. Declarations are kept at the top level of the
+/// program, every other line is appended to the body of `main`.
+#[derive(Debug, Default)]
+struct Session {
+    items: String,
+    statements: String,
+}
+
+impl Session {
+    fn source(items: &str, statements: &str) -> String {
+        format!("{items}fn main() {{\n{statements}}}\n")
+    }
+}
+
+/// Reads input a line at a time and typechecks it together with everything entered before it. The
+/// type of an entered expression is printed, input that doesn't typecheck is reported and
+/// discarded. Lines leaving a bracket open are continued on the next line.
+pub fn run(
+    input: impl BufRead,
+    output: &mut impl Write,
+    json_diagnostics: bool,
+    diagnostics_writer: &StandardStream,
+) -> io::Result<()> {
+    let mut session = Session::default();
+    let mut pending = String::new();
+    let mut lines = input.lines();
+
+    loop {
+        write!(output, "{}", if pending.is_empty() { "> " } else { "... " })?;
+        output.flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => {
+                writeln!(output)?;
+                return Ok(());
+            }
+        };
+        pending.push_str(&line);
+        pending.push('\n');
+
+        if pending.trim().is_empty() {
+            pending.clear();
+            continue;
+        }
+        if open_brackets(&pending) > 0 {
+            continue;
+        }
+
+        let entry = std::mem::take(&mut pending);
+        if let Some(ttype) = eval(&mut session, &entry, json_diagnostics, diagnostics_writer) {
+            writeln!(output, "{ttype}")?;
+        }
+    }
+}
+
+/// How many brackets are opened in `text` without being closed.
+fn open_brackets(text: &str) -> isize {
+    let (tokens, _) = lexer::lex(FileId(0), text);
+    tokens
+        .iter()
+        .map(|token| match token.kind {
+            TokenKind::OParen | TokenKind::OBrace | TokenKind::OBracket => 1,
+            TokenKind::CParen | TokenKind::CBrace | TokenKind::CBracket => -1,
+            _ => 0,
+        })
+        .sum()
+}
+
+/// Adds `entry` to the session if the program still typechecks with it, returning the type of
+/// `entry` if it is an expression of a type other than `unit`.
+fn eval(
+    session: &mut Session,
+    entry: &str,
+    json_diagnostics: bool,
+    diagnostics_writer: &StandardStream,
+) -> Option<Type> {
+    let (tokens, _) = lexer::lex(FileId(0), entry);
+    let is_item = matches!(
+        tokens.first().map(|token| &token.kind),
+        Some(
+            TokenKind::Fn
+                | TokenKind::Pub
+                | TokenKind::Extern
+                | TokenKind::Struct
+                | TokenKind::Opaque
+                | TokenKind::Enum
+                | TokenKind::Impl
+                | TokenKind::Trait
+                | TokenKind::Type
+                | TokenKind::Const
+                | TokenKind::Import
+                | TokenKind::Hash
+        )
+    );
+
+    let mut entry = entry.trim_end().to_string();
+    // Let a lone expression be entered without its semicolon
+    if !is_item && !entry.ends_with(';') && !entry.ends_with('}') {
+        entry.push(';');
+    }
+    entry.push('\n');
+
+    let (items, statements) = if is_item {
+        (session.items.clone() + &entry, session.statements.clone())
+    } else {
+        (session.items.clone(), session.statements.clone() + &entry)
+    };
+
+    let mut source_map = SourceMap::default();
+    let file_id = source_map.add_file(
+        PathBuf::from("repl.clara"),
+        Session::source(&items, &statements),
+    );
+    let (program, load_errors) = loader::load_program(&mut source_map, file_id);
+    emit_diagnostics(
+        &load_errors,
+        json_diagnostics,
+        diagnostics_writer,
+        &source_map,
+    );
+    if !load_errors.is_empty() {
+        return None;
+    }
+
+    let (checked_program, typecheck_errors) = typechecker::typecheck_program(&program);
+    emit_diagnostics(
+        &typecheck_errors,
+        json_diagnostics,
+        diagnostics_writer,
+        &source_map,
+    );
+    if !typecheck_errors.iter().all(IntoDiagnostic::is_warning) {
+        return None;
+    }
+
+    session.items = items;
+    session.statements = statements;
+    if is_item {
+        return None;
+    }
+
+    let main = checked_program
+        .functions
+        .iter()
+        .find(|func| func.name == "main")?;
+    match main.body.statements.last() {
+        Some(CheckedStatement::Expression(expression)) => {
+            Some(expression.ttype()).filter(|ttype| !matches!(ttype, Type::Unit))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,80 @@
+use std::{ops::Range, path::PathBuf};
+
+use codespan_reporting::files::{self, Files};
+
+use crate::span::FileId;
+
+/// All source files of a compilation, spans refer to them through their [`FileId`].
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<(PathBuf, String)>,
+    line_starts: Vec<Vec<usize>>,
+}
+
+impl SourceMap {
+    pub fn add_file(&mut self, path: PathBuf, source: String) -> FileId {
+        self.line_starts.push(files::line_starts(&source).collect());
+        self.files.push((path, source));
+        FileId(self.files.len() - 1)
+    }
+
+    pub fn path(&self, file_id: FileId) -> &PathBuf {
+        &self.files[file_id.0].0
+    }
+
+    pub fn source(&self, file_id: FileId) -> &str {
+        &self.files[file_id.0].1
+    }
+
+    fn line_start(&self, file_id: usize, line_index: usize) -> Result<usize, files::Error> {
+        let line_starts = self
+            .line_starts
+            .get(file_id)
+            .ok_or(files::Error::FileMissing)?;
+
+        match line_index.cmp(&line_starts.len()) {
+            std::cmp::Ordering::Less => Ok(line_starts[line_index]),
+            std::cmp::Ordering::Equal => Ok(self.files[file_id].1.len()),
+            std::cmp::Ordering::Greater => Err(files::Error::LineTooLarge {
+                given: line_index,
+                max: line_starts.len() - 1,
+            }),
+        }
+    }
+}
+
+impl<'a> Files<'a> for SourceMap {
+    type FileId = usize;
+    type Name = std::path::Display<'a>;
+    type Source = &'a str;
+
+    fn name(&'a self, file_id: usize) -> Result<Self::Name, files::Error> {
+        let (path, _) = self.files.get(file_id).ok_or(files::Error::FileMissing)?;
+        Ok(path.display())
+    }
+
+    fn source(&'a self, file_id: usize) -> Result<Self::Source, files::Error> {
+        let (_, source) = self.files.get(file_id).ok_or(files::Error::FileMissing)?;
+        Ok(source)
+    }
+
+    fn line_index(&'a self, file_id: usize, byte_index: usize) -> Result<usize, files::Error> {
+        let line_starts = self
+            .line_starts
+            .get(file_id)
+            .ok_or(files::Error::FileMissing)?;
+        Ok(line_starts
+            .binary_search(&byte_index)
+            .unwrap_or_else(|next_line| next_line - 1))
+    }
+
+    fn line_range(
+        &'a self,
+        file_id: usize,
+        line_index: usize,
+    ) -> Result<Range<usize>, files::Error> {
+        let line_start = self.line_start(file_id, line_index)?;
+        let next_line_start = self.line_start(file_id, line_index + 1)?;
+        Ok(line_start..next_line_start)
+    }
+}
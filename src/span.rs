@@ -1,15 +1,26 @@
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use std::ops::Range;
 
 use serde_json::json;
 
+use crate::source_map::SourceMap;
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct FileId(pub usize);
 
+impl FileId {
+    /// The file of spans that don't point at source code, see [`Span::synthetic`]
+    pub const SYNTHETIC: FileId = FileId(usize::MAX);
+}
+
 pub trait Spanned {
     fn span(&self) -> Span;
 }
 
 #[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Span {
     pub source: FileId,
     pub start: usize,
@@ -21,6 +32,16 @@ impl Span {
         Self { source, start, len }
     }
 
+    /// A span for something the compiler made up rather than read from a file, like the `unit`
+    /// return type of a function declared without one. Diagnostics leave out labels at it.
+    pub fn synthetic() -> Self {
+        Self::new(FileId::SYNTHETIC, 0, 0)
+    }
+
+    pub fn is_synthetic(self) -> bool {
+        self.source == FileId::SYNTHETIC
+    }
+
     pub fn to(self, other: Self) -> Self {
         Self {
             start: self.start,
@@ -29,10 +50,67 @@ impl Span {
         }
     }
 
+    /// The 1-based line and column of the start of the span in `source`, columns counting
+    /// characters rather than bytes.
+    pub fn to_line_col(&self, source: &str) -> (usize, usize) {
+        let preceding = &source[..self.start];
+        let line = preceding.matches('\n').count() + 1;
+        let line_start = preceding.rfind('\n').map_or(0, |newline| newline + 1);
+        (line, preceding[line_start..].chars().count() + 1)
+    }
+
+    /// An empty span at the 1-based `line` and `col` of `source`, if that position exists. Like in
+    /// [`Span::to_line_col`], columns count characters.
+    pub fn from_line_col(file_id: FileId, source: &str, line: usize, col: usize) -> Option<Span> {
+        if line == 0 || col == 0 {
+            return None;
+        }
+
+        let line_start = if line == 1 {
+            0
+        } else {
+            source.match_indices('\n').nth(line - 2)?.0 + 1
+        };
+        let line_len = source[line_start..]
+            .find('\n')
+            .unwrap_or(source.len() - line_start);
+        let line_text = &source[line_start..line_start + line_len];
+
+        // The column just past the end of a line is where text would be appended to it
+        let offset = line_text
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(line_len))
+            .nth(col - 1)?;
+
+        Some(Span::new(file_id, line_start + offset, 0))
+    }
+
+    pub fn end(self) -> usize {
+        self.start + self.len
+    }
+
     pub fn json(self) -> serde_json::Value {
         json!({
             "start": self.start,
+            "end": self.end(),
+            "len": self.len,
+        })
+    }
+
+    /// Like [`Span::json`] but also includes the 1-based line and column of the start of the span,
+    /// located in the file of the span.
+    pub fn json_with_source(self, source_map: &SourceMap) -> serde_json::Value {
+        if self.is_synthetic() {
+            return serde_json::Value::Null;
+        }
+        let (line, column) = self.to_line_col(source_map.source(self.source));
+        json!({
+            "start": self.start,
+            "end": self.end(),
             "len": self.len,
+            "line": line,
+            "column": column,
         })
     }
 }
@@ -45,6 +123,6 @@ impl Spanned for Span {
 
 impl From<Span> for Range<usize> {
     fn from(span: Span) -> Self {
-        span.start..(span.start + span.len)
+        span.start..span.end()
     }
 }
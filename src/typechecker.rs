@@ -1,43 +1,74 @@
-use std::collections::{hash_map::Entry, HashMap};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
+};
 
 use codespan_reporting::diagnostic::{Diagnostic, Label};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
 use crate::{
+    diagnostic::IntoDiagnostic,
     parser::{
-        CompareOperation, FunctionParameter, Literal, MathOperation, ParsedBlock, ParsedExpression,
-        ParsedFunction, ParsedProgram, ParsedStatement, ParsedStruct,
+        CallArg, CompareOperation, FunctionParameter, Literal, MathOperation, ParsedBlock,
+        ParsedConstDecl, ParsedExpression, ParsedFunction, ParsedFunctionCall, ParsedGenericParam,
+        ParsedIfElse, ParsedImpl, ParsedLetAssign, ParsedLetPattern, ParsedMatch, ParsedMatchArm,
+        ParsedPattern, ParsedProgram, ParsedStatement, ParsedStruct, ParsedSwitch, ParsedTrait,
+        ParsedType, ParsedWhileLoop,
     },
+    source_map::SourceMap,
     span::{Span, Spanned},
 };
 
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Type {
     UserDefined(String),
     Pointer(Box<Type>, bool),
+    NullablePointer(Box<Type>, bool),
+    /// The type of a `null` literal that isn't used as a specific pointer type.
+    Null,
     GenericInt,
     String,
+    /// A view of string data it doesn't own, a pointer to its first byte along with its length in
+    /// bytes. The type of string literals.
+    Str,
     Int,
     Bool,
+    Char,
     Unit,
+    Never,
     CChar,
     CInt,
     GenericEmptyArray,
     Array(Box<Type>, usize),
+    Slice(Box<Type>),
+    Tuple(Vec<Type>),
+    FnPtr {
+        params: Vec<Type>,
+        return_type: Box<Type>,
+    },
+    Range(Box<Type>),
+    /// A type parameter of the generic function whose declaration is being checked. Nothing is
+    /// known about the type it stands for, besides the methods of its bounds.
+    TypeParam(String),
     Incomplete,
 }
 
 impl Type {
     pub fn element_type(&self) -> Option<Type> {
         match self {
-            Type::Pointer(elem_type, _) => Some(*elem_type.clone()),
+            Type::Pointer(elem_type, _) | Type::NullablePointer(elem_type, _) => {
+                Some(*elem_type.clone())
+            }
             _ => None,
         }
     }
 
     pub fn is_mut_pointer(&self) -> bool {
         match self {
-            Type::Pointer(_, is_mut) => *is_mut,
+            Type::Pointer(_, is_mut) | Type::NullablePointer(_, is_mut) => *is_mut,
             _ => false,
         }
     }
@@ -46,6 +77,43 @@ impl Type {
         matches!(self, Type::Pointer(_, _))
     }
 
+    /// The type of the elements of an array or slice.
+    pub fn indexed_type(&self) -> Option<Type> {
+        match self {
+            Type::Array(elem_type, _) | Type::Slice(elem_type) => Some(*elem_type.clone()),
+            _ => None,
+        }
+    }
+
+    /// The type of the values a for-in loop over this type binds to its element variable,
+    /// `None` if the type can't be iterated over.
+    pub fn iterable_element_type(&self) -> Option<Type> {
+        match self {
+            Type::Array(elem_type, _) | Type::Slice(elem_type) | Type::Range(elem_type) => {
+                Some(*elem_type.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether a type parameter is part of this type.
+    pub fn has_type_param(&self) -> bool {
+        match self {
+            Type::TypeParam(_) => true,
+            Type::Pointer(subtype, _)
+            | Type::NullablePointer(subtype, _)
+            | Type::Array(subtype, _)
+            | Type::Slice(subtype)
+            | Type::Range(subtype) => subtype.has_type_param(),
+            Type::Tuple(element_types) => element_types.iter().any(Type::has_type_param),
+            Type::FnPtr {
+                params,
+                return_type,
+            } => params.iter().any(Type::has_type_param) || return_type.has_type_param(),
+            _ => false,
+        }
+    }
+
     pub fn is_integer_type(&self) -> bool {
         matches!(self, Self::GenericInt | Self::CInt | Self::Int)
     }
@@ -57,8 +125,42 @@ impl Type {
             match (self, other) {
                 (Self::GenericInt, _) => other.is_integer_type(),
                 (_, Self::GenericInt) => self.is_integer_type(),
-                (Self::Pointer(this, this_mut), Self::Pointer(other, other_mut)) => {
-                    this_mut == other_mut && this.matches(other)
+                // Only nullable pointers can hold `null`
+                (Self::Null, Self::NullablePointer(..))
+                | (Self::NullablePointer(..), Self::Null) => true,
+                (Self::Pointer(this, this_mut), Self::Pointer(other, other_mut))
+                | (
+                    Self::NullablePointer(this, this_mut),
+                    Self::NullablePointer(other, other_mut),
+                ) => this_mut == other_mut && this.matches(other),
+                (Self::Array(this, this_size), Self::Array(other, other_size)) => {
+                    this_size == other_size && this.matches(other)
+                }
+                (Self::Slice(this), Self::Slice(other)) => this.matches(other),
+                (Self::Range(this), Self::Range(other)) => this.matches(other),
+                (
+                    Self::FnPtr {
+                        params: these_params,
+                        return_type: this_return_type,
+                    },
+                    Self::FnPtr {
+                        params: other_params,
+                        return_type: other_return_type,
+                    },
+                ) => {
+                    these_params.len() == other_params.len()
+                        && these_params
+                            .iter()
+                            .zip(other_params)
+                            .all(|(this, other)| this.matches(other))
+                        && this_return_type.matches(other_return_type)
+                }
+                (Self::Tuple(these), Self::Tuple(others)) => {
+                    these.len() == others.len()
+                        && these
+                            .iter()
+                            .zip(others)
+                            .all(|(this, other)| this.matches(other))
                 }
                 _ => false,
             }
@@ -68,32 +170,123 @@ impl Type {
     pub fn from_string(typename: &str) -> Self {
         match typename {
             "string" => Type::String,
+            "str" => Type::Str,
             "int" => Type::Int,
             "bool" => Type::Bool,
+            "char" => Type::Char,
             "c_char" => Type::CChar,
             "c_int" => Type::CInt,
-            "()" => Type::Unit,
+            "()" | "unit" => Type::Unit,
             _ => Type::UserDefined(typename.to_string()),
         }
     }
+}
 
-    pub fn to_str(&self) -> String {
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::GenericInt => "{integer}".to_string(),
-            Self::Pointer(ty, true) => format!("->mut {}", ty.to_str()),
-            Self::Pointer(ty, false) => format!("->{}", ty.to_str()),
-            Self::String => "string".to_string(),
-            Self::Int => "int".to_string(),
-            Self::Bool => "bool".to_string(),
-            Self::Unit => "unit".to_string(),
-            Self::CChar => "c_char".to_string(),
-            Self::CInt => "c_int".to_string(),
-            Self::Incomplete => "incomplete type".to_string(),
-            Self::UserDefined(name) => name.clone(),
-            Self::GenericEmptyArray => "[_; 0]".to_string(),
-            Self::Array(elem_type, size) => format!("[{}; {size}]", elem_type.to_str()),
+            Self::GenericInt => write!(f, "{{integer}}"),
+            Self::Pointer(ty, true) => write!(f, "->mut {ty}"),
+            Self::Pointer(ty, false) => write!(f, "->{ty}"),
+            Self::NullablePointer(ty, true) => write!(f, "?->mut {ty}"),
+            Self::NullablePointer(ty, false) => write!(f, "?->{ty}"),
+            Self::Null => write!(f, "null"),
+            Self::String => write!(f, "string"),
+            Self::Str => write!(f, "str"),
+            Self::Int => write!(f, "int"),
+            Self::Bool => write!(f, "bool"),
+            Self::Char => write!(f, "char"),
+            Self::Unit => write!(f, "unit"),
+            Self::Never => write!(f, "!"),
+            Self::CChar => write!(f, "c_char"),
+            Self::CInt => write!(f, "c_int"),
+            Self::Incomplete => write!(f, "incomplete type"),
+            Self::UserDefined(name) | Self::TypeParam(name) => write!(f, "{name}"),
+            Self::GenericEmptyArray => write!(f, "[_; 0]"),
+            Self::Array(elem_type, size) => write!(f, "[{elem_type}; {size}]"),
+            Self::Slice(elem_type) => write!(f, "[{elem_type}]"),
+            Self::Range(elem_type) => write!(f, "range<{elem_type}>"),
+            Self::FnPtr {
+                params,
+                return_type,
+            } => {
+                write!(f, "fn(")?;
+                write_comma_separated(f, params)?;
+                write!(f, ")")?;
+                match **return_type {
+                    Self::Unit => Ok(()),
+                    ref return_type => write!(f, ": {return_type}"),
+                }
+            }
+            Self::Tuple(element_types) if element_types.len() == 1 => {
+                write!(f, "({},)", element_types[0])
+            }
+            Self::Tuple(element_types) => {
+                write!(f, "(")?;
+                write_comma_separated(f, element_types)?;
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+fn write_comma_separated(f: &mut fmt::Formatter<'_>, types: &[Type]) -> fmt::Result {
+    for (idx, ttype) in types.iter().enumerate() {
+        if idx > 0 {
+            write!(f, ", ")?;
         }
+        write!(f, "{ttype}")?;
     }
+    Ok(())
+}
+
+/// Attributes the compiler knows about, see [`crate::parser::ParsedAttribute`].
+const KNOWN_ATTRIBUTES: &[&str] = &["no_mangle", "inline", "export", "test", "eq", "unsafe"];
+
+/// Calling conventions extern functions may be declared with, others are warned about and the
+/// function uses the C calling convention.
+pub const KNOWN_CALLING_CONVENTIONS: &[&str] = &[
+    "C",
+    "cdecl",
+    "stdcall",
+    "fastcall",
+    "thiscall",
+    "vectorcall",
+    "win64",
+    "sysv64",
+];
+
+/// The functions of `lib/clara.c` that interpolated strings are desugared to, `assert` and the
+/// bounds check of `str_slice`, which are declared implicitly when a program uses them without
+/// declaring them itself.
+fn builtin_function(name: &str) -> Option<CheckedExternFunction> {
+    let string = || Type::Pointer(Box::new(Type::CChar), false);
+    let (parameters, return_type) = match name {
+        "string_concat" => (vec![("a", string()), ("b", string())], string()),
+        "int_to_string" => (vec![("n", Type::Int)], string()),
+        "char_to_string" => (vec![("c", Type::Char)], string()),
+        "bool_to_string" => (vec![("b", Type::Bool)], string()),
+        "assert" => (vec![("condition", Type::Bool)], Type::Unit),
+        "str_slice_check" => (
+            vec![("start", Type::Int), ("end", Type::Int), ("len", Type::Int)],
+            Type::Unit,
+        ),
+        _ => return None,
+    };
+    Some(CheckedExternFunction {
+        calling_convention: None,
+        name: name.to_string(),
+        parameters: parameters
+            .into_iter()
+            .map(|(name, ttype)| CheckedParameter {
+                name: name.to_string(),
+                ttype,
+                is_by_ref: false,
+            })
+            .collect(),
+        is_variadic: false,
+        return_type,
+    })
 }
 
 pub enum TypeCheckError {
@@ -114,19 +307,167 @@ pub enum TypeCheckError {
     DuplicateParameterName(String, Span),
     DuplicateVariableName(String, Span),
     DuplicateFuncStructName(String, Span),
+    DuplicateFunctionDefinition {
+        name: String,
+        first_span: Span,
+        second_span: Span,
+    },
+    MissingReturn {
+        function_name: String,
+        span: Span,
+    },
     WrongElementTypeInArray(Type, Type, Span),
-    InvalidIterableInForIn(Type, Span),
+    NotIterable(Type, Span),
     AssignmentTypeMismatch(Type, Type, Span, Span),
+    TypeAnnotationMismatch(Type, Type, Span, Span),
+    ArraySizeMismatch(usize, usize, Span, Span),
+    NonConstantInitializer(Span),
+    /// A constant initializer that overflows or divides by zero, which is never one of
+    /// [`ConstEvalFailure::NotConstant`]
+    ConstantEvaluationFailed(ConstEvalFailure, Span),
+    CyclicTypeAlias(Span),
+    DuplicateEnumVariant(String, Span),
+    UnknownEnumVariant(String, String, Span),
+    EnumVariantWrongType(String, String, Type, Type, Span),
+    EnumVariantMissingValue(String, String, Span),
+    EnumVariantUnexpectedValue(String, String, Span),
+    PatternTypeMismatch(Type, Type, Span),
+    MatchArmTypeMismatch(Type, Type, Span),
+    NonExhaustiveMatch(Vec<String>, Span),
     AssignmentToNonLValue(Span),
     AssignmentToImmutable(Span),
+    /// A value that may be null assigned to a variable narrowed to a non-null pointer, with the
+    /// variable's name and the span of the value
+    NullAssignmentToNarrowedVariable(String, Span),
+    RefArgumentNotLValue(Span),
     MutablePointerToImmutableVariable(Span),
     DerefNonPointer(Type, Span),
+    PotentialNullDeref(Span),
     NotAnArray(Span),
-    InvalidArrayIndex(Span),
+    NonIntegerIndex {
+        found: Type,
+        span: Span,
+    },
+    StaticIndexOutOfBounds {
+        index: i64,
+        size: usize,
+        span: Span,
+    },
+    UnknownMethod(Type, String, Span),
+    DuplicateMethod(String, String, Span),
+    MethodWithoutReceiver(String, Span),
+    NotATuple(Type, Span),
+    TupleIndexOutOfBounds(usize, Type, Span),
+    LogicalOpNonBool(Type, Span),
+    NegateNonNumeric(Type, Span),
+    NotNonBool(Type, Span),
+    ModuloNonInteger(Type, Span),
+    BitwiseOpNonInteger(MathOperation, Type, Span),
+    BitNotNonInteger(Type, Span),
+    UnknownAttribute(String, Span),
+    RangeNonInteger(Type, Span),
+    BreakOutsideLoop(Span),
+    ContinueOutsideLoop(Span),
+    UnknownLoopLabel(String, Span),
+    TypeParameterCountMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    CannotInferTypeParam {
+        param_name: String,
+        name: String,
+        span: Span,
+    },
+    UnboundedGenericRecursion(String, Span),
+    UnknownTrait(String, Span),
+    DuplicateTrait(String, Span),
+    TraitMethodMissing {
+        trait_name: String,
+        method_name: String,
+        span: Span,
+    },
+    MethodNotInTrait {
+        trait_name: String,
+        method_name: String,
+        span: Span,
+    },
+    TraitMethodSignatureMismatch {
+        trait_name: String,
+        method_name: String,
+        expected: Type,
+        found: Type,
+        span: Span,
+    },
+    TraitNotImplemented {
+        ttype: Type,
+        trait_name: String,
+        span: Span,
+    },
+    MethodNotInBounds {
+        type_param: String,
+        method_name: String,
+        span: Span,
+    },
+    /// An operand of an arithmetic or comparison operator has the type of a type parameter.
+    OperatorOnTypeParam(String, Span),
+    LetPatternMismatch {
+        pattern: String,
+        value_type: Type,
+        span: Span,
+    },
+    NoStringConversion(Type, Span),
+    UnsizedOpaqueStruct(String, Span),
+    IfWithoutElse(Type, Span),
+    IncompatibleIfBranches {
+        if_type: Type,
+        else_type: Type,
+        span: Span,
+    },
+    UnknownNamedArg {
+        function_name: String,
+        arg_name: String,
+        span: Span,
+    },
+    MissingArg {
+        function_name: String,
+        param_name: String,
+        span: Span,
+    },
+    DuplicateArg {
+        param_name: String,
+        span: Span,
+    },
+    DeferNotCall(Span),
+    DeferNotUnit(Type, Span),
+    InvalidTestFunction(String, Span),
+    TypeNotComparable {
+        ttype: Type,
+        span: Span,
+    },
+    InvalidCast {
+        from: Type,
+        to: Type,
+        span: Span,
+    },
+    IntegerToPointerCast(Type, Span),
+    InvalidSwitchType(Type, Span),
+    NonConstantSwitchCase(Span),
+    DuplicateSwitchCase(Span),
+    /// A `switch` without `default` along with the enum variants it doesn't handle, none for
+    /// switches on integers
+    NonExhaustiveSwitch(Type, Vec<String>, Span),
+    /// A union literal that doesn't initialize exactly one field
+    UnionLiteralFieldCount(String, Span),
+    /// Dereferencing a pointer, casting to or from a pointer, reading a field of a union that may
+    /// not be the one written last or calling an extern function marked `#[unsafe]` outside of an
+    /// `unsafe` block
+    UnsafeOperationOutsideUnsafeBlock(Span),
 }
 
-impl TypeCheckError {
-    pub fn report(&self) -> Diagnostic<usize> {
+impl IntoDiagnostic for TypeCheckError {
+    fn report(&self) -> Diagnostic<usize> {
         match *self {
             Self::WrongNumArgs(span, actual, expected) => Diagnostic::error()
                 .with_message("incorrect number of arguments to function call")
@@ -140,8 +481,7 @@ impl TypeCheckError {
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
                         "argument has type {} but function expects {}",
-                        actual.to_str(),
-                        expected.to_str()
+                        actual, expected
                     ),
                 )]),
             Self::UnknownFunction(ref function_name, span) => Diagnostic::error()
@@ -163,19 +503,19 @@ impl TypeCheckError {
             Self::WrongConditionType(span, ref actual) => Diagnostic::error()
                 .with_message("incorrect type in condition")
                 .with_labels(vec![Label::primary(span.source.0, span)
-                    .with_message(format!("expression has type {}", actual.to_str()))])
+                    .with_message(format!("expression has type {}", actual))])
                 .with_notes(vec![format!(
                     "expression in condition has to be of type {}",
-                    Type::Bool.to_str()
+                    Type::Bool.to_string()
                 )]),
             Self::BinaryOpMismatch(ref lhs_type, ref rhs_type, lhs_span, rhs_span) => {
                 Diagnostic::error()
                     .with_message("type mismatch in binary operator")
                     .with_labels(vec![
                         Label::primary(lhs_span.source.0, lhs_span)
-                            .with_message(format!("left operand has type {}", lhs_type.to_str())),
+                            .with_message(format!("left operand has type {}", lhs_type)),
                         Label::primary(rhs_span.source.0, rhs_span)
-                            .with_message(format!("right operand has type {}", rhs_type.to_str())),
+                            .with_message(format!("right operand has type {}", rhs_type)),
                     ])
                     .with_notes(vec![
                         "Both sides of the operator need to have the same type".to_string(),
@@ -189,10 +529,7 @@ impl TypeCheckError {
             Self::OpaqueStructFieldAccess(ref object_type, span) => Diagnostic::error()
                 .with_message("field access on opaque struct")
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
-                    format!(
-                        "cannot access fields of opaque struct type {}",
-                        object_type.to_str()
-                    ),
+                    format!("cannot access fields of opaque struct type {}", object_type),
                 )]),
             Self::FieldAccessInvalidField(ref object_type, ref field_name, span) => {
                 Diagnostic::error()
@@ -200,18 +537,14 @@ impl TypeCheckError {
                     .with_labels(vec![Label::primary(span.source.0, span).with_message(
                         format!(
                             "struct type {} has no field by the name of `{}`",
-                            object_type.to_str(),
-                            field_name
+                            object_type, field_name
                         ),
                     )])
             }
             Self::ObjectIsNotAStruct(ref object_type, span) => Diagnostic::error()
                 .with_message("object is not a struct")
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
-                    format!(
-                        "trying to access field on non-struct type {}",
-                        object_type.to_str()
-                    ),
+                    format!("trying to access field on non-struct type {}", object_type),
                 )]),
             Self::StructMissingField(ref struct_name, ref missing_field_name, span) => {
                 Diagnostic::error()
@@ -234,15 +567,14 @@ impl TypeCheckError {
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
                         "expression has type {} but struct expects type {}",
-                        actual.to_str(),
-                        expected.to_str()
+                        actual, expected
                     ),
                 )])
                 .with_notes(vec![format!(
                     "Field `{}` on struct `{}` has type `{}`",
                     field_name,
                     struct_name,
-                    expected.to_str()
+                    expected.to_string()
                 )]),
             Self::StructSuperfluousField(ref struct_name, ref field_name, span) => {
                 Diagnostic::error()
@@ -256,8 +588,7 @@ impl TypeCheckError {
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
                         "expression has type `{}` but function expected type `{}`",
-                        actual.to_str(),
-                        expected.to_str(),
+                        actual, expected,
                     ),
                 )]),
             Self::DuplicateParameterName(ref name, span) => Diagnostic::error()
@@ -270,6 +601,30 @@ impl TypeCheckError {
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!("variable name `{name}` has already been used in this function"),
                 )]),
+            Self::DuplicateFunctionDefinition {
+                ref name,
+                first_span,
+                second_span,
+            } => Diagnostic::error()
+                .with_message(format!("function `{name}` is defined more than once"))
+                .with_labels(vec![
+                    Label::primary(second_span.source.0, second_span)
+                        .with_message(format!("`{name}` redefined here")),
+                    Label::secondary(first_span.source.0, first_span)
+                        .with_message(format!("`{name}` first defined here")),
+                ]),
+            Self::MissingReturn {
+                ref function_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!("function `{function_name}` may not return a value"))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    "not every path through the function returns this type",
+                )])
+                .with_notes(vec![
+                    "both branches of an `if` have to return, a `while` loop may not run at all"
+                        .to_string(),
+                ]),
             Self::DuplicateFuncStructName(ref name, span) => Diagnostic::error()
                 .with_message("duplicate function or struct name")
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
@@ -280,32 +635,51 @@ impl TypeCheckError {
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
                         "expression has type `{}` but array expected type `{}`",
-                        actual.to_str(),
-                        expected.to_str()
+                        actual, expected
                     ),
                 )])
                 .with_notes(vec![
                     "All elements of an array must have the same type".to_string()
                 ]),
-            Self::InvalidIterableInForIn(ref actual, span) => Diagnostic::error()
-                .with_message("wrong type for iterable in for-in loop")
+            Self::NotIterable(ref actual, span) => Diagnostic::error()
+                .with_message(format!("`{}` is not iterable", actual))
                 .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
-                        "expression has type `{}` expected array type",
-                        actual.to_str()
+                        "expression has type `{}` expected array, slice or range type",
+                        actual
                     ),
                 )])
                 .with_notes(vec![format!(
-                    "For-in loops currently only support arrays as iterables"
+                    "Only arrays, slices and ranges can be iterated over in for-in loops"
                 )]),
             Self::AssignmentTypeMismatch(ref actual, ref expected, lhs_span, rhs_span) => {
                 Diagnostic::error()
                     .with_message("wrong type in assignment")
                     .with_labels(vec![
                         Label::primary(lhs_span.source.0, lhs_span)
-                            .with_message(format!("lhs has type {}", expected.to_str())),
+                            .with_message(format!("lhs has type {}", expected)),
                         Label::secondary(rhs_span.source.0, rhs_span)
-                            .with_message(format!("rhs has type {}", actual.to_str())),
+                            .with_message(format!("rhs has type {}", actual)),
+                    ])
+            }
+            Self::TypeAnnotationMismatch(ref expected, ref actual, annotation_span, value_span) => {
+                Diagnostic::error()
+                    .with_message("value does not match type annotation")
+                    .with_labels(vec![
+                        Label::primary(value_span.source.0, value_span)
+                            .with_message(format!("value has type {}", actual)),
+                        Label::secondary(annotation_span.source.0, annotation_span)
+                            .with_message(format!("expected type {}", expected)),
+                    ])
+            }
+            Self::ArraySizeMismatch(expected, actual, annotation_span, value_span) => {
+                Diagnostic::error()
+                    .with_message("array size does not match type annotation")
+                    .with_labels(vec![
+                        Label::primary(value_span.source.0, value_span)
+                            .with_message(format!("array has {} elements", actual)),
+                        Label::secondary(annotation_span.source.0, annotation_span)
+                            .with_message(format!("expected {} elements", expected)),
                     ])
             }
             Self::AssignmentToNonLValue(span) => Diagnostic::error()
@@ -314,686 +688,4742 @@ impl TypeCheckError {
             Self::AssignmentToImmutable(span) => Diagnostic::error()
                 .with_message("lhs of assignment is not mutable")
                 .with_labels(vec![Label::primary(span.source.0, span)]),
+            Self::NullAssignmentToNarrowedVariable(ref name, span) => Diagnostic::error()
+                .with_message(format!(
+                    "cannot assign a pointer that may be null to `{name}`, which was checked not to be null"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![format!(
+                    "`{name}` is known not to be null for the rest of the `if`"
+                )]),
+            Self::RefArgumentNotLValue(span) => Diagnostic::error()
+                .with_message("argument for `ref` parameter is not an l-value")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("the function is passed a pointer to the argument")]),
             Self::MutablePointerToImmutableVariable(span) => Diagnostic::error()
                 .with_message("cannot create mutable pointer to immutable variable")
                 .with_labels(vec![Label::primary(span.source.0, span)]),
             Self::DerefNonPointer(ref ttype, span) => Diagnostic::error()
                 .with_message("cannot dereference value of non-pointer type")
                 .with_labels(vec![Label::primary(span.source.0, span)
-                    .with_message(format!("value has type `{}`", ttype.to_str()))]),
+                    .with_message(format!("value has type `{}`", ttype))]),
+            Self::PotentialNullDeref(span) => Diagnostic::error()
+                .with_message("cannot dereference a pointer that may be null")
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "check that the pointer is not null with `if ptr != null` first".to_string(),
+                ]),
             Self::NotAnArray(span) => Diagnostic::error()
                 .with_message("expression is not an array")
                 .with_labels(vec![Label::primary(span.source.0, span)]),
-            Self::InvalidArrayIndex(span) => Diagnostic::error()
-                .with_message("invalid index for array")
-                .with_labels(vec![Label::primary(span.source.0, span)]),
-        }
-    }
-}
-
-impl TypeCheckError {
-    pub fn json(&self) -> serde_json::Value {
-        match *self {
-            Self::WrongNumArgs(span, actual, expected) => json!({
-                "message":
-                format!(
-                    "incorrect number of arguments to function call, expected {} but found {}",
-                    expected,
-                    actual
-                ),
-                "span": span.json(),
-            }),
-            Self::WrongArgType(span, ref actual, ref expected) => json!({
-                "message":
-                    format!(
-                        "incorrect argument type in function call, expected type {} but found type {}",
-                        expected.to_str(),
-                        actual.to_str()
-                    ),
-                "span": span.json(),
-            }),
-            Self::UnknownFunction(ref function_name, span) => json!({
-                "message": format!("reference to unknown function `{}`", function_name),
-                "span": span.json(),
-            }),
-            Self::UnknownVariable(ref variable_name, ref function_name, span) => {
-                let message = if let Some(functio_name) = function_name {
-                    format!(
-                        "reference to unknown variable `{}` in function `{}`",
-                        variable_name, functio_name
-                    )
-                } else {
-                    format!("unknown variable `{}`", variable_name)
-                };
-                json!({
-                    "message": message,
-                    "span": span.json(),
-                })
-            }
-            Self::WrongConditionType(span, ref actual) => json!({
-                "message":
-                    format!(
-                        "wrong type in condition, expected `{}` but found `{}`",
-                        Type::Bool.to_str(),
-                        actual.to_str()
-                    ),
-                "span": span.json(),
-            }),
-            Self::BinaryOpMismatch(ref lhs_type, ref rhs_type, lhs_span, rhs_span) => json!({
-                "message":
-                    format!(
-                        "type mismatch in binary operator, type `{}` on the left and type `{}` on the right",
-                        lhs_type.to_str(),
-                        rhs_type.to_str()
-                    ),
-                "span": lhs_span.to(rhs_span).json(),
-            }),
-            Self::UnknownType(ref type_name, span) => json!({
-                "message": format!("reference to unknown type `{}`", type_name),
-                "span": span.json(),
-            }),
-            Self::OpaqueStructFieldAccess(ref object_type, span) => json!({
-                "message": format!("field access on opaque struct type `{}`", object_type.to_str()),
-                "span": span.json(),
-            }),
-            Self::FieldAccessInvalidField(ref object_type, ref field_name, span) => json!({
-                "message":
+            Self::NotATuple(ref object_type, span) => Diagnostic::error()
+                .with_message("expression is not a tuple")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("trying to index into non-tuple type {}", object_type),
+                )]),
+            Self::TupleIndexOutOfBounds(index, ref tuple_type, span) => Diagnostic::error()
+                .with_message("tuple index out of bounds")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("tuple type {} has no element {}", tuple_type, index),
+                )]),
+            Self::UnknownMethod(ref object_type, ref method_name, span) => Diagnostic::error()
+                .with_message(format!("reference to unknown method `{}`", method_name))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
-                        "struct type `{}` has no field by the name of `{}",
-                        object_type.to_str(),
-                        field_name
+                        "type {} has no method by the name of `{}`",
+                        object_type, method_name
                     ),
-                "span": span.json(),
-            }),
-
-            Self::ObjectIsNotAStruct(ref object_type, span) => json!({
-                "message": format!("object of type `{}` is not a struct", object_type.to_str()),
-                "span": span.json(),
-            }),
-            Self::StructMissingField(ref struct_name, ref missing_field_name, span) => json!({
-                "message":
+                )]),
+            Self::DuplicateMethod(ref type_name, ref method_name, span) => Diagnostic::error()
+                .with_message("duplicate method")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
-                        "initializer for field `{}` missing in struct literal for struct type `{}`",
-                        missing_field_name,
-                        struct_name
+                        "method `{}` is already declared for type {}",
+                        method_name, type_name
                     ),
-                "span": span.json(),
-            }),
-            Self::StructFieldWrongType(
-                ref _struct_name,
-                ref field_name,
-                ref actual,
+                )]),
+            Self::MethodWithoutReceiver(ref method_name, span) => Diagnostic::error()
+                .with_message("method without receiver")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("method `{}` has no parameters", method_name))])
+                .with_notes(vec![format!(
+                    "The first parameter of a method receives the object it is called on"
+                )]),
+            Self::NonIntegerIndex { ref found, span } => Diagnostic::error()
+                .with_message("invalid index for array")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("index has type `{found}`"))])
+                .with_notes(vec![
+                    "Arrays and slices can only be indexed by integers".to_string()
+                ]),
+            Self::StaticIndexOutOfBounds { index, size, span } => Diagnostic::error()
+                .with_message("index out of bounds")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("index is {index} but the array has {size} elements"),
+                )]),
+            Self::LogicalOpNonBool(ref actual, span) => Diagnostic::error()
+                .with_message("incorrect operand type for logical operator")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("operand has type {}", actual))])
+                .with_notes(vec![format!(
+                    "Both operands of `&&` and `||` have to be of type {}",
+                    Type::Bool.to_string()
+                )]),
+            Self::NegateNonNumeric(ref actual, span) => Diagnostic::error()
+                .with_message("cannot negate value of non-numeric type")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("value has type `{}`", actual))]),
+            Self::NotNonBool(ref actual, span) => Diagnostic::error()
+                .with_message("cannot apply `!` to value of non-bool type")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("value has type `{}`", actual))]),
+            Self::ModuloNonInteger(ref actual, span) => Diagnostic::error()
+                .with_message("incorrect operand type for `%` operator")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("operand has type `{}`", actual))])
+                .with_notes(vec!["Both operands of `%` have to be integers".to_string()]),
+            Self::BitwiseOpNonInteger(op, ref actual, span) => Diagnostic::error()
+                .with_message(format!(
+                    "incorrect operand type for `{}` operator",
+                    op.symbol()
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("operand has type `{}`", actual))])
+                .with_notes(vec![format!(
+                    "Both operands of `{}` have to be integers",
+                    op.symbol()
+                )]),
+            Self::BitNotNonInteger(ref actual, span) => Diagnostic::error()
+                .with_message("cannot apply `~` to value of non-integer type")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("value has type `{}`", actual))]),
+            Self::UnknownAttribute(ref name, span) => Diagnostic::warning()
+                .with_message(format!("unknown attribute `{name}`"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![format!(
+                    "Known attributes are {}",
+                    KNOWN_ATTRIBUTES
+                        .iter()
+                        .map(|name| format!("`{name}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )]),
+            Self::RangeNonInteger(ref actual, span) => Diagnostic::error()
+                .with_message("incorrect bound type for range")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("bound has type `{}`", actual))])
+                .with_notes(vec![
+                    "Both bounds of a range have to be integers".to_string()
+                ]),
+            Self::BreakOutsideLoop(span) => Diagnostic::error()
+                .with_message("`break` outside of a loop")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("cannot `break` outside of a loop")]),
+            Self::ContinueOutsideLoop(span) => Diagnostic::error()
+                .with_message("`continue` outside of a loop")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("cannot `continue` outside of a loop")]),
+            Self::UnknownLoopLabel(ref label, span) => Diagnostic::error()
+                .with_message(format!("use of undeclared label `'{label}`"))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("no enclosing loop has this label")]),
+            Self::TypeParameterCountMismatch {
+                ref name,
+                expected,
+                found,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!("wrong number of type arguments for `{name}`"))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("expected {expected} type arguments but {found} were given"),
+                )]),
+            Self::CannotInferTypeParam {
+                ref param_name,
+                ref name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "cannot infer type parameter `{param_name}` of `{name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("the arguments don't determine the type of `{param_name}`"),
+                )])
+                .with_notes(vec![
+                    "the type arguments can be given explicitly".to_string()
+                ]),
+            Self::UnboundedGenericRecursion(ref name, span) => Diagnostic::error()
+                .with_message(format!(
+                    "generic instantiation of `{name}` recurses without bound"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    "every instance calls an instance with larger type arguments",
+                )])
+                .with_notes(vec![format!(
+                    "instances of a function can only be nested {MAX_GENERIC_RECURSION} deep"
+                )]),
+            Self::UnknownTrait(ref trait_name, span) => Diagnostic::error()
+                .with_message(format!("reference to unknown trait `{trait_name}`"))
+                .with_labels(vec![Label::primary(span.source.0, span)]),
+            Self::DuplicateTrait(ref trait_name, span) => Diagnostic::error()
+                .with_message("duplicate trait")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("trait `{trait_name}` is already declared"))]),
+            Self::TraitMethodMissing {
+                ref trait_name,
+                ref method_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "missing method `{method_name}` of trait `{trait_name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("implementation doesn't declare `{method_name}`"),
+                )]),
+            Self::MethodNotInTrait {
+                ref trait_name,
+                ref method_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "method `{method_name}` is not a member of trait `{trait_name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Methods that aren't part of the trait go in a separate `impl` block"
+                        .to_string(),
+                ]),
+            Self::TraitMethodSignatureMismatch {
+                ref trait_name,
+                ref method_name,
                 ref expected,
+                ref found,
                 span,
-            ) => json!({
-                "message":
-                    format!(
-                        "field `{}` has incorrect type, expected `{}` but found `{}`",
-                        field_name,
-                        expected.to_str(),
-                        actual.to_str()
-                    ),
-                "span": span.json(),
-            }),
-            Self::StructSuperfluousField(ref struct_name, ref field_name, span) => json!({
-                "message":
-                    format!(
-                        "struct type `{struct_name}` has no field by the name of `{field_name}`"
-                    ),
-                "span": span.json(),
-            }),
-            Self::InvalidReturnType(ref actual, ref expected, span) => json!({
-                "message":
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "method `{method_name}` doesn't match its declaration in trait `{trait_name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("method has type {found} but the trait declares {expected}"),
+                )]),
+            Self::TraitNotImplemented {
+                ref ttype,
+                ref trait_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "type {ttype} doesn't implement trait `{trait_name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("required by a bound on a type parameter")]),
+            Self::MethodNotInBounds {
+                ref type_param,
+                ref method_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "reference to unknown method `{method_name}` of type parameter `{type_param}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("the bounds of `{type_param}` declare no method `{method_name}`"),
+                )])
+                .with_notes(vec![
+                    "a generic function can only call the methods of the traits bounding its type parameters"
+                        .to_string(),
+                ]),
+            Self::OperatorOnTypeParam(ref type_param, span) => Diagnostic::error()
+                .with_message(format!(
+                    "operator applied to a value of type parameter `{type_param}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("`{type_param}` may stand for types the operator doesn't apply to"),
+                )]),
+            Self::LetPatternMismatch {
+                ref pattern,
+                ref value_type,
+                span,
+            } => Diagnostic::error()
+                .with_message("pattern doesn't match the type of the value")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("pattern destructures {pattern} but the value has type {value_type}"),
+                )]),
+            Self::UnsizedOpaqueStruct(ref struct_name, span) => Diagnostic::error()
+                .with_message(format!("opaque struct `{struct_name}` has no known size"))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("values of it can't be stored or passed around")])
+                .with_notes(vec![format!(
+                    "Use a pointer (`->{struct_name}`) or declare its size in bytes with \
+                     `opaque struct {struct_name}: <size>;`."
+                )]),
+            Self::IfWithoutElse(ref ttype, span) => Diagnostic::error()
+                .with_message("`if` expression without `else` has to be of type unit")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("the `if` branch has type {ttype}"))])
+                .with_notes(vec![
+                    "Add an `else` branch producing a value of the same type.".to_string(),
+                ]),
+            Self::IncompatibleIfBranches {
+                ref if_type,
+                ref else_type,
+                span,
+            } => Diagnostic::error()
+                .with_message("`if` and `else` have incompatible types")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
                     format!(
-                        "return value has type `{}` but function expected type `{}`",
-                        actual.to_str(),
-                        expected.to_str(),
+                    "the `if` branch has type {if_type} but the `else` branch has type {else_type}"
+                ),
+                )]),
+            Self::UnknownNamedArg {
+                ref function_name,
+                ref arg_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "`{function_name}` has no parameter named `{arg_name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)]),
+            Self::MissingArg {
+                ref function_name,
+                ref param_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!(
+                    "missing argument for parameter `{param_name}` of `{function_name}`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)]),
+            Self::DuplicateArg {
+                ref param_name,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!("parameter `{param_name}` is given more than once"))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("argument passed to the parameter again here")]),
+            Self::DeferNotCall(span) => Diagnostic::error()
+                .with_message("only calls can be deferred")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("expected a function or method call")]),
+            Self::DeferNotUnit(ref ttype, span) => Diagnostic::error()
+                .with_message("deferred call has to return unit")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message(format!("call returns {ttype}"))])
+                .with_notes(vec![
+                    "The value of a deferred call would be discarded.".to_string()
+                ]),
+            Self::InvalidTestFunction(ref name, span) => Diagnostic::error()
+                .with_message(format!("test function `{name}` has the wrong signature"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Test functions take no parameters and return unit.".to_string()
+                ]),
+            Self::InvalidCast {
+                ref from,
+                ref to,
+                span,
+            } => Diagnostic::error()
+                .with_message(format!("can't cast {from} to {to}"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Integers and bools can be cast to integers, and integers and pointers to \
+                     pointers."
+                        .to_string(),
+                ]),
+            Self::IntegerToPointerCast(ref ttype, span) => Diagnostic::warning()
+                .with_message(format!("casting an integer to {ttype}"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "The pointer is only valid if the integer is the address of a value."
+                        .to_string(),
+                ]),
+            Self::InvalidSwitchType(ref ttype, span) => Diagnostic::error()
+                .with_message(format!("can't switch on a value of type {ttype}"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Only integers, chars and enum values can be switched on.".to_string(),
+                ]),
+            Self::NonConstantSwitchCase(span) => Diagnostic::error()
+                .with_message("switch case is not a constant expression")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("cannot be evaluated at compile time")]),
+            Self::DuplicateSwitchCase(span) => Diagnostic::error()
+                .with_message("value is already handled by an earlier case")
+                .with_labels(vec![Label::primary(span.source.0, span)]),
+            Self::NonExhaustiveSwitch(ref ttype, ref missing_variants, span) => {
+                let label = if missing_variants.is_empty() {
+                    format!("not every {ttype} is handled by a case")
+                } else {
+                    format!(
+                        "variants not handled: {}",
+                        missing_variants
+                            .iter()
+                            .map(|variant| format!("`{variant}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    )
+                };
+                Diagnostic::warning()
+                    .with_message("switch without `default` doesn't handle every value")
+                    .with_labels(vec![Label::primary(span.source.0, span).with_message(label)])
+                    .with_notes(vec![
+                        "Add a `default:` case to handle the remaining values.".to_string(),
+                    ])
+            }
+            Self::UnionLiteralFieldCount(ref union_name, span) => Diagnostic::error()
+                .with_message(format!(
+                    "union literal of `{union_name}` has to initialize exactly one field"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)]),
+            Self::UnsafeOperationOutsideUnsafeBlock(span) => Diagnostic::error()
+                .with_message("unsafe operation outside of an `unsafe` block")
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Dereferencing pointers, casting to or from pointers, reading fields of \
+                     unions and calling extern functions marked `#[unsafe]` is only allowed in \
+                     `unsafe { ... }` blocks."
+                        .to_string(),
+                ]),
+            Self::TypeNotComparable { ref ttype, span } => Diagnostic::error()
+                .with_message(format!(
+                    "values of type {ttype} can't be compared with `==`"
+                ))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Structs declared with `#[eq]` are compared field by field, which requires \
+                     all of their fields to be comparable."
+                        .to_string(),
+                ]),
+            Self::NoStringConversion(ref ttype, span) => Diagnostic::error()
+                .with_message(format!("type {ttype} can't be converted to a string"))
+                .with_labels(vec![Label::primary(span.source.0, span)])
+                .with_notes(vec![
+                    "Only integers, characters, booleans and strings can be interpolated."
+                        .to_string(),
+                ]),
+            Self::DuplicateEnumVariant(ref variant_name, span) => Diagnostic::error()
+                .with_message("duplicate enum variant")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("variant `{}` is already declared", variant_name),
+                )]),
+            Self::UnknownEnumVariant(ref enum_name, ref variant_name, span) => Diagnostic::error()
+                .with_message("unknown enum variant")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!("no variant `{}` on enum `{}`", variant_name, enum_name),
+                )]),
+            Self::EnumVariantWrongType(
+                ref enum_name,
+                ref variant_name,
+                ref actual,
+                ref expected,
+                span,
+            ) => Diagnostic::error()
+                .with_message("wrong type for value of enum variant")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!(
+                        "expression has type {} but variant expects type {}",
+                        actual, expected
+                    ),
+                )])
+                .with_notes(vec![format!(
+                    "Variant `{}::{}` holds a value of type `{}`",
+                    enum_name,
+                    variant_name,
+                    expected.to_string()
+                )]),
+            Self::EnumVariantMissingValue(ref enum_name, ref variant_name, span) => {
+                Diagnostic::error()
+                    .with_message("missing value for enum variant")
+                    .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                        format!("variant `{}::{}` holds a value", enum_name, variant_name),
+                    )])
+            }
+            Self::EnumVariantUnexpectedValue(ref enum_name, ref variant_name, span) => {
+                Diagnostic::error()
+                    .with_message("unexpected value for enum variant")
+                    .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                        format!(
+                            "variant `{}::{}` does not hold a value",
+                            enum_name, variant_name
+                        ),
+                    )])
+            }
+            Self::PatternTypeMismatch(ref pattern_type, ref scrutinee_type, span) => {
+                Diagnostic::error()
+                    .with_message("pattern does not match type of matched value")
+                    .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                        format!(
+                            "pattern has type `{}` but matched value has type `{}`",
+                            pattern_type, scrutinee_type
+                        ),
+                    )])
+            }
+            Self::MatchArmTypeMismatch(ref actual, ref expected, span) => Diagnostic::error()
+                .with_message("match arms have incompatible types")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!(
+                        "arm has type `{}` but previous arms have type `{}`",
+                        actual, expected
+                    ),
+                )]),
+            Self::NonExhaustiveMatch(ref missing_patterns, span) => Diagnostic::error()
+                .with_message("non-exhaustive match")
+                .with_labels(vec![Label::primary(span.source.0, span).with_message(
+                    format!(
+                        "patterns not covered: {}",
+                        missing_patterns
+                            .iter()
+                            .map(|pattern| format!("`{}`", pattern))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                )])
+                .with_notes(vec![format!(
+                    "Add arms for the missing patterns or a wildcard `_` arm"
+                )]),
+            Self::CyclicTypeAlias(span) => Diagnostic::error()
+                .with_message("cyclic type alias")
+                .with_labels(vec![
+                    Label::primary(span.source.0, span).with_message("alias expands to itself")
+                ]),
+            Self::NonConstantInitializer(span) => Diagnostic::error()
+                .with_message("constant initializer is not a constant expression")
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("cannot be evaluated at compile time")]),
+            Self::ConstantEvaluationFailed(failure, span) => Diagnostic::error()
+                .with_message(const_eval_failure_message(failure))
+                .with_labels(vec![Label::primary(span.source.0, span)
+                    .with_message("evaluated at compile time")]),
+        }
+    }
+
+    fn json(&self, source_map: &SourceMap) -> serde_json::Value {
+        match *self {
+            Self::WrongNumArgs(span, actual, expected) => json!({
+                "message":
+                format!(
+                    "incorrect number of arguments to function call, expected {} but found {}",
+                    expected,
+                    actual
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::WrongArgType(span, ref actual, ref expected) => json!({
+                "message":
+                    format!(
+                        "incorrect argument type in function call, expected type {} but found type {}",
+                        expected,
+                        actual
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownFunction(ref function_name, span) => json!({
+                "message": format!("reference to unknown function `{}`", function_name),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownVariable(ref variable_name, ref function_name, span) => {
+                let message = if let Some(functio_name) = function_name {
+                    format!(
+                        "reference to unknown variable `{}` in function `{}`",
+                        variable_name, functio_name
+                    )
+                } else {
+                    format!("unknown variable `{}`", variable_name)
+                };
+                json!({
+                    "message": message,
+                    "span": span.json_with_source(source_map),
+                })
+            }
+            Self::WrongConditionType(span, ref actual) => json!({
+                "message":
+                    format!(
+                        "wrong type in condition, expected `{}` but found `{}`",
+                        Type::Bool,
+                        actual
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::BinaryOpMismatch(ref lhs_type, ref rhs_type, lhs_span, rhs_span) => json!({
+                "message":
+                    format!(
+                        "type mismatch in binary operator, type `{}` on the left and type `{}` on the right",
+                        lhs_type,
+                        rhs_type
+                    ),
+                "span": lhs_span.to(rhs_span).json_with_source(source_map),
+            }),
+            Self::UnknownType(ref type_name, span) => json!({
+                "message": format!("reference to unknown type `{}`", type_name),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::OpaqueStructFieldAccess(ref object_type, span) => json!({
+                "message": format!("field access on opaque struct type `{}`", object_type),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::FieldAccessInvalidField(ref object_type, ref field_name, span) => json!({
+                "message":
+                    format!(
+                        "struct type `{}` has no field by the name of `{}`",
+                        object_type,
+                        field_name
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+
+            Self::ObjectIsNotAStruct(ref object_type, span) => json!({
+                "message": format!("object of type `{}` is not a struct", object_type),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::StructMissingField(ref struct_name, ref missing_field_name, span) => json!({
+                "message":
+                    format!(
+                        "initializer for field `{}` missing in struct literal for struct type `{}`",
+                        missing_field_name,
+                        struct_name
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::StructFieldWrongType(
+                ref _struct_name,
+                ref field_name,
+                ref actual,
+                ref expected,
+                span,
+            ) => json!({
+                "message":
+                    format!(
+                        "field `{}` has incorrect type, expected `{}` but found `{}`",
+                        field_name,
+                        expected,
+                        actual
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::StructSuperfluousField(ref struct_name, ref field_name, span) => json!({
+                "message":
+                    format!(
+                        "struct type `{struct_name}` has no field by the name of `{field_name}`"
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::InvalidReturnType(ref actual, ref expected, span) => json!({
+                "message":
+                    format!(
+                        "return value has type `{}` but function expected type `{}`",
+                        actual,
+                        expected,
                     ),
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             Self::DuplicateParameterName(ref name, span) => json!({
                 "message": format!("parameter name `{name}` used more than once"),
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             Self::DuplicateVariableName(ref name, span) => json!({
                 "message": format!("variable name `{name}` used more than once in this function"),
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DuplicateFunctionDefinition {
+                ref name,
+                first_span,
+                second_span,
+            } => json!({
+                "message": format!("function `{name}` is defined more than once"),
+                "span": second_span.json_with_source(source_map),
+                "first_span": first_span.json_with_source(source_map),
+            }),
+            Self::MissingReturn {
+                ref function_name,
+                span,
+            } => json!({
+                "message": format!("function `{function_name}` may not return a value"),
+                "span": span.json_with_source(source_map),
             }),
             Self::DuplicateFuncStructName(ref name, span) => json!({
                 "message": format!("function or struct name `{name}` used more than once"),
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             Self::WrongElementTypeInArray(ref actual, ref expected, span) => json!({
                 "message":
                     format!(
                         "element has type `{}` but array expected type `{}`",
-                        actual.to_str(),
-                        expected.to_str()
+                        actual,
+                        expected
                     ),
-                    "span": span.json(),
+                    "span": span.json_with_source(source_map),
             }),
-            Self::InvalidIterableInForIn(ref actual, span) => json!({
-                "message":
-                    format!(
-                        "element has type `{}` but expected array in for-in loop",
-                        actual.to_str(),
-                    ),
-                "span": span.json(),
+            Self::NotIterable(ref actual, span) => json!({
+                "message": format!("`{}` is not iterable", actual),
+                "span": span.json_with_source(source_map),
             }),
             Self::AssignmentTypeMismatch(ref _actual, ref _expected, _lhs_span, rhs_span) => {
                 json!({
                     "message": "type mismatch in assignment",
-                    "json": rhs_span.json(),
+                    "span": rhs_span.json_with_source(source_map),
+                })
+            }
+            Self::TypeAnnotationMismatch(
+                ref expected,
+                ref actual,
+                _annotation_span,
+                value_span,
+            ) => {
+                json!({
+                    "message":
+                        format!(
+                            "value has type `{}` but expected `{}`",
+                            actual,
+                            expected,
+                        ),
+                    "span": value_span.json_with_source(source_map),
                 })
             }
+            Self::ArraySizeMismatch(expected, actual, _annotation_span, value_span) => json!({
+                "message": format!("array has {actual} elements but expected {expected}"),
+                "span": value_span.json_with_source(source_map),
+            }),
             Self::AssignmentToNonLValue(span) => json!({
                 "message": "lhs of assignment is not an l-value",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             Self::AssignmentToImmutable(span) => json!({
                 "message": "lhs of assignment is not mutable",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NullAssignmentToNarrowedVariable(ref name, span) => json!({
+                "message": format!(
+                    "cannot assign a pointer that may be null to `{name}`, which was checked not to be null"
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::RefArgumentNotLValue(span) => json!({
+                "message": "argument for `ref` parameter is not an l-value",
+                "span": span.json_with_source(source_map),
             }),
             Self::MutablePointerToImmutableVariable(span) => json!({
                 "message": "cannot create mutable pointer to immutable variable",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
             Self::DerefNonPointer(ref _ttype, span) => json!({
                 "message": "cannot dereference value of non-pointer type",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::PotentialNullDeref(span) => json!({
+                "message": "cannot dereference a pointer that may be null",
+                "span": span.json_with_source(source_map),
             }),
             Self::NotAnArray(span) => json!({
                 "message": "expression is not an array",
-                "span": span.json(),
+                "span": span.json_with_source(source_map),
             }),
-            Self::InvalidArrayIndex(span) => json!({
-                "message": "invalid index for array",
-                "span": span.json(),
+            Self::NotATuple(ref object_type, span) => json!({
+                "message":
+                    format!("trying to index into non-tuple type `{}`", object_type),
+                "span": span.json_with_source(source_map),
             }),
-        }
-    }
-}
-
-#[derive(Debug)]
-pub struct CheckedFunctionCall {
-    pub name: String,
-    pub args: Vec<CheckedExpression>,
-    pub ttype: Type,
-}
-
-#[derive(Debug)]
-pub struct CheckedStructLiteral {
-    pub name: String,
-    pub fields: HashMap<String, CheckedExpression>,
-}
-
-#[derive(Debug)]
-pub struct CheckedArrayLiteral {
-    pub elements: Vec<CheckedExpression>,
-    pub element_type: Option<Type>,
-}
-
-#[derive(Debug)]
-pub enum CheckedLiteral {
-    String(String, Type),
-    Int(i32, Type),
-    Bool(bool, Type),
-    Struct(CheckedStructLiteral, Struct, Type),
-    Array(CheckedArrayLiteral, Type),
-}
-
-#[derive(Debug)]
-pub struct CheckedFieldAccess {
-    pub object: Box<CheckedExpression>,
-    pub object_is_ptr: bool,
-    pub field_name: String,
-    pub is_mut: bool,
-}
-
-#[derive(Debug)]
-pub struct CheckedArrayIndex {
-    pub array: Box<CheckedExpression>,
-    pub index: Box<CheckedExpression>,
-    pub ttype: Type,
-}
-
-#[derive(Debug)]
-pub struct CheckedPointerTo {
-    pub inner: Box<CheckedExpression>,
-    pub ttype: Type,
-}
-
-#[derive(Debug)]
+            Self::TupleIndexOutOfBounds(index, ref tuple_type, span) => json!({
+                "message":
+                    format!("tuple type `{}` has no element {index}", tuple_type),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownMethod(ref object_type, ref method_name, span) => json!({
+                "message":
+                    format!(
+                        "type `{}` has no method by the name of `{method_name}`",
+                        object_type
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DuplicateMethod(ref type_name, ref method_name, span) => json!({
+                "message":
+                    format!("method `{method_name}` is already declared for type `{type_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::MethodWithoutReceiver(ref method_name, span) => json!({
+                "message": format!("method `{method_name}` has no receiver parameter"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NonIntegerIndex { ref found, span } => json!({
+                "message": format!("array index has non-integer type `{found}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::StaticIndexOutOfBounds { index, size, span } => json!({
+                "message": format!("index {index} is out of bounds for an array of {size} elements"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::LogicalOpNonBool(ref actual, span) => json!({
+                "message":
+                    format!(
+                        "operand of logical operator has type `{}` but expected `{}`",
+                        actual,
+                        Type::Bool
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NegateNonNumeric(ref actual, span) => json!({
+                "message": format!("cannot negate value of non-numeric type `{}`", actual),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NotNonBool(ref actual, span) => json!({
+                "message": format!("cannot apply `!` to value of non-bool type `{}`", actual),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::ModuloNonInteger(ref actual, span) => json!({
+                "message": format!("operand of `%` has non-integer type `{}`", actual),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::BitwiseOpNonInteger(op, ref actual, span) => json!({
+                "message": format!(
+                    "operand of `{}` has non-integer type `{}`",
+                    op.symbol(),
+                    actual
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::BitNotNonInteger(ref actual, span) => json!({
+                "message": format!("cannot apply `~` to value of non-integer type `{}`", actual),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownAttribute(ref name, span) => json!({
+                "message": format!("unknown attribute `{name}`"),
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::RangeNonInteger(ref actual, span) => json!({
+                "message": format!("bound of range has non-integer type `{}`", actual),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::BreakOutsideLoop(span) => json!({
+                "message": "`break` outside of a loop",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::ContinueOutsideLoop(span) => json!({
+                "message": "`continue` outside of a loop",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownLoopLabel(ref label, span) => json!({
+                "message": format!("no enclosing loop is labeled `'{label}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::TypeParameterCountMismatch {
+                ref name,
+                expected,
+                found,
+                span,
+            } => json!({
+                "message": format!("`{name}` expects {expected} type arguments but {found} were given"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::CannotInferTypeParam {
+                ref param_name,
+                ref name,
+                span,
+            } => json!({
+                "message": format!("cannot infer type parameter `{param_name}` of `{name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnboundedGenericRecursion(ref name, span) => json!({
+                "message": format!("generic instantiation of `{name}` recurses without bound"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownTrait(ref trait_name, span) => json!({
+                "message": format!("reference to unknown trait `{trait_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DuplicateTrait(ref trait_name, span) => json!({
+                "message": format!("trait `{trait_name}` is declared more than once"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::TraitMethodMissing {
+                ref trait_name,
+                ref method_name,
+                span,
+            } => json!({
+                "message": format!("missing method `{method_name}` of trait `{trait_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::MethodNotInTrait {
+                ref trait_name,
+                ref method_name,
+                span,
+            } => json!({
+                "message": format!("method `{method_name}` is not a member of trait `{trait_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::TraitMethodSignatureMismatch {
+                ref trait_name,
+                ref method_name,
+                ref expected,
+                ref found,
+                span,
+            } => json!({
+                "message":
+                    format!(
+                        "method `{method_name}` has type `{found}` but trait `{trait_name}` declares `{expected}`"
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::TraitNotImplemented {
+                ref ttype,
+                ref trait_name,
+                span,
+            } => json!({
+                "message": format!("type `{ttype}` doesn't implement trait `{trait_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::MethodNotInBounds {
+                ref type_param,
+                ref method_name,
+                span,
+            } => json!({
+                "message": format!(
+                    "the bounds of type parameter `{type_param}` declare no method `{method_name}`"
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::OperatorOnTypeParam(ref type_param, span) => json!({
+                "message": format!("operator applied to a value of type parameter `{type_param}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::LetPatternMismatch {
+                ref pattern,
+                ref value_type,
+                span,
+            } => json!({
+                "message":
+                    format!("pattern destructures {pattern} but the value has type `{value_type}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnsizedOpaqueStruct(ref struct_name, span) => json!({
+                "message": format!("opaque struct `{struct_name}` has no known size"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::IfWithoutElse(ref ttype, span) => json!({
+                "message": format!("`if` without `else` is `unit` but its branch has type `{ttype}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::IncompatibleIfBranches {
+                ref if_type,
+                ref else_type,
+                span,
+            } => json!({
+                "message": format!(
+                    "`if` branch has type `{if_type}` but `else` branch has type `{else_type}`"
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownNamedArg {
+                ref function_name,
+                ref arg_name,
+                span,
+            } => json!({
+                "message": format!("`{function_name}` has no parameter named `{arg_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::MissingArg {
+                ref function_name,
+                ref param_name,
+                span,
+            } => json!({
+                "message": format!(
+                    "missing argument for parameter `{param_name}` of `{function_name}`"
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DuplicateArg {
+                ref param_name,
+                span,
+            } => json!({
+                "message": format!("parameter `{param_name}` is given more than once"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DeferNotCall(span) => json!({
+                "message": "only function and method calls can be deferred",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DeferNotUnit(ref ttype, span) => json!({
+                "message": format!("deferred call returns `{ttype}` instead of `unit`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::InvalidTestFunction(ref name, span) => json!({
+                "message": format!(
+                    "test function `{name}` has to take no parameters and return `unit`"
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::InvalidCast {
+                ref from,
+                ref to,
+                span,
+            } => json!({
+                "message": format!("can't cast `{from}` to `{to}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::IntegerToPointerCast(ref ttype, span) => json!({
+                "message": format!("casting an integer to `{ttype}`"),
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::InvalidSwitchType(ref ttype, span) => json!({
+                "message": format!("can't switch on a value of type `{ttype}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NonConstantSwitchCase(span) => json!({
+                "message": "switch case is not a constant expression",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DuplicateSwitchCase(span) => json!({
+                "message": "value is already handled by an earlier case",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NonExhaustiveSwitch(ref ttype, ref missing_variants, span) => json!({
+                "message": if missing_variants.is_empty() {
+                    format!("switch on `{ttype}` without `default` doesn't handle every value")
+                } else {
+                    format!(
+                        "switch on `{ttype}` without `default` doesn't handle {}",
+                        missing_variants.join(", ")
+                    )
+                },
+                "severity": "warning",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnionLiteralFieldCount(ref union_name, span) => json!({
+                "message": format!(
+                    "union literal of `{union_name}` has to initialize exactly one field"
+                ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnsafeOperationOutsideUnsafeBlock(span) => json!({
+                "message": "unsafe operation outside of an `unsafe` block",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::TypeNotComparable { ref ttype, span } => json!({
+                "message": format!("values of type `{ttype}` can't be compared with `==`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NoStringConversion(ref ttype, span) => json!({
+                "message": format!("type `{ttype}` can't be converted to a string"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::DuplicateEnumVariant(ref variant_name, span) => json!({
+                "message": format!("duplicate enum variant `{variant_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::UnknownEnumVariant(ref enum_name, ref variant_name, span) => json!({
+                "message":
+                    format!("enum type `{enum_name}` has no variant by the name of `{variant_name}`"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::EnumVariantWrongType(
+                ref _enum_name,
+                ref variant_name,
+                ref actual,
+                ref expected,
+                span,
+            ) => json!({
+                "message":
+                    format!(
+                        "value of variant `{}` has incorrect type, expected `{}` but found `{}`",
+                        variant_name,
+                        expected,
+                        actual
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::EnumVariantMissingValue(ref enum_name, ref variant_name, span) => json!({
+                "message": format!("variant `{enum_name}::{variant_name}` is missing its value"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::EnumVariantUnexpectedValue(ref enum_name, ref variant_name, span) => json!({
+                "message": format!("variant `{enum_name}::{variant_name}` does not hold a value"),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::PatternTypeMismatch(ref pattern_type, ref scrutinee_type, span) => json!({
+                "message":
+                    format!(
+                        "pattern has type `{}` but matched value has type `{}`",
+                        pattern_type,
+                        scrutinee_type,
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::MatchArmTypeMismatch(ref actual, ref expected, span) => json!({
+                "message":
+                    format!(
+                        "match arm has type `{}` but previous arms have type `{}`",
+                        actual,
+                        expected,
+                    ),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NonExhaustiveMatch(ref missing_patterns, span) => json!({
+                "message":
+                    format!("non-exhaustive match, missing {}", missing_patterns.join(", ")),
+                "span": span.json_with_source(source_map),
+            }),
+            Self::CyclicTypeAlias(span) => json!({
+                "message": "cyclic type alias",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::NonConstantInitializer(span) => json!({
+                "message": "constant initializer is not a constant expression",
+                "span": span.json_with_source(source_map),
+            }),
+            Self::ConstantEvaluationFailed(failure, span) => json!({
+                "message": const_eval_failure_message(failure),
+                "span": span.json_with_source(source_map),
+            }),
+        }
+    }
+
+    fn is_warning(&self) -> bool {
+        matches!(
+            self,
+            Self::UnknownAttribute(..)
+                | Self::IntegerToPointerCast(..)
+                | Self::NonExhaustiveSwitch(..)
+        )
+    }
+}
+
+fn const_eval_failure_message(failure: ConstEvalFailure) -> String {
+    match failure {
+        ConstEvalFailure::NotConstant => {
+            "constant initializer is not a constant expression".to_string()
+        }
+        ConstEvalFailure::Overflow(op) => {
+            format!("`{}` in constant initializer overflows", op.symbol())
+        }
+        ConstEvalFailure::DivisionByZero => "division by zero in constant initializer".to_string(),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedFunctionCall {
+    pub name: String,
+    pub args: Vec<CheckedExpression>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedFunctionPointerCall {
+    pub callee: Box<CheckedExpression>,
+    pub args: Vec<CheckedExpression>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedStructLiteral {
+    pub name: String,
+    pub fields: HashMap<String, CheckedExpression>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedArrayLiteral {
+    pub elements: Vec<CheckedExpression>,
+    pub element_type: Option<Type>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CheckedLiteral {
+    String(String, Type),
+    Char(char, Type),
+    Int(i32, Type),
+    Bool(bool, Type),
+    Null(Type),
+    Struct(CheckedStructLiteral, Struct, Type),
+    Array(CheckedArrayLiteral, Type),
+    Tuple(Vec<CheckedExpression>, Type),
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedFieldAccess {
+    pub object: Box<CheckedExpression>,
+    pub object_is_ptr: bool,
+    pub field_name: String,
+    pub is_mut: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedTupleIndex {
+    pub object: Box<CheckedExpression>,
+    pub index: usize,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedArrayToSlice {
+    pub array: Box<CheckedExpression>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedPointerToNullable {
+    pub pointer: Box<CheckedExpression>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedArrayIndex {
+    pub array: Box<CheckedExpression>,
+    pub index: Box<CheckedExpression>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedPointerTo {
+    pub inner: Box<CheckedExpression>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedEnumVariant {
+    pub r#enum: Enum,
+    pub variant_index: usize,
+    pub value: Option<Box<CheckedExpression>>,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
 pub struct CheckedDeref {
     pub inner: Box<CheckedExpression>,
     pub ttype: Type,
 }
 
-#[derive(Debug)]
-pub enum CheckedExpression {
-    Literal(CheckedLiteral),
-    FunctionCall(CheckedFunctionCall),
-    Variable(String, Type, bool),
-    CompareOp(
-        Box<CheckedExpression>,
-        Box<CheckedExpression>,
-        CompareOperation,
-        Type,
-    ),
-    MathOp(
-        Box<CheckedExpression>,
-        Box<CheckedExpression>,
-        MathOperation,
-        Type,
-    ),
-    FieldAccess(CheckedFieldAccess, Struct, Type),
-    ArrayIndex(CheckedArrayIndex),
-    Assignment(Box<CheckedExpression>, Box<CheckedExpression>),
-    PointerTo(CheckedPointerTo),
-    Deref(CheckedDeref),
+/// `cast<T>(inner)`, where `ttype` is `T`.
+#[derive(Debug, Clone)]
+pub struct CheckedCast {
+    pub inner: Box<CheckedExpression>,
+    pub ttype: Type,
+}
+
+/// The functions on `str` values, which are called like functions without being declared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrBuiltin {
+    /// `str_len(s: str): int`, the length of the string in bytes
+    Len,
+    /// `str_data(s: str): ->c_char`, a pointer to the first byte of the string. Only the data of
+    /// string literals is followed by a 0 like a C string.
+    Data,
+    /// `str_slice(s: str, start: int, end: int): str`, the bytes from `start` up to `end`
+    Slice,
+    /// The `str` of a C string up to its terminating 0, which interpolated strings are coerced with
+    /// where a `str` is expected. It can't be called by name.
+    FromCString,
+}
+
+impl StrBuiltin {
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "str_len" => Self::Len,
+            "str_data" => Self::Data,
+            "str_slice" => Self::Slice,
+            _ => return None,
+        })
+    }
+
+    fn parameters(self) -> Vec<(&'static str, Type)> {
+        match self {
+            Self::Len | Self::Data => vec![("s", Type::Str)],
+            Self::Slice => vec![("s", Type::Str), ("start", Type::Int), ("end", Type::Int)],
+            Self::FromCString => vec![("s", Type::Pointer(Box::new(Type::CChar), false))],
+        }
+    }
+
+    pub fn return_type(self) -> Type {
+        match self {
+            Self::Len => Type::Int,
+            Self::Data => Type::Pointer(Box::new(Type::CChar), false),
+            Self::Slice | Self::FromCString => Type::Str,
+        }
+    }
+}
+
+/// A call of one of the [`StrBuiltin`]s, the string is its first argument. The arguments of
+/// `str_slice` are constants or variables, so that they can be evaluated twice.
+#[derive(Debug, Clone)]
+pub struct CheckedStrBuiltin {
+    pub builtin: StrBuiltin,
+    pub args: Vec<CheckedExpression>,
+}
+
+/// `{ statements; value }` used as an expression, evaluating to `value` after running the
+/// statements. Without a value the block is of type `unit`, or `!` if its end is never reached.
+#[derive(Debug, Clone)]
+pub struct CheckedBlockExpression {
+    pub body: CheckedBlock,
+    pub value: Option<Box<CheckedExpression>>,
+    pub ttype: Type,
+}
+
+/// `if` used as an expression. Without an `else` branch the else body is empty and the expression
+/// is of type `unit`.
+#[derive(Debug, Clone)]
+pub struct CheckedIfExpr {
+    pub condition: Box<CheckedExpression>,
+    pub if_body: CheckedBlockExpression,
+    pub else_body: CheckedBlockExpression,
+    pub ttype: Type,
+}
+
+impl CheckedIfExpr {
+    /// The if-else statement doing the same, discarding the values of both branches.
+    fn into_statement(self) -> CheckedStatement {
+        let into_block = |block: CheckedBlockExpression| {
+            let mut body = block.body;
+            if let Some(value) = block.value {
+                body.statements.push(CheckedStatement::Expression(*value));
+            }
+            body
+        };
+
+        CheckedStatement::IfElse(CheckedIfElse {
+            condition: *self.condition,
+            if_body: into_block(self.if_body),
+            else_body: into_block(self.else_body),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedRange {
+    pub start: Box<CheckedExpression>,
+    pub end: Box<CheckedExpression>,
+    pub inclusive: bool,
+    pub ttype: Type,
+}
+
+#[derive(Debug, Clone)]
+pub enum CheckedExpression {
+    Literal(CheckedLiteral),
+    FunctionCall(CheckedFunctionCall),
+    FunctionPointerCall(CheckedFunctionPointerCall),
+    Variable(String, Type, bool),
+    Function(String, Type),
+    CompareOp(
+        Box<CheckedExpression>,
+        Box<CheckedExpression>,
+        CompareOperation,
+        Type,
+    ),
+    MathOp(
+        Box<CheckedExpression>,
+        Box<CheckedExpression>,
+        MathOperation,
+        Type,
+    ),
+    LogicalAnd(Box<CheckedExpression>, Box<CheckedExpression>),
+    LogicalOr(Box<CheckedExpression>, Box<CheckedExpression>),
+    UnaryMinus(Box<CheckedExpression>, Type),
+    Not(Box<CheckedExpression>),
+    BitNot(Box<CheckedExpression>, Type),
+    FieldAccess(CheckedFieldAccess, Struct, Type),
+    TupleIndex(CheckedTupleIndex),
+    ArrayIndex(CheckedArrayIndex),
+    ArrayToSlice(CheckedArrayToSlice),
+    PointerToNullable(CheckedPointerToNullable),
+    Assignment(Box<CheckedExpression>, Box<CheckedExpression>),
+    Range(CheckedRange),
+    PointerTo(CheckedPointerTo),
+    Deref(CheckedDeref),
+    EnumVariant(CheckedEnumVariant),
+    SizeOf(Type),
+    /// The offset in bytes of the named field of a struct.
+    OffsetOf(Struct, String),
+    Cast(CheckedCast),
+    StrBuiltin(CheckedStrBuiltin),
+    Block(CheckedBlockExpression),
+    IfExpr(CheckedIfExpr),
+}
+
+impl CheckedExpression {
+    pub fn ttype(&self) -> Type {
+        match self {
+            Self::Literal(literal) => match literal {
+                CheckedLiteral::String(_, ttype) => ttype,
+                CheckedLiteral::Char(_, ttype) => ttype,
+                CheckedLiteral::Int(_, ttype) => ttype,
+                CheckedLiteral::Bool(_, ttype) => ttype,
+                CheckedLiteral::Null(ttype) => ttype,
+                CheckedLiteral::Struct(_, _, ttype) => ttype,
+                CheckedLiteral::Array(_, ttype) => ttype,
+                CheckedLiteral::Tuple(_, ttype) => ttype,
+            }
+            .clone(),
+            Self::FunctionCall(func_call) => func_call.ttype.clone(),
+            Self::FunctionPointerCall(func_call) => func_call.ttype.clone(),
+            Self::Function(_, ttype) => ttype.clone(),
+            Self::Variable(_name, ttype, _is_mut) => ttype.clone(),
+            Self::CompareOp(_lhs, _rhs, _op, ttype) => ttype.clone(),
+            Self::MathOp(_lhs, _rhs, _op, ttype) => ttype.clone(),
+            Self::LogicalAnd(_lhs, _rhs) | Self::LogicalOr(_lhs, _rhs) => Type::Bool,
+            Self::UnaryMinus(_inner, ttype) => ttype.clone(),
+            Self::Not(_inner) => Type::Bool,
+            Self::BitNot(_inner, ttype) => ttype.clone(),
+            Self::FieldAccess(_field_access, _struct, ttype) => ttype.clone(),
+            Self::TupleIndex(tuple_index) => tuple_index.ttype.clone(),
+            Self::ArrayIndex(array_index) => array_index.ttype.clone(),
+            Self::ArrayToSlice(array_to_slice) => array_to_slice.ttype.clone(),
+            Self::PointerToNullable(pointer_to_nullable) => pointer_to_nullable.ttype.clone(),
+            Self::Assignment(_lhs, _rhs) => Type::Unit,
+            Self::Range(range) => range.ttype.clone(),
+            Self::PointerTo(pointer_to) => pointer_to.ttype.clone(),
+            Self::Deref(deref) => deref.ttype.clone(),
+            Self::EnumVariant(enum_variant) => enum_variant.ttype.clone(),
+            Self::SizeOf(_) | Self::OffsetOf(..) => Type::Int,
+            Self::Cast(cast) => cast.ttype.clone(),
+            Self::StrBuiltin(str_builtin) => str_builtin.builtin.return_type(),
+            Self::Block(block) => block.ttype.clone(),
+            Self::IfExpr(if_expr) => if_expr.ttype.clone(),
+        }
+    }
+
+    pub fn is_mut(&self) -> bool {
+        match self {
+            Self::Variable(_, _, is_mut) => *is_mut,
+            Self::Function(_, _) => false,
+            Self::FieldAccess(field_acces, _, _) => field_acces.is_mut,
+            Self::TupleIndex(tuple_index) => tuple_index.object.is_mut(),
+            _ => true,
+        }
+    }
+
+    /// Whether this is a block or `if` containing a `break` that exits the loop `loop_depth` loops
+    /// out from it.
+    fn contains_break(&self, loop_depth: usize) -> bool {
+        match self {
+            Self::Block(block) => block.body.contains_break(loop_depth),
+            Self::IfExpr(if_expr) => {
+                if_expr.if_body.body.contains_break(loop_depth)
+                    || if_expr.else_body.body.contains_break(loop_depth)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedWhileLoop {
+    pub condition: CheckedExpression,
+    pub body: CheckedBlock,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedIfElse {
+    pub condition: CheckedExpression,
+    pub if_body: CheckedBlock,
+    pub else_body: CheckedBlock,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedForInLoop {
+    pub elem_var_name: String,
+    pub elem_var_type: Type,
+    pub index_var: Option<String>,
+    pub iterable: CheckedExpression,
+    pub body: CheckedBlock,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedEnumVariantPattern {
+    pub r#enum: Enum,
+    pub variant_index: usize,
+    pub binding: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CheckedPattern {
+    Wildcard,
+    /// Always a `CheckedExpression::Literal`
+    Literal(CheckedExpression),
+    EnumVariant(CheckedEnumVariantPattern),
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedMatchArm {
+    pub pattern: CheckedPattern,
+    pub body: CheckedExpression,
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedMatch {
+    pub scrutinee: CheckedExpression,
+    pub arms: Vec<CheckedMatchArm>,
+}
+
+/// A `switch` with the constant values each of its cases handles. Enum values are switched on by
+/// their tag, so their cases are the indices of the variants.
+#[derive(Debug, Clone)]
+pub struct CheckedSwitch {
+    pub scrutinee: CheckedExpression,
+    pub cases: Vec<(Vec<i64>, CheckedBlock)>,
+    /// Empty for a `switch` without `default`
+    pub default: CheckedBlock,
+}
+
+#[derive(Debug, Clone)]
+pub enum CheckedStatement {
+    Expression(CheckedExpression),
+    LetAssign(String, CheckedExpression),
+    WhileLoop(CheckedWhileLoop),
+    Loop(CheckedBlock),
+    IfElse(CheckedIfElse),
+    Match(CheckedMatch),
+    Switch(CheckedSwitch),
+    ForInLoop(CheckedForInLoop),
+    Return(CheckedExpression),
+    /// `break` and `continue` with the number of loops between them and the loop they leave, `0`
+    /// for the innermost loop
+    Break(usize),
+    Continue(usize),
+    /// Constants are substituted at their uses, so their declarations leave nothing to emit.
+    Const,
+}
+
+impl CheckedStatement {
+    /// Whether control flow can never continue past this statement.
+    pub fn diverges(&self) -> bool {
+        match self {
+            Self::Return(_) | Self::Break(_) | Self::Continue(_) => true,
+            Self::Expression(expression) => expression.ttype() == Type::Never,
+            Self::IfElse(if_else) => if_else.if_body.diverges() && if_else.else_body.diverges(),
+            Self::Switch(switch) => {
+                switch.cases.iter().all(|(_, body)| body.diverges()) && switch.default.diverges()
+            }
+            // Without a `break` leaving it, which may be in a nested loop, the only way out of a
+            // `loop` is by returning from the function
+            Self::Loop(body) => !body.contains_break(0),
+            Self::LetAssign(_, _)
+            | Self::Match(_)
+            | Self::Const
+            | Self::WhileLoop(_)
+            | Self::ForInLoop(_) => false,
+        }
+    }
+
+    /// Whether this statement contains a `break` that exits the loop `loop_depth` loops out from
+    /// it.
+    fn contains_break(&self, loop_depth: usize) -> bool {
+        match self {
+            Self::Break(depth) => *depth == loop_depth,
+            Self::Expression(expression) => expression.contains_break(loop_depth),
+            Self::Match(r#match) => r#match
+                .arms
+                .iter()
+                .any(|arm| arm.body.contains_break(loop_depth)),
+            Self::Switch(switch) => {
+                switch
+                    .cases
+                    .iter()
+                    .any(|(_, body)| body.contains_break(loop_depth))
+                    || switch.default.contains_break(loop_depth)
+            }
+            Self::IfElse(if_else) => {
+                if_else.if_body.contains_break(loop_depth)
+                    || if_else.else_body.contains_break(loop_depth)
+            }
+            Self::WhileLoop(CheckedWhileLoop { body, .. })
+            | Self::Loop(body)
+            | Self::ForInLoop(CheckedForInLoop { body, .. }) => body.contains_break(loop_depth + 1),
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedBlock {
+    pub statements: Vec<CheckedStatement>,
+}
+
+impl CheckedBlock {
+    /// Whether control flow can never reach the end of this block.
+    pub fn diverges(&self) -> bool {
+        self.statements.iter().any(CheckedStatement::diverges)
+    }
+
+    /// The type of the block's result, `!` if the end of the block is never reached.
+    pub fn ttype(&self) -> Type {
+        if self.diverges() {
+            Type::Never
+        } else {
+            Type::Unit
+        }
+    }
+
+    fn contains_break(&self, loop_depth: usize) -> bool {
+        self.statements
+            .iter()
+            .any(|statement| statement.contains_break(loop_depth))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheckedParameter {
+    pub name: String,
+    /// A pointer to the argument for `ref` parameters.
+    pub ttype: Type,
+    pub is_by_ref: bool,
+}
+
+#[derive(Debug)]
+pub struct CheckedFunction {
+    pub name: String,
+    pub body: CheckedBlock,
+    pub parameters: Vec<CheckedParameter>,
+    pub return_type: Type,
+    pub is_inline: bool,
+}
+
+#[derive(Debug)]
+pub struct CheckedExternFunction {
+    /// One of [`KNOWN_CALLING_CONVENTIONS`], `None` for the C calling convention
+    pub calling_convention: Option<String>,
+    pub name: String,
+    pub parameters: Vec<CheckedParameter>,
+    pub is_variadic: bool,
+    pub return_type: Type,
+}
+
+#[derive(Debug, Clone)]
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<(String, Type)>,
+    pub is_opaque: bool,
+    /// The size in bytes an opaque struct was declared with, values of opaque structs without one
+    /// can't be created
+    pub opaque_size: Option<usize>,
+    /// Whether the struct is declared with `#[eq]`, so its values can be compared field by field
+    pub has_eq: bool,
+    /// Whether this is a union, whose fields all start at the beginning of its memory
+    pub is_union: bool,
+}
+
+impl Struct {
+    fn get_field(&self, field_name: &str) -> Option<&Type> {
+        for (delcared_field_name, declared_field_type) in &self.fields {
+            if delcared_field_name == field_name {
+                return Some(declared_field_type);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Enum {
+    pub name: String,
+    pub variants: Vec<(String, Option<Type>)>,
+}
+
+impl Enum {
+    fn get_variant(&self, variant_name: &str) -> Option<(usize, &Option<Type>)> {
+        self.variants
+            .iter()
+            .position(|(declared_variant_name, _)| declared_variant_name == variant_name)
+            .map(|variant_index| (variant_index, &self.variants[variant_index].1))
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckedProgram {
+    pub extern_functions: Vec<CheckedExternFunction>,
+    pub functions: Vec<CheckedFunction>,
+    pub structs: Vec<Struct>,
+    pub enums: Vec<Enum>,
+    /// The functions marked `#[test]`, in the order they are declared
+    pub tests: Vec<String>,
+    /// The type of every expression by its span, for tools like the language server
+    pub expression_types: Vec<(Span, Type)>,
+}
+
+#[derive(Debug, Clone)]
+struct Function {
+    name_span: Span,
+    parameters: Vec<CheckedParameter>,
+    is_variadic: bool,
+    return_type: Type,
+    /// Whether this is an extern function declared with `#[unsafe]`, which can only be called in
+    /// `unsafe` blocks
+    is_unsafe: bool,
+}
+
+#[derive(Debug)]
+struct KnownVariable {
+    ttype: Type,
+    is_mut: bool,
+    constant: Option<ConstValue>,
+    /// The field of an immutable union variable its initializer wrote, which stays the field
+    /// written last
+    written_union_field: Option<String>,
+    /// Whether this is a nullable pointer variable that a condition proved not to be null
+    is_narrowed: bool,
+}
+
+/// A value computed at compile time for a `const` declaration.
+#[derive(Debug, Clone)]
+enum ConstValue {
+    String(String),
+    Char(char),
+    Int(i32),
+    Bool(bool),
+}
+
+impl ConstValue {
+    fn to_literal(&self, ttype: Type) -> CheckedLiteral {
+        match self {
+            Self::String(value) => CheckedLiteral::String(value.clone(), ttype),
+            Self::Char(value) => CheckedLiteral::Char(*value, ttype),
+            Self::Int(value) => CheckedLiteral::Int(*value, ttype),
+            Self::Bool(value) => CheckedLiteral::Bool(*value, ttype),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct ScopeStack {
+    stack: Vec<(Option<String>, HashMap<String, KnownVariable>)>,
+}
+
+impl ScopeStack {
+    fn push_scope(&mut self, function_name: Option<String>) {
+        self.stack.push((function_name, HashMap::default()));
+    }
+
+    fn pop_scope(&mut self) {
+        self.stack.pop();
+    }
+
+    /// Removes the scopes of the function being checked, leaving the module level scope.
+    fn take_function_scopes(&mut self) -> Vec<(Option<String>, HashMap<String, KnownVariable>)> {
+        self.stack.split_off(self.stack.len().min(1))
+    }
+
+    fn restore_function_scopes(
+        &mut self,
+        scopes: Vec<(Option<String>, HashMap<String, KnownVariable>)>,
+    ) {
+        self.stack.extend(scopes);
+    }
+
+    fn add_variable(&mut self, variable_name: &str, ttype: Type, is_mut: bool) -> bool {
+        let entry = self
+            .stack
+            .last_mut()
+            .unwrap()
+            .1
+            .entry(variable_name.to_string());
+
+        match entry {
+            Entry::Occupied(_) => true,
+            _ => {
+                entry.or_insert(KnownVariable {
+                    ttype,
+                    is_mut,
+                    constant: None,
+                    written_union_field: None,
+                    is_narrowed: false,
+                });
+                false
+            }
+        }
+    }
+
+    /// Shadows a nullable pointer variable with its non-null type, which only takes non-null
+    /// pointers as long as it is known not to be null.
+    fn add_narrowed_variable(&mut self, variable_name: &str, ttype: Type, is_mut: bool) {
+        self.stack.last_mut().unwrap().1.insert(
+            variable_name.to_string(),
+            KnownVariable {
+                ttype,
+                is_mut,
+                constant: None,
+                written_union_field: None,
+                is_narrowed: true,
+            },
+        );
+    }
+
+    fn add_constant(
+        &mut self,
+        constant_name: &str,
+        ttype: Type,
+        value: Option<ConstValue>,
+    ) -> bool {
+        let entry = self
+            .stack
+            .last_mut()
+            .unwrap()
+            .1
+            .entry(constant_name.to_string());
+
+        match entry {
+            Entry::Occupied(_) => true,
+            _ => {
+                entry.or_insert(KnownVariable {
+                    ttype,
+                    is_mut: false,
+                    constant: value,
+                    written_union_field: None,
+                    is_narrowed: false,
+                });
+                false
+            }
+        }
+    }
+
+    fn set_written_union_field(&mut self, variable_name: &str, field_name: String) {
+        if let Some(var) = self.stack.last_mut().unwrap().1.get_mut(variable_name) {
+            var.written_union_field = Some(field_name);
+        }
+    }
+
+    fn get_variable_type(&self, variable_name: &str) -> Option<&KnownVariable> {
+        for scope in self.stack.iter().rev() {
+            if let Some(var) = scope.1.get(variable_name) {
+                return Some(var);
+            }
+        }
+        None
+    }
+
+    fn get_current_function(&self) -> Option<&str> {
+        for scope in self.stack.iter().rev() {
+            if let Some(ref function_name) = scope.0 {
+                return Some(function_name);
+            }
+        }
+        None
+    }
+}
+
+/// The type parameters and fields of a generic struct.
+type GenericStruct<'a> = (&'a [ParsedGenericParam], &'a [(String, ParsedType)], bool);
+
+#[derive(Debug)]
+struct Context<'a> {
+    known_structs: HashMap<String, Struct>,
+    known_enums: HashMap<String, Enum>,
+    known_functions: HashMap<String, Function>,
+    /// Functions with type parameters, their bodies are checked once for their type parameters and
+    /// then instantiated for every set of types they are called with
+    generic_functions: HashMap<String, &'a ParsedFunction>,
+    /// The types the type parameters of the generic function being checked stand for
+    type_params: HashMap<String, Type>,
+    /// Instances of generic functions checked so far
+    instances: Vec<CheckedFunction>,
+    /// The generic functions whose instances are being checked, the innermost last
+    instantiating: Vec<String>,
+    /// The generic functions whose bodies have been checked for their type parameters, and
+    /// whether they were free of errors. Only those that were are instantiated.
+    generic_declarations: HashMap<String, bool>,
+    /// Instances of generic functions for the type parameters of the generic function being
+    /// checked, which only have a signature and are forgotten once its body is checked
+    type_param_instances: Vec<String>,
+    /// Structs with type parameters, instantiated for every set of types they are used with
+    generic_structs: HashMap<String, GenericStruct<'a>>,
+    /// The generic struct and the type arguments every instance of a generic struct is made of
+    struct_instances: HashMap<String, (String, Vec<Type>)>,
+    /// Methods declared in `impl` blocks, by type name and then method name
+    known_methods: HashMap<String, HashMap<String, Function>>,
+    known_traits: HashMap<String, &'a ParsedTrait>,
+    /// The type and trait of every `impl Trait for Type` block
+    trait_impls: HashSet<(String, String)>,
+    /// Builtin functions called without being declared, see [`builtin_function`]
+    used_builtins: Vec<CheckedExternFunction>,
+    /// Aliases that are part of a cycle are `None`
+    type_aliases: HashMap<String, Option<ParsedType>>,
+    scope_stack: ScopeStack,
+    current_function: Option<&'a ParsedFunction>,
+    /// The calls deferred in each block being checked, with the innermost block last
+    deferred_calls: Vec<Vec<CheckedExpression>>,
+    /// The label of each loop around the statement being checked and the number of blocks around
+    /// it, `break` and `continue` only leave the blocks inside of the loop. The innermost loop is
+    /// last.
+    loops: Vec<(Option<String>, usize)>,
+    /// Whether the expression being checked is inside an `unsafe` block of the function it's in
+    in_unsafe: bool,
+    expression_types: Vec<(Span, Type)>,
+}
+
+impl<'a> Context<'a> {
+    /// Declare the builtin function `name` if it is called without being declared.
+    fn use_builtin(&mut self, name: &str, span: Span) {
+        if self.known_functions.contains_key(name) {
+            return;
+        }
+        if let Some(builtin) = builtin_function(name) {
+            self.known_functions.insert(
+                name.to_string(),
+                Function {
+                    name_span: span,
+                    parameters: builtin.parameters.clone(),
+                    is_variadic: false,
+                    return_type: builtin.return_type.clone(),
+                    is_unsafe: false,
+                },
+            );
+            self.used_builtins.push(builtin);
+        }
+    }
+
+    /// The names of the parameters of the function called `name`, for passing arguments by name.
+    fn parameter_names(&self, name: &str) -> Option<Vec<String>> {
+        if let Some(generic) = self.generic_functions.get(name) {
+            return Some(
+                generic
+                    .parameters
+                    .iter()
+                    .map(|param| param.name.clone())
+                    .collect(),
+            );
+        }
+        let parameters = match self.known_functions.get(name) {
+            Some(func) => func.parameters.clone(),
+            None => builtin_function(name)?.parameters,
+        };
+        Some(parameters.into_iter().map(|param| param.name).collect())
+    }
+
+    /// Statements running the calls deferred in the blocks from `depth` inwards, innermost and
+    /// latest first.
+    fn deferred_calls_from(&self, depth: usize) -> Vec<CheckedStatement> {
+        self.deferred_calls[depth..]
+            .iter()
+            .rev()
+            .flat_map(|calls| calls.iter().rev().cloned())
+            .map(CheckedStatement::Expression)
+            .collect()
+    }
+
+    /// Whether `ttype` has an `impl` block for the trait, or is a type parameter bound by it.
+    fn implements(&self, ttype: &Type, trait_name: &str) -> bool {
+        match ttype {
+            Type::UserDefined(name) => self
+                .trait_impls
+                .contains(&(name.clone(), trait_name.to_string())),
+            Type::TypeParam(name) => self.bounds(name).any(|bound| bound == trait_name),
+            _ => false,
+        }
+    }
+
+    /// The traits bounding a type parameter of the function being checked.
+    fn bounds<'t>(&self, type_param: &'t str) -> impl Iterator<Item = &'a String> + 't
+    where
+        'a: 't,
+    {
+        self.current_function
+            .into_iter()
+            .flat_map(|func| &func.generic_params)
+            .filter(move |generic_param| generic_param.name == type_param)
+            .flat_map(|generic_param| &generic_param.bounds)
+            .map(|(bound, _)| bound)
+    }
+
+    /// The method of that name declared by the bounds of a type parameter, with `Self` standing
+    /// for the type parameter.
+    fn bound_method(&mut self, type_param: &str, method_name: &str) -> Option<Function> {
+        let trait_method = self
+            .bounds(type_param)
+            .filter_map(|bound| self.known_traits.get(bound).copied())
+            .flat_map(|r#trait| &r#trait.methods)
+            .find(|method| method.name == method_name)?;
+
+        let outer_self = self
+            .type_params
+            .insert("Self".to_string(), Type::TypeParam(type_param.to_string()));
+        let method = Function {
+            name_span: trait_method.name_span,
+            parameters: self.resolve_parameters(&trait_method.parameters),
+            is_variadic: false,
+            return_type: self.resolve_type(&trait_method.return_type),
+            is_unsafe: false,
+        };
+        match outer_self {
+            Some(outer_self) => self.type_params.insert("Self".to_string(), outer_self),
+            None => self.type_params.remove("Self"),
+        };
+        Some(method)
+    }
+
+    /// The name of an opaque struct without a declared size that a value of `ttype` would hold.
+    fn unsized_opaque_struct(&self, ttype: &Type) -> Option<String> {
+        match ttype {
+            Type::UserDefined(name) => self
+                .known_structs
+                .get(name)
+                .filter(|r#struct| r#struct.is_opaque && r#struct.opaque_size.is_none())
+                .map(|_| name.clone()),
+            Type::Array(element_type, _) => self.unsized_opaque_struct(element_type),
+            Type::Tuple(element_types) => element_types
+                .iter()
+                .find_map(|element_type| self.unsized_opaque_struct(element_type)),
+            _ => None,
+        }
+    }
+
+    fn type_is_defined(&self, ttype: &Type) -> bool {
+        match ttype {
+            Type::Pointer(ref subtype, _)
+            | Type::NullablePointer(ref subtype, _)
+            | Type::Array(ref subtype, _)
+            | Type::Slice(ref subtype) => self.type_is_defined(subtype),
+            Type::Tuple(ref element_types) => element_types
+                .iter()
+                .all(|element_type| self.type_is_defined(element_type)),
+            Type::FnPtr {
+                ref params,
+                ref return_type,
+            } => {
+                params.iter().all(|param| self.type_is_defined(param))
+                    && self.type_is_defined(return_type)
+            }
+            Type::UserDefined(ref name) => {
+                (self.known_structs.contains_key(name) || self.known_enums.contains_key(name))
+                    && self
+                        .struct_instances
+                        .get(name)
+                        .is_none_or(|(_, type_args)| {
+                            type_args
+                                .iter()
+                                .all(|type_arg| self.type_is_defined(type_arg))
+                        })
+            }
+            _ => true,
+        }
+    }
+
+    /// The error for a type as written in the source that isn't defined: a wrong number of type
+    /// arguments for a struct it names, or else the type being unknown.
+    fn unknown_type_error(&self, ttype: &ParsedType, span: Span) -> TypeCheckError {
+        match self.type_arg_count_mismatch(ttype) {
+            Some((name, expected, found)) => TypeCheckError::TypeParameterCountMismatch {
+                name: name.to_string(),
+                expected,
+                found,
+                span,
+            },
+            None => TypeCheckError::UnknownType(ttype.to_str(), span),
+        }
+    }
+
+    /// The first struct named with a number of type arguments it doesn't take, with the number
+    /// it takes and the number it was given.
+    fn type_arg_count_mismatch<'t>(
+        &self,
+        ttype: &'t ParsedType,
+    ) -> Option<(&'t str, usize, usize)> {
+        match ttype {
+            ParsedType::Name(_) | ParsedType::Unit => None,
+            ParsedType::Generic(ref name, ref type_args) => {
+                let expected = match self.generic_structs.get(name) {
+                    Some((generic_params, ..)) => generic_params.len(),
+                    None if self.known_structs.contains_key(name)
+                        || self.known_enums.contains_key(name) =>
+                    {
+                        0
+                    }
+                    None => return None,
+                };
+                if expected != type_args.len() {
+                    return Some((name, expected, type_args.len()));
+                }
+                type_args
+                    .iter()
+                    .find_map(|type_arg| self.type_arg_count_mismatch(type_arg))
+            }
+            ParsedType::Pointer(ref subtype, _)
+            | ParsedType::NullablePointer(ref subtype, _)
+            | ParsedType::Array(ref subtype, _)
+            | ParsedType::Slice(ref subtype) => self.type_arg_count_mismatch(subtype),
+            ParsedType::Tuple(ref element_types) => element_types
+                .iter()
+                .find_map(|element_type| self.type_arg_count_mismatch(element_type)),
+            ParsedType::FnPtr {
+                ref params,
+                ref return_type,
+            } => params
+                .iter()
+                .chain(std::iter::once(&**return_type))
+                .find_map(|ttype| self.type_arg_count_mismatch(ttype)),
+        }
+    }
+
+    /// Turn a type as written in the source into a type, replacing every type alias with the type
+    /// it stands for and instantiating the generic structs it names.
+    fn resolve_type(&mut self, ttype: &ParsedType) -> Type {
+        match ttype {
+            ParsedType::Name(ref name) if self.type_params.contains_key(name) => {
+                self.type_params[name].clone()
+            }
+            ParsedType::Name(ref name) => match self.type_aliases.get(name) {
+                Some(Some(aliased)) => self.resolve_type(&aliased.clone()),
+                Some(None) => Type::Incomplete,
+                None => Type::from_string(name),
+            },
+            ParsedType::Generic(ref name, ref type_args) => {
+                let type_args = type_args
+                    .iter()
+                    .map(|type_arg| self.resolve_type(type_arg))
+                    .collect();
+                self.instantiate_generic_struct(name, type_args)
+            }
+            ParsedType::Pointer(ref subtype, is_mut) => {
+                Type::Pointer(Box::new(self.resolve_type(subtype)), *is_mut)
+            }
+            ParsedType::NullablePointer(ref subtype, is_mut) => {
+                Type::NullablePointer(Box::new(self.resolve_type(subtype)), *is_mut)
+            }
+            ParsedType::Array(ref subtype, len) => {
+                Type::Array(Box::new(self.resolve_type(subtype)), *len)
+            }
+            ParsedType::Slice(ref subtype) => Type::Slice(Box::new(self.resolve_type(subtype))),
+            ParsedType::FnPtr {
+                ref params,
+                ref return_type,
+            } => Type::FnPtr {
+                params: params
+                    .iter()
+                    .map(|param| self.resolve_type(param))
+                    .collect(),
+                return_type: Box::new(self.resolve_type(return_type)),
+            },
+            ParsedType::Tuple(ref element_types) => Type::Tuple(
+                element_types
+                    .iter()
+                    .map(|element_type| self.resolve_type(element_type))
+                    .collect(),
+            ),
+            ParsedType::Unit => Type::Unit,
+        }
+    }
+
+    /// The instance of the generic struct for the type arguments, which is created the first time
+    /// it is used. Unknown structs and wrong numbers of type arguments give a type that isn't
+    /// defined.
+    fn instantiate_generic_struct(&mut self, name: &str, type_args: Vec<Type>) -> Type {
+        let instance_name = instance_name(name, &type_args);
+        if self.known_structs.contains_key(&instance_name) {
+            return Type::UserDefined(instance_name);
+        }
+        let Some(&(generic_params, fields, has_eq)) = self.generic_structs.get(name) else {
+            return Type::UserDefined(instance_name);
+        };
+        if generic_params.len() != type_args.len() {
+            return Type::UserDefined(instance_name);
+        }
+
+        // Known before the fields are resolved, so fields can point to the struct itself
+        self.known_structs.insert(
+            instance_name.clone(),
+            Struct {
+                name: instance_name.clone(),
+                fields: vec![],
+                is_opaque: false,
+                opaque_size: None,
+                has_eq,
+                is_union: false,
+            },
+        );
+        self.struct_instances
+            .insert(instance_name.clone(), (name.to_string(), type_args.clone()));
+
+        let type_params = generic_params
+            .iter()
+            .map(|generic_param| generic_param.name.clone())
+            .zip(type_args)
+            .collect();
+        let outer_type_params = std::mem::replace(&mut self.type_params, type_params);
+        let fields = fields
+            .iter()
+            .map(|(field_name, field_type)| (field_name.clone(), self.resolve_type(field_type)))
+            .collect();
+        self.type_params = outer_type_params;
+
+        if let Some(r#struct) = self.known_structs.get_mut(&instance_name) {
+            r#struct.fields = fields;
+        }
+        Type::UserDefined(instance_name)
+    }
+
+    fn resolve_parameters(&mut self, parameters: &[FunctionParameter]) -> Vec<CheckedParameter> {
+        parameters
+            .iter()
+            .map(|param| CheckedParameter {
+                name: param.name.clone(),
+                ttype: self.resolve_parameter_type(param),
+                is_by_ref: param.is_by_ref,
+            })
+            .collect()
+    }
+
+    /// The type of the parameter inside the function, a pointer for `ref` parameters.
+    fn resolve_parameter_type(&mut self, param: &FunctionParameter) -> Type {
+        let resolved = self.resolve_type(&param.ttype);
+        if param.is_by_ref {
+            Type::Pointer(Box::new(resolved), false)
+        } else {
+            resolved
+        }
+    }
+
+    /// Whether expanding `ttype` leads back to the first alias in `expanding`.
+    fn alias_is_cyclic(&self, ttype: &ParsedType, expanding: &mut Vec<String>) -> bool {
+        match ttype {
+            ParsedType::Pointer(ref subtype, _)
+            | ParsedType::NullablePointer(ref subtype, _)
+            | ParsedType::Array(ref subtype, _)
+            | ParsedType::Slice(ref subtype) => self.alias_is_cyclic(subtype, expanding),
+            ParsedType::Tuple(ref element_types) | ParsedType::Generic(_, ref element_types) => {
+                element_types
+                    .iter()
+                    .any(|element_type| self.alias_is_cyclic(element_type, expanding))
+            }
+            ParsedType::FnPtr {
+                ref params,
+                ref return_type,
+            } => {
+                params
+                    .iter()
+                    .any(|param| self.alias_is_cyclic(param, expanding))
+                    || self.alias_is_cyclic(return_type, expanding)
+            }
+            ParsedType::Name(ref name) => match self.type_aliases.get(name) {
+                // Cycles not involving the first alias are reported for their own aliases
+                Some(_) if expanding.contains(name) => expanding[0] == *name,
+                Some(Some(aliased)) => {
+                    expanding.push(name.clone());
+                    let is_cyclic = self.alias_is_cyclic(aliased, expanding);
+                    expanding.pop();
+                    is_cyclic
+                }
+                Some(None) | None => false,
+            },
+            ParsedType::Unit => false,
+        }
+    }
+}
+
+pub fn typecheck_program(program: &ParsedProgram) -> (CheckedProgram, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+
+    let mut context = Context {
+        known_structs: HashMap::new(),
+        known_enums: HashMap::new(),
+        known_functions: HashMap::new(),
+        generic_functions: HashMap::new(),
+        type_params: HashMap::new(),
+        instances: vec![],
+        instantiating: vec![],
+        generic_declarations: HashMap::new(),
+        type_param_instances: vec![],
+        generic_structs: HashMap::new(),
+        struct_instances: HashMap::new(),
+        known_methods: HashMap::new(),
+        known_traits: HashMap::new(),
+        trait_impls: HashSet::new(),
+        used_builtins: vec![],
+        type_aliases: HashMap::new(),
+        scope_stack: ScopeStack::default(),
+        current_function: None,
+        deferred_calls: vec![],
+        loops: vec![],
+        in_unsafe: false,
+        expression_types: vec![],
+    };
+
+    for type_alias in &program.type_aliases {
+        if context.type_aliases.contains_key(&type_alias.name) {
+            errors.push(TypeCheckError::DuplicateFuncStructName(
+                type_alias.name.clone(),
+                type_alias.name_span,
+            ));
+            continue;
+        }
+
+        context
+            .type_aliases
+            .insert(type_alias.name.clone(), Some(type_alias.aliased.clone()));
+    }
+    for type_alias in &program.type_aliases {
+        if context.alias_is_cyclic(&type_alias.aliased, &mut vec![type_alias.name.clone()]) {
+            errors.push(TypeCheckError::CyclicTypeAlias(type_alias.span));
+            // Break the cycle so that resolving the other aliases in it terminates
+            context.type_aliases.insert(type_alias.name.clone(), None);
+        }
+    }
+
+    for r#trait in &program.traits {
+        if context.known_traits.contains_key(&r#trait.name) {
+            errors.push(TypeCheckError::DuplicateTrait(
+                r#trait.name.clone(),
+                r#trait.name_span,
+            ));
+            continue;
+        }
+
+        for method in &r#trait.methods {
+            if method.parameters.is_empty() {
+                errors.push(TypeCheckError::MethodWithoutReceiver(
+                    method.name.clone(),
+                    method.name_span,
+                ));
+            }
+        }
+        context.known_traits.insert(r#trait.name.clone(), r#trait);
+    }
+
+    // Generic structs are instantiated while resolving types, which starts with the signatures of
+    // functions
+    for r#struct in &program.structs {
+        if let ParsedStruct::Transparent(name, _, generic_params, fields, attributes, _) = r#struct
+        {
+            if !generic_params.is_empty() {
+                let has_eq = attributes.iter().any(|attribute| attribute.name == "eq");
+                context
+                    .generic_structs
+                    .insert(name.clone(), (generic_params, fields, has_eq));
+            }
+        }
+    }
+
+    for func in &program.extern_functions {
+        let name = func.name.clone();
+
+        if let Some(first) = context.known_functions.get(&name) {
+            errors.push(TypeCheckError::DuplicateFunctionDefinition {
+                name,
+                first_span: first.name_span,
+                second_span: func.name_span,
+            });
+            continue;
+        }
+
+        let function = Function {
+            name_span: func.name_span,
+            parameters: context.resolve_parameters(&func.parameters),
+            is_variadic: func.is_variadic,
+            return_type: context.resolve_type(&func.return_type),
+            is_unsafe: func
+                .attributes
+                .iter()
+                .any(|attribute| attribute.name == "unsafe"),
+        };
+        context.known_functions.insert(name, function);
+    }
+    // Unknown attributes are only warned about, so programs using attributes of newer versions of
+    // the language still compile
+    for attribute in program
+        .functions
+        .iter()
+        .flat_map(|func| &func.attributes)
+        .chain(program.structs.iter().flat_map(ParsedStruct::attributes))
+        .chain(
+            program
+                .extern_functions
+                .iter()
+                .flat_map(|func| &func.attributes),
+        )
+    {
+        if !KNOWN_ATTRIBUTES.contains(&attribute.name.as_str()) {
+            errors.push(TypeCheckError::UnknownAttribute(
+                attribute.name.clone(),
+                attribute.span,
+            ));
+        }
+    }
+
+    let mut tests = vec![];
+    for func in &program.functions {
+        if !func
+            .attributes
+            .iter()
+            .any(|attribute| attribute.name == "test")
+        {
+            continue;
+        }
+        if !func.parameters.is_empty()
+            || !func.generic_params.is_empty()
+            || context.resolve_type(&func.return_type) != Type::Unit
+        {
+            errors.push(TypeCheckError::InvalidTestFunction(
+                func.name.clone(),
+                func.name_span,
+            ));
+        }
+        tests.push(func.name.clone());
+    }
+
+    for func in &program.functions {
+        let name = func.name.clone();
+
+        let first_span = context
+            .known_functions
+            .get(&name)
+            .map(|first| first.name_span)
+            .or_else(|| {
+                context
+                    .generic_functions
+                    .get(&name)
+                    .map(|first| first.name_span)
+            });
+        if let Some(first_span) = first_span {
+            errors.push(TypeCheckError::DuplicateFunctionDefinition {
+                name,
+                first_span,
+                second_span: func.name_span,
+            });
+            continue;
+        }
+
+        if !func.generic_params.is_empty() {
+            for (bound, bound_span) in func
+                .generic_params
+                .iter()
+                .flat_map(|generic_param| &generic_param.bounds)
+            {
+                if !context.known_traits.contains_key(bound) {
+                    errors.push(TypeCheckError::UnknownTrait(bound.clone(), *bound_span));
+                }
+            }
+            context.generic_functions.insert(name, func);
+            continue;
+        }
+
+        let function = Function {
+            name_span: func.name_span,
+            parameters: context.resolve_parameters(&func.parameters),
+            is_variadic: false,
+            return_type: context.resolve_type(&func.return_type),
+            is_unsafe: false,
+        };
+        context.known_functions.insert(name, function);
+    }
+    for r#struct in &program.structs {
+        match r#struct {
+            ParsedStruct::Opaque(name, name_span, size, _, _) => {
+                if context.known_functions.contains_key(name)
+                    || context.generic_functions.contains_key(name)
+                    || context.type_aliases.contains_key(name)
+                {
+                    errors.push(TypeCheckError::DuplicateFuncStructName(
+                        name.clone(),
+                        *name_span,
+                    ));
+                    continue;
+                }
+
+                context.known_structs.insert(
+                    name.clone(),
+                    Struct {
+                        name: name.clone(),
+                        fields: vec![],
+                        is_opaque: true,
+                        opaque_size: *size,
+                        has_eq: false,
+                        is_union: false,
+                    },
+                );
+            }
+            ParsedStruct::Transparent(name, name_span, generic_params, fields, attributes, _) => {
+                if context.known_functions.contains_key(name)
+                    || context.generic_functions.contains_key(name)
+                    || context.type_aliases.contains_key(name)
+                {
+                    errors.push(TypeCheckError::DuplicateFuncStructName(
+                        name.clone(),
+                        *name_span,
+                    ));
+                    continue;
+                }
+
+                if !generic_params.is_empty() {
+                    continue;
+                }
+
+                let fields = fields
+                    .iter()
+                    .map(|(name, ttype)| (name.clone(), context.resolve_type(ttype)))
+                    .collect();
+                context.known_structs.insert(
+                    name.clone(),
+                    Struct {
+                        name: name.clone(),
+                        fields,
+                        is_opaque: false,
+                        opaque_size: None,
+                        has_eq: attributes.iter().any(|attribute| attribute.name == "eq"),
+                        is_union: false,
+                    },
+                );
+            }
+        };
+    }
+
+    for r#enum in &program.enums {
+        if context.known_functions.contains_key(&r#enum.name)
+            || context.generic_functions.contains_key(&r#enum.name)
+            || context.type_aliases.contains_key(&r#enum.name)
+            || context.known_structs.contains_key(&r#enum.name)
+            || context.generic_structs.contains_key(&r#enum.name)
+            || context.known_enums.contains_key(&r#enum.name)
+        {
+            errors.push(TypeCheckError::DuplicateFuncStructName(
+                r#enum.name.clone(),
+                r#enum.name_span,
+            ));
+            continue;
+        }
+
+        let mut variants: Vec<(String, Option<Type>)> = vec![];
+        for (variant_name, variant_span, value_type) in &r#enum.variants {
+            if variants.iter().any(|(name, _)| name == variant_name) {
+                errors.push(TypeCheckError::DuplicateEnumVariant(
+                    variant_name.clone(),
+                    *variant_span,
+                ));
+                continue;
+            }
+
+            variants.push((
+                variant_name.clone(),
+                value_type.as_ref().map(|ttype| context.resolve_type(ttype)),
+            ));
+        }
+
+        context.known_enums.insert(
+            r#enum.name.clone(),
+            Enum {
+                name: r#enum.name.clone(),
+                variants,
+            },
+        );
+    }
+    // Value types are checked once all enums are known, so that enums may hold one another
+    for r#enum in &program.enums {
+        for (_, variant_span, value_type) in &r#enum.variants {
+            if let Some(value_type) = value_type {
+                let resolved_type = context.resolve_type(value_type);
+                if !context.type_is_defined(&resolved_type) {
+                    errors.push(context.unknown_type_error(value_type, *variant_span));
+                } else if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+                    errors.push(TypeCheckError::UnsizedOpaqueStruct(
+                        struct_name,
+                        *variant_span,
+                    ));
+                }
+            }
+        }
+    }
+    for r#struct in &program.structs {
+        if let ParsedStruct::Transparent(_, name_span, generic_params, fields, _, _) = r#struct {
+            if !generic_params.is_empty() {
+                continue;
+            }
+            for (_, field_type) in fields {
+                let resolved_type = context.resolve_type(field_type);
+                if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+                    errors.push(TypeCheckError::UnsizedOpaqueStruct(struct_name, *name_span));
+                }
+            }
+        }
+    }
+    for union in &program.unions {
+        if context.known_functions.contains_key(&union.name)
+            || context.generic_functions.contains_key(&union.name)
+            || context.type_aliases.contains_key(&union.name)
+            || context.known_structs.contains_key(&union.name)
+            || context.generic_structs.contains_key(&union.name)
+            || context.known_enums.contains_key(&union.name)
+        {
+            errors.push(TypeCheckError::DuplicateFuncStructName(
+                union.name.clone(),
+                union.name_span,
+            ));
+            continue;
+        }
+
+        let fields = union
+            .fields
+            .iter()
+            .map(|(name, ttype)| (name.clone(), context.resolve_type(ttype)))
+            .collect();
+        context.known_structs.insert(
+            union.name.clone(),
+            Struct {
+                name: union.name.clone(),
+                fields,
+                is_opaque: false,
+                opaque_size: None,
+                has_eq: false,
+                is_union: true,
+            },
+        );
+    }
+    for union in &program.unions {
+        for (_, field_type) in &union.fields {
+            let resolved_type = context.resolve_type(field_type);
+            if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+                errors.push(TypeCheckError::UnsizedOpaqueStruct(
+                    struct_name,
+                    union.name_span,
+                ));
+            }
+        }
+    }
+
+    for r#impl in &program.impls {
+        if !context.known_structs.contains_key(&r#impl.struct_name)
+            && !context.known_enums.contains_key(&r#impl.struct_name)
+        {
+            errors.push(TypeCheckError::UnknownType(
+                r#impl.struct_name.clone(),
+                r#impl.struct_name_span,
+            ));
+            continue;
+        }
+
+        // `Self` stands for the type the methods are declared for
+        context.type_params = HashMap::from([(
+            "Self".to_string(),
+            Type::UserDefined(r#impl.struct_name.clone()),
+        )]);
+
+        if let Some((trait_name, trait_name_span)) = &r#impl.trait_name {
+            let mut errs = check_trait_impl(&mut context, r#impl, trait_name, *trait_name_span);
+            errors.append(&mut errs);
+        }
+
+        for method in &r#impl.methods {
+            // The first parameter receives the object the method is called on
+            if method.parameters.is_empty() {
+                errors.push(TypeCheckError::MethodWithoutReceiver(
+                    method.name.clone(),
+                    method.name_span,
+                ));
+                continue;
+            }
+
+            let function = Function {
+                name_span: method.name_span,
+                parameters: context.resolve_parameters(&method.parameters),
+                is_variadic: false,
+                return_type: context.resolve_type(&method.return_type),
+                is_unsafe: false,
+            };
+
+            let methods = context
+                .known_methods
+                .entry(r#impl.struct_name.clone())
+                .or_default();
+            if methods.contains_key(&method.name) {
+                errors.push(TypeCheckError::DuplicateMethod(
+                    r#impl.struct_name.clone(),
+                    method.name.clone(),
+                    method.name_span,
+                ));
+                continue;
+            }
+            methods.insert(method.name.clone(), function);
+        }
+        context.type_params.clear();
+    }
+
+    // Module level constants live in a scope enclosing every function
+    context.scope_stack.push_scope(None);
+    for const_decl in const_evaluation_order(&program.consts) {
+        let mut errs = typecheck_const_decl(&mut context, const_decl);
+        errors.append(&mut errs);
+    }
+
+    let mut extern_functions: Vec<CheckedExternFunction> = program
+        .extern_functions
+        .iter()
+        .map(|func| {
+            let mut seen_param_names: Vec<&str> = vec![];
+
+            for param in &func.parameters {
+                let resolved_type = context.resolve_type(&param.ttype);
+                if !context.type_is_defined(&resolved_type) {
+                    errors.push(context.unknown_type_error(&param.ttype, param.type_span));
+                } else if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+                    errors.push(TypeCheckError::UnsizedOpaqueStruct(
+                        struct_name,
+                        param.type_span,
+                    ));
+                }
+
+                if seen_param_names.contains(&param.name.as_str()) {
+                    errors.push(TypeCheckError::DuplicateParameterName(
+                        param.name.clone(),
+                        param.name_span,
+                    ));
+                }
+
+                seen_param_names.push(&param.name);
+            }
+
+            let resolved_type = context.resolve_type(&func.return_type);
+            if !context.type_is_defined(&resolved_type) {
+                errors.push(context.unknown_type_error(&func.return_type, func.return_type_span));
+            } else if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+                errors.push(TypeCheckError::UnsizedOpaqueStruct(
+                    struct_name,
+                    func.return_type_span,
+                ));
+            }
+
+            CheckedExternFunction {
+                calling_convention: func
+                    .calling_convention
+                    .as_ref()
+                    .map(|(name, _)| name.clone())
+                    .filter(|name| KNOWN_CALLING_CONVENTIONS.contains(&name.as_str())),
+                name: func.name.clone(),
+                parameters: context.resolve_parameters(&func.parameters),
+                is_variadic: func.is_variadic,
+                return_type: context.resolve_type(&func.return_type),
+            }
+        })
+        .collect();
+
+    // The bodies of generic functions are checked once by themselves, before any of their instances
+    for func in &program.functions {
+        if func.generic_params.is_empty() {
+            continue;
+        }
+        let mut errs = check_generic_declaration(&mut context, func);
+        errors.append(&mut errs);
+
+        for name in context.type_param_instances.drain(..) {
+            context.known_functions.remove(&name);
+        }
+        let known_structs = &mut context.known_structs;
+        context.struct_instances.retain(|name, (_, type_args)| {
+            let is_type_param_instance = type_args.iter().any(Type::has_type_param);
+            if is_type_param_instance {
+                known_structs.remove(name);
+            }
+            !is_type_param_instance
+        });
+    }
+
+    let mut functions: Vec<CheckedFunction> = program
+        .functions
+        .iter()
+        .filter(|func| func.generic_params.is_empty())
+        .map(|func| {
+            let (checked_function, mut errs) =
+                typecheck_function(&mut context, func, func.name.clone());
+            errors.append(&mut errs);
+            checked_function
+        })
+        .collect();
+
+    for r#impl in &program.impls {
+        context.type_params = HashMap::from([(
+            "Self".to_string(),
+            Type::UserDefined(r#impl.struct_name.clone()),
+        )]);
+        for method in &r#impl.methods {
+            let (checked_method, mut errs) = typecheck_function(
+                &mut context,
+                method,
+                method_symbol_name(&r#impl.struct_name, &method.name),
+            );
+            errors.append(&mut errs);
+            functions.push(checked_method);
+        }
+    }
+    context.type_params.clear();
+    functions.append(&mut context.instances);
+    extern_functions.append(&mut context.used_builtins);
+
+    context.scope_stack.pop_scope();
+
+    (
+        CheckedProgram {
+            functions,
+            extern_functions,
+            structs: context.known_structs.into_values().collect(),
+            enums: context.known_enums.into_values().collect(),
+            tests,
+            expression_types: context.expression_types,
+        },
+        errors,
+    )
+}
+
+/// Typecheck the body of a function or method, `name` is the name of the emitted symbol.
+fn typecheck_function<'a>(
+    context: &mut Context<'a>,
+    func: &'a ParsedFunction,
+    name: String,
+) -> (CheckedFunction, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+    let mut seen_param_names: Vec<&str> = vec![];
+
+    for param in &func.parameters {
+        let resolved_type = context.resolve_type(&param.ttype);
+        if !context.type_is_defined(&resolved_type) {
+            errors.push(context.unknown_type_error(&param.ttype, param.type_span));
+        } else if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+            errors.push(TypeCheckError::UnsizedOpaqueStruct(
+                struct_name,
+                param.type_span,
+            ));
+        }
+
+        if seen_param_names.contains(&param.name.as_str()) {
+            errors.push(TypeCheckError::DuplicateParameterName(
+                param.name.clone(),
+                param.name_span,
+            ));
+        }
+
+        seen_param_names.push(&param.name);
+    }
+
+    let resolved_type = context.resolve_type(&func.return_type);
+    if !context.type_is_defined(&resolved_type) {
+        errors.push(context.unknown_type_error(&func.return_type, func.return_type_span));
+    } else if let Some(struct_name) = context.unsized_opaque_struct(&resolved_type) {
+        errors.push(TypeCheckError::UnsizedOpaqueStruct(
+            struct_name,
+            func.return_type_span,
+        ));
+    }
+
+    context.scope_stack.push_scope(Some(name.clone()));
+    context.current_function = Some(func);
+
+    for param in &func.parameters {
+        let param_type = context.resolve_parameter_type(param);
+        context
+            .scope_stack
+            .add_variable(&param.name, param_type, false);
+    }
+
+    let return_type = context.resolve_type(&func.return_type);
+    let (mut body, value, mut errs) =
+        typecheck_block_with_value(context, &func.body, return_type != Type::Unit);
+    errors.append(&mut errs);
+
+    // The value of the body is returned from the function, unless both are `unit`
+    if let Some((value, span)) = value {
+        if return_type == Type::Unit && value.ttype() == Type::Unit {
+            body.statements.push(CheckedStatement::Expression(value));
+        } else {
+            let (value, mut errs) = check_return_value(context, value, span);
+            errors.append(&mut errs);
+            body.statements.push(CheckedStatement::Return(value));
+        }
+    }
+
+    context.current_function.take();
+    context.scope_stack.pop_scope();
+
+    let return_type = context.resolve_type(&func.return_type);
+    if !matches!(return_type, Type::Unit | Type::Incomplete) && !body.diverges() {
+        errors.push(TypeCheckError::MissingReturn {
+            function_name: name.clone(),
+            span: func.return_type_span,
+        });
+    }
+
+    let checked_function = CheckedFunction {
+        name,
+        parameters: context.resolve_parameters(&func.parameters),
+        return_type,
+        body,
+        is_inline: func.is_inline,
+    };
+
+    (checked_function, errors)
+}
+
+/// Checks that an `impl Trait for Type` block declares exactly the methods of the trait, with the
+/// signatures the trait declares for them.
+fn check_trait_impl(
+    context: &mut Context,
+    r#impl: &ParsedImpl,
+    trait_name: &str,
+    trait_name_span: Span,
+) -> Vec<TypeCheckError> {
+    let mut errors = vec![];
+
+    let Some(&r#trait) = context.known_traits.get(trait_name) else {
+        errors.push(TypeCheckError::UnknownTrait(
+            trait_name.to_string(),
+            trait_name_span,
+        ));
+        return errors;
+    };
+
+    for trait_method in &r#trait.methods {
+        let Some(method) = r#impl
+            .methods
+            .iter()
+            .find(|method| method.name == trait_method.name)
+        else {
+            errors.push(TypeCheckError::TraitMethodMissing {
+                trait_name: trait_name.to_string(),
+                method_name: trait_method.name.clone(),
+                span: r#impl.struct_name_span,
+            });
+            continue;
+        };
+
+        let expected = Type::FnPtr {
+            params: context
+                .resolve_parameters(&trait_method.parameters)
+                .into_iter()
+                .map(|param| param.ttype)
+                .collect(),
+            return_type: Box::new(context.resolve_type(&trait_method.return_type)),
+        };
+        let found = Type::FnPtr {
+            params: context
+                .resolve_parameters(&method.parameters)
+                .into_iter()
+                .map(|param| param.ttype)
+                .collect(),
+            return_type: Box::new(context.resolve_type(&method.return_type)),
+        };
+        if found != expected {
+            errors.push(TypeCheckError::TraitMethodSignatureMismatch {
+                trait_name: trait_name.to_string(),
+                method_name: method.name.clone(),
+                expected,
+                found,
+                span: method.name_span,
+            });
+        }
+    }
+
+    for method in &r#impl.methods {
+        if !r#trait
+            .methods
+            .iter()
+            .any(|trait_method| trait_method.name == method.name)
+        {
+            errors.push(TypeCheckError::MethodNotInTrait {
+                trait_name: trait_name.to_string(),
+                method_name: method.name.clone(),
+                span: method.name_span,
+            });
+        }
+    }
+
+    context
+        .trait_impls
+        .insert((r#impl.struct_name.clone(), trait_name.to_string()));
+
+    errors
+}
+
+/// Check the body of a generic function once for whatever types it's instantiated with, unless
+/// an earlier call already did. Its type parameters stand for types that only have the methods of
+/// their bounds.
+fn check_generic_declaration<'a>(
+    context: &mut Context<'a>,
+    func: &'a ParsedFunction,
+) -> Vec<TypeCheckError> {
+    if context.generic_declarations.contains_key(&func.name) {
+        return vec![];
+    }
+    // Instances the body asks for while it's being checked are checked as if it were valid
+    context.generic_declarations.insert(func.name.clone(), true);
+
+    let type_params = func
+        .generic_params
+        .iter()
+        .map(|generic_param| {
+            let name = generic_param.name.clone();
+            (name.clone(), Type::TypeParam(name))
+        })
+        .collect();
+    let (_, errors) = typecheck_detached(context, func, func.name.clone(), type_params);
+
+    context
+        .generic_declarations
+        .insert(func.name.clone(), errors.is_empty());
+    errors
+}
+
+/// Typecheck a function with the types given for its type parameters, apart from the function
+/// being checked when it's called, whose variables and loops it doesn't see.
+fn typecheck_detached<'a>(
+    context: &mut Context<'a>,
+    func: &'a ParsedFunction,
+    name: String,
+    type_params: HashMap<String, Type>,
+) -> (CheckedFunction, Vec<TypeCheckError>) {
+    let outer_type_params = std::mem::replace(&mut context.type_params, type_params);
+    let outer_scopes = context.scope_stack.take_function_scopes();
+    let outer_function = context.current_function.take();
+    let outer_deferred_calls = std::mem::take(&mut context.deferred_calls);
+    let outer_loops = std::mem::take(&mut context.loops);
+    let outer_unsafe = std::mem::take(&mut context.in_unsafe);
+
+    let checked = typecheck_function(context, func, name);
+
+    context.scope_stack.restore_function_scopes(outer_scopes);
+    context.current_function = outer_function;
+    context.deferred_calls = outer_deferred_calls;
+    context.loops = outer_loops;
+    context.in_unsafe = outer_unsafe;
+    context.type_params = outer_type_params;
+    checked
+}
+
+/// How many instances of one generic function can be checked inside of each other.
+const MAX_GENERIC_RECURSION: usize = 16;
+
+/// Check the body of a generic function for the types of its type parameters, unless an earlier
+/// call already did. Returns the name of the instance, which is `None` when the types couldn't be
+/// determined or the body has errors.
+fn instantiate_generic_function<'a>(
+    context: &mut Context<'a>,
+    func: &'a ParsedFunction,
+    func_call: &ParsedFunctionCall,
+    checked_args: &[CheckedExpression],
+) -> (Option<String>, Vec<TypeCheckError>) {
+    let values: Vec<_> = func
+        .parameters
+        .iter()
+        .zip(checked_args)
+        .map(|(param, arg)| (&param.ttype, arg.ttype()))
+        .collect();
+    let (type_args, mut errors) = determine_type_args(
+        context,
+        &func.name,
+        &func.generic_params,
+        &func_call.type_args,
+        &values,
+        func_call.name_span,
+    );
+    let Some(type_args) = type_args else {
+        return (None, errors);
+    };
+
+    for (generic_param, type_arg) in func.generic_params.iter().zip(&type_args) {
+        for (bound, _) in &generic_param.bounds {
+            if context.known_traits.contains_key(bound) && !context.implements(type_arg, bound) {
+                errors.push(TypeCheckError::TraitNotImplemented {
+                    ttype: type_arg.clone(),
+                    trait_name: bound.clone(),
+                    span: func_call.name_span,
+                });
+            }
+        }
+    }
+    // Errors in the body are reported for the function itself rather than for its instances
+    errors.append(&mut check_generic_declaration(context, func));
+    if !errors.is_empty() || !context.generic_declarations[&func.name] {
+        return (None, errors);
+    }
+
+    let name = instance_name(&func.name, &type_args);
+    if context.known_functions.contains_key(&name) {
+        return (Some(name), errors);
+    }
+
+    let type_params: HashMap<String, Type> = func
+        .generic_params
+        .iter()
+        .map(|generic_param| generic_param.name.clone())
+        .zip(type_args.iter().cloned())
+        .collect();
+    let outer_type_params = std::mem::replace(&mut context.type_params, type_params.clone());
+    let function = Function {
+        name_span: func.name_span,
+        parameters: context.resolve_parameters(&func.parameters),
+        is_variadic: false,
+        return_type: context.resolve_type(&func.return_type),
+        is_unsafe: false,
+    };
+    context.type_params = outer_type_params;
+
+    // Calls from a generic function with its own type parameters only need a signature
+    if type_args.iter().any(Type::has_type_param) {
+        context.known_functions.insert(name.clone(), function);
+        context.type_param_instances.push(name.clone());
+        return (Some(name), errors);
+    }
+
+    // Polymorphic recursion, as in `grow((x, x))` inside `grow<T>`, would otherwise ask for ever
+    // larger instances
+    let depth = context
+        .instantiating
+        .iter()
+        .filter(|instantiating| **instantiating == func.name)
+        .count();
+    if depth >= MAX_GENERIC_RECURSION {
+        errors.push(TypeCheckError::UnboundedGenericRecursion(
+            func.name.clone(),
+            func_call.name_span,
+        ));
+        return (None, errors);
+    }
+
+    // Known before the body is checked, so recursive calls refer to the instance being checked
+    context.known_functions.insert(name.clone(), function);
+
+    context.instantiating.push(func.name.clone());
+    let (instance, mut errs) = typecheck_detached(context, func, name.clone(), type_params);
+    errors.append(&mut errs);
+    context.instances.push(instance);
+    context.instantiating.pop();
+
+    (Some(name), errors)
+}
+
+/// The types of the type parameters of a generic function or struct, given explicitly in
+/// `type_args` or else inferred from the values passed for its parameters or fields, along with
+/// their types as declared. `None` when the types couldn't be determined.
+fn determine_type_args(
+    context: &mut Context,
+    name: &str,
+    generic_params: &[ParsedGenericParam],
+    type_args: &[ParsedType],
+    values: &[(&ParsedType, Type)],
+    span: Span,
+) -> (Option<Vec<Type>>, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+
+    if !type_args.is_empty() {
+        if type_args.len() != generic_params.len() {
+            errors.push(TypeCheckError::TypeParameterCountMismatch {
+                name: name.to_string(),
+                expected: generic_params.len(),
+                found: type_args.len(),
+                span,
+            });
+            return (None, errors);
+        }
+
+        let mut resolved_type_args = vec![];
+        for type_arg in type_args {
+            let ttype = context.resolve_type(type_arg);
+            if !context.type_is_defined(&ttype) {
+                errors.push(context.unknown_type_error(type_arg, span));
+            }
+            resolved_type_args.push(ttype);
+        }
+        return (errors.is_empty().then_some(resolved_type_args), errors);
+    }
+
+    let mut inferred = HashMap::new();
+    // Integer literals fit any integer type, so they only decide type parameters no other value
+    // does
+    for default_ints in [false, true] {
+        for (declared_type, value_type) in values {
+            infer_type_params(
+                generic_params,
+                declared_type,
+                value_type,
+                default_ints,
+                &context.struct_instances,
+                &mut inferred,
+            );
+        }
+    }
+
+    // Values that failed to check have already been reported
+    let values_incomplete = values
+        .iter()
+        .any(|(_, value_type)| matches!(value_type, Type::Incomplete));
+    let mut inferred_type_args = vec![];
+    for generic_param in generic_params {
+        match inferred.remove(&generic_param.name) {
+            Some(ttype) => inferred_type_args.push(ttype),
+            None if values_incomplete => return (None, errors),
+            None => errors.push(TypeCheckError::CannotInferTypeParam {
+                param_name: generic_param.name.clone(),
+                name: name.to_string(),
+                span,
+            }),
+        }
+    }
+    (errors.is_empty().then_some(inferred_type_args), errors)
+}
+
+/// Instances of generic functions and structs are named after the type arguments, as in
+/// `identity<int>`.
+fn instance_name(name: &str, type_args: &[Type]) -> String {
+    format!(
+        "{name}<{}>",
+        type_args
+            .iter()
+            .map(Type::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    )
+}
+
+/// Match the type of a parameter or field as written in a generic function or struct against the
+/// type of the value passed for it, recording the types that type parameters stand for. The first argument to decide
+/// a type parameter wins, mismatches are left for checking the arguments against the instance.
+fn infer_type_params(
+    generic_params: &[ParsedGenericParam],
+    param_type: &ParsedType,
+    arg_type: &Type,
+    default_ints: bool,
+    struct_instances: &HashMap<String, (String, Vec<Type>)>,
+    inferred: &mut HashMap<String, Type>,
+) {
+    let mut infer = |param_type, arg_type| {
+        infer_type_params(
+            generic_params,
+            param_type,
+            arg_type,
+            default_ints,
+            struct_instances,
+            inferred,
+        )
+    };
+
+    match (param_type, arg_type) {
+        (ParsedType::Name(name), arg_type)
+            if generic_params
+                .iter()
+                .any(|generic_param| generic_param.name == *name) =>
+        {
+            let ttype = match arg_type {
+                Type::GenericInt if default_ints => Type::Int,
+                Type::GenericInt
+                | Type::GenericEmptyArray
+                | Type::Null
+                | Type::Never
+                | Type::Incomplete => return,
+                arg_type => arg_type.clone(),
+            };
+            inferred.entry(name.clone()).or_insert(ttype);
+        }
+        (
+            ParsedType::Pointer(param_type, _) | ParsedType::NullablePointer(param_type, _),
+            Type::Pointer(arg_type, _) | Type::NullablePointer(arg_type, _),
+        )
+        | (ParsedType::Array(param_type, _), Type::Array(arg_type, _))
+        | (ParsedType::Slice(param_type), Type::Array(arg_type, _) | Type::Slice(arg_type)) => {
+            infer(param_type, arg_type)
+        }
+        (ParsedType::Tuple(param_types), Type::Tuple(arg_types)) => {
+            for (param_type, arg_type) in param_types.iter().zip(arg_types) {
+                infer(param_type, arg_type);
+            }
+        }
+        (ParsedType::Generic(struct_name, param_types), Type::UserDefined(instance_name)) => {
+            if let Some((instance_of, arg_types)) = struct_instances.get(instance_name) {
+                if instance_of == struct_name {
+                    for (param_type, arg_type) in param_types.iter().zip(arg_types) {
+                        infer(param_type, arg_type);
+                    }
+                }
+            }
+        }
+        (
+            ParsedType::FnPtr {
+                params: param_types,
+                return_type: param_return_type,
+            },
+            Type::FnPtr {
+                params: arg_types,
+                return_type: arg_return_type,
+            },
+        ) => {
+            for (param_type, arg_type) in param_types.iter().zip(arg_types) {
+                infer(param_type, arg_type);
+            }
+            infer(param_return_type, arg_return_type);
+        }
+        _ => {}
+    }
+}
+
+/// Methods are emitted as functions named after both the type and the method.
+fn method_symbol_name(type_name: &str, method_name: &str) -> String {
+    format!("{type_name}::{method_name}")
+}
+
+/// Order the arguments of a call by the parameters they are passed to, so named arguments can be
+/// given in any order after the positional ones. Without parameter names, as for calls through
+/// function pointers, the arguments keep their order. The arguments also keep their order when
+/// they can't be matched to the parameters, which is reported.
+fn order_call_args<'p>(
+    args: &'p [(CallArg, Span)],
+    parameter_names: Option<&[String]>,
+    function_name: &str,
+    call_span: Span,
+) -> (Vec<(&'p ParsedExpression, Span)>, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+    let in_order = args
+        .iter()
+        .map(|(arg, span)| (arg.value(), *span))
+        .collect();
+
+    let has_named_args = args.iter().any(|(arg, _)| matches!(arg, CallArg::Named(_)));
+    let Some(parameter_names) = parameter_names.filter(|_| has_named_args) else {
+        return (in_order, errors);
+    };
+
+    let mut ordered: Vec<Option<(&ParsedExpression, Span)>> = vec![None; parameter_names.len()];
+    let mut extra_args = vec![];
+    for (idx, (arg, arg_span)) in args.iter().enumerate() {
+        match arg {
+            CallArg::Positional(value) if idx < ordered.len() => {
+                ordered[idx] = Some((value, *arg_span));
+            }
+            CallArg::Positional(value) => extra_args.push((value, *arg_span)),
+            CallArg::Named(named_arg) => {
+                match parameter_names
+                    .iter()
+                    .position(|name| *name == named_arg.name)
+                {
+                    Some(param_idx) if ordered[param_idx].is_some() => {
+                        errors.push(TypeCheckError::DuplicateArg {
+                            param_name: named_arg.name.clone(),
+                            span: named_arg.name_span,
+                        });
+                    }
+                    Some(param_idx) => ordered[param_idx] = Some((&named_arg.value, *arg_span)),
+                    None => errors.push(TypeCheckError::UnknownNamedArg {
+                        function_name: function_name.to_string(),
+                        arg_name: named_arg.name.clone(),
+                        span: named_arg.name_span,
+                    }),
+                }
+            }
+        }
+    }
+
+    for (param_name, arg) in parameter_names.iter().zip(&ordered) {
+        if arg.is_none() {
+            errors.push(TypeCheckError::MissingArg {
+                function_name: function_name.to_string(),
+                param_name: param_name.clone(),
+                span: call_span,
+            });
+        }
+    }
+
+    if !errors.is_empty() {
+        return (in_order, errors);
+    }
+    let ordered = ordered.into_iter().flatten().chain(extra_args).collect();
+    (ordered, errors)
+}
+
+/// Arguments are checked in the order of the parameters they're passed to, but evaluated in the
+/// order they're written. When named arguments are written in another order, the receiver of a
+/// method and the arguments are bound to variables in that order first, in a block ending with
+/// the call. The variables are named after the span of the name of the called function.
+fn in_source_order(
+    mut func_call: CheckedFunctionCall,
+    arg_spans: &[Span],
+    name_span: Span,
+) -> CheckedExpression {
+    let mut source_order: Vec<usize> = (0..arg_spans.len()).collect();
+    source_order.sort_by_key(|&arg_idx| arg_spans[arg_idx].start);
+    if source_order
+        .iter()
+        .enumerate()
+        .all(|(idx, &arg_idx)| idx == arg_idx)
+    {
+        return CheckedExpression::FunctionCall(func_call);
+    }
+
+    // Arguments are preceded by the receiver in method calls
+    let first_arg = func_call.args.len() - arg_spans.len();
+    let evaluation_order =
+        (0..first_arg).chain(source_order.into_iter().map(|idx| first_arg + idx));
+    let mut statements = vec![];
+    for idx in evaluation_order {
+        let arg = &mut func_call.args[idx];
+        let ttype = arg.ttype();
+        let is_constant = matches!(
+            arg,
+            CheckedExpression::Literal(
+                CheckedLiteral::Int(..)
+                    | CheckedLiteral::Char(..)
+                    | CheckedLiteral::Bool(..)
+                    | CheckedLiteral::Null(_)
+                    | CheckedLiteral::String(..)
+            )
+        );
+        if is_constant || matches!(ttype, Type::Unit | Type::Never) {
+            continue;
+        }
+        // Not a valid identifier, so it can't clash with the variables of the program
+        let name = format!("arg.{}.{idx}", name_span.start);
+        let value = std::mem::replace(arg, CheckedExpression::Variable(name.clone(), ttype, false));
+        statements.push(CheckedStatement::LetAssign(name, value));
+    }
+
+    let ttype = func_call.ttype.clone();
+    CheckedExpression::Block(CheckedBlockExpression {
+        body: CheckedBlock { statements },
+        value: Some(Box::new(CheckedExpression::FunctionCall(func_call))),
+        ttype,
+    })
+}
+
+/// Without a function of that name, `assert_eq(a, b)` asserts `a == b`, so it compares values of
+/// every type `==` works on.
+fn typecheck_assert_eq(
+    context: &mut Context,
+    func_call: &ParsedFunctionCall,
+) -> (CheckedExpression, Vec<TypeCheckError>) {
+    let parameter_names = ["a".to_string(), "b".to_string()];
+    let (args, mut errors) = order_call_args(
+        &func_call.args,
+        Some(&parameter_names),
+        &func_call.name,
+        func_call.span,
+    );
+    if errors.is_empty() && args.len() != 2 {
+        errors.push(TypeCheckError::WrongNumArgs(func_call.span, args.len(), 2));
+    }
+
+    let [(lhs, _), (rhs, _)] = args[..] else {
+        let checked_args = args
+            .iter()
+            .map(|(arg, _)| {
+                let (checked_arg, mut errs) = typecheck_expression(context, arg);
+                errors.append(&mut errs);
+                checked_arg
+            })
+            .collect();
+        return (
+            CheckedExpression::FunctionCall(CheckedFunctionCall {
+                name: func_call.name.clone(),
+                args: checked_args,
+                ttype: Type::Incomplete,
+            }),
+            errors,
+        );
+    };
+
+    let comparison = ParsedExpression::CompareOp(
+        Box::new(lhs.clone()),
+        Box::new(rhs.clone()),
+        CompareOperation::Equality,
+    );
+    let (checked_comparison, mut errs) = typecheck_expression(context, &comparison);
+    errors.append(&mut errs);
+
+    context.use_builtin("assert", func_call.name_span);
+    (
+        CheckedExpression::FunctionCall(CheckedFunctionCall {
+            name: "assert".to_string(),
+            args: vec![checked_comparison],
+            ttype: Type::Unit,
+        }),
+        errors,
+    )
+}
+
+/// Without a function of that name, `str_len`, `str_data` and `str_slice` are the
+/// [`StrBuiltin`]s. A slice is preceded by a call to `str_slice_check`, stopping the program
+/// unless `0 <= start <= end <= str_len(s)`, so its arguments that aren't constants or variables
+/// are evaluated once into hidden variables.
+fn typecheck_str_builtin(
+    context: &mut Context,
+    func_call: &ParsedFunctionCall,
+    builtin: StrBuiltin,
+) -> (CheckedExpression, Vec<TypeCheckError>) {
+    let parameters = builtin.parameters();
+    let parameter_names: Vec<_> = parameters
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect();
+    let (args, mut errors) = order_call_args(
+        &func_call.args,
+        Some(&parameter_names),
+        &func_call.name,
+        func_call.span,
+    );
+    let args_matched = errors.is_empty();
+
+    let checked_args: Vec<_> = args
+        .iter()
+        .map(|(arg, _)| {
+            let (checked_arg, mut errs) = typecheck_expression(context, arg);
+            errors.append(&mut errs);
+            checked_arg
+        })
+        .collect();
+    let parameter_types: Vec<_> = parameters.into_iter().map(|(_, ttype)| ttype).collect();
+    let (mut checked_args, mut errs) =
+        check_call_args(checked_args, &args, &parameter_types, false, func_call.span);
+    errors.append(&mut errs);
+
+    if !args_matched || checked_args.len() != parameter_types.len() {
+        let ttype = if args_matched {
+            builtin.return_type()
+        } else {
+            Type::Incomplete
+        };
+        return (
+            CheckedExpression::FunctionCall(CheckedFunctionCall {
+                name: func_call.name.clone(),
+                args: checked_args,
+                ttype,
+            }),
+            errors,
+        );
+    }
+
+    if builtin != StrBuiltin::Slice {
+        let str_builtin = CheckedExpression::StrBuiltin(CheckedStrBuiltin {
+            builtin,
+            args: checked_args,
+        });
+        return (str_builtin, errors);
+    }
+
+    let mut statements = vec![];
+    for (arg, (param_name, ttype)) in checked_args.iter_mut().zip(builtin.parameters()) {
+        if const_eval(arg).is_some() || matches!(arg, CheckedExpression::Variable(..)) {
+            continue;
+        }
+        // Not a valid identifier, so it can't clash with the variables of the program
+        let name = format!("slice_{param_name}.{}", func_call.span.start);
+        let value = std::mem::replace(arg, CheckedExpression::Variable(name.clone(), ttype, false));
+        statements.push(CheckedStatement::LetAssign(name, value));
+    }
+
+    context.use_builtin("str_slice_check", func_call.name_span);
+    let len = CheckedExpression::StrBuiltin(CheckedStrBuiltin {
+        builtin: StrBuiltin::Len,
+        args: vec![checked_args[0].clone()],
+    });
+    statements.push(CheckedStatement::Expression(
+        CheckedExpression::FunctionCall(CheckedFunctionCall {
+            name: "str_slice_check".to_string(),
+            args: vec![checked_args[1].clone(), checked_args[2].clone(), len],
+            ttype: Type::Unit,
+        }),
+    ));
+
+    let str_builtin = CheckedExpression::StrBuiltin(CheckedStrBuiltin {
+        builtin,
+        args: checked_args,
+    });
+    (
+        CheckedExpression::Block(CheckedBlockExpression {
+            body: CheckedBlock { statements },
+            value: Some(Box::new(str_builtin)),
+            ttype: builtin.return_type(),
+        }),
+        errors,
+    )
+}
+
+/// Lower `==` and `!=` on two values of a struct type to a comparison of their fields. Operands that
+/// aren't variables are evaluated once into hidden variables first.
+fn typecheck_struct_comparison(
+    context: &Context,
+    lhs: CheckedExpression,
+    rhs: CheckedExpression,
+    op: CompareOperation,
+    span: Span,
+) -> (CheckedExpression, Vec<TypeCheckError>) {
+    let ttype = lhs.ttype();
+    let mut statements = vec![];
+    let mut operand = |value: CheckedExpression, name: String| match value {
+        CheckedExpression::Variable(..) => value,
+        _ => {
+            // Not a valid identifier, so it can't clash with the variables of the program
+            statements.push(CheckedStatement::LetAssign(name.clone(), value));
+            CheckedExpression::Variable(name, ttype.clone(), false)
+        }
+    };
+    let lhs = operand(lhs, format!("eq.{}", span.start));
+    let rhs = operand(rhs, format!("eq.{}", span.end()));
+
+    let (comparison, errors) = match compare_fields(context, lhs.clone(), rhs.clone()) {
+        Ok(comparison) => (comparison, vec![]),
+        Err(ttype) => (
+            CheckedExpression::CompareOp(
+                Box::new(lhs),
+                Box::new(rhs),
+                CompareOperation::Equality,
+                Type::Bool,
+            ),
+            vec![TypeCheckError::TypeNotComparable { ttype, span }],
+        ),
+    };
+    let comparison = match op {
+        CompareOperation::NotEqual => CheckedExpression::Not(Box::new(comparison)),
+        _ => comparison,
+    };
+
+    if statements.is_empty() {
+        return (comparison, errors);
+    }
+    (
+        CheckedExpression::Block(CheckedBlockExpression {
+            body: CheckedBlock { statements },
+            value: Some(Box::new(comparison)),
+            ttype: Type::Bool,
+        }),
+        errors,
+    )
 }
 
-impl CheckedExpression {
-    pub fn ttype(&self) -> Type {
-        match self {
-            Self::Literal(literal) => match literal {
-                CheckedLiteral::String(_, ttype) => ttype,
-                CheckedLiteral::Int(_, ttype) => ttype,
-                CheckedLiteral::Bool(_, ttype) => ttype,
-                CheckedLiteral::Struct(_, _, ttype) => ttype,
-                CheckedLiteral::Array(_, ttype) => ttype,
+/// Whether two values are equal, comparing structs declared with `#[eq]` field by field. Gives the
+/// type that can't be compared otherwise.
+fn compare_fields(
+    context: &Context,
+    lhs: CheckedExpression,
+    rhs: CheckedExpression,
+) -> Result<CheckedExpression, Type> {
+    let ttype = lhs.ttype();
+    let r#struct = match ttype {
+        Type::UserDefined(ref name) => context.known_structs.get(name),
+        _ => None,
+    };
+    match r#struct {
+        Some(r#struct) if r#struct.is_opaque || !r#struct.has_eq => Err(ttype),
+        Some(r#struct) => {
+            let field = |object: &CheckedExpression, field_name: &String, field_type: &Type| {
+                CheckedExpression::FieldAccess(
+                    CheckedFieldAccess {
+                        object: Box::new(object.clone()),
+                        object_is_ptr: false,
+                        field_name: field_name.clone(),
+                        is_mut: false,
+                    },
+                    r#struct.clone(),
+                    field_type.clone(),
+                )
+            };
+            let mut comparison = None;
+            for (field_name, field_type) in &r#struct.fields {
+                let field_comparison = compare_fields(
+                    context,
+                    field(&lhs, field_name, field_type),
+                    field(&rhs, field_name, field_type),
+                )?;
+                comparison = Some(match comparison {
+                    Some(comparison) => CheckedExpression::LogicalAnd(
+                        Box::new(comparison),
+                        Box::new(field_comparison),
+                    ),
+                    None => field_comparison,
+                });
             }
-            .clone(),
-            Self::FunctionCall(func_call) => func_call.ttype.clone(),
-            Self::Variable(_name, ttype, _is_mut) => ttype.clone(),
-            Self::CompareOp(_lhs, _rhs, _op, ttype) => ttype.clone(),
-            Self::MathOp(_lhs, _rhs, _op, ttype) => ttype.clone(),
-            Self::FieldAccess(_field_access, _struct, ttype) => ttype.clone(),
-            Self::ArrayIndex(array_index) => array_index.ttype.clone(),
-            Self::Assignment(_lhs, _rhs) => Type::Unit,
-            Self::PointerTo(pointer_to) => pointer_to.ttype.clone(),
-            Self::Deref(deref) => deref.ttype.clone(),
+            Ok(
+                comparison.unwrap_or(CheckedExpression::Literal(CheckedLiteral::Bool(
+                    true,
+                    Type::Bool,
+                ))),
+            )
         }
+        None => match ttype {
+            Type::Array(..)
+            | Type::Slice(_)
+            | Type::Str
+            | Type::Tuple(_)
+            | Type::FnPtr { .. }
+            | Type::Range(_)
+            | Type::Unit => Err(ttype),
+            _ => Ok(CheckedExpression::CompareOp(
+                Box::new(lhs),
+                Box::new(rhs),
+                CompareOperation::Equality,
+                Type::Bool,
+            )),
+        },
     }
+}
 
-    pub fn is_mut(&self) -> bool {
-        match self {
-            Self::Variable(_, _, is_mut) => *is_mut,
-            Self::FieldAccess(field_acces, _, _) => field_acces.is_mut,
-            _ => true,
+/// Check the arguments of a call against the types of the parameters of the called function.
+/// Variadic functions accept any number of extra arguments of any type.
+fn check_call_args(
+    checked_args: Vec<CheckedExpression>,
+    args: &[(&ParsedExpression, Span)],
+    parameter_types: &[Type],
+    is_variadic: bool,
+    call_span: Span,
+) -> (Vec<CheckedExpression>, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+
+    if checked_args.len() < parameter_types.len()
+        || (!is_variadic && checked_args.len() > parameter_types.len())
+    {
+        errors.push(TypeCheckError::WrongNumArgs(
+            call_span,
+            checked_args.len(),
+            parameter_types.len(),
+        ));
+    }
+
+    let checked_args: Vec<CheckedExpression> = checked_args
+        .into_iter()
+        .enumerate()
+        .map(|(idx, checked_arg)| match parameter_types.get(idx) {
+            Some(param_type) => coerce_to(checked_arg, param_type),
+            // C's variadic functions take string literals as C strings
+            None if matches!(
+                checked_arg,
+                CheckedExpression::Literal(CheckedLiteral::String(..))
+            ) =>
+            {
+                coerce_to(checked_arg, &Type::Pointer(Box::new(Type::CChar), false))
+            }
+            None => checked_arg,
+        })
+        .collect();
+
+    for ((checked_arg, (_, arg_span)), param_type) in
+        checked_args.iter().zip(args).zip(parameter_types)
+    {
+        if !checked_arg.ttype().matches(param_type) {
+            errors.push(TypeCheckError::WrongArgType(
+                *arg_span,
+                checked_arg.ttype(),
+                param_type.clone(),
+            ));
         }
     }
-}
 
-#[derive(Debug)]
-pub struct CheckedWhileLoop {
-    pub condition: CheckedExpression,
-    pub body: CheckedBlock,
+    (checked_args, errors)
 }
 
-#[derive(Debug)]
-pub struct CheckedIfElse {
-    pub condition: CheckedExpression,
-    pub if_body: CheckedBlock,
-    pub else_body: CheckedBlock,
-}
+/// Arguments for `ref` parameters are passed as pointers to them, so they have to be l-values.
+/// Pointers that already fit the parameter, like another `ref` parameter, are passed on as they
+/// are.
+fn pass_by_ref(
+    checked_args: Vec<CheckedExpression>,
+    args: &[(&ParsedExpression, Span)],
+    parameters: &[CheckedParameter],
+) -> (Vec<CheckedExpression>, Vec<TypeCheckError>) {
+    let mut errors = vec![];
 
-#[derive(Debug)]
-pub struct CheckedForInLoop {
-    pub elem_var_name: String,
-    pub elem_var_type: Type,
-    pub index_var: Option<String>,
-    pub iterable: CheckedExpression,
-    pub body: CheckedBlock,
+    let checked_args = checked_args
+        .into_iter()
+        .zip(args)
+        .enumerate()
+        .map(
+            |(idx, (checked_arg, (_, arg_span)))| match parameters.get(idx) {
+                Some(param) if param.is_by_ref && !checked_arg.ttype().matches(&param.ttype) => {
+                    if !is_l_value(&checked_arg) {
+                        errors.push(TypeCheckError::RefArgumentNotLValue(*arg_span));
+                    }
+                    let ttype = Type::Pointer(Box::new(checked_arg.ttype()), false);
+                    CheckedExpression::PointerTo(CheckedPointerTo {
+                        inner: Box::new(checked_arg),
+                        ttype,
+                    })
+                }
+                _ => checked_arg,
+            },
+        )
+        .collect();
+
+    (checked_args, errors)
 }
 
-#[derive(Debug)]
-pub enum CheckedStatement {
-    Expression(CheckedExpression),
-    LetAssign(String, CheckedExpression),
-    WhileLoop(CheckedWhileLoop),
-    IfElse(CheckedIfElse),
-    ForInLoop(CheckedForInLoop),
-    Return(CheckedExpression),
+/// Whether the expression refers to a value stored in memory, which can be pointed to.
+fn is_l_value(expression: &CheckedExpression) -> bool {
+    match expression {
+        CheckedExpression::Variable(..)
+        | CheckedExpression::ArrayIndex(_)
+        | CheckedExpression::Deref(_) => true,
+        CheckedExpression::FieldAccess(field_access, _, _) => {
+            field_access.object_is_ptr || is_l_value(&field_access.object)
+        }
+        CheckedExpression::TupleIndex(tuple_index) => is_l_value(&tuple_index.object),
+        _ => false,
+    }
 }
 
-#[derive(Debug)]
-pub struct CheckedBlock {
-    pub statements: Vec<CheckedStatement>,
+/// Arrays are implicitly turned into slices where a slice of their element type is expected,
+/// pointers into nullable pointers where a nullable pointer is expected, and `null` takes on the
+/// pointer type it's used as. String literals are C strings where a `->c_char` is expected, and
+/// interpolated strings are `str`s where a `str` is expected.
+fn coerce_to(expression: CheckedExpression, target_type: &Type) -> CheckedExpression {
+    // The values of blocks and branches are coerced where they are produced, and the elements of
+    // array literals one by one
+    let expression = match expression {
+        CheckedExpression::Block(mut block) => {
+            coerce_block_to(&mut block, target_type);
+            return CheckedExpression::Block(block);
+        }
+        CheckedExpression::IfExpr(mut if_expr) if if_expr.ttype != Type::Unit => {
+            coerce_block_to(&mut if_expr.if_body, target_type);
+            coerce_block_to(&mut if_expr.else_body, target_type);
+            if_expr.ttype = match if_expr.if_body.ttype {
+                Type::Never => if_expr.else_body.ttype.clone(),
+                ref ttype => ttype.clone(),
+            };
+            return CheckedExpression::IfExpr(if_expr);
+        }
+        CheckedExpression::Literal(CheckedLiteral::Array(array_literal, array_type)) => {
+            match target_type {
+                Type::Array(target_element_type, _) => {
+                    let elements: Vec<_> = array_literal
+                        .elements
+                        .into_iter()
+                        .map(|element| coerce_to(element, target_element_type))
+                        .collect();
+                    let (element_type, array_type) = match elements.first() {
+                        Some(element) => (
+                            Some(element.ttype()),
+                            Type::Array(Box::new(element.ttype()), elements.len()),
+                        ),
+                        None => (array_literal.element_type, array_type),
+                    };
+                    return CheckedExpression::Literal(CheckedLiteral::Array(
+                        CheckedArrayLiteral {
+                            elements,
+                            element_type,
+                        },
+                        array_type,
+                    ));
+                }
+                _ => CheckedExpression::Literal(CheckedLiteral::Array(array_literal, array_type)),
+            }
+        }
+        expression => expression,
+    };
+
+    match (target_type, expression.ttype()) {
+        (Type::NullablePointer(..), Type::Null) => {
+            CheckedExpression::Literal(CheckedLiteral::Null(target_type.clone()))
+        }
+        (Type::Pointer(elem_type, false) | Type::NullablePointer(elem_type, false), Type::Str)
+            if **elem_type == Type::CChar =>
+        {
+            match expression {
+                CheckedExpression::Literal(CheckedLiteral::String(value, _)) => coerce_to(
+                    CheckedExpression::Literal(CheckedLiteral::String(
+                        value,
+                        Type::Pointer(Box::new(Type::CChar), false),
+                    )),
+                    target_type,
+                ),
+                expression => expression,
+            }
+        }
+        (Type::Str, Type::Pointer(elem_type, false))
+            if *elem_type == Type::CChar && is_interpolated_string(&expression) =>
+        {
+            CheckedExpression::StrBuiltin(CheckedStrBuiltin {
+                builtin: StrBuiltin::FromCString,
+                args: vec![expression],
+            })
+        }
+        (
+            Type::NullablePointer(nullable_elem_type, nullable_mut),
+            Type::Pointer(elem_type, is_mut),
+        ) if *nullable_mut == is_mut && elem_type.matches(nullable_elem_type) => {
+            CheckedExpression::PointerToNullable(CheckedPointerToNullable {
+                pointer: Box::new(expression),
+                ttype: target_type.clone(),
+            })
+        }
+        (Type::Slice(slice_elem_type), Type::Array(array_elem_type, _))
+            if array_elem_type.matches(slice_elem_type) =>
+        {
+            CheckedExpression::ArrayToSlice(CheckedArrayToSlice {
+                array: Box::new(expression),
+                ttype: target_type.clone(),
+            })
+        }
+        _ => expression,
+    }
 }
 
-#[derive(Debug)]
-pub struct CheckedFunction {
-    pub name: String,
-    pub body: CheckedBlock,
-    pub parameters: Vec<FunctionParameter>,
-    pub return_type: Type,
+/// Interpolated strings are desugared into calls of `string_concat`, or of one of the builtins
+/// converting a value to a string if they consist of a single placeholder.
+fn is_interpolated_string(expression: &CheckedExpression) -> bool {
+    matches!(
+        expression,
+        CheckedExpression::FunctionCall(func_call) if matches!(
+            func_call.name.as_str(),
+            "string_concat" | "int_to_string" | "char_to_string" | "bool_to_string"
+        )
+    )
 }
 
-#[derive(Debug)]
-pub struct CheckedExternFunction {
-    pub name: String,
-    pub parameters: Vec<FunctionParameter>,
-    pub return_type: Type,
+fn coerce_block_to(block: &mut CheckedBlockExpression, target_type: &Type) {
+    if let Some(value) = block.value.take() {
+        let value = coerce_to(*value, target_type);
+        block.ttype = value.ttype();
+        block.value = Some(Box::new(value));
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct Struct {
-    pub name: String,
-    pub fields: Vec<(String, Type)>,
-    pub is_opaque: bool,
+/// The variables a constant expression refers to, the other constants a constant's initializer
+/// uses. Expressions that can't be constant aren't looked into.
+fn referenced_variables<'a>(expression: &'a ParsedExpression, names: &mut Vec<&'a str>) {
+    match expression {
+        ParsedExpression::Variable(name, _) => names.push(name),
+        ParsedExpression::CompareOp(lhs, rhs, _)
+        | ParsedExpression::MathOp(lhs, rhs, _)
+        | ParsedExpression::LogicalAnd(lhs, rhs)
+        | ParsedExpression::LogicalOr(lhs, rhs) => {
+            referenced_variables(lhs, names);
+            referenced_variables(rhs, names);
+        }
+        ParsedExpression::UnaryMinus(inner, _)
+        | ParsedExpression::Not(inner, _)
+        | ParsedExpression::BitNot(inner, _)
+        | ParsedExpression::Parenthesized(inner, _) => referenced_variables(inner, names),
+        _ => {}
+    }
 }
 
-impl Struct {
-    fn get_field(&self, field_name: &str) -> Option<&Type> {
-        for (delcared_field_name, declared_field_type) in &self.fields {
-            if delcared_field_name == field_name {
-                return Some(declared_field_type);
+/// Module level constants in an order in which every constant comes after the ones its initializer
+/// uses, and otherwise in the order they are declared. Of constants using each other, one is
+/// checked before the other and refers to a constant that is still unknown.
+fn const_evaluation_order(consts: &[ParsedConstDecl]) -> Vec<&ParsedConstDecl> {
+    fn visit<'a>(
+        const_decl: &'a ParsedConstDecl,
+        by_name: &HashMap<&str, &'a ParsedConstDecl>,
+        visited: &mut HashSet<*const ParsedConstDecl>,
+        order: &mut Vec<&'a ParsedConstDecl>,
+    ) {
+        if !visited.insert(const_decl) {
+            return;
+        }
+        let mut names = vec![];
+        referenced_variables(&const_decl.value, &mut names);
+        for name in names {
+            if let Some(dependency) = by_name.get(name) {
+                visit(dependency, by_name, visited, order);
             }
         }
-        None
+        order.push(const_decl);
     }
-}
 
-#[derive(Debug)]
-pub struct CheckedProgram {
-    pub extern_functions: Vec<CheckedExternFunction>,
-    pub functions: Vec<CheckedFunction>,
-    pub structs: Vec<Struct>,
+    let mut by_name = HashMap::new();
+    for const_decl in consts {
+        by_name
+            .entry(const_decl.name.as_str())
+            .or_insert(const_decl);
+    }
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    for const_decl in consts {
+        visit(const_decl, &by_name, &mut visited, &mut order);
+    }
+    order
 }
 
-#[derive(Debug)]
-struct Function {
-    parameters: Vec<FunctionParameter>,
-    return_type: Type,
+fn typecheck_const_decl(
+    context: &mut Context,
+    const_decl: &ParsedConstDecl,
+) -> Vec<TypeCheckError> {
+    let (checked_value, mut errors) = typecheck_expression(context, &const_decl.value);
+
+    let const_type = context.resolve_type(&const_decl.ttype);
+    let checked_value = coerce_to(checked_value, &const_type);
+    if !context.type_is_defined(&const_type) {
+        errors.push(context.unknown_type_error(&const_decl.ttype, const_decl.type_span));
+    } else if const_type != Type::Incomplete && !const_type.matches(&checked_value.ttype()) {
+        errors.push(TypeCheckError::TypeAnnotationMismatch(
+            const_type.clone(),
+            checked_value.ttype(),
+            const_decl.type_span,
+            const_decl.value.span(),
+        ));
+    }
+
+    let value = match try_const_eval(&checked_value) {
+        Ok(value) => Some(value),
+        Err(_) if !errors.is_empty() => None,
+        Err(ConstEvalFailure::NotConstant) => {
+            errors.push(TypeCheckError::NonConstantInitializer(
+                const_decl.value.span(),
+            ));
+            None
+        }
+        Err(failure) => {
+            errors.push(TypeCheckError::ConstantEvaluationFailed(
+                failure,
+                const_decl.value.span(),
+            ));
+            None
+        }
+    };
+
+    if context
+        .scope_stack
+        .add_constant(&const_decl.name, const_type, value)
+    {
+        errors.push(TypeCheckError::DuplicateVariableName(
+            const_decl.name.clone(),
+            const_decl.name_span,
+        ));
+    }
+
+    errors
 }
 
-#[derive(Debug)]
-struct KnownVariable {
-    ttype: Type,
-    is_mut: bool,
+/// Evaluate an expression at compile time, `None` if it is not a constant expression or its
+/// evaluation fails (e.g. overflow or division by zero).
+fn const_eval(expression: &CheckedExpression) -> Option<ConstValue> {
+    try_const_eval(expression).ok()
 }
 
-#[derive(Debug, Default)]
-struct ScopeStack {
-    stack: Vec<(Option<String>, HashMap<String, KnownVariable>)>,
+/// Why an expression couldn't be evaluated at compile time.
+#[derive(Debug, Clone, Copy)]
+pub enum ConstEvalFailure {
+    NotConstant,
+    /// An operation on constants overflowed an `int`, negation counts as subtraction
+    Overflow(MathOperation),
+    DivisionByZero,
 }
 
-impl ScopeStack {
-    fn push_scope(&mut self, function_name: Option<String>) {
-        self.stack.push((function_name, HashMap::default()));
-    }
+/// Evaluate an expression at compile time like [`const_eval`], telling apart expressions that
+/// aren't constant from constant ones whose evaluation fails.
+fn try_const_eval(expression: &CheckedExpression) -> Result<ConstValue, ConstEvalFailure> {
+    use ConstEvalFailure::*;
+
+    Ok(match expression {
+        CheckedExpression::Literal(literal) => match literal {
+            CheckedLiteral::String(value, _) => ConstValue::String(value.clone()),
+            CheckedLiteral::Char(value, _) => ConstValue::Char(*value),
+            CheckedLiteral::Int(value, _) => ConstValue::Int(*value),
+            CheckedLiteral::Bool(value, _) => ConstValue::Bool(*value),
+            CheckedLiteral::Null(_)
+            | CheckedLiteral::Struct(_, _, _)
+            | CheckedLiteral::Array(_, _)
+            | CheckedLiteral::Tuple(_, _) => return Err(NotConstant),
+        },
+        CheckedExpression::MathOp(lhs, rhs, op, _) => {
+            let (lhs, rhs) = match (try_const_eval(lhs)?, try_const_eval(rhs)?) {
+                (ConstValue::Int(lhs), ConstValue::Int(rhs)) => (lhs, rhs),
+                _ => return Err(NotConstant),
+            };
+            let value = match op {
+                MathOperation::Division | MathOperation::Modulo if rhs == 0 => {
+                    return Err(DivisionByZero)
+                }
+                MathOperation::Addition => lhs.checked_add(rhs),
+                MathOperation::Subtraction => lhs.checked_sub(rhs),
+                MathOperation::Multiplication => lhs.checked_mul(rhs),
+                MathOperation::Division => lhs.checked_div(rhs),
+                MathOperation::Modulo => lhs.checked_rem(rhs),
+                MathOperation::BitAnd => Some(lhs & rhs),
+                MathOperation::BitOr => Some(lhs | rhs),
+                MathOperation::BitXor => Some(lhs ^ rhs),
+                MathOperation::Shl => u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shl(rhs)),
+                MathOperation::Shr => u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_shr(rhs)),
+            };
+            ConstValue::Int(value.ok_or(Overflow(*op))?)
+        }
+        CheckedExpression::CompareOp(lhs, rhs, op, _) => {
+            let ordering = match (try_const_eval(lhs)?, try_const_eval(rhs)?) {
+                (ConstValue::Char(lhs), ConstValue::Char(rhs)) => lhs.cmp(&rhs),
+                (ConstValue::Int(lhs), ConstValue::Int(rhs)) => lhs.cmp(&rhs),
+                (ConstValue::Bool(lhs), ConstValue::Bool(rhs)) => lhs.cmp(&rhs),
+                _ => return Err(NotConstant),
+            };
+            ConstValue::Bool(match op {
+                CompareOperation::Equality => ordering.is_eq(),
+                CompareOperation::NotEqual => ordering.is_ne(),
+                CompareOperation::GreaterThan => ordering.is_gt(),
+                CompareOperation::GreaterThanEqual => ordering.is_ge(),
+                CompareOperation::LessThan => ordering.is_lt(),
+                CompareOperation::LessThanEqual => ordering.is_le(),
+            })
+        }
+        CheckedExpression::LogicalAnd(lhs, rhs) => {
+            match (try_const_eval(lhs)?, try_const_eval(rhs)?) {
+                (ConstValue::Bool(lhs), ConstValue::Bool(rhs)) => ConstValue::Bool(lhs && rhs),
+                _ => return Err(NotConstant),
+            }
+        }
+        CheckedExpression::LogicalOr(lhs, rhs) => {
+            match (try_const_eval(lhs)?, try_const_eval(rhs)?) {
+                (ConstValue::Bool(lhs), ConstValue::Bool(rhs)) => ConstValue::Bool(lhs || rhs),
+                _ => return Err(NotConstant),
+            }
+        }
+        CheckedExpression::UnaryMinus(inner, _) => match try_const_eval(inner)? {
+            ConstValue::Int(value) => ConstValue::Int(
+                value
+                    .checked_neg()
+                    .ok_or(Overflow(MathOperation::Subtraction))?,
+            ),
+            _ => return Err(NotConstant),
+        },
+        CheckedExpression::Not(inner) => match try_const_eval(inner)? {
+            ConstValue::Bool(value) => ConstValue::Bool(!value),
+            _ => return Err(NotConstant),
+        },
+        CheckedExpression::BitNot(inner, _) => match try_const_eval(inner)? {
+            ConstValue::Int(value) => ConstValue::Int(!value),
+            _ => return Err(NotConstant),
+        },
+        _ => return Err(NotConstant),
+    })
+}
 
-    fn pop_scope(&mut self) {
-        self.stack.pop();
+/// Typechecks a block whose value isn't used, a tail expression is kept as its last statement.
+fn typecheck_block(
+    context: &mut Context,
+    block: &ParsedBlock,
+) -> (CheckedBlock, Vec<TypeCheckError>) {
+    let (mut checked_block, value, errors) = typecheck_block_with_value(context, block, false);
+    if let Some((value, _)) = value {
+        checked_block
+            .statements
+            .push(CheckedStatement::Expression(value));
     }
+    (checked_block, errors)
+}
 
-    fn add_variable(&mut self, variable_name: &str, ttype: Type, is_mut: bool) -> bool {
-        let entry = self
-            .stack
-            .last_mut()
-            .unwrap()
-            .1
-            .entry(variable_name.to_string());
+/// Typechecks a block and its value with its span, which is checked in the scope of the block.
+/// With `trailing_if_is_value` a final if-else statement producing a value is the block's value
+/// instead, as in `{ let a = f(); if a > 0 { a } else { 0 } }`. A final `unsafe` block with a
+/// value always is the block's value, as in `{ unsafe { *ptr } }`.
+fn typecheck_block_with_value(
+    context: &mut Context,
+    block: &ParsedBlock,
+    trailing_if_is_value: bool,
+) -> (
+    CheckedBlock,
+    Option<(CheckedExpression, Span)>,
+    Vec<TypeCheckError>,
+) {
+    let mut errors = vec![];
 
-        match entry {
-            Entry::Occupied(_) => true,
-            _ => {
-                entry.or_insert(KnownVariable { ttype, is_mut });
-                false
-            }
+    let trailing_if = match (block.statements.last(), &block.tail_expr) {
+        (Some(ParsedStatement::IfElse(if_else)), None)
+            if trailing_if_is_value && if_else.else_body.is_some() =>
+        {
+            Some(if_else)
         }
-    }
+        _ => None,
+    };
+    let trailing_unsafe = match (block.statements.last(), &block.tail_expr) {
+        (Some(ParsedStatement::Unsafe(body, span)), None) if body.tail_expr.is_some() => {
+            Some((body, *span))
+        }
+        _ => None,
+    };
+    let statement_count =
+        block.statements.len() - usize::from(trailing_if.is_some() || trailing_unsafe.is_some());
 
-    fn get_variable_type(&self, variable_name: &str) -> Option<&KnownVariable> {
-        for scope in self.stack.iter().rev() {
-            if let Some(var) = scope.1.get(variable_name) {
-                return Some(var);
-            }
+    context.scope_stack.push_scope(None);
+    context.deferred_calls.push(vec![]);
+    let mut statements: Vec<_> = block.statements[..statement_count]
+        .iter()
+        .flat_map(|stmt| {
+            let (checked_stmts, mut errs) = match stmt {
+                ParsedStatement::LetAssign(let_assign) => typecheck_let_assign(context, let_assign),
+                ParsedStatement::WhileLoop(while_loop) => typecheck_while_loop(context, while_loop),
+                ParsedStatement::Defer(call, _) => (vec![], typecheck_defer(context, call)),
+                ParsedStatement::Return(_)
+                | ParsedStatement::Break(..)
+                | ParsedStatement::Continue(..) => {
+                    let (checked_stmt, errs) = typecheck_statement(context, stmt);
+                    (run_deferred_calls(context, stmt, checked_stmt), errs)
+                }
+                _ => {
+                    let (checked_stmt, errs) = typecheck_statement(context, stmt);
+                    (vec![checked_stmt], errs)
+                }
+            };
+            errors.append(&mut errs);
+            checked_stmts
+        })
+        .collect();
+
+    let value = if let Some(if_else) = trailing_if {
+        let (if_expr, mut errs) = typecheck_if_expression(context, if_else);
+        errors.append(&mut errs);
+
+        // An if-else without a value stays a statement
+        if matches!(if_expr.ttype, Type::Unit | Type::Never) {
+            statements.push(if_expr.into_statement());
+            None
+        } else {
+            Some((CheckedExpression::IfExpr(if_expr), if_else.span))
         }
-        None
-    }
+    } else if let Some((body, span)) = trailing_unsafe {
+        let (checked_body, mut errs) = typecheck_unsafe_block(context, body);
+        errors.append(&mut errs);
+        Some((CheckedExpression::Block(checked_body), span))
+    } else {
+        block.tail_expr.as_ref().map(|tail_expr| {
+            let (checked_value, mut errs) = typecheck_expression(context, tail_expr);
+            errors.append(&mut errs);
+            (checked_value, tail_expr.span())
+        })
+    };
 
-    fn get_current_function(&self) -> Option<&str> {
-        for scope in self.stack.iter().rev() {
-            if let Some(ref function_name) = scope.0 {
-                return Some(function_name);
+    // Leaving the block at its end runs its deferred calls after computing its value
+    let deferred_calls = context.deferred_calls_from(context.deferred_calls.len() - 1);
+    context.deferred_calls.pop();
+    let value = match value {
+        _ if deferred_calls.is_empty() => value,
+        None if statements.last().is_some_and(CheckedStatement::diverges) => None,
+        None => {
+            statements.extend(deferred_calls);
+            None
+        }
+        Some((value, _)) if matches!(value.ttype(), Type::Unit | Type::Never) => {
+            let diverges = value.ttype() == Type::Never;
+            statements.push(CheckedStatement::Expression(value));
+            if !diverges {
+                statements.extend(deferred_calls);
             }
+            None
         }
-        None
+        Some((value, span)) => {
+            let name = format!("defer.{}", span.start);
+            let ttype = value.ttype();
+            statements.push(CheckedStatement::LetAssign(name.clone(), value));
+            statements.extend(deferred_calls);
+            Some((CheckedExpression::Variable(name, ttype, false), span))
+        }
+    };
+    context.scope_stack.pop_scope();
+
+    (CheckedBlock { statements }, value, errors)
+}
+
+/// Typechecks the body of a loop, which `break` and `continue` leave.
+fn typecheck_loop_body(
+    context: &mut Context,
+    body: &ParsedBlock,
+    label: &Option<(String, Span)>,
+) -> (CheckedBlock, Vec<TypeCheckError>) {
+    let label = label.as_ref().map(|(label, _)| label.clone());
+    context.loops.push((label, context.deferred_calls.len()));
+    let checked_body = typecheck_block(context, body);
+    context.loops.pop();
+    checked_body
+}
+
+/// The number of loops between a `break` or `continue` and the loop it leaves, which is the
+/// innermost loop unless a label names another one.
+fn loop_target(context: &Context, label: &Option<(String, Span)>) -> Option<usize> {
+    match label {
+        Some((label, _)) => context
+            .loops
+            .iter()
+            .rev()
+            .position(|(loop_label, _)| loop_label.as_ref() == Some(label)),
+        None => Some(0),
     }
 }
 
-#[derive(Debug)]
-struct Context<'a> {
-    known_structs: HashMap<String, Struct>,
-    known_functions: HashMap<String, Function>,
-    scope_stack: ScopeStack,
-    current_function: Option<&'a ParsedFunction>,
+/// Typechecks a deferred call, which is run whenever the enclosing block is left.
+fn typecheck_defer(context: &mut Context, call: &ParsedExpression) -> Vec<TypeCheckError> {
+    let (checked_call, mut errors) = typecheck_expression(context, call);
+
+    if !matches!(
+        call,
+        ParsedExpression::FunctionCall(_) | ParsedExpression::MethodCall(_)
+    ) {
+        errors.push(TypeCheckError::DeferNotCall(call.span()));
+    } else if !matches!(checked_call.ttype(), Type::Unit | Type::Incomplete) {
+        errors.push(TypeCheckError::DeferNotUnit(
+            checked_call.ttype(),
+            call.span(),
+        ));
+    }
+
+    context
+        .deferred_calls
+        .last_mut()
+        .expect("statements are checked inside of blocks")
+        .push(checked_call);
+    errors
 }
 
-impl<'a> Context<'a> {
-    fn type_is_defined(&self, ttype: &Type) -> bool {
-        match ttype {
-            Type::Pointer(ref subtype, _is_mut) => self.type_is_defined(subtype),
-            Type::UserDefined(ref name) => self.known_structs.contains_key(name),
-            _ => true,
+/// Runs the calls deferred in the blocks a `return`, `break` or `continue` leaves before it. A
+/// returned value is computed before the deferred calls run.
+fn run_deferred_calls(
+    context: &Context,
+    statement: &ParsedStatement,
+    checked_statement: CheckedStatement,
+) -> Vec<CheckedStatement> {
+    let depth = match checked_statement {
+        CheckedStatement::Break(loop_depth) | CheckedStatement::Continue(loop_depth) => context
+            .loops
+            .iter()
+            .rev()
+            .nth(loop_depth)
+            .map_or(0, |&(_, defer_depth)| defer_depth),
+        _ => 0,
+    };
+    let mut statements = context.deferred_calls_from(depth);
+    if statements.is_empty() {
+        return vec![checked_statement];
+    }
+
+    match checked_statement {
+        CheckedStatement::Return(value) if value.ttype() == Type::Never => {
+            vec![CheckedStatement::Return(value)]
+        }
+        CheckedStatement::Return(value) if value.ttype() == Type::Unit => {
+            statements.insert(0, CheckedStatement::Expression(value));
+            statements.push(CheckedStatement::Return(CheckedExpression::Block(
+                CheckedBlockExpression {
+                    body: CheckedBlock { statements: vec![] },
+                    value: None,
+                    ttype: Type::Unit,
+                },
+            )));
+            statements
+        }
+        CheckedStatement::Return(value) => {
+            let ParsedStatement::Return(return_value) = statement else {
+                unreachable!("only `return` statements return a value");
+            };
+            let name = format!("defer.{}", return_value.span().start);
+            let ttype = value.ttype();
+            statements.insert(0, CheckedStatement::LetAssign(name.clone(), value));
+            statements.push(CheckedStatement::Return(CheckedExpression::Variable(
+                name, ttype, false,
+            )));
+            statements
+        }
+        statement => {
+            statements.push(statement);
+            statements
         }
     }
 }
 
-pub fn typecheck_program(program: &ParsedProgram) -> (CheckedProgram, Vec<TypeCheckError>) {
-    let mut errors = vec![];
-
-    let mut context = Context {
-        known_structs: HashMap::new(),
-        known_functions: HashMap::new(),
-        scope_stack: ScopeStack::default(),
-        current_function: None,
+/// Typechecks a block used as an expression, it takes the type of its value.
+fn typecheck_block_expression(
+    context: &mut Context,
+    block: &ParsedBlock,
+) -> (CheckedBlockExpression, Vec<TypeCheckError>) {
+    let (body, value, errors) = typecheck_block_with_value(context, block, true);
+    let ttype = match value {
+        Some((ref value, _)) => value.ttype(),
+        None => body.ttype(),
     };
 
-    for func in &program.extern_functions {
-        let name = func.name.clone();
+    (
+        CheckedBlockExpression {
+            body,
+            value: value.map(|(value, _)| Box::new(value)),
+            ttype,
+        },
+        errors,
+    )
+}
 
-        if context.known_functions.contains_key(&name) {
-            errors.push(TypeCheckError::DuplicateFuncStructName(
-                name.clone(),
-                func.name_span,
-            ));
-            continue;
-        }
+/// The body of an `unsafe` block, in which unsafe operations are allowed. Functions called from
+/// it are checked on their own and don't become unsafe blocks themselves.
+fn typecheck_unsafe_block(
+    context: &mut Context,
+    body: &ParsedBlock,
+) -> (CheckedBlockExpression, Vec<TypeCheckError>) {
+    let outer_unsafe = std::mem::replace(&mut context.in_unsafe, true);
+    let checked_body = typecheck_block_expression(context, body);
+    context.in_unsafe = outer_unsafe;
+    checked_body
+}
 
-        context.known_functions.insert(
-            name,
-            Function {
-                parameters: func.parameters.clone(),
-                return_type: func.return_type.clone(),
-            },
-        );
+/// An `if` used as an expression. Its branches have to be of the same type, unless one of them
+/// never reaches its end, and without an `else` branch it has to be of type `unit`.
+fn typecheck_if_expression(
+    context: &mut Context,
+    if_else: &ParsedIfElse,
+) -> (CheckedIfExpr, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+
+    let (checked_condition, mut errs) = typecheck_expression(context, &if_else.condition);
+    errors.append(&mut errs);
+
+    if checked_condition.ttype() != Type::Bool {
+        errors.push(TypeCheckError::WrongConditionType(
+            if_else.condition.span(),
+            checked_condition.ttype(),
+        ));
     }
-    for func in &program.functions {
-        let name = func.name.clone();
 
-        if context.known_functions.contains_key(&name) {
-            errors.push(TypeCheckError::DuplicateFuncStructName(
-                name.clone(),
-                func.name_span,
+    let (mut if_body, mut errs) =
+        if let Some((name, ttype, is_mut)) = null_checked_variable(context, &if_else.condition) {
+            context.scope_stack.push_scope(None);
+            context
+                .scope_stack
+                .add_narrowed_variable(&name, ttype, is_mut);
+            let checked_if_body = typecheck_block_expression(context, &if_else.if_body);
+            context.scope_stack.pop_scope();
+            checked_if_body
+        } else {
+            typecheck_block_expression(context, &if_else.if_body)
+        };
+    errors.append(&mut errs);
+
+    let Some(ref else_body) = if_else.else_body else {
+        if !matches!(if_body.ttype, Type::Unit | Type::Never | Type::Incomplete) {
+            errors.push(TypeCheckError::IfWithoutElse(
+                if_body.ttype.clone(),
+                if_else.span,
             ));
-            continue;
         }
 
-        context.known_functions.insert(
-            name,
-            Function {
-                parameters: func.parameters.clone(),
-                return_type: func.return_type.clone(),
+        let if_expr = CheckedIfExpr {
+            condition: Box::new(checked_condition),
+            if_body,
+            else_body: CheckedBlockExpression {
+                body: CheckedBlock { statements: vec![] },
+                value: None,
+                ttype: Type::Unit,
             },
-        );
-    }
-    for r#struct in &program.structs {
-        match r#struct {
-            ParsedStruct::Opaque(name, name_span) => {
-                if context.known_functions.contains_key(name) {
-                    errors.push(TypeCheckError::DuplicateFuncStructName(
-                        name.clone(),
-                        *name_span,
-                    ));
-                    continue;
-                }
+            ttype: Type::Unit,
+        };
+        return (if_expr, errors);
+    };
 
-                context.known_structs.insert(
-                    name.clone(),
-                    Struct {
-                        name: name.clone(),
-                        fields: vec![],
-                        is_opaque: true,
-                    },
-                );
+    let (mut else_body, mut errs) = typecheck_block_expression(context, else_body);
+    errors.append(&mut errs);
+
+    let ttype = match (&if_body.ttype, &else_body.ttype) {
+        (Type::Incomplete, _) | (_, Type::Incomplete) => Type::Incomplete,
+        (Type::Never, ttype) | (ttype, Type::Never) => ttype.clone(),
+        (if_type, else_type) if if_type.matches(else_type) => {
+            // The branch of a more specific type decides, as for `if c { 1 } else { x }`
+            if matches!(
+                if_type,
+                Type::GenericInt | Type::GenericEmptyArray | Type::Null
+            ) {
+                else_type.clone()
+            } else {
+                if_type.clone()
             }
-            ParsedStruct::Transparent(name, name_span, fields) => {
-                if context.known_functions.contains_key(name) {
-                    errors.push(TypeCheckError::DuplicateFuncStructName(
-                        name.clone(),
-                        *name_span,
-                    ));
-                    continue;
-                }
+        }
+        (if_type, else_type) => {
+            errors.push(TypeCheckError::IncompatibleIfBranches {
+                if_type: if_type.clone(),
+                else_type: else_type.clone(),
+                span: if_else.span,
+            });
+            if_type.clone()
+        }
+    };
+    coerce_block_to(&mut if_body, &ttype);
+    coerce_block_to(&mut else_body, &ttype);
+
+    let if_expr = CheckedIfExpr {
+        condition: Box::new(checked_condition),
+        if_body,
+        else_body,
+        ttype,
+    };
+    (if_expr, errors)
+}
 
-                context.known_structs.insert(
-                    name.clone(),
-                    Struct {
-                        name: name.clone(),
-                        fields: fields.clone(),
-                        is_opaque: false,
-                    },
-                );
-            }
-        };
-    }
+/// A `while` loop with an `else` block is lowered to a loop setting a flag once its body runs,
+/// followed by an `if` running the `else` block when the flag is still unset.
+fn typecheck_while_loop(
+    context: &mut Context,
+    while_loop: &ParsedWhileLoop,
+) -> (Vec<CheckedStatement>, Vec<TypeCheckError>) {
+    let mut errors = vec![];
 
-    let extern_functions = program
-        .extern_functions
-        .iter()
-        .map(|func| {
-            let mut seen_param_names: Vec<&str> = vec![];
+    let (checked_condition, mut errs) = typecheck_expression(context, &while_loop.condition);
+    errors.append(&mut errs);
 
-            for param in &func.parameters {
-                if !context.type_is_defined(&param.ttype) {
-                    errors.push(TypeCheckError::UnknownType(
-                        param.ttype.to_str(),
-                        param.type_span,
-                    ));
-                }
+    if checked_condition.ttype() != Type::Bool {
+        errors.push(TypeCheckError::WrongConditionType(
+            while_loop.condition.span(),
+            checked_condition.ttype(),
+        ));
+    }
 
-                if seen_param_names.contains(&param.name.as_str()) {
-                    errors.push(TypeCheckError::DuplicateParameterName(
-                        param.name.clone(),
-                        param.name_span,
-                    ));
-                }
+    let (mut checked_body, mut errs) =
+        typecheck_loop_body(context, &while_loop.body, &while_loop.label);
+    errors.append(&mut errs);
 
-                seen_param_names.push(&param.name);
-            }
+    let Some(ref else_body) = while_loop.else_body else {
+        let checked_loop = CheckedWhileLoop {
+            condition: checked_condition,
+            body: checked_body,
+        };
+        return (vec![CheckedStatement::WhileLoop(checked_loop)], errors);
+    };
 
-            if !context.type_is_defined(&func.return_type) {
-                errors.push(TypeCheckError::UnknownType(
-                    func.return_type.to_str(),
-                    func.return_type_span,
-                ));
-            }
+    let (checked_else_body, mut errs) = typecheck_block(context, else_body);
+    errors.append(&mut errs);
+
+    // Not a valid identifier, so it can't clash with the variables of the program
+    let flag_name = format!("while.{}", while_loop.condition.span().start);
+    let flag = || CheckedExpression::Variable(flag_name.clone(), Type::Bool, true);
+    let bool_literal = |value| CheckedExpression::Literal(CheckedLiteral::Bool(value, Type::Bool));
+
+    checked_body.statements.insert(
+        0,
+        CheckedStatement::Expression(CheckedExpression::Assignment(
+            Box::new(flag()),
+            Box::new(bool_literal(true)),
+        )),
+    );
+    let statements = vec![
+        CheckedStatement::LetAssign(flag_name.clone(), bool_literal(false)),
+        CheckedStatement::WhileLoop(CheckedWhileLoop {
+            condition: checked_condition,
+            body: checked_body,
+        }),
+        CheckedStatement::IfElse(CheckedIfElse {
+            condition: CheckedExpression::Not(Box::new(flag())),
+            if_body: checked_else_body,
+            else_body: CheckedBlock { statements: vec![] },
+        }),
+    ];
+    (statements, errors)
+}
 
-            CheckedExternFunction {
-                name: func.name.clone(),
-                parameters: func.parameters.clone(),
-                return_type: func.return_type.clone(),
+/// The variable a condition like `ptr != null` proves not to be null, along with the non-null
+/// pointer type it has inside the body of the `if` and whether it is mutable. Only non-null
+/// pointers can be assigned to the narrowed variable.
+fn null_checked_variable(
+    context: &Context,
+    condition: &ParsedExpression,
+) -> Option<(String, Type, bool)> {
+    let name = match condition {
+        ParsedExpression::CompareOp(lhs, rhs, CompareOperation::NotEqual) => {
+            match (lhs.as_ref(), rhs.as_ref()) {
+                (
+                    ParsedExpression::Variable(name, _),
+                    ParsedExpression::Literal(Literal::Null(_)),
+                )
+                | (
+                    ParsedExpression::Literal(Literal::Null(_)),
+                    ParsedExpression::Variable(name, _),
+                ) => name,
+                _ => return None,
             }
-        })
-        .collect();
+        }
+        _ => return None,
+    };
 
-    let functions = program
-        .functions
-        .iter()
-        .map(|func| {
-            let mut seen_param_names: Vec<&str> = vec![];
+    match context.scope_stack.get_variable_type(name)? {
+        KnownVariable {
+            ttype: Type::NullablePointer(element_type, is_mut_pointer),
+            is_mut,
+            constant: None,
+            ..
+        } => Some((
+            name.clone(),
+            Type::Pointer(element_type.clone(), *is_mut_pointer),
+            *is_mut,
+        )),
+        _ => None,
+    }
+}
 
-            for param in &func.parameters {
-                if !context.type_is_defined(&param.ttype) {
-                    errors.push(TypeCheckError::UnknownType(
-                        param.ttype.to_str(),
-                        param.type_span,
-                    ));
+/// A `let` binding a single name is checked into a single statement. Destructuring ones store
+/// the value in a variable first, followed by a statement for every name bound from it.
+fn typecheck_let_assign(
+    context: &mut Context,
+    let_assign: &ParsedLetAssign,
+) -> (Vec<CheckedStatement>, Vec<TypeCheckError>) {
+    let (mut checked_value, mut errors) = typecheck_expression(context, &let_assign.value);
+
+    let var_type = if let Some((ref annotation, annotation_span)) = let_assign.type_annotation {
+        let annotated = context.resolve_type(annotation);
+        checked_value = coerce_to(checked_value, &annotated);
+        // Cyclic aliases resolve to an incomplete type that was already reported
+        if !context.type_is_defined(&annotated) {
+            errors.push(context.unknown_type_error(annotation, annotation_span));
+        } else if annotated != Type::Incomplete && !annotated.matches(&checked_value.ttype()) {
+            let error = match (&annotated, checked_value.ttype()) {
+                (Type::Array(_, expected_size), Type::Array(_, actual_size))
+                    if *expected_size != actual_size =>
+                {
+                    TypeCheckError::ArraySizeMismatch(
+                        *expected_size,
+                        actual_size,
+                        annotation_span,
+                        let_assign.value.span(),
+                    )
                 }
+                (_, actual) => TypeCheckError::TypeAnnotationMismatch(
+                    annotated.clone(),
+                    actual,
+                    annotation_span,
+                    let_assign.value.span(),
+                ),
+            };
+            errors.push(error);
+        }
+        annotated
+    } else {
+        checked_value.ttype()
+    };
 
-                if seen_param_names.contains(&param.name.as_str()) {
-                    errors.push(TypeCheckError::DuplicateParameterName(
-                        param.name.clone(),
-                        param.name_span,
-                    ));
-                }
+    if let Some(struct_name) = context.unsized_opaque_struct(&var_type) {
+        errors.push(TypeCheckError::UnsizedOpaqueStruct(
+            struct_name,
+            let_assign.pattern.span(),
+        ));
+    }
 
-                seen_param_names.push(&param.name);
+    let (name, name_span) = match let_assign.pattern {
+        ParsedLetPattern::Ident(ref name, name_span) => (name.clone(), name_span),
+        ref pattern => {
+            // Not a valid identifier, so it can't clash with the variables of the program
+            (format!("let.{}", pattern.span().start), pattern.span())
+        }
+    };
+    errors.extend(declare_let_binding(
+        context,
+        &name,
+        name_span,
+        var_type.clone(),
+        let_assign.is_mut,
+    ));
+    if let CheckedExpression::Literal(CheckedLiteral::Struct(union_literal, r#struct, _)) =
+        &checked_value
+    {
+        if r#struct.is_union && !let_assign.is_mut && union_literal.fields.len() == 1 {
+            let field_name = union_literal.fields.keys().next().unwrap().clone();
+            context
+                .scope_stack
+                .set_written_union_field(&name, field_name);
+        }
+    }
+    let mut statements = vec![CheckedStatement::LetAssign(name.clone(), checked_value)];
+    let value = || CheckedExpression::Variable(name.clone(), var_type.clone(), false);
+
+    match (&let_assign.pattern, &var_type) {
+        (ParsedLetPattern::Ident(..), _) => {}
+        (ParsedLetPattern::Tuple(elements, _), Type::Tuple(element_types))
+            if elements.len() == element_types.len() =>
+        {
+            for (index, ((binding, binding_span), element_type)) in
+                elements.iter().zip(element_types).enumerate()
+            {
+                if binding == "_" {
+                    continue;
+                }
+                let element = CheckedExpression::TupleIndex(CheckedTupleIndex {
+                    object: Box::new(value()),
+                    index,
+                    ttype: element_type.clone(),
+                });
+                errors.extend(declare_let_binding(
+                    context,
+                    binding,
+                    *binding_span,
+                    element_type.clone(),
+                    let_assign.is_mut,
+                ));
+                statements.push(CheckedStatement::LetAssign(binding.clone(), element));
             }
-
-            if !context.type_is_defined(&func.return_type) {
-                errors.push(TypeCheckError::UnknownType(
-                    func.return_type.to_str(),
-                    func.return_type_span,
+        }
+        (
+            ParsedLetPattern::Struct {
+                name: struct_name,
+                fields,
+                ..
+            },
+            Type::UserDefined(type_name),
+        ) if struct_name == type_name
+            || context
+                .struct_instances
+                .get(type_name)
+                .is_some_and(|(generic_struct, _)| generic_struct == struct_name) =>
+        {
+            let r#struct = context.known_structs[type_name].clone();
+            for (field_name, binding, binding_span) in fields {
+                let field_type = match r#struct.get_field(field_name) {
+                    Some(field_type) => {
+                        if r#struct.is_union && !context.in_unsafe {
+                            errors.push(TypeCheckError::UnsafeOperationOutsideUnsafeBlock(
+                                *binding_span,
+                            ));
+                        }
+                        field_type.clone()
+                    }
+                    None => {
+                        errors.push(TypeCheckError::FieldAccessInvalidField(
+                            var_type.clone(),
+                            field_name.clone(),
+                            *binding_span,
+                        ));
+                        Type::Incomplete
+                    }
+                };
+                let field = CheckedExpression::FieldAccess(
+                    CheckedFieldAccess {
+                        object: Box::new(value()),
+                        object_is_ptr: false,
+                        field_name: field_name.clone(),
+                        is_mut: false,
+                    },
+                    r#struct.clone(),
+                    field_type.clone(),
+                );
+                errors.extend(declare_let_binding(
+                    context,
+                    binding,
+                    *binding_span,
+                    field_type,
+                    let_assign.is_mut,
                 ));
+                statements.push(CheckedStatement::LetAssign(binding.clone(), field));
             }
-
-            context.scope_stack.push_scope(Some(func.name.clone()));
-            context.current_function = Some(func);
-
-            for param in &func.parameters {
-                context
-                    .scope_stack
-                    .add_variable(&param.name, param.ttype.clone(), false);
+        }
+        (pattern, _) => {
+            match pattern {
+                ParsedLetPattern::Struct {
+                    name: struct_name,
+                    name_span,
+                    ..
+                } if !context.known_structs.contains_key(struct_name)
+                    && !context.generic_structs.contains_key(struct_name) =>
+                {
+                    errors.push(TypeCheckError::UnknownType(struct_name.clone(), *name_span));
+                }
+                // The value has already been reported
+                _ if var_type == Type::Incomplete => {}
+                _ => errors.push(TypeCheckError::LetPatternMismatch {
+                    pattern: match pattern {
+                        ParsedLetPattern::Tuple(elements, _) => {
+                            format!("a tuple of {} elements", elements.len())
+                        }
+                        ParsedLetPattern::Struct {
+                            name: struct_name, ..
+                        } => format!("struct `{struct_name}`"),
+                        ParsedLetPattern::Ident(..) => unreachable!(),
+                    },
+                    value_type: var_type.clone(),
+                    span: pattern.span(),
+                }),
             }
 
-            let (body, mut errs) = typecheck_block(&mut context, &func.body);
-            errors.append(&mut errs);
-
-            context.current_function.take();
-            context.scope_stack.pop_scope();
-
-            CheckedFunction {
-                name: func.name.clone(),
-                parameters: func.parameters.clone(),
-                return_type: func.return_type.clone(),
-                body,
+            // Keep the names around so their uses aren't reported as well
+            for (binding, binding_span) in pattern.bindings() {
+                errors.extend(declare_let_binding(
+                    context,
+                    binding,
+                    binding_span,
+                    Type::Incomplete,
+                    let_assign.is_mut,
+                ));
             }
-        })
-        .collect();
+        }
+    }
 
-    (
-        CheckedProgram {
-            functions,
-            extern_functions,
-            structs: context.known_structs.into_values().collect(),
-        },
-        errors,
-    )
+    (statements, errors)
 }
 
-fn typecheck_block(
+fn declare_let_binding(
     context: &mut Context,
-    block: &ParsedBlock,
-) -> (CheckedBlock, Vec<TypeCheckError>) {
-    let mut errors = vec![];
-
-    context.scope_stack.push_scope(None);
-    let statements = block
-        .statements
-        .iter()
-        .map(|stmt| {
-            let (checked_stmt, mut errs) = typecheck_statement(context, stmt);
-            errors.append(&mut errs);
-            checked_stmt
-        })
-        .collect();
-    context.scope_stack.pop_scope();
-
-    (CheckedBlock { statements }, errors)
+    name: &str,
+    span: Span,
+    ttype: Type,
+    is_mut: bool,
+) -> Option<TypeCheckError> {
+    context
+        .scope_stack
+        .add_variable(name, ttype, is_mut)
+        .then(|| TypeCheckError::DuplicateVariableName(name.to_string(), span))
 }
 
 fn typecheck_statement(
@@ -1005,47 +5435,71 @@ fn typecheck_statement(
             let (checked_expr, errors) = typecheck_expression(context, expr);
             (CheckedStatement::Expression(checked_expr), errors)
         }
-        ParsedStatement::LetAssign(let_assign) => {
-            let (checked_value, mut errors) = typecheck_expression(context, &let_assign.value);
-            if context.scope_stack.add_variable(
-                &let_assign.name,
-                checked_value.ttype(),
-                let_assign.is_mut,
-            ) {
-                errors.push(TypeCheckError::DuplicateVariableName(
-                    let_assign.name.clone(),
-                    let_assign.name_span,
-                ));
-            }
+        ParsedStatement::LetAssign(_) => {
+            unreachable!(
+                "`let` can bind several variables and is checked by `typecheck_let_assign`"
+            )
+        }
+        ParsedStatement::Const(const_decl) => {
+            let errors = typecheck_const_decl(context, const_decl);
+            (CheckedStatement::Const, errors)
+        }
+        ParsedStatement::Match(r#match) => {
+            let (checked_match, errors) = typecheck_match(context, r#match);
+            (CheckedStatement::Match(checked_match), errors)
+        }
+        ParsedStatement::Switch(switch) => {
+            let (checked_switch, errors) = typecheck_switch(context, switch);
+            (CheckedStatement::Switch(checked_switch), errors)
+        }
+        ParsedStatement::WhileLoop(_) => unreachable!("checked by `typecheck_while_loop`"),
+        ParsedStatement::Defer(..) => unreachable!("checked by `typecheck_defer`"),
+        ParsedStatement::Unsafe(body, _) => {
+            let (checked_body, errors) = typecheck_unsafe_block(context, body);
             (
-                CheckedStatement::LetAssign(let_assign.name.clone(), checked_value),
+                CheckedStatement::Expression(CheckedExpression::Block(checked_body)),
                 errors,
             )
         }
-        ParsedStatement::WhileLoop(while_loop) => {
-            let mut errors = vec![];
-
-            let (checked_condition, mut errs) =
-                typecheck_expression(context, &while_loop.condition);
-            errors.append(&mut errs);
-
-            if checked_condition.ttype() != Type::Bool {
-                errors.push(TypeCheckError::WrongConditionType(
-                    while_loop.condition.span(),
-                    checked_condition.ttype(),
-                ));
-            }
-
-            let (checked_body, mut errs) = typecheck_block(context, &while_loop.body);
-            errors.append(&mut errs);
+        ParsedStatement::Loop(body, label, _) => {
+            let (checked_body, errors) = typecheck_loop_body(context, body, label);
+            (CheckedStatement::Loop(checked_body), errors)
+        }
+        ParsedStatement::WhileLet {
+            pattern,
+            value,
+            body,
+            label,
+            span,
+        } => {
+            // `loop { match value { pattern => body, _ => { break; } } }`
+            let break_block = ParsedBlock {
+                statements: vec![ParsedStatement::Break(None, Span::synthetic())],
+                tail_expr: None,
+            };
+            let r#match = ParsedMatch {
+                scrutinee: Box::new(value.clone()),
+                arms: vec![
+                    ParsedMatchArm {
+                        pattern: pattern.clone(),
+                        body: ParsedExpression::Block(Box::new(body.clone()), *span),
+                        span: *span,
+                    },
+                    ParsedMatchArm {
+                        pattern: ParsedPattern::Wildcard(Span::synthetic()),
+                        body: ParsedExpression::Block(Box::new(break_block), Span::synthetic()),
+                        span: Span::synthetic(),
+                    },
+                ],
+                span: *span,
+            };
+            let loop_body = ParsedBlock {
+                statements: vec![ParsedStatement::Match(r#match)],
+                tail_expr: None,
+            };
+            let (checked_body, errors) = typecheck_loop_body(context, &loop_body, label);
 
-            (
-                CheckedStatement::WhileLoop(CheckedWhileLoop {
-                    condition: checked_condition,
-                    body: checked_body,
-                }),
-                errors,
-            )
+            (CheckedStatement::Loop(checked_body), errors)
         }
         ParsedStatement::IfElse(if_else) => {
             let mut errors = vec![];
@@ -1060,7 +5514,19 @@ fn typecheck_statement(
                 ));
             }
 
-            let (checked_if_body, mut errs) = typecheck_block(context, &if_else.if_body);
+            let (checked_if_body, mut errs) = if let Some((name, ttype, is_mut)) =
+                null_checked_variable(context, &if_else.condition)
+            {
+                context.scope_stack.push_scope(None);
+                context
+                    .scope_stack
+                    .add_narrowed_variable(&name, ttype, is_mut);
+                let checked_if_body = typecheck_block(context, &if_else.if_body);
+                context.scope_stack.pop_scope();
+                checked_if_body
+            } else {
+                typecheck_block(context, &if_else.if_body)
+            };
             errors.append(&mut errs);
 
             let checked_else_body = if let Some(ref else_body) = if_else.else_body {
@@ -1087,13 +5553,17 @@ fn typecheck_statement(
                 typecheck_expression(context, &for_in.iterable_value);
             errors.append(&mut errs);
 
-            let elem_type = if let Type::Array(elem_type, _) = checked_iterable.ttype() {
-                *elem_type
+            let iterable_type = checked_iterable.ttype();
+            let elem_type = if let Some(elem_type) = iterable_type.iterable_element_type() {
+                elem_type
             } else {
-                errors.push(TypeCheckError::InvalidIterableInForIn(
-                    checked_iterable.ttype(),
-                    for_in.iterable_value.span(),
-                ));
+                // An incomplete type was already reported where it came from
+                if iterable_type != Type::Incomplete {
+                    errors.push(TypeCheckError::NotIterable(
+                        iterable_type,
+                        for_in.iterable_value.span(),
+                    ));
+                }
                 Type::Incomplete
             };
 
@@ -1115,13 +5585,14 @@ fn typecheck_statement(
                     .add_variable(index_var_name, Type::Int, false)
                 {
                     errors.push(TypeCheckError::DuplicateVariableName(
-                        for_in.elem_var_name.clone(),
+                        index_var_name.clone(),
                         index_var_name_span,
                     ));
                 }
             }
 
-            let (checked_body, mut errs) = typecheck_block(context, &for_in.body);
+            let (checked_body, mut errs) =
+                typecheck_loop_body(context, &for_in.body, &for_in.label);
             errors.append(&mut errs);
 
             context.scope_stack.pop_scope();
@@ -1139,33 +5610,405 @@ fn typecheck_statement(
         }
         ParsedStatement::Return(return_value) => {
             let (checked_return_value, mut errors) = typecheck_expression(context, return_value);
+            let (checked_return_value, mut errs) =
+                check_return_value(context, checked_return_value, return_value.span());
+            errors.append(&mut errs);
+
+            (CheckedStatement::Return(checked_return_value), errors)
+        }
+        ParsedStatement::Break(label, span) | ParsedStatement::Continue(label, span) => {
+            let is_break = matches!(statement, ParsedStatement::Break(..));
+            let mut errors = vec![];
+            let loop_depth = if context.loops.is_empty() {
+                errors.push(if is_break {
+                    TypeCheckError::BreakOutsideLoop(*span)
+                } else {
+                    TypeCheckError::ContinueOutsideLoop(*span)
+                });
+                0
+            } else if let Some(loop_depth) = loop_target(context, label) {
+                loop_depth
+            } else {
+                let (label, label_span) = label.clone().expect("unlabeled loops always exist");
+                errors.push(TypeCheckError::UnknownLoopLabel(label, label_span));
+                0
+            };
+
+            if is_break {
+                (CheckedStatement::Break(loop_depth), errors)
+            } else {
+                (CheckedStatement::Continue(loop_depth), errors)
+            }
+        }
+    }
+}
+
+/// Checks a value returned from the current function against its return type.
+fn check_return_value(
+    context: &mut Context,
+    return_value: CheckedExpression,
+    span: Span,
+) -> (CheckedExpression, Vec<TypeCheckError>) {
+    let mut errors = vec![];
+
+    let function_return_type =
+        &context.resolve_type(&context.current_function.unwrap().return_type);
+    let return_value = coerce_to(return_value, function_return_type);
+    // Values that failed to check have already been reported
+    if !function_return_type.matches(&return_value.ttype())
+        && return_value.ttype() != Type::Incomplete
+    {
+        errors.push(TypeCheckError::InvalidReturnType(
+            return_value.ttype(),
+            function_return_type.clone(),
+            span,
+        ));
+    }
+
+    (return_value, errors)
+}
+
+/// Typecheck an expression and return the type of the expression.
+fn typecheck_match(
+    context: &mut Context,
+    r#match: &ParsedMatch,
+) -> (CheckedMatch, Vec<TypeCheckError>) {
+    let (checked_scrutinee, mut errors) = typecheck_expression(context, &r#match.scrutinee);
+    let scrutinee_type = checked_scrutinee.ttype();
+
+    let mut has_wildcard = false;
+    let mut covered_variants = vec![];
+    let mut covered_bools = vec![];
+    let mut arm_type: Option<Type> = None;
+
+    let mut arms = vec![];
+    for arm in &r#match.arms {
+        // Bindings introduced by the pattern are only visible in the arm's body
+        context.scope_stack.push_scope(None);
+
+        let pattern = match &arm.pattern {
+            ParsedPattern::Wildcard(_) => {
+                has_wildcard = true;
+                CheckedPattern::Wildcard
+            }
+            ParsedPattern::Literal(literal) => {
+                let checked_literal = match *literal {
+                    Literal::Char(value, _) => CheckedLiteral::Char(value, Type::Char),
+                    Literal::Int(value, _) => CheckedLiteral::Int(value, Type::GenericInt),
+                    Literal::Bool(value, _) => {
+                        covered_bools.push(value);
+                        CheckedLiteral::Bool(value, Type::Bool)
+                    }
+                    _ => unreachable!("only char, int and bool literals are parsed as patterns"),
+                };
+                let checked_literal = CheckedExpression::Literal(checked_literal);
+
+                if !checked_literal.ttype().matches(&scrutinee_type) {
+                    errors.push(TypeCheckError::PatternTypeMismatch(
+                        checked_literal.ttype(),
+                        scrutinee_type.clone(),
+                        arm.pattern.span(),
+                    ));
+                }
+
+                CheckedPattern::Literal(checked_literal)
+            }
+            ParsedPattern::EnumVariant(pattern) => {
+                if let Some(r#enum) = context.known_enums.get(&pattern.enum_name).cloned() {
+                    let pattern_type = Type::UserDefined(r#enum.name.clone());
+                    if !pattern_type.matches(&scrutinee_type) {
+                        errors.push(TypeCheckError::PatternTypeMismatch(
+                            pattern_type,
+                            scrutinee_type.clone(),
+                            pattern.span,
+                        ));
+                    }
+
+                    if let Some((variant_index, value_type)) =
+                        r#enum.get_variant(&pattern.variant_name)
+                    {
+                        covered_variants.push(variant_index);
+
+                        // The value of a variant may be ignored by leaving out the binding
+                        let binding = match (value_type, &pattern.binding) {
+                            (Some(value_type), Some((binding_name, _))) => {
+                                context.scope_stack.add_variable(
+                                    binding_name,
+                                    value_type.clone(),
+                                    false,
+                                );
+                                Some(binding_name.clone())
+                            }
+                            (None, Some((_, binding_span))) => {
+                                errors.push(TypeCheckError::EnumVariantUnexpectedValue(
+                                    r#enum.name.clone(),
+                                    pattern.variant_name.clone(),
+                                    *binding_span,
+                                ));
+                                None
+                            }
+                            (_, None) => None,
+                        };
+
+                        CheckedPattern::EnumVariant(CheckedEnumVariantPattern {
+                            r#enum: r#enum.clone(),
+                            variant_index,
+                            binding,
+                        })
+                    } else {
+                        errors.push(TypeCheckError::UnknownEnumVariant(
+                            r#enum.name.clone(),
+                            pattern.variant_name.clone(),
+                            pattern.variant_name_span,
+                        ));
+                        CheckedPattern::Wildcard
+                    }
+                } else {
+                    errors.push(TypeCheckError::UnknownType(
+                        pattern.enum_name.clone(),
+                        pattern.enum_name_span,
+                    ));
+                    CheckedPattern::Wildcard
+                }
+            }
+        };
+
+        let (checked_body, mut errs) = typecheck_expression(context, &arm.body);
+        errors.append(&mut errs);
+
+        context.scope_stack.pop_scope();
 
-            let function_return_type = &context.current_function.unwrap().return_type;
-            if !function_return_type.matches(&checked_return_value.ttype()) {
-                errors.push(TypeCheckError::InvalidReturnType(
-                    checked_return_value.ttype(),
-                    function_return_type.clone(),
-                    return_value.span(),
+        // Arms that never reach their end, like `{ break; }`, fit in with any other arm
+        match arm_type {
+            _ if checked_body.ttype() == Type::Never => {}
+            Some(ref expected) if !expected.matches(&checked_body.ttype()) => {
+                errors.push(TypeCheckError::MatchArmTypeMismatch(
+                    checked_body.ttype(),
+                    expected.clone(),
+                    arm.body.span(),
                 ));
             }
+            Some(_) => {}
+            None => arm_type = Some(checked_body.ttype()),
+        }
 
-            (CheckedStatement::Return(checked_return_value), errors)
+        arms.push(CheckedMatchArm {
+            pattern,
+            body: checked_body,
+        });
+    }
+
+    if !has_wildcard {
+        let missing_patterns: Vec<String> = match scrutinee_type {
+            Type::UserDefined(ref name) if context.known_enums.contains_key(name) => context
+                .known_enums[name]
+                .variants
+                .iter()
+                .enumerate()
+                .filter(|(variant_index, _)| !covered_variants.contains(variant_index))
+                .map(|(_, (variant_name, value_type))| {
+                    let value = if value_type.is_some() { "(_)" } else { "" };
+                    format!("{}::{}{}", name, variant_name, value)
+                })
+                .collect(),
+            Type::Bool => [true, false]
+                .iter()
+                .filter(|value| !covered_bools.contains(value))
+                .map(|value| value.to_string())
+                .collect(),
+            // The type of the scrutinee is already reported as erroneous
+            Type::Incomplete => vec![],
+            _ => vec!["_".to_string()],
+        };
+
+        if !missing_patterns.is_empty() {
+            errors.push(TypeCheckError::NonExhaustiveMatch(
+                missing_patterns,
+                r#match.scrutinee.span(),
+            ));
+        }
+    }
+
+    (
+        CheckedMatch {
+            scrutinee: checked_scrutinee,
+            arms,
+        },
+        errors,
+    )
+}
+
+fn typecheck_switch(
+    context: &mut Context,
+    switch: &ParsedSwitch,
+) -> (CheckedSwitch, Vec<TypeCheckError>) {
+    let (checked_scrutinee, mut errors) = typecheck_expression(context, &switch.scrutinee);
+    let scrutinee_type = checked_scrutinee.ttype();
+
+    let r#enum = match scrutinee_type {
+        Type::UserDefined(ref name) => context.known_enums.get(name).cloned(),
+        _ => None,
+    };
+    let is_switchable = r#enum.is_some()
+        || scrutinee_type.is_integer_type()
+        || matches!(scrutinee_type, Type::Char | Type::Incomplete);
+    if !is_switchable {
+        errors.push(TypeCheckError::InvalidSwitchType(
+            scrutinee_type.clone(),
+            switch.scrutinee.span(),
+        ));
+    }
+
+    let mut handled_values = vec![];
+    let mut cases = vec![];
+    for (values, body) in &switch.cases {
+        let mut case_values = vec![];
+        for value in values {
+            let (case_value, mut errs) = switch_case_value(context, value, &scrutinee_type);
+            errors.append(&mut errs);
+
+            match case_value {
+                Some(case_value) if handled_values.contains(&case_value) => {
+                    errors.push(TypeCheckError::DuplicateSwitchCase(value.span()));
+                }
+                Some(case_value) => {
+                    handled_values.push(case_value);
+                    case_values.push(case_value);
+                }
+                None => {}
+            }
+        }
+
+        let (checked_body, mut errs) = typecheck_block(context, body);
+        errors.append(&mut errs);
+        cases.push((case_values, checked_body));
+    }
+
+    let default = match switch.default {
+        Some(ref default) => {
+            let (checked_default, mut errs) = typecheck_block(context, default);
+            errors.append(&mut errs);
+            checked_default
+        }
+        None => {
+            let missing_variants: Vec<_> = match r#enum {
+                Some(ref r#enum) => r#enum
+                    .variants
+                    .iter()
+                    .enumerate()
+                    .filter(|(variant_index, _)| !handled_values.contains(&(*variant_index as i64)))
+                    .map(|(_, (variant_name, _))| format!("{}::{}", r#enum.name, variant_name))
+                    .collect(),
+                None => vec![],
+            };
+            // Integers and chars have too many values to handle all of them one by one
+            let is_exhaustive = match scrutinee_type {
+                _ if r#enum.is_some() => missing_variants.is_empty(),
+                Type::Incomplete => true,
+                _ => !is_switchable,
+            };
+            if !is_exhaustive {
+                errors.push(TypeCheckError::NonExhaustiveSwitch(
+                    scrutinee_type.clone(),
+                    missing_variants,
+                    switch.scrutinee.span(),
+                ));
+            }
+            CheckedBlock { statements: vec![] }
+        }
+    };
+
+    (
+        CheckedSwitch {
+            scrutinee: checked_scrutinee,
+            cases,
+            default,
+        },
+        errors,
+    )
+}
+
+/// The constant value a case of a `switch` handles, the index of the variant for enums.
+fn switch_case_value(
+    context: &mut Context,
+    value: &ParsedExpression,
+    scrutinee_type: &Type,
+) -> (Option<i64>, Vec<TypeCheckError>) {
+    // Variants are named without their value, which a case can't compare
+    if let ParsedExpression::EnumVariant(variant) = value {
+        let Some(r#enum) = context.known_enums.get(&variant.enum_name) else {
+            let error =
+                TypeCheckError::UnknownType(variant.enum_name.clone(), variant.enum_name_span);
+            return (None, vec![error]);
+        };
+
+        let variant_type = Type::UserDefined(r#enum.name.clone());
+        let error = if variant.value.is_some() {
+            TypeCheckError::NonConstantSwitchCase(variant.span)
+        } else if !variant_type.matches(scrutinee_type) && *scrutinee_type != Type::Incomplete {
+            TypeCheckError::PatternTypeMismatch(variant_type, scrutinee_type.clone(), variant.span)
+        } else if let Some((variant_index, _)) = r#enum.get_variant(&variant.variant_name) {
+            return (Some(variant_index as i64), vec![]);
+        } else {
+            TypeCheckError::UnknownEnumVariant(
+                r#enum.name.clone(),
+                variant.variant_name.clone(),
+                variant.variant_name_span,
+            )
+        };
+        return (None, vec![error]);
+    }
+
+    let (checked_value, mut errors) = typecheck_expression(context, value);
+    let value_type = checked_value.ttype();
+    if !value_type.matches(scrutinee_type)
+        && !matches!(value_type, Type::Incomplete)
+        && *scrutinee_type != Type::Incomplete
+    {
+        errors.push(TypeCheckError::PatternTypeMismatch(
+            value_type,
+            scrutinee_type.clone(),
+            value.span(),
+        ));
+        return (None, errors);
+    }
+
+    match const_eval(&checked_value) {
+        Some(ConstValue::Int(value)) => (Some(i64::from(value)), errors),
+        Some(ConstValue::Char(value)) => (Some(i64::from(u32::from(value))), errors),
+        // Constants like `true` only match a scrutinee of a type that can't be switched on, which
+        // is reported already
+        Some(ConstValue::Bool(_) | ConstValue::String(_)) => (None, errors),
+        None => {
+            errors.push(TypeCheckError::NonConstantSwitchCase(value.span()));
+            (None, errors)
         }
     }
 }
 
-/// Typecheck an expression and return the type of the expression.
 fn typecheck_expression(
     context: &mut Context,
     expression: &ParsedExpression,
+) -> (CheckedExpression, Vec<TypeCheckError>) {
+    let (checked_expression, errors) = check_expression(context, expression);
+    context
+        .expression_types
+        .push((expression.span(), checked_expression.ttype()));
+    (checked_expression, errors)
+}
+
+fn check_expression(
+    context: &mut Context,
+    expression: &ParsedExpression,
 ) -> (CheckedExpression, Vec<TypeCheckError>) {
     match expression {
         ParsedExpression::Literal(literal) => match literal {
             Literal::String(value, _) => (
-                CheckedExpression::Literal(CheckedLiteral::String(
-                    value.clone(),
-                    Type::Pointer(Box::new(Type::CChar), false),
-                )),
+                CheckedExpression::Literal(CheckedLiteral::String(value.clone(), Type::Str)),
+                vec![],
+            ),
+            Literal::Char(value, _) => (
+                CheckedExpression::Literal(CheckedLiteral::Char(*value, Type::Char)),
                 vec![],
             ),
             Literal::Int(value, _) => (
@@ -1176,10 +6019,14 @@ fn typecheck_expression(
                 CheckedExpression::Literal(CheckedLiteral::Bool(*value, Type::Bool)),
                 vec![],
             ),
+            Literal::Null(_) => (
+                CheckedExpression::Literal(CheckedLiteral::Null(Type::Null)),
+                vec![],
+            ),
             Literal::Struct(struct_literal, _) => {
                 let mut errors = vec![];
 
-                let checked_fields: HashMap<_, _> = struct_literal
+                let checked_fields: Vec<_> = struct_literal
                     .fields
                     .iter()
                     .map(|(field_name, _, field_value)| {
@@ -1190,62 +6037,151 @@ fn typecheck_expression(
                     })
                     .collect();
 
-                let r#struct =
-                    if let Some(r#struct) = context.known_structs.get(&struct_literal.name) {
+                let struct_name = if let Some(&(generic_params, fields, _)) =
+                    context.generic_structs.get(&struct_literal.name)
+                {
+                    let values: Vec<_> = checked_fields
+                        .iter()
+                        .filter_map(|(field_name, checked_field_value)| {
+                            let (_, field_type) =
+                                fields.iter().find(|(name, _)| name == field_name)?;
+                            Some((field_type, checked_field_value.ttype()))
+                        })
+                        .collect();
+                    let (type_args, mut errs) = determine_type_args(
+                        context,
+                        &struct_literal.name,
+                        generic_params,
+                        &struct_literal.type_args,
+                        &values,
+                        struct_literal.name_span,
+                    );
+                    errors.append(&mut errs);
+                    match type_args {
+                        Some(type_args) => {
+                            let name = instance_name(&struct_literal.name, &type_args);
+                            context.instantiate_generic_struct(&struct_literal.name, type_args);
+                            name
+                        }
+                        None => {
+                            let fields = checked_fields.into_iter().collect();
+                            return (
+                                CheckedExpression::Literal(CheckedLiteral::Struct(
+                                    CheckedStructLiteral {
+                                        name: struct_literal.name.clone(),
+                                        fields,
+                                    },
+                                    Struct {
+                                        name: String::new(),
+                                        fields: vec![],
+                                        is_opaque: true,
+                                        opaque_size: None,
+                                        has_eq: false,
+                                        is_union: false,
+                                    },
+                                    Type::Incomplete,
+                                )),
+                                errors,
+                            );
+                        }
+                    }
+                } else {
+                    if !struct_literal.type_args.is_empty()
+                        && context.known_structs.contains_key(&struct_literal.name)
+                    {
+                        errors.push(TypeCheckError::TypeParameterCountMismatch {
+                            name: struct_literal.name.clone(),
+                            expected: 0,
+                            found: struct_literal.type_args.len(),
+                            span: struct_literal.name_span,
+                        });
+                    }
+                    struct_literal.name.clone()
+                };
+
+                let checked_fields: HashMap<_, _> = checked_fields
+                    .into_iter()
+                    .map(|(field_name, checked_field_value)| {
+                        let checked_field_value = match context
+                            .known_structs
+                            .get(&struct_name)
+                            .and_then(|r#struct| r#struct.get_field(&field_name))
+                        {
+                            Some(field_type) => coerce_to(checked_field_value, field_type),
+                            None => checked_field_value,
+                        };
+                        (field_name, checked_field_value)
+                    })
+                    .collect();
+
+                let r#struct = if let Some(r#struct) = context.known_structs.get(&struct_name) {
+                    if r#struct.is_union {
+                        // Only one field of a union can be written at a time
+                        if struct_literal.fields.len() != 1 {
+                            errors.push(TypeCheckError::UnionLiteralFieldCount(
+                                struct_name.clone(),
+                                struct_literal.span,
+                            ));
+                        }
+                    } else {
                         for (field_name, _) in &r#struct.fields {
                             if !checked_fields.iter().any(|(name, _)| name == field_name) {
                                 errors.push(TypeCheckError::StructMissingField(
-                                    struct_literal.name.clone(),
+                                    struct_name.clone(),
                                     field_name.clone(),
                                     struct_literal.span,
                                 ));
                             }
                         }
+                    }
 
-                        for ((field_name, checked_field), (_, field_name_span, parsed_field)) in
-                            checked_fields.iter().zip(struct_literal.fields.iter())
-                        {
-                            if let Some(field_type) = r#struct.get_field(field_name) {
-                                if !checked_field.ttype().matches(field_type) {
-                                    errors.push(TypeCheckError::StructFieldWrongType(
-                                        struct_literal.name.clone(),
-                                        field_name.clone(),
-                                        checked_field.ttype(),
-                                        field_type.clone(),
-                                        parsed_field.span(),
-                                    ))
-                                }
-                            } else {
-                                errors.push(TypeCheckError::StructSuperfluousField(
-                                    struct_literal.name.clone(),
+                    for ((field_name, checked_field), (_, field_name_span, parsed_field)) in
+                        checked_fields.iter().zip(struct_literal.fields.iter())
+                    {
+                        if let Some(field_type) = r#struct.get_field(field_name) {
+                            if !checked_field.ttype().matches(field_type) {
+                                errors.push(TypeCheckError::StructFieldWrongType(
+                                    struct_name.clone(),
                                     field_name.clone(),
-                                    *field_name_span,
-                                ));
+                                    checked_field.ttype(),
+                                    field_type.clone(),
+                                    parsed_field.span(),
+                                ))
                             }
+                        } else {
+                            errors.push(TypeCheckError::StructSuperfluousField(
+                                struct_name.clone(),
+                                field_name.clone(),
+                                *field_name_span,
+                            ));
                         }
+                    }
 
-                        r#struct.clone()
-                    } else {
-                        errors.push(TypeCheckError::UnknownType(
-                            struct_literal.name.clone(),
-                            struct_literal.name_span,
-                        ));
+                    r#struct.clone()
+                } else {
+                    errors.push(TypeCheckError::UnknownType(
+                        struct_literal.name.clone(),
+                        struct_literal.name_span,
+                    ));
 
-                        Struct {
-                            name: String::new(),
-                            fields: vec![],
-                            is_opaque: true,
-                        }
-                    };
+                    Struct {
+                        name: String::new(),
+                        fields: vec![],
+                        is_opaque: true,
+                        opaque_size: None,
+                        has_eq: false,
+                        is_union: false,
+                    }
+                };
 
                 (
                     CheckedExpression::Literal(CheckedLiteral::Struct(
                         CheckedStructLiteral {
-                            name: struct_literal.name.clone(),
+                            name: struct_name.clone(),
                             fields: checked_fields,
                         },
                         r#struct,
-                        Type::UserDefined(struct_literal.name.clone()),
+                        Type::UserDefined(struct_name.clone()),
                     )),
                     errors,
                 )
@@ -1255,7 +6191,7 @@ fn typecheck_expression(
 
                 let mut checked_elements = Vec::with_capacity(array_literal.elements.len());
 
-                let array_elem_type = if let Some(first_elem) = array_literal.elements.get(0) {
+                let array_elem_type = if let Some(first_elem) = array_literal.elements.first() {
                     let (checked_elem, mut errs) = typecheck_expression(context, first_elem);
                     errors.append(&mut errs);
 
@@ -1301,42 +6237,219 @@ fn typecheck_expression(
                     errors,
                 )
             }
+            Literal::Tuple(elements, _) => {
+                let mut errors = vec![];
+
+                let checked_elements: Vec<_> = elements
+                    .iter()
+                    .map(|element| {
+                        let (checked_element, mut errs) = typecheck_expression(context, element);
+                        errors.append(&mut errs);
+                        checked_element
+                    })
+                    .collect();
+                let tuple_type = Type::Tuple(
+                    checked_elements
+                        .iter()
+                        .map(CheckedExpression::ttype)
+                        .collect(),
+                );
+
+                (
+                    CheckedExpression::Literal(CheckedLiteral::Tuple(checked_elements, tuple_type)),
+                    errors,
+                )
+            }
         },
+        ParsedExpression::FunctionCall(func_call)
+            if func_call.name == "assert_eq"
+                && context.parameter_names("assert_eq").is_none()
+                && context.scope_stack.get_variable_type("assert_eq").is_none() =>
+        {
+            typecheck_assert_eq(context, func_call)
+        }
+        ParsedExpression::FunctionCall(func_call)
+            if StrBuiltin::from_name(&func_call.name).is_some()
+                && context.parameter_names(&func_call.name).is_none()
+                && context
+                    .scope_stack
+                    .get_variable_type(&func_call.name)
+                    .is_none() =>
+        {
+            let builtin = StrBuiltin::from_name(&func_call.name).unwrap();
+            typecheck_str_builtin(context, func_call, builtin)
+        }
         ParsedExpression::FunctionCall(func_call) => {
-            let mut errors = vec![];
-
-            let checked_args: Vec<CheckedExpression> = func_call
-                .args
+            // Function pointers have no parameter names to pass arguments to by name
+            let parameter_names = match context.scope_stack.get_variable_type(&func_call.name) {
+                Some(KnownVariable {
+                    ttype: Type::FnPtr { .. },
+                    ..
+                }) => None,
+                _ => context.parameter_names(&func_call.name),
+            };
+            let (args, mut errors) = order_call_args(
+                &func_call.args,
+                parameter_names.as_deref(),
+                &func_call.name,
+                func_call.span,
+            );
+            let args_matched = errors.is_empty();
+
+            let mut checked_args: Vec<CheckedExpression> = args
                 .iter()
-                .map(|arg| {
+                .map(|(arg, _)| {
                     let (checked_arg, mut errs) = typecheck_expression(context, arg);
                     errors.append(&mut errs);
                     checked_arg
                 })
                 .collect();
 
-            let return_type = if let Some(func) = context.known_functions.get(&func_call.name) {
-                if checked_args.len() != func.parameters.len() {
-                    errors.push(TypeCheckError::WrongNumArgs(
-                        func_call.span,
-                        checked_args.len(),
-                        func.parameters.len(),
-                    ));
+            if !args_matched {
+                return (
+                    CheckedExpression::FunctionCall(CheckedFunctionCall {
+                        name: func_call.name.clone(),
+                        args: checked_args,
+                        ttype: Type::Incomplete,
+                    }),
+                    errors,
+                );
+            }
+
+            // Variables holding function pointers shadow functions of the same name
+            if let Some(KnownVariable {
+                ttype:
+                    fn_type @ Type::FnPtr {
+                        params,
+                        return_type,
+                    },
+                is_mut,
+                ..
+            }) = context.scope_stack.get_variable_type(&func_call.name)
+            {
+                for (arg, _) in &func_call.args {
+                    if let CallArg::Named(named_arg) = arg {
+                        errors.push(TypeCheckError::UnknownNamedArg {
+                            function_name: func_call.name.clone(),
+                            arg_name: named_arg.name.clone(),
+                            span: named_arg.name_span,
+                        });
+                    }
                 }
+                let (checked_args, mut errs) =
+                    check_call_args(checked_args, &args, params, false, func_call.span);
+                errors.append(&mut errs);
 
-                for ((checked_arg, arg), param) in checked_args
-                    .iter()
-                    .zip(func_call.args.iter())
-                    .zip(func.parameters.iter())
-                {
-                    if !checked_arg.ttype().matches(&param.ttype) {
-                        errors.push(TypeCheckError::WrongArgType(
-                            arg.span(),
-                            checked_arg.ttype(),
-                            param.ttype.clone(),
-                        ));
+                return (
+                    CheckedExpression::FunctionPointerCall(CheckedFunctionPointerCall {
+                        callee: Box::new(CheckedExpression::Variable(
+                            func_call.name.clone(),
+                            fn_type.clone(),
+                            *is_mut,
+                        )),
+                        args: checked_args,
+                        ttype: *return_type.clone(),
+                    }),
+                    errors,
+                );
+            }
+
+            // Without a function of that name, `to_string` converts its argument by its type
+            let to_string_builtin = if func_call.name == "to_string"
+                && !context.known_functions.contains_key("to_string")
+                && !context.generic_functions.contains_key("to_string")
+                && checked_args.len() == 1
+            {
+                match checked_args[0].ttype() {
+                    Type::Int | Type::GenericInt => Some("int_to_string"),
+                    Type::Char => Some("char_to_string"),
+                    Type::Bool => Some("bool_to_string"),
+                    Type::Pointer(elem_type, _) if *elem_type == Type::CChar => {
+                        return (checked_args.remove(0), errors);
+                    }
+                    Type::Str
+                        if matches!(
+                            checked_args[0],
+                            CheckedExpression::Literal(CheckedLiteral::String(..))
+                        ) =>
+                    {
+                        let string = Type::Pointer(Box::new(Type::CChar), false);
+                        return (coerce_to(checked_args.remove(0), &string), errors);
+                    }
+                    ttype => {
+                        if ttype != Type::Incomplete {
+                            errors
+                                .push(TypeCheckError::NoStringConversion(ttype, args[0].0.span()));
+                        }
+                        // Still typed as a string to not cascade into the surrounding concatenation
+                        return (
+                            CheckedExpression::FunctionCall(CheckedFunctionCall {
+                                name: func_call.name.clone(),
+                                args: checked_args,
+                                ttype: Type::Pointer(Box::new(Type::CChar), false),
+                            }),
+                            errors,
+                        );
                     }
                 }
+            } else {
+                None
+            };
+
+            let name = if let Some(builtin) = to_string_builtin {
+                builtin.to_string()
+            } else if let Some(generic) = context.generic_functions.get(&func_call.name).copied() {
+                let (instance_name, mut errs) =
+                    instantiate_generic_function(context, generic, func_call, &checked_args);
+                errors.append(&mut errs);
+                let Some(instance_name) = instance_name else {
+                    return (
+                        CheckedExpression::FunctionCall(CheckedFunctionCall {
+                            name: func_call.name.clone(),
+                            args: checked_args,
+                            ttype: Type::Incomplete,
+                        }),
+                        errors,
+                    );
+                };
+                instance_name
+            } else {
+                if !func_call.type_args.is_empty()
+                    && context.known_functions.contains_key(&func_call.name)
+                {
+                    errors.push(TypeCheckError::TypeParameterCountMismatch {
+                        name: func_call.name.clone(),
+                        expected: 0,
+                        found: func_call.type_args.len(),
+                        span: func_call.name_span,
+                    });
+                }
+                func_call.name.clone()
+            };
+
+            context.use_builtin(&name, func_call.name_span);
+            let return_type = if let Some(func) = context.known_functions.get(&name) {
+                if func.is_unsafe && !context.in_unsafe {
+                    errors.push(TypeCheckError::UnsafeOperationOutsideUnsafeBlock(
+                        func_call.span,
+                    ));
+                }
+                let parameter_types: Vec<Type> = func
+                    .parameters
+                    .iter()
+                    .map(|param| param.ttype.clone())
+                    .collect();
+                let (by_ref_args, mut errs) = pass_by_ref(checked_args, &args, &func.parameters);
+                errors.append(&mut errs);
+                let (coerced_args, mut errs) = check_call_args(
+                    by_ref_args,
+                    &args,
+                    &parameter_types,
+                    func.is_variadic,
+                    func_call.span,
+                );
+                errors.append(&mut errs);
+                checked_args = coerced_args;
 
                 func.return_type.clone()
             } else {
@@ -1347,23 +6460,48 @@ fn typecheck_expression(
                 Type::Incomplete
             };
 
+            let arg_spans: Vec<Span> = args.iter().map(|(_, span)| *span).collect();
+            let checked_call = CheckedFunctionCall {
+                name,
+                args: checked_args,
+                ttype: return_type,
+            };
             (
-                CheckedExpression::FunctionCall(CheckedFunctionCall {
-                    name: func_call.name.clone(),
-                    args: checked_args,
-                    ttype: return_type,
-                }),
+                in_source_order(checked_call, &arg_spans, func_call.name_span),
                 errors,
             )
         }
         ParsedExpression::Variable(variable_name, span) => {
-            if let Some(KnownVariable { ttype, is_mut }) =
+            if let Some(KnownVariable {
+                ttype,
+                constant: Some(value),
+                ..
+            }) = context.scope_stack.get_variable_type(variable_name)
+            {
+                (
+                    CheckedExpression::Literal(value.to_literal(ttype.clone())),
+                    vec![],
+                )
+            } else if let Some(KnownVariable { ttype, is_mut, .. }) =
                 context.scope_stack.get_variable_type(variable_name)
             {
                 (
                     CheckedExpression::Variable(variable_name.clone(), ttype.clone(), *is_mut),
                     vec![],
                 )
+            } else if let Some(func) = context.known_functions.get(variable_name) {
+                let fn_type = Type::FnPtr {
+                    params: func
+                        .parameters
+                        .iter()
+                        .map(|param| param.ttype.clone())
+                        .collect(),
+                    return_type: Box::new(func.return_type.clone()),
+                };
+                (
+                    CheckedExpression::Function(variable_name.clone(), fn_type),
+                    vec![],
+                )
             } else {
                 (
                     CheckedExpression::Variable(variable_name.clone(), Type::Incomplete, false),
@@ -1383,6 +6521,9 @@ fn typecheck_expression(
             let (checked_rhs, mut errs) = typecheck_expression(context, rhs);
             errors.append(&mut errs);
 
+            let checked_rhs = coerce_to(checked_rhs, &checked_lhs.ttype());
+            let checked_lhs = coerce_to(checked_lhs, &checked_rhs.ttype());
+
             if !checked_lhs.ttype().matches(&checked_rhs.ttype()) {
                 errors.push(TypeCheckError::BinaryOpMismatch(
                     checked_lhs.ttype(),
@@ -1390,10 +6531,35 @@ fn typecheck_expression(
                     lhs.span(),
                     rhs.span(),
                 ))
+            } else if let Type::TypeParam(type_param) = checked_lhs.ttype() {
+                errors.push(TypeCheckError::OperatorOnTypeParam(
+                    type_param,
+                    expression.span(),
+                ));
+            }
+
+            let is_struct = |ttype: Type| match ttype {
+                Type::UserDefined(name) => context.known_structs.contains_key(&name),
+                _ => false,
+            };
+            if matches!(op, CompareOperation::Equality | CompareOperation::NotEqual)
+                && is_struct(checked_lhs.ttype())
+                && checked_lhs.ttype() == checked_rhs.ttype()
+            {
+                let (comparison, mut errs) = typecheck_struct_comparison(
+                    context,
+                    checked_lhs,
+                    checked_rhs,
+                    *op,
+                    expression.span(),
+                );
+                errors.append(&mut errs);
+                return (comparison, errors);
             }
 
             let ttype = match op {
                 CompareOperation::Equality => Type::Bool,
+                CompareOperation::NotEqual => Type::Bool,
                 CompareOperation::GreaterThan => Type::Bool,
                 CompareOperation::GreaterThanEqual => Type::Bool,
                 CompareOperation::LessThan => Type::Bool,
@@ -1422,6 +6588,31 @@ fn typecheck_expression(
                     lhs.span(),
                     rhs.span(),
                 ))
+            } else if let Type::TypeParam(type_param) = checked_lhs.ttype() {
+                if !op.is_integer_only() {
+                    errors.push(TypeCheckError::OperatorOnTypeParam(
+                        type_param,
+                        expression.span(),
+                    ));
+                }
+            }
+
+            if op.is_integer_only() {
+                for (checked_operand, operand) in [(&checked_lhs, lhs), (&checked_rhs, rhs)] {
+                    if !checked_operand.ttype().is_integer_type() {
+                        errors.push(match op {
+                            MathOperation::Modulo => TypeCheckError::ModuloNonInteger(
+                                checked_operand.ttype(),
+                                operand.span(),
+                            ),
+                            _ => TypeCheckError::BitwiseOpNonInteger(
+                                *op,
+                                checked_operand.ttype(),
+                                operand.span(),
+                            ),
+                        });
+                    }
+                }
             }
 
             let ttype = checked_lhs.ttype();
@@ -1430,6 +6621,75 @@ fn typecheck_expression(
                 errors,
             )
         }
+        ParsedExpression::LogicalAnd(lhs, rhs) | ParsedExpression::LogicalOr(lhs, rhs) => {
+            let (checked_lhs, mut errors) = typecheck_expression(context, lhs);
+            let (checked_rhs, mut errs) = typecheck_expression(context, rhs);
+            errors.append(&mut errs);
+
+            for (checked_operand, operand) in [(&checked_lhs, lhs), (&checked_rhs, rhs)] {
+                if checked_operand.ttype() != Type::Bool {
+                    errors.push(TypeCheckError::LogicalOpNonBool(
+                        checked_operand.ttype(),
+                        operand.span(),
+                    ));
+                }
+            }
+
+            let (checked_lhs, checked_rhs) = (Box::new(checked_lhs), Box::new(checked_rhs));
+            (
+                if let ParsedExpression::LogicalAnd(_, _) = expression {
+                    CheckedExpression::LogicalAnd(checked_lhs, checked_rhs)
+                } else {
+                    CheckedExpression::LogicalOr(checked_lhs, checked_rhs)
+                },
+                errors,
+            )
+        }
+        expr @ ParsedExpression::UnaryMinus(inner, _) => {
+            let (checked_inner, mut errors) = typecheck_expression(context, inner);
+
+            if !checked_inner.ttype().is_integer_type() {
+                errors.push(TypeCheckError::NegateNonNumeric(
+                    checked_inner.ttype(),
+                    expr.span(),
+                ));
+            }
+
+            let ttype = checked_inner.ttype();
+            (
+                CheckedExpression::UnaryMinus(Box::new(checked_inner), ttype),
+                errors,
+            )
+        }
+        expr @ ParsedExpression::Not(inner, _) => {
+            let (checked_inner, mut errors) = typecheck_expression(context, inner);
+
+            if checked_inner.ttype() != Type::Bool {
+                errors.push(TypeCheckError::NotNonBool(
+                    checked_inner.ttype(),
+                    expr.span(),
+                ));
+            }
+
+            (CheckedExpression::Not(Box::new(checked_inner)), errors)
+        }
+        expr @ ParsedExpression::BitNot(inner, _) => {
+            let (checked_inner, mut errors) = typecheck_expression(context, inner);
+
+            if !checked_inner.ttype().is_integer_type() {
+                errors.push(TypeCheckError::BitNotNonInteger(
+                    checked_inner.ttype(),
+                    expr.span(),
+                ));
+            }
+
+            let ttype = checked_inner.ttype();
+            (
+                CheckedExpression::BitNot(Box::new(checked_inner), ttype),
+                errors,
+            )
+        }
+        ParsedExpression::Parenthesized(inner, _) => typecheck_expression(context, inner),
         ParsedExpression::FieldAccess(field_access) => {
             let (checked_object, mut errors) = typecheck_expression(context, &field_access.object);
 
@@ -1440,39 +6700,62 @@ fn typecheck_expression(
                     (false, checked_object.ttype(), checked_object.is_mut())
                 };
 
-            let (ttype, r#struct) =
-                if let Some(r#struct) = context.known_structs.get(&obj_type.to_str()) {
-                    let ttype = if r#struct.is_opaque {
-                        errors.push(TypeCheckError::OpaqueStructFieldAccess(
-                            obj_type,
-                            field_access.field_name_span,
-                        ));
-                        Type::Incomplete
-                    } else if let Some(field_type) = r#struct.get_field(&field_access.field_name) {
-                        field_type.clone()
-                    } else {
-                        errors.push(TypeCheckError::FieldAccessInvalidField(
-                            obj_type,
-                            field_access.field_name.clone(),
+            let (ttype, r#struct) = if let Some(r#struct) =
+                context.known_structs.get(&obj_type.to_string())
+            {
+                let ttype = if r#struct.is_opaque {
+                    errors.push(TypeCheckError::OpaqueStructFieldAccess(
+                        obj_type,
+                        field_access.field_name_span,
+                    ));
+                    Type::Incomplete
+                } else if let Some(field_type) = r#struct.get_field(&field_access.field_name) {
+                    // The field written last is only known for union literals and the immutable
+                    // variables they initialize
+                    let written_field = match &checked_object {
+                        CheckedExpression::Literal(CheckedLiteral::Struct(union_literal, ..)) => {
+                            union_literal.fields.keys().next().cloned()
+                        }
+                        CheckedExpression::Variable(name, _, false) => context
+                            .scope_stack
+                            .get_variable_type(name)
+                            .and_then(|var| var.written_union_field.clone()),
+                        _ => None,
+                    };
+                    let reads_written_field =
+                        written_field.as_deref() == Some(field_access.field_name.as_str());
+                    if r#struct.is_union && !reads_written_field && !context.in_unsafe {
+                        errors.push(TypeCheckError::UnsafeOperationOutsideUnsafeBlock(
                             field_access.field_name_span,
                         ));
-                        Type::Incomplete
-                    };
-                    (ttype, r#struct.clone())
+                    }
+                    field_type.clone()
                 } else {
-                    errors.push(TypeCheckError::ObjectIsNotAStruct(
+                    errors.push(TypeCheckError::FieldAccessInvalidField(
                         obj_type,
-                        field_access.object_span,
+                        field_access.field_name.clone(),
+                        field_access.field_name_span,
                     ));
-                    (
-                        Type::Incomplete,
-                        Struct {
-                            name: String::new(),
-                            fields: vec![],
-                            is_opaque: true,
-                        },
-                    )
+                    Type::Incomplete
                 };
+                (ttype, r#struct.clone())
+            } else {
+                errors.push(TypeCheckError::ObjectIsNotAStruct(
+                    obj_type,
+                    field_access.object_span,
+                ));
+                (
+                    Type::Incomplete,
+                    Struct {
+                        name: String::new(),
+                        fields: vec![],
+                        is_opaque: true,
+                        opaque_size: None,
+                        has_eq: false,
+                        is_union: false,
+                    },
+                )
+            };
 
             (
                 CheckedExpression::FieldAccess(
@@ -1488,20 +6771,212 @@ fn typecheck_expression(
                 errors,
             )
         }
+        ParsedExpression::MethodCall(method_call) => {
+            let (checked_object, mut errors) = typecheck_expression(context, &method_call.object);
+
+            // Methods can be called both on objects and on pointers to objects
+            let object_type = checked_object.ttype();
+            let receiver_type = match object_type {
+                Type::Pointer(ref element_type, _) => element_type.as_ref().clone(),
+                ref object_type => object_type.clone(),
+            };
+            let type_name = receiver_type.to_string();
+
+            let method = match receiver_type {
+                Type::TypeParam(ref type_param) => {
+                    context.bound_method(type_param, &method_call.method_name)
+                }
+                _ => context
+                    .known_methods
+                    .get(&type_name)
+                    .and_then(|methods| methods.get(&method_call.method_name))
+                    .cloned(),
+            };
+
+            let parameter_names: Option<Vec<String>> = method.as_ref().map(|method| {
+                method.parameters[1..]
+                    .iter()
+                    .map(|param| param.name.clone())
+                    .collect()
+            });
+            let (args, mut errs) = order_call_args(
+                &method_call.args,
+                parameter_names.as_deref(),
+                &method_call.method_name,
+                method_call.span,
+            );
+            let args_matched = errs.is_empty();
+            errors.append(&mut errs);
+
+            let mut checked_args: Vec<CheckedExpression> = args
+                .iter()
+                .map(|(arg, _)| {
+                    let (checked_arg, mut errs) = typecheck_expression(context, arg);
+                    errors.append(&mut errs);
+                    checked_arg
+                })
+                .collect();
+
+            if !args_matched {
+                let mut args = vec![checked_object];
+                args.extend(checked_args);
+                return (
+                    CheckedExpression::FunctionCall(CheckedFunctionCall {
+                        name: method_symbol_name(&type_name, &method_call.method_name),
+                        args,
+                        ttype: Type::Incomplete,
+                    }),
+                    errors,
+                );
+            }
+
+            let (receiver, return_type) = if let Some(method) = method {
+                let receiver_type = &method.parameters[0].ttype;
+                let parameter_types: Vec<Type> = method.parameters[1..]
+                    .iter()
+                    .map(|param| param.ttype.clone())
+                    .collect();
+                let (by_ref_args, mut errs) =
+                    pass_by_ref(checked_args, &args, &method.parameters[1..]);
+                errors.append(&mut errs);
+                let (coerced_args, mut errs) = check_call_args(
+                    by_ref_args,
+                    &args,
+                    &parameter_types,
+                    false,
+                    method_call.span,
+                );
+                errors.append(&mut errs);
+                checked_args = coerced_args;
+
+                // Take the address of or dereference the object to fit the receiver parameter
+                let receiver = match (receiver_type, &object_type) {
+                    _ if receiver_type.matches(&object_type) => checked_object,
+                    (Type::Pointer(element_type, is_mut), _)
+                        if element_type.matches(&object_type) =>
+                    {
+                        if *is_mut && !checked_object.is_mut() {
+                            errors.push(TypeCheckError::MutablePointerToImmutableVariable(
+                                method_call.object_span,
+                            ));
+                        }
+                        CheckedExpression::PointerTo(CheckedPointerTo {
+                            inner: Box::new(checked_object),
+                            ttype: receiver_type.clone(),
+                        })
+                    }
+                    (_, Type::Pointer(element_type, _)) if receiver_type.matches(element_type) => {
+                        CheckedExpression::Deref(CheckedDeref {
+                            inner: Box::new(checked_object),
+                            ttype: *element_type.clone(),
+                        })
+                    }
+                    _ => {
+                        errors.push(TypeCheckError::WrongArgType(
+                            method_call.object_span,
+                            object_type.clone(),
+                            receiver_type.clone(),
+                        ));
+                        checked_object
+                    }
+                };
+
+                (receiver, method.return_type)
+            } else if let Type::TypeParam(type_param) = receiver_type {
+                errors.push(TypeCheckError::MethodNotInBounds {
+                    type_param,
+                    method_name: method_call.method_name.clone(),
+                    span: method_call.method_name_span,
+                });
+                (checked_object, Type::Incomplete)
+            } else {
+                if object_type != Type::Incomplete {
+                    errors.push(TypeCheckError::UnknownMethod(
+                        object_type.clone(),
+                        method_call.method_name.clone(),
+                        method_call.method_name_span,
+                    ));
+                }
+                (checked_object, Type::Incomplete)
+            };
+
+            let arg_spans: Vec<Span> = args.iter().map(|(_, span)| *span).collect();
+            let mut args = vec![receiver];
+            args.extend(checked_args);
+            let checked_call = CheckedFunctionCall {
+                name: method_symbol_name(&type_name, &method_call.method_name),
+                args,
+                ttype: return_type,
+            };
+            (
+                in_source_order(checked_call, &arg_spans, method_call.method_name_span),
+                errors,
+            )
+        }
+        ParsedExpression::TupleIndex(tuple_index) => {
+            let (checked_object, mut errors) = typecheck_expression(context, &tuple_index.object);
+
+            let ttype = match checked_object.ttype() {
+                Type::Tuple(ref element_types) if tuple_index.index < element_types.len() => {
+                    element_types[tuple_index.index].clone()
+                }
+                tuple_type @ Type::Tuple(_) => {
+                    errors.push(TypeCheckError::TupleIndexOutOfBounds(
+                        tuple_index.index,
+                        tuple_type,
+                        tuple_index.index_span,
+                    ));
+                    Type::Incomplete
+                }
+                Type::Incomplete => Type::Incomplete,
+                object_type => {
+                    errors.push(TypeCheckError::NotATuple(
+                        object_type,
+                        tuple_index.object.span(),
+                    ));
+                    Type::Incomplete
+                }
+            };
+
+            (
+                CheckedExpression::TupleIndex(CheckedTupleIndex {
+                    object: Box::new(checked_object),
+                    index: tuple_index.index,
+                    ttype,
+                }),
+                errors,
+            )
+        }
         ParsedExpression::ArrayIndex(array_index) => {
             let (checked_array, mut errors) = typecheck_expression(context, &array_index.array);
             let (checked_index, mut errs) = typecheck_expression(context, &array_index.index);
             errors.append(&mut errs);
 
-            let element_type = if let Type::Array(element_type, _) = checked_array.ttype() {
-                *element_type
+            let element_type = if let Some(element_type) = checked_array.ttype().indexed_type() {
+                element_type
             } else {
                 errors.push(TypeCheckError::NotAnArray(array_index.array.span()));
                 Type::Incomplete
             };
 
-            if !checked_index.ttype().matches(&Type::Int) {
-                errors.push(TypeCheckError::InvalidArrayIndex(array_index.index.span()));
+            if !checked_index.ttype().is_integer_type() {
+                errors.push(TypeCheckError::NonIntegerIndex {
+                    found: checked_index.ttype(),
+                    span: array_index.index.span(),
+                });
+            }
+
+            // Only the size of arrays is known at compile time, the length of slices is not
+            if let (Type::Array(_, size), Some(ConstValue::Int(index))) =
+                (checked_array.ttype(), const_eval(&checked_index))
+            {
+                if index < 0 || index as usize >= size {
+                    errors.push(TypeCheckError::StaticIndexOutOfBounds {
+                        index: index.into(),
+                        size,
+                        span: array_index.index.span(),
+                    });
+                }
             }
 
             (
@@ -1540,7 +7015,24 @@ fn typecheck_expression(
                 errors.push(TypeCheckError::AssignmentToImmutable(lhs.span()));
             }
 
-            if !target_type.matches(&checked_rhs.ttype()) {
+            let narrowed_variable = match &checked_lhs {
+                CheckedExpression::Variable(name, ..) => context
+                    .scope_stack
+                    .get_variable_type(name)
+                    .filter(|variable| variable.is_narrowed)
+                    .map(|_| name.clone()),
+                _ => None,
+            };
+
+            let checked_rhs = coerce_to(checked_rhs, &target_type);
+            if let (Some(name), Type::Null | Type::NullablePointer(..)) =
+                (narrowed_variable, checked_rhs.ttype())
+            {
+                errors.push(TypeCheckError::NullAssignmentToNarrowedVariable(
+                    name,
+                    rhs.span(),
+                ));
+            } else if !target_type.matches(&checked_rhs.ttype()) {
                 errors.push(TypeCheckError::AssignmentTypeMismatch(
                     checked_rhs.ttype(),
                     target_type,
@@ -1554,6 +7046,46 @@ fn typecheck_expression(
                 errors,
             )
         }
+        ParsedExpression::Range(range) => {
+            let (checked_start, mut errors) = typecheck_expression(context, &range.start);
+            let (checked_end, mut errs) = typecheck_expression(context, &range.end);
+            errors.append(&mut errs);
+
+            if !checked_start.ttype().matches(&checked_end.ttype()) {
+                errors.push(TypeCheckError::BinaryOpMismatch(
+                    checked_start.ttype(),
+                    checked_end.ttype(),
+                    range.start.span(),
+                    range.end.span(),
+                ))
+            }
+
+            for (checked_bound, bound) in
+                [(&checked_start, &range.start), (&checked_end, &range.end)]
+            {
+                if !checked_bound.ttype().is_integer_type() {
+                    errors.push(TypeCheckError::RangeNonInteger(
+                        checked_bound.ttype(),
+                        bound.span(),
+                    ));
+                }
+            }
+
+            // A literal bound takes on the integer type of the other bound
+            let elem_type = match checked_start.ttype() {
+                Type::GenericInt => checked_end.ttype(),
+                ttype => ttype,
+            };
+            (
+                CheckedExpression::Range(CheckedRange {
+                    start: Box::new(checked_start),
+                    end: Box::new(checked_end),
+                    inclusive: range.inclusive,
+                    ttype: Type::Range(Box::new(elem_type)),
+                }),
+                errors,
+            )
+        }
         expr @ ParsedExpression::PointerTo(pointer_to) => {
             let (checked_inner, mut errors) = typecheck_expression(context, &pointer_to.inner);
 
@@ -1572,14 +7104,93 @@ fn typecheck_expression(
                 errors,
             )
         }
+        ParsedExpression::EnumVariant(enum_variant) => {
+            let mut errors = vec![];
+
+            let checked_value = enum_variant.value.as_ref().map(|value| {
+                let (checked_value, mut errs) = typecheck_expression(context, value);
+                errors.append(&mut errs);
+                (checked_value, value.span())
+            });
+
+            let r#enum = if let Some(r#enum) = context.known_enums.get(&enum_variant.enum_name) {
+                r#enum.clone()
+            } else {
+                errors.push(TypeCheckError::UnknownType(
+                    enum_variant.enum_name.clone(),
+                    enum_variant.enum_name_span,
+                ));
+                Enum {
+                    name: enum_variant.enum_name.clone(),
+                    variants: vec![],
+                }
+            };
+
+            let variant_index = match r#enum.get_variant(&enum_variant.variant_name) {
+                Some((variant_index, value_type)) => {
+                    match (value_type, &checked_value) {
+                        (Some(expected), Some((checked_value, value_span))) => {
+                            if !expected.matches(&checked_value.ttype()) {
+                                errors.push(TypeCheckError::EnumVariantWrongType(
+                                    r#enum.name.clone(),
+                                    enum_variant.variant_name.clone(),
+                                    checked_value.ttype(),
+                                    expected.clone(),
+                                    *value_span,
+                                ));
+                            }
+                        }
+                        (Some(_), None) => errors.push(TypeCheckError::EnumVariantMissingValue(
+                            r#enum.name.clone(),
+                            enum_variant.variant_name.clone(),
+                            enum_variant.span,
+                        )),
+                        (None, Some((_, value_span))) => {
+                            errors.push(TypeCheckError::EnumVariantUnexpectedValue(
+                                r#enum.name.clone(),
+                                enum_variant.variant_name.clone(),
+                                *value_span,
+                            ))
+                        }
+                        (None, None) => {}
+                    }
+                    variant_index
+                }
+                None => {
+                    // Unknown enums were reported above already
+                    if context.known_enums.contains_key(&enum_variant.enum_name) {
+                        errors.push(TypeCheckError::UnknownEnumVariant(
+                            r#enum.name.clone(),
+                            enum_variant.variant_name.clone(),
+                            enum_variant.variant_name_span,
+                        ));
+                    }
+                    0
+                }
+            };
+
+            let ttype = Type::UserDefined(r#enum.name.clone());
+            (
+                CheckedExpression::EnumVariant(CheckedEnumVariant {
+                    r#enum,
+                    variant_index,
+                    value: checked_value.map(|(checked_value, _)| Box::new(checked_value)),
+                    ttype,
+                }),
+                errors,
+            )
+        }
         expr @ ParsedExpression::Deref(deref) => {
             let (checked_inner, mut errors) = typecheck_expression(context, &deref.inner);
-
-            if !checked_inner.ttype().is_pointer() {
-                errors.push(TypeCheckError::DerefNonPointer(
-                    checked_inner.ttype(),
-                    expr.span(),
-                ))
+            match checked_inner.ttype() {
+                Type::Pointer(..) if !context.in_unsafe => errors.push(
+                    TypeCheckError::UnsafeOperationOutsideUnsafeBlock(expr.span()),
+                ),
+                Type::Pointer(..) => {}
+                Type::NullablePointer(..) => {
+                    errors.push(TypeCheckError::PotentialNullDeref(expr.span()))
+                }
+                ttype => errors.push(TypeCheckError::DerefNonPointer(ttype, expr.span())),
             }
 
             let ttype = checked_inner
@@ -1594,5 +7205,155 @@ fn typecheck_expression(
                 errors,
             )
         }
+        ParsedExpression::SizeOf(ttype, span) => {
+            let mut errors = vec![];
+
+            let resolved = context.resolve_type(ttype);
+            if !context.type_is_defined(&resolved) {
+                errors.push(context.unknown_type_error(ttype, *span));
+            } else if let Some(struct_name) = context.unsized_opaque_struct(&resolved) {
+                errors.push(TypeCheckError::UnsizedOpaqueStruct(struct_name, *span));
+            }
+
+            (CheckedExpression::SizeOf(resolved), errors)
+        }
+        ParsedExpression::OffsetOf {
+            ttype,
+            field,
+            field_span,
+            span,
+        } => {
+            let mut errors = vec![];
+
+            let resolved = context.resolve_type(ttype);
+            let r#struct = match context.known_structs.get(&resolved.to_string()) {
+                _ if !context.type_is_defined(&resolved) => {
+                    errors.push(context.unknown_type_error(ttype, *span));
+                    None
+                }
+                Some(r#struct) if r#struct.is_opaque => {
+                    errors.push(TypeCheckError::OpaqueStructFieldAccess(
+                        resolved,
+                        *field_span,
+                    ));
+                    None
+                }
+                Some(r#struct) if r#struct.get_field(field).is_none() => {
+                    errors.push(TypeCheckError::FieldAccessInvalidField(
+                        resolved,
+                        field.clone(),
+                        *field_span,
+                    ));
+                    None
+                }
+                Some(r#struct) => Some(r#struct.clone()),
+                None => {
+                    errors.push(TypeCheckError::ObjectIsNotAStruct(resolved, *span));
+                    None
+                }
+            };
+            let r#struct = r#struct.unwrap_or_else(|| Struct {
+                name: String::new(),
+                fields: vec![],
+                is_opaque: true,
+                opaque_size: None,
+                has_eq: false,
+                is_union: false,
+            });
+
+            (CheckedExpression::OffsetOf(r#struct, field.clone()), errors)
+        }
+        ParsedExpression::ArrayRepeat { value, count, span } => {
+            let (checked_value, errors) = typecheck_expression(context, value);
+            let element_type = checked_value.ttype();
+
+            // Constants and variables are copied as they are, other values are evaluated once
+            let (statements, element) = if const_eval(&checked_value).is_some()
+                || matches!(checked_value, CheckedExpression::Variable(..))
+            {
+                (vec![], checked_value)
+            } else {
+                // Not a valid identifier, so it can't clash with the variables of the program
+                let name = format!("repeat.{}", span.start);
+                let element =
+                    CheckedExpression::Variable(name.clone(), element_type.clone(), false);
+                (
+                    vec![CheckedStatement::LetAssign(name, checked_value)],
+                    element,
+                )
+            };
+
+            let array_type = Type::Array(Box::new(element_type.clone()), *count);
+            let array = CheckedExpression::Literal(CheckedLiteral::Array(
+                CheckedArrayLiteral {
+                    elements: vec![element; *count],
+                    element_type: Some(element_type),
+                },
+                array_type.clone(),
+            ));
+            if statements.is_empty() {
+                return (array, errors);
+            }
+            (
+                CheckedExpression::Block(CheckedBlockExpression {
+                    body: CheckedBlock { statements },
+                    value: Some(Box::new(array)),
+                    ttype: array_type,
+                }),
+                errors,
+            )
+        }
+        ParsedExpression::Cast(cast) => {
+            let (checked_inner, mut errors) = typecheck_expression(context, &cast.inner);
+
+            let target_type = context.resolve_type(&cast.target_type);
+            if !context.type_is_defined(&target_type) {
+                errors.push(context.unknown_type_error(&cast.target_type, cast.span));
+            }
+
+            let checked_inner = coerce_to(checked_inner, &target_type);
+            let is_integer = |ttype: &Type| {
+                matches!(
+                    ttype,
+                    Type::GenericInt | Type::Int | Type::CInt | Type::CChar | Type::Char
+                )
+            };
+            let is_pointer =
+                |ttype: &Type| matches!(ttype, Type::Pointer(..) | Type::NullablePointer(..));
+            match (checked_inner.ttype(), &target_type) {
+                (Type::Incomplete, _) | (_, Type::Incomplete) => {}
+                (from, to) if from.matches(to) => {}
+                (from, to) if (is_integer(&from) || from == Type::Bool) && is_integer(to) => {}
+                (from, to) if (is_pointer(&from) || from == Type::Null) && is_pointer(to) => {}
+                (from, to) if is_integer(&from) && is_pointer(to) => {
+                    errors.push(TypeCheckError::IntegerToPointerCast(to.clone(), cast.span));
+                }
+                (from, to) => errors.push(TypeCheckError::InvalidCast {
+                    from,
+                    to: to.clone(),
+                    span: cast.span,
+                }),
+            }
+            if (is_pointer(&checked_inner.ttype()) || is_pointer(&target_type))
+                && !context.in_unsafe
+            {
+                errors.push(TypeCheckError::UnsafeOperationOutsideUnsafeBlock(cast.span));
+            }
+            (
+                CheckedExpression::Cast(CheckedCast {
+                    inner: Box::new(checked_inner),
+                    ttype: target_type,
+                }),
+                errors,
+            )
+        }
+        ParsedExpression::Block(block, _) => {
+            let (block, errors) = typecheck_block_expression(context, block);
+            (CheckedExpression::Block(block), errors)
+        }
+        ParsedExpression::IfExpr(if_else) => {
+            let (if_expr, errors) = typecheck_if_expression(context, if_else);
+            (CheckedExpression::IfExpr(if_expr), errors)
+        }
     }
 }
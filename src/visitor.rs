@@ -0,0 +1,476 @@
+use crate::parser::{
+    FunctionParameter, Literal, ParsedBlock, ParsedConstDecl, ParsedEnum, ParsedExpression,
+    ParsedExternFunction, ParsedFunction, ParsedIfElse, ParsedImpl, ParsedMatchArm, ParsedPattern,
+    ParsedProgram, ParsedStatement, ParsedStruct, ParsedTrait, ParsedType, ParsedTypeAlias,
+    ParsedUnion,
+};
+
+/// Read-only traversal of the parsed AST. Every method defaults to visiting the children of its
+/// node through the matching `walk_*` function, so visitors only override the nodes they care
+/// about and call the `walk_*` function themselves to keep descending.
+pub trait Visitor {
+    fn visit_program(&mut self, program: &ParsedProgram) {
+        walk_program(self, program);
+    }
+
+    fn visit_const_decl(&mut self, const_decl: &ParsedConstDecl) {
+        walk_const_decl(self, const_decl);
+    }
+
+    fn visit_type_alias(&mut self, type_alias: &ParsedTypeAlias) {
+        walk_type_alias(self, type_alias);
+    }
+
+    fn visit_struct(&mut self, r#struct: &ParsedStruct) {
+        walk_struct(self, r#struct);
+    }
+
+    fn visit_enum(&mut self, r#enum: &ParsedEnum) {
+        walk_enum(self, r#enum);
+    }
+
+    fn visit_union(&mut self, union: &ParsedUnion) {
+        walk_union(self, union);
+    }
+
+    fn visit_impl(&mut self, r#impl: &ParsedImpl) {
+        walk_impl(self, r#impl);
+    }
+
+    fn visit_trait(&mut self, r#trait: &ParsedTrait) {
+        walk_trait(self, r#trait);
+    }
+
+    fn visit_extern_function(&mut self, func: &ParsedExternFunction) {
+        walk_extern_function(self, func);
+    }
+
+    fn visit_function(&mut self, func: &ParsedFunction) {
+        walk_function(self, func);
+    }
+
+    fn visit_parameter(&mut self, param: &FunctionParameter) {
+        walk_parameter(self, param);
+    }
+
+    fn visit_block(&mut self, block: &ParsedBlock) {
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &ParsedStatement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_match_arm(&mut self, arm: &ParsedMatchArm) {
+        walk_match_arm(self, arm);
+    }
+
+    fn visit_pattern(&mut self, pattern: &ParsedPattern) {
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_expression(&mut self, expression: &ParsedExpression) {
+        walk_expression(self, expression);
+    }
+
+    fn visit_literal(&mut self, literal: &Literal) {
+        walk_literal(self, literal);
+    }
+
+    fn visit_type(&mut self, ttype: &ParsedType) {
+        walk_type(self, ttype);
+    }
+}
+
+pub fn walk_program<V: Visitor + ?Sized>(visitor: &mut V, program: &ParsedProgram) {
+    for const_decl in &program.consts {
+        visitor.visit_const_decl(const_decl);
+    }
+    for type_alias in &program.type_aliases {
+        visitor.visit_type_alias(type_alias);
+    }
+    for r#struct in &program.structs {
+        visitor.visit_struct(r#struct);
+    }
+    for r#enum in &program.enums {
+        visitor.visit_enum(r#enum);
+    }
+    for union in &program.unions {
+        visitor.visit_union(union);
+    }
+    for r#impl in &program.impls {
+        visitor.visit_impl(r#impl);
+    }
+    for r#trait in &program.traits {
+        visitor.visit_trait(r#trait);
+    }
+    for func in &program.extern_functions {
+        visitor.visit_extern_function(func);
+    }
+    for func in &program.functions {
+        visitor.visit_function(func);
+    }
+}
+
+pub fn walk_const_decl<V: Visitor + ?Sized>(visitor: &mut V, const_decl: &ParsedConstDecl) {
+    visitor.visit_type(&const_decl.ttype);
+    visitor.visit_expression(&const_decl.value);
+}
+
+pub fn walk_type_alias<V: Visitor + ?Sized>(visitor: &mut V, type_alias: &ParsedTypeAlias) {
+    visitor.visit_type(&type_alias.aliased);
+}
+
+pub fn walk_struct<V: Visitor + ?Sized>(visitor: &mut V, r#struct: &ParsedStruct) {
+    if let ParsedStruct::Transparent(_, _, _, fields, _, _) = r#struct {
+        for (_, field_type) in fields {
+            visitor.visit_type(field_type);
+        }
+    }
+}
+
+pub fn walk_enum<V: Visitor + ?Sized>(visitor: &mut V, r#enum: &ParsedEnum) {
+    for (_, _, value_type) in &r#enum.variants {
+        if let Some(value_type) = value_type {
+            visitor.visit_type(value_type);
+        }
+    }
+}
+
+pub fn walk_union<V: Visitor + ?Sized>(visitor: &mut V, union: &ParsedUnion) {
+    for (_, field_type) in &union.fields {
+        visitor.visit_type(field_type);
+    }
+}
+
+pub fn walk_impl<V: Visitor + ?Sized>(visitor: &mut V, r#impl: &ParsedImpl) {
+    for method in &r#impl.methods {
+        visitor.visit_function(method);
+    }
+}
+
+pub fn walk_trait<V: Visitor + ?Sized>(visitor: &mut V, r#trait: &ParsedTrait) {
+    for method in &r#trait.methods {
+        visitor.visit_extern_function(method);
+    }
+}
+
+pub fn walk_extern_function<V: Visitor + ?Sized>(visitor: &mut V, func: &ParsedExternFunction) {
+    for param in &func.parameters {
+        visitor.visit_parameter(param);
+    }
+    visitor.visit_type(&func.return_type);
+}
+
+pub fn walk_function<V: Visitor + ?Sized>(visitor: &mut V, func: &ParsedFunction) {
+    for param in &func.parameters {
+        visitor.visit_parameter(param);
+    }
+    visitor.visit_type(&func.return_type);
+    visitor.visit_block(&func.body);
+}
+
+pub fn walk_parameter<V: Visitor + ?Sized>(visitor: &mut V, param: &FunctionParameter) {
+    visitor.visit_type(&param.ttype);
+}
+
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &ParsedBlock) {
+    for statement in &block.statements {
+        visitor.visit_statement(statement);
+    }
+    if let Some(ref tail_expr) = block.tail_expr {
+        visitor.visit_expression(tail_expr);
+    }
+}
+
+fn walk_if_else<V: Visitor + ?Sized>(visitor: &mut V, if_else: &ParsedIfElse) {
+    visitor.visit_expression(&if_else.condition);
+    visitor.visit_block(&if_else.if_body);
+    if let Some(ref else_body) = if_else.else_body {
+        visitor.visit_block(else_body);
+    }
+}
+
+pub fn walk_statement<V: Visitor + ?Sized>(visitor: &mut V, statement: &ParsedStatement) {
+    match statement {
+        ParsedStatement::Expression(expr)
+        | ParsedStatement::Return(expr)
+        | ParsedStatement::Defer(expr, _) => {
+            visitor.visit_expression(expr);
+        }
+        ParsedStatement::LetAssign(let_assign) => {
+            if let Some((ref annotation, _)) = let_assign.type_annotation {
+                visitor.visit_type(annotation);
+            }
+            visitor.visit_expression(&let_assign.value);
+        }
+        ParsedStatement::Const(const_decl) => visitor.visit_const_decl(const_decl),
+        ParsedStatement::WhileLoop(while_loop) => {
+            visitor.visit_expression(&while_loop.condition);
+            visitor.visit_block(&while_loop.body);
+            if let Some(ref else_body) = while_loop.else_body {
+                visitor.visit_block(else_body);
+            }
+        }
+        ParsedStatement::WhileLet {
+            pattern,
+            value,
+            body,
+            ..
+        } => {
+            visitor.visit_pattern(pattern);
+            visitor.visit_expression(value);
+            visitor.visit_block(body);
+        }
+        ParsedStatement::Loop(body, _, _) | ParsedStatement::Unsafe(body, _) => {
+            visitor.visit_block(body)
+        }
+        ParsedStatement::IfElse(if_else) => walk_if_else(visitor, if_else),
+        ParsedStatement::Match(r#match) => {
+            visitor.visit_expression(&r#match.scrutinee);
+            for arm in &r#match.arms {
+                visitor.visit_match_arm(arm);
+            }
+        }
+        ParsedStatement::Switch(switch) => {
+            visitor.visit_expression(&switch.scrutinee);
+            for (values, body) in &switch.cases {
+                for value in values {
+                    visitor.visit_expression(value);
+                }
+                visitor.visit_block(body);
+            }
+            if let Some(ref default) = switch.default {
+                visitor.visit_block(default);
+            }
+        }
+        ParsedStatement::ForInLoop(for_in) => {
+            visitor.visit_expression(&for_in.iterable_value);
+            visitor.visit_block(&for_in.body);
+        }
+        ParsedStatement::Break(..) | ParsedStatement::Continue(..) => {}
+    }
+}
+
+pub fn walk_match_arm<V: Visitor + ?Sized>(visitor: &mut V, arm: &ParsedMatchArm) {
+    visitor.visit_pattern(&arm.pattern);
+    visitor.visit_expression(&arm.body);
+}
+
+pub fn walk_pattern<V: Visitor + ?Sized>(visitor: &mut V, pattern: &ParsedPattern) {
+    if let ParsedPattern::Literal(literal) = pattern {
+        visitor.visit_literal(literal);
+    }
+}
+
+pub fn walk_expression<V: Visitor + ?Sized>(visitor: &mut V, expression: &ParsedExpression) {
+    match expression {
+        ParsedExpression::Literal(literal) => visitor.visit_literal(literal),
+        ParsedExpression::FunctionCall(func_call) => {
+            for type_arg in &func_call.type_args {
+                visitor.visit_type(type_arg);
+            }
+            for (arg, _) in &func_call.args {
+                visitor.visit_expression(arg.value());
+            }
+        }
+        ParsedExpression::Variable(_, _) => {}
+        ParsedExpression::CompareOp(lhs, rhs, _)
+        | ParsedExpression::MathOp(lhs, rhs, _)
+        | ParsedExpression::LogicalAnd(lhs, rhs)
+        | ParsedExpression::LogicalOr(lhs, rhs)
+        | ParsedExpression::Assignment(lhs, rhs) => {
+            visitor.visit_expression(lhs);
+            visitor.visit_expression(rhs);
+        }
+        ParsedExpression::UnaryMinus(inner, _)
+        | ParsedExpression::Not(inner, _)
+        | ParsedExpression::BitNot(inner, _)
+        | ParsedExpression::Parenthesized(inner, _) => visitor.visit_expression(inner),
+        ParsedExpression::FieldAccess(field_access) => {
+            visitor.visit_expression(&field_access.object);
+        }
+        ParsedExpression::MethodCall(method_call) => {
+            visitor.visit_expression(&method_call.object);
+            for (arg, _) in &method_call.args {
+                visitor.visit_expression(arg.value());
+            }
+        }
+        ParsedExpression::TupleIndex(tuple_index) => visitor.visit_expression(&tuple_index.object),
+        ParsedExpression::ArrayIndex(array_index) => {
+            visitor.visit_expression(&array_index.array);
+            visitor.visit_expression(&array_index.index);
+        }
+        ParsedExpression::Range(range) => {
+            visitor.visit_expression(&range.start);
+            visitor.visit_expression(&range.end);
+        }
+        ParsedExpression::PointerTo(pointer_to) => visitor.visit_expression(&pointer_to.inner),
+        ParsedExpression::Deref(deref) => visitor.visit_expression(&deref.inner),
+        ParsedExpression::SizeOf(ttype, _) | ParsedExpression::OffsetOf { ttype, .. } => {
+            visitor.visit_type(ttype)
+        }
+        ParsedExpression::Cast(cast) => {
+            visitor.visit_type(&cast.target_type);
+            visitor.visit_expression(&cast.inner);
+        }
+        ParsedExpression::ArrayRepeat { value, .. } => visitor.visit_expression(value),
+        ParsedExpression::Block(block, _) => visitor.visit_block(block),
+        ParsedExpression::IfExpr(if_else) => walk_if_else(visitor, if_else),
+        ParsedExpression::EnumVariant(enum_variant) => {
+            if let Some(ref value) = enum_variant.value {
+                visitor.visit_expression(value);
+            }
+        }
+    }
+}
+
+pub fn walk_literal<V: Visitor + ?Sized>(visitor: &mut V, literal: &Literal) {
+    match literal {
+        Literal::String(_, _)
+        | Literal::Char(_, _)
+        | Literal::Int(_, _)
+        | Literal::Bool(_, _)
+        | Literal::Null(_) => {}
+        Literal::Struct(struct_literal, _) => {
+            for type_arg in &struct_literal.type_args {
+                visitor.visit_type(type_arg);
+            }
+            for (_, _, field_value) in &struct_literal.fields {
+                visitor.visit_expression(field_value);
+            }
+        }
+        Literal::Array(array_literal, _) => {
+            for element in &array_literal.elements {
+                visitor.visit_expression(element);
+            }
+        }
+        Literal::Tuple(elements, _) => {
+            for element in elements {
+                visitor.visit_expression(element);
+            }
+        }
+    }
+}
+
+pub fn walk_type<V: Visitor + ?Sized>(visitor: &mut V, ttype: &ParsedType) {
+    match ttype {
+        ParsedType::Name(_) | ParsedType::Unit => {}
+        ParsedType::Pointer(subtype, _)
+        | ParsedType::NullablePointer(subtype, _)
+        | ParsedType::Array(subtype, _)
+        | ParsedType::Slice(subtype) => visitor.visit_type(subtype),
+        ParsedType::Tuple(element_types) | ParsedType::Generic(_, element_types) => {
+            for element_type in element_types {
+                visitor.visit_type(element_type);
+            }
+        }
+        ParsedType::FnPtr {
+            params,
+            return_type,
+        } => {
+            for param in params {
+                visitor.visit_type(param);
+            }
+            visitor.visit_type(return_type);
+        }
+    }
+}
+
+/// Counts every node of the AST, serves as a reference for visitors reaching every node.
+#[derive(Debug, Default)]
+pub struct NodeCounter {
+    pub count: usize,
+}
+
+impl Visitor for NodeCounter {
+    fn visit_program(&mut self, program: &ParsedProgram) {
+        self.count += 1;
+        walk_program(self, program);
+    }
+
+    fn visit_const_decl(&mut self, const_decl: &ParsedConstDecl) {
+        self.count += 1;
+        walk_const_decl(self, const_decl);
+    }
+
+    fn visit_type_alias(&mut self, type_alias: &ParsedTypeAlias) {
+        self.count += 1;
+        walk_type_alias(self, type_alias);
+    }
+
+    fn visit_struct(&mut self, r#struct: &ParsedStruct) {
+        self.count += 1;
+        walk_struct(self, r#struct);
+    }
+
+    fn visit_enum(&mut self, r#enum: &ParsedEnum) {
+        self.count += 1;
+        walk_enum(self, r#enum);
+    }
+
+    fn visit_union(&mut self, union: &ParsedUnion) {
+        self.count += 1;
+        walk_union(self, union);
+    }
+
+    fn visit_impl(&mut self, r#impl: &ParsedImpl) {
+        self.count += 1;
+        walk_impl(self, r#impl);
+    }
+
+    fn visit_trait(&mut self, r#trait: &ParsedTrait) {
+        self.count += 1;
+        walk_trait(self, r#trait);
+    }
+
+    fn visit_extern_function(&mut self, func: &ParsedExternFunction) {
+        self.count += 1;
+        walk_extern_function(self, func);
+    }
+
+    fn visit_function(&mut self, func: &ParsedFunction) {
+        self.count += 1;
+        walk_function(self, func);
+    }
+
+    fn visit_parameter(&mut self, param: &FunctionParameter) {
+        self.count += 1;
+        walk_parameter(self, param);
+    }
+
+    fn visit_block(&mut self, block: &ParsedBlock) {
+        self.count += 1;
+        walk_block(self, block);
+    }
+
+    fn visit_statement(&mut self, statement: &ParsedStatement) {
+        self.count += 1;
+        walk_statement(self, statement);
+    }
+
+    fn visit_match_arm(&mut self, arm: &ParsedMatchArm) {
+        self.count += 1;
+        walk_match_arm(self, arm);
+    }
+
+    fn visit_pattern(&mut self, pattern: &ParsedPattern) {
+        self.count += 1;
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_expression(&mut self, expression: &ParsedExpression) {
+        self.count += 1;
+        walk_expression(self, expression);
+    }
+
+    fn visit_literal(&mut self, literal: &Literal) {
+        self.count += 1;
+        walk_literal(self, literal);
+    }
+
+    fn visit_type(&mut self, ttype: &ParsedType) {
+        self.count += 1;
+        walk_type(self, ttype);
+    }
+}
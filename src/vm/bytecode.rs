@@ -0,0 +1,128 @@
+/// Instructions of the VM, which operate on a stack of `i64` words. Values take up as many words
+/// as they have scalar parts, a struct of an `int` and a pointer is pushed as two words. Pointers
+/// are indices into the VM's memory, function pointers are the address of the first instruction
+/// of the function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Push(i64),
+    /// Discard the given number of words
+    Pop(usize),
+
+    // Integer arithmetic, which wraps around like 32 bit integers do
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Mod,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+    Neg,
+    BitNot,
+    /// Logical not, pushes 1 for 0 and 0 for anything else
+    Not,
+    /// Keep the given number of low bits of a word, sign extended
+    Truncate(u32),
+
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+
+    Jmp(usize),
+    /// Pop a word and jump if it isn't 0
+    JmpIf(usize),
+
+    /// Call the function starting at the given instruction, its arguments are on the stack
+    Call(usize),
+    /// Pop a function pointer and call it
+    CallIndirect,
+    /// Call a function provided by the VM, standing in for an extern function
+    CallNative {
+        native: Native,
+        /// Words taken up by the arguments, more than the parameters for variadic functions
+        arg_words: usize,
+        return_words: usize,
+    },
+    /// Return to the caller, leaving the return value on the stack
+    Ret,
+
+    /// Reserve memory for the locals of the current function
+    Alloc(usize),
+    /// Push the address of the local at the given offset into the frame of the current function
+    LocalAddr(usize),
+    /// Pop an address and push the given number of words stored there
+    Load(usize),
+    /// Pop an address, then store the given number of words popped after it there
+    Store(usize),
+    /// Replace a value of `total` words on the stack with the `size` words at `offset` into it
+    GetField {
+        offset: usize,
+        size: usize,
+        total: usize,
+    },
+
+    Halt,
+}
+
+/// Functions the VM implements itself, after the functions of `lib/clara.c` and the C standard
+/// library functions the examples use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Native {
+    PrintInt,
+    PrintLn,
+    PrintStr,
+    PrintChar,
+    Puts,
+    Putchar,
+    /// Supports the `%d`, `%s`, `%.*s`, `%c` and `%%` conversions
+    Printf,
+    StringConcat,
+    IntToString,
+    CharToString,
+    BoolToString,
+    Strlen,
+    /// Stops the program with [`RuntimeError::AssertionFailed`] if its argument is 0
+    ///
+    /// [`RuntimeError::AssertionFailed`]: super::interpreter::RuntimeError::AssertionFailed
+    Assert,
+    /// Stops the program with [`RuntimeError::SliceOutOfBounds`] unless its arguments `start`,
+    /// `end` and `len` are in order, starting at 0
+    ///
+    /// [`RuntimeError::SliceOutOfBounds`]: super::interpreter::RuntimeError::SliceOutOfBounds
+    StrSliceCheck,
+}
+
+impl Native {
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "print_int" => Self::PrintInt,
+            "print_ln" => Self::PrintLn,
+            "print_str" => Self::PrintStr,
+            "print_char" => Self::PrintChar,
+            "puts" => Self::Puts,
+            "putchar" => Self::Putchar,
+            "printf" => Self::Printf,
+            "string_concat" => Self::StringConcat,
+            "int_to_string" => Self::IntToString,
+            "char_to_string" => Self::CharToString,
+            "bool_to_string" => Self::BoolToString,
+            "strlen" => Self::Strlen,
+            "assert" => Self::Assert,
+            "str_slice_check" => Self::StrSliceCheck,
+            _ => return None,
+        })
+    }
+}
+
+/// A compiled program, execution starts at the first instruction.
+#[derive(Debug)]
+pub struct Program {
+    pub instructions: Vec<Instruction>,
+    /// The initial contents of memory, holding the string literals
+    pub data: Vec<i64>,
+}
@@ -0,0 +1,980 @@
+use std::collections::HashMap;
+
+use eyre::{bail, eyre};
+
+use crate::{
+    parser::{CompareOperation, MathOperation},
+    typechecker::{
+        CheckedArrayIndex, CheckedBlock, CheckedBlockExpression, CheckedExpression,
+        CheckedExternFunction, CheckedFunction, CheckedLiteral, CheckedPattern, CheckedProgram,
+        CheckedStatement, CheckedStrBuiltin, Enum, StrBuiltin, Struct, Type,
+    },
+};
+
+use super::bytecode::{Instruction, Native, Program};
+
+/// Jumps out of a loop, patched with their target once the end of the loop is known.
+#[derive(Debug, Default)]
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+struct Compiler<'a> {
+    instructions: Vec<Instruction>,
+    data: Vec<i64>,
+    structs: HashMap<&'a str, &'a Struct>,
+    enums: HashMap<&'a str, &'a Enum>,
+    extern_functions: HashMap<&'a str, &'a CheckedExternFunction>,
+    /// The first instruction of every function compiled so far
+    function_entries: HashMap<&'a str, usize>,
+    /// `Call`s and `Push`es of functions that may not have been compiled yet
+    function_references: Vec<(usize, &'a str)>,
+    /// Offsets of the variables into the frame of the current function
+    scopes: Vec<HashMap<&'a str, usize>>,
+    frame_size: usize,
+    loops: Vec<LoopJumps>,
+}
+
+/// Compiles a typechecked program to bytecode, starting with a call to `main`. Extern functions
+/// can only be called if the VM provides them, see `Native`.
+pub fn compile(program: &CheckedProgram) -> eyre::Result<Program> {
+    compile_with_entry(program, "main")
+}
+
+/// Compiles a typechecked program to bytecode that calls `entry` instead of `main`, as the test
+/// runner does for every test function.
+pub fn compile_with_entry(program: &CheckedProgram, entry: &str) -> eyre::Result<Program> {
+    let mut compiler = Compiler {
+        instructions: vec![],
+        // Address 0 is never valid, so that it can be used for null pointers
+        data: vec![0],
+        structs: program
+            .structs
+            .iter()
+            .map(|r#struct| (r#struct.name.as_str(), r#struct))
+            .collect(),
+        enums: program
+            .enums
+            .iter()
+            .map(|r#enum| (r#enum.name.as_str(), r#enum))
+            .collect(),
+        extern_functions: program
+            .extern_functions
+            .iter()
+            .map(|func| (func.name.as_str(), func))
+            .collect(),
+        function_entries: HashMap::new(),
+        function_references: vec![],
+        scopes: vec![],
+        frame_size: 0,
+        loops: vec![],
+    };
+
+    compiler.function_references.push((0, entry));
+    compiler.emit(Instruction::Call(0));
+    compiler.emit(Instruction::Halt);
+
+    for func in &program.functions {
+        compiler.compile_function(func)?;
+    }
+
+    for &(instruction_idx, name) in &compiler.function_references {
+        let entry = *compiler
+            .function_entries
+            .get(name)
+            .ok_or_else(|| eyre!("program has no function `{name}`"))?;
+        match &mut compiler.instructions[instruction_idx] {
+            Instruction::Call(target) => *target = entry,
+            Instruction::Push(value) => *value = entry as i64,
+            _ => unreachable!("function references are calls or pushes"),
+        }
+    }
+
+    Ok(Program {
+        instructions: compiler.instructions,
+        data: compiler.data,
+    })
+}
+
+impl<'a> Compiler<'a> {
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        self.instructions.push(instruction);
+        self.instructions.len() - 1
+    }
+
+    /// Point the jump at `jump_idx` to the next instruction to be emitted.
+    fn patch_jump(&mut self, jump_idx: usize) {
+        let target = self.instructions.len();
+        match &mut self.instructions[jump_idx] {
+            Instruction::Jmp(jump_target) | Instruction::JmpIf(jump_target) => {
+                *jump_target = target
+            }
+            _ => unreachable!("only jumps are patched"),
+        }
+    }
+
+    fn alloc_local(&mut self, size: usize) -> usize {
+        let offset = self.frame_size;
+        self.frame_size += size;
+        offset
+    }
+
+    fn declare(&mut self, name: &'a str, offset: usize) {
+        self.scopes.last_mut().unwrap().insert(name, offset);
+    }
+
+    fn lookup(&self, name: &str) -> usize {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .expect("existence of variable was previously established in typechecker")
+    }
+
+    /// The number of words a value of the type takes up.
+    fn size_of(&self, ttype: &Type) -> usize {
+        match ttype {
+            Type::Unit | Type::Never | Type::GenericEmptyArray => 0,
+            Type::Array(element_type, len) => self.size_of(element_type) * len,
+            // Strings and slices are a pointer to their first element followed by their length,
+            // ranges are their start followed by their exclusive end
+            Type::Str | Type::Slice(_) | Type::Range(_) => 2,
+            Type::Tuple(element_types) => element_types
+                .iter()
+                .map(|element_type| self.size_of(element_type))
+                .sum(),
+            Type::UserDefined(name) => match self.structs.get(name.as_str()) {
+                // One word for every byte of an opaque struct with a known size
+                Some(r#struct) if r#struct.is_opaque => r#struct.opaque_size.unwrap_or(0),
+                // The fields of a union share its words
+                Some(r#struct) if r#struct.is_union => r#struct
+                    .fields
+                    .iter()
+                    .map(|(_, field_type)| self.size_of(field_type))
+                    .max()
+                    .unwrap_or(0),
+                Some(r#struct) => r#struct
+                    .fields
+                    .iter()
+                    .map(|(_, field_type)| self.size_of(field_type))
+                    .sum(),
+                // Enums are a tag holding the index of the variant, followed by the value of
+                // every variant that holds one
+                None => {
+                    1 + self.enums[name.as_str()]
+                        .variants
+                        .iter()
+                        .filter_map(|(_, value_type)| value_type.as_ref())
+                        .map(|value_type| self.size_of(value_type))
+                        .sum::<usize>()
+                }
+            },
+            Type::Incomplete => panic!("attempted to use incomplete type in vm"),
+            _ => 1,
+        }
+    }
+
+    /// The size and alignment in bytes a value of the type has when compiled natively, so that
+    /// `sizeof` gives the same result in the VM as in the other backends.
+    fn native_layout(&self, ttype: &Type) -> (usize, usize) {
+        match ttype {
+            Type::Unit | Type::Never | Type::GenericEmptyArray => (0, 1),
+            Type::Bool | Type::CChar => (1, 1),
+            Type::GenericInt | Type::Int | Type::CInt | Type::Char => (4, 4),
+            Type::Array(element_type, len) => {
+                let (size, align) = self.native_layout(element_type);
+                (size * len, align)
+            }
+            Type::Str | Type::Slice(_) => Self::native_struct_layout(&[(8, 8), (4, 4)]),
+            Type::Range(_) => (8, 4),
+            Type::Tuple(element_types) => Self::native_struct_layout(
+                &element_types
+                    .iter()
+                    .map(|element_type| self.native_layout(element_type))
+                    .collect::<Vec<_>>(),
+            ),
+            Type::UserDefined(name) => match self.structs.get(name.as_str()) {
+                Some(r#struct) if r#struct.is_opaque => (r#struct.opaque_size.unwrap_or(0), 1),
+                Some(r#struct) if r#struct.is_union => {
+                    let fields: Vec<_> = r#struct
+                        .fields
+                        .iter()
+                        .map(|(_, field_type)| self.native_layout(field_type))
+                        .collect();
+                    let align = fields.iter().map(|&(_, align)| align).max().unwrap_or(1);
+                    let size = fields.iter().map(|&(size, _)| size).max().unwrap_or(0);
+                    (size.next_multiple_of(align), align)
+                }
+                Some(r#struct) => Self::native_struct_layout(
+                    &r#struct
+                        .fields
+                        .iter()
+                        .map(|(_, field_type)| self.native_layout(field_type))
+                        .collect::<Vec<_>>(),
+                ),
+                None => {
+                    let mut fields = vec![(4, 4)];
+                    fields.extend(
+                        self.enums[name.as_str()]
+                            .variants
+                            .iter()
+                            .filter_map(|(_, value_type)| value_type.as_ref())
+                            .map(|value_type| self.native_layout(value_type)),
+                    );
+                    Self::native_struct_layout(&fields)
+                }
+            },
+            Type::Incomplete => panic!("attempted to use incomplete type in vm"),
+            _ => (8, 8),
+        }
+    }
+
+    /// Lays out fields of the given sizes and alignments one after the other like a C struct.
+    fn native_struct_layout(fields: &[(usize, usize)]) -> (usize, usize) {
+        let align = fields.iter().map(|&(_, align)| align).max().unwrap_or(1);
+        let size = fields.iter().fold(0usize, |offset, &(size, align)| {
+            offset.next_multiple_of(align) + size
+        });
+        (size.next_multiple_of(align), align)
+    }
+
+    /// The offset in bytes of a field when compiled natively, the counterpart of `native_layout`
+    /// for `offsetof`.
+    fn native_field_offset(&self, r#struct: &Struct, field_name: &str) -> usize {
+        if r#struct.is_union {
+            return 0;
+        }
+        let mut offset = 0usize;
+        for (declared_field_name, field_type) in &r#struct.fields {
+            let (size, align) = self.native_layout(field_type);
+            offset = offset.next_multiple_of(align);
+            if declared_field_name == field_name {
+                break;
+            }
+            offset += size;
+        }
+        offset
+    }
+
+    fn field_offset(&self, r#struct: &Struct, field_name: &str) -> usize {
+        if r#struct.is_union {
+            return 0;
+        }
+        r#struct
+            .fields
+            .iter()
+            .take_while(|(declared_field_name, _)| declared_field_name != field_name)
+            .map(|(_, field_type)| self.size_of(field_type))
+            .sum()
+    }
+
+    fn tuple_offset(&self, tuple_type: &Type, index: usize) -> usize {
+        match tuple_type {
+            Type::Tuple(element_types) => element_types[..index]
+                .iter()
+                .map(|element_type| self.size_of(element_type))
+                .sum(),
+            _ => panic!("tuple index into non-tuple"),
+        }
+    }
+
+    fn enum_value_offset(&self, r#enum: &Enum, variant_index: usize) -> usize {
+        1 + r#enum.variants[..variant_index]
+            .iter()
+            .filter_map(|(_, value_type)| value_type.as_ref())
+            .map(|value_type| self.size_of(value_type))
+            .sum::<usize>()
+    }
+
+    fn compile_function(&mut self, func: &'a CheckedFunction) -> eyre::Result<()> {
+        self.function_entries
+            .insert(&func.name, self.instructions.len());
+        self.frame_size = 0;
+        self.scopes.push(HashMap::new());
+
+        // The size of the frame is only known once the whole body is compiled
+        let alloc_idx = self.emit(Instruction::Alloc(0));
+        let param_offsets: Vec<_> = func
+            .parameters
+            .iter()
+            .map(|param| {
+                let offset = self.alloc_local(self.size_of(&param.ttype));
+                self.declare(&param.name, offset);
+                offset
+            })
+            .collect();
+        // The last argument is on top of the stack
+        for (param, offset) in func.parameters.iter().zip(param_offsets).rev() {
+            self.emit(Instruction::LocalAddr(offset));
+            self.emit(Instruction::Store(self.size_of(&param.ttype)));
+        }
+
+        self.compile_block(&func.body)?;
+        self.emit(Instruction::Ret);
+
+        self.instructions[alloc_idx] = Instruction::Alloc(self.frame_size);
+        self.scopes.pop();
+        Ok(())
+    }
+
+    fn compile_block(&mut self, block: &'a CheckedBlock) -> eyre::Result<()> {
+        self.scopes.push(HashMap::new());
+        for statement in &block.statements {
+            self.compile_statement(statement)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Compiles a block and leaves its value, if any, on the stack.
+    fn compile_block_expression(&mut self, block: &'a CheckedBlockExpression) -> eyre::Result<()> {
+        self.scopes.push(HashMap::new());
+        for statement in &block.body.statements {
+            self.compile_statement(statement)?;
+        }
+        if let Some(ref value) = block.value {
+            self.compile_expression(value)?;
+        }
+        self.scopes.pop();
+        Ok(())
+    }
+
+    /// Store the value on top of the stack into a new local.
+    fn store_local(&mut self, ttype: &Type) -> usize {
+        let size = self.size_of(ttype);
+        let offset = self.alloc_local(size);
+        self.emit(Instruction::LocalAddr(offset));
+        self.emit(Instruction::Store(size));
+        offset
+    }
+
+    fn compile_statement(&mut self, statement: &'a CheckedStatement) -> eyre::Result<()> {
+        match statement {
+            CheckedStatement::Expression(expression) => {
+                self.compile_expression(expression)?;
+                let size = self.size_of(&expression.ttype());
+                if size > 0 {
+                    self.emit(Instruction::Pop(size));
+                }
+            }
+            CheckedStatement::Const => {}
+            CheckedStatement::LetAssign(variable_name, value) => {
+                self.compile_expression(value)?;
+                let offset = self.store_local(&value.ttype());
+                self.declare(variable_name, offset);
+            }
+            CheckedStatement::IfElse(if_else) => {
+                self.compile_expression(&if_else.condition)?;
+                self.emit(Instruction::Not);
+                let to_else = self.emit(Instruction::JmpIf(0));
+                self.compile_block(&if_else.if_body)?;
+                let to_end = self.emit(Instruction::Jmp(0));
+                self.patch_jump(to_else);
+                self.compile_block(&if_else.else_body)?;
+                self.patch_jump(to_end);
+            }
+            CheckedStatement::WhileLoop(while_loop) => {
+                let condition_idx = self.instructions.len();
+                self.compile_expression(&while_loop.condition)?;
+                self.emit(Instruction::Not);
+                let to_end = self.emit(Instruction::JmpIf(0));
+
+                self.loops.push(LoopJumps::default());
+                self.compile_block(&while_loop.body)?;
+                self.emit(Instruction::Jmp(condition_idx));
+                self.patch_jump(to_end);
+                self.patch_loop_jumps(condition_idx);
+            }
+            CheckedStatement::Loop(body) => {
+                let body_idx = self.instructions.len();
+                self.loops.push(LoopJumps::default());
+                self.compile_block(body)?;
+                self.emit(Instruction::Jmp(body_idx));
+                self.patch_loop_jumps(body_idx);
+            }
+            CheckedStatement::ForInLoop(for_in) => {
+                self.scopes.push(HashMap::new());
+
+                // Arrays are iterated as a slice of themselves, both hold a pointer to the first
+                // element followed by the length. Ranges hold their start followed by their end.
+                let iterable_type = for_in.iterable.ttype();
+                if let Type::Array(_, array_len) = iterable_type {
+                    self.compile_place(&for_in.iterable)?;
+                    self.emit(Instruction::Push(array_len as i64));
+                } else {
+                    self.compile_expression(&for_in.iterable)?;
+                }
+                let iterable = self.alloc_local(2);
+                self.emit(Instruction::LocalAddr(iterable));
+                self.emit(Instruction::Store(2));
+
+                self.emit(Instruction::Push(0));
+                let index = self.store_local(&Type::Int);
+                if let Some(index_var_name) = &for_in.index_var {
+                    self.declare(index_var_name, index);
+                }
+                let elem_size = self.size_of(&for_in.elem_var_type);
+                let elem = self.alloc_local(elem_size);
+                self.declare(&for_in.elem_var_name, elem);
+
+                let condition_idx = self.instructions.len();
+                self.emit(Instruction::LocalAddr(index));
+                self.emit(Instruction::Load(1));
+                self.emit(Instruction::LocalAddr(iterable + 1));
+                self.emit(Instruction::Load(1));
+                if let Type::Range(_) = iterable_type {
+                    self.emit(Instruction::LocalAddr(iterable));
+                    self.emit(Instruction::Load(1));
+                    self.emit(Instruction::Sub);
+                }
+                self.emit(Instruction::Lt);
+                self.emit(Instruction::Not);
+                let to_end = self.emit(Instruction::JmpIf(0));
+
+                self.emit(Instruction::LocalAddr(iterable));
+                self.emit(Instruction::Load(1));
+                self.emit(Instruction::LocalAddr(index));
+                self.emit(Instruction::Load(1));
+                if let Type::Range(_) = iterable_type {
+                    self.emit(Instruction::Add);
+                } else {
+                    self.emit(Instruction::Push(elem_size as i64));
+                    self.emit(Instruction::Mul);
+                    self.emit(Instruction::Add);
+                    self.emit(Instruction::Load(elem_size));
+                }
+                self.emit(Instruction::LocalAddr(elem));
+                self.emit(Instruction::Store(elem_size));
+
+                self.loops.push(LoopJumps::default());
+                self.compile_block(&for_in.body)?;
+
+                let increment_idx = self.instructions.len();
+                self.emit(Instruction::LocalAddr(index));
+                self.emit(Instruction::Load(1));
+                self.emit(Instruction::Push(1));
+                self.emit(Instruction::Add);
+                self.emit(Instruction::LocalAddr(index));
+                self.emit(Instruction::Store(1));
+                self.emit(Instruction::Jmp(condition_idx));
+                self.patch_jump(to_end);
+                self.patch_loop_jumps(increment_idx);
+
+                self.scopes.pop();
+            }
+            CheckedStatement::Match(r#match) => {
+                self.compile_expression(&r#match.scrutinee)?;
+                let scrutinee = self.store_local(&r#match.scrutinee.ttype());
+
+                // Arms are tested one after another, falling through to the next arm on mismatch
+                let mut to_end = vec![];
+                for arm in &r#match.arms {
+                    let to_next_arm = match &arm.pattern {
+                        CheckedPattern::Wildcard => None,
+                        CheckedPattern::Literal(literal) => {
+                            self.emit(Instruction::LocalAddr(scrutinee));
+                            self.emit(Instruction::Load(1));
+                            self.compile_expression(literal)?;
+                            self.emit(Instruction::Eq);
+                            self.emit(Instruction::Not);
+                            Some(self.emit(Instruction::JmpIf(0)))
+                        }
+                        CheckedPattern::EnumVariant(pattern) => {
+                            self.emit(Instruction::LocalAddr(scrutinee));
+                            self.emit(Instruction::Load(1));
+                            self.emit(Instruction::Push(pattern.variant_index as i64));
+                            self.emit(Instruction::Eq);
+                            self.emit(Instruction::Not);
+                            Some(self.emit(Instruction::JmpIf(0)))
+                        }
+                    };
+
+                    self.scopes.push(HashMap::new());
+                    if let CheckedPattern::EnumVariant(pattern) = &arm.pattern {
+                        if let Some(binding) = &pattern.binding {
+                            let value_offset =
+                                self.enum_value_offset(&pattern.r#enum, pattern.variant_index);
+                            self.declare(binding, scrutinee + value_offset);
+                        }
+                    }
+                    self.compile_expression(&arm.body)?;
+                    let size = self.size_of(&arm.body.ttype());
+                    if size > 0 {
+                        self.emit(Instruction::Pop(size));
+                    }
+                    self.scopes.pop();
+
+                    to_end.push(self.emit(Instruction::Jmp(0)));
+                    if let Some(to_next_arm) = to_next_arm {
+                        self.patch_jump(to_next_arm);
+                    }
+                }
+
+                for jump_idx in to_end {
+                    self.patch_jump(jump_idx);
+                }
+            }
+            CheckedStatement::Switch(switch) => {
+                // Enum values are stored with their tag first, which is the value switched on
+                self.compile_expression(&switch.scrutinee)?;
+                let scrutinee = self.store_local(&switch.scrutinee.ttype());
+
+                // Each value is compared in turn, jumping to the body of its case on a match
+                let mut to_cases = vec![];
+                for (values, _) in &switch.cases {
+                    let mut to_case = vec![];
+                    for &value in values {
+                        self.emit(Instruction::LocalAddr(scrutinee));
+                        self.emit(Instruction::Load(1));
+                        self.emit(Instruction::Push(value));
+                        self.emit(Instruction::Eq);
+                        to_case.push(self.emit(Instruction::JmpIf(0)));
+                    }
+                    to_cases.push(to_case);
+                }
+
+                let mut to_end = vec![];
+                self.compile_block(&switch.default)?;
+                to_end.push(self.emit(Instruction::Jmp(0)));
+                for (to_case, (_, body)) in to_cases.into_iter().zip(&switch.cases) {
+                    for jump_idx in to_case {
+                        self.patch_jump(jump_idx);
+                    }
+                    self.compile_block(body)?;
+                    to_end.push(self.emit(Instruction::Jmp(0)));
+                }
+
+                for jump_idx in to_end {
+                    self.patch_jump(jump_idx);
+                }
+            }
+            CheckedStatement::Return(return_value) => {
+                self.compile_expression(return_value)?;
+                self.emit(Instruction::Ret);
+            }
+            CheckedStatement::Break(loop_depth) => {
+                let jump_idx = self.emit(Instruction::Jmp(0));
+                self.loops
+                    .iter_mut()
+                    .rev()
+                    .nth(*loop_depth)
+                    .expect("break and continue only appear in loops as established by typechecker")
+                    .breaks
+                    .push(jump_idx);
+            }
+            CheckedStatement::Continue(loop_depth) => {
+                let jump_idx = self.emit(Instruction::Jmp(0));
+                self.loops
+                    .iter_mut()
+                    .rev()
+                    .nth(*loop_depth)
+                    .expect("break and continue only appear in loops as established by typechecker")
+                    .continues
+                    .push(jump_idx);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Points the `break`s of the innermost loop past the end of the loop and its `continue`s at
+    /// `continue_idx`.
+    fn patch_loop_jumps(&mut self, continue_idx: usize) {
+        let loop_jumps = self.loops.pop().unwrap();
+        for jump_idx in loop_jumps.breaks {
+            self.patch_jump(jump_idx);
+        }
+        for jump_idx in loop_jumps.continues {
+            self.instructions[jump_idx] = Instruction::Jmp(continue_idx);
+        }
+    }
+
+    /// Whether the expression refers to memory it can be accessed through, rather than being a
+    /// temporary value.
+    fn is_place(expression: &CheckedExpression) -> bool {
+        match expression {
+            CheckedExpression::Variable(..)
+            | CheckedExpression::ArrayIndex(_)
+            | CheckedExpression::Deref(_) => true,
+            CheckedExpression::FieldAccess(field_access, _, _) => {
+                field_access.object_is_ptr || Self::is_place(&field_access.object)
+            }
+            CheckedExpression::TupleIndex(tuple_index) => Self::is_place(&tuple_index.object),
+            _ => false,
+        }
+    }
+
+    /// Pushes the address of the expression, temporaries are stored in a new local first.
+    fn compile_place(&mut self, expression: &'a CheckedExpression) -> eyre::Result<()> {
+        match expression {
+            CheckedExpression::Variable(variable_name, _type, _is_mut) => {
+                self.emit(Instruction::LocalAddr(self.lookup(variable_name)));
+            }
+            CheckedExpression::FieldAccess(field_access, r#struct, _type)
+                if Self::is_place(expression) =>
+            {
+                if field_access.object_is_ptr {
+                    self.compile_expression(&field_access.object)?;
+                } else {
+                    self.compile_place(&field_access.object)?;
+                }
+                self.emit_offset(self.field_offset(r#struct, &field_access.field_name));
+            }
+            CheckedExpression::TupleIndex(tuple_index) if Self::is_place(expression) => {
+                self.compile_place(&tuple_index.object)?;
+                self.emit_offset(self.tuple_offset(&tuple_index.object.ttype(), tuple_index.index));
+            }
+            CheckedExpression::ArrayIndex(array_index) => {
+                self.compile_element_address(array_index)?
+            }
+            CheckedExpression::Deref(deref) => self.compile_expression(&deref.inner)?,
+            _ => {
+                self.compile_expression(expression)?;
+                let offset = self.store_local(&expression.ttype());
+                self.emit(Instruction::LocalAddr(offset));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit_offset(&mut self, offset: usize) {
+        if offset > 0 {
+            self.emit(Instruction::Push(offset as i64));
+            self.emit(Instruction::Add);
+        }
+    }
+
+    fn compile_element_address(&mut self, array_index: &'a CheckedArrayIndex) -> eyre::Result<()> {
+        if let Type::Slice(_) = array_index.array.ttype() {
+            self.compile_expression(&array_index.array)?;
+            self.emit(Instruction::GetField {
+                offset: 0,
+                size: 1,
+                total: 2,
+            });
+        } else {
+            self.compile_place(&array_index.array)?;
+        }
+        self.compile_expression(&array_index.index)?;
+        self.emit(Instruction::Push(self.size_of(&array_index.ttype) as i64));
+        self.emit(Instruction::Mul);
+        self.emit(Instruction::Add);
+        Ok(())
+    }
+
+    fn compile_arguments(&mut self, args: &'a [CheckedExpression]) -> eyre::Result<usize> {
+        let mut arg_words = 0;
+        for arg in args {
+            self.compile_expression(arg)?;
+            arg_words += self.size_of(&arg.ttype());
+        }
+        Ok(arg_words)
+    }
+
+    /// Pushes the value of the expression.
+    fn compile_expression(&mut self, expression: &'a CheckedExpression) -> eyre::Result<()> {
+        match expression {
+            CheckedExpression::Literal(literal) => match literal {
+                CheckedLiteral::Int(value, _) => {
+                    self.emit(Instruction::Push(*value as i64));
+                }
+                CheckedLiteral::Char(value, _) => {
+                    self.emit(Instruction::Push(*value as i64));
+                }
+                CheckedLiteral::Bool(value, _) => {
+                    self.emit(Instruction::Push(*value as i64));
+                }
+                CheckedLiteral::Null(_) => {
+                    self.emit(Instruction::Push(0));
+                }
+                // Strings are stored a byte per word, followed by a 0 like C strings
+                CheckedLiteral::String(value, ttype) => {
+                    let address = self.data.len();
+                    self.data
+                        .extend(value.bytes().map(i64::from).chain(std::iter::once(0)));
+                    self.emit(Instruction::Push(address as i64));
+                    if *ttype == Type::Str {
+                        self.emit(Instruction::Push(value.len() as i64));
+                    }
+                }
+                CheckedLiteral::Struct(struct_literal, r#struct, union_type)
+                    if r#struct.is_union =>
+                {
+                    // The written field is followed by zeroes up to the size of the union
+                    let mut words = 0;
+                    for field_value in struct_literal.fields.values() {
+                        self.compile_expression(field_value)?;
+                        words += self.size_of(&field_value.ttype());
+                    }
+                    for _ in words..self.size_of(union_type) {
+                        self.emit(Instruction::Push(0));
+                    }
+                }
+                CheckedLiteral::Struct(struct_literal, r#struct, _type) => {
+                    for (declared_field_name, _) in &r#struct.fields {
+                        let field_value = struct_literal
+                            .fields
+                            .get(declared_field_name.as_str())
+                            .expect("existence of field value was established by typechecker");
+                        self.compile_expression(field_value)?;
+                    }
+                }
+                CheckedLiteral::Tuple(elements, _type) => {
+                    self.compile_arguments(elements)?;
+                }
+                CheckedLiteral::Array(array_literal, _type) => {
+                    self.compile_arguments(&array_literal.elements)?;
+                }
+            },
+            CheckedExpression::FunctionCall(func_call) => {
+                let arg_words = self.compile_arguments(&func_call.args)?;
+                if let Some(extern_function) = self.extern_functions.get(func_call.name.as_str()) {
+                    let native = Native::from_name(&extern_function.name).ok_or_else(|| {
+                        eyre!(
+                            "extern function `{}` is not available in the vm",
+                            extern_function.name
+                        )
+                    })?;
+                    self.emit(Instruction::CallNative {
+                        native,
+                        arg_words,
+                        return_words: self.size_of(&func_call.ttype),
+                    });
+                } else {
+                    let call_idx = self.emit(Instruction::Call(0));
+                    self.function_references.push((call_idx, &func_call.name));
+                }
+            }
+            CheckedExpression::FunctionPointerCall(func_call) => {
+                self.compile_arguments(&func_call.args)?;
+                self.compile_expression(&func_call.callee)?;
+                self.emit(Instruction::CallIndirect);
+            }
+            CheckedExpression::Function(function_name, _type) => {
+                if self.extern_functions.contains_key(function_name.as_str()) {
+                    bail!(
+                        "pointers to extern function `{function_name}` are not supported in the vm"
+                    );
+                }
+                let push_idx = self.emit(Instruction::Push(0));
+                self.function_references.push((push_idx, function_name));
+            }
+            CheckedExpression::Variable(..) => {
+                self.compile_place(expression)?;
+                self.emit(Instruction::Load(self.size_of(&expression.ttype())));
+            }
+            CheckedExpression::CompareOp(lhs, rhs, op, _type) => {
+                self.compile_expression(lhs)?;
+                self.compile_expression(rhs)?;
+                self.emit(match op {
+                    CompareOperation::Equality => Instruction::Eq,
+                    CompareOperation::NotEqual => Instruction::Ne,
+                    CompareOperation::GreaterThan => Instruction::Gt,
+                    CompareOperation::GreaterThanEqual => Instruction::Ge,
+                    CompareOperation::LessThan => Instruction::Lt,
+                    CompareOperation::LessThanEqual => Instruction::Le,
+                });
+            }
+            CheckedExpression::MathOp(lhs, rhs, op, _type) => {
+                self.compile_expression(lhs)?;
+                self.compile_expression(rhs)?;
+                self.emit(match op {
+                    MathOperation::Addition => Instruction::Add,
+                    MathOperation::Subtraction => Instruction::Sub,
+                    MathOperation::Multiplication => Instruction::Mul,
+                    MathOperation::Division => Instruction::Div,
+                    MathOperation::Modulo => Instruction::Mod,
+                    MathOperation::BitAnd => Instruction::BitAnd,
+                    MathOperation::BitOr => Instruction::BitOr,
+                    MathOperation::BitXor => Instruction::BitXor,
+                    MathOperation::Shl => Instruction::Shl,
+                    MathOperation::Shr => Instruction::Shr,
+                });
+            }
+            // The rhs is only evaluated if the lhs does not already determine the result
+            CheckedExpression::LogicalAnd(lhs, rhs) => {
+                self.compile_expression(lhs)?;
+                self.emit(Instruction::Not);
+                let to_short_circuit = self.emit(Instruction::JmpIf(0));
+                self.compile_expression(rhs)?;
+                let to_end = self.emit(Instruction::Jmp(0));
+                self.patch_jump(to_short_circuit);
+                self.emit(Instruction::Push(0));
+                self.patch_jump(to_end);
+            }
+            CheckedExpression::LogicalOr(lhs, rhs) => {
+                self.compile_expression(lhs)?;
+                let to_short_circuit = self.emit(Instruction::JmpIf(0));
+                self.compile_expression(rhs)?;
+                let to_end = self.emit(Instruction::Jmp(0));
+                self.patch_jump(to_short_circuit);
+                self.emit(Instruction::Push(1));
+                self.patch_jump(to_end);
+            }
+            CheckedExpression::UnaryMinus(inner, _type) => {
+                self.compile_expression(inner)?;
+                self.emit(Instruction::Neg);
+            }
+            CheckedExpression::Not(inner) => {
+                self.compile_expression(inner)?;
+                self.emit(Instruction::Not);
+            }
+            CheckedExpression::BitNot(inner, _type) => {
+                self.compile_expression(inner)?;
+                self.emit(Instruction::BitNot);
+            }
+            CheckedExpression::FieldAccess(field_access, r#struct, field_type) => {
+                let size = self.size_of(field_type);
+                if Self::is_place(expression) {
+                    self.compile_place(expression)?;
+                    self.emit(Instruction::Load(size));
+                } else {
+                    // Fields of temporaries like `f().x` are taken from the value on the stack
+                    self.compile_expression(&field_access.object)?;
+                    self.emit(Instruction::GetField {
+                        offset: self.field_offset(r#struct, &field_access.field_name),
+                        size,
+                        total: self.size_of(&field_access.object.ttype()),
+                    });
+                }
+            }
+            CheckedExpression::TupleIndex(tuple_index) => {
+                let size = self.size_of(&tuple_index.ttype);
+                if Self::is_place(expression) {
+                    self.compile_place(expression)?;
+                    self.emit(Instruction::Load(size));
+                } else {
+                    let object_type = tuple_index.object.ttype();
+                    self.compile_expression(&tuple_index.object)?;
+                    self.emit(Instruction::GetField {
+                        offset: self.tuple_offset(&object_type, tuple_index.index),
+                        size,
+                        total: self.size_of(&object_type),
+                    });
+                }
+            }
+            CheckedExpression::ArrayIndex(array_index) => {
+                self.compile_element_address(array_index)?;
+                self.emit(Instruction::Load(self.size_of(&array_index.ttype)));
+            }
+            // Nullable pointers are represented the same as pointers
+            CheckedExpression::PointerToNullable(pointer_to_nullable) => {
+                self.compile_expression(&pointer_to_nullable.pointer)?;
+            }
+            CheckedExpression::ArrayToSlice(array_to_slice) => {
+                let array_len = if let Type::Array(_, array_len) = array_to_slice.array.ttype() {
+                    array_len
+                } else {
+                    panic!("non-array converted to slice");
+                };
+                self.compile_place(&array_to_slice.array)?;
+                self.emit(Instruction::Push(array_len as i64));
+            }
+            CheckedExpression::Assignment(lhs, rhs) => {
+                self.compile_expression(rhs)?;
+                self.compile_place(lhs)?;
+                self.emit(Instruction::Store(self.size_of(&rhs.ttype())));
+            }
+            CheckedExpression::Range(range) => {
+                self.compile_expression(&range.start)?;
+                self.compile_expression(&range.end)?;
+                if range.inclusive {
+                    self.emit(Instruction::Push(1));
+                    self.emit(Instruction::Add);
+                }
+            }
+            CheckedExpression::PointerTo(pointer_to) => self.compile_place(&pointer_to.inner)?,
+            CheckedExpression::Deref(deref) => {
+                self.compile_expression(&deref.inner)?;
+                self.emit(Instruction::Load(self.size_of(&deref.ttype)));
+            }
+            CheckedExpression::SizeOf(ttype) => {
+                self.emit(Instruction::Push(self.native_layout(ttype).0 as i64));
+            }
+            CheckedExpression::OffsetOf(r#struct, field_name) => {
+                self.emit(Instruction::Push(
+                    self.native_field_offset(r#struct, field_name) as i64,
+                ));
+            }
+            CheckedExpression::Cast(cast) => {
+                self.compile_expression(&cast.inner)?;
+                if cast.ttype == Type::CChar && cast.inner.ttype() != Type::CChar {
+                    self.emit(Instruction::Truncate(8));
+                }
+            }
+            CheckedExpression::StrBuiltin(CheckedStrBuiltin {
+                builtin: StrBuiltin::FromCString,
+                args,
+            }) => {
+                // The pointer is stored in a local, as it's pushed again for `strlen`
+                self.compile_expression(&args[0])?;
+                let offset = self.store_local(&args[0].ttype());
+                for _ in 0..2 {
+                    self.emit(Instruction::LocalAddr(offset));
+                    self.emit(Instruction::Load(1));
+                }
+                self.emit(Instruction::CallNative {
+                    native: Native::Strlen,
+                    arg_words: 1,
+                    return_words: 1,
+                });
+            }
+            CheckedExpression::StrBuiltin(str_builtin) => {
+                self.compile_expression(&str_builtin.args[0])?;
+                let field_offset = match str_builtin.builtin {
+                    StrBuiltin::Len => 1,
+                    StrBuiltin::Data | StrBuiltin::Slice => 0,
+                    StrBuiltin::FromCString => unreachable!("compiled above"),
+                };
+                self.emit(Instruction::GetField {
+                    offset: field_offset,
+                    size: 1,
+                    total: 2,
+                });
+                if str_builtin.builtin == StrBuiltin::Slice {
+                    self.compile_expression(&str_builtin.args[1])?;
+                    self.emit(Instruction::Add);
+                    self.compile_expression(&str_builtin.args[2])?;
+                    self.compile_expression(&str_builtin.args[1])?;
+                    self.emit(Instruction::Sub);
+                }
+            }
+            CheckedExpression::Block(block) => self.compile_block_expression(block)?,
+            CheckedExpression::IfExpr(if_expr) => {
+                self.compile_expression(&if_expr.condition)?;
+                self.emit(Instruction::Not);
+                let to_else = self.emit(Instruction::JmpIf(0));
+                self.compile_block_expression(&if_expr.if_body)?;
+                let to_end = self.emit(Instruction::Jmp(0));
+                self.patch_jump(to_else);
+                self.compile_block_expression(&if_expr.else_body)?;
+                self.patch_jump(to_end);
+            }
+            CheckedExpression::EnumVariant(enum_variant) => {
+                self.emit(Instruction::Push(enum_variant.variant_index as i64));
+                for (variant_index, (_, value_type)) in
+                    enum_variant.r#enum.variants.iter().enumerate()
+                {
+                    let value_type = match value_type {
+                        Some(value_type) => value_type,
+                        None => continue,
+                    };
+                    match &enum_variant.value {
+                        Some(value) if variant_index == enum_variant.variant_index => {
+                            self.compile_expression(value)?;
+                        }
+                        _ => {
+                            for _ in 0..self.size_of(value_type) {
+                                self.emit(Instruction::Push(0));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,404 @@
+use std::{fmt, io};
+
+use super::bytecode::{Instruction, Native, Program};
+
+/// How deeply calls may be nested before the program is considered to be recursing endlessly.
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// The address of the first word of the heap, far above any address of the static data and locals.
+const HEAP_START: usize = 1 << 32;
+
+#[derive(Debug)]
+pub enum RuntimeError {
+    DivisionByZero,
+    NullPointer,
+    InvalidAddress(i64),
+    StackOverflow,
+    AssertionFailed,
+    SliceOutOfBounds { start: i32, end: i32, len: i32 },
+    Io(io::Error),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DivisionByZero => write!(f, "division by zero"),
+            Self::NullPointer => write!(f, "dereferenced a null pointer"),
+            Self::InvalidAddress(address) => write!(f, "accessed invalid address {address}"),
+            Self::StackOverflow => write!(f, "stack overflow"),
+            Self::AssertionFailed => write!(f, "assertion failed"),
+            Self::SliceOutOfBounds { start, end, len } => write!(
+                f,
+                "str_slice out of bounds: start {start}, end {end}, length {len}"
+            ),
+            Self::Io(err) => write!(f, "failed to write output: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+#[derive(Debug)]
+struct Frame {
+    return_idx: usize,
+    /// Where the locals of the function start in memory
+    base: usize,
+}
+
+/// The words addressable by a program. The stack part starts out as the static data of the program
+/// and grows by the locals of every called function, which are freed again when it returns. Strings
+/// built at runtime are allocated on the heap, which is never freed.
+struct Memory {
+    stack: Vec<i64>,
+    heap: Vec<i64>,
+}
+
+impl Memory {
+    /// The `words` words at `address`.
+    fn get(&self, address: i64, words: usize) -> Result<&[i64], RuntimeError> {
+        let (region, start) = self.locate(address, words)?;
+        Ok(match region {
+            Region::Stack => &self.stack[start..start + words],
+            Region::Heap => &self.heap[start..start + words],
+        })
+    }
+
+    fn get_mut(&mut self, address: i64, words: usize) -> Result<&mut [i64], RuntimeError> {
+        let (region, start) = self.locate(address, words)?;
+        Ok(match region {
+            Region::Stack => &mut self.stack[start..start + words],
+            Region::Heap => &mut self.heap[start..start + words],
+        })
+    }
+
+    /// The region holding an access of `words` words at `address` and the index of it in there.
+    fn locate(&self, address: i64, words: usize) -> Result<(Region, usize), RuntimeError> {
+        if address == 0 {
+            return Err(RuntimeError::NullPointer);
+        }
+        match usize::try_from(address) {
+            Ok(start) if start + words <= self.stack.len() => Ok((Region::Stack, start)),
+            Ok(start) if start >= HEAP_START && start - HEAP_START + words <= self.heap.len() => {
+                Ok((Region::Heap, start - HEAP_START))
+            }
+            _ => Err(RuntimeError::InvalidAddress(address)),
+        }
+    }
+
+    /// Copies `string` to the heap with a terminating 0 and returns its address.
+    fn alloc_string(&mut self, string: &[u8]) -> i64 {
+        let address = HEAP_START + self.heap.len();
+        self.heap.extend(string.iter().map(|&byte| byte as i64));
+        self.heap.push(0);
+        address as i64
+    }
+}
+
+enum Region {
+    Stack,
+    Heap,
+}
+
+/// Executes a program with a stack of operands and a stack of call frames.
+pub struct Interpreter<'a, W> {
+    program: &'a Program,
+    memory: Memory,
+    stack: Vec<i64>,
+    frames: Vec<Frame>,
+    output: W,
+}
+
+impl<'a, W: io::Write> Interpreter<'a, W> {
+    /// Output of the program's printing functions is written to `output`.
+    pub fn new(program: &'a Program, output: W) -> Self {
+        Self {
+            program,
+            memory: Memory {
+                stack: program.data.clone(),
+                heap: vec![],
+            },
+            stack: vec![],
+            frames: vec![],
+            output,
+        }
+    }
+
+    pub fn run(&mut self) -> Result<(), RuntimeError> {
+        let mut instruction_idx = 0;
+
+        loop {
+            let instruction = self.program.instructions[instruction_idx];
+            instruction_idx += 1;
+
+            match instruction {
+                Instruction::Push(value) => self.stack.push(value),
+                Instruction::Pop(words) => self.stack.truncate(self.stack.len() - words),
+
+                Instruction::Add => self.binary(|lhs, rhs| Ok(lhs.wrapping_add(rhs)))?,
+                Instruction::Sub => self.binary(|lhs, rhs| Ok(lhs.wrapping_sub(rhs)))?,
+                Instruction::Mul => self.binary(|lhs, rhs| Ok(lhs.wrapping_mul(rhs)))?,
+                Instruction::Div => self.binary(|lhs, rhs| {
+                    if rhs == 0 {
+                        Err(RuntimeError::DivisionByZero)
+                    } else {
+                        Ok(lhs.wrapping_div(rhs))
+                    }
+                })?,
+                Instruction::Mod => self.binary(|lhs, rhs| {
+                    if rhs == 0 {
+                        Err(RuntimeError::DivisionByZero)
+                    } else {
+                        Ok(lhs.wrapping_rem(rhs))
+                    }
+                })?,
+                Instruction::BitAnd => self.binary(|lhs, rhs| Ok(lhs & rhs))?,
+                Instruction::BitOr => self.binary(|lhs, rhs| Ok(lhs | rhs))?,
+                Instruction::BitXor => self.binary(|lhs, rhs| Ok(lhs ^ rhs))?,
+                Instruction::Shl => self.binary(|lhs, rhs| Ok(lhs.wrapping_shl(rhs as u32)))?,
+                Instruction::Shr => self.binary(|lhs, rhs| Ok(lhs.wrapping_shr(rhs as u32)))?,
+                Instruction::Neg => {
+                    let value = self.pop_int();
+                    self.stack.push(value.wrapping_neg() as i64);
+                }
+                Instruction::BitNot => {
+                    let value = self.pop_int();
+                    self.stack.push(!value as i64);
+                }
+                Instruction::Not => {
+                    let value = self.pop();
+                    self.stack.push((value == 0) as i64);
+                }
+                Instruction::Truncate(bits) => {
+                    let shift = i64::BITS - bits;
+                    let value = self.pop();
+                    self.stack.push((value << shift) >> shift);
+                }
+
+                Instruction::Eq => self.compare(|lhs, rhs| lhs == rhs),
+                Instruction::Ne => self.compare(|lhs, rhs| lhs != rhs),
+                Instruction::Lt => self.compare(|lhs, rhs| lhs < rhs),
+                Instruction::Le => self.compare(|lhs, rhs| lhs <= rhs),
+                Instruction::Gt => self.compare(|lhs, rhs| lhs > rhs),
+                Instruction::Ge => self.compare(|lhs, rhs| lhs >= rhs),
+
+                Instruction::Jmp(target) => instruction_idx = target,
+                Instruction::JmpIf(target) => {
+                    if self.pop() != 0 {
+                        instruction_idx = target;
+                    }
+                }
+
+                Instruction::Call(target) => {
+                    self.push_frame(instruction_idx)?;
+                    instruction_idx = target;
+                }
+                Instruction::CallIndirect => {
+                    let target = self.pop();
+                    if target == 0 {
+                        return Err(RuntimeError::NullPointer);
+                    }
+                    self.push_frame(instruction_idx)?;
+                    instruction_idx = target as usize;
+                }
+                Instruction::CallNative {
+                    native,
+                    arg_words,
+                    return_words,
+                } => {
+                    let args = self.stack.split_off(self.stack.len() - arg_words);
+                    let return_value = self.call_native(native, &args)?;
+                    if return_words > 0 {
+                        self.stack.push(return_value);
+                        self.stack.extend(std::iter::repeat_n(0, return_words - 1));
+                    }
+                }
+                Instruction::Ret => {
+                    let frame = self
+                        .frames
+                        .pop()
+                        .expect("returned from outside of a function");
+                    self.memory.stack.truncate(frame.base);
+                    instruction_idx = frame.return_idx;
+                }
+
+                Instruction::Alloc(words) => {
+                    let len = self.memory.stack.len();
+                    self.memory.stack.resize(len + words, 0);
+                }
+                Instruction::LocalAddr(offset) => {
+                    let frame = self.frames.last().expect("local outside of a function");
+                    self.stack.push((frame.base + offset) as i64);
+                }
+                Instruction::Load(words) => {
+                    let address = self.pop();
+                    self.stack
+                        .extend_from_slice(self.memory.get(address, words)?);
+                }
+                Instruction::Store(words) => {
+                    let address = self.pop();
+                    let value_start = self.stack.len() - words;
+                    self.memory
+                        .get_mut(address, words)?
+                        .copy_from_slice(&self.stack[value_start..]);
+                    self.stack.truncate(value_start);
+                }
+                Instruction::GetField {
+                    offset,
+                    size,
+                    total,
+                } => {
+                    let value_start = self.stack.len() - total;
+                    let field_start = value_start + offset;
+                    self.stack
+                        .copy_within(field_start..field_start + size, value_start);
+                    self.stack.truncate(value_start + size);
+                }
+
+                Instruction::Halt => {
+                    self.output.flush()?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    fn pop(&mut self) -> i64 {
+        self.stack.pop().expect("popped from empty stack")
+    }
+
+    /// Integers are 32 bit, like `int` in the other backends.
+    fn pop_int(&mut self) -> i32 {
+        self.pop() as i32
+    }
+
+    fn binary(
+        &mut self,
+        op: impl Fn(i32, i32) -> Result<i32, RuntimeError>,
+    ) -> Result<(), RuntimeError> {
+        let rhs = self.pop_int();
+        let lhs = self.pop_int();
+        self.stack.push(op(lhs, rhs)? as i64);
+        Ok(())
+    }
+
+    fn compare(&mut self, op: impl Fn(i64, i64) -> bool) {
+        let rhs = self.pop();
+        let lhs = self.pop();
+        self.stack.push(op(lhs, rhs) as i64);
+    }
+
+    fn push_frame(&mut self, return_idx: usize) -> Result<(), RuntimeError> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(RuntimeError::StackOverflow);
+        }
+        self.frames.push(Frame {
+            return_idx,
+            base: self.memory.stack.len(),
+        });
+        Ok(())
+    }
+
+    /// Reads the 0 terminated string at `address`.
+    fn read_string(&self, address: i64) -> Result<Vec<u8>, RuntimeError> {
+        let mut string = vec![];
+        let mut address = address;
+        loop {
+            match self.memory.get(address, 1)?[0] {
+                0 => return Ok(string),
+                byte => string.push(byte as u8),
+            }
+            address += 1;
+        }
+    }
+
+    fn call_native(&mut self, native: Native, args: &[i64]) -> Result<i64, RuntimeError> {
+        match native {
+            Native::PrintInt => write!(self.output, "{}", args[0] as i32)?,
+            Native::PrintLn => writeln!(self.output)?,
+            Native::PrintStr => {
+                let string = self.read_string(args[0])?;
+                self.output.write_all(&string)?;
+            }
+            Native::PrintChar | Native::Putchar => {
+                self.output.write_all(&[args[0] as u8])?;
+                return Ok(args[0]);
+            }
+            Native::Puts => {
+                let string = self.read_string(args[0])?;
+                self.output.write_all(&string)?;
+                writeln!(self.output)?;
+            }
+            Native::Printf => {
+                let format = self.read_string(args[0])?;
+                let mut args = args[1..].iter();
+                let mut bytes = format.into_iter();
+                while let Some(byte) = bytes.next() {
+                    if byte != b'%' {
+                        self.output.write_all(&[byte])?;
+                        continue;
+                    }
+                    match bytes.next() {
+                        Some(b'd') => {
+                            let value = args.next().copied().unwrap_or_default();
+                            write!(self.output, "{}", value as i32)?;
+                        }
+                        Some(b'c') => {
+                            let value = args.next().copied().unwrap_or_default();
+                            self.output.write_all(&[value as u8])?;
+                        }
+                        Some(b's') => {
+                            let address = args.next().copied().unwrap_or_default();
+                            let string = self.read_string(address)?;
+                            self.output.write_all(&string)?;
+                        }
+                        Some(b'.') if bytes.as_slice().starts_with(b"*s") => {
+                            bytes.nth(1);
+                            let len = args.next().copied().unwrap_or_default();
+                            let address = args.next().copied().unwrap_or_default();
+                            let string = self.read_string(address)?;
+                            let len = (len.max(0) as usize).min(string.len());
+                            self.output.write_all(&string[..len])?;
+                        }
+                        Some(b'%') => self.output.write_all(b"%")?,
+                        Some(other) => self.output.write_all(&[b'%', other])?,
+                        None => self.output.write_all(b"%")?,
+                    }
+                }
+            }
+            Native::StringConcat => {
+                let mut string = self.read_string(args[0])?;
+                string.extend(self.read_string(args[1])?);
+                return Ok(self.memory.alloc_string(&string));
+            }
+            Native::IntToString => {
+                let string = (args[0] as i32).to_string();
+                return Ok(self.memory.alloc_string(string.as_bytes()));
+            }
+            Native::CharToString => return Ok(self.memory.alloc_string(&[args[0] as u8])),
+            Native::BoolToString => {
+                let string: &[u8] = if args[0] != 0 { b"true" } else { b"false" };
+                return Ok(self.memory.alloc_string(string));
+            }
+            Native::Strlen => return Ok(self.read_string(args[0])?.len() as i64),
+            Native::Assert => {
+                if args[0] == 0 {
+                    return Err(RuntimeError::AssertionFailed);
+                }
+            }
+            Native::StrSliceCheck => {
+                let [start, end, len] = [args[0], args[1], args[2]].map(|arg| arg as i32);
+                if !(0 <= start && start <= end && end <= len) {
+                    return Err(RuntimeError::SliceOutOfBounds { start, end, len });
+                }
+            }
+        }
+
+        Ok(0)
+    }
+}
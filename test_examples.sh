@@ -15,6 +15,17 @@ for FILE in ./examples/*.clara; do
 
   printf "$FILE: "
 
+  # Pretty printing the pretty printed source has to give the same output,
+  # which only happens when both parse to the same AST
+  PRETTY_OUT=`./target/debug/clara --pretty-print "$FILE" 2>&1`
+  REPRETTY_OUT=`printf "%s\n" "$PRETTY_OUT" | ./target/debug/clara --pretty-print - 2>&1`
+  if [ "$PRETTY_OUT" != "$REPRETTY_OUT" ]; then
+    printf "ROUND TRIP ERR\n"
+    printf "%s\n" "$REPRETTY_OUT" | diff <(printf "%s\n" "$PRETTY_OUT") -
+    EXIT_CODE=1
+    continue
+  fi
+
   CLARAC_OUT=`./target/debug/clara "$FILE" 2>&1`
 
   if [ $? -eq 0 ]; then
@@ -25,12 +36,30 @@ for FILE in ./examples/*.clara; do
         EXPECTED_STDOUT=`cat "$EXPECTED_STDOUT_FILE"`
         ACTUAL_STDOUT=`./build/a.out`
 
-        if [ "$EXPECTED_STDOUT" = "$ACTUAL_STDOUT" ]; then
-          ((TESTS_SUCCESSFUL += 1))
-          echo "OK"
+        # The program translated to C has to behave the same
+        C_FILE="./build/`basename "${FILE%.*}"`.c"
+        C_OUT=`./target/debug/clara --emit c "$FILE" 2>&1 && gcc -o ./build/c.out "$C_FILE" ./lib/clara.c 2>&1`
+        if [ $? -eq 0 ]; then
+          C_STDOUT=`./build/c.out`
         else
+          C_STDOUT="$C_OUT"
+        fi
+
+        # And so does running it in the VM
+        VM_STDOUT=`./target/debug/clara --interpret "$FILE" 2>&1`
+
+        if [ "$EXPECTED_STDOUT" != "$ACTUAL_STDOUT" ]; then
           printf "INCORRECT OUTPUT\n"
           printf "$ACTUAL_STDOUT\n" | diff - "$EXPECTED_STDOUT_FILE"
+        elif [ "$EXPECTED_STDOUT" != "$C_STDOUT" ]; then
+          printf "INCORRECT C OUTPUT\n"
+          printf "%s\n" "$C_STDOUT" | diff - "$EXPECTED_STDOUT_FILE"
+        elif [ "$EXPECTED_STDOUT" != "$VM_STDOUT" ]; then
+          printf "INCORRECT VM OUTPUT\n"
+          printf "%s\n" "$VM_STDOUT" | diff - "$EXPECTED_STDOUT_FILE"
+        else
+          ((TESTS_SUCCESSFUL += 1))
+          echo "OK"
         fi
       else
         ((TESTS_SUCCESSFUL += 1))
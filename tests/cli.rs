@@ -0,0 +1,2353 @@
+use std::{
+    io::Write,
+    process::{Command, Output, Stdio},
+};
+
+use serde_json::{json, Value};
+
+/// Runs the compiler with `args`, passing `source` on stdin.
+fn run_clara(args: &[&str], source: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clara"))
+        .args(args)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("compiler binary should start");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn dump_tokens() {
+    let output = run_clara(&["--dump-tokens"], "x = a <= 1;");
+    assert!(output.status.success());
+
+    let tokens: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        tokens,
+        json!([
+            { "kind": "identifier", "start": 0, "len": 1 },
+            { "kind": "`=`", "start": 2, "len": 1 },
+            { "kind": "identifier", "start": 4, "len": 1 },
+            { "kind": "`<=`", "start": 6, "len": 2 },
+            { "kind": "integer literal", "start": 9, "len": 1 },
+            { "kind": "`;`", "start": 10, "len": 1 },
+        ])
+    );
+}
+
+#[test]
+fn dump_tokens_with_lex_error() {
+    let output = run_clara(&["--dump-tokens", "--json-diagnostics"], "a $ b");
+    assert!(!output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let mut lines = stdout.lines();
+    let error: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(error["code"], "E0001");
+
+    let tokens: Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+    assert_eq!(tokens.as_array().unwrap().len(), 3);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn dump_ast() {
+    let output = run_clara(&["--dump-ast"], "fn main() { x = 1; }");
+    assert!(output.status.success());
+
+    let program: Value = serde_json::from_slice(&output.stdout).unwrap();
+    let main = &program["functions"][0];
+    assert_eq!(main["name"], "main");
+    assert_eq!(
+        main["body"]["statements"][0]["Expression"]["Assignment"][1]["Literal"]["Int"][0],
+        1
+    );
+    // The implicit `unit` return type isn't anywhere in the source
+    assert_eq!(main["return_type_span"]["source"], u64::MAX);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn dump_ast_with_parse_error() {
+    let output = run_clara(&["--dump-ast"], "fn main() {}\nlet x = 1;");
+    assert!(!output.status.success());
+    assert!(!output.stderr.is_empty());
+
+    // Everything up to the error is still dumped
+    let program: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(program["functions"][0]["name"], "main");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn pub_declarations() {
+    let output = run_clara(
+        &["--dump-ast"],
+        "pub const LIMIT: int = 3;
+pub struct Point { x: int }
+opaque struct Handle;
+pub extern fn puts(s: ->c_char): int;
+pub fn main() { const LOCAL: int = 1; }
+fn helper() {}",
+    );
+    assert!(output.status.success());
+
+    let program: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(program["consts"][0]["is_public"], true);
+    assert_eq!(program["structs"][0]["Transparent"][5], true);
+    assert_eq!(program["structs"][1]["Opaque"][4], false);
+    assert_eq!(program["extern_functions"][0]["is_public"], true);
+    assert_eq!(program["functions"][0]["is_public"], true);
+    assert_eq!(
+        program["functions"][0]["body"]["statements"][0]["Const"]["is_public"],
+        false
+    );
+    assert_eq!(program["functions"][1]["is_public"], false);
+}
+
+/// Typechecks `source` without emitting code, returning the JSON diagnostics it reports.
+fn check_diagnostics(source: &str) -> Vec<Value> {
+    let output = run_clara(&["--no-emit", "--json-diagnostics"], source);
+    String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+#[test]
+fn struct_literals_in_parenthesized_conditions() {
+    let diagnostics = check_diagnostics(
+        "struct Range { start: int, end: int }
+impl Range {
+  fn is_empty(self: Range): bool { self.start >= self.end }
+}
+fn main() {
+  if (Range { start: 0, end: 1 }).is_empty() {}
+  while (Range { start: 1, end: 1 }).is_empty() == false {}
+  let pair = (Range { start: 0, end: 2 }, 1);
+  if pair.0.end == (Range { start: 0, end: 2 }).end {}
+}",
+    );
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn misplaced_pub() {
+    let diagnostics = check_diagnostics("pub enum Color { Red }\nfn main() {}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0101");
+    assert_eq!(diagnostics[0]["span"]["line"], 1);
+    assert_eq!(diagnostics[0]["span"]["column"], 5);
+}
+
+/// Checks `source` without emitting code, returning the diagnostics it renders for the terminal
+/// without colors and trailing whitespace.
+fn rendered_diagnostics(source: &str) -> String {
+    let output = run_clara(&["--no-emit"], source);
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let mut rendered = String::new();
+    let mut chars = stderr.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            // Color escape sequences end in `m`
+            chars.by_ref().find(|&c| c == 'm');
+        } else {
+            rendered.push(c);
+        }
+    }
+    rendered
+        .lines()
+        .map(|line| format!("{}\n", line.trim_end()))
+        .collect()
+}
+
+#[test]
+fn rendered_lex_error() {
+    assert_eq!(
+        rendered_diagnostics("fn main() {\n  let c = '';\n}"),
+        "error[E0007]: empty character literal
+  ┌─ -:2:11
+  │
+2 │   let c = '';
+  │           ^^
+
+"
+    );
+}
+
+#[test]
+fn rendered_parse_error() {
+    assert_eq!(
+        rendered_diagnostics("fn main() {\n  let = 1;\n}"),
+        "error[E0102]: expected identifier
+  ┌─ -:2:7
+  │
+2 │   let = 1;
+  │       ^
+
+"
+    );
+}
+
+#[test]
+fn rendered_type_error() {
+    assert_eq!(
+        rendered_diagnostics("fn main() {\n  let x: bool = 1;\n}"),
+        "error: value does not match type annotation
+  ┌─ -:2:17
+  │
+2 │   let x: bool = 1;
+  │          ----   ^ value has type {integer}
+  │          │
+  │          expected type bool
+
+"
+    );
+}
+
+#[test]
+fn json_diagnostic_spans() {
+    let span = |start: usize, len: usize, line: usize, column: usize| {
+        json!({
+            "start": start,
+            "end": start + len,
+            "len": len,
+            "line": line,
+            "column": column,
+        })
+    };
+    let cases = [
+        (
+            "fn main() {\n  let x = $;\n}",
+            json!({
+                "code": "E0001",
+                "message": "unknown character `$` encountered",
+                "span": span(22, 1, 2, 11),
+            }),
+        ),
+        (
+            "fn main() {\n  let = 1;\n}",
+            json!({
+                "code": "E0102",
+                "message": "expected identifier",
+                "span": span(18, 1, 2, 7),
+            }),
+        ),
+        (
+            "fn main() {\n  return missing;\n}",
+            json!({
+                "message": "reference to unknown variable `missing` in function `main`",
+                "span": span(21, 7, 2, 10),
+            }),
+        ),
+        (
+            "fn foo() {}\nfn foo() {}\nfn main() {}",
+            json!({
+                "message": "function `foo` is defined more than once",
+                "span": span(15, 3, 2, 4),
+                "first_span": span(3, 3, 1, 4),
+            }),
+        ),
+    ];
+    for (source, expected) in cases {
+        let diagnostics = check_diagnostics(source);
+        assert_eq!(diagnostics[0], expected, "{source}");
+    }
+}
+
+#[test]
+fn unknown_multibyte_character() {
+    let diagnostics = check_diagnostics("fn main() {\n  let é = 1;\n}");
+    assert_eq!(
+        diagnostics[0]["message"],
+        "unknown character `é` encountered"
+    );
+    assert_eq!(diagnostics[0]["span"]["start"], 18);
+    assert_eq!(diagnostics[0]["span"]["len"], 2);
+    assert_eq!(diagnostics[0]["span"]["column"], 7);
+    // The column of the `=` after it counts `é` as a single character
+    let last = diagnostics.last().unwrap();
+    assert_eq!(last["span"]["start"], 21);
+    assert_eq!(last["span"]["column"], 9);
+}
+
+#[test]
+fn duplicate_function_definition() {
+    let diagnostics = check_diagnostics("fn foo() {}\nfn foo(x: int) {}\nfn main() {}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "function `foo` is defined more than once"
+    );
+    assert_eq!(diagnostics[0]["first_span"]["line"], 1);
+    assert_eq!(diagnostics[0]["span"]["line"], 2);
+}
+
+#[test]
+fn duplicate_extern_function_definition() {
+    let diagnostics = check_diagnostics("extern fn foo();\nfn main() {}\nfn foo() {}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "function `foo` is defined more than once"
+    );
+    assert_eq!(diagnostics[0]["first_span"]["line"], 1);
+    assert_eq!(diagnostics[0]["span"]["line"], 3);
+}
+
+#[test]
+fn local_variable_shadows_function() {
+    let diagnostics = check_diagnostics(
+        "fn foo(): int { return 1; }\nfn main() { let foo = 2; let bar = foo + 1; }",
+    );
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+}
+
+#[test]
+fn missing_return() {
+    let diagnostics = check_diagnostics(
+        "fn a(x: int): int { if x > 0 { return 1; } }
+fn b(x: int): int { while x > 0 { return 1; } }
+fn c(x: int): int { if x > 0 { return 1; } else { return 2; } }
+fn d(x: int): int { loop { if x > 0 { return 1; } } }
+fn e(x: int): int { return x; let y = 1; }
+fn main() {}",
+    );
+    let lines: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            assert!(diagnostic["message"]
+                .as_str()
+                .unwrap()
+                .ends_with("may not return a value"));
+            diagnostic["span"]["line"].as_u64().unwrap()
+        })
+        .collect();
+    assert_eq!(lines, [1, 2]);
+}
+
+#[test]
+fn array_index_bounds() {
+    let diagnostics = check_diagnostics(
+        "const LAST: int = 2;
+fn main() {
+  let xs = [1, 2, 3];
+  let a = xs[0] + xs[LAST];
+  let b = xs[3];
+  let c = xs[-1];
+  let s: [int] = xs;
+  let d = s[5];
+  let e = xs[true];
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "index 3 is out of bounds for an array of 3 elements",
+            "index -1 is out of bounds for an array of 3 elements",
+            "array index has non-integer type `bool`",
+        ]
+    );
+}
+
+#[test]
+fn const_evaluation() {
+    let source = "extern fn print_int(n: int);
+const AREA: int = WIDTH * 2;
+const WIDTH: int = 21;
+fn main() {
+  print_int(AREA);
+}";
+    let diagnostics = check_diagnostics(source);
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    let output = run_clara(&["--interpret"], source);
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "42");
+
+    let diagnostics = check_diagnostics(
+        "const MAX: int = 2147483647;
+const NEXT: int = MAX + 1;
+const WRAPPED: int = 2147483647 + 1;
+fn main() {
+  const HALF: int = MAX / 0;
+  const DOUBLE: int = 2147483647 * 2;
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("`+` in constant initializer overflows", 2),
+            ("`+` in constant initializer overflows", 3),
+            ("division by zero in constant initializer", 5),
+            ("`*` in constant initializer overflows", 6),
+        ]
+    );
+}
+
+#[test]
+fn generic_functions() {
+    let diagnostics = check_diagnostics(
+        "fn identity<T>(x: T): T { return x; }
+fn zero<T>(): int { return 0; }
+fn plain(x: int): int { return x; }
+fn main() {
+  let a: bool = identity(true) && identity::<bool>(false);
+  let b: int = identity(1) + zero::<char>();
+  let c = zero();
+  let d = identity::<int, int>(1);
+  let e = plain::<int>(1);
+  let f: int = identity('c');
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "cannot infer type parameter `T` of `zero`",
+            "`identity` expects 1 type arguments but 2 were given",
+            "`plain` expects 0 type arguments but 1 were given",
+            "value has type `char` but expected `int`",
+        ]
+    );
+}
+
+#[test]
+fn unbounded_generic_recursion() {
+    let diagnostics = check_diagnostics(
+        "fn grow<T>(x: T, n: int) { if n > 0 { grow((x, x), n - 1); } }
+fn countdown<T>(x: T, n: int) { if n > 0 { countdown(x, n - 1); } }
+fn main() {
+  grow(1, 3);
+  countdown(1, 3);
+}",
+    );
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(
+        diagnostics[0]["message"],
+        "generic instantiation of `grow` recurses without bound"
+    );
+    assert_eq!(diagnostics[0]["span"]["line"], 1);
+}
+
+#[test]
+fn generic_structs() {
+    let diagnostics = check_diagnostics(
+        "struct Pair<A, B> { first: A, second: B }
+struct Point { x: int }
+fn main() {
+  let a: Pair<int, bool> = Pair { first: 1, second: true };
+  let b: int = a.first + Pair<int, int> { first: 2, second: 3 }.second;
+  let c: bool = a.first;
+  let d: Pair<int> = Pair { first: 1, second: 2 };
+  let e: Pair<int, Missing> = Pair { first: 1, second: 2 };
+  let f = Point<int> { x: 1 };
+  let g = Pair<int, char> { first: 1, second: 2 };
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "value has type `int` but expected `bool`",
+            "`Pair` expects 2 type arguments but 1 were given",
+            "reference to unknown type `Pair<int, Missing>`",
+            "`Point` expects 0 type arguments but 1 were given",
+            "field `second` has incorrect type, expected `char` but found `{integer}`",
+        ]
+    );
+}
+
+#[test]
+fn generic_struct_type_arg_count() {
+    let diagnostics = check_diagnostics(
+        "struct Pair<A, B> { first: A, second: B }
+struct Point { x: int }
+fn take(pair: ->Pair<int>) {}
+fn main() {
+  let points: [Point<int>; 2] = [Point { x: 1 }, Point { x: 2 }];
+  let nested: Pair<Pair<int>, int> = Pair { first: 1, second: 2 };
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("`Pair` expects 2 type arguments but 1 were given", 3),
+            ("`Point` expects 0 type arguments but 1 were given", 5),
+            ("`Pair` expects 2 type arguments but 1 were given", 6),
+        ]
+    );
+}
+
+#[test]
+fn traits() {
+    let diagnostics = check_diagnostics(
+        "trait Show { fn show(self: Self): int; fn id(self: Self): int; }
+trait Show { fn other(self: Self); }
+struct A { x: int }
+struct B { x: int }
+impl Show for A { fn show(self: A): int { return self.x; } }
+impl Show for B {
+  fn show(self: ->B): int { return self.x; }
+  fn id(self: B): int { return 0; }
+  fn extra(self: B) {}
+}
+impl Missing for A {}
+fn display<T: Show>(value: T): int { return value.show(); }
+fn unbounded<T: Unknown>(value: T) {}
+fn describe<T: Show>(value: T, other: ->T): int {
+  let total = value.show() + other.id();
+  let copy: T = value;
+  copy.area() + value.size()
+}
+fn main() {
+  let a = display(B { x: 1 });
+  let b = display(1);
+  let c = describe(A { x: 1 }, ->A { x: 2 });
+  let d = describe(B { x: 1 }, ->B { x: 2 });
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "trait `Show` is declared more than once",
+            "reference to unknown trait `Unknown`",
+            "missing method `id` of trait `Show`",
+            "method `show` has type `fn(->B): int` but trait `Show` declares `fn(B): int`",
+            "method `extra` is not a member of trait `Show`",
+            "reference to unknown trait `Missing`",
+            "the bounds of type parameter `T` declare no method `area`",
+            "the bounds of type parameter `T` declare no method `size`",
+            "type `int` doesn't implement trait `Show`",
+        ]
+    );
+}
+
+#[test]
+fn generic_bodies() {
+    let diagnostics = check_diagnostics(
+        "trait Show { fn show(self: ->Self): int; fn copy(self: Self): Self; }
+struct A { x: int }
+impl Show for A {
+  fn show(self: ->A): int { return self.x; }
+  fn copy(self: A): A { return self; }
+}
+fn display<T: Show>(value: T): int { return value.copy().show() + shown(->value); }
+fn shown<U: Show>(value: ->U): int { return value.show(); }
+fn parenthesized<T: Show>(x: ->T): int { return (x).secret(); }
+fn shadowed<T: Show>(x: T): int {
+  if true { let x = 1; }
+  return x.secret();
+}
+fn unbounded<T>(x: T): int { return shown(->x); }
+fn add<T>(a: T, b: T): T { return a + b; }
+fn never_called<T>(x: T) { let y: bool = 1; }
+fn called_twice<T>(x: T) { let y: bool = 1; }
+fn main() {
+  let a = display(A { x: 1 });
+  called_twice(1);
+  called_twice(true);
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "the bounds of type parameter `T` declare no method `secret`",
+            "the bounds of type parameter `T` declare no method `secret`",
+            "type `T` doesn't implement trait `Show`",
+            "operator applied to a value of type parameter `T`",
+            "value has type `{integer}` but expected `bool`",
+            "value has type `{integer}` but expected `bool`",
+        ]
+    );
+    let lines: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["span"]["line"].as_u64().unwrap())
+        .collect();
+    assert_eq!(lines, [9, 12, 14, 15, 16, 17]);
+}
+
+#[test]
+fn destructuring_let() {
+    let diagnostics = check_diagnostics(
+        "struct Point { x: int, y: int }
+struct Other { x: int }
+fn main() {
+  let p = Point { x: 1, y: 2 };
+  let Point { x, z } = p;
+  let Other { x: a } = p;
+  let Missing { b } = p;
+  let (c, d) = (1, 2, 3);
+  let (e, f) = p;
+  let (g, g) = (1, 2);
+  let h: bool = x;
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "struct type `Point` has no field by the name of `z`",
+            "pattern destructures struct `Other` but the value has type `Point`",
+            "reference to unknown type `Missing`",
+            "pattern destructures a tuple of 2 elements but the value has type `({integer}, {integer}, {integer})`",
+            "pattern destructures a tuple of 2 elements but the value has type `Point`",
+            "variable name `g` used more than once in this function",
+            "value has type `int` but expected `bool`",
+        ]
+    );
+}
+
+#[test]
+fn string_interpolation() {
+    let diagnostics = check_diagnostics(
+        "struct Point { x: int }
+fn main() {
+  let p = Point { x: 1 };
+  let a = \"point: ${p}\";
+  let b: int = \"x: ${p.x}\";
+  let c = \"${missing}!\";
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "type `Point` can't be converted to a string",
+            "value has type `->c_char` but expected `int`",
+            "reference to unknown variable `missing` in function `main`",
+        ]
+    );
+
+    let diagnostics = check_diagnostics("fn main() {\n  let a = \"${} ${1 2}\";\n}");
+    let codes: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["code"].as_str().unwrap())
+        .collect();
+    assert_eq!(codes, ["E0009", "E0101"]);
+}
+
+#[test]
+fn unterminated_multiline_string() {
+    let diagnostics = check_diagnostics("fn main() {\n  let s = \"\"\"\n    text\n  \"\";\n}");
+    // The string runs to the end of the input, so the parser reports the missing rest too
+    assert_eq!(diagnostics[0]["code"], "E0010");
+    assert_eq!(diagnostics[0]["message"], "unterminated multi-line string");
+    assert_eq!(diagnostics[0]["span"]["line"], 2);
+    assert_eq!(diagnostics[0]["span"]["column"], 11);
+}
+
+#[test]
+fn raw_strings() {
+    let diagnostics =
+        check_diagnostics("fn main() {\n  let a = r\"\\q \\\";\n  let b = r#\"\"\\x\"\"#;\n}");
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+
+    let diagnostics = check_diagnostics("fn main() {\n  let a = r#\"text\";\n}");
+    assert_eq!(diagnostics[0]["code"], "E0002");
+}
+
+#[test]
+fn block_tail_expressions() {
+    let diagnostics = check_diagnostics(
+        "fn flag(): int { true }
+fn main() {
+  let x: int = { let inner = 1; inner == 1 };
+  inner;
+  5
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "return value has type `bool` but function expected type `int`",
+                1
+            ),
+            ("value has type `bool` but expected `int`", 3),
+            (
+                "reference to unknown variable `inner` in function `main`",
+                4
+            ),
+            (
+                "return value has type `{integer}` but function expected type `unit`",
+                5
+            ),
+        ]
+    );
+
+    // A block statement ends at its brace instead of continuing as an operand
+    let output = run_clara(
+        &["--interpret"],
+        "extern fn printf(format: ->c_char, ...): c_int;
+fn main() {
+  let mut x = 1;
+  { x = x + 1; }
+  -x;
+  printf(\"%d\\n\", { let y = x * 10; y + 1 });
+}",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "21\n");
+}
+
+#[test]
+fn if_expressions() {
+    let diagnostics = check_diagnostics(
+        "fn noop() {}
+fn main() {
+  let a: int = if true { 1 } else { false };
+  let b = if true { 1 };
+  let c = if true { noop(); };
+  let d: bool = if true { loop {} } else { false };
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "`if` branch has type `{integer}` but `else` branch has type `bool`",
+                3
+            ),
+            (
+                "`if` without `else` is `unit` but its branch has type `{integer}`",
+                4
+            ),
+        ]
+    );
+
+    let output = run_clara(
+        &["--interpret"],
+        "extern fn printf(format: ->c_char, ...): c_int;
+fn main() {
+  let x = 3;
+  printf(\"%d\\n\", if x > 2 { x * 2 } else { 0 });
+  printf(\"%d\\n\", 1 + if x > 5 { 10 } else if x > 1 { 20 } else { 30 });
+  (if x > 0 { printf(\"unit\\n\"); });
+}",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "6\n21\nunit\n");
+}
+
+#[test]
+fn named_arguments() {
+    let diagnostics = check_diagnostics(
+        "fn area(width: int, height: int): int { width * height }
+fn main() {
+  area(1, widht: 2);
+  area(height: 2);
+  area(3, width: 2);
+  let f = area;
+  f(width: 1, height: 2);
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("`area` has no parameter named `widht`", 3),
+            ("missing argument for parameter `height` of `area`", 3),
+            ("missing argument for parameter `width` of `area`", 4),
+            ("parameter `width` is given more than once", 5),
+            ("missing argument for parameter `height` of `area`", 5),
+            ("`f` has no parameter named `width`", 7),
+            ("`f` has no parameter named `height`", 7),
+        ]
+    );
+
+    let diagnostics = check_diagnostics(
+        "fn area(width: int, height: int): int { width * height }
+fn main() { area(width: 1, 2); }",
+    );
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0109");
+    assert_eq!(diagnostics[0]["span"]["line"], 2);
+    assert_eq!(diagnostics[0]["span"]["column"], 28);
+}
+
+#[test]
+fn labeled_loops() {
+    let diagnostics = check_diagnostics(
+        "fn main() {
+  'outer: while true {
+    for i in 0..3 {
+      break 'inner;
+    }
+    continue 'outer;
+  }
+  loop { break 'outer; }
+  break 'outer;
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+                diagnostic["span"]["column"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("no enclosing loop is labeled `'inner`", 4, 13),
+            ("no enclosing loop is labeled `'outer`", 8, 16),
+            ("`break` outside of a loop", 9, 3),
+        ]
+    );
+
+    let diagnostics = check_diagnostics("fn main() { 'outer: if true {} }");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0110");
+    assert_eq!(diagnostics[0]["span"]["column"], 13);
+}
+
+#[test]
+fn unterminated_char_literal_is_not_a_label() {
+    let diagnostics = check_diagnostics("fn main() {\n  let d = 'a;\n}");
+    assert_eq!(diagnostics.len(), 1, "{diagnostics:?}");
+    assert_eq!(diagnostics[0]["code"], "E0005");
+    assert_eq!(diagnostics[0]["span"]["line"], 2);
+    assert_eq!(diagnostics[0]["span"]["column"], 11);
+}
+
+#[test]
+fn while_let_loops() {
+    let diagnostics = check_diagnostics(
+        "enum Token {
+  End,
+  Digit(int),
+}
+
+fn next(): Token { Token::End }
+
+fn main() {
+  while let Token::Digit(d) = next() {
+    let x: int = d;
+  }
+  while let 1 = true {}
+  let y = d;
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+                diagnostic["span"]["column"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "pattern has type `{integer}` but matched value has type `bool`",
+                12,
+                13
+            ),
+            (
+                "reference to unknown variable `d` in function `main`",
+                13,
+                11
+            ),
+        ]
+    );
+}
+
+#[test]
+fn switch_statements() {
+    let diagnostics = check_diagnostics(
+        "enum Color { Red, Green, Blue }
+fn pick(): Color { Color::Red }
+fn main() {
+  let n = 3;
+  switch n {
+    case 1, 1:
+    case n:
+    case 'a':
+  }
+  switch pick() {
+    case Color::Red:
+    case Color::Purple:
+  }
+  switch true {
+    case true, false:
+    default:
+  }
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+                diagnostic["span"]["column"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("value is already handled by an earlier case", 6, 13),
+            ("switch case is not a constant expression", 7, 10),
+            (
+                "pattern has type `char` but matched value has type `{integer}`",
+                8,
+                10
+            ),
+            (
+                "switch on `{integer}` without `default` doesn't handle every value",
+                5,
+                10
+            ),
+            (
+                "enum type `Color` has no variant by the name of `Purple`",
+                12,
+                17
+            ),
+            (
+                "switch on `Color` without `default` doesn't handle Color::Green, Color::Blue",
+                10,
+                10
+            ),
+            ("can't switch on a value of type `bool`", 14, 10),
+        ]
+    );
+    assert_eq!(diagnostics[3]["severity"], "warning");
+    assert_eq!(diagnostics[5]["severity"], "warning");
+
+    let diagnostics = check_diagnostics("fn main() { switch 1 { default: default: } }");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0101");
+    assert_eq!(diagnostics[0]["span"]["column"], 33);
+}
+
+#[test]
+fn array_repeat() {
+    let diagnostics = check_diagnostics("fn main() {\n  let a: [int; 2] = [1; 3];\n}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "array has 3 elements but expected 2"
+    );
+
+    // The count has to be an integer literal
+    let diagnostics = check_diagnostics("fn main() {\n  let n = 3;\n  let b = [0; n];\n}");
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "E0101");
+    assert_eq!(diagnostics[0]["span"]["line"], 3);
+    assert_eq!(diagnostics[0]["span"]["column"], 15);
+}
+
+#[test]
+fn defer_statements() {
+    let diagnostics = check_diagnostics(
+        "fn value(): int { 1 }
+fn main() {
+  defer 5;
+  defer value();
+  defer main();
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("only function and method calls can be deferred", 3),
+            ("deferred call returns `int` instead of `unit`", 4),
+        ]
+    );
+
+    let output = run_clara(
+        &["--interpret"],
+        "extern fn printf(format: ->c_char, ...): c_int;
+fn say(n: int) { printf(\"%d\\n\", n); }
+fn pick(n: int): int {
+  defer say(1);
+  if n > 0 {
+    defer say(2);
+    return n * 10;
+  }
+  say(3);
+  n
+}
+fn main() {
+  say(pick(4));
+  say(pick(0));
+}",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "2\n1\n40\n3\n1\n0\n"
+    );
+}
+
+#[test]
+fn str_values() {
+    let diagnostics = check_diagnostics(
+        "extern fn puts(s: ->c_char);
+const NAME: ->c_char = \"clara\";
+fn main() {
+  let s = \"hi\";
+  puts(s);
+  let n: int = str_len(s, 1);
+  let t = str_slice(s, true, 2);
+  let c: ->c_char = \"ok\";
+  let words: [->c_char; 2] = [\"a\", \"b\"];
+  puts(str_data(s));
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "incorrect argument type in function call, expected type ->c_char but found type str",
+                5
+            ),
+            (
+                "incorrect number of arguments to function call, expected 1 but found 2",
+                6
+            ),
+            (
+                "incorrect argument type in function call, expected type int but found type bool",
+                7
+            ),
+        ]
+    );
+}
+
+#[test]
+fn str_slice_bounds() {
+    let source = "extern fn printf(format: ->c_char, ...): c_int;
+fn main() {
+  let s = \"hello\";
+  printf(\"%d\\n\", str_len(str_slice(s, 1, 5)));
+  let end = str_len(s) + 1;
+  printf(\"%d\\n\", str_len(str_slice(s, 2, end)));
+}";
+    let output = run_clara(&["--interpret"], source);
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "4\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "runtime error: str_slice out of bounds: start 2, end 6, length 5\n"
+    );
+
+    // Compiled programs call the same check in `lib/clara.c`
+    let output = run_clara(&["--emit", "llvm-ir"], source);
+    assert!(output.status.success());
+    let ir = String::from_utf8(output.stdout).unwrap();
+    assert!(ir.contains("declare void @str_slice_check(i32, i32, i32)"));
+    assert_eq!(ir.matches("call void @str_slice_check(").count(), 2);
+}
+
+#[test]
+fn interpolated_str_values() {
+    let source = "extern fn printf(format: ->c_char, ...): c_int;
+fn show(s: str) {
+  printf(\"%.*s\\n\", str_len(s), str_data(s));
+}
+fn main() {
+  let x = 42;
+  printf(\"%d\\n\", str_len(\"x\"));
+  printf(\"%d\\n\", str_len(\"x ${x}\"));
+  show(\"x = ${x}\");
+  let s: str = \"${x}\";
+  show(s);
+}";
+    let diagnostics = check_diagnostics(source);
+    assert!(diagnostics.is_empty(), "{diagnostics:?}");
+    let output = run_clara(&["--interpret"], source);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "1\n4\nx = 42\n42\n"
+    );
+
+    let output = run_clara(&["--emit", "llvm-ir"], source);
+    assert!(output.status.success());
+    let ir = String::from_utf8(output.stdout).unwrap();
+    assert!(ir.contains("declare i64 @strlen(i8*)"));
+}
+
+#[test]
+fn unions() {
+    let diagnostics = check_diagnostics(
+        "union Number { whole: int, letter: char }
+fn letter_of(number: Number): char {
+  number.letter
+}
+fn main() {
+  let number = Number { whole: 65 };
+  let whole = number.whole;
+  let letter = number.letter;
+  let mut changing = Number { letter: 'a' };
+  let other = changing.letter;
+  let empty = Number {};
+  let both = Number { whole: 1, letter: 'b' };
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["severity"].as_str().unwrap_or("error"),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("unsafe operation outside of an `unsafe` block", "error", 3),
+            ("unsafe operation outside of an `unsafe` block", "error", 8),
+            ("unsafe operation outside of an `unsafe` block", "error", 10),
+            (
+                "union literal of `Number` has to initialize exactly one field",
+                "error",
+                11
+            ),
+            (
+                "union literal of `Number` has to initialize exactly one field",
+                "error",
+                12
+            ),
+        ]
+    );
+}
+
+#[test]
+fn unsafe_blocks() {
+    let diagnostics = check_diagnostics(
+        "#[unsafe]
+extern fn free(ptr: ->c_char);
+extern fn puts(s: ->c_char);
+union Bits { whole: int, letter: char }
+fn read(p: ->int): int {
+  unsafe { *p }
+}
+fn main() {
+  let x = 1;
+  let p = ->x;
+  let y = *p;
+  let q = cast<->c_char>(p);
+  free(\"text\");
+  let bits = Bits { letter: 'a' };
+  let whole = bits.whole;
+  unsafe {
+    let y = *p;
+    let q = cast<->c_char>(p);
+    free(q);
+    if x > 0 {
+      puts(q);
+      let whole = bits.whole;
+    }
+  }
+  let z = unsafe_count();
+}
+fn unsafe_count(): int {
+  *(->1)
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("unsafe operation outside of an `unsafe` block", 11),
+            ("unsafe operation outside of an `unsafe` block", 12),
+            ("unsafe operation outside of an `unsafe` block", 13),
+            ("unsafe operation outside of an `unsafe` block", 15),
+            ("unsafe operation outside of an `unsafe` block", 28),
+        ]
+    );
+}
+
+#[test]
+fn casts() {
+    let diagnostics = check_diagnostics(
+        "struct Point { x: int }
+fn main() {
+  let p = Point { x: 1 };
+  let a = cast<int>(p);
+  let b = cast<bool>(1);
+  unsafe {
+    let c = cast<->int>(4096);
+    let d = cast<->c_char>(->p);
+    let e = cast<int>(d);
+  }
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["severity"].as_str().unwrap_or("error"),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("can't cast `Point` to `int`", "error", 4),
+            ("can't cast `{integer}` to `bool`", "error", 5),
+            ("casting an integer to `->int`", "warning", 7),
+            ("can't cast `->c_char` to `int`", "error", 9),
+        ]
+    );
+}
+
+#[test]
+fn struct_equality() {
+    let diagnostics = check_diagnostics(
+        "opaque struct Handle: 8;
+#[eq]
+struct Wrapper { handle: Handle }
+#[eq]
+struct Grid { cells: [int; 4] }
+struct Plain { x: int }
+#[eq]
+struct Point { x: int, y: int }
+fn compare(w: Wrapper, g: Grid, p: Plain, q: Point): bool {
+  w == w || g != g || p == p || q == q
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["column"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("values of type `Handle` can't be compared with `==`", 3),
+            ("values of type `[int; 4]` can't be compared with `==`", 13),
+            ("values of type `Plain` can't be compared with `==`", 23),
+        ]
+    );
+}
+
+#[test]
+fn test_runner() {
+    let output = run_clara(
+        &["--test"],
+        "fn double(x: int): int { x * 2 }
+#[test]
+fn doubling() {
+  assert(double(2) == 4);
+  assert_eq(double(x: 0), 0);
+}
+#[test]
+fn wrong_expectation() {
+  assert_eq(double(3), 7);
+}",
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "running 2 tests
+test doubling ... ok
+test wrong_expectation ... FAILED: assertion failed
+
+test result: FAILED. 1 passed; 1 failed
+"
+    );
+
+    let diagnostics = check_diagnostics(
+        "#[test]
+fn takes_input(x: int) {}
+#[test]
+fn returns_value(): int { 1 }
+fn main() { assert_eq(1, true); }",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (
+                "test function `takes_input` has to take no parameters and return `unit`",
+                2
+            ),
+            (
+                "test function `returns_value` has to take no parameters and return `unit`",
+                4
+            ),
+            (
+                "type mismatch in binary operator, type `{integer}` on the left and type `bool` on the right",
+                5
+            ),
+        ]
+    );
+}
+
+#[test]
+fn for_loop_bindings() {
+    let diagnostics = check_diagnostics(
+        "fn main() {
+  let flags = [true, false];
+  for flag in flags {
+    let a: int = flag;
+  }
+  for i, flag in flags {
+    let b: bool = i;
+    let c: int = flag;
+  }
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "value has type `bool` but expected `int`",
+            "value has type `int` but expected `bool`",
+            "value has type `bool` but expected `int`",
+        ]
+    );
+}
+
+#[test]
+fn while_else_is_outside_the_loop() {
+    let diagnostics = check_diagnostics(
+        "fn main() {
+  while false {
+  } else {
+    let a: int = true;
+    break;
+  }
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic["message"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "value has type `bool` but expected `int`",
+            "`break` outside of a loop",
+        ]
+    );
+}
+
+#[test]
+fn constant_fold_warnings() {
+    let output = run_clara(
+        &["--no-emit", "--json-diagnostics"],
+        "fn main() {
+  let a = (1 + 2) * 3 < 10 && !false;
+  let b = 2147483647 + 1;
+  let c = 7 / (2 - 2);
+}",
+    );
+    assert!(output.status.success());
+
+    let warnings: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let messages: Vec<_> = warnings
+        .iter()
+        .map(|warning| {
+            assert_eq!(warning["severity"], "warning");
+            warning["message"].as_str().unwrap()
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        ["`+` in constant expression overflows", "division by zero"]
+    );
+}
+
+#[test]
+fn warn_unused_variables() {
+    let source = "fn apply(f: fn(int): int, x: int): int { return f(x); }
+fn inc(x: int): int { return x + 1; }
+fn main() {
+  let unused = 1;
+  let _ignored = 2;
+  let mut never_mutated = 3;
+  let mut counter = 0;
+  counter = counter + never_mutated;
+  let mut assigned_only = 0;
+  assigned_only = 1;
+  let callback: fn(int): int = inc;
+  let end = callback(counter);
+  for i in 0..end {
+    let mut pointed = i;
+    let p = ->mut pointed;
+    unsafe {
+      *p = apply(inc, 1);
+    }
+  }
+}";
+    let output = run_clara(
+        &["--no-emit", "--json-diagnostics", "--warn-unused"],
+        source,
+    );
+    assert!(output.status.success());
+
+    let messages: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let warning: Value = serde_json::from_str(line).unwrap();
+            assert_eq!(warning["severity"], "warning");
+            warning["message"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            "unused variable `unused`",
+            "variable `never_mutated` does not need to be mutable",
+            "unused variable `assigned_only`",
+        ]
+    );
+
+    // Without the flag the lints don't run
+    let output = run_clara(&["--no-emit", "--json-diagnostics"], source);
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn repl() {
+    let output = run_clara(
+        &["--repl", "--json-diagnostics"],
+        "let x = 1 + 2
+let y: bool = x
+fn add(a: int, b: int): int {
+  return a + b;
+}
+add(x, 4) < 3
+(x, true)
+",
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let (prompts, rest) = stdout.split_once('{').unwrap();
+    assert_eq!(prompts, "> > ");
+    let (error, rest) = rest.split_once('\n').unwrap();
+    let error: Value = serde_json::from_str(&format!("{{{error}")).unwrap();
+    assert_eq!(
+        error["message"],
+        "value has type `{integer}` but expected `bool`"
+    );
+    assert_eq!(rest, "> ... ... > bool\n> ({integer}, bool)\n> \n");
+}
+
+#[test]
+fn interpret() {
+    let output = run_clara(
+        &["--interpret"],
+        "extern fn print_int(n: int);
+extern fn print_ln();
+
+fn fib(n: int): int {
+  if n < 2 {
+    return n;
+  }
+  return fib(n - 1) + fib(n - 2);
+}
+
+fn main() {
+  let xs = [fib(10), 7 / 2, -7 % 3];
+  for x in xs {
+    print_int(x);
+    print_ln();
+  }
+}",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "55\n3\n-1\n");
+}
+
+#[test]
+fn interpret_errors() {
+    let output = run_clara(
+        &["--interpret"],
+        "fn div(a: int, b: int): int { return a / b; }
+fn main() { div(1, 0); }",
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "runtime error: division by zero\n"
+    );
+
+    let output = run_clara(
+        &["--interpret"],
+        "extern fn abort();
+fn main() { abort(); }",
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: extern function `abort` is not available in the vm\n"
+    );
+}
+
+#[test]
+fn emit_llvm_ir() {
+    let output = run_clara(
+        &["--emit", "llvm-ir"],
+        "extern fn printf(fmt: ->c_char, ...): int;
+fn main() { printf(\"hello %d\\n\", 1); }",
+    );
+    assert!(output.status.success());
+
+    let ir = String::from_utf8(output.stdout).unwrap();
+    assert!(ir.contains("declare i32 @printf(i8*, ...)"));
+    assert!(ir.contains("define void @main()"));
+}
+
+#[test]
+fn inline_functions() {
+    let output = run_clara(
+        &["--emit", "llvm-ir"],
+        "extern fn print_int(n: int);
+#[inline]
+fn square(x: int): int { x * x }
+#[inline]
+fn fourth(x: int): int { square(x) * square(x) }
+#[inline]
+fn forever(n: int): int { forever(n) + 1 }
+#[inline]
+fn even(n: int): bool { n == 0 || odd(n - 1) }
+#[inline]
+fn odd(n: int): bool { n != 0 && even(n - 1) }
+fn main() {
+  let a = 3;
+  let mut b = 4;
+  print_int(fourth(a));
+  print_int(square(b));
+  print_int(forever(a));
+  let c = even(a);
+}",
+    );
+    assert!(output.status.success());
+
+    let ir = String::from_utf8(output.stdout).unwrap();
+    let main = &ir[ir.find("define void @main()").unwrap()..];
+    let main = &main[..main.find("\n}").unwrap()];
+    // `fourth` and the `square` calls in it are inlined, `b` is mutable so `square(b)` isn't
+    assert!(!main.contains("@fourth("));
+    assert_eq!(main.matches("@square(").count(), 1);
+    // Recursive functions are called
+    assert!(main.contains("@forever("));
+    assert!(main.contains("@even("));
+}
+
+#[test]
+fn calling_conventions() {
+    let source = "extern \"stdcall\" fn set_text(text: ->c_char);
+extern \"pascal\" fn old(n: int);
+fn main() { set_text(\"hi\"); old(1); }";
+
+    let output = run_clara(&["--emit", "llvm-ir"], source);
+    assert!(output.status.success());
+    let ir = String::from_utf8(output.stdout).unwrap();
+    assert!(ir.contains("declare x86_stdcallcc void @set_text(i8*)"));
+    assert!(ir.contains("call x86_stdcallcc void @set_text("));
+    assert!(ir.contains("declare void @old(i32)"));
+
+    let diagnostics = check_diagnostics(source);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "unknown calling convention `pascal`"
+    );
+    assert_eq!(diagnostics[0]["severity"], "warning");
+}
+
+#[test]
+fn unsized_opaque_structs() {
+    let diagnostics = check_diagnostics(
+        "opaque struct Window;
+opaque struct Buffer: 64;
+struct Holder { window: Window, buffer: Buffer }
+extern fn create(): ->Window;
+fn show(window: Window) {}
+fn main() {
+  let p = create();
+  unsafe { let window = *p; }
+  let size = sizeof([Window; 2]);
+  let buffer_size = sizeof(Buffer);
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                &diagnostic["span"]["line"],
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("opaque struct `Window` has no known size", &json!(3)),
+            ("opaque struct `Window` has no known size", &json!(5)),
+            ("opaque struct `Window` has no known size", &json!(8)),
+            ("opaque struct `Window` has no known size", &json!(9)),
+        ]
+    );
+}
+
+#[test]
+fn ref_parameters() {
+    let diagnostics = check_diagnostics(
+        "struct Rect { width: int, height: int }
+fn area(rect: ref Rect): int { rect.width * rect.height }
+fn make(): Rect { Rect { width: 1, height: 2 } }
+fn main() {
+  let r = make();
+  let a = area(r);
+  let b = area(make());
+  let c = area(r.width);
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("argument for `ref` parameter is not an l-value", 7),
+            (
+                "incorrect argument type in function call, expected type ->Rect but found type ->int",
+                8
+            ),
+        ]
+    );
+}
+
+#[test]
+fn null_only_fits_nullable_pointers() {
+    let diagnostics = check_diagnostics(
+        "fn main() {
+  let x = 1;
+  let a: ?->int = null;
+  let b: ->int = null;
+  let mut p: ?->int = ->x;
+  let q: ?->int = null;
+  if p != null {
+    p = null;
+    p = q;
+    p = ->x;
+    unsafe { *p; }
+  }
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("value has type `null` but expected `->int`", 4),
+            (
+                "cannot assign a pointer that may be null to `p`, which was checked not to be null",
+                8
+            ),
+            (
+                "cannot assign a pointer that may be null to `p`, which was checked not to be null",
+                9
+            ),
+        ]
+    );
+}
+
+#[test]
+fn offsetof_errors() {
+    let diagnostics = check_diagnostics(
+        "opaque struct Window: 64;
+struct Point { x: int, y: int }
+fn main() {
+  let a = offsetof(Point, \"y\");
+  let b = offsetof(Point, \"z\");
+  let c = offsetof(Window, \"width\");
+  let d = offsetof(int, \"x\");
+  let e = offsetof(Shape, \"x\");
+}",
+    );
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            (
+                diagnostic["message"].as_str().unwrap(),
+                diagnostic["span"]["line"].as_u64().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            ("struct type `Point` has no field by the name of `z`", 5),
+            ("field access on opaque struct type `Window`", 6),
+            ("object of type `int` is not a struct", 7),
+            ("reference to unknown type `Shape`", 8),
+        ]
+    );
+}
+
+/// An s-expression of WebAssembly text, either an atom or a list in parentheses.
+#[derive(Debug, PartialEq)]
+enum Sexpr {
+    Atom(String),
+    List(Vec<Sexpr>),
+}
+
+/// Parses the s-expressions of `text`, returning `None` if its parentheses are unbalanced.
+fn parse_sexprs(text: &str) -> Option<Vec<Sexpr>> {
+    let mut lists = vec![vec![]];
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '(' => lists.push(vec![]),
+            ')' => {
+                let list = lists.pop()?;
+                lists.last_mut()?.push(Sexpr::List(list));
+            }
+            '"' => {
+                let mut atom = String::from('"');
+                while let Some(c) = chars.next() {
+                    atom.push(c);
+                    match c {
+                        '\\' => atom.push(chars.next()?),
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+                lists.last_mut()?.push(Sexpr::Atom(atom));
+            }
+            c if c.is_whitespace() => {}
+            c => {
+                let mut atom = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                lists.last_mut()?.push(Sexpr::Atom(atom));
+            }
+        }
+    }
+
+    if lists.len() == 1 {
+        lists.pop()
+    } else {
+        None
+    }
+}
+
+#[test]
+fn emit_c_unsupported_types() {
+    let output = run_clara(
+        &["--emit", "c"],
+        "fn main() {
+  let empty = [];
+}",
+    );
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: empty arrays without an element type are not supported by the C backend\n"
+    );
+
+    let output = run_clara(&["--emit", "c"], "fn greet(name: string) {}\nfn main() {}");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: `string` values are not supported by the C backend\n"
+    );
+}
+
+#[test]
+fn target_wasm32() {
+    std::fs::create_dir_all("./build").unwrap();
+    let output = run_clara(
+        &["--target", "wasm32"],
+        "extern fn print_int(n: int);
+fn average(a: int, b: int): int { return (a + b) / 2; }
+fn main() { print_int(average(3, 5)); }",
+    );
+    assert!(output.status.success());
+
+    let wat = std::fs::read_to_string("./build/out.wat").unwrap();
+    let sexprs = parse_sexprs(&wat).expect("parentheses should be balanced");
+    let fields = match &sexprs[..] {
+        [Sexpr::List(module)] if module[0] == Sexpr::Atom("module".to_string()) => &module[1..],
+        _ => panic!("expected a single module, got {wat}"),
+    };
+    let average = fields
+        .iter()
+        .find_map(|field| match field {
+            Sexpr::List(func) if func[..2] == [atom("func"), atom("$average")] => Some(func),
+            _ => None,
+        })
+        .expect("module should define `average`");
+    assert_eq!(
+        average[2..],
+        [
+            list(vec![atom("export"), atom("\"average\"")]),
+            list(vec![atom("param"), atom("$a"), atom("i32")]),
+            list(vec![atom("param"), atom("$b"), atom("i32")]),
+            list(vec![atom("result"), atom("i32")]),
+            list(vec![
+                atom("return"),
+                list(vec![
+                    atom("i32.div_s"),
+                    list(vec![
+                        atom("i32.add"),
+                        list(vec![atom("local.get"), atom("$a")]),
+                        list(vec![atom("local.get"), atom("$b")]),
+                    ]),
+                    list(vec![atom("i32.const"), atom("2")]),
+                ]),
+            ]),
+            list(vec![atom("unreachable")]),
+        ]
+    );
+    assert!(wat.contains("(import \"env\" \"print_int\" (func $print_int (param i32)))"));
+}
+
+#[test]
+fn target_wasm32_pointers() {
+    // A file of its own, so that the module doesn't overwrite the one of `target_wasm32`
+    let source_path = std::env::temp_dir().join("wasm_pointers.clara");
+    std::fs::write(
+        &source_path,
+        "extern fn print_int(n: int);
+struct Point { x: int, flag: bool, y: int }
+fn bump(p: ->mut int) { unsafe { *p = *p + 1; } }
+fn main() {
+  let mut count = 41;
+  bump(->mut count);
+  let point = Point { x: count, flag: true, y: 2 };
+  print_int(point.x + point.y);
+}",
+    )
+    .unwrap();
+    std::fs::create_dir_all("./build").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_clara"))
+        .args(["--target", "wasm32"])
+        .arg(&source_path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+
+    let wat = std::fs::read_to_string("./build/wasm_pointers.wat").unwrap();
+    assert!(wat.contains("(global $stack_pointer (mut i32) (i32.const 131072))"));
+    let sexprs = parse_sexprs(&wat).expect("parentheses should be balanced");
+    let fields = match &sexprs[..] {
+        [Sexpr::List(module)] if module[0] == Sexpr::Atom("module".to_string()) => &module[1..],
+        _ => panic!("expected a single module, got {wat}"),
+    };
+    let main = fields
+        .iter()
+        .find_map(|field| match field {
+            Sexpr::List(func) if func[..2] == [atom("func"), atom("$main")] => Some(func),
+            _ => None,
+        })
+        .expect("module should define `main`");
+    let slot = |offset: &str| {
+        list(vec![
+            atom("i32.add"),
+            list(vec![atom("local.get"), atom("$clara_frame")]),
+            list(vec![atom("i32.const"), atom(offset)]),
+        ])
+    };
+    let field = |offset: &str| {
+        list(vec![
+            atom("i32.add"),
+            list(vec![atom("local.get"), atom("$clara_target")]),
+            list(vec![atom("i32.const"), atom(offset)]),
+        ])
+    };
+    // `count` is at the start of the 16 byte frame, `point` after it with `y` aligned to 4 bytes
+    assert_eq!(
+        main[6..],
+        [
+            list(vec![
+                atom("local.set"),
+                atom("$clara_stack_top"),
+                list(vec![atom("global.get"), atom("$stack_pointer")]),
+            ]),
+            list(vec![
+                atom("local.set"),
+                atom("$clara_frame"),
+                list(vec![
+                    atom("i32.sub"),
+                    list(vec![atom("local.get"), atom("$clara_stack_top")]),
+                    list(vec![atom("i32.const"), atom("16")]),
+                ]),
+            ]),
+            list(vec![
+                atom("global.set"),
+                atom("$stack_pointer"),
+                list(vec![atom("local.get"), atom("$clara_frame")]),
+            ]),
+            list(vec![
+                atom("i32.store"),
+                slot("0"),
+                list(vec![atom("i32.const"), atom("41")]),
+            ]),
+            list(vec![atom("call"), atom("$bump"), slot("0")]),
+            list(vec![
+                atom("block"),
+                list(vec![atom("local.set"), atom("$clara_target"), slot("4")]),
+                list(vec![
+                    atom("i32.store"),
+                    field("0"),
+                    list(vec![atom("i32.load"), slot("0")]),
+                ]),
+                list(vec![
+                    atom("i32.store8"),
+                    field("4"),
+                    list(vec![atom("i32.const"), atom("1")]),
+                ]),
+                list(vec![
+                    atom("i32.store"),
+                    field("8"),
+                    list(vec![atom("i32.const"), atom("2")]),
+                ]),
+            ]),
+            list(vec![
+                atom("call"),
+                atom("$print_int"),
+                list(vec![
+                    atom("i32.add"),
+                    list(vec![
+                        atom("i32.load"),
+                        list(vec![
+                            atom("i32.add"),
+                            slot("4"),
+                            list(vec![atom("i32.const"), atom("0")])
+                        ]),
+                    ]),
+                    list(vec![
+                        atom("i32.load"),
+                        list(vec![
+                            atom("i32.add"),
+                            slot("4"),
+                            list(vec![atom("i32.const"), atom("8")])
+                        ]),
+                    ]),
+                ]),
+            ]),
+            list(vec![
+                atom("global.set"),
+                atom("$stack_pointer"),
+                list(vec![atom("local.get"), atom("$clara_stack_top")]),
+            ]),
+        ]
+    );
+}
+
+fn atom(text: &str) -> Sexpr {
+    Sexpr::Atom(text.to_string())
+}
+
+fn list(items: Vec<Sexpr>) -> Sexpr {
+    Sexpr::List(items)
+}
+
+/// Runs the language server on `messages`, returning the messages it sends.
+fn run_lsp(messages: &[Value]) -> Vec<Value> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_clara"))
+        .arg("--lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("compiler binary should start");
+    let mut stdin = child.stdin.take().unwrap();
+    for message in messages {
+        let content = message.to_string();
+        write!(stdin, "Content-Length: {}\r\n\r\n{content}", content.len()).unwrap();
+    }
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    stdout
+        .split("Content-Length: ")
+        .skip(1)
+        .map(|message| {
+            let (_, content) = message.split_once("\r\n\r\n").unwrap();
+            serde_json::from_str(content).unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn lsp() {
+    let uri = "file:///tmp/lsp_test.clara";
+    let text = "fn add(a: int, b: int): int {
+  let sum = a + b;
+  return sum;
+}
+fn main() {
+  let x: bool = add(1, 2);
+}
+";
+    let position = |line, character| {
+        json!({
+            "textDocument": { "uri": uri },
+            "position": { "line": line, "character": character },
+        })
+    };
+    let range = |line, start, end| {
+        json!({
+            "start": { "line": line, "character": start },
+            "end": { "line": line, "character": end },
+        })
+    };
+
+    let responses = run_lsp(&[
+        json!({ "jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {} }),
+        json!({ "jsonrpc": "2.0", "method": "initialized", "params": {} }),
+        json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": {
+                "textDocument": { "uri": uri, "languageId": "clara", "version": 1, "text": text },
+            },
+        }),
+        json!({ "jsonrpc": "2.0", "id": 2, "method": "textDocument/hover", "params": position(1, 12) }),
+        json!({ "jsonrpc": "2.0", "id": 3, "method": "textDocument/definition", "params": position(2, 10) }),
+        json!({ "jsonrpc": "2.0", "id": 4, "method": "textDocument/definition", "params": position(5, 17) }),
+        json!({ "jsonrpc": "2.0", "id": 5, "method": "shutdown" }),
+        json!({ "jsonrpc": "2.0", "method": "exit" }),
+    ]);
+
+    assert_eq!(responses.len(), 6);
+    assert_eq!(
+        responses[0]["result"]["capabilities"]["hoverProvider"],
+        true
+    );
+
+    let diagnostics = &responses[1]["params"]["diagnostics"];
+    assert_eq!(responses[1]["method"], "textDocument/publishDiagnostics");
+    assert_eq!(diagnostics.as_array().unwrap().len(), 1);
+    assert_eq!(diagnostics[0]["range"], range(5, 16, 25));
+
+    assert_eq!(
+        responses[2]["result"],
+        json!({ "contents": { "kind": "plaintext", "value": "int" }, "range": range(1, 12, 13) })
+    );
+    assert_eq!(
+        responses[3]["result"],
+        json!({ "uri": uri, "range": range(1, 6, 9) })
+    );
+    assert_eq!(
+        responses[4]["result"],
+        json!({ "uri": uri, "range": range(0, 3, 6) })
+    );
+    assert_eq!(
+        responses[5],
+        json!({ "jsonrpc": "2.0", "id": 5, "result": null })
+    );
+}
+
+#[test]
+fn lsp_definition_shadowing() {
+    let uri = "file:///tmp/lsp_shadowing.clara";
+    let text = "fn main() {
+  let x = 1;
+  let y = x;
+  {
+    let x = x + 1;
+    y + x;
+  }
+  for x in 0..y {
+    x;
+  }
+  x;
+}
+fn other(x: int) { x; }
+";
+    // Uses of `x` by their line and character, along with the line and character of the
+    // declaration they refer to
+    let cases = [
+        ((2, 10), (1, 6)),
+        ((4, 8), (4, 8)),
+        ((4, 12), (1, 6)),
+        ((5, 8), (4, 8)),
+        ((8, 4), (7, 6)),
+        ((10, 2), (1, 6)),
+        ((12, 19), (12, 9)),
+    ];
+
+    let mut messages = vec![json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/didOpen",
+        "params": {
+            "textDocument": { "uri": uri, "languageId": "clara", "version": 1, "text": text },
+        },
+    })];
+    for (id, ((line, character), _)) in cases.iter().enumerate() {
+        messages.push(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "textDocument/definition",
+            "params": {
+                "textDocument": { "uri": uri },
+                "position": { "line": line, "character": character },
+            },
+        }));
+    }
+    let responses = run_lsp(&messages);
+
+    // The diagnostics published for the document come first
+    assert_eq!(responses.len(), 1 + cases.len());
+    for (response, ((line, character), (decl_line, decl_character))) in
+        responses[1..].iter().zip(cases)
+    {
+        assert_eq!(
+            response["result"]["range"]["start"],
+            json!({ "line": decl_line, "character": decl_character }),
+            "definition of `x` at {line}:{character}"
+        );
+    }
+}
+
+#[test]
+fn fmt() {
+    let unformatted = "struct Point { x: int, y: int }
+fn main() { let p = Point { x: 1, y: 2 }; if p.x > 0 { p.y; } }";
+    let formatted = "struct Point {
+    x: int,
+    y: int,
+}
+
+fn main() {
+    let p = Point {
+        x: 1,
+        y: 2,
+    };
+    if p.x > 0 {
+        p.y;
+    }
+}
+";
+
+    let output = run_clara(&["--fmt"], unformatted);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), formatted);
+
+    assert!(!run_clara(&["--fmt", "--check"], unformatted)
+        .status
+        .success());
+    assert!(run_clara(&["--fmt", "--check"], formatted).status.success());
+
+    // Comments between declarations move along with the declaration after them
+    let output = run_clara(
+        &["--fmt"],
+        "// Entry point\nfn main() {}\n\n/* A point,\n   in 2D */\nstruct Point { x: int }\n// The end\n",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "/* A point,
+   in 2D */
+struct Point {
+    x: int,
+}
+
+// Entry point
+fn main() {}
+
+// The end
+"
+    );
+
+    // Comments inside of declarations stay next to the tokens around them
+    let output = run_clara(
+        &["--fmt"],
+        "struct Point { x: int, // the x
+  y: int // the y
+}
+fn main() {
+  // nothing yet
+} // main
+fn double(a: int): int {
+  // Shifting would do too
+  let b = a * 2; // doubled
+  /* block
+     comment */
+  return b;
+}
+",
+    );
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "struct Point {
+    x: int, // the x
+    y: int, // the y
+}
+
+fn main() {
+    // nothing yet
+} // main
+
+fn double(a: int): int {
+    // Shifting would do too
+    let b = a * 2; // doubled
+    /* block
+       comment */
+    return b;
+}
+"
+    );
+
+    let output = run_clara(&["--fmt"], "fn main() {\n  let x = /* inline */ 42;\n}\n");
+    assert!(!output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "error: -:2:11: formatting would remove this comment, only comments on lines of their own \
+         or at the end of a line can be kept\n"
+    );
+}
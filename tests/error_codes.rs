@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+
+use clara::{
+    diagnostic::IntoDiagnostic,
+    lexer::{LexError, TokenKind},
+    loader::CompileError,
+    parser::ParseError,
+    span::{FileId, Span},
+};
+
+/// One error of every kind reported with a code. The matches have no wildcard, so that new
+/// variants can't be added without being listed here.
+fn every_error() -> Vec<CompileError> {
+    let span = Span::new(FileId(0), 0, 1);
+    let lex_errors = [
+        LexError::UnknownToken('$', span),
+        LexError::UnterminatedString(span),
+        LexError::InvalidInt(span),
+        LexError::InvalidEscapeSequence {
+            escape_char: 'q',
+            span,
+        },
+        LexError::UnterminatedCharLiteral(span),
+        LexError::MultiCharLiteral(span),
+        LexError::EmptyCharLiteral(span),
+        LexError::UnterminatedBlockComment(span),
+        LexError::EmptyInterpolation(span),
+        LexError::UnterminatedMultilineString(span),
+    ];
+    let parse_errors = [
+        ParseError::UnexpectedToken(span),
+        ParseError::ExpectedIdentifier(span),
+        ParseError::ExpectedToken(TokenKind::Fn, span),
+        ParseError::UnexpectedEndOfInput(span),
+        ParseError::StructLiteralInRestrictedContext(span),
+        ParseError::UnexpectedTopLevelToken {
+            found: TokenKind::Fn,
+            span,
+            recovered_at: span,
+        },
+        ParseError::MisplacedAttribute(span),
+        ParseError::DuplicateField {
+            field_name: "x".to_string(),
+            first_span: span,
+            second_span: span,
+        },
+        ParseError::PositionalArgAfterNamedArg(span),
+        ParseError::LabelWithoutLoop(span),
+    ];
+
+    let mut errors = vec![];
+    for error in lex_errors {
+        match error {
+            LexError::UnknownToken(..)
+            | LexError::UnterminatedString(_)
+            | LexError::InvalidInt(_)
+            | LexError::InvalidEscapeSequence { .. }
+            | LexError::UnterminatedCharLiteral(_)
+            | LexError::MultiCharLiteral(_)
+            | LexError::EmptyCharLiteral(_)
+            | LexError::UnterminatedBlockComment(_)
+            | LexError::EmptyInterpolation(_)
+            | LexError::UnterminatedMultilineString(_) => errors.push(CompileError::Lex(error)),
+        }
+    }
+    for error in parse_errors {
+        match error {
+            ParseError::UnexpectedToken(_)
+            | ParseError::ExpectedIdentifier(_)
+            | ParseError::ExpectedToken(..)
+            | ParseError::UnexpectedEndOfInput(_)
+            | ParseError::StructLiteralInRestrictedContext(_)
+            | ParseError::UnexpectedTopLevelToken { .. }
+            | ParseError::MisplacedAttribute(_)
+            | ParseError::DuplicateField { .. }
+            | ParseError::PositionalArgAfterNamedArg(_)
+            | ParseError::LabelWithoutLoop(_) => errors.push(CompileError::Parse(error)),
+        }
+    }
+    for error in [
+        CompileError::ImportNotFound("a.clara".to_string(), span),
+        CompileError::CircularImport("a.clara".to_string(), span),
+    ] {
+        match error {
+            CompileError::Lex(_) | CompileError::Parse(_) => unreachable!(),
+            CompileError::ImportNotFound(..) | CompileError::CircularImport(..) => {
+                errors.push(error)
+            }
+        }
+    }
+    errors
+}
+
+#[test]
+fn error_codes_are_unique() {
+    let mut messages_by_code: HashMap<String, String> = HashMap::new();
+    for error in every_error() {
+        let diagnostic = error.report();
+        let code = diagnostic
+            .code
+            .unwrap_or_else(|| panic!("`{}` should have a code", diagnostic.message));
+        assert!(
+            code.len() == 5 && code.starts_with('E'),
+            "`{code}` should be an `E` followed by four digits"
+        );
+        if let Some(other) = messages_by_code.insert(code.clone(), diagnostic.message.clone()) {
+            panic!(
+                "`{other}` and `{}` share the code {code}",
+                diagnostic.message
+            );
+        }
+    }
+    assert_eq!(messages_by_code.len(), 22);
+}
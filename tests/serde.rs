@@ -0,0 +1,50 @@
+#![cfg(feature = "serde")]
+
+use std::process::Command;
+
+use clara::{lexer::lex, parser::parse_program, parser::ParsedProgram, span::FileId};
+use serde_json::Value;
+
+/// Deserializes the AST and serializes it again, which should give back the same JSON.
+fn round_trip(json: &Value) -> Value {
+    let program: ParsedProgram =
+        serde_json::from_value(json.clone()).expect("AST should deserialize");
+    serde_json::to_value(&program).unwrap()
+}
+
+#[test]
+fn examples_round_trip() {
+    let mut paths: Vec<_> = std::fs::read_dir("examples")
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "clara")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    for path in paths {
+        let source = std::fs::read_to_string(&path).unwrap();
+        let (tokens, lex_errors) = lex(FileId(0), &source);
+        let (program, parse_errors) = parse_program(&tokens, &mut 0);
+        assert!(lex_errors.is_empty() && parse_errors.is_empty());
+
+        let json = serde_json::to_value(&program).unwrap();
+        assert_eq!(round_trip(&json), json, "{}", path.display());
+    }
+}
+
+#[test]
+fn dump_ast_round_trips() {
+    let output = Command::new(env!("CARGO_BIN_EXE_clara"))
+        .args(["--dump-ast", "examples/methods.clara"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let json: Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(json["impls"][0]["methods"][0]["name"], "get");
+    assert_eq!(round_trip(&json), json);
+}
@@ -0,0 +1,54 @@
+use clara::span::{FileId, Span};
+
+const SOURCE: &str = "fn main() {\n  let é = \"日本\";\n  é\n}";
+
+fn line_col(start: usize) -> (usize, usize) {
+    Span::new(FileId(0), start, 1).to_line_col(SOURCE)
+}
+
+fn start(line: usize, col: usize) -> Option<usize> {
+    Span::from_line_col(FileId(0), SOURCE, line, col).map(|span| span.start)
+}
+
+#[test]
+fn line_starts() {
+    assert_eq!(line_col(0), (1, 1));
+    assert_eq!(line_col(12), (2, 1));
+    assert_eq!(line_col(SOURCE.find("\n}").unwrap() + 1), (4, 1));
+    // The newline ending a line is its last column
+    assert_eq!(line_col(11), (1, 12));
+}
+
+#[test]
+fn final_line_without_newline() {
+    assert_eq!(line_col(SOURCE.len() - 1), (4, 1));
+    assert_eq!(start(4, 1), Some(SOURCE.len() - 1));
+    assert_eq!(start(4, 2), Some(SOURCE.len()));
+    assert_eq!(start(4, 3), None);
+    assert_eq!(start(5, 1), None);
+}
+
+#[test]
+fn columns_count_characters() {
+    // `é` takes up two bytes, each character of `日本` three
+    let string = SOURCE.find('"').unwrap();
+    assert_eq!(line_col(string), (2, 11));
+    assert_eq!(line_col(string + 1), (2, 12));
+    assert_eq!(line_col(string + 1 + 3), (2, 13));
+    assert_eq!(line_col(string + 1 + 6), (2, 14));
+    assert_eq!(line_col(SOURCE.rfind('é').unwrap()), (3, 3));
+    assert_eq!(line_col(SOURCE.rfind('é').unwrap() + 2), (3, 4));
+}
+
+#[test]
+fn from_line_col_inverts_to_line_col() {
+    for (start, _) in SOURCE.char_indices() {
+        let (line, col) = line_col(start);
+        assert_eq!(self::start(line, col), Some(start), "at {line}:{col}");
+    }
+    // Past the end of a line, including the position appending to it
+    assert_eq!(start(2, 16), Some(SOURCE.find(";\n").unwrap() + 1));
+    assert_eq!(start(2, 17), None);
+    assert_eq!(start(0, 1), None);
+    assert_eq!(start(1, 0), None);
+}
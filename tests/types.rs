@@ -0,0 +1,54 @@
+use clara::typechecker::Type;
+
+#[test]
+fn display_types() {
+    let int = || Box::new(Type::Int);
+    let cases = [
+        (Type::UserDefined("Person".to_string()), "Person"),
+        (Type::Pointer(int(), false), "->int"),
+        (Type::Pointer(int(), true), "->mut int"),
+        (Type::NullablePointer(int(), false), "?->int"),
+        (Type::NullablePointer(int(), true), "?->mut int"),
+        (Type::Null, "null"),
+        (Type::GenericInt, "{integer}"),
+        (Type::String, "string"),
+        (Type::Str, "str"),
+        (Type::Int, "int"),
+        (Type::Bool, "bool"),
+        (Type::Char, "char"),
+        (Type::Unit, "unit"),
+        (Type::Never, "!"),
+        (Type::CChar, "c_char"),
+        (Type::CInt, "c_int"),
+        (Type::GenericEmptyArray, "[_; 0]"),
+        (Type::Array(int(), 5), "[int; 5]"),
+        (Type::Slice(int()), "[int]"),
+        (Type::Tuple(vec![]), "()"),
+        (Type::Tuple(vec![Type::Int]), "(int,)"),
+        (Type::Tuple(vec![Type::Int, Type::Bool]), "(int, bool)"),
+        (
+            Type::FnPtr {
+                params: vec![Type::Int, Type::Char],
+                return_type: Box::new(Type::Bool),
+            },
+            "fn(int, char): bool",
+        ),
+        (
+            Type::FnPtr {
+                params: vec![],
+                return_type: Box::new(Type::Unit),
+            },
+            "fn()",
+        ),
+        (Type::Range(int()), "range<int>"),
+        (Type::Incomplete, "incomplete type"),
+        (
+            Type::Pointer(Box::new(Type::Slice(Box::new(Type::CChar))), false),
+            "->[c_char]",
+        ),
+    ];
+
+    for (ttype, expected) in cases {
+        assert_eq!(ttype.to_string(), expected);
+    }
+}
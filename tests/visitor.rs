@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use clara::{
+    lexer::lex,
+    parser::{parse_program, ParsedExpression, ParsedProgram, ParsedStatement},
+    span::FileId,
+    visitor::{walk_expression, walk_statement, NodeCounter, Visitor},
+};
+
+/// Uses every kind of statement and expression, with the number of nodes on each line.
+const PROGRAM: &str = "
+struct P { x: int }                                       // 2
+fn f(p: ->P): int {                                       // 6
+  let a: int = -b;                                        // 4
+  const C: int = sizeof(P) + offsetof(P, \"x\");          // 8
+  a = cast<int>(a) * (a - 1);                             // 12
+  while a < 0 && !c || d { }                              // 11
+  while let E::A(b) = E::A(a) { break; }                  // 6
+  'l: loop { continue 'l; }                               // 3
+  if p.x == a.0 { a[1]; } else { q.m(a); }                // 16
+  match a { 1 => [0; 2], _ => { 3 } }                     // 14
+  switch a { case 1: defer f(->a); default: unsafe { *p; } } // 15
+  for i in 0..a { }                                       // 6
+  return if a > 0 { ~a } else { a };                      // 12
+}
+";
+
+fn parse(source: &str) -> ParsedProgram {
+    let (tokens, lex_errors) = lex(FileId(0), source);
+    assert!(lex_errors.is_empty(), "{lex_errors:?}");
+    let (program, parse_errors) = parse_program(&tokens, &mut 0);
+    assert!(parse_errors.is_empty(), "{parse_errors:?}");
+    program
+}
+
+/// The names of the kinds of statements and expressions visited.
+#[derive(Default)]
+struct Kinds(BTreeSet<String>);
+
+impl Kinds {
+    fn insert(&mut self, debug: String) {
+        let name = debug.split(|c: char| !c.is_alphanumeric()).next().unwrap();
+        self.0.insert(name.to_string());
+    }
+}
+
+impl Visitor for Kinds {
+    fn visit_statement(&mut self, statement: &ParsedStatement) {
+        self.insert(format!("{statement:?}"));
+        walk_statement(self, statement);
+    }
+
+    fn visit_expression(&mut self, expression: &ParsedExpression) {
+        self.insert(format!("{expression:?}"));
+        walk_expression(self, expression);
+    }
+}
+
+#[test]
+fn node_counter_counts_every_node() {
+    let program = parse(PROGRAM);
+
+    let mut kinds = Kinds::default();
+    kinds.visit_program(&program);
+    let statements = [
+        "Expression",
+        "LetAssign",
+        "Const",
+        "WhileLoop",
+        "WhileLet",
+        "Loop",
+        "IfElse",
+        "Match",
+        "Switch",
+        "ForInLoop",
+        "Return",
+        "Break",
+        "Continue",
+        "Defer",
+        "Unsafe",
+    ];
+    let expressions = [
+        "Literal",
+        "FunctionCall",
+        "Variable",
+        "CompareOp",
+        "MathOp",
+        "LogicalAnd",
+        "LogicalOr",
+        "UnaryMinus",
+        "Not",
+        "BitNot",
+        "Parenthesized",
+        "FieldAccess",
+        "MethodCall",
+        "TupleIndex",
+        "ArrayIndex",
+        "Assignment",
+        "Range",
+        "PointerTo",
+        "Deref",
+        "EnumVariant",
+        "SizeOf",
+        "OffsetOf",
+        "Cast",
+        "ArrayRepeat",
+        "Block",
+        "IfExpr",
+    ];
+    let expected: BTreeSet<String> = statements
+        .iter()
+        .chain(&expressions)
+        .map(|kind| kind.to_string())
+        .collect();
+    assert_eq!(kinds.0, expected);
+
+    let mut counter = NodeCounter::default();
+    counter.visit_program(&program);
+    // The program itself, then the nodes of every line
+    assert_eq!(counter.count, 1 + 115);
+}
+
+#[test]
+fn node_counter_counts_types_and_literals() {
+    let program = parse("fn main() { let xs: [->int; 2] = [1, 2]; }");
+
+    let mut counter = NodeCounter::default();
+    counter.visit_program(&program);
+    // Program, function, unit return type, body, statement, the array, pointer and int types, the
+    // array literal as an expression and literal, and its two elements as expressions and literals
+    assert_eq!(counter.count, 14);
+}